@@ -0,0 +1,1209 @@
+use crate::config::{AlertConfig, EmailConfig, TagAlertRule};
+use crate::db::duckdb::DuckDB;
+use crate::email::EmailReporter;
+use crate::models::{percentage, AlertState, CategoryCount, CenterFetchReport, FetchAudit, MonitorSummary, NewFailure, ResponseTimeRegression, RunStorage};
+use crate::notify::{build_notifier, Notification, Notifier};
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use tracing::{error, info, warn};
+
+/// 告警中列出的错误类别数
+const TOP_CATEGORIES: usize = 5;
+
+/// 数据中心成功率告警
+#[derive(Debug, Clone, Serialize)]
+pub struct CenterAlert {
+    pub center_name: String,
+    /// below_threshold 或 rate_drop
+    pub reason: String,
+    pub previous_rate: Option<f64>,
+    pub current_rate: f64,
+    pub total: usize,
+    pub failed: usize,
+    pub top_error_categories: Vec<CategoryCount>,
+    pub sample_failures: Vec<String>,
+    pub run_id: String,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl CenterAlert {
+    pub fn to_notification(&self) -> Notification {
+        let reason = match self.reason.as_str() {
+            "below_threshold" => "成功率低于阈值",
+            "rate_drop" => "成功率大幅下降",
+            other => other,
+        };
+        let mut body = format!("**数据中心**: {}\n\n**原因**: {}\n\n**成功率**: {:.1}%", self.center_name, reason, self.current_rate);
+        if let Some(previous) = self.previous_rate {
+            let _ = write!(body, "（上次 {:.1}%）", previous);
+        }
+        let _ = write!(body, "\n\n**失败**: {}/{}\n", self.failed, self.total);
+        for category in &self.top_error_categories {
+            let _ = write!(body, "\n- {}: {}", category.category, category.count);
+        }
+        if !self.top_error_categories.is_empty() {
+            body.push('\n');
+        }
+        let _ = write!(body, "\n运行 {}，{}", self.run_id, self.checked_at.format("%Y-%m-%d %H:%M:%S"));
+        Notification {
+            title: format!("数据集监测告警: {}", self.center_name),
+            body,
+            items: self.sample_failures.clone(),
+            payload: serde_json::to_value(self).unwrap_or_default(),
+        }
+    }
+}
+
+/// 比较两次运行的汇总，返回需要告警的数据中心
+///
+/// 成功率低于 `min_success_rate`，或较上一次运行下降超过 `max_rate_drop` 个百分点时告警；
+/// 本次没有检查任何URL的数据中心不参与判断。
+pub fn evaluate_center_alerts(previous: Option<&MonitorSummary>, current: &MonitorSummary, config: &AlertConfig) -> Vec<CenterAlert> {
+    let mut alerts = Vec::new();
+    for center in current.centers.iter().filter(|c| c.total > 0) {
+        let previous_rate = previous
+            .and_then(|p| p.center(&center.center_name))
+            .filter(|c| c.total > 0)
+            .map(|c| c.success_rate);
+
+        let reason = if config.min_success_rate.is_some_and(|min| center.success_rate < min) {
+            "below_threshold"
+        } else if let (Some(max_drop), Some(prev)) = (config.max_rate_drop, previous_rate)
+            && prev - center.success_rate > max_drop
+        {
+            "rate_drop"
+        } else {
+            continue;
+        };
+
+        alerts.push(CenterAlert {
+            center_name: center.center_name.clone(),
+            reason: reason.to_string(),
+            previous_rate,
+            current_rate: center.success_rate,
+            total: center.total,
+            failed: center.total - center.success,
+            top_error_categories: center.error_categories.iter().take(TOP_CATEGORIES).cloned().collect(),
+            sample_failures: center.sample_failures.clone(),
+            run_id: current.run_id.clone(),
+            checked_at: current.finished_at,
+        });
+    }
+    alerts
+}
+
+/// 本次运行由成功变为失败的URL告警，按数据中心分组；数量未达到 `new_failure_min_count` 时不告警
+pub fn new_failure_notification(summary: &MonitorSummary, config: &AlertConfig) -> Option<Notification> {
+    scoped_new_failure_notification(summary, config, config.new_failure_min_count?, None)
+}
+
+/// 带 `rule.tag` 标签的数据集中由成功变为失败的URL告警，数量未达到规则的 new_failure_min_count 时不告警
+pub fn tag_new_failure_notification(summary: &MonitorSummary, config: &AlertConfig, rule: &TagAlertRule) -> Option<Notification> {
+    scoped_new_failure_notification(summary, config, rule.new_failure_min_count?, Some(&rule.tag))
+}
+
+fn scoped_new_failure_notification(summary: &MonitorSummary, config: &AlertConfig, min_count: usize, tag: Option<&str>) -> Option<Notification> {
+    let failures: Vec<&NewFailure> = summary.new_failures.iter()
+        .filter(|f| !(config.new_failure_exclude_local && f.is_likely_local_issue))
+        .filter(|f| tag.is_none_or(|tag| f.tags.iter().any(|t| t == tag)))
+        .collect();
+    if failures.is_empty() || failures.len() < min_count {
+        return None;
+    }
+
+    let mut by_center: BTreeMap<&str, Vec<&NewFailure>> = BTreeMap::new();
+    for failure in &failures {
+        by_center.entry(failure.center_name.as_str()).or_default().push(failure);
+    }
+
+    let mut body = format!("**新失败URL**: {}\n", failures.len());
+    let mut items = Vec::new();
+    for (center, center_failures) in &by_center {
+        let _ = write!(body, "\n- {}: {}", center, center_failures.len());
+        for failure in center_failures.iter().take(config.new_failure_max_per_center) {
+            let mut item = format!(
+                "[{}] {} {} ({}: {})",
+                center,
+                failure.name.as_deref().unwrap_or("-"),
+                failure.url,
+                failure.error_category.as_deref().unwrap_or("-"),
+                failure.error_msg.as_deref().unwrap_or("-"),
+            );
+            // 重定向后才失败时注明失败的URL，避免误以为是原URL的问题
+            if let (Some(index @ 1..), Some(hop_url)) = (failure.failed_hop_index, &failure.failed_hop_url) {
+                let _ = write!(item, " 第 {} 次重定向后失败: {}", index, hop_url);
+            }
+            if let Some(last_success_at) = failure.last_success_at {
+                let _ = write!(item, " 上次正常 {}", last_success_at.format("%Y-%m-%d"));
+            }
+            items.push(item);
+        }
+        if center_failures.len() > config.new_failure_max_per_center {
+            items.push(format!("[{}] ... 另有 {} 条", center, center_failures.len() - config.new_failure_max_per_center));
+        }
+    }
+    let _ = write!(body, "\n\n运行 {}，{}", summary.run_id, summary.finished_at.format("%Y-%m-%d %H:%M:%S"));
+
+    let title = match tag {
+        Some(tag) => format!("数据集监测: 标签 {} 的 {} 个URL新近失败", tag, failures.len()),
+        None => format!("数据集监测: {} 个URL新近失败", failures.len()),
+    };
+    Some(Notification {
+        title,
+        body,
+        items,
+        payload: serde_json::json!({
+            "type": "new_failures",
+            "tag": tag,
+            "run_id": summary.run_id,
+            "checked_at": summary.finished_at,
+            "count": failures.len(),
+            "failures": failures,
+        }),
+    })
+}
+
+/// 带 `rule.tag` 标签的数据集成功率低于规则的 min_success_rate 时的告警，本次没有检查该标签的数据集时不判断；
+/// 返回成功率和告警（未触发时为 None）
+pub fn evaluate_tag_alert(summary: &MonitorSummary, rule: &TagAlertRule) -> Option<(f64, Option<Notification>)> {
+    let min_success_rate = rule.min_success_rate?;
+    let stats = summary.tags.iter().find(|t| t.tag == rule.tag && t.total > 0)?;
+    let breach = (stats.success_rate < min_success_rate).then(|| Notification {
+        title: format!("数据集监测告警: 标签 {}", rule.tag),
+        body: format!(
+            "**标签**: {}\n\n**成功率**: {:.1}%（阈值 {:.1}%）\n\n**失败**: {}/{}\n\n运行 {}，{}",
+            rule.tag, stats.success_rate, min_success_rate, stats.total - stats.success, stats.total,
+            summary.run_id, summary.finished_at.format("%Y-%m-%d %H:%M:%S")
+        ),
+        items: Vec::new(),
+        payload: serde_json::json!({
+            "type": "tag_success_rate",
+            "tag": rule.tag,
+            "success_rate": stats.success_rate,
+            "min_success_rate": min_success_rate,
+            "total": stats.total,
+            "success": stats.success,
+            "run_id": summary.run_id,
+            "checked_at": summary.finished_at,
+        }),
+    });
+    Some((stats.success_rate, breach))
+}
+
+/// 本地网络问题占比（百分比），URL数量低于 `local_issue_min_sample` 时返回 None，不参与判断
+pub fn local_issue_rate(summary: &MonitorSummary, config: &AlertConfig) -> Option<f64> {
+    if summary.total == 0 || summary.total < config.local_issue_min_sample {
+        return None;
+    }
+    Some(percentage(summary.local_issues as i64, summary.total as i64))
+}
+
+/// 本地网络问题占比超过 `local_issue_percent` 时的告警，未配置或样本不足时不判断；
+/// 返回占比和告警（未触发时为 None）
+pub fn evaluate_local_issues(summary: &MonitorSummary, config: &AlertConfig) -> Option<(f64, Option<Notification>)> {
+    let threshold = config.local_issue_percent?;
+    let rate = local_issue_rate(summary, config)?;
+    Some((rate, (rate > threshold).then(|| local_issue_notification(summary, rate))))
+}
+
+/// 本地网络问题告警，列出各数据中心的本地问题数便于区分本机出口故障和远端大面积不可达
+pub fn local_issue_notification(summary: &MonitorSummary, rate: f64) -> Notification {
+    let mut body = format!("**本地网络问题占比**: {:.1}% ({}/{})\n\n**远程问题**: {}\n",
+                           rate, summary.local_issues, summary.total, summary.remote_issues);
+    for center in summary.centers.iter().filter(|c| c.local_issues > 0) {
+        let _ = write!(body, "\n- {}: {}/{}", center.center_name, center.local_issues, center.total);
+    }
+    let _ = write!(body, "\n\n运行 {}，{}", summary.run_id, summary.finished_at.format("%Y-%m-%d %H:%M:%S"));
+    Notification {
+        title: "数据集监测: 本地网络问题过多".to_string(),
+        body,
+        items: Vec::new(),
+        payload: serde_json::json!({
+            "type": "local_network_issues",
+            "run_id": summary.run_id,
+            "checked_at": summary.finished_at,
+            "local_issue_rate": rate,
+            "local_issues": summary.local_issues,
+            "remote_issues": summary.remote_issues,
+            "total": summary.total,
+        }),
+    }
+}
+
+pub fn regression_notification(summary: &MonitorSummary, regression: &ResponseTimeRegression) -> Notification {
+    Notification {
+        title: format!("数据中心响应变慢: {}", regression.center_name),
+        body: format!("**数据中心**: {}\n\n**响应时间中位数**: {}ms，基线 {}ms（之前 {} 次运行），为基线的 {:.1} 倍\n\n运行 {}，{}",
+                      regression.center_name, regression.median_ms, regression.baseline_ms, regression.baseline_runs,
+                      regression.ratio, summary.run_id, summary.finished_at.format("%Y-%m-%d %H:%M:%S")),
+        items: Vec::new(),
+        payload: serde_json::json!({
+            "type": "response_time_regression",
+            "run_id": summary.run_id,
+            "center_name": regression.center_name,
+            "median_ms": regression.median_ms,
+            "baseline_ms": regression.baseline_ms,
+            "ratio": regression.ratio,
+            "checked_at": summary.finished_at,
+        }),
+    }
+}
+
+/// 在运行相关的告警中附上运行的出口 IP 和主机名，便于数据中心核对拦截记录
+pub fn with_environment(mut notification: Notification, summary: &MonitorSummary) -> Notification {
+    let Some(environment) = &summary.environment else {
+        return notification;
+    };
+    let _ = write!(notification.body, "\n\n出口 IP: {}，主机: {}，版本: {}",
+                   environment.egress_ip.as_deref().unwrap_or("-"),
+                   environment.hostname.as_deref().unwrap_or("-"),
+                   environment.version);
+    if let Some(payload) = notification.payload.as_object_mut() {
+        payload.insert("environment".to_string(), serde_json::json!(environment));
+    }
+    notification
+}
+
+/// 检查结果写库失败、写入溢出文件的告警
+pub fn spill_notification(summary: &MonitorSummary) -> Option<Notification> {
+    let path = summary.spill_file.as_deref().filter(|_| summary.spilled > 0)?;
+    Some(Notification {
+        title: format!("数据集监测: {} 条检查结果写库失败", summary.spilled),
+        body: format!("**溢出文件**: {}
+
+写库恢复后执行 `data_monitor replay-spill {}` 重新写入
+
+运行 {}，{}",
+                      path, path, summary.run_id, summary.finished_at.format("%Y-%m-%d %H:%M:%S")),
+        items: Vec::new(),
+        payload: serde_json::json!({
+            "type": "spilled_results",
+            "run_id": summary.run_id,
+            "checked_at": summary.finished_at,
+            "spilled": summary.spilled,
+            "spill_file": path,
+        }),
+    })
+}
+
+/// 数据库文件超过 duckdb.storage_soft_limit_mb 的告警
+pub fn storage_notification(summary: &MonitorSummary, storage: &RunStorage) -> Notification {
+    let mb = |bytes: i64| bytes as f64 / (1024.0 * 1024.0);
+    let limit = storage.soft_limit_bytes.unwrap_or_default();
+    let mut body = format!("**数据库大小**: {:.0} MB（含 WAL），软限制 {:.0} MB\n\n**本次运行增长**: {:.0} MB",
+                           mb(storage.end.total_bytes() as i64), mb(limit as i64), mb(storage.growth_bytes()));
+    if let Some(pruned) = storage.pruned_records {
+        let _ = write!(body, "\n\n已删除 {} 条过期检查记录，数据库文件不会因此变小，之后的增长会复用空出的空间", pruned);
+    }
+    let _ = write!(body, "\n\n运行 {}，{}", summary.run_id, summary.finished_at.format("%Y-%m-%d %H:%M:%S"));
+    Notification {
+        title: "数据集监测: 数据库超过大小限制".to_string(),
+        body,
+        items: Vec::new(),
+        payload: serde_json::json!({
+            "type": "database_size",
+            "run_id": summary.run_id,
+            "checked_at": summary.finished_at,
+            "start": storage.start,
+            "end": storage.end,
+            "growth_bytes": storage.growth_bytes(),
+            "soft_limit_bytes": limit,
+            "pruned_records": storage.pruned_records,
+        }),
+    }
+}
+
+/// 告警中错误信息（含响应内容）的最大字符数
+const MAX_ERROR_CHARS: usize = 1000;
+
+fn truncate_chars(s: &str, max_chars: usize) -> String {
+    match s.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &s[..end]),
+        None => s.to_string(),
+    }
+}
+
+/// 数据获取失败告警，错误信息中包含HTTP状态码和响应内容
+pub fn fetch_failure_notification(center_name: &str, error: &str, now: DateTime<Utc>) -> Notification {
+    let error = truncate_chars(error, MAX_ERROR_CHARS);
+    Notification {
+        title: format!("数据获取失败: {}", center_name),
+        body: format!("**数据中心**: {}\n\n**错误**:\n\n```\n{}\n```\n\n{}",
+                      center_name, error, now.format("%Y-%m-%d %H:%M:%S")),
+        items: Vec::new(),
+        payload: serde_json::json!({
+            "type": "fetch_failure",
+            "center_name": center_name,
+            "error": error,
+            "failed_at": now,
+        }),
+    }
+}
+
+/// 定时任务连续失败次数达到 monitor.failure_alert_threshold 时告警，`error` 为最近一次的错误
+pub fn job_failure_notification(job: &str, failures: u32, error: &str, now: DateTime<Utc>) -> Notification {
+    let error = truncate_chars(error, MAX_ERROR_CHARS);
+    Notification {
+        title: format!("任务连续失败: {}", job),
+        body: format!("**任务**: {}\n\n**连续失败**: {} 次\n\n**最近一次错误**:\n\n```\n{}\n```\n\n{}",
+                      job, failures, error, now.format("%Y-%m-%d %H:%M:%S")),
+        items: Vec::new(),
+        payload: serde_json::json!({
+            "type": "job_failures",
+            "job": job,
+            "consecutive_failures": failures,
+            "error": error,
+            "failed_at": now,
+        }),
+    }
+}
+
+/// 数据导出失败告警，`target` 为本地文件或对象存储的对象地址
+pub fn export_failure_notification(target: &str, error: &str, now: DateTime<Utc>) -> Notification {
+    let error = truncate_chars(error, MAX_ERROR_CHARS);
+    Notification {
+        title: format!("数据导出失败: {}", target),
+        body: format!("**目标**: {}\n\n**错误**:\n\n```\n{}\n```\n\n{}",
+                      target, error, now.format("%Y-%m-%d %H:%M:%S")),
+        items: Vec::new(),
+        payload: serde_json::json!({
+            "type": "export_failure",
+            "target": target,
+            "error": error,
+            "failed_at": now,
+        }),
+    }
+}
+
+/// 数据集列表异常：之前有数据而本次为空，或数量下降超过 `drop_percent`
+pub fn evaluate_fetch_anomaly(previous: Option<&FetchAudit>, current: &CenterFetchReport, drop_percent: Option<f64>) -> Option<String> {
+    let previous_listed = previous.and_then(|p| p.outcome.as_ref()).map(|o| o.listed)?;
+    if previous_listed == 0 {
+        return None;
+    }
+    if current.listed == 0 {
+        return Some(format!("数据集列表为空，上次成功获取时为 {} 条", previous_listed));
+    }
+    let drop = percentage(previous_listed as i64 - current.listed as i64, previous_listed as i64);
+    match drop_percent {
+        Some(max_drop) if drop > max_drop => Some(format!(
+            "数据集列表数量由 {} 条降至 {} 条，下降 {:.1}%", previous_listed, current.listed, drop
+        )),
+        _ => None,
+    }
+}
+
+/// 判断待处理ID数较上一次成功获取是否增加超过 `max_growth`，返回告警原因；
+/// 任一次没有积压统计（如旧的审计记录）时不判断
+pub fn evaluate_backlog_growth(previous: Option<&FetchAudit>, current: &CenterFetchReport, max_growth: Option<u64>) -> Option<String> {
+    let max_growth = max_growth?;
+    let previous_pending = previous.and_then(|p| p.outcome.as_ref()).and_then(|o| o.backlog.as_ref())?.pending;
+    let current_pending = current.backlog.as_ref()?.pending;
+    let growth = current_pending.saturating_sub(previous_pending);
+    (growth > max_growth).then(|| format!(
+        "待处理ID由 {} 个增至 {} 个，增加 {} 个（本次新发现 {} 个，处理 {} 个）",
+        previous_pending, current_pending, growth, current.discovered, current.processed
+    ))
+}
+
+/// 由最近的运行汇总拼出每个数据中心上一次的结果（按中心调度时每次运行只包含一个中心），
+/// 只检查部分数据集的标签运行不参与比较
+pub fn previous_summary(recent_runs: &[MonitorSummary], current_run_id: &str) -> Option<MonitorSummary> {
+    let mut runs = recent_runs.iter().filter(|r| r.run_id != current_run_id && r.tag.is_none());
+    let mut previous = runs.next()?.clone();
+    for run in runs {
+        for center in &run.centers {
+            if previous.center(&center.center_name).is_none() {
+                previous.centers.push(center.clone());
+            }
+        }
+    }
+    Some(previous)
+}
+
+/// 数据中心成功率规则
+pub const RULE_CENTER_SUCCESS_RATE: &str = "center_success_rate";
+/// 本地网络问题占比规则
+pub const RULE_LOCAL_NETWORK: &str = "local_network_issues";
+/// 标签成功率规则
+pub const RULE_TAG_SUCCESS_RATE: &str = "tag_success_rate";
+/// 数据中心响应时间变慢规则
+pub const RULE_RESPONSE_TIME_REGRESSION: &str = "response_time_regression";
+/// 数据库大小规则
+pub const RULE_DATABASE_SIZE: &str = "database_size";
+
+/// 一次规则评估后对告警的处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertAction {
+    /// 首次触发，或冷却期已过仍未恢复
+    Fire,
+    /// 冷却期内重复触发，不发送
+    Suppress,
+    /// 之前触发的告警已恢复
+    Resolve,
+    /// 未触发且没有未恢复的告警
+    Nothing,
+}
+
+pub fn next_action(state: Option<&AlertState>, breached: bool, now: DateTime<Utc>, cooldown: Duration) -> AlertAction {
+    let open = state.filter(|s| s.open);
+    match (breached, open) {
+        (true, Some(state)) if state.last_fired_at.is_some_and(|last| now - last < cooldown) => AlertAction::Suppress,
+        (true, _) => AlertAction::Fire,
+        (false, Some(_)) => AlertAction::Resolve,
+        (false, None) => AlertAction::Nothing,
+    }
+}
+
+/// 告警恢复通知
+pub fn resolved_notification(rule: &str, subject: &str, value: f64, now: DateTime<Utc>) -> Notification {
+    Notification {
+        title: format!("告警已恢复: {}", subject),
+        body: format!("**规则**: {}\n\n**对象**: {}\n\n**当前值**: {:.1}\n\n恢复时间 {}",
+                      rule, subject, value, now.format("%Y-%m-%d %H:%M:%S")),
+        items: Vec::new(),
+        payload: serde_json::json!({
+            "status": "resolved",
+            "rule": rule,
+            "subject": subject,
+            "value": value,
+            "resolved_at": now,
+        }),
+    }
+}
+
+/// 告警发送，状态保存在 DuckDB 的 alert_state 表中，重启后冷却期和未恢复的告警仍然有效
+pub struct Alerter {
+    config: AlertConfig,
+    client: reqwest::Client,
+    notifiers: Vec<Box<dyn Notifier>>,
+}
+
+impl Alerter {
+    pub fn new(config: &AlertConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(15))
+            .build()
+            .expect("failed to build http client");
+        Self {
+            config: config.clone(),
+            client,
+            notifiers: config.targets.iter().map(build_notifier).collect(),
+        }
+    }
+
+    /// 监测运行结束后评估并发送告警
+    pub async fn on_monitor_run(&self, summary: &MonitorSummary, duckdb: &DuckDB) -> Result<()> {
+        if self.notifiers.is_empty() {
+            return Ok(());
+        }
+        // 标签运行只检查了部分数据集，其成功率不代表数据中心整体，不评估数据中心规则
+        let alerts = if summary.tag.is_none() {
+            let recent_runs = duckdb.get_recent_runs(50).await?;
+            let previous = previous_summary(&recent_runs, &summary.run_id);
+            evaluate_center_alerts(previous.as_ref(), summary, &self.config)
+        } else {
+            Vec::new()
+        };
+        for center in summary.centers.iter().filter(|c| summary.tag.is_none() && c.total > 0) {
+            let breach = alerts.iter().find(|a| a.center_name == center.center_name);
+            if let Some(alert) = breach {
+                warn!("数据中心 {} 成功率告警: {:.1}% (上次 {:?})",
+                      alert.center_name, alert.current_rate, alert.previous_rate);
+            }
+            self.process(
+                duckdb,
+                RULE_CENTER_SUCCESS_RATE,
+                &center.center_name,
+                center.success_rate,
+                breach.map(|alert| with_environment(alert.to_notification(), summary)),
+                summary.finished_at,
+            ).await?;
+        }
+
+        if let Some((rate, breach)) = evaluate_local_issues(summary, &self.config) {
+            let breach = breach.map(|notification| with_environment(notification, summary));
+            self.process(duckdb, RULE_LOCAL_NETWORK, "local", rate, breach, summary.finished_at).await?;
+        }
+
+        for rule in &self.config.tag_rules {
+            if let Some((rate, breach)) = evaluate_tag_alert(summary, rule) {
+                if breach.is_some() {
+                    warn!("标签 {} 的数据集成功率告警: {:.1}%", rule.tag, rate);
+                }
+                let breach = breach.map(|notification| with_environment(notification, summary));
+                self.process(duckdb, RULE_TAG_SUCCESS_RATE, &rule.tag, rate, breach, summary.finished_at).await?;
+            }
+        }
+
+        // 记录的值为响应时间中位数（毫秒）
+        if self.config.response_time_regression {
+            for center in &summary.centers {
+                let Some(median_ms) = center.median_response_ms else {
+                    continue;
+                };
+                let breach = summary.response_time_regressions.iter()
+                    .find(|r| r.center_name == center.center_name)
+                    .map(|r| with_environment(regression_notification(summary, r), summary));
+                self.process(duckdb, RULE_RESPONSE_TIME_REGRESSION, &center.center_name, median_ms as f64, breach, summary.finished_at).await?;
+            }
+        }
+
+        // 记录的值为运行结束时的数据库大小（MB）
+        if let Some(storage) = summary.storage.as_ref().filter(|s| s.soft_limit_bytes.is_some()) {
+            let breach = storage.over_soft_limit().then(|| storage_notification(summary, storage));
+            let value = storage.end.total_bytes() as f64 / (1024.0 * 1024.0);
+            self.process(duckdb, RULE_DATABASE_SIZE, "duckdb", value, breach, summary.finished_at).await?;
+        }
+
+        // 新失败的URL每次运行单独告警，不做冷却去重
+        if let Some(notification) = new_failure_notification(summary, &self.config) {
+            warn!("{}", notification.title);
+            self.send(&with_environment(notification, summary)).await;
+        }
+        for rule in &self.config.tag_rules {
+            if let Some(notification) = tag_new_failure_notification(summary, &self.config, rule) {
+                warn!("{}", notification.title);
+                self.send(&with_environment(notification, summary)).await;
+            }
+        }
+        if let Some(notification) = spill_notification(summary) {
+            self.send(&with_environment(notification, summary)).await;
+        }
+        Ok(())
+    }
+
+    /// 数据获取失败时告警
+    pub async fn on_fetch_failure(&self, center_name: &str, error: &str) {
+        if self.notifiers.is_empty() {
+            return;
+        }
+        self.send(&fetch_failure_notification(center_name, error, Utc::now())).await;
+    }
+
+    /// 定时任务连续失败达到阈值
+    pub async fn on_job_failures(&self, job: &str, failures: u32, error: &str) {
+        if self.notifiers.is_empty() {
+            return;
+        }
+        self.send(&job_failure_notification(job, failures, error, Utc::now())).await;
+    }
+
+    /// Parquet 导出或上传在重试后仍然失败
+    pub async fn on_export_failure(&self, target: &str, error: &str) {
+        if self.notifiers.is_empty() {
+            return;
+        }
+        self.send(&export_failure_notification(target, error, Utc::now())).await;
+    }
+
+    /// 数据获取成功后检查数据集列表是否异常，`previous` 为上一次成功获取的审计记录
+    pub async fn on_fetch_success(&self, center_name: &str, previous: Option<&FetchAudit>, outcome: &CenterFetchReport) {
+        if self.notifiers.is_empty() {
+            return;
+        }
+        if let Some(reason) = evaluate_fetch_anomaly(previous, outcome, self.config.fetch_count_drop_percent) {
+            warn!("数据中心 {} {}", center_name, reason);
+            let now = Utc::now();
+            self.send(&Notification {
+                title: format!("数据集列表异常: {}", center_name),
+                body: format!("**数据中心**: {}\n\n{}\n\n{}", center_name, reason, now.format("%Y-%m-%d %H:%M:%S")),
+                items: Vec::new(),
+                payload: serde_json::json!({
+                    "type": "fetch_anomaly",
+                    "center_name": center_name,
+                    "reason": reason,
+                    "previous_listed": previous.and_then(|p| p.outcome.as_ref()).map(|o| o.listed),
+                    "listed": outcome.listed,
+                    "checked_at": now,
+                }),
+            }).await;
+        }
+    }
+
+    /// 数据获取成功后检查ID积压是否增长过快，`previous` 为上一次成功的获取（包括增量获取）
+    pub async fn on_fetch_backlog(&self, center_name: &str, previous: Option<&FetchAudit>, outcome: &CenterFetchReport) {
+        if self.notifiers.is_empty() {
+            return;
+        }
+        if let Some(reason) = evaluate_backlog_growth(previous, outcome, self.config.fetch_backlog_growth) {
+            warn!("数据中心 {} {}", center_name, reason);
+            let now = Utc::now();
+            self.send(&Notification {
+                title: format!("数据集ID积压增长: {}", center_name),
+                body: format!("**数据中心**: {}\n\n{}\n\n{}", center_name, reason, now.format("%Y-%m-%d %H:%M:%S")),
+                items: Vec::new(),
+                payload: serde_json::json!({
+                    "type": "fetch_backlog_growth",
+                    "center_name": center_name,
+                    "reason": reason,
+                    "previous_backlog": previous.and_then(|p| p.outcome.as_ref()).and_then(|o| o.backlog.as_ref()),
+                    "backlog": outcome.backlog,
+                    "checked_at": now,
+                }),
+            }).await;
+        }
+    }
+
+    /// 按 (rule, subject) 处理一次规则评估结果，`breach` 为 None 表示本次未触发
+    pub async fn process(
+        &self,
+        duckdb: &DuckDB,
+        rule: &str,
+        subject: &str,
+        value: f64,
+        breach: Option<Notification>,
+        now: DateTime<Utc>,
+    ) -> Result<AlertAction> {
+        let state = duckdb.get_alert_state(rule, subject).await?;
+        let cooldown = Duration::minutes(self.config.cooldown_minutes as i64);
+        let action = next_action(state.as_ref(), breach.is_some(), now, cooldown);
+        let last_fired_at = state.as_ref().and_then(|s| s.last_fired_at);
+        let next_state = |open: bool, last_fired_at: Option<DateTime<Utc>>| AlertState {
+            rule: rule.to_string(),
+            subject: subject.to_string(),
+            open,
+            last_fired_at,
+            last_value: Some(value),
+            updated_at: now,
+        };
+        match action {
+            AlertAction::Fire => {
+                if let Some(notification) = &breach {
+                    self.send(notification).await;
+                }
+                duckdb.upsert_alert_state(&next_state(true, Some(now))).await?;
+            }
+            AlertAction::Suppress => {
+                info!("{} {} 的告警处于冷却期，跳过", rule, subject);
+                duckdb.upsert_alert_state(&next_state(true, last_fired_at)).await?;
+            }
+            AlertAction::Resolve => {
+                info!("{} {} 的告警已恢复", rule, subject);
+                self.send(&resolved_notification(rule, subject, value, now)).await;
+                duckdb.upsert_alert_state(&next_state(false, last_fired_at)).await?;
+            }
+            AlertAction::Nothing => {}
+        }
+        Ok(action)
+    }
+
+    /// 发送到所有告警接收方，失败只记录日志
+    pub async fn send(&self, notification: &Notification) {
+        for notifier in &self.notifiers {
+            if let Err(e) = self.send_to(notifier.as_ref(), notification).await {
+                warn!("告警发送到 {} 失败: {}", notifier.target(), e);
+            }
+        }
+    }
+
+    async fn send_to(&self, notifier: &dyn Notifier, notification: &Notification) -> Result<()> {
+        let response = notifier.request(&self.client, notification).send().await?;
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            anyhow::bail!("HTTP状态码: {}，响应内容: {}", status, truncate_chars(&body, MAX_ERROR_CHARS));
+        }
+        notifier.check_response(&body)
+    }
+
+    /// 向每个告警接收方发送测试通知，返回各接收方的结果
+    pub async fn send_test(&self) -> Vec<(String, Result<()>)> {
+        let notification = test_notification(Utc::now());
+        let mut results = Vec::new();
+        for notifier in &self.notifiers {
+            results.push((notifier.target().to_string(), self.send_to(notifier.as_ref(), &notification).await));
+        }
+        results
+    }
+}
+
+/// 向所有告警接收方和邮件发送测试通知并逐个记录结果；未配置任何接收方或有接收方失败时返回错误
+pub async fn alert_test(alerts: &AlertConfig, email: Option<EmailConfig>) -> Result<()> {
+    let mut results = Alerter::new(alerts).send_test().await;
+    if let Some(result) = EmailReporter::new(email).send_test().await {
+        results.push(("email".to_string(), result));
+    }
+    if results.is_empty() {
+        anyhow::bail!("未配置任何告警接收方");
+    }
+
+    let mut failed = 0;
+    for (target, result) in &results {
+        match result {
+            Ok(()) => info!("测试通知发送成功: {}", target),
+            Err(e) => {
+                failed += 1;
+                error!("测试通知发送失败: {}: {:#}", target, e);
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{}/{} 个告警接收方测试失败", failed, results.len());
+    }
+    Ok(())
+}
+
+/// 告警配置测试用的通知，不包含任何监测数据
+pub fn test_notification(now: DateTime<Utc>) -> Notification {
+    Notification {
+        title: "【测试】数据集监测告警测试".to_string(),
+        body: format!("这是一条测试通知，用于验证告警接收方配置，请忽略。\n\n发送时间 {}", now.format("%Y-%m-%d %H:%M:%S")),
+        items: Vec::new(),
+        payload: serde_json::json!({
+            "type": "test",
+            "test": true,
+            "message": "这是一条测试通知，用于验证告警接收方配置，请忽略",
+            "sent_at": now,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{IdBacklog, RunEnvironment, StorageSize};
+    use crate::test_support::{response, stub_server, Stub};
+
+    /// `centers` 为 (数据中心, 检查数, 成功数)
+    fn summary(run_id: &str, centers: &[(&str, usize, usize)]) -> MonitorSummary {
+        let centers: Vec<_> = centers.iter()
+            .map(|(name, total, success)| serde_json::json!({
+                "center_name": name,
+                "total": total,
+                "success": success,
+                "success_rate": percentage(*success as i64, *total as i64),
+                "local_issues": 0,
+                "remote_issues": total - success,
+                "error_categories": [{ "category": "HTTP_5XX", "count": total - success }],
+                "sample_failures": [],
+            }))
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "run_id": run_id,
+            "started_at": "2026-01-01T00:00:00Z",
+            "finished_at": "2026-01-01T01:00:00Z",
+            "total": 0,
+            "success": 0,
+            "local_issues": 0,
+            "remote_issues": 0,
+            "centers": centers,
+        })).unwrap()
+    }
+
+    fn config(min_success_rate: Option<f64>, max_rate_drop: Option<f64>) -> AlertConfig {
+        AlertConfig { min_success_rate, max_rate_drop, ..AlertConfig::default() }
+    }
+
+    fn reasons(alerts: &[CenterAlert]) -> Vec<(&str, &str)> {
+        alerts.iter().map(|a| (a.center_name.as_str(), a.reason.as_str())).collect()
+    }
+
+    #[test]
+    fn alerts_below_threshold() {
+        let current = summary("r2", &[("A", 100, 89), ("B", 100, 90), ("C", 0, 0)]);
+        let alerts = evaluate_center_alerts(None, &current, &config(Some(90.0), None));
+        // 恰好等于阈值不告警，没有检查URL的数据中心不参与判断
+        assert_eq!(reasons(&alerts), [("A", "below_threshold")]);
+        let alert = &alerts[0];
+        assert_eq!((alert.total, alert.failed, alert.previous_rate), (100, 11, None));
+        assert_eq!(alert.run_id, "r2");
+
+        assert!(evaluate_center_alerts(None, &current, &config(None, None)).is_empty());
+    }
+
+    #[test]
+    fn alerts_on_drop_from_previous_summary() {
+        let previous = summary("r1", &[("A", 100, 100), ("B", 100, 100), ("C", 0, 0)]);
+        let current = summary("r2", &[("A", 100, 89), ("B", 100, 90), ("C", 100, 10), ("D", 100, 10)]);
+        let alerts = evaluate_center_alerts(Some(&previous), &current, &config(None, Some(10.0)));
+        // 下降恰好 10 个百分点不告警；上次没有检查（C）或没有记录（D）的数据中心不比较
+        assert_eq!(reasons(&alerts), [("A", "rate_drop")]);
+        assert_eq!(alerts[0].previous_rate, Some(100.0));
+
+        // 同时满足时按低于阈值告警
+        let alerts = evaluate_center_alerts(Some(&previous), &current, &config(Some(50.0), Some(10.0)));
+        assert_eq!(reasons(&alerts), [("A", "rate_drop"), ("C", "below_threshold"), ("D", "below_threshold")]);
+        assert!(evaluate_center_alerts(None, &current, &config(None, Some(10.0))).is_empty());
+    }
+
+    #[test]
+    fn previous_summary_merges_recent_runs() {
+        let mut tagged = summary("r4", &[("A", 10, 0)]);
+        tagged.tag = Some("core".to_string());
+        // 最近的运行在前
+        let recent = [
+            summary("r5", &[("A", 100, 50)]),
+            tagged,
+            summary("r3", &[("B", 100, 80)]),
+            summary("r2", &[("A", 100, 100), ("C", 100, 70)]),
+        ];
+        let previous = previous_summary(&recent, "r5").unwrap();
+        assert_eq!(previous.run_id, "r3");
+        let rates: Vec<_> = previous.centers.iter().map(|c| (c.center_name.as_str(), c.success_rate)).collect();
+        assert_eq!(rates, [("B", 80.0), ("A", 100.0), ("C", 70.0)]);
+
+        assert!(previous_summary(&recent[..1], "r5").is_none());
+        assert!(previous_summary(&[], "r5").is_none());
+    }
+
+    fn local_summary(total: usize, local_issues: usize) -> MonitorSummary {
+        let mut summary = summary("r1", &[("A", total, total - local_issues)]);
+        summary.total = total;
+        summary.local_issues = local_issues;
+        summary
+    }
+
+    #[test]
+    fn local_issue_boundaries() {
+        let config = AlertConfig {
+            local_issue_percent: Some(20.0),
+            local_issue_min_sample: 50,
+            ..AlertConfig::default()
+        };
+        // 样本数恰好等于 local_issue_min_sample 时参与判断，少一个则不判断
+        assert_eq!(local_issue_rate(&local_summary(49, 49), &config), None);
+        assert_eq!(local_issue_rate(&local_summary(50, 10), &config), Some(20.0));
+        // 占比恰好等于阈值不告警，超过才告警
+        let (rate, breach) = evaluate_local_issues(&local_summary(50, 10), &config).unwrap();
+        assert_eq!((rate, breach.is_some()), (20.0, false));
+        let (rate, breach) = evaluate_local_issues(&local_summary(50, 11), &config).unwrap();
+        assert_eq!((rate, breach.is_some()), (22.0, true));
+        assert!(evaluate_local_issues(&local_summary(49, 49), &config).is_none());
+
+        // 没有检查任何URL时即使不要求样本数也不判断
+        let no_minimum = AlertConfig { local_issue_min_sample: 0, ..config.clone() };
+        assert_eq!(local_issue_rate(&local_summary(0, 0), &no_minimum), None);
+        let (rate, breach) = evaluate_local_issues(&local_summary(1, 1), &no_minimum).unwrap();
+        assert_eq!((rate, breach.is_some()), (100.0, true));
+        // 未配置阈值时不判断
+        let disabled = AlertConfig { local_issue_percent: None, ..config };
+        assert!(evaluate_local_issues(&local_summary(100, 100), &disabled).is_none());
+    }
+
+    fn state(open: bool, last_fired_at: Option<DateTime<Utc>>) -> AlertState {
+        AlertState {
+            rule: RULE_CENTER_SUCCESS_RATE.to_string(),
+            subject: "A".to_string(),
+            open,
+            last_fired_at,
+            last_value: Some(80.0),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn cooldown_suppresses_repeated_alerts() {
+        let now = Utc::now();
+        let cooldown = Duration::minutes(60);
+        let recent = state(true, Some(now - Duration::minutes(59)));
+        let expired = state(true, Some(now - Duration::minutes(60)));
+        let closed = state(false, Some(now - Duration::minutes(1)));
+
+        assert_eq!(next_action(None, true, now, cooldown), AlertAction::Fire);
+        assert_eq!(next_action(Some(&recent), true, now, cooldown), AlertAction::Suppress);
+        assert_eq!(next_action(Some(&expired), true, now, cooldown), AlertAction::Fire);
+        assert_eq!(next_action(Some(&state(true, None)), true, now, cooldown), AlertAction::Fire);
+        // 已恢复的告警再次触发不受冷却期限制
+        assert_eq!(next_action(Some(&closed), true, now, cooldown), AlertAction::Fire);
+
+        assert_eq!(next_action(Some(&recent), false, now, cooldown), AlertAction::Resolve);
+        assert_eq!(next_action(Some(&closed), false, now, cooldown), AlertAction::Nothing);
+        assert_eq!(next_action(None, false, now, cooldown), AlertAction::Nothing);
+    }
+
+    fn fetch_report(pending: Option<u64>) -> CenterFetchReport {
+        CenterFetchReport {
+            name: "A".to_string(),
+            discovered: 30,
+            processed: 5,
+            backlog: pending.map(|pending| IdBacklog { center_name: "A".to_string(), pending, ..IdBacklog::default() }),
+            ..CenterFetchReport::default()
+        }
+    }
+
+    fn fetch_audit(pending: Option<u64>) -> FetchAudit {
+        FetchAudit {
+            center_name: "A".to_string(),
+            started_at: Utc::now(),
+            finished_at: Utc::now(),
+            success: true,
+            outcome: Some(fetch_report(pending)),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn backlog_growth_boundaries() {
+        let previous = fetch_audit(Some(100));
+        assert_eq!(evaluate_backlog_growth(Some(&previous), &fetch_report(Some(125)), Some(25)), None);
+        assert_eq!(
+            evaluate_backlog_growth(Some(&previous), &fetch_report(Some(126)), Some(25)).as_deref(),
+            Some("待处理ID由 100 个增至 126 个，增加 26 个（本次新发现 30 个，处理 5 个）")
+        );
+        // 积压减少、未配置、缺少任一次的统计时不判断
+        assert_eq!(evaluate_backlog_growth(Some(&previous), &fetch_report(Some(0)), Some(0)), None);
+        assert_eq!(evaluate_backlog_growth(Some(&previous), &fetch_report(Some(500)), None), None);
+        assert_eq!(evaluate_backlog_growth(None, &fetch_report(Some(500)), Some(0)), None);
+        assert_eq!(evaluate_backlog_growth(Some(&fetch_audit(None)), &fetch_report(Some(500)), Some(0)), None);
+        assert_eq!(evaluate_backlog_growth(Some(&previous), &fetch_report(None), Some(0)), None);
+    }
+
+    #[test]
+    fn notifications_carry_the_run_environment() {
+        let mut current = summary("r1", &[("A", 100, 50)]);
+        let notification = local_issue_notification(&current, 60.0);
+        // 没有环境信息的运行（旧版本写入的汇总）原样返回
+        let unchanged = with_environment(notification.clone(), &current);
+        assert_eq!(unchanged.body, notification.body);
+        assert!(unchanged.payload.get("environment").is_none());
+
+        current.environment = Some(RunEnvironment {
+            egress_ip: Some("203.0.113.7".to_string()),
+            hostname: None,
+            version: "1.2.3".to_string(),
+            git_commit: "abc".to_string(),
+        });
+        let notification = with_environment(notification, &current);
+        assert!(notification.body.ends_with("出口 IP: 203.0.113.7，主机: -，版本: 1.2.3"), "{}", notification.body);
+        assert_eq!(notification.payload["environment"]["egress_ip"], "203.0.113.7");
+        assert_eq!(notification.payload["type"], "local_network_issues");
+    }
+
+    /// 以 `status` 响应的 webhook 桩服务，返回 webhook URL 和桩服务
+    async fn webhook_server(status: &'static str) -> (String, Stub) {
+        let stub = stub_server(move |_| response(status, &[], "")).await;
+        (format!("{}/hook", stub.base), stub)
+    }
+
+    /// 按收到的顺序解析 webhook 请求体
+    fn bodies(stub: &Stub) -> Vec<serde_json::Value> {
+        stub.requests.lock().unwrap().iter().map(|request| serde_json::from_slice(&request.body).unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn breach_repeat_and_recovery_notify_once_each() {
+        let (url, stub) = webhook_server("200 OK").await;
+        let config = AlertConfig {
+            min_success_rate: Some(90.0),
+            cooldown_minutes: 60,
+            targets: vec![crate::config::AlertTarget { kind: crate::config::AlertTargetKind::Webhook, url, secret: None }],
+            ..AlertConfig::default()
+        };
+        let alerter = Alerter::new(&config);
+        let db = DuckDB::new(":memory:").await.unwrap();
+
+        // 第一轮触发，第二轮仍低于阈值但在冷却期内，第三轮恢复
+        let rounds = [("r1", 50, 0), ("r2", 60, 10), ("r3", 100, 20)];
+        let mut open = Vec::new();
+        for (run_id, success, minutes) in rounds {
+            let mut current = summary(run_id, &[("A", 100, success)]);
+            current.finished_at += Duration::minutes(minutes);
+            alerter.on_monitor_run(&current, &db).await.unwrap();
+            open.push(db.get_alert_state(RULE_CENTER_SUCCESS_RATE, "A").await.unwrap().map(|s| s.open));
+        }
+        assert_eq!(open, [Some(true), Some(true), Some(false)]);
+
+        let received = bodies(&stub);
+        assert_eq!(received.len(), 2, "{:?}", received);
+        assert_eq!((received[0]["center_name"].as_str(), received[0]["reason"].as_str()), (Some("A"), Some("below_threshold")));
+        assert_eq!(received[0]["run_id"], "r1");
+        assert_eq!(received[1]["status"], "resolved");
+        assert_eq!((received[1]["rule"].as_str(), received[1]["subject"].as_str()), (Some(RULE_CENTER_SUCCESS_RATE), Some("A")));
+        assert_eq!(received[1]["value"], 100.0);
+    }
+
+    #[tokio::test]
+    async fn database_size_above_the_soft_limit_alerts_and_resolves() {
+        let (url, stub) = webhook_server("200 OK").await;
+        let config = AlertConfig {
+            cooldown_minutes: 60,
+            targets: vec![crate::config::AlertTarget { kind: crate::config::AlertTargetKind::Webhook, url, secret: None }],
+            ..AlertConfig::default()
+        };
+        let alerter = Alerter::new(&config);
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let mb = |n: u64| Some(n * 1024 * 1024);
+
+        // 未配置软限制时只记录大小；之后超过、仍超过（冷却期内）、清理后回到限制以下
+        let rounds = [("r0", None, 300, None), ("r1", mb(200), 300, Some(42)), ("r2", mb(200), 320, Some(0)), ("r3", mb(400), 320, None)];
+        let mut open = Vec::new();
+        for (minutes, (run_id, soft_limit_bytes, end_mb, pruned_records)) in rounds.into_iter().enumerate() {
+            let mut current = summary(run_id, &[]);
+            current.finished_at += Duration::minutes(minutes as i64 * 10);
+            current.storage = Some(RunStorage {
+                start: StorageSize { db_bytes: mb(100), wal_bytes: None },
+                end: StorageSize { db_bytes: mb(end_mb - 20), wal_bytes: mb(20) },
+                soft_limit_bytes,
+                pruned_records,
+            });
+            alerter.on_monitor_run(&current, &db).await.unwrap();
+            open.push(db.get_alert_state(RULE_DATABASE_SIZE, "duckdb").await.unwrap().map(|s| s.open));
+        }
+        assert_eq!(open, [None, Some(true), Some(true), Some(false)]);
+
+        let received = bodies(&stub);
+        assert_eq!(received.len(), 2, "{:?}", received);
+        assert_eq!(received[0]["type"], "database_size");
+        assert_eq!(received[0]["run_id"], "r1");
+        assert_eq!(received[0]["growth_bytes"], 200 * 1024 * 1024);
+        assert_eq!(received[0]["soft_limit_bytes"], 200 * 1024 * 1024);
+        assert_eq!(received[0]["pruned_records"], 42);
+        assert_eq!(received[1]["status"], "resolved");
+        assert_eq!((received[1]["rule"].as_str(), received[1]["subject"].as_str()), (Some(RULE_DATABASE_SIZE), Some("duckdb")));
+        assert_eq!(received[1]["value"], 320.0);
+    }
+
+    fn failure(id: &str, center: &str, local: bool) -> NewFailure {
+        NewFailure {
+            id: id.to_string(),
+            url: format!("https://{}.example.org/{}", center.to_lowercase(), id),
+            name: Some(format!("数据集{}", id)),
+            center_name: center.to_string(),
+            status_code: Some(503),
+            error_category: Some("HTTP_5XX".to_string()),
+            error_msg: Some("HTTP 503".to_string()),
+            is_likely_local_issue: local,
+            failed_hop_index: None,
+            failed_hop_url: None,
+            tags: Vec::new(),
+            last_success_at: None,
+        }
+    }
+
+    fn with_failures(failures: Vec<NewFailure>) -> MonitorSummary {
+        let mut current = summary("r1", &[]);
+        current.new_failure_count = failures.len();
+        current.new_failures = failures;
+        current
+    }
+
+    #[test]
+    fn new_failures_below_min_count_are_ignored() {
+        let config = AlertConfig { new_failure_min_count: Some(3), ..AlertConfig::default() };
+        let current = with_failures(vec![failure("1", "A", false), failure("2", "B", false)]);
+        assert!(new_failure_notification(&current, &config).is_none());
+        // 未配置 new_failure_min_count 时不告警
+        let current = with_failures(vec![failure("1", "A", false), failure("2", "B", false), failure("3", "B", false)]);
+        assert!(new_failure_notification(&current, &AlertConfig::default()).is_none());
+        assert!(new_failure_notification(&current, &config).is_some());
+        assert!(new_failure_notification(&with_failures(Vec::new()), &AlertConfig { new_failure_min_count: Some(0), ..config }).is_none());
+    }
+
+    #[test]
+    fn local_issue_failures_can_be_excluded() {
+        let failures = vec![failure("1", "A", true), failure("2", "A", true), failure("3", "B", false)];
+        let excluded = AlertConfig { new_failure_min_count: Some(2), ..AlertConfig::default() };
+        // 排除本地网络问题后只剩 1 个，未达到最小数量
+        assert!(new_failure_notification(&with_failures(failures.clone()), &excluded).is_none());
+
+        let included = AlertConfig { new_failure_exclude_local: false, ..excluded };
+        let notification = new_failure_notification(&with_failures(failures), &included).unwrap();
+        assert_eq!(notification.payload["count"], 3);
+        assert_eq!(notification.title, "数据集监测: 3 个URL新近失败");
+    }
+
+    #[test]
+    fn new_failures_are_grouped_and_capped_per_center() {
+        let config = AlertConfig { new_failure_min_count: Some(1), new_failure_max_per_center: 2, ..AlertConfig::default() };
+        let mut hop = failure("9", "A", false);
+        hop.failed_hop_index = Some(1);
+        hop.failed_hop_url = Some("https://mirror.example.org/9".to_string());
+        let current = with_failures(vec![
+            failure("1", "B", false),
+            failure("2", "A", false),
+            failure("3", "B", false),
+            failure("4", "B", false),
+            hop,
+        ]);
+        let notification = new_failure_notification(&current, &config).unwrap();
+        assert!(notification.body.starts_with("**新失败URL**: 5\n\n- A: 2\n- B: 3\n\n运行 r1"), "{}", notification.body);
+        assert_eq!(notification.items, [
+            "[A] 数据集2 https://a.example.org/2 (HTTP_5XX: HTTP 503)",
+            "[A] 数据集9 https://a.example.org/9 (HTTP_5XX: HTTP 503) 第 1 次重定向后失败: https://mirror.example.org/9",
+            "[B] 数据集1 https://b.example.org/1 (HTTP_5XX: HTTP 503)",
+            "[B] 数据集3 https://b.example.org/3 (HTTP_5XX: HTTP 503)",
+            "[B] ... 另有 1 条",
+        ]);
+        assert_eq!(notification.payload["type"], "new_failures");
+        assert_eq!(notification.payload["failures"].as_array().unwrap().len(), 5);
+    }
+
+    fn listed_audit(listed: Option<usize>) -> FetchAudit {
+        FetchAudit {
+            outcome: listed.map(|listed| CenterFetchReport { listed, ..fetch_report(None) }),
+            ..fetch_audit(None)
+        }
+    }
+
+    fn listed_report(listed: usize) -> CenterFetchReport {
+        CenterFetchReport { listed, ..fetch_report(None) }
+    }
+
+    #[test]
+    fn empty_dataset_list_after_a_non_empty_one() {
+        let previous = listed_audit(Some(120));
+        // 列表为空时不论是否配置 fetch_count_drop_percent 都告警
+        assert_eq!(
+            evaluate_fetch_anomaly(Some(&previous), &listed_report(0), None).as_deref(),
+            Some("数据集列表为空，上次成功获取时为 120 条")
+        );
+        // 之前也为空、没有上次成功的获取或上次没有统计时不告警
+        assert_eq!(evaluate_fetch_anomaly(Some(&listed_audit(Some(0))), &listed_report(0), Some(10.0)), None);
+        assert_eq!(evaluate_fetch_anomaly(None, &listed_report(0), Some(10.0)), None);
+        assert_eq!(evaluate_fetch_anomaly(Some(&listed_audit(None)), &listed_report(0), Some(10.0)), None);
+    }
+
+    #[test]
+    fn dataset_count_drop_boundaries() {
+        let previous = listed_audit(Some(200));
+        // 下降恰好 10% 不告警
+        assert_eq!(evaluate_fetch_anomaly(Some(&previous), &listed_report(180), Some(10.0)), None);
+        assert_eq!(
+            evaluate_fetch_anomaly(Some(&previous), &listed_report(179), Some(10.0)).as_deref(),
+            Some("数据集列表数量由 200 条降至 179 条，下降 10.5%")
+        );
+        // 未配置下降比例、数量增加时不告警
+        assert_eq!(evaluate_fetch_anomaly(Some(&previous), &listed_report(1), None), None);
+        assert_eq!(evaluate_fetch_anomaly(Some(&previous), &listed_report(400), Some(0.0)), None);
+    }
+
+    #[test]
+    fn fetch_failures_carry_a_truncated_response() {
+        let error = format!("获取数据集列表失败，HTTP状态码: 401 Unauthorized，响应内容: {}", "x".repeat(2000));
+        let notification = fetch_failure_notification("A", &error, Utc::now());
+        let sent = notification.payload["error"].as_str().unwrap();
+        assert!(sent.starts_with("获取数据集列表失败，HTTP状态码: 401 Unauthorized"));
+        assert!(sent.ends_with("...") && sent.chars().count() == MAX_ERROR_CHARS + 3);
+        assert!(notification.body.contains(sent));
+    }
+
+    #[tokio::test]
+    async fn alert_test_reports_each_target_and_fails_on_any_failure() {
+        let (ok_url, ok) = webhook_server("200 OK").await;
+        let (failing_url, failing) = webhook_server("500 Internal Server Error").await;
+        let config = AlertConfig {
+            targets: [&ok_url, &failing_url].into_iter()
+                .map(|url| crate::config::AlertTarget { kind: crate::config::AlertTargetKind::Webhook, url: url.clone(), secret: None })
+                .collect(),
+            ..AlertConfig::default()
+        };
+
+        let results = Alerter::new(&config).send_test().await;
+        let outcomes: Vec<_> = results.iter().map(|(target, result)| (target.as_str(), result.is_ok())).collect();
+        assert_eq!(outcomes, [(ok_url.as_str(), true), (failing_url.as_str(), false)]);
+        assert!(results[1].1.as_ref().unwrap_err().to_string().contains("500"));
+        assert_eq!(bodies(&ok)[0]["type"], "test");
+
+        let error = alert_test(&config, None).await.unwrap_err().to_string();
+        assert!(error.contains("1/2"), "{}", error);
+        assert_eq!((ok.requests.lock().unwrap().len(), failing.requests.lock().unwrap().len()), (2, 2));
+
+        // 没有任何接收方时不能当作测试通过
+        let error = alert_test(&AlertConfig::default(), None).await.unwrap_err().to_string();
+        assert!(error.contains("未配置任何告警接收方"), "{}", error);
+    }
+}
@@ -0,0 +1,89 @@
+//! 已发现但尚未处理的数据集ID积压：按数据中心统计各状态的ID数，并写成 Prometheus 文本格式供 node_exporter 的 textfile collector 采集
+
+use crate::config::Config;
+use crate::db::mongodb::MongoDB;
+use crate::models::IdBacklog;
+use anyhow::{Context, Result};
+use std::fmt::Write;
+use std::path::Path;
+use tokio::sync::Mutex;
+
+/// 多个数据中心同时获取时串行写指标文件，避免互相覆盖临时文件
+static WRITE_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// 统计所有启用的数据中心的积压，配置了 backlog_metrics_file 时同时刷新指标文件
+pub async fn refresh(config: &Config, db: &MongoDB) -> Result<Vec<IdBacklog>> {
+    let mut backlogs = Vec::new();
+    for center in config.centers.iter().filter(|c| c.enabled) {
+        backlogs.push(db.count_ids_by_status(&center.name).await
+            .with_context(|| format!("统计数据中心 {} 的ID积压失败", center.name))?);
+    }
+    if let Some(path) = &config.monitor.backlog_metrics_file {
+        write_metrics(path, &backlogs).await?;
+    }
+    Ok(backlogs)
+}
+
+/// 渲染 Prometheus 文本格式的积压指标
+pub fn render_metrics(backlogs: &[IdBacklog]) -> String {
+    let mut text = String::new();
+    text.push_str("# HELP dataset_fetch_backlog 数据中心各状态的数据集ID数，failed 为至少失败过一次的待处理ID\n");
+    text.push_str("# TYPE dataset_fetch_backlog gauge\n");
+    for backlog in backlogs {
+        let center = backlog.center_name.replace('\\', "\\\\").replace('"', "\\\"");
+        for (status, value) in [
+            ("pending", backlog.pending),
+            ("in_progress", backlog.in_progress),
+            ("processed", backlog.processed),
+            ("failed", backlog.failed),
+            ("expired", backlog.expired),
+        ] {
+            let _ = writeln!(text, "dataset_fetch_backlog{{center=\"{}\",status=\"{}\"}} {}", center, status, value);
+        }
+    }
+    text
+}
+
+/// 写入指标文件，先写临时文件再重命名，避免采集时读到写了一半的文件
+async fn write_metrics(path: &str, backlogs: &[IdBacklog]) -> Result<()> {
+    let _guard = WRITE_LOCK.lock().await;
+    if let Some(dir) = Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).with_context(|| format!("创建指标文件目录失败: {}", dir.display()))?;
+    }
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, render_metrics(backlogs)).with_context(|| format!("写入指标文件失败: {}", tmp))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("写入指标文件失败: {}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backlog(center_name: &str, pending: u64) -> IdBacklog {
+        IdBacklog { center_name: center_name.to_string(), pending, in_progress: 2, processed: 10, failed: 1, expired: 0 }
+    }
+
+    #[test]
+    fn metrics_escape_center_labels() {
+        let text = render_metrics(&[backlog("A", 5), backlog("B \"x\" \\", 0)]);
+        let samples: Vec<&str> = text.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(samples.len(), 10);
+        assert_eq!(samples[0], "dataset_fetch_backlog{center=\"A\",status=\"pending\"} 5");
+        assert_eq!(samples[4], "dataset_fetch_backlog{center=\"A\",status=\"expired\"} 0");
+        assert_eq!(samples[6], "dataset_fetch_backlog{center=\"B \\\"x\\\" \\\\\",status=\"in_progress\"} 2");
+        assert!(text.contains("# TYPE dataset_fetch_backlog gauge\n"));
+    }
+
+    #[tokio::test]
+    async fn metrics_file_is_replaced_whole() {
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-backlog-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("textfile").join("backlog.prom").display().to_string();
+        write_metrics(&path, &[backlog("A", 5)]).await.unwrap();
+        write_metrics(&path, &[backlog("A", 7)]).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), render_metrics(&[backlog("A", 7)]));
+        assert!(!Path::new(&format!("{}.tmp", path)).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
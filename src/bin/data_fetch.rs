@@ -1,54 +1,287 @@
 use anyhow::Result;
+use dataset_monitor::alert::Alerter;
+use dataset_monitor::config::Center;
+use dataset_monitor::db::duckdb::DuckDB;
 use dataset_monitor::db::mongodb::MongoDB;
-use dataset_monitor::{config, db, init_logging, DataFetcher};
+use dataset_monitor::heartbeat::{Heartbeat, HeartbeatJob};
+use dataset_monitor::scheduler::{fetch_schedules, log_next_fire, run_with_lease, sleep_jitter, JobGuard, JobState, Shutdown, Supervisor};
+use dataset_monitor::duplicates::{duplicate_pair_counts, reconcile_duplicates};
+use dataset_monitor::{backlog, build_info, config, db, init_logging, systemd, DataFetcher};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio_cron_scheduler::{Job, JobScheduler};
-use tracing::{error, info};
-
-async fn execute_data_fetch(config: Arc<config::Config>, db: Arc<MongoDB>) -> Result<()> {
-    info!("开始执行数据获取任务");
-    let fetcher = DataFetcher::new(config);
-    fetcher.fetch_all_center(&db).await.map_err(|e| {
-        error!("数据获取失败: {}", e);
-        e
-    })
+use tracing::{error, info, warn};
+
+async fn execute_data_fetch(config: Arc<config::Config>, center: Center, db: Arc<MongoDB>, state: Arc<JobState>, heartbeat: Arc<Heartbeat>, alerter: Arc<Alerter>, shutdown: Shutdown) -> Result<()> {
+    if shutdown.is_cancelled() {
+        return Ok(());
+    }
+    let _running = shutdown.running();
+    info!("开始执行数据中心 {} 的数据获取任务", center.name);
+    // 本次运行前最近一次成功的完整获取记录，用于判断数据集列表是否异常
+    let previous = db.get_last_successful_fetch(&center.name, true).await.unwrap_or_else(|e| {
+        warn!("读取数据中心 {} 的数据获取记录失败: {}", center.name, e);
+        None
+    });
+    // 上一次成功的获取（包括增量获取），用于比较ID积压的增长
+    let previous_any = db.get_last_successful_fetch(&center.name, false).await.unwrap_or_else(|e| {
+        warn!("读取数据中心 {} 的数据获取记录失败: {}", center.name, e);
+        None
+    });
+    let fetcher = DataFetcher::new(config.clone()).with_cancel_token(shutdown.cancel_token());
+    // 获取到运行锁后才发送开始心跳，被其他实例跳过的运行不留下没有结束的开始心跳
+    let run = async {
+        heartbeat.start().await;
+        Ok(fetcher.fetch_center(&center, &db).await)
+    };
+    let result = if config.monitor.distributed_lock {
+        let ttl = Duration::from_secs(config.monitor.lock_ttl_secs);
+        run_with_lease(&db, &format!("data_fetch-{}", center.name), ttl, run).await
+    } else {
+        run.await.map(Some)
+    };
+    let report = match result {
+        Ok(Some(report)) => report,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            error!("数据中心 {} 数据获取失败: {}", center.name, e);
+            alerter.on_fetch_failure(&center.name, &format!("{:#}", e)).await;
+            return Err(e);
+        }
+    };
+    info!("数据获取结果: {}", serde_json::to_string(&report)?);
+    // 取消的运行只有部分结果，不告警，也不算作成功运行，重启后会补跑
+    if report.cancelled {
+        return Ok(());
+    }
+    if let Some(error) = &report.list_error {
+        alerter.on_fetch_failure(&center.name, error).await;
+        anyhow::bail!("数据中心 {} 数据获取失败: {}", center.name, error);
+    }
+    // 增量获取的列表只含有变化的ID，不与上次的列表数量比较
+    if !report.incremental {
+        alerter.on_fetch_success(&center.name, previous.as_ref(), &report).await;
+    }
+    alerter.on_fetch_backlog(&center.name, previous_any.as_ref(), &report).await;
+    // 数据变化后重新识别跨数据中心的重复数据集
+    if let Err(e) = reconcile_duplicates(&config, &db).await {
+        warn!("识别重复数据集失败: {:#}", e);
+    }
+    if let Err(e) = state.record_success(chrono::Utc::now()) {
+        warn!("记录数据获取运行状态失败: {}", e);
+    }
+    heartbeat.success().await;
+    Ok(())
+}
+/// 在后台运行详情工作任务，退出时随 `shutdown` 取消并等待进行中的批次完成
+fn spawn_detail_workers(config: &Arc<config::Config>, db: &Arc<MongoDB>, shutdown: &Shutdown, workers: usize) {
+    let fetcher = DataFetcher::new(config.clone()).with_cancel_token(shutdown.cancel_token());
+    let db = db.clone();
+    let running = shutdown.running();
+    tokio::spawn(async move {
+        let _running = running;
+        fetcher.run_detail_workers(&db, workers).await;
+    });
+}
+
+async fn dry_run(config: &Arc<config::Config>, db: &MongoDB, center_name: Option<&str>) -> Result<()> {
+    let fetcher = DataFetcher::new(config.clone());
+    let mut plans = Vec::new();
+    for center in config.centers.iter().filter(|c| c.enabled && center_name.is_none_or(|name| c.name == name)) {
+        match fetcher.plan_center(center, db).await {
+            Ok(plan) => plans.push(plan),
+            Err(e) => error!("数据中心 {} 试运行失败: {:#}", center.name, e),
+        }
+    }
+    println!("{}", serde_json::to_string_pretty(&plans)?);
+    Ok(())
 }
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    build_info::started_at();
+    // version：输出版本和构建信息后退出
+    if std::env::args().nth(1).as_deref() == Some("version") {
+        println!("{}", serde_json::to_string_pretty(&build_info::runtime_info("data_fetch", "config.yaml"))?);
+        return Ok(());
+    }
     init_logging("data-fetch.log")?;
 
     info!("启动数据获取系统");
+    build_info::log_startup("data_fetch", "config.yaml");
 
     // 加载配置
     let config = config::Config::load("config.yaml")?;
     let config_arc = Arc::new(config);
 
     let db = Arc::new(MongoDB::new(&config_arc.mongodb).await?);
-    db::init_duckdb(&config_arc.duckdb.path).await?;
-    let scheduler = JobScheduler::new().await?;
-    if let Err(e) = execute_data_fetch(config_arc.clone(), db.clone()).await {
-        error!("首次数据获取失败: {}", e);
+
+    // --dry-run [数据中心名称]：只请求和解析，不写 MongoDB，输出试运行报告后退出
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("--dry-run") {
+        return dry_run(&config_arc, &db, args.get(2).map(String::as_str)).await;
+    }
+    // reparse：重新解析之前保存的原始响应后退出
+    if args.get(1).map(String::as_str) == Some("reparse") {
+        let (succeeded, failed) = DataFetcher::new(config_arc.clone()).reparse_raw_responses(&db).await?;
+        info!("重新解析原始响应: 成功 {}，失败 {}", succeeded, failed);
+        return Ok(());
+    }
+    // run：立即并发获取所有启用的数据中心一次，输出各中心的结果后退出
+    if args.get(1).map(String::as_str) == Some("run") {
+        let report = DataFetcher::new(config_arc.clone()).fetch_all_center(&db).await;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    // metrics [天数]：输出最近 N 天（默认 30）各数据中心的数据获取性能记录后退出
+    if args.get(1).map(String::as_str) == Some("metrics") {
+        let days: i64 = args.get(2).map(|d| d.parse()).transpose()?.unwrap_or(30);
+        let until = chrono::Utc::now();
+        let duckdb = DuckDB::new(&config_arc.duckdb.path).await?.with_query_timeouts(|group| config_arc.duckdb.query_timeout(group));
+        let metrics = duckdb.get_fetch_metrics(until - chrono::Duration::days(days), until).await?;
+        println!("{}", serde_json::to_string_pretty(&metrics)?);
+        return Ok(());
     }
+    // pending：输出各数据中心待处理、失败过、已过期的 ID 数量后退出
+    if args.get(1).map(String::as_str) == Some("pending") {
+        println!("{}", serde_json::to_string_pretty(&db.get_pending_stats().await?)?);
+        return Ok(());
+    }
+    // backlog：统计各数据中心各状态的 ID 数量并输出，配置了 backlog_metrics_file 时同时刷新指标文件后退出
+    if args.get(1).map(String::as_str) == Some("backlog") {
+        println!("{}", serde_json::to_string_pretty(&backlog::refresh(&config_arc, &db).await?)?);
+        return Ok(());
+    }
+    // resurrect [数据中心名称]：数据中心修复接口后，把过期的 ID 恢复为待处理
+    if args.get(1).map(String::as_str) == Some("resurrect") {
+        let count = db.resurrect_expired_ids(args.get(2).map(String::as_str)).await?;
+        info!("已恢复 {} 个过期的 ID 为待处理", count);
+        return Ok(());
+    }
+    // migrate-collections [--copy]：把用数据中心名称命名的旧集合迁移到配置的集合名（默认重命名，--copy 时复制）后退出
+    if args.get(1).map(String::as_str) == Some("migrate-collections") {
+        let copy = args.iter().skip(2).any(|a| a == "--copy");
+        for center in &config_arc.centers {
+            let collection = config_arc.collection_name(&center.name);
+            if collection == center.name {
+                continue;
+            }
+            match db.migrate_collection(&center.name, &collection, copy).await {
+                Ok(count) => info!("数据中心 {} 的集合 {} 已迁移到 {}（{} 个文档）", center.name, center.name, collection, count),
+                Err(e) => error!("迁移数据中心 {} 的集合失败: {:#}", center.name, e),
+            }
+        }
+        return Ok(());
+    }
+    // duplicates：重新识别跨数据中心的重复数据集，输出各数据中心之间的重复组数后退出
+    if args.get(1).map(String::as_str) == Some("duplicates") {
+        let groups = reconcile_duplicates(&config_arc, &db).await?;
+        println!("{}", serde_json::to_string_pretty(&duplicate_pair_counts(&groups))?);
+        return Ok(());
+    }
+    // --workers [数量]：只运行详情工作任务（数量默认 monitor.detail_workers，未配置时为 1），持续处理待处理的ID，
+    // 收到退出信号后等进行中的批次写库再退出
+    if args.get(1).map(String::as_str) == Some("--workers") {
+        let workers = args.get(2).map(|n| n.parse()).transpose()?.unwrap_or(config_arc.monitor.detail_workers.max(1));
+        let shutdown = Shutdown::new();
+        spawn_detail_workers(&config_arc, &db, &shutdown, workers);
+        systemd::ready();
+        systemd::shutdown_signal().await;
+        info!("收到退出信号，停止详情工作任务");
+        systemd::stopping();
+        shutdown.cancel_and_wait(Duration::from_secs(config_arc.monitor.http_timeout_secs + 30)).await;
+        return Ok(());
+    }
+    db::init_duckdb(&config_arc.duckdb.path).await?;
+    // 配置已加载、数据库可用，通知 systemd 就绪；首次运行期间由看门狗定时器持续喂狗
+    systemd::ready();
+    let _watchdog = systemd::spawn_watchdog();
+    let mut scheduler = JobScheduler::new().await?;
+    let tz = config_arc.monitor.schedule_tz()?;
+    let grace = chrono::Duration::hours(config_arc.monitor.catch_up_grace_hours as i64);
+    let alerter = Arc::new(Alerter::new(&config_arc.alerts));
+    let shutdown = Shutdown::new();
 
-    let fetch_interval_days = config_arc.monitor.fetch_interval_days;
-    let cron_expression = format!("0 0 0 */{} * *", fetch_interval_days);
+    // 每个数据中心一个任务，各自的调度周期
+    let mut job_ids = Vec::new();
+    let mut catch_ups = Vec::new();
+    for schedule in fetch_schedules(&config_arc) {
+        let job_name = format!("data_fetch-{}", schedule.center.name);
+        // 定时触发与手动触发共用同一个守卫，避免重叠运行
+        let guard = Arc::new(JobGuard::new(&job_name, config_arc.monitor.queue_overlapping_runs));
+        let state = Arc::new(JobState::new(&config_arc.monitor.state_dir, &job_name));
+        // 连续失败后退避，跳过中间的触发
+        let supervisor = Arc::new(Supervisor::from_config(&job_name, &config_arc.monitor));
+        let center = schedule.center;
+        let heartbeat = Arc::new(Heartbeat::from_config(&config_arc.heartbeat, HeartbeatJob::Fetch, &center));
+
+        // 仅在错过调度（超期）时启动即补跑，避免每次重启都全量运行
+        if state.is_overdue(schedule.interval, grace) {
+            catch_ups.push((center.clone(), guard.clone(), state.clone(), supervisor.clone(), heartbeat.clone()));
+        }
 
-    let job = Job::new_async(&cron_expression, move |_uuid, _l| {
         let config = config_arc.clone();
         let db = db.clone(); // 这里 clone Arc，而不是 MongoDB 本身
-        Box::pin(async move {
-            if let Err(e) = execute_data_fetch(config, db).await {
-                error!("定时数据获取失败: {}", e);
-            }
-        })
-    })?;
-    scheduler.add(job).await?;
+        let alerter = alerter.clone();
+        let shutdown = shutdown.clone();
+        let name = job_name.clone();
+        let job = Job::new_async_tz(&schedule.cron, tz, move |uuid, mut l| {
+            let config = config.clone();
+            let center = center.clone();
+            let db = db.clone();
+            let guard = guard.clone();
+            let supervisor = supervisor.clone();
+            let state = state.clone();
+            let heartbeat = heartbeat.clone();
+            let alerter = alerter.clone();
+            let shutdown = shutdown.clone();
+            let name = name.clone();
+            Box::pin(async move {
+                // 仅定时触发加随机延迟，且不超过下一次触发时间
+                let next_fire = l.next_tick_for_job(uuid).await.ok().flatten();
+                state.set_jitter(sleep_jitter(&name, config.monitor.schedule_jitter_minutes, next_fire).await);
+                guard.run(|| async {
+                    if let Some(Err(e)) = supervisor.run(&alerter, execute_data_fetch(config.clone(), center.clone(), db.clone(), state.clone(), heartbeat.clone(), alerter.clone(), shutdown.clone())).await {
+                        error!("定时数据获取失败: {}", e);
+                    }
+                }).await;
+            })
+        })?;
+        job_ids.push((job_name, scheduler.add(job).await?));
+    }
+
+    if config_arc.monitor.detail_workers > 0 {
+        spawn_detail_workers(&config_arc, &db, &shutdown, config_arc.monitor.detail_workers);
+    }
 
     scheduler.start().await?;
+    for (job_name, job_id) in job_ids {
+        log_next_fire(&job_name, scheduler.next_tick_for_job(job_id).await?, tz);
+    }
 
-    // 保持程序运行
-    loop {
-        tokio::time::sleep(Duration::from_secs(60)).await;
+    // 调度器启动后在后台补跑，补跑期间其他数据中心照常按调度触发，也能及时响应退出信号
+    for (center, guard, state, supervisor, heartbeat) in catch_ups {
+        let config = config_arc.clone();
+        let db = db.clone();
+        let alerter = alerter.clone();
+        let shutdown = shutdown.clone();
+        let running = shutdown.running();
+        tokio::spawn(async move {
+            let _running = running;
+            guard.run(|| async {
+                if let Some(Err(e)) = supervisor.run(&alerter, execute_data_fetch(config.clone(), center.clone(), db.clone(), state.clone(), heartbeat.clone(), alerter.clone(), shutdown.clone())).await {
+                    error!("补跑数据获取失败: {}", e);
+                }
+            }).await;
+        });
     }
-}
\ No newline at end of file
+
+    // 保持程序运行，直到收到退出信号
+    systemd::shutdown_signal().await;
+    info!("收到退出信号，停止调度");
+    systemd::stopping();
+    scheduler.shutdown().await?;
+    // 进行中的请求最多再等一个请求超时，已完成的批次写库后退出
+    shutdown.cancel_and_wait(Duration::from_secs(config_arc.monitor.http_timeout_secs + 30)).await;
+    Ok(())
+}
@@ -1,55 +1,739 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clokwerk::{AsyncScheduler, TimeUnits};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio_cron_scheduler::{Job, JobScheduler};
-use tracing::{error, info};
-
-use dataset_monitor::{config::Config, db, init_logging, DataMonitor};
-async fn execute_url_monitoring(config: Arc<Config>) -> Result<()> {
-    info!("开始执行URL监测任务");
-    let monitor = DataMonitor::new(config);
-    monitor.check_all_urls().await.map_err(|e| {
-        error!("URL监测失败: {}", e);
+use tracing::{error, info, warn};
+
+use dataset_monitor::alert::{self, Alerter};
+use dataset_monitor::check_file;
+use dataset_monitor::cmdb::CmdbPusher;
+use dataset_monitor::config::Center;
+use dataset_monitor::db::duckdb::DuckDB;
+use dataset_monitor::db::filter::QueryFilter;
+use dataset_monitor::db::mongodb::MongoDB;
+use dataset_monitor::email::EmailReporter;
+use dataset_monitor::export::export_day;
+use dataset_monitor::heartbeat::{Heartbeat, HeartbeatJob};
+use dataset_monitor::models::{center_trends, CoverageReport, DataAsOf, HostSort, StatusGrouping, TimeBasis, TrendGranularity};
+use dataset_monitor::import::{import_csv, ImportMapping};
+use dataset_monitor::monitor::lookup_dataset;
+use dataset_monitor::reclassify::reclassify;
+use dataset_monitor::regression;
+use dataset_monitor::sanitize::{self, TextLimits};
+use dataset_monitor::report::{generate_dashboard, generate_link_report, generate_weekly_report, write_link_reports, write_run_summary, IsoWeek};
+use dataset_monitor::scheduler::{check_schedules, log_next_fire, run_with_lease, sleep_jitter, JobGuard, JobState, Shutdown, Supervisor};
+use dataset_monitor::{build_info, config::Config, db, init_logging, janitor, meta, slow_hosts, spill, systemd, url_quality, DataMonitor};
+/// 输出统计结果的子命令
+const STATS_COMMANDS: [&str; 16] = [
+    "timing", "http-versions", "problematic-urls", "coverage", "hosts", "error-trends", "center-trends",
+    "regressions", "storage", "shared-urls", "hourly", "status-codes", "content-changes", "url-quality", "dataset", "history",
+];
+
+/// 各中心监测任务共用的资源
+struct MonitorContext {
+    config: Arc<Config>,
+    duckdb: Arc<DuckDB>,
+    monitor: DataMonitor,
+    /// 多实例部署时用于分布式运行锁
+    lock: Option<Arc<MongoDB>>,
+    alerter: Alerter,
+    email: EmailReporter,
+    cmdb: Option<CmdbPusher>,
+    shutdown: Shutdown,
+}
+
+async fn execute_url_monitoring(ctx: Arc<MonitorContext>, center: Center, state: Arc<JobState>, heartbeat: Arc<Heartbeat>) -> Result<()> {
+    if ctx.shutdown.is_cancelled() {
+        return Ok(());
+    }
+    let _running = ctx.shutdown.running();
+    info!("开始执行数据中心 {} 的URL监测任务", center.name);
+    // 获取到运行锁后才发送开始心跳，被其他实例跳过的运行不留下没有结束的开始心跳
+    let run = async {
+        heartbeat.start().await;
+        ctx.monitor.check_center(&center).await
+    };
+    let result = match &ctx.lock {
+        Some(db) => {
+            let ttl = Duration::from_secs(ctx.config.monitor.lock_ttl_secs);
+            run_with_lease(db, &format!("data_monitor-{}", center.name), ttl, run).await
+        }
+        None => run.await.map(Some),
+    };
+    let Some(summary) = result.map_err(|e| {
+        error!("数据中心 {} URL监测失败: {}", center.name, e);
+        e
+    })? else {
+        return Ok(());
+    };
+    // 取消的运行只有部分结果，不告警，也不算作成功运行，重启后会补跑
+    if summary.cancelled {
+        return Ok(());
+    }
+    if let Err(e) = state.record_success(chrono::Utc::now()) {
+        warn!("记录URL监测运行状态失败: {}", e);
+    }
+    if let Err(e) = ctx.alerter.on_monitor_run(&summary, &ctx.duckdb).await {
+        warn!("评估监测告警失败: {}", e);
+    }
+    let report = &ctx.config.report;
+    let mut link_reports = Vec::new();
+    if let Some(dir) = &report.link_report_dir {
+        match write_link_reports(&ctx.duckdb, &summary, dir).await {
+            Ok(paths) => link_reports = paths,
+            Err(e) => warn!("写入链接报告失败: {:#}", e),
+        }
+    }
+    ctx.email.send_report(&summary, &link_reports).await;
+    if let Some(path) = &report.dashboard_path {
+        match generate_dashboard(&ctx.duckdb, report.dashboard_days, path).await {
+            Ok(()) => info!("概览页已更新: {}", path),
+            Err(e) => warn!("生成概览页失败: {:#}", e),
+        }
+    }
+    if report.run_summary_keep > 0 {
+        match write_run_summary(&summary, &report.run_summary_dir, report.run_summary_keep) {
+            Ok(path) => info!("运行汇总已写入: {}", path.display()),
+            Err(e) => warn!("写入运行汇总失败: {:#}", e),
+        }
+    }
+    if let Some(cmdb) = &ctx.cmdb
+        && ctx.config.integrations.cmdb.as_ref().is_some_and(|c| c.after_run)
+    {
+        cmdb.push_run(&ctx.duckdb, &summary).await;
+    }
+    if ctx.config.export.after_run {
+        export_and_alert(&ctx, chrono::Utc::now().date_naive()).await;
+    }
+    heartbeat.success().await;
+    Ok(())
+}
+
+/// 导出失败只告警，不影响监测运行的结果
+async fn export_and_alert(ctx: &MonitorContext, date: chrono::NaiveDate) {
+    if let Err(e) = export_day(&ctx.duckdb, &ctx.config.export, date).await {
+        error!("导出 {} 的检查记录失败: {}", date, e);
+        ctx.alerter.on_export_failure(&e.target, &format!("{:#}", e.error)).await;
+    }
+}
+
+/// 统计命令的输出，带 data_as_of 字段
+fn stats_json<T: serde::Serialize>(output: &T, as_of: Option<&DataAsOf>) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&DataAsOf::attach(as_of, output)?)?)
+}
+
+/// 从参数中取出 `name <值>` 并移除，其余位置参数不受影响
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let index = args.iter().position(|a| a == name)?;
+    let value = args.get(index + 1).cloned();
+    args.drain(index..(index + 2).min(args.len()));
+    value
+}
+
+/// 只检查带 `tag` 标签的数据集，结束后评估告警
+async fn execute_tag_monitoring(ctx: Arc<MonitorContext>, tag: String) -> Result<()> {
+    if ctx.shutdown.is_cancelled() {
+        return Ok(());
+    }
+    let _running = ctx.shutdown.running();
+    let summary = ctx.monitor.check_tagged(&tag).await.map_err(|e| {
+        error!("标签 {} 的URL监测失败: {}", tag, e);
         e
-    })
+    })?;
+    if !summary.cancelled
+        && let Err(e) = ctx.alerter.on_monitor_run(&summary, &ctx.duckdb).await
+    {
+        warn!("评估监测告警失败: {}", e);
+    }
+    Ok(())
+}
+
+/// 解析 RFC 3339 时间或 YYYY-MM-DD（UTC 当天 0 点）
+fn parse_time(value: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&chrono::Utc));
+    }
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("无法解析时间 {}，格式为 RFC 3339 或 YYYY-MM-DD", value))?;
+    Ok(date.and_time(chrono::NaiveTime::MIN).and_utc())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    build_info::started_at();
+    // version：输出版本和构建信息后退出
+    if std::env::args().nth(1).as_deref() == Some("version") {
+        println!("{}", serde_json::to_string_pretty(&build_info::runtime_info("data_monitor", "config.yaml"))?);
+        return Ok(());
+    }
     init_logging("data-monitor.log")?;
 
     info!("启动URL监测系统");
+    build_info::log_startup("data_monitor", "config.yaml");
 
     // 加载配置
     let config = Config::load("config.yaml")?;
     let config_arc = Arc::new(config);
 
+    let mut args: Vec<String> = std::env::args().collect();
+    // --tag <标签>：统计命令只统计检查时带该标签的数据集
+    let tag = take_option(&mut args, "--tag");
+    // --include-in-progress：统计命令包括进行中的运行已写入的记录（默认只统计已结束的运行）
+    let include_in_progress = match args.iter().position(|a| a == "--include-in-progress") {
+        Some(index) => {
+            args.remove(index);
+            true
+        }
+        None => false,
+    };
+    let range = |since, until| QueryFilter { tag: tag.clone(), include_in_progress, ..QueryFilter::range(since, until) };
+    // alert-test 子命令：向所有告警接收方和邮件发送测试通知后退出，有失败时返回非零退出码
+    if args.get(1).map(String::as_str) == Some("alert-test") {
+        return alert::alert_test(&config_arc.alerts, config_arc.email.clone()).await;
+    }
+
     db::init_duckdb(&config_arc.duckdb.path).await?;
+    // 各中心的监测任务共用同一个 DuckDB 连接写入结果
+    let duckdb = Arc::new(DuckDB::new(&config_arc.duckdb.path).await?
+        .with_success_statuses(&config_arc.monitor.success_statuses)
+        .with_query_timeouts(|group| config_arc.duckdb.query_timeout(group)));
+
+    // 统计类子命令的 JSON 输出带 data_as_of 字段（最近一次检查和最近一次运行），数组结果放到 items 中；读取失败时为 null
+    let data_as_of = if args.get(1).is_some_and(|command| STATS_COMMANDS.contains(&command.as_str())) {
+        duckdb.get_data_as_of().await
+            .inspect_err(|e| warn!("读取数据时效失败: {}", e))
+            .ok()
+    } else {
+        None
+    };
+    // data-as-of：输出最近一次检查的时间（整体和各数据中心）和最近一次运行的状态后退出
+    if args.get(1).map(String::as_str) == Some("data-as-of") {
+        println!("{}", serde_json::to_string_pretty(&duckdb.get_data_as_of().await?)?);
+        return Ok(());
+    }
+    // report --center <名称> --out <文件>：生成数据中心最近一次运行的失败链接报告（.csv 为逗号分隔，其他为 TSV）后退出
+    if args.get(1).map(String::as_str) == Some("report") && args.iter().any(|a| a == "--center") {
+        let option = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+        let usage = "用法: data_monitor report --center <名称> --out <文件>";
+        let center = option("--center").ok_or_else(|| anyhow::anyhow!(usage))?;
+        let out = option("--out").ok_or_else(|| anyhow::anyhow!(usage))?;
+        let count = generate_link_report(&duckdb, center, None, std::path::Path::new(out)).await?;
+        info!("链接报告已生成: {}（{} 条）", out, count);
+        return Ok(());
+    }
+    // report 子命令：生成周报后退出，可选参数为ISO周（如 2025-W07），默认上一周
+    if args.get(1).map(String::as_str) == Some("report") {
+        let week = match args.get(2) {
+            Some(week) => IsoWeek::parse(week)?,
+            None => IsoWeek::previous(chrono::Utc::now()),
+        };
+        let path = generate_weekly_report(&duckdb, week, &config_arc.report.output_dir).await?;
+        info!("周报已生成: {}", path.display());
+        return Ok(());
+    }
 
-    let scheduler = JobScheduler::new().await?;
-    // 启动时立即执行一次
-    if let Err(e) = execute_url_monitoring(config_arc.clone()).await {
-        error!("首次URL监测失败: {}", e);
+    // timing [天数] [final|total]：输出最近 N 天（默认 7）各数据中心检查的分阶段平均耗时后退出，
+    // 总耗时默认取最后一次尝试，total 时包含重试
+    if args.get(1).map(String::as_str) == Some("timing") {
+        let days: i64 = args.get(2).map(|d| d.parse()).transpose()?.unwrap_or(7);
+        let basis: TimeBasis = args.get(3).map(|b| b.parse()).transpose()?.unwrap_or_default();
+        let until = chrono::Utc::now();
+        let breakdown = duckdb.get_timing_breakdown(&range(until - chrono::Duration::days(days), until), basis).await?;
+        println!("{}", stats_json(&breakdown, data_as_of.as_ref())?);
+        return Ok(());
     }
-    let check_interval_days = config_arc.monitor.check_interval_days;
 
-    let cron_expression = format!("0 0 0 5/{} * *", check_interval_days);
-    // URL监测任务
-    let job = Job::new_async(&cron_expression, move |_uuid, _l| {
-        let config = config_arc.clone();
-        Box::pin(async move {
-            if let Err(e) = execute_url_monitoring(config).await {
-                error!("定时URL监测失败: {}", e);
+    // http-versions [天数]：输出最近 N 天（默认 7）各数据中心按HTTP版本统计的检查数后退出
+    if args.get(1).map(String::as_str) == Some("http-versions") {
+        let days: i64 = args.get(2).map(|d| d.parse()).transpose()?.unwrap_or(7);
+        let until = chrono::Utc::now();
+        let counts = duckdb.get_http_version_counts(&range(until - chrono::Duration::days(days), until)).await?;
+        println!("{}", stats_json(&counts, data_as_of.as_ref())?);
+        return Ok(());
+    }
+    // problematic-urls [最低失败率] [数据中心]：输出失败率不低于指定百分比（默认 50）的URL及其最近 5 次失败后退出
+    if args.get(1).map(String::as_str) == Some("problematic-urls") {
+        let min_failure_rate: f64 = args.get(2).map(|r| r.parse()).transpose()?.unwrap_or(50.0);
+        let filter = QueryFilter { center_name: args.get(3).cloned(), tag: tag.clone(), include_in_progress, ..QueryFilter::default() };
+        let urls = duckdb.get_problematic_urls(&filter, min_failure_rate, 5).await?;
+        println!("{}", stats_json(&urls, data_as_of.as_ref())?);
+        return Ok(());
+    }
+    // dashboard [天数] [输出文件]：生成最近 N 天（默认 report.dashboard_days）的概览页后退出，
+    // 输出文件默认 report.dashboard_path，未配置时为 {output_dir}/dashboard.html
+    if args.get(1).map(String::as_str) == Some("dashboard") {
+        let days: u32 = args.get(2).map(|d| d.parse()).transpose()?.unwrap_or(config_arc.report.dashboard_days);
+        let path = args.get(3).cloned()
+            .or_else(|| config_arc.report.dashboard_path.clone())
+            .unwrap_or_else(|| format!("{}/dashboard.html", config_arc.report.output_dir.trim_end_matches('/')));
+        generate_dashboard(&duckdb, days, &path).await?;
+        println!("{}", path);
+        return Ok(());
+    }
+    // url-quality [--center <名称>]：输出最近一次运行记录的无法检查的数据集URL（格式错误、不支持的协议、内网地址、
+    // 占位内容）及各数据中心按原因的统计后退出
+    if args.get(1).map(String::as_str) == Some("url-quality") {
+        let center = args.iter().position(|a| a == "--center").and_then(|i| args.get(i + 1));
+        let issues = duckdb.get_url_quality_issues(center.map(String::as_str)).await?;
+        let output = serde_json::json!({ "centers": url_quality::summarize(&issues), "issues": issues });
+        println!("{}", stats_json(&output, data_as_of.as_ref())?);
+        return Ok(());
+    }
+    // meta：输出统计命令可用的过滤值（错误类别、数据中心、时间粒度、成功的判定方式、已有数据的时间范围）后退出
+    if args.get(1).map(String::as_str) == Some("meta") {
+        println!("{}", serde_json::to_string_pretty(&meta::build(&config_arc, &duckdb).await?)?);
+        return Ok(());
+    }
+    // janitor：立即执行一次清理（过期的认领、没有结束的运行、过期文件、过大的缓存），输出清理报告后退出
+    if args.get(1).map(String::as_str) == Some("janitor") {
+        let report = janitor::run(&config_arc, &duckdb).await;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    // coverage [运行次数]：输出各数据中心最近一次运行的数据集覆盖情况和最近 N 次（默认 30）运行的趋势后退出
+    if args.get(1).map(String::as_str) == Some("coverage") {
+        let runs: usize = args.get(2).map(|n| n.parse()).transpose()?.unwrap_or(30);
+        let report = CoverageReport::from_runs(&duckdb.get_recent_runs(runs).await?);
+        println!("{}", stats_json(&report, data_as_of.as_ref())?);
+        return Ok(());
+    }
+    // replay-spill <溢出文件>：把写库失败时写入溢出文件的检查结果重新写入 DuckDB 后退出，可重复执行
+    if args.get(1).map(String::as_str) == Some("replay-spill") {
+        let path = args.get(2).ok_or_else(|| anyhow::anyhow!("用法: data_monitor replay-spill <溢出文件>"))?;
+        let inserted = spill::replay(&duckdb, std::path::Path::new(path)).await?;
+        println!("写入 {} 条记录", inserted);
+        return Ok(());
+    }
+    // method-cache [clear [主机]]：输出 check_method 为 auto 时记住的拒绝 HEAD 的主机后退出，
+    // clear 清除指定主机（未指定时全部），这些主机下次运行重新尝试 HEAD
+    if args.get(1).map(String::as_str) == Some("method-cache") {
+        if args.get(2).map(String::as_str) == Some("clear") {
+            let cleared = duckdb.clear_method_cache(args.get(3).map(String::as_str)).await?;
+            println!("已清除 {} 个主机", cleared);
+        } else {
+            println!("{}", serde_json::to_string_pretty(&duckdb.get_method_cache().await?)?);
+        }
+        return Ok(());
+    }
+    // slow-hosts [learn]：输出配置的和自动识别的慢主机及其超时后退出，learn 先按 slow_host_learning 重新识别
+    if args.get(1).map(String::as_str) == Some("slow-hosts") {
+        if args.get(2).map(String::as_str) == Some("learn") && slow_hosts::learn(&duckdb, &config_arc.monitor).await?.is_none() {
+            anyhow::bail!("未配置 monitor.slow_host_learning");
+        }
+        let output = serde_json::json!({
+            "configured": config_arc.monitor.slow_hosts,
+            "learned": duckdb.get_learned_slow_hosts().await?,
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+    // hosts [天数] [failures|datasets]：输出最近 N 天（默认 7）按URL主机汇总的检查结果后退出，
+    // 默认按最近一次检查失败的数据集数排序
+    if args.get(1).map(String::as_str) == Some("hosts") {
+        let days: i64 = args.get(2).map(|d| d.parse()).transpose()?.unwrap_or(7);
+        let sort: HostSort = args.get(3).map(|s| s.parse()).transpose()?.unwrap_or_default();
+        let until = chrono::Utc::now();
+        let hosts = duckdb.get_host_stats(&range(until - chrono::Duration::days(days), until), sort, 100).await?;
+        println!("{}", stats_json(&hosts, data_as_of.as_ref())?);
+        return Ok(());
+    }
+    // error-trends [天数] [hour|day|week] [--center <数据中心>] [--local-issue <true|false>]：
+    // 输出最近 N 天（默认 28）各错误类别按时间段（默认 day）的检查数后退出
+    if args.get(1).map(String::as_str) == Some("error-trends") {
+        let option = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+        let days: i64 = args.get(2).filter(|a| !a.starts_with("--")).map(|d| d.parse()).transpose()?.unwrap_or(28);
+        let granularity: TrendGranularity = args.get(3).filter(|a| !a.starts_with("--")).map(|g| g.parse()).transpose()?.unwrap_or_default();
+        let until = chrono::Utc::now();
+        let filter = QueryFilter {
+            center_name: option("--center").cloned(),
+            local_issue: option("--local-issue").map(|v| v.parse()).transpose()?,
+            ..range(until - chrono::Duration::days(days), until)
+        };
+        println!("{}", stats_json(&duckdb.get_error_trends(&filter, granularity).await?, data_as_of.as_ref())?);
+        return Ok(());
+    }
+    // center-trends [天数] [--center <数据中心,...>] [--max-points <点数>]：输出最近 N 天（默认 90，含今天，UTC）各数据中心
+    // 每天的检查数和成功率后退出，没有检查的日期补 0；天数超过点数上限（默认 90）时把相邻的几天合并为一个点
+    if args.get(1).map(String::as_str) == Some("center-trends") {
+        let option = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+        let days: usize = args.get(2).filter(|a| !a.starts_with("--")).map(|d| d.parse()).transpose()?.unwrap_or(90).max(1);
+        let max_points: usize = option("--max-points").map(|n| n.parse()).transpose()?.unwrap_or(90);
+        let selected: Option<Vec<String>> = option("--center").map(|c| c.split(',').map(str::to_string).collect());
+        let until = chrono::Utc::now();
+        let first_day = until.date_naive() - chrono::Duration::days(days as i64 - 1);
+        let mut rows = duckdb.get_center_daily_counts(&range(first_day.and_time(chrono::NaiveTime::MIN).and_utc(), until)).await?;
+        let centers = match selected {
+            Some(selected) => {
+                rows.retain(|(center, ..)| selected.contains(center));
+                selected
             }
-        })
-    })?;
+            None => config_arc.centers.iter().filter(|c| c.enabled).map(|c| c.name.clone()).collect(),
+        };
+        println!("{}", stats_json(&center_trends(first_day, days, max_points, &centers, rows), data_as_of.as_ref())?);
+        return Ok(());
+    }
+    // shared-urls [天数]：输出最近 N 天（默认 30）被多个数据中心检查过的URL、各数据中心的检查数和统计归属后退出
+    if args.get(1).map(String::as_str) == Some("shared-urls") {
+        let days: i64 = args.get(2).map(|d| d.parse()).transpose()?.unwrap_or(30);
+        let until = chrono::Utc::now();
+        println!("{}", stats_json(&duckdb.get_shared_urls(&range(until - chrono::Duration::days(days), until)).await?, data_as_of.as_ref())?);
+        return Ok(());
+    }
+    // storage [运行数]：输出最近 N 次运行（默认 50）开始和结束时的数据库大小及增长后退出
+    if args.get(1).map(String::as_str) == Some("storage") {
+        let runs: usize = args.get(2).map(|n| n.parse()).transpose()?.unwrap_or(50);
+        println!("{}", stats_json(&duckdb.get_storage_series(runs).await?, data_as_of.as_ref())?);
+        return Ok(());
+    }
+    // regressions [运行数]：输出最近 N 次运行（默认 50）中检测到的数据中心响应时间变慢后退出
+    if args.get(1).map(String::as_str) == Some("regressions") {
+        let runs: usize = args.get(2).map(|n| n.parse()).transpose()?.unwrap_or(50);
+        println!("{}", stats_json(&regression::recorded(&duckdb.get_recent_runs(runs).await?), data_as_of.as_ref())?);
+        return Ok(());
+    }
+    // hourly [小时数]：输出最近 N 小时（默认 24）每小时的检查数和成功率后退出
+    if args.get(1).map(String::as_str) == Some("hourly") {
+        let hours: i64 = args.get(2).map(|h| h.parse()).transpose()?.unwrap_or(24);
+        let until = chrono::Utc::now();
+        let stats = duckdb.get_hourly_stats(&range(until - chrono::Duration::hours(hours), until)).await?;
+        println!("{}", stats_json(&stats, data_as_of.as_ref())?);
+        return Ok(());
+    }
+    // status-codes [天数] [class|code]：输出最近 N 天（默认 7）按状态码类别（默认）或具体状态码统计的检查数后退出
+    if args.get(1).map(String::as_str) == Some("status-codes") {
+        let days: i64 = args.get(2).map(|d| d.parse()).transpose()?.unwrap_or(7);
+        let group_by: StatusGrouping = args.get(3).map(|g| g.parse()).transpose()?.unwrap_or_default();
+        let until = chrono::Utc::now();
+        let stats = duckdb.get_status_code_stats(&range(until - chrono::Duration::days(days), until), group_by).await?;
+        println!("{}", stats_json(&stats, data_as_of.as_ref())?);
+        return Ok(());
+    }
+    // content-changes [天数]：输出最近 N 天（默认 7）响应体指纹发生变化的URL后退出
+    if args.get(1).map(String::as_str) == Some("content-changes") {
+        let days: i64 = args.get(2).map(|d| d.parse()).transpose()?.unwrap_or(7);
+        let until = chrono::Utc::now();
+        let changes = duckdb.get_content_changes(&range(until - chrono::Duration::days(days), until)).await?;
+        println!("{}", stats_json(&changes, data_as_of.as_ref())?);
+        return Ok(());
+    }
+    // compact [--dry-run]：按 max_error_msg_bytes、max_error_detail_bytes、max_header_bytes 截断已有记录中过长的文本，
+    // 隐藏响应头中的凭证，输出节省的字节数后退出；--dry-run 只统计不写回
+    if args.get(1).map(String::as_str) == Some("compact") {
+        let limits = TextLimits::from_config(&config_arc.monitor);
+        let report = sanitize::compact(&duckdb, limits, args.iter().any(|a| a == "--dry-run")).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    // reclassify --from <时间> [--to <时间>] [--dry-run]：按当前规则重新计算 [from, to)（to 默认现在）内
+    // 检查记录的 error_category 和 is_likely_local_issue，输出各分类变化的行数后退出；
+    // 时间为 RFC 3339 或 YYYY-MM-DD（UTC 0 点），--dry-run 只输出将会变化的记录，不写回
+    if args.get(1).map(String::as_str) == Some("reclassify") {
+        let option = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+        let from = option("--from").ok_or_else(|| anyhow::anyhow!("用法: data_monitor reclassify --from <时间> [--to <时间>] [--dry-run]"))?;
+        let until = option("--to").map(|t| parse_time(t)).transpose()?.unwrap_or_else(chrono::Utc::now);
+        // 进行中的运行已写入的记录、归属其他数据中心的共用URL的记录同样按当前规则重新分类
+        let filter = range(parse_time(from)?, until).include_in_progress().ignore_attribution();
+        let report = reclassify(&duckdb, &filter, args.iter().any(|a| a == "--dry-run")).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    // import --file <CSV> --mapping <映射YAML> [--skip-duplicates]：导入其他工具的历史检查记录后退出
+    if args.get(1).map(String::as_str) == Some("import") {
+        let option = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+        let usage = "用法: data_monitor import --file <CSV> --mapping <映射YAML> [--skip-duplicates]";
+        let file = option("--file").ok_or_else(|| anyhow::anyhow!(usage))?;
+        let mapping = ImportMapping::load(option("--mapping").ok_or_else(|| anyhow::anyhow!(usage))?)?;
+        let skip_duplicates = args.iter().any(|a| a == "--skip-duplicates");
+        let report = import_csv(&duckdb, file, &mapping, skip_duplicates).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    // export [YYYY-MM-DD]：导出某天（UTC，默认前一天）的检查记录为 Parquet，配置了 export.s3 时上传
+    if args.get(1).map(String::as_str) == Some("export") {
+        let date = match args.get(2) {
+            Some(date) => chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")?,
+            None => chrono::Utc::now().date_naive() - chrono::Duration::days(1),
+        };
+        let report = export_day(&duckdb, &config_arc.export, date).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    // cmdb-push：把各启用数据中心最近一次运行的结果推送到 CMDB，输出推送结果后退出
+    if args.get(1).map(String::as_str) == Some("cmdb-push") {
+        let cmdb = config_arc.integrations.cmdb.as_ref().ok_or_else(|| anyhow::anyhow!("未配置 integrations.cmdb"))?;
+        let centers: Vec<&str> = config_arc.centers.iter().filter(|c| c.enabled).map(|c| c.name.as_str()).collect();
+        let deliveries = CmdbPusher::new(cmdb).push_latest(&duckdb, &centers).await?;
+        println!("{}", serde_json::to_string_pretty(&deliveries)?);
+        return Ok(());
+    }
+    // resume [run_id]：不带参数时输出可以续跑的未结束运行，带 run_id 时续跑该运行（跳过已完成的URL），
+    // 输出合并后的汇总后退出
+    if args.get(1).map(String::as_str) == Some("resume") {
+        let monitor = DataMonitor::new(config_arc.clone(), duckdb.clone());
+        match args.get(2) {
+            Some(run_id) => println!("{}", serde_json::to_string_pretty(&monitor.resume_run(run_id).await?)?),
+            None => println!("{}", serde_json::to_string_pretty(&monitor.close_stale_runs().await?)?),
+        }
+        return Ok(());
+    }
+    // run <run_id>：输出单次运行的汇总和运行时的配置快照后退出
+    if args.get(1).map(String::as_str) == Some("run") {
+        let run_id = args.get(2).ok_or_else(|| anyhow::anyhow!("用法: data_monitor run <run_id>"))?;
+        match duckdb.get_run(run_id).await? {
+            Some(run) => println!("{}", serde_json::to_string_pretty(&run)?),
+            None => anyhow::bail!("没有找到运行 {}", run_id),
+        }
+        return Ok(());
+    }
+    // tag <@id 或 casdc_id> [+标签 | -标签 ...]：添加或移除数据集的标签，输出修改后的标签后退出
+    if args.get(1).map(String::as_str) == Some("tag") {
+        let usage = "用法: data_monitor tag <@id 或 casdc_id> [+标签 | -标签 ...]";
+        let id = args.get(2).ok_or_else(|| anyhow::anyhow!(usage))?;
+        let mut add = Vec::new();
+        let mut remove = Vec::new();
+        for arg in &args[3..] {
+            match (arg.strip_prefix('+'), arg.strip_prefix('-')) {
+                (Some(tag), _) if !tag.is_empty() && !tag.contains(',') => add.push(tag.to_string()),
+                (_, Some(tag)) if !tag.is_empty() => remove.push(tag.to_string()),
+                _ => anyhow::bail!("{}（标签不能为空或包含逗号）", usage),
+            }
+        }
+        let mongo = MongoDB::new(&config_arc.mongodb).await?;
+        let centers: Vec<(&str, String)> = config_arc.centers.iter()
+            .map(|c| (c.name.as_str(), config_arc.collection_name(&c.name)))
+            .collect();
+        let (center_name, dataset) = mongo.find_dataset(&centers, id).await?
+            .ok_or_else(|| anyhow::anyhow!("没有找到数据集 {}", id))?;
+        let tags = mongo.update_dataset_tags(&config_arc.collection_name(&center_name), &dataset.raw_id, &add, &remove).await?
+            .unwrap_or_default();
+        let output = serde_json::json!({ "center_name": center_name, "raw_id": dataset.raw_id, "tags": tags });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+    // dataset <@id 或 casdc_id> [条数]：输出数据集最近的检查结果（默认 20 条）后退出
+    if args.get(1).map(String::as_str) == Some("dataset") {
+        let id = args.get(2).ok_or_else(|| anyhow::anyhow!("用法: data_monitor dataset <@id 或 casdc_id> [条数]"))?;
+        let limit: usize = args.get(3).map(|n| n.parse()).transpose()?.unwrap_or(20);
+        println!("{}", stats_json(&lookup_dataset(&config_arc, &duckdb, id, limit).await?, data_as_of.as_ref())?);
+        return Ok(());
+    }
+    // history <URL> [条数]：输出URL最近的检查结果（默认 20 条）后退出
+    if args.get(1).map(String::as_str) == Some("history") {
+        let url = args.get(2).ok_or_else(|| anyhow::anyhow!("用法: data_monitor history <URL> [条数]"))?;
+        let limit: usize = args.get(3).map(|n| n.parse()).transpose()?.unwrap_or(20);
+        println!("{}", stats_json(&duckdb.get_url_history(url, limit).await?, data_as_of.as_ref())?);
+        return Ok(());
+    }
+
+    let shutdown = Shutdown::new();
+    let ctx = Arc::new(MonitorContext {
+        config: config_arc.clone(),
+        monitor: DataMonitor::new(config_arc.clone(), duckdb.clone()).with_cancel_token(shutdown.cancel_token()),
+        duckdb,
+        lock: if config_arc.monitor.distributed_lock {
+            Some(Arc::new(MongoDB::new(&config_arc.mongodb).await?))
+        } else {
+            None
+        },
+        alerter: Alerter::new(&config_arc.alerts),
+        email: EmailReporter::new(config_arc.email.clone()),
+        cmdb: config_arc.integrations.cmdb.as_ref().map(CmdbPusher::new),
+        shutdown: shutdown.clone(),
+    });
+    // check-tag <标签>：立即检查所有数据中心中带该标签的数据集，评估告警并输出运行汇总后退出
+    if args.get(1).map(String::as_str) == Some("check-tag") {
+        let tag = args.get(2).ok_or_else(|| anyhow::anyhow!("用法: data_monitor check-tag <标签>"))?;
+        let summary = ctx.monitor.check_tagged(tag).await?;
+        if let Err(e) = ctx.alerter.on_monitor_run(&summary, &ctx.duckdb).await {
+            warn!("评估监测告警失败: {}", e);
+        }
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+    // check-file --file <文件> [--center <名称>] [--save]：检查文件中的URL（每行一个，忽略空行和 # 注释），
+    // 数据中心默认为 ad-hoc；输出结果表后退出，--save 时结果写入 DuckDB 并记为一次运行
+    if args.get(1).map(String::as_str) == Some("check-file") {
+        let option = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+        let file = option("--file").ok_or_else(|| anyhow::anyhow!("用法: data_monitor check-file --file <文件> [--center <名称>] [--save]"))?;
+        let center = option("--center").map_or("ad-hoc", String::as_str);
+        let content = std::fs::read_to_string(file).with_context(|| format!("读取URL文件失败: {}", file))?;
+        let list = check_file::parse(&content, |scheme| ctx.monitor.supports_scheme(scheme));
+        for line in &list.rejected {
+            warn!("第 {} 行无法检查（{}）: {}", line.line, line.reason.description(), line.content);
+        }
+        let (summary, results) = ctx.monitor.check_urls(center, list.urls, args.iter().any(|a| a == "--save")).await?;
+        print!("{}", check_file::render_table(&results, &list.rejected, &config_arc.monitor.success_statuses));
+        if summary.cancelled {
+            warn!("检查已取消，完成 {} 个URL", results.len());
+        }
+        return Ok(());
+    }
+    // 配置已加载、数据库可用，通知 systemd 就绪；首次运行期间由看门狗定时器持续喂狗
+    systemd::ready();
+    let _watchdog = systemd::spawn_watchdog();
+
+    let mut scheduler = JobScheduler::new().await?;
+    let tz = config_arc.monitor.schedule_tz()?;
+    let grace = chrono::Duration::hours(config_arc.monitor.catch_up_grace_hours as i64);
+
+    // 异常退出前未完成的运行在启动时续跑，超过 resume_max_age_hours 的标记为 aborted
+    let unfinished = ctx.monitor.close_stale_runs().await?;
+
+    // URL监测任务：每个数据中心一个任务，各自的调度周期
+    let mut job_ids = Vec::new();
+    let mut catch_ups = Vec::new();
+    for schedule in check_schedules(&config_arc) {
+        let job_name = format!("data_monitor-{}", schedule.center.name);
+        // 定时触发与手动触发共用同一个守卫，避免重叠运行
+        let guard = Arc::new(JobGuard::new(&job_name, config_arc.monitor.queue_overlapping_runs));
+        let state = Arc::new(JobState::new(&config_arc.monitor.state_dir, &job_name));
+        // 连续失败后退避，跳过中间的触发
+        let supervisor = Arc::new(Supervisor::from_config(&job_name, &config_arc.monitor));
+        let center = schedule.center;
+        let heartbeat = Arc::new(Heartbeat::from_config(&config_arc.heartbeat, HeartbeatJob::Monitor, &center));
+
+        // 仅在错过调度（超期）或有未完成的运行时启动即补跑，避免每次重启都全量运行
+        let interrupted = unfinished.iter().any(|run| run.centers == [center.name.as_str()]);
+        if interrupted {
+            info!("数据中心 {} 有未完成的运行，启动后续跑", center.name);
+        }
+        if interrupted || state.is_overdue(schedule.interval, grace) {
+            catch_ups.push((center.clone(), guard.clone(), state.clone(), supervisor.clone(), heartbeat.clone()));
+        }
+
+        let ctx = ctx.clone();
+        let name = job_name.clone();
+        let job = Job::new_async_tz(&schedule.cron, tz, move |uuid, mut l| {
+            let ctx = ctx.clone();
+            let center = center.clone();
+            let guard = guard.clone();
+            let supervisor = supervisor.clone();
+            let state = state.clone();
+            let heartbeat = heartbeat.clone();
+            let name = name.clone();
+            Box::pin(async move {
+                // 仅定时触发加随机延迟，且不超过下一次触发时间
+                let next_fire = l.next_tick_for_job(uuid).await.ok().flatten();
+                state.set_jitter(sleep_jitter(&name, ctx.config.monitor.schedule_jitter_minutes, next_fire).await);
+                guard.run(|| async {
+                    if let Some(Err(e)) = supervisor.run(&ctx.alerter, execute_url_monitoring(ctx.clone(), center.clone(), state.clone(), heartbeat.clone())).await {
+                        error!("定时URL监测失败: {}", e);
+                    }
+                }).await;
+            })
+        })?;
+        job_ids.push((job_name, scheduler.add(job).await?));
+    }
+
+    // 按标签单独检查重点数据集
+    for schedule in &config_arc.monitor.tag_schedules {
+        let job_name = format!("data_monitor-tag-{}", schedule.tag);
+        let guard = Arc::new(JobGuard::new(&job_name, config_arc.monitor.queue_overlapping_runs));
+        let ctx = ctx.clone();
+        let tag = schedule.tag.clone();
+        let job = Job::new_async_tz(&schedule.cron, tz, move |_uuid, _l| {
+            let ctx = ctx.clone();
+            let guard = guard.clone();
+            let tag = tag.clone();
+            Box::pin(async move {
+                guard.run(|| async {
+                    if let Err(e) = execute_tag_monitoring(ctx.clone(), tag.clone()).await {
+                        error!("定时标签监测失败: {}", e);
+                    }
+                }).await;
+            })
+        })?;
+        job_ids.push((job_name, scheduler.add(job).await?));
+    }
+
+    // 定时生成上一周的周报
+    if let Some(cron) = &config_arc.report.weekly_cron {
+        let ctx = ctx.clone();
+        let job = Job::new_async_tz(cron, tz, move |_uuid, _l| {
+            let ctx = ctx.clone();
+            Box::pin(async move {
+                let week = IsoWeek::previous(chrono::Utc::now());
+                match generate_weekly_report(&ctx.duckdb, week, &ctx.config.report.output_dir).await {
+                    Ok(path) => info!("周报已生成: {}", path.display()),
+                    Err(e) => error!("生成周报失败: {}", e),
+                }
+            })
+        })?;
+        job_ids.push(("weekly_report".to_string(), scheduler.add(job).await?));
+    }
+
+    // 定时导出前一天的检查记录
+    if let Some(cron) = &config_arc.export.cron {
+        let ctx = ctx.clone();
+        let job = Job::new_async_tz(cron, tz, move |_uuid, _l| {
+            let ctx = ctx.clone();
+            Box::pin(async move {
+                export_and_alert(&ctx, chrono::Utc::now().date_naive() - chrono::Duration::days(1)).await;
+            })
+        })?;
+        job_ids.push(("export".to_string(), scheduler.add(job).await?));
+    }
+
+    // 定时清理过期的认领、没有结束的运行、过期文件和过大的缓存
+    if let Some(cron) = &config_arc.janitor.cron {
+        let ctx = ctx.clone();
+        let guard = Arc::new(JobGuard::new("janitor", false));
+        let job = Job::new_async_tz(cron, tz, move |_uuid, _l| {
+            let ctx = ctx.clone();
+            let guard = guard.clone();
+            Box::pin(async move {
+                guard.run(|| async {
+                    janitor::run(&ctx.config, &ctx.duckdb).await;
+                }).await;
+            })
+        })?;
+        job_ids.push(("janitor".to_string(), scheduler.add(job).await?));
+    }
+
+    // 定时推送各数据中心最近一次运行的结果到 CMDB
+    if let Some(cron) = config_arc.integrations.cmdb.as_ref().and_then(|c| c.cron.as_ref()) {
+        let ctx = ctx.clone();
+        let job = Job::new_async_tz(cron, tz, move |_uuid, _l| {
+            let ctx = ctx.clone();
+            Box::pin(async move {
+                let Some(cmdb) = &ctx.cmdb else {
+                    return;
+                };
+                let centers: Vec<&str> = ctx.config.centers.iter().filter(|c| c.enabled).map(|c| c.name.as_str()).collect();
+                if let Err(e) = cmdb.push_latest(&ctx.duckdb, &centers).await {
+                    error!("定时推送 CMDB 失败: {}", e);
+                }
+            })
+        })?;
+        job_ids.push(("cmdb_push".to_string(), scheduler.add(job).await?));
+    }
 
-    scheduler.add(job).await?;
     scheduler.start().await?;
+    for (job_name, job_id) in job_ids {
+        log_next_fire(&job_name, scheduler.next_tick_for_job(job_id).await?, tz);
+    }
 
-    // 保持程序运行
-    loop {
-        tokio::time::sleep(Duration::from_secs(60)).await;
+    // 调度器启动后在后台补跑，补跑期间其他任务照常按调度触发，也能及时响应退出信号
+    for (center, guard, state, supervisor, heartbeat) in catch_ups {
+        let ctx = ctx.clone();
+        let running = shutdown.running();
+        tokio::spawn(async move {
+            let _running = running;
+            guard.run(|| async {
+                if let Some(Err(e)) = supervisor.run(&ctx.alerter, execute_url_monitoring(ctx.clone(), center.clone(), state.clone(), heartbeat.clone())).await {
+                    error!("补跑URL监测失败: {}", e);
+                }
+            }).await;
+        });
     }
+
+    // 保持程序运行，直到收到退出信号
+    systemd::shutdown_signal().await;
+    info!("收到退出信号，停止调度");
+    systemd::stopping();
+    scheduler.shutdown().await?;
+    // 进行中的请求最多再等一个请求超时，已完成的结果写库后退出
+    shutdown.cancel_and_wait(Duration::from_secs(config_arc.monitor.http_timeout_secs + 30)).await;
+    Ok(())
 }
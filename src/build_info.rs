@@ -0,0 +1,156 @@
+//! 运行中程序的版本、构建信息和运行时长，构建信息由 build.rs 在编译时写入
+
+use crate::config::MonitorConfig;
+use crate::models::RunEnvironment;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{info, warn};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_COMMIT: &str = env!("BUILD_GIT_COMMIT");
+pub const RUSTC_VERSION: &str = env!("BUILD_RUSTC_VERSION");
+const BUILD_TIMESTAMP: &str = env!("BUILD_TIMESTAMP");
+
+static STARTED_AT: OnceLock<DateTime<Utc>> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeInfo {
+    pub binary: String,
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    pub build_time: Option<DateTime<Utc>>,
+    pub rustc_version: &'static str,
+    pub started_at: DateTime<Utc>,
+    pub uptime_secs: i64,
+    pub config_path: String,
+    /// 配置文件内容的 SHA-256，读取失败时为 None
+    pub config_hash: Option<String>,
+}
+
+pub fn build_time() -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(BUILD_TIMESTAMP.parse().ok()?, 0)
+}
+
+/// 进程启动时间，第一次调用时记录，应在 main 开始时调用
+pub fn started_at() -> DateTime<Utc> {
+    *STARTED_AT.get_or_init(Utc::now)
+}
+
+pub fn runtime_info(binary: &str, config_path: &str) -> RuntimeInfo {
+    use sha2::{Digest, Sha256};
+    let started_at = started_at();
+    RuntimeInfo {
+        binary: binary.to_string(),
+        version: VERSION,
+        git_commit: GIT_COMMIT,
+        build_time: build_time(),
+        rustc_version: RUSTC_VERSION,
+        started_at,
+        uptime_secs: (Utc::now() - started_at).num_seconds(),
+        config_path: std::fs::canonicalize(config_path)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| config_path.to_string()),
+        config_hash: std::fs::read(config_path).ok()
+            .map(|content| Sha256::digest(&content).iter().map(|b| format!("{:02x}", b)).collect()),
+    }
+}
+
+/// 启动时记录一次版本和构建信息
+pub fn log_startup(binary: &str, config_path: &str) {
+    let info = runtime_info(binary, config_path);
+    info!(
+        "{} {} (commit {}，构建于 {}，{})，配置 {}（sha256 {}）",
+        info.binary,
+        info.version,
+        info.git_commit,
+        info.build_time.map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string()).unwrap_or_else(|| "-".to_string()),
+        info.rustc_version,
+        info.config_path,
+        info.config_hash.as_deref().unwrap_or("-"),
+    );
+}
+
+/// 监测运行的环境。出口 IP 优先取配置的 egress_ip，否则查询 egress_ip_url，查询失败时记为 None
+pub async fn run_environment(config: &MonitorConfig) -> RunEnvironment {
+    let egress_ip = match (&config.egress_ip, &config.egress_ip_url) {
+        (Some(ip), _) => Some(ip.clone()),
+        (None, Some(url)) => match query_egress_ip(url).await {
+            Ok(ip) => Some(ip),
+            Err(e) => {
+                warn!("查询出口 IP 失败: {}: {:#}", url, e);
+                None
+            }
+        },
+        (None, None) => None,
+    };
+    RunEnvironment {
+        egress_ip,
+        hostname: hostname::get().ok().map(|h| h.to_string_lossy().into_owned()),
+        version: VERSION.to_string(),
+        git_commit: GIT_COMMIT.to_string(),
+    }
+}
+
+async fn query_egress_ip(url: &str) -> anyhow::Result<String> {
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(5)).build()?;
+    let body = client.get(url).send().await?.error_for_status()?.text().await?;
+    let ip = body.trim();
+    ip.parse::<std::net::IpAddr>()
+        .map_err(|_| anyhow::anyhow!("响应不是 IP 地址: {}", ip.chars().take(100).collect::<String>()))?;
+    Ok(ip.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{response, stub_server};
+
+    /// 出口 IP 服务，对任何请求返回 `status` 和 `body`
+    async fn ip_service(status: &'static str, body: &'static str) -> String {
+        format!("{}/ip", stub_server(move |_| response(status, &[], body)).await.base)
+    }
+
+    fn config(egress_ip: Option<&str>, egress_ip_url: Option<String>) -> MonitorConfig {
+        let mut config: MonitorConfig = serde_yaml::from_str(
+            "{ fetch_interval_days: 30, check_interval_days: 7, http_timeout_secs: 5, max_concurrent: 4 }"
+        ).unwrap();
+        config.egress_ip = egress_ip.map(str::to_string);
+        config.egress_ip_url = egress_ip_url;
+        config
+    }
+
+    #[tokio::test]
+    async fn egress_ip_is_queried_from_the_configured_service() {
+        let url = ip_service("200 OK", " 203.0.113.7\n").await;
+        let environment = run_environment(&config(None, Some(url))).await;
+        assert_eq!(environment.egress_ip.as_deref(), Some("203.0.113.7"));
+        assert_eq!(environment.version, VERSION);
+        assert_eq!(environment.git_commit, GIT_COMMIT);
+
+        let url = ip_service("200 OK", "2001:db8::1").await;
+        assert_eq!(run_environment(&config(None, Some(url))).await.egress_ip.as_deref(), Some("2001:db8::1"));
+    }
+
+    #[tokio::test]
+    async fn static_egress_ip_skips_the_query() {
+        // 服务不可达，配置了静态值时不查询
+        let environment = run_environment(&config(Some("198.51.100.1"), Some("http://127.0.0.1:9/ip".to_string()))).await;
+        assert_eq!(environment.egress_ip.as_deref(), Some("198.51.100.1"));
+        assert_eq!(run_environment(&config(None, None)).await.egress_ip, None);
+    }
+
+    #[tokio::test]
+    async fn failed_egress_ip_queries_leave_it_empty() {
+        let unreachable = "http://127.0.0.1:9/ip".to_string();
+        assert_eq!(run_environment(&config(None, Some(unreachable))).await.egress_ip, None);
+        let error = ip_service("503 Service Unavailable", "203.0.113.7").await;
+        assert_eq!(run_environment(&config(None, Some(error))).await.egress_ip, None);
+        let not_ip = ip_service("200 OK", "<html>rate limited</html>").await;
+        let environment = run_environment(&config(None, Some(not_ip))).await;
+        assert_eq!(environment.egress_ip, None);
+        // 其余环境信息照常记录
+        assert_eq!(environment.version, VERSION);
+    }
+}
@@ -0,0 +1,118 @@
+//! 检查文本文件中的URL列表（每行一个），用于数据中心另行提供、不在 MongoDB 中的URL
+
+use crate::config::SuccessStatuses;
+use crate::models::{MonitorRecord, UrlIssue};
+use crate::url_quality;
+use serde::Serialize;
+use std::fmt::Write;
+
+/// 无法检查的行
+#[derive(Debug, Clone, Serialize)]
+pub struct RejectedLine {
+    /// 从 1 开始的行号
+    pub line: usize,
+    pub content: String,
+    pub reason: UrlIssue,
+}
+
+#[derive(Debug, Default)]
+pub struct UrlList {
+    pub urls: Vec<String>,
+    pub rejected: Vec<RejectedLine>,
+}
+
+/// 逐行解析，忽略空行和 `#` 开头的注释，去掉首尾空白；同一URL只保留第一次出现。
+/// `supported` 判断协议是否有对应的检查器
+pub fn parse(content: &str, supported: impl Fn(&str) -> bool) -> UrlList {
+    let mut list = UrlList::default();
+    for (index, line) in content.lines().enumerate() {
+        let url = line.trim();
+        if url.is_empty() || url.starts_with('#') {
+            continue;
+        }
+        match url_quality::classify(url, &supported) {
+            Some(reason) => list.rejected.push(RejectedLine { line: index + 1, content: url.to_string(), reason }),
+            None if !list.urls.iter().any(|u| u == url) => list.urls.push(url.to_string()),
+            None => {}
+        }
+    }
+    list
+}
+
+/// 输出到终端的结果表，失败的URL在前，之后是无法检查的行
+pub fn render_table(results: &[MonitorRecord], rejected: &[RejectedLine], success: &SuccessStatuses) -> String {
+    let mut rows: Vec<(bool, &MonitorRecord)> = results.iter()
+        .map(|record| (success.is_success(record.status_code), record))
+        .collect();
+    rows.sort_by(|(a_ok, a), (b_ok, b)| a_ok.cmp(b_ok).then_with(|| a.url.cmp(&b.url)));
+    let mut out = String::new();
+    let _ = writeln!(out, "{:<6} {:<24} {:>9}  URL", "结果", "状态", "耗时(ms)");
+    for (ok, record) in &rows {
+        let status = match (record.status_code, &record.error_category) {
+            (Some(code), _) => code.to_string(),
+            (None, Some(category)) => category.clone(),
+            (None, None) => "-".to_string(),
+        };
+        let elapsed = record.response_time_ms.map(|ms| ms.to_string()).unwrap_or_else(|| "-".to_string());
+        let _ = writeln!(out, "{:<6} {:<24} {:>9}  {}", if *ok { "OK" } else { "FAIL" }, status, elapsed, record.url);
+    }
+    for line in rejected {
+        let _ = writeln!(out, "{:<6} {:<24} {:>9}  第 {} 行: {}", "SKIP", line.reason.as_str(), "-", line.line, line.content);
+    }
+    let succeeded = rows.iter().filter(|(ok, _)| *ok).count();
+    let _ = writeln!(out, "\n共 {} 个URL: 成功 {}，失败 {}，无法检查的行 {}", rows.len(), succeeded, rows.len() - succeeded, rejected.len());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_skips_comments_and_duplicates() {
+        let content = "\
+# 数据中心另行提供的URL
+https://data.casdc.cn/1
+
+  https://data.casdc.cn/2\t
+https://data.casdc.cn/1
+ftp://ftp.casdc.cn/file
+gopher://old.casdc.cn/
+http://localhost/x
+TODO
+not a url
+https://www.example.org/x
+";
+        let list = parse(content, |scheme| matches!(scheme, "http" | "https" | "ftp"));
+        assert_eq!(list.urls, ["https://data.casdc.cn/1", "https://data.casdc.cn/2", "ftp://ftp.casdc.cn/file"]);
+        let rejected: Vec<(usize, &str, UrlIssue)> = list.rejected.iter().map(|r| (r.line, r.content.as_str(), r.reason)).collect();
+        assert_eq!(rejected, [
+            (7, "gopher://old.casdc.cn/", UrlIssue::UnsupportedScheme),
+            (8, "http://localhost/x", UrlIssue::PrivateAddress),
+            (9, "TODO", UrlIssue::Placeholder),
+            (10, "not a url", UrlIssue::Unparseable),
+            (11, "https://www.example.org/x", UrlIssue::Placeholder),
+        ]);
+    }
+
+    fn record(url: &str, status_code: Option<u16>, error_category: Option<&str>, response_time_ms: Option<u64>) -> MonitorRecord {
+        MonitorRecord { url: url.to_string(), response_time_ms, ..crate::test_support::record(url, "A", status_code, error_category) }
+    }
+
+    #[test]
+    fn table_lists_failures_first() {
+        let results = [
+            record("https://a.example.org/ok", Some(200), None, Some(12)),
+            record("https://b.example.org/timeout", None, Some("TIMEOUT_ERROR"), None),
+            record("https://a.example.org/missing", Some(404), Some("CLIENT_ERROR"), Some(30)),
+        ];
+        let rejected = [RejectedLine { line: 3, content: "TODO".to_string(), reason: UrlIssue::Placeholder }];
+        let table = render_table(&results, &rejected, &SuccessStatuses::default());
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[1].starts_with("FAIL") && lines[1].contains(" 404 ") && lines[1].ends_with("  https://a.example.org/missing"));
+        assert!(lines[2].starts_with("FAIL") && lines[2].contains("TIMEOUT_ERROR") && lines[2].ends_with("  https://b.example.org/timeout"));
+        assert!(lines[3].starts_with("OK") && lines[3].contains(" 12  "));
+        assert!(lines[4].starts_with("SKIP") && lines[4].contains("placeholder") && lines[4].ends_with("第 3 行: TODO"));
+        assert_eq!(*lines.last().unwrap(), "共 3 个URL: 成功 1，失败 2，无法检查的行 1");
+    }
+}
@@ -0,0 +1,127 @@
+use crate::models::MethodCacheEntry;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use tracing::info;
+
+/// 单次监测运行内 check_method 为 auto 的主机用 HEAD 还是 GET
+///
+/// 运行开始时从 head_method_cache 加载拒绝 HEAD 的主机，这些主机直接用 GET；
+/// 距上次尝试 HEAD 已满 `retest_runs` 次运行的主机本次重新尝试 HEAD。
+/// 运行中记录每个主机 HEAD 的结果，运行结束后写回。
+pub struct HeadLearning {
+    /// 本次运行直接用 GET 的主机
+    get_hosts: HashSet<String>,
+    outcomes: Mutex<Outcomes>,
+}
+
+#[derive(Default)]
+struct Outcomes {
+    /// HEAD 收到错误响应而 GET 成功的主机
+    rejected: HashSet<String>,
+    /// HEAD 成功的主机
+    accepted: HashSet<String>,
+}
+
+/// 一次运行的结果，对应 [`DuckDB::update_method_cache`](crate::db::duckdb::DuckDB::update_method_cache) 的参数
+pub struct HeadOutcome {
+    pub rejected: Vec<String>,
+    pub accepted: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+impl HeadLearning {
+    /// `retest_runs` 为 0 时不重新尝试
+    pub fn new(cache: &[MethodCacheEntry], retest_runs: u32) -> Self {
+        let mut get_hosts = HashSet::new();
+        for entry in cache {
+            if retest_runs > 0 && entry.runs_since_probe + 1 >= retest_runs {
+                info!("主机 {} 已连续 {} 次运行直接用 GET，本次重新尝试 HEAD", entry.host, entry.runs_since_probe);
+            } else {
+                get_hosts.insert(entry.host.clone());
+            }
+        }
+        Self { get_hosts, outcomes: Mutex::new(Outcomes::default()) }
+    }
+
+    pub fn use_get(&self, host: &str) -> bool {
+        self.get_hosts.contains(host)
+    }
+
+    /// 记录一次 HEAD 是否被主机接受
+    pub fn record(&self, host: &str, head_ok: bool) {
+        let mut outcomes = self.outcomes.lock().unwrap();
+        let set = if head_ok { &mut outcomes.accepted } else { &mut outcomes.rejected };
+        set.insert(host.to_string());
+    }
+
+    /// 本次运行的结果。同一主机 HEAD 有成功也有被拒绝时不算拒绝 HEAD
+    pub fn outcome(&self) -> HeadOutcome {
+        let outcomes = self.outcomes.lock().unwrap();
+        let mut rejected: Vec<String> = outcomes.rejected.difference(&outcomes.accepted).cloned().collect();
+        let mut accepted: Vec<String> = outcomes.accepted.iter().cloned().collect();
+        let mut skipped: Vec<String> = self.get_hosts.iter().cloned().collect();
+        rejected.sort();
+        accepted.sort();
+        skipped.sort();
+        HeadOutcome { rejected, accepted, skipped }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::duckdb::DuckDB;
+
+    /// 一次运行：`checks` 为 (主机, HEAD 是否成功)，用 GET 的主机不尝试 HEAD
+    async fn run(db: &DuckDB, retest_runs: u32, checks: &[(&str, bool)]) -> Vec<String> {
+        let learning = HeadLearning::new(&db.get_method_cache().await.unwrap(), retest_runs);
+        let mut get_hosts = Vec::new();
+        for &(host, head_ok) in checks {
+            if learning.use_get(host) {
+                get_hosts.push(host.to_string());
+            } else {
+                learning.record(host, head_ok);
+            }
+        }
+        let outcome = learning.outcome();
+        db.update_method_cache(&outcome.rejected, &outcome.accepted, &outcome.skipped).await.unwrap();
+        get_hosts.dedup();
+        get_hosts
+    }
+
+    #[test]
+    fn mixed_head_results_are_not_rejections() {
+        let learning = HeadLearning::new(&[], 3);
+        learning.record("b.example.org", true);
+        learning.record("a.example.org", false);
+        learning.record("c.example.org", false);
+        learning.record("c.example.org", true);
+        let outcome = learning.outcome();
+        assert_eq!(outcome.rejected, ["a.example.org"]);
+        assert_eq!(outcome.accepted, ["b.example.org", "c.example.org"]);
+        assert!(outcome.skipped.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejecting_hosts_use_get_until_retest() {
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let checks = [("a.example.org", false), ("a.example.org", false), ("b.example.org", true)];
+        assert!(run(&db, 3, &checks).await.is_empty());
+        // 之后两次运行直接用 GET，第三次重新尝试 HEAD
+        assert_eq!(run(&db, 3, &checks).await, ["a.example.org"]);
+        assert_eq!(run(&db, 3, &checks).await, ["a.example.org"]);
+        assert!(run(&db, 3, &[("a.example.org", true)]).await.is_empty());
+        // HEAD 成功后不再记住
+        assert!(db.get_method_cache().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn zero_retest_runs_never_retests() {
+        let db = DuckDB::new(":memory:").await.unwrap();
+        run(&db, 0, &[("a.example.org", false)]).await;
+        for _ in 0..5 {
+            assert_eq!(run(&db, 0, &[("a.example.org", true)]).await, ["a.example.org"]);
+        }
+        assert_eq!(db.get_method_cache().await.unwrap()[0].runs_since_probe, 5);
+    }
+}
@@ -0,0 +1,728 @@
+//! URL检查器：按URL协议选择检查方式，结果统一为 [`ResponseInfo`] / [`CheckError`]
+//!
+//! HTTP(S) 之外的协议实现 [`UrlChecker`] 后在 [`DataMonitor`](crate::monitor::DataMonitor) 中注册即可，
+//! 重试、熔断、计时和写库由调用方处理。
+
+use crate::config::{TerminalRedirect, UrlAuth};
+use crate::sanitize::{redact_headers, truncate_end};
+use crate::models::{CheckError, ErrorCategory, FailedHop, RedirectLimitExceeded, RequestErrorSignals, ResponseInfo};
+use futures::future::BoxFuture;
+use std::fmt::Write;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tracing::{info, warn};
+
+/// 一次检查的参数
+#[derive(Clone, Copy)]
+pub struct CheckRequest<'a> {
+    pub url: &'a str,
+    pub auth: Option<&'a UrlAuth>,
+    /// 不为 None 时读取内容开头最多这么多字节计算指纹，不支持的检查器忽略
+    pub fingerprint_bytes: Option<usize>,
+    /// 先用开销较小的方式（HTTP 的 HEAD）检查，不支持的检查器忽略
+    pub head_first: bool,
+    /// 超时，HTTP 为每一跳请求的超时，其他检查器为整个检查的超时
+    pub timeout: Duration,
+}
+
+pub struct CheckOutcome {
+    pub result: Result<ResponseInfo, CheckError>,
+    /// 主机是否接受 HEAD，见 [`HttpChecker`]；其他检查器始终为 None
+    pub head_accepted: Option<bool>,
+}
+
+impl CheckOutcome {
+    fn new(result: Result<ResponseInfo, CheckError>) -> Self {
+        Self { result, head_accepted: None }
+    }
+}
+
+/// 某类URL协议的检查方式
+pub trait UrlChecker: Send + Sync {
+    /// 处理的URL协议，小写
+    fn schemes(&self) -> &'static [&'static str];
+
+    /// 是否在连接池中复用连接，为 false 时不记录 connection_reused
+    fn reuses_connections(&self) -> bool {
+        false
+    }
+
+    fn check<'a>(&'a self, request: CheckRequest<'a>) -> BoxFuture<'a, CheckOutcome>;
+}
+
+/// http:// 和 https:// URL 的检查器，最多跟随 `max_redirects` 次重定向。
+/// 客户端需要关闭自动重定向（`redirect::Policy::none()`），由检查器逐跳跟随以记录失败的那一跳。
+/// 没有跟随的 3xx（超过上限、没有 Location）按 `terminal_redirect` 处理
+pub struct HttpChecker {
+    client: reqwest::Client,
+    max_redirects: usize,
+    max_header_bytes: usize,
+    terminal_redirect: TerminalRedirect,
+    follow_insecure_redirects: bool,
+}
+
+impl HttpChecker {
+    pub fn new(client: reqwest::Client, max_redirects: usize, max_header_bytes: usize) -> Self {
+        Self {
+            client,
+            max_redirects,
+            max_header_bytes,
+            terminal_redirect: TerminalRedirect::Failure,
+            follow_insecure_redirects: true,
+        }
+    }
+
+    /// 没有跟随的 3xx 的处理方式，`follow_insecure` 为 false 时不跟随 HTTPS 到 HTTP 的重定向
+    pub fn with_redirect_policy(mut self, terminal: TerminalRedirect, follow_insecure: bool) -> Self {
+        self.terminal_redirect = terminal;
+        self.follow_insecure_redirects = follow_insecure;
+        self
+    }
+
+    /// `head_first` 时先发 HEAD，收到错误响应（部分服务器不支持 HEAD）再用 GET 确认。
+    /// `head_accepted`：HEAD 失败而 GET 成功时为 false，没有发 HEAD、
+    /// HEAD 没有收到响应或 GET 也失败时为 None
+    async fn check_head_first(&self, request: &CheckRequest<'_>) -> CheckOutcome {
+        let CheckRequest { url, auth, fingerprint_bytes, head_first, timeout } = *request;
+        if !head_first {
+            return CheckOutcome::new(self.check_url(reqwest::Method::GET, url, auth, fingerprint_bytes, timeout).await);
+        }
+        match self.check_url(reqwest::Method::HEAD, url, auth, None, timeout).await {
+            Ok(info) => CheckOutcome { result: Ok(info), head_accepted: Some(true) },
+            Err(e) if e.status_code.is_none() => CheckOutcome::new(Err(e)),
+            Err(e) => {
+                info!("HEAD {} 返回 {:?}，改用 GET", url, e.status_code);
+                let result = self.check_url(reqwest::Method::GET, url, auth, fingerprint_bytes, timeout).await;
+                let head_accepted = result.is_ok().then_some(false);
+                CheckOutcome { result, head_accepted }
+            }
+        }
+    }
+
+    /// `fingerprint_bytes` 不为 None 时，成功的响应读取响应体开头计算指纹。
+    /// 失败时记录失败的那一跳，发生过重定向时错误详情中附上重定向链
+    async fn check_url(&self, method: reqwest::Method, url: &str, auth: Option<&UrlAuth>, fingerprint_bytes: Option<usize>, timeout: Duration)
+        -> Result<ResponseInfo, CheckError> {
+        let (response, chain) = self.follow_redirects(method, url, auth, timeout).await?;
+        let status = response.status();
+        let status_code = status.as_u16();
+        let http_version = format!("{:?}", response.version());
+        let status_text = status.canonical_reason()
+            .unwrap_or("Unknown")
+            .to_string();
+        let headers = response.headers()
+            .iter()
+            .map(|(k, v)| format!("{}: {:?}", k, v))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let headers = truncate_end(redact_headers(&headers), self.max_header_bytes);
+        let redirect_location = redirect_target(&response).map(|location| location.to_string());
+        let Some(category) = ErrorCategory::from_status(status_code, auth.is_some()) else {
+            // 非错误状态（如按 terminal_redirect 计为成功的 3xx）也算收到了响应，只有 2xx 计算指纹
+            let (content_hash, body_read) = match fingerprint_bytes.filter(|_| status.is_success()) {
+                Some(max_bytes) => {
+                    let started = std::time::Instant::now();
+                    (fingerprint_response(response, max_bytes).await, Some(started.elapsed()))
+                }
+                None => (None, None),
+            };
+            return Ok(ResponseInfo {
+                status_code,
+                status_text,
+                headers: Some(headers),
+                http_version,
+                content_hash,
+                body_read,
+                redirect_location,
+            });
+        };
+        let message = match category {
+            ErrorCategory::ServerError => format!("服务器错误: {}", status),
+            ErrorCategory::AuthRejected => "配置的凭证被拒绝: 401 Unauthorized".to_string(),
+            _ => format!("客户端错误: {}", status),
+        };
+        let failed = chain.len() - 1;
+        let mut detail = format!("状态码: {}, 原因: {}", status_code, status_text);
+        push_chain(&mut detail, &chain, failed);
+        Err(CheckError {
+            category,
+            message,
+            detail,
+            status_code: Some(status_code),
+            http_version: Some(http_version),
+            failed_hop: Some(FailedHop { index: failed, url: chain[failed].clone(), location: redirect_location }),
+        })
+    }
+
+    /// 发出请求并逐跳跟随重定向（客户端不自动跟随），返回最终的响应和依次请求的URL（第一个为 `url`）。
+    /// 与 reqwest 的处理一致，跳转到其他源（协议、主机或端口不同）后不再携带认证凭证
+    async fn follow_redirects(&self, method: reqwest::Method, url: &str, auth: Option<&UrlAuth>, timeout: Duration)
+        -> Result<(reqwest::Response, Vec<String>), CheckError> {
+        let origin = reqwest::Url::parse(url).ok().map(|url| url.origin());
+        let mut chain = vec![url.to_string()];
+        loop {
+            let current = chain.last().expect("chain starts with the requested url");
+            let same_origin = reqwest::Url::parse(current).ok().map(|url| url.origin()) == origin;
+            let response = self.request(method.clone(), current, auth.filter(|_| same_origin)).timeout(timeout).send().await
+                .map_err(|e| request_error(&e, &chain))?;
+            if !response.status().is_redirection() {
+                return Ok((response, chain));
+            }
+            let Some(next) = redirect_target(&response) else {
+                return match self.terminal_redirect {
+                    TerminalRedirect::Success => Ok((response, chain)),
+                    TerminalRedirect::Failure => Err(unfollowed(ErrorCategory::ServerError, "重定向响应没有有效的 Location", &response, &chain, None)),
+                    TerminalRedirect::Redirected => Err(unfollowed(ErrorCategory::Redirected, "重定向响应没有有效的 Location", &response, &chain, None)),
+                };
+            };
+            if !self.follow_insecure_redirects && response.url().scheme() == "https" && next.scheme() == "http" {
+                let message = format!("HTTPS 重定向到 HTTP: {}", next);
+                return Err(unfollowed(ErrorCategory::InsecureRedirect, &message, &response, &chain, Some(next.to_string())));
+            }
+            if chain.len() > self.max_redirects {
+                match self.terminal_redirect {
+                    TerminalRedirect::Success => return Ok((response, chain)),
+                    TerminalRedirect::Redirected => {
+                        let message = format!("超过最大重定向次数 {}，没有跟随到 {}", self.max_redirects, next);
+                        return Err(unfollowed(ErrorCategory::Redirected, &message, &response, &chain, Some(next.to_string())));
+                    }
+                    TerminalRedirect::Failure => {}
+                }
+                let failed = chain.len() - 1;
+                let failed_hop = FailedHop { index: failed, url: chain[failed].clone(), location: Some(next.to_string()) };
+                chain.push(next.to_string());
+                let exceeded = RedirectLimitExceeded { max: self.max_redirects, chain };
+                let mut detail = format!("错误详情: {}", exceeded);
+                // 完整的跳转链便于看出 A -> B -> A 这样的循环
+                push_chain(&mut detail, &exceeded.chain, failed);
+                return Err(CheckError {
+                    category: ErrorCategory::TooManyRedirects,
+                    message: exceeded.to_string(),
+                    detail,
+                    status_code: None,
+                    http_version: None,
+                    failed_hop: Some(failed_hop),
+                });
+            }
+            chain.push(next.to_string());
+        }
+    }
+
+    fn request(&self, method: reqwest::Method, url: &str, auth: Option<&UrlAuth>) -> reqwest::RequestBuilder {
+        let mut request = self.client.request(method, url);
+        match auth {
+            Some(UrlAuth::Basic { username, password }) => request = request.basic_auth(username, Some(password)),
+            Some(UrlAuth::Bearer(token)) => request = request.bearer_auth(token),
+            None => {}
+        }
+        request.header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) \
+                              AppleWebKit/537.36 (KHTML, like Gecko) \
+                              Chrome/127.0.0.0 Safari/537.36")
+            .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
+            .header("Accept-Language", "en-US,en;q=0.5")
+            .header("Connection", "keep-alive")
+    }
+}
+
+/// 3xx 响应的 Location 解析为绝对URL，没有或无效时为 None
+fn redirect_target(response: &reqwest::Response) -> Option<reqwest::Url> {
+    if !response.status().is_redirection() {
+        return None;
+    }
+    response.headers().get(reqwest::header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .and_then(|location| response.url().join(location).ok())
+}
+
+/// 没有跟随的 3xx 响应，失败的是跳转链的最后一跳，`location` 为没有跟随的目标
+fn unfollowed(category: ErrorCategory, message: &str, response: &reqwest::Response, chain: &[String], location: Option<String>) -> CheckError {
+    let failed = chain.len() - 1;
+    let status = response.status();
+    let mut detail = format!("状态码: {}, Location: {}", status.as_u16(), location.as_deref().unwrap_or("-"));
+    push_chain(&mut detail, chain, failed);
+    CheckError {
+        category,
+        message: message.to_string(),
+        detail,
+        status_code: Some(status.as_u16()),
+        http_version: Some(format!("{:?}", response.version())),
+        failed_hop: Some(FailedHop { index: failed, url: chain[failed].clone(), location }),
+    }
+}
+
+/// 请求没有收到响应（或响应无法读取），失败的是跳转链的最后一跳
+fn request_error(e: &reqwest::Error, chain: &[String]) -> CheckError {
+    let failed = chain.len() - 1;
+    let signals = RequestErrorSignals::from_error(e);
+    let mut detail = format!("错误详情: {}\n{}", e, signals.detail());
+    push_chain(&mut detail, chain, failed);
+    CheckError {
+        category: ErrorCategory::from_signals(&signals),
+        message: e.to_string(),
+        detail,
+        status_code: e.status().map(|s| s.as_u16()),
+        http_version: None,
+        failed_hop: Some(FailedHop { index: failed, url: chain[failed].clone(), location: None }),
+    }
+}
+
+/// 发生过重定向时在错误详情后附上重定向链，标出失败的一跳
+fn push_chain(detail: &mut String, chain: &[String], failed: usize) {
+    if chain.len() < 2 {
+        return;
+    }
+    detail.push_str("\n重定向链:");
+    for (i, url) in chain.iter().enumerate() {
+        let mark = if i == failed { " <- 失败" } else { "" };
+        let _ = write!(detail, "\n  {}. {}{}", i + 1, url, mark);
+    }
+}
+
+impl UrlChecker for HttpChecker {
+    fn schemes(&self) -> &'static [&'static str] {
+        &["http", "https"]
+    }
+
+    fn reuses_connections(&self) -> bool {
+        true
+    }
+
+    fn check<'a>(&'a self, request: CheckRequest<'a>) -> BoxFuture<'a, CheckOutcome> {
+        Box::pin(async move { self.check_head_first(&request).await })
+    }
+}
+
+/// ftp:// URL 的检查器。登录后用 SIZE 确认文件存在，SIZE 不可用或路径是目录时依次尝试 MLST 和 CWD，
+/// 只使用控制连接，不建立数据连接，也不计算内容指纹。
+///
+/// 为了和HTTP URL一起统计，结果按HTTP状态码记录：存在为 200，550（文件不可用）为 404，
+/// 530（未登录）为 401，4xx 暂时性错误为 503，其他错误为 400；http_version 记为 `FTP`
+pub struct FtpChecker;
+
+/// FTP 服务器的一条应答，多行应答的各行用换行连接
+struct FtpReply {
+    code: u16,
+    text: String,
+}
+
+/// FTP 控制连接，记录收发过程用于错误详情（不记录密码）
+struct FtpControl {
+    reader: BufReader<TcpStream>,
+    transcript: String,
+}
+
+/// 多行应答最多读取的行数和每行的字节数，防止异常的服务器无限输出
+const FTP_MAX_REPLY_LINES: usize = 100;
+const FTP_MAX_LINE_BYTES: u64 = 4096;
+
+impl FtpChecker {
+    async fn probe(&self, url: &str, auth: Option<&UrlAuth>) -> Result<ResponseInfo, CheckError> {
+        let url = reqwest::Url::parse(url).map_err(|e| invalid_url(url, e))?;
+        let host = url.host_str().ok_or_else(|| invalid_url(url.as_str(), "缺少主机名"))?;
+        let port = url.port_or_known_default().unwrap_or(21);
+        let decode = |s: &str| urlencoding::decode(s).map(|s| s.into_owned()).unwrap_or_else(|_| s.to_string());
+        // 配置的凭证优先，其次是URL中的用户名密码，都没有时匿名登录
+        let (username, password, credentials_given) = match auth {
+            Some(UrlAuth::Basic { username, password }) => (username.clone(), password.clone(), true),
+            _ if !url.username().is_empty() => (decode(url.username()), url.password().map(decode).unwrap_or_default(), true),
+            _ => ("anonymous".to_string(), "anonymous@".to_string(), false),
+        };
+        let path = decode(url.path());
+        // 解码后的 CR、LF 会在控制连接上拼出额外的命令（可能以配置的凭证执行），连接前拒绝
+        if let Some(field) = [("用户名", &username), ("密码", &password), ("路径", &path)].iter()
+            .find(|(_, value)| value.contains(['\r', '\n', '\0']))
+            .map(|(field, _)| field) {
+            return Err(invalid_url(url.as_str(), format!("{}包含控制字符", field)));
+        }
+
+        let addrs: Vec<_> = tokio::net::lookup_host((host, port)).await
+            .map_err(|e| io_error(ErrorCategory::DnsResolution, &format!("解析 {} 失败", host), &e))?
+            .collect();
+        let stream = TcpStream::connect(&addrs[..]).await
+            .map_err(|e| io_error(connect_category(&e), &format!("连接 {}:{} 失败", host, port), &e))?;
+        let mut control = FtpControl { reader: BufReader::new(stream), transcript: String::new() };
+
+        let greeting = control.reply().await?;
+        if greeting.code != 220 {
+            return Err(control.error(&greeting, credentials_given));
+        }
+        let mut login = control.command(&format!("USER {}", username), None).await?;
+        if login.code == 331 {
+            login = control.command(&format!("PASS {}", password), Some("PASS ****")).await?;
+        }
+        if login.code != 230 {
+            return Err(control.error(&login, credentials_given));
+        }
+        // SIZE 在 ASCII 模式下的结果依赖服务器实现，先切换到二进制模式
+        control.command("TYPE I", None).await?;
+        let size = control.command(&format!("SIZE {}", path), None).await?;
+        let found = if size.code == 213 {
+            Some(format!("content-length: {}", size.text.trim()))
+        } else if control.command(&format!("MLST {}", path), None).await?.code == 250 {
+            Some("ftp-mlst: 250".to_string())
+        } else {
+            let cwd = control.command(&format!("CWD {}", path), None).await?;
+            if cwd.code == 250 {
+                Some("ftp-type: directory".to_string())
+            } else {
+                // 550 表示路径不存在或不可访问，其他应答（如 502 命令未实现）以 SIZE 的结果为准
+                let reply = if cwd.code == 550 { cwd } else { size };
+                return Err(control.error(&reply, credentials_given));
+            }
+        };
+        // 服务器对 QUIT 的应答不影响结果
+        let _ = control.command("QUIT", None).await;
+        Ok(ResponseInfo {
+            status_code: 200,
+            status_text: "OK".to_string(),
+            headers: found,
+            http_version: "FTP".to_string(),
+            content_hash: None,
+            body_read: None,
+            redirect_location: None,
+        })
+    }
+}
+
+impl FtpControl {
+    /// `shown` 为记录到过程中的命令，不为 None 时代替实际命令
+    async fn command(&mut self, command: &str, shown: Option<&str>) -> Result<FtpReply, CheckError> {
+        let _ = writeln!(self.transcript, "> {}", shown.unwrap_or(command));
+        self.reader.get_mut().write_all(format!("{}\r\n", command).as_bytes()).await
+            .map_err(|e| self.io_error("发送 FTP 命令失败", &e))?;
+        self.reply().await
+    }
+
+    async fn reply(&mut self) -> Result<FtpReply, CheckError> {
+        let mut text = Vec::new();
+        let mut code = None;
+        for _ in 0..FTP_MAX_REPLY_LINES {
+            let mut line = String::new();
+            let read = (&mut self.reader).take(FTP_MAX_LINE_BYTES).read_line(&mut line).await
+                .map_err(|e| self.io_error("读取 FTP 应答失败", &e))?;
+            if read == 0 {
+                return Err(self.protocol_error("服务器关闭了连接"));
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            let _ = writeln!(self.transcript, "< {}", line);
+            // 多行应答以 "123-" 开始，以 "123 " 结束，中间的行不一定带应答码
+            let parsed = line.get(..3).and_then(|c| c.parse::<u16>().ok());
+            let expected = match (code, parsed) {
+                (Some(c), _) => c,
+                (None, Some(c)) => *code.insert(c),
+                (None, None) => return Err(self.protocol_error("无法解析 FTP 应答")),
+            };
+            let separator = line.as_bytes().get(3).copied();
+            // 中间不带应答码的行原样保留
+            let body = if parsed.is_some() { line.get(4..).unwrap_or_default() } else { line };
+            text.push(body.to_string());
+            if parsed == Some(expected) && separator != Some(b'-') {
+                return Ok(FtpReply { code: expected, text: text.join("\n") });
+            }
+        }
+        Err(self.protocol_error("FTP 应答行数过多"))
+    }
+
+    /// 按应答码生成检查错误，见 [`FtpChecker`] 的状态码对应关系
+    fn error(&self, reply: &FtpReply, credentials_given: bool) -> CheckError {
+        let (status_code, category) = match reply.code {
+            530 if credentials_given => (401, ErrorCategory::AuthRejected),
+            530 => (401, ErrorCategory::ClientError),
+            550 => (404, ErrorCategory::ClientError),
+            400..=499 => (503, ErrorCategory::ServerError),
+            _ => (400, ErrorCategory::ClientError),
+        };
+        CheckError {
+            category,
+            message: format!("FTP {} {}", reply.code, reply.text),
+            detail: format!("FTP 会话:\n{}", self.transcript),
+            status_code: Some(status_code),
+            http_version: Some("FTP".to_string()),
+            failed_hop: None,
+        }
+    }
+
+    fn io_error(&self, context: &str, e: &std::io::Error) -> CheckError {
+        let mut error = io_error(ErrorCategory::NetworkConnection, context, e);
+        let _ = write!(error.detail, "\nFTP 会话:\n{}", self.transcript);
+        error
+    }
+
+    fn protocol_error(&self, message: &str) -> CheckError {
+        CheckError {
+            category: ErrorCategory::Unknown,
+            message: message.to_string(),
+            detail: format!("FTP 会话:\n{}", self.transcript),
+            status_code: None,
+            http_version: None,
+            failed_hop: None,
+        }
+    }
+}
+
+impl UrlChecker for FtpChecker {
+    fn schemes(&self) -> &'static [&'static str] {
+        &["ftp"]
+    }
+
+    fn check<'a>(&'a self, request: CheckRequest<'a>) -> BoxFuture<'a, CheckOutcome> {
+        Box::pin(async move {
+            let result = match tokio::time::timeout(request.timeout, self.probe(request.url, request.auth)).await {
+                Ok(result) => result,
+                Err(_) => Err(CheckError {
+                    category: ErrorCategory::Timeout,
+                    message: format!("FTP 检查超过 {:?}", request.timeout),
+                    detail: format!("超时: {:?}", request.timeout),
+                    status_code: None,
+                    http_version: None,
+                    failed_hop: None,
+                }),
+            };
+            // FTP 没有重定向，失败都发生在URL本身
+            let result = result.map_err(|mut e| {
+                e.failed_hop.get_or_insert_with(|| FailedHop { index: 0, url: request.url.to_string(), location: None });
+                e
+            });
+            CheckOutcome::new(result)
+        })
+    }
+}
+
+/// file:// URL 的检查器，只检查本机路径是否存在，不读取内容。
+/// 数据集URL来自外部目录，默认不启用（`monitor.check_file_urls`），避免探测本机文件
+pub struct FileChecker;
+
+impl UrlChecker for FileChecker {
+    fn schemes(&self) -> &'static [&'static str] {
+        &["file"]
+    }
+
+    fn check<'a>(&'a self, request: CheckRequest<'a>) -> BoxFuture<'a, CheckOutcome> {
+        Box::pin(async move {
+            let path = reqwest::Url::parse(request.url).ok().and_then(|url| url.to_file_path().ok());
+            let Some(path) = path else {
+                return CheckOutcome::new(Err(invalid_url(request.url, "不是有效的本地路径")));
+            };
+            let result = match tokio::fs::metadata(&path).await {
+                Ok(metadata) => Ok(ResponseInfo {
+                    status_code: 200,
+                    status_text: "OK".to_string(),
+                    headers: Some(format!("content-length: {}", metadata.len())),
+                    http_version: "FILE".to_string(),
+                    content_hash: None,
+                    body_read: None,
+                    redirect_location: None,
+                }),
+                Err(e) => {
+                    let status_code = match e.kind() {
+                        std::io::ErrorKind::NotFound => 404,
+                        std::io::ErrorKind::PermissionDenied => 403,
+                        _ => 400,
+                    };
+                    Err(CheckError {
+                        category: ErrorCategory::ClientError,
+                        message: format!("读取 {} 失败: {}", path.display(), e),
+                        detail: format!("错误类型: {:?}", e.kind()),
+                        status_code: Some(status_code),
+                        http_version: Some("FILE".to_string()),
+                        failed_hop: None,
+                    })
+                }
+            };
+            CheckOutcome::new(result)
+        })
+    }
+}
+
+/// URL协议没有对应的检查器
+pub fn unsupported_scheme(url: &str, scheme: &str) -> CheckError {
+    CheckError {
+        category: ErrorCategory::UnsupportedScheme,
+        message: format!("不支持检查 {} 协议的URL", scheme),
+        detail: format!("URL: {}", url),
+        status_code: None,
+        http_version: None,
+        failed_hop: None,
+    }
+}
+
+fn invalid_url(url: &str, reason: impl std::fmt::Display) -> CheckError {
+    CheckError {
+        category: ErrorCategory::ClientError,
+        message: format!("无效的URL: {}", reason),
+        detail: format!("URL: {}", url),
+        status_code: None,
+        http_version: None,
+        failed_hop: None,
+    }
+}
+
+fn io_error(category: ErrorCategory, context: &str, e: &std::io::Error) -> CheckError {
+    CheckError {
+        category,
+        message: format!("{}: {}", context, e),
+        detail: format!("错误详情: {}\n错误类型: {:?}", e, e.kind()),
+        status_code: None,
+        http_version: None,
+        failed_hop: None,
+    }
+}
+
+fn connect_category(e: &std::io::Error) -> ErrorCategory {
+    match e.kind() {
+        std::io::ErrorKind::ConnectionRefused => ErrorCategory::ConnectionRefused,
+        std::io::ErrorKind::TimedOut => ErrorCategory::Timeout,
+        _ => ErrorCategory::NetworkConnection,
+    }
+}
+
+/// 读取响应体开头最多 `max_bytes` 字节计算 SHA-256；文本内容先把连续空白合并为一个空格，
+/// 避免缩进、换行等变化被当作内容变化，二进制内容按原始字节计算。读取失败时返回 None
+async fn fingerprint_response(mut response: reqwest::Response, max_bytes: usize) -> Option<String> {
+    use sha2::{Digest, Sha256};
+    let is_text = response.headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_ascii_lowercase())
+        .is_some_and(|v| v.starts_with("text/") || ["html", "xml", "json"].iter().any(|t| v.contains(t)));
+    let mut body = Vec::new();
+    while body.len() < max_bytes {
+        match response.chunk().await {
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk[..chunk.len().min(max_bytes - body.len())]),
+            Ok(None) => break,
+            Err(e) => {
+                warn!("读取响应体计算指纹失败: {}: {}", response.url(), e);
+                return None;
+            }
+        }
+    }
+    let digest = if is_text {
+        let mut hasher = Sha256::new();
+        for (i, word) in body.split(|b| b.is_ascii_whitespace()).filter(|w| !w.is_empty()).enumerate() {
+            if i > 0 {
+                hasher.update(b" ");
+            }
+            hasher.update(word);
+        }
+        hasher.finalize()
+    } else {
+        Sha256::digest(&body)
+    };
+    Some(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// 本地 FTP 桩服务：连接后发送 `greeting`，之后每条命令用 `respond(命令)` 的结果应答，
+    /// 记录收到的命令
+    async fn ftp_stub(greeting: &'static str, respond: fn(&str) -> &'static str) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("ftp://{}", listener.local_addr().unwrap());
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let log = commands.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let log = log.clone();
+                tokio::spawn(async move {
+                    let mut socket = BufReader::new(socket);
+                    if socket.get_mut().write_all(greeting.as_bytes()).await.is_err() {
+                        return;
+                    }
+                    let mut line = String::new();
+                    while matches!(socket.read_line(&mut line).await, Ok(n) if n > 0) {
+                        let command = line.trim_end().to_string();
+                        line.clear();
+                        let reply = respond(&command);
+                        log.lock().unwrap().push(command);
+                        if socket.get_mut().write_all(reply.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (base, commands)
+    }
+
+    async fn check(url: &str) -> Result<ResponseInfo, CheckError> {
+        let request = CheckRequest { url, auth: None, fingerprint_bytes: None, head_first: false, timeout: Duration::from_secs(5) };
+        FtpChecker.check(request).await.result
+    }
+
+    /// 匿名登录成功，`/data.csv` 大小为 1234，`/dir` 是目录，其他路径不存在
+    fn files(command: &str) -> &'static str {
+        match command {
+            "USER anonymous" => "331 Password required\r\n",
+            "PASS anonymous@" => "230 Logged in\r\n",
+            "TYPE I" => "200 Type set to I\r\n",
+            "SIZE /data.csv" => "213 1234\r\n",
+            "CWD /dir" => "250 Directory changed\r\n",
+            "QUIT" => "221 Bye\r\n",
+            _ => "550 No such file or directory\r\n",
+        }
+    }
+
+    #[tokio::test]
+    async fn size_reply_means_the_file_exists() {
+        let (base, commands) = ftp_stub("220 ready\r\n", files).await;
+        let info = check(&format!("{}/data.csv", base)).await.unwrap();
+        assert_eq!((info.status_code, info.http_version.as_str()), (200, "FTP"));
+        assert_eq!(info.headers.as_deref(), Some("content-length: 1234"));
+        assert_eq!(*commands.lock().unwrap(), ["USER anonymous", "PASS anonymous@", "TYPE I", "SIZE /data.csv", "QUIT"]);
+
+        let info = check(&format!("{}/dir", base)).await.unwrap();
+        assert_eq!(info.headers.as_deref(), Some("ftp-type: directory"));
+    }
+
+    #[tokio::test]
+    async fn unavailable_files_are_not_found() {
+        let (base, commands) = ftp_stub("220 ready\r\n", files).await;
+        let error = check(&format!("{}/missing.csv", base)).await.unwrap_err();
+        assert_eq!((error.status_code, error.category.to_string()), (Some(404), ErrorCategory::ClientError.to_string()));
+        assert!(error.message.starts_with("FTP 550"), "{}", error.message);
+        assert!(error.detail.contains("> PASS ****") && !error.detail.contains("anonymous@"), "{}", error.detail);
+        assert_eq!(error.failed_hop.unwrap().index, 0);
+        assert_eq!(commands.lock().unwrap()[3..], ["SIZE /missing.csv", "MLST /missing.csv", "CWD /missing.csv"]);
+    }
+
+    #[tokio::test]
+    async fn rejected_logins_are_unauthorized() {
+        let (base, _) = ftp_stub("220 ready\r\n", |command| match command {
+            "USER u" | "USER anonymous" => "331 Password required\r\n",
+            _ => "530 Login incorrect\r\n",
+        }).await;
+        // URL 中的凭证被拒绝
+        let error = check(&base.replace("ftp://", "ftp://u:p@")).await.unwrap_err();
+        assert_eq!((error.status_code, error.category.to_string()), (Some(401), ErrorCategory::AuthRejected.to_string()));
+        // 匿名登录被拒绝不是凭证问题
+        let error = check(&format!("{}/data.csv", base)).await.unwrap_err();
+        assert_eq!((error.status_code, error.category.to_string()), (Some(401), ErrorCategory::ClientError.to_string()));
+    }
+
+    #[tokio::test]
+    async fn multi_line_replies_are_read_to_the_end() {
+        let (base, _) = ftp_stub("220-Welcome\r\nthis line has no code\r\n220 ready\r\n", |command| match command {
+            "USER anonymous" => "230-Logged in\r\n230-Quota: none\r\n230 OK\r\n",
+            "SIZE /data.csv" => "213-Status\r\n 213 is not the end\r\n213 42\r\n",
+            "TYPE I" => "200 OK\r\n",
+            _ => "221 Bye\r\n",
+        }).await;
+        let info = check(&format!("{}/data.csv", base)).await.unwrap();
+        assert_eq!(info.headers.as_deref(), Some("content-length: Status\n 213 is not the end\n42"));
+    }
+
+    #[tokio::test]
+    async fn control_characters_are_rejected_before_connecting() {
+        let (base, commands) = ftp_stub("220 ready\r\n", files).await;
+        for url in [format!("{}/a%0D%0ADELE%20b", base), format!("{}/a%00", base), base.replace("ftp://", "ftp://u%0Ax:p@")] {
+            let error = check(&url).await.unwrap_err();
+            assert_eq!((error.category.to_string(), error.status_code), (ErrorCategory::ClientError.to_string(), None), "{}", url);
+            assert!(error.message.contains("控制字符"), "{}", error.message);
+        }
+        assert!(commands.lock().unwrap().is_empty());
+    }
+}
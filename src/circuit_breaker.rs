@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::warn;
+
+/// 单次监测运行内按主机的熔断器
+///
+/// 同一主机连续 `threshold` 次没有收到响应（超时、连接失败等）后熔断，本次运行中
+/// 该主机剩余的URL不再请求。收到任何HTTP响应（包括 4xx/5xx）都说明主机可达，重新计数。
+pub struct CircuitBreaker {
+    /// 0 表示不熔断
+    threshold: u32,
+    hosts: Mutex<HashMap<String, HostState>>,
+}
+
+#[derive(Debug, Default)]
+struct HostState {
+    consecutive_failures: u32,
+    open: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32) -> Self {
+        Self { threshold, hosts: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn is_open(&self, host: &str) -> bool {
+        self.threshold > 0
+            && self.hosts.lock().unwrap().get(host).is_some_and(|state| state.open)
+    }
+
+    /// 记录一次检查是否收到了响应
+    pub fn record(&self, host: &str, responded: bool) {
+        if self.threshold == 0 {
+            return;
+        }
+        let mut hosts = self.hosts.lock().unwrap();
+        let state = hosts.entry(host.to_string()).or_default();
+        if responded {
+            state.consecutive_failures = 0;
+            return;
+        }
+        state.consecutive_failures += 1;
+        if !state.open && state.consecutive_failures >= self.threshold {
+            state.open = true;
+            warn!("主机 {} 连续 {} 次请求没有响应，本次运行跳过该主机剩余的URL", host, state.consecutive_failures);
+        }
+    }
+
+    /// 重新探测成功后关闭熔断
+    pub fn close(&self, host: &str) {
+        if let Some(state) = self.hosts.lock().unwrap().get_mut(host) {
+            state.open = false;
+            state.consecutive_failures = 0;
+        }
+    }
+}
+
+/// URL的主机名，用作熔断的粒度
+pub fn host_of(url: &str) -> Option<String> {
+    reqwest::Url::parse(url).ok()?.host_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(3);
+        breaker.record("a", false);
+        breaker.record("a", false);
+        // 收到响应后重新计数
+        breaker.record("a", true);
+        breaker.record("a", false);
+        breaker.record("a", false);
+        assert!(!breaker.is_open("a"));
+        breaker.record("a", false);
+        assert!(breaker.is_open("a"));
+        // 其他主机不受影响
+        assert!(!breaker.is_open("b"));
+
+        // 熔断后收到的响应不会关闭熔断，只有重新探测成功后关闭
+        breaker.record("a", true);
+        assert!(breaker.is_open("a"));
+        breaker.close("a");
+        assert!(!breaker.is_open("a"));
+        breaker.record("a", false);
+        breaker.record("a", false);
+        assert!(!breaker.is_open("a"));
+        breaker.close("unknown");
+    }
+
+    #[test]
+    fn zero_threshold_never_opens() {
+        let breaker = CircuitBreaker::new(0);
+        for _ in 0..10 {
+            breaker.record("a", false);
+        }
+        assert!(!breaker.is_open("a"));
+        assert!(breaker.hosts.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn host_of_url() {
+        assert_eq!(host_of("https://Data.Example.org:8443/a?b=c").as_deref(), Some("data.example.org"));
+        assert_eq!(host_of("ftp://files.example.org/x").as_deref(), Some("files.example.org"));
+        assert_eq!(host_of("http://[::1]/").as_deref(), Some("[::1]"));
+        assert_eq!(host_of("file:///tmp/x"), None);
+        assert_eq!(host_of("not a url"), None);
+    }
+}
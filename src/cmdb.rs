@@ -0,0 +1,278 @@
+//! 向资产管理系统（CMDB）推送各数据中心的链接健康摘要
+//!
+//! 每个数据中心一个 JSON 文档，PUT 到 `integrations.cmdb.endpoint` 中 `{center}` 替换后的地址。
+//! 文档结构变化时递增 [`SCHEMA_VERSION`]，接收方按 schema_version 解析。
+
+use crate::config::CmdbConfig;
+use crate::db::duckdb::DuckDB;
+use crate::models::{CategoryCount, CenterSummary, CmdbDelivery, MonitorSummary};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{info, warn};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// 推送的文档
+#[derive(Debug, Clone, Serialize)]
+pub struct CenterHealthDocument<'a> {
+    pub schema_version: u32,
+    pub center: &'a str,
+    pub run_id: &'a str,
+    pub last_run_at: DateTime<Utc>,
+    pub generated_at: DateTime<Utc>,
+    /// 本次运行检查的数据集URL数
+    pub total_datasets: usize,
+    pub reachable: usize,
+    pub broken: usize,
+    /// 可达率（百分比）
+    pub reachable_rate: f64,
+    /// 失败中判断为本地网络问题的数量，不代表数据中心的链接失效
+    pub local_issues: usize,
+    pub broken_by_category: &'a [CategoryCount],
+}
+
+impl<'a> CenterHealthDocument<'a> {
+    pub fn new(summary: &'a MonitorSummary, center: &'a CenterSummary, now: DateTime<Utc>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            center: &center.center_name,
+            run_id: &summary.run_id,
+            last_run_at: summary.finished_at,
+            generated_at: now,
+            total_datasets: center.total,
+            reachable: center.success,
+            broken: center.total - center.success,
+            reachable_rate: center.success_rate,
+            local_issues: center.local_issues,
+            broken_by_category: &center.error_categories,
+        }
+    }
+}
+
+pub struct CmdbPusher {
+    config: CmdbConfig,
+    client: reqwest::Client,
+}
+
+impl CmdbPusher {
+    pub fn new(config: &CmdbConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .expect("failed to build http client");
+        Self { config: config.clone(), client }
+    }
+
+    /// 推送一次运行中检查过的每个数据中心，推送结果写入 cmdb_deliveries 表
+    pub async fn push_run(&self, duckdb: &DuckDB, summary: &MonitorSummary) -> Vec<CmdbDelivery> {
+        let mut deliveries = Vec::new();
+        for center in summary.centers.iter().filter(|c| c.total > 0) {
+            let delivery = self.push_center(summary, center).await;
+            if let Err(e) = duckdb.insert_cmdb_delivery(&delivery).await {
+                warn!("记录 CMDB 推送结果失败: {}", e);
+            }
+            deliveries.push(delivery);
+        }
+        deliveries
+    }
+
+    /// 推送各数据中心最近一次运行的结果
+    pub async fn push_latest(&self, duckdb: &DuckDB, centers: &[&str]) -> Result<Vec<CmdbDelivery>> {
+        let recent_runs = duckdb.get_recent_runs(50).await?;
+        let mut deliveries = Vec::new();
+        for name in centers {
+            let latest = recent_runs.iter()
+                .find_map(|run| run.center(name).filter(|c| c.total > 0).map(|center| (run, center)));
+            let Some((run, center)) = latest else {
+                warn!("最近的运行中没有数据中心 {}，不推送到 CMDB", name);
+                continue;
+            };
+            let delivery = self.push_center(run, center).await;
+            if let Err(e) = duckdb.insert_cmdb_delivery(&delivery).await {
+                warn!("记录 CMDB 推送结果失败: {}", e);
+            }
+            deliveries.push(delivery);
+        }
+        Ok(deliveries)
+    }
+
+    async fn push_center(&self, summary: &MonitorSummary, center: &CenterSummary) -> CmdbDelivery {
+        let url = self.config.endpoint.replace("{center}", &urlencoding::encode(&center.center_name));
+        let document = CenterHealthDocument::new(summary, center, Utc::now());
+        let mut delivery = CmdbDelivery {
+            center_name: center.center_name.clone(),
+            run_id: summary.run_id.clone(),
+            delivered_at: Utc::now(),
+            success: false,
+            attempts: 0,
+            http_status: None,
+            error: None,
+        };
+        while delivery.attempts < self.config.max_attempts {
+            delivery.attempts += 1;
+            let result = self.put(&url, &document).await;
+            delivery.delivered_at = Utc::now();
+            match result {
+                Ok(status) if status.is_success() => {
+                    delivery.http_status = Some(status.as_u16());
+                    delivery.success = true;
+                    delivery.error = None;
+                    info!("数据中心 {} 的链接健康摘要已推送到 CMDB（HTTP {}）", center.center_name, status.as_u16());
+                    return delivery;
+                }
+                Ok(status) => {
+                    delivery.http_status = Some(status.as_u16());
+                    delivery.error = Some(format!("HTTP {}", status));
+                }
+                Err(e) => {
+                    delivery.http_status = None;
+                    delivery.error = Some(format!("{:#}", e));
+                }
+            }
+            if delivery.attempts < self.config.max_attempts {
+                let delay = Duration::from_secs(1 << delivery.attempts.min(6));
+                warn!("推送数据中心 {} 到 CMDB 失败（第 {} 次），{:?} 后重试: {}",
+                      center.center_name, delivery.attempts, delay, delivery.error.as_deref().unwrap_or_default());
+                tokio::time::sleep(delay).await;
+            }
+        }
+        warn!("推送数据中心 {} 到 CMDB 失败，已尝试 {} 次: {}",
+              center.center_name, delivery.attempts, delivery.error.as_deref().unwrap_or_default());
+        delivery
+    }
+
+    async fn put(&self, url: &str, document: &CenterHealthDocument<'_>) -> Result<reqwest::StatusCode> {
+        let mut request = self.client.put(url).json(document);
+        if let Some((name, value)) = self.config.auth_header()? {
+            request = request.header(name, value);
+        }
+        Ok(request.send().await?.status())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{response, stub_server, Stub};
+
+    /// CMDB 桩服务，返回推送地址模板和桩服务
+    async fn cmdb_server() -> (String, Stub) {
+        let stub = stub_server(|_| response("204 No Content", &[], "")).await;
+        (format!("{}/cmdb/centers/{{center}}/health", stub.base), stub)
+    }
+
+    fn summary() -> MonitorSummary {
+        serde_json::from_value(serde_json::json!({
+            "run_id": "run-1",
+            "started_at": "2026-01-01T00:00:00Z",
+            "finished_at": "2026-01-01T01:30:00Z",
+            "total": 5,
+            "success": 3,
+            "local_issues": 1,
+            "remote_issues": 1,
+            "centers": [
+                {
+                    "center_name": "A & B",
+                    "total": 4,
+                    "success": 2,
+                    "success_rate": 50.0,
+                    "local_issues": 1,
+                    "remote_issues": 1,
+                    "error_categories": [
+                        { "category": "HTTP_5XX", "count": 1 },
+                        { "category": "TIMEOUT", "count": 1 },
+                    ],
+                    "sample_failures": [],
+                },
+                {
+                    "center_name": "C",
+                    "total": 1,
+                    "success": 1,
+                    "success_rate": 100.0,
+                    "local_issues": 0,
+                    "remote_issues": 0,
+                    "error_categories": [],
+                    "sample_failures": [],
+                },
+                {
+                    "center_name": "D",
+                    "total": 0,
+                    "success": 0,
+                    "success_rate": 0.0,
+                    "local_issues": 0,
+                    "remote_issues": 0,
+                    "error_categories": [],
+                    "sample_failures": [],
+                },
+            ],
+        })).unwrap()
+    }
+
+    fn schema() -> jsonschema::Validator {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/cmdb-center-health.schema.json");
+        let schema: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        jsonschema::validator_for(&schema).unwrap()
+    }
+
+    #[tokio::test]
+    async fn pushed_documents_match_the_schema() {
+        let (endpoint, stub) = cmdb_server().await;
+        let config = CmdbConfig {
+            endpoint,
+            auth_header: Some("Authorization: Bearer t0ken".to_string()),
+            after_run: true,
+            cron: None,
+            max_attempts: 1,
+            timeout_secs: 5,
+        };
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let deliveries = CmdbPusher::new(&config).push_run(&db, &summary()).await;
+
+        // 没有检查URL的数据中心 D 不推送
+        let delivered: Vec<_> = deliveries.iter()
+            .map(|d| (d.center_name.as_str(), d.success, d.attempts, d.http_status))
+            .collect();
+        assert_eq!(delivered, [("A & B", true, 1, Some(204)), ("C", true, 1, Some(204))]);
+
+        let validator = schema();
+        let requests = stub.requests.lock().unwrap();
+        let paths: Vec<_> = requests.iter().map(|request| (request.method.as_str(), request.target.as_str())).collect();
+        assert_eq!(paths, [("PUT", "/cmdb/centers/A%20%26%20B/health"), ("PUT", "/cmdb/centers/C/health")]);
+        let documents: Vec<serde_json::Value> = requests.iter().map(|request| serde_json::from_slice(&request.body).unwrap()).collect();
+        for (request, document) in requests.iter().zip(&documents) {
+            assert_eq!(request.header("authorization"), Some("Bearer t0ken"));
+            let errors: Vec<_> = validator.iter_errors(document).map(|e| format!("{} at {}", e, e.instance_path)).collect();
+            assert!(errors.is_empty(), "{} 的文档不符合 schema: {:?}", request.target, errors);
+        }
+
+        let document = &documents[0];
+        assert_eq!(document["center"], "A & B");
+        assert_eq!(document["run_id"], "run-1");
+        assert_eq!(document["last_run_at"], "2026-01-01T01:30:00Z");
+        assert_eq!((&document["total_datasets"], &document["reachable"], &document["broken"]), (&4.into(), &2.into(), &2.into()));
+        assert_eq!(document["broken_by_category"][1], serde_json::json!({ "category": "TIMEOUT", "count": 1 }));
+    }
+
+    #[test]
+    fn schema_fixture_rejects_changed_documents() {
+        // 确认 schema 足够严格：字段缺失、多出或类型变化都应被发现，否则文档结构变化时上面的测试发现不了
+        let summary = summary();
+        let document = serde_json::to_value(CenterHealthDocument::new(&summary, &summary.centers[0], Utc::now())).unwrap();
+        let validator = schema();
+        assert!(validator.is_valid(&document));
+
+        let mut missing = document.clone();
+        missing.as_object_mut().unwrap().remove("reachable_rate");
+        let mut extra = document.clone();
+        extra["reachable_percent"] = 50.into();
+        let mut retyped = document.clone();
+        retyped["broken"] = "2".into();
+        let mut bumped = document;
+        bumped["schema_version"] = 2.into();
+        for changed in [missing, extra, retyped, bumped] {
+            assert!(!validator.is_valid(&changed), "{}", changed);
+        }
+    }
+}
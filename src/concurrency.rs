@@ -0,0 +1,345 @@
+use crate::config::AdaptiveConcurrencyConfig;
+use crate::models::{CenterConcurrency, ConcurrencyAdjustment, MonitorSummary};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use tokio::sync::Notify;
+use tracing::info;
+
+/// 单次监测运行内按数据中心统计并发情况，只使用原子计数，不加锁
+///
+/// 时间以创建跟踪器时为起点的微秒数记录。所有记录在开始检查前一次性创建，
+/// 所以排队等待时间按创建跟踪器到开始检查计算。
+pub struct ConcurrencyTracker {
+    created: Instant,
+    centers: HashMap<String, CenterCounters>,
+}
+
+struct CenterCounters {
+    in_flight: AtomicUsize,
+    peak: AtomicUsize,
+    checks: AtomicUsize,
+    queue_wait_us: AtomicU64,
+    first_start_us: AtomicU64,
+    last_end_us: AtomicU64,
+}
+
+/// 进行中的一次检查，drop 时结束计数
+pub struct CheckGuard<'a> {
+    tracker: &'a ConcurrencyTracker,
+    counters: Option<&'a CenterCounters>,
+}
+
+impl ConcurrencyTracker {
+    pub fn new<'a>(center_names: impl IntoIterator<Item = &'a str>) -> Self {
+        let centers = center_names.into_iter()
+            .map(|name| (name.to_string(), CenterCounters {
+                in_flight: AtomicUsize::new(0),
+                peak: AtomicUsize::new(0),
+                checks: AtomicUsize::new(0),
+                queue_wait_us: AtomicU64::new(0),
+                first_start_us: AtomicU64::new(u64::MAX),
+                last_end_us: AtomicU64::new(0),
+            }))
+            .collect();
+        Self { created: Instant::now(), centers }
+    }
+
+    fn now_us(&self) -> u64 {
+        self.created.elapsed().as_micros() as u64
+    }
+
+    /// 开始一次检查，返回的 guard drop 时视为检查结束
+    pub fn start(&self, center_name: &str) -> CheckGuard<'_> {
+        let counters = self.centers.get(center_name);
+        if let Some(counters) = counters {
+            let now = self.now_us();
+            let in_flight = counters.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+            counters.peak.fetch_max(in_flight, Ordering::Relaxed);
+            counters.queue_wait_us.fetch_add(now, Ordering::Relaxed);
+            counters.first_start_us.fetch_min(now, Ordering::Relaxed);
+        }
+        CheckGuard { tracker: self, counters }
+    }
+
+    /// 把统计结果写入汇总中对应的数据中心
+    pub fn apply(&self, summary: &mut MonitorSummary) {
+        for center in &mut summary.centers {
+            let Some(counters) = self.centers.get(&center.center_name) else {
+                continue;
+            };
+            let checks = counters.checks.load(Ordering::Relaxed);
+            if checks == 0 {
+                continue;
+            }
+            let first_start = counters.first_start_us.load(Ordering::Relaxed);
+            let wall_us = counters.last_end_us.load(Ordering::Relaxed).saturating_sub(first_start);
+            center.concurrency = Some(CenterConcurrency {
+                peak_concurrent: counters.peak.load(Ordering::Relaxed),
+                checks,
+                wall_time_ms: wall_us / 1000,
+                avg_queue_wait_ms: counters.queue_wait_us.load(Ordering::Relaxed) as f64 / checks as f64 / 1000.0,
+                throughput_per_sec: if wall_us > 0 { checks as f64 * 1_000_000.0 / wall_us as f64 } else { 0.0 },
+            });
+        }
+    }
+}
+
+impl Drop for CheckGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(counters) = self.counters {
+            counters.in_flight.fetch_sub(1, Ordering::Relaxed);
+            counters.checks.fetch_add(1, Ordering::Relaxed);
+            counters.last_end_us.fetch_max(self.tracker.now_us(), Ordering::Relaxed);
+        }
+    }
+}
+
+/// 按最近检查中超时和连接错误的比例调整的并发上限（AIMD）
+///
+/// 每收集 `window` 个检查结果判断一次，调整后清空样本，下一次判断只看调整后的检查。
+/// 降低上限时不打断进行中的检查，进行中的检查数会在它们完成后降到新的上限以内。
+pub struct AdaptiveLimiter {
+    config: AdaptiveConcurrencyConfig,
+    started: Instant,
+    state: Mutex<LimiterState>,
+    released: Notify,
+}
+
+struct LimiterState {
+    limit: usize,
+    in_flight: usize,
+    checks: usize,
+    /// 最近的检查是否超时或连接失败
+    samples: VecDeque<bool>,
+    adjustments: Vec<ConcurrencyAdjustment>,
+}
+
+pub struct AdaptivePermit<'a> {
+    limiter: &'a AdaptiveLimiter,
+}
+
+impl AdaptiveLimiter {
+    pub fn new(config: &AdaptiveConcurrencyConfig, initial: usize) -> Self {
+        Self {
+            config: config.clone(),
+            started: Instant::now(),
+            state: Mutex::new(LimiterState {
+                limit: initial.clamp(config.min, config.max),
+                in_flight: 0,
+                checks: 0,
+                samples: VecDeque::with_capacity(config.window),
+                adjustments: Vec::new(),
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    /// 等待进行中的检查数低于当前上限
+    pub async fn acquire(&self) -> AdaptivePermit<'_> {
+        loop {
+            // 先注册等待再检查，避免错过检查之后、等待之前的释放
+            let released = self.released.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < state.limit {
+                    state.in_flight += 1;
+                    return AdaptivePermit { limiter: self };
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// 记录一次检查的结果，`congested` 表示超时或连接错误
+    pub fn record(&self, congested: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.checks += 1;
+        state.samples.push_back(congested);
+        if state.samples.len() < self.config.window {
+            return;
+        }
+        let error_rate = state.samples.iter().filter(|&&c| c).count() as f64 / state.samples.len() as f64;
+        state.samples.clear();
+        let limit = state.limit;
+        let next = if error_rate > self.config.decrease_above {
+            ((limit as f64 * self.config.decrease_factor) as usize).max(self.config.min)
+        } else if error_rate < self.config.increase_below {
+            (limit + self.config.increase_step).min(self.config.max)
+        } else {
+            limit
+        };
+        if next == limit {
+            return;
+        }
+        info!("最近 {} 个检查中超时和连接错误占 {:.1}%，并发数 {} -> {}",
+              self.config.window, error_rate * 100.0, limit, next);
+        state.limit = next;
+        let adjustment = ConcurrencyAdjustment {
+            after_checks: state.checks,
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+            from: limit,
+            to: next,
+            error_rate,
+        };
+        state.adjustments.push(adjustment);
+        drop(state);
+        if next > limit {
+            self.released.notify_waiters();
+        }
+    }
+
+    /// 运行中的并发数调整记录
+    pub fn adjustments(&self) -> Vec<ConcurrencyAdjustment> {
+        self.state.lock().unwrap().adjustments.clone()
+    }
+}
+
+impl Drop for AdaptivePermit<'_> {
+    fn drop(&mut self) {
+        self.limiter.state.lock().unwrap().in_flight -= 1;
+        self.limiter.released.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(centers: &[&str]) -> MonitorSummary {
+        let centers: Vec<_> = centers.iter()
+            .map(|name| serde_json::json!({
+                "center_name": name, "total": 0, "success": 0, "success_rate": 0.0, "local_issues": 0,
+                "remote_issues": 0, "error_categories": [], "sample_failures": [],
+            }))
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "run_id": "r", "started_at": "2026-01-01T00:00:00Z", "finished_at": "2026-01-01T01:00:00Z",
+            "total": 0, "success": 0, "local_issues": 0, "remote_issues": 0, "centers": centers,
+        })).unwrap()
+    }
+
+    #[test]
+    fn tracker_counts_peak_and_checks_per_center() {
+        let tracker = ConcurrencyTracker::new(["A", "B", "C"]);
+        {
+            let _a1 = tracker.start("A");
+            let _a2 = tracker.start("A");
+            {
+                let _a3 = tracker.start("A");
+            }
+            let _b = tracker.start("B");
+            // 未跟踪的数据中心忽略
+            let _unknown = tracker.start("X");
+        }
+        drop(tracker.start("A"));
+
+        let mut summary = summary(&["A", "B", "C"]);
+        tracker.apply(&mut summary);
+        let a = summary.centers[0].concurrency.as_ref().unwrap();
+        assert_eq!((a.peak_concurrent, a.checks), (3, 4));
+        assert!(a.avg_queue_wait_ms >= 0.0 && a.throughput_per_sec >= 0.0);
+        let b = summary.centers[1].concurrency.as_ref().unwrap();
+        assert_eq!((b.peak_concurrent, b.checks), (1, 1));
+        // 没有检查的数据中心不写入
+        assert!(summary.centers[2].concurrency.is_none());
+        assert_eq!(tracker.centers["A"].in_flight.load(Ordering::Relaxed), 0);
+    }
+
+    fn limiter_config() -> AdaptiveConcurrencyConfig {
+        AdaptiveConcurrencyConfig {
+            min: 2,
+            max: 10,
+            window: 4,
+            decrease_above: 0.5,
+            increase_below: 0.25,
+            decrease_factor: 0.5,
+            increase_step: 3,
+        }
+    }
+
+    fn record_window(limiter: &AdaptiveLimiter, congested: usize) {
+        for i in 0..limiter.config.window {
+            limiter.record(i < congested);
+        }
+    }
+
+    #[test]
+    fn initial_limit_is_clamped() {
+        let config = limiter_config();
+        assert_eq!(AdaptiveLimiter::new(&config, 0).limit(), 2);
+        assert_eq!(AdaptiveLimiter::new(&config, 6).limit(), 6);
+        assert_eq!(AdaptiveLimiter::new(&config, 50).limit(), 10);
+    }
+
+    #[test]
+    fn limit_follows_error_rate_per_window() {
+        let limiter = AdaptiveLimiter::new(&limiter_config(), 6);
+        // 窗口未满不调整
+        for _ in 0..3 {
+            limiter.record(true);
+        }
+        assert_eq!(limiter.limit(), 6);
+        limiter.record(true);
+        assert_eq!(limiter.limit(), 3);
+        // 样本已清空，上一窗口的错误不再计入
+        record_window(&limiter, 3);
+        assert_eq!(limiter.limit(), 2);
+        record_window(&limiter, 4);
+        assert_eq!(limiter.limit(), 2, "不低于 min");
+        // 0.25 到 0.5 之间保持不变
+        record_window(&limiter, 1);
+        record_window(&limiter, 2);
+        assert_eq!(limiter.limit(), 2);
+        record_window(&limiter, 0);
+        assert_eq!(limiter.limit(), 5);
+        record_window(&limiter, 0);
+        record_window(&limiter, 0);
+        assert_eq!(limiter.limit(), 10, "不超过 max");
+
+        let adjustments: Vec<_> = limiter.adjustments().iter()
+            .map(|a| (a.after_checks, a.from, a.to))
+            .collect();
+        assert_eq!(adjustments, vec![(4, 6, 3), (8, 3, 2), (24, 2, 5), (28, 5, 8), (32, 8, 10)]);
+        assert_eq!(limiter.adjustments()[0].error_rate, 1.0);
+    }
+
+    #[tokio::test]
+    async fn acquire_waits_for_release_or_higher_limit() {
+        let limiter = AdaptiveLimiter::new(&limiter_config(), 2);
+        let first = limiter.acquire().await;
+        let _second = limiter.acquire().await;
+        let wait = std::time::Duration::from_millis(50);
+        assert!(tokio::time::timeout(wait, limiter.acquire()).await.is_err());
+
+        // 等待中的获取在释放后被唤醒
+        let (third, _) = tokio::join!(
+            tokio::time::timeout(wait * 4, limiter.acquire()),
+            async move {
+                tokio::task::yield_now().await;
+                drop(first);
+            },
+        );
+        let third = third.expect("释放后应能获取");
+        assert!(tokio::time::timeout(wait, limiter.acquire()).await.is_err());
+
+        // 上限提高后等待中的获取被唤醒
+        let (fourth, _) = tokio::join!(
+            tokio::time::timeout(wait * 4, limiter.acquire()),
+            async {
+                tokio::task::yield_now().await;
+                record_window(&limiter, 0);
+            },
+        );
+        let _fourth = fourth.expect("上限提高后应能获取");
+        assert_eq!(limiter.limit(), 5);
+        assert_eq!(limiter.state.lock().unwrap().in_flight, 3);
+        drop(third);
+        assert_eq!(limiter.state.lock().unwrap().in_flight, 2);
+    }
+}
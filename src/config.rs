@@ -1,5 +1,7 @@
-use anyhow::Result;
-use serde::Deserialize;
+use anyhow::{Context, Result};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 
 #[derive(Debug, Deserialize, Clone)]
@@ -8,40 +10,1709 @@ pub struct Config {
     pub mongodb: MongoDBConfig,
     pub duckdb: DuckDBConfig,
     pub monitor: MonitorConfig,
+    #[serde(default)]
+    pub heartbeat: HeartbeatConfig,
+    #[serde(default)]
+    pub alerts: AlertConfig,
+    /// 每次监测后发送邮件报告，需要启用 email feature
+    #[serde(default)]
+    pub email: Option<EmailConfig>,
+    #[serde(default)]
+    pub report: ReportConfig,
+    #[serde(default)]
+    pub export: ExportConfig,
+    #[serde(default)]
+    pub integrations: IntegrationsConfig,
+    #[serde(default)]
+    pub http: HttpConfig,
+    #[serde(default)]
+    pub janitor: JanitorConfig,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Center {
     pub name: String,
-    #[serde(rename = "secretKey")]
+    /// 不写入运行配置快照
+    #[serde(rename = "secretKey", skip_serializing)]
     pub secret_key: String,
     pub url: String,
     pub enabled: bool,
+    /// 数据获取 cron 表达式，优先于 fetch_interval_days
+    #[serde(default)]
+    pub fetch_cron: Option<String>,
+    /// 数据获取间隔天数，未配置时使用 monitor.fetch_interval_days
+    #[serde(default)]
+    pub fetch_interval_days: Option<u32>,
+    /// URL监测 cron 表达式，优先于 check_interval_days
+    #[serde(default)]
+    pub check_cron: Option<String>,
+    /// URL监测间隔天数，未配置时使用 monitor.check_interval_days
+    #[serde(default)]
+    pub check_interval_days: Option<u32>,
+    /// 数据集列表接口的分页方式，返回分页结构时按此翻页
+    #[serde(default)]
+    pub pagination: Option<PaginationConfig>,
+    /// 数据集列表接口的请求方法
+    #[serde(default)]
+    pub list_method: ListMethod,
+    /// POST 请求数据集列表时发送的 JSON 请求体
+    #[serde(default)]
+    pub list_body: Option<serde_json::Value>,
+    /// 认证响应中 ticket.expires 的含义，默认按数值大小推断
+    #[serde(default)]
+    pub token_expires: TokenExpires,
+    /// 服务名映射：标准名（DATASET_LIST、GET_DATASET_DETAILS）-> 该数据中心 serviceList 中的名称
+    #[serde(default)]
+    pub service_names: HashMap<String, String>,
+    /// 数据集列表接口支持 updatedSince 参数，只返回该时间之后有变化的ID
+    #[serde(default)]
+    pub supports_updated_since: bool,
+    /// 每连续增量获取多少次后做一次完整获取，用于发现被删除的数据集
+    #[serde(default = "default_full_fetch_every")]
+    pub full_fetch_every: usize,
+    /// 详情接口每次请求的ID数，大于 1 时以逗号分隔传入并返回数组
+    #[serde(default = "default_detail_batch_size")]
+    pub detail_batch_size: usize,
+    /// 是否计算响应体指纹，未配置时使用 monitor.fingerprint
+    #[serde(default)]
+    pub fingerprint: Option<bool>,
+    /// 检查需要认证的数据集URL时使用的凭证，按顺序匹配第一条；不写入运行配置快照
+    #[serde(default, skip_serializing)]
+    pub url_credentials: Vec<UrlCredential>,
+    /// 检查前从数据集URL中去掉的查询参数（如会过期的会话令牌、签名），原URL仍按原样保存
+    #[serde(default)]
+    pub strip_query_params: Vec<String>,
+    /// 保存数据集的 MongoDB 集合，未配置时使用名称转换成的 ASCII 名称（见 [`collection_slug`]）
+    #[serde(default)]
+    pub collection: Option<String>,
+    /// 检查数据集URL的请求方法
+    #[serde(default)]
+    pub check_method: CheckMethod,
+    /// 检查数据集URL时最多跟随的重定向次数，未配置时使用 monitor.max_redirects
+    #[serde(default)]
+    pub max_redirects: Option<usize>,
+    /// 详情接口每秒最多请求数，未配置时不限速
+    #[serde(default)]
+    pub detail_requests_per_sec: Option<f64>,
+    /// 该数据中心数据获取成功后请求的心跳地址，未配置时使用 heartbeat.fetch_url；不写入运行配置快照
+    #[serde(default, skip_serializing)]
+    pub fetch_heartbeat_url: Option<String>,
+    /// 该数据中心URL监测成功后请求的心跳地址，未配置时使用 heartbeat.monitor_url；不写入运行配置快照
+    #[serde(default, skip_serializing)]
+    pub monitor_heartbeat_url: Option<String>,
+}
+
+/// 检查URL时没有跟随的 3xx 响应的处理
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TerminalRedirect {
+    /// 计为成功，success_statuses 自动包含 3xx
+    Success,
+    /// 计为失败：超过重定向上限为 TOO_MANY_REDIRECTS_ERROR，没有 Location 为 SERVER_ERROR
+    Failure,
+    /// 计为失败，错误类别为 REDIRECTED，与其他错误分开统计
+    Redirected,
+}
+
+/// 多个数据中心共用的URL在统计中的归属方式
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SharedUrlAttribution {
+    /// 计入每个登记了该URL的数据中心
+    #[default]
+    All,
+    /// 只计入最早检查该URL的数据中心（同一次运行中首次出现时按配置顺序）
+    FirstSeen,
+}
+
+/// 共用URL的检查结果仍按各数据中心分别记录，只是统计时只计入归属的数据中心，
+/// 记录的 attributed_center 和 attribution_rule 说明归属及其依据
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct SharedUrlConfig {
+    #[serde(default)]
+    pub attribution: SharedUrlAttribution,
+    /// 指定URL归属的数据中心（URL -> 数据中心名称），优先于 attribution；
+    /// 该数据中心没有登记这个URL时不生效
+    #[serde(default)]
+    pub primary: BTreeMap<String, String>,
+}
+
+impl TerminalRedirect {
+    pub const ALL: [TerminalRedirect; 3] = [Self::Success, Self::Failure, Self::Redirected];
+}
+
+/// 检查数据集URL的请求方法。head 先发 HEAD，收到错误响应时再用 GET 确认；
+/// auto 在此基础上记住 HEAD 被拒绝而 GET 成功的主机，之后的运行直接用 GET（见 monitor.head_retest_runs）。
+/// 计算响应体指纹时始终用 GET
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckMethod {
+    Head,
+    #[default]
+    Get,
+    Auto,
+}
+
+/// 数据集URL的认证凭证，配置 username/password（Basic）或 token（Bearer）之一
+#[derive(Debug, Deserialize, Clone)]
+pub struct UrlCredential {
+    /// 匹配URL的正则表达式，未配置时匹配该数据中心的所有URL
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<SecretValue>,
+    #[serde(default)]
+    pub token: Option<SecretValue>,
+}
+
+/// 密钥：直接写值，或 `{env: 变量名}` 从环境变量读取，或 `{file: 路径}` 从文件读取（去掉首尾空白）
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum SecretValue {
+    Value(String),
+    Env { env: String },
+    File { file: String },
+}
+
+impl SecretValue {
+    pub fn resolve(&self) -> Result<String> {
+        match self {
+            SecretValue::Value(value) => Ok(value.clone()),
+            SecretValue::Env { env } => std::env::var(env).with_context(|| format!("环境变量 {} 未设置", env)),
+            SecretValue::File { file } => Ok(fs::read_to_string(file)
+                .with_context(|| format!("读取密钥文件失败: {}", file))?
+                .trim()
+                .to_string()),
+        }
+    }
+}
+
+/// 解析后的URL认证方式
+#[derive(Clone)]
+pub enum UrlAuth {
+    Basic { username: String, password: String },
+    Bearer(String),
+}
+
+impl UrlCredential {
+    pub fn resolve(&self) -> Result<UrlAuth> {
+        match (&self.username, &self.token) {
+            (Some(username), None) => Ok(UrlAuth::Basic {
+                username: username.clone(),
+                password: self.password.as_ref().map(SecretValue::resolve).transpose()?.unwrap_or_default(),
+            }),
+            (None, Some(token)) => Ok(UrlAuth::Bearer(token.resolve()?)),
+            _ => anyhow::bail!("url_credentials 需要配置 username/password 或 token 之一"),
+        }
+    }
+}
+
+/// ticket.expires 的解释方式
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenExpires {
+    /// 按数值大小推断：毫秒时间戳、秒时间戳、毫秒时长或秒时长
+    #[default]
+    Auto,
+    Seconds,
+    Millis,
+    EpochSeconds,
+    EpochMillis,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ListMethod {
+    #[default]
+    Get,
+    Post,
+}
+
+/// 数据集列表分页配置
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PaginationConfig {
+    #[serde(default)]
+    pub style: PaginationStyle,
+    #[serde(default = "default_page_size")]
+    pub page_size: usize,
+    /// 最多请求的页数，防止接口返回错误的 total 时无限翻页
+    #[serde(default = "default_max_pages")]
+    pub max_pages: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PaginationStyle {
+    /// `page`（从 1 开始）和 `pageSize` 参数
+    #[default]
+    Page,
+    /// `offset` 和 `limit` 参数
+    Offset,
+}
+
+impl PaginationStyle {
+    /// 第 `index` 页（从 0 开始）的查询参数
+    pub fn query(&self, index: usize, page_size: usize) -> Vec<(&'static str, String)> {
+        match self {
+            PaginationStyle::Page => vec![("page", (index + 1).to_string()), ("pageSize", page_size.to_string())],
+            PaginationStyle::Offset => vec![("offset", (index * page_size).to_string()), ("limit", page_size.to_string())],
+        }
+    }
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        Self {
+            style: PaginationStyle::default(),
+            page_size: default_page_size(),
+            max_pages: default_max_pages(),
+        }
+    }
+}
+
+fn default_full_fetch_every() -> usize {
+    10
+}
+
+fn default_detail_batch_size() -> usize {
+    1
+}
+
+fn default_page_size() -> usize {
+    100
+}
+
+fn default_max_pages() -> usize {
+    1000
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct MongoDBConfig {
     pub uri: String,
     pub database: String,
+    /// 未配置 collection 的数据中心直接用名称作为集合名（旧版本的行为），
+    /// 迁移到新集合名前保持开启，迁移见 `data_fetch migrate-collections`
+    #[serde(default)]
+    pub legacy_collection_names: bool,
+}
+
+/// 系统自己使用的集合，数据中心不能使用
+const RESERVED_COLLECTIONS: [&str; 4] = ["processed_dataset_ids", "fetch_audit", "dataset_duplicates", "run_locks"];
+
+/// 把数据中心名称转换为 ASCII 集合名：字母数字转小写，其他字符合并为下划线；
+/// 名称含非 ASCII 字符时追加名称哈希的前 8 位，避免不同的中文名称得到相同的集合名
+pub fn collection_slug(name: &str) -> String {
+    use sha2::{Digest, Sha256};
+    let mut slug = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    let slug = slug.trim_end_matches('_');
+    if name.is_ascii() && !slug.is_empty() {
+        return slug.to_string();
+    }
+    let hash: String = Sha256::digest(name.as_bytes()).iter().take(4).map(|b| format!("{:02x}", b)).collect();
+    if slug.is_empty() {
+        format!("center_{}", hash)
+    } else {
+        format!("{}_{}", slug, hash)
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct DuckDBConfig {
     pub path: String,
+    /// 统计查询（趋势、可用率、问题URL等）的超时秒数，超时后中断查询，0 表示不限制
+    #[serde(default = "default_query_timeout_secs")]
+    pub query_timeout_secs: u64,
+    /// 各查询组单独的超时秒数，未配置的组使用 query_timeout_secs
+    #[serde(default)]
+    pub query_timeouts: QueryTimeouts,
+    /// 数据库文件（含 WAL）的软限制（MB）。监测运行结束时超过则告警，
+    /// 并删除早于 prune_retention_days 的检查记录；未配置时只记录大小
+    #[serde(default)]
+    pub storage_soft_limit_mb: Option<u64>,
+    /// 超过软限制时保留最近多少天的检查记录
+    #[serde(default = "default_prune_retention_days")]
+    pub prune_retention_days: u32,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+fn default_query_timeout_secs() -> u64 {
+    120
+}
+
+fn default_prune_retention_days() -> u32 {
+    365
+}
+
+/// 统计查询的分组，各组可以单独配置超时
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QueryGroup {
+    /// 可用率、状态码、主机等汇总统计
+    Stats,
+    /// 按天、小时划分的趋势
+    Trends,
+    /// 单个URL的检查历史、问题URL、内容变化
+    History,
+    /// 失效链接、URL问题、共用URL等报告
+    Reports,
+}
+
+impl QueryGroup {
+    pub const ALL: [QueryGroup; 4] = [QueryGroup::Stats, QueryGroup::Trends, QueryGroup::History, QueryGroup::Reports];
+}
+
+/// duckdb.query_timeouts，各查询组的超时秒数，0 表示不限制
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct QueryTimeouts {
+    #[serde(default)]
+    pub stats: Option<u64>,
+    #[serde(default)]
+    pub trends: Option<u64>,
+    #[serde(default)]
+    pub history: Option<u64>,
+    #[serde(default)]
+    pub reports: Option<u64>,
+}
+
+impl DuckDBConfig {
+    /// 查询组的超时时间，组没有单独配置时取 query_timeout_secs，为 0 时不限制
+    pub fn query_timeout(&self, group: QueryGroup) -> Option<std::time::Duration> {
+        let timeouts = &self.query_timeouts;
+        let secs = match group {
+            QueryGroup::Stats => timeouts.stats,
+            QueryGroup::Trends => timeouts.trends,
+            QueryGroup::History => timeouts.history,
+            QueryGroup::Reports => timeouts.reports,
+        }.unwrap_or(self.query_timeout_secs);
+        (secs > 0).then(|| std::time::Duration::from_secs(secs))
+    }
+
+    pub fn storage_soft_limit_bytes(&self) -> Option<u64> {
+        self.storage_soft_limit_mb.map(|mb| mb * 1024 * 1024)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MonitorConfig {
     pub fetch_interval_days: u32,
     pub check_interval_days: u32,
     pub http_timeout_secs: u64,
     pub max_concurrent: usize,
+    /// 超时、连接失败和 5xx 的检查最多重试的次数，重试前等待 2、4、8... 秒
+    #[serde(default)]
+    pub check_retries: u32,
+    /// 检查URL和请求数据中心接口时最多跟随的重定向次数，可按数据中心覆盖
+    #[serde(default = "default_max_redirects")]
+    pub max_redirects: usize,
+    /// 检查URL时没有跟随的 3xx 响应（超过重定向上限、没有 Location）的处理，
+    /// 未配置时 success_statuses 包含 3xx 则为 success，否则为 failure
+    #[serde(default)]
+    pub terminal_redirect: Option<TerminalRedirect>,
+    /// 检查URL时是否跟随 HTTPS 到 HTTP 的重定向，为 false 时不跟随，记为 INSECURE_REDIRECT
+    #[serde(default = "default_true")]
+    pub follow_insecure_redirects: bool,
+    /// check_method 为 auto 时，记住直接用 GET 的主机每隔多少次运行重新尝试一次 HEAD
+    #[serde(default = "default_head_retest_runs")]
+    pub head_retest_runs: u32,
+    /// 检查URL的顺序：config 按配置中数据中心的顺序，interleaved 在数据中心之间轮流，
+    /// shuffled 每次运行随机打乱（种子记录在运行汇总中）
+    #[serde(default)]
+    pub dispatch_order: DispatchOrder,
+    /// 按超时和连接错误的比例自动调整并发数，从 max_concurrent 开始；不配置时并发数固定
+    #[serde(default)]
+    pub adaptive_concurrency: Option<AdaptiveConcurrencyConfig>,
+    /// 响应较慢的主机使用更长的超时，优先于自动识别的结果
+    #[serde(default)]
+    pub slow_hosts: Vec<SlowHost>,
+    /// 自动识别慢主机，未配置时不识别
+    #[serde(default)]
+    pub slow_host_learning: Option<SlowHostLearning>,
+    /// 监测运行中每隔多少秒输出一行检查进度（已检查数、成功率、吞吐、预计剩余时间），0 表示不输出
+    #[serde(default = "default_progress_interval_secs")]
+    pub progress_interval_secs: u64,
+    /// 检测各数据中心响应时间变慢，未配置时不检测
+    #[serde(default)]
+    pub response_time_regression: Option<RegressionDetection>,
+    /// 按 cron 单独检查带某个标签的数据集（如 critical），可比各数据中心的完整运行更频繁
+    #[serde(default)]
+    pub tag_schedules: Vec<TagSchedule>,
+    /// 待处理ID超过该天数仍无法获取详情时标记为过期，0 表示不过期
+    #[serde(default = "default_max_pending_age_days")]
+    pub max_pending_age_days: u32,
+    /// 跨数据中心重复的数据集每组只检查一次，结果归到组内所有成员
+    #[serde(default)]
+    pub check_duplicates_once: bool,
+    /// 多个数据中心登记了同一URL时，统计中归到哪个数据中心
+    #[serde(default)]
+    pub shared_urls: SharedUrlConfig,
+    /// 单次运行中同一主机连续多少次没有响应后熔断，跳过该主机剩余的URL，0 表示不熔断
+    #[serde(default = "default_circuit_breaker_failures")]
+    pub circuit_breaker_failures: u32,
+    /// 运行结束前对熔断的主机重新探测一次，恢复后重新检查被跳过的URL
+    #[serde(default = "default_true")]
+    pub circuit_breaker_reprobe: bool,
+    /// 每完成多少个URL检查写一次库，同时也是内存中等待写库的记录上限
+    #[serde(default = "default_result_batch_size")]
+    pub result_batch_size: usize,
+    /// 检查所用的出口 IP，配置后不再查询 egress_ip_url
+    #[serde(default)]
+    pub egress_ip: Option<String>,
+    /// 运行开始时查询出口 IP 的地址，响应体为纯文本 IP（如 https://api.ipify.org），查询失败不影响运行
+    #[serde(default)]
+    pub egress_ip_url: Option<String>,
+    /// 异常退出或取消的运行在多少小时内可以续跑，超过的标记为 aborted，0 表示不续跑
+    #[serde(default = "default_resume_max_age_hours")]
+    pub resume_max_age_hours: u32,
+    /// 检查结果写库失败（重试一次后）时写入的溢出文件目录，每次运行一个 {run_id}.ndjson
+    #[serde(default = "default_spill_dir")]
+    pub spill_dir: String,
+    /// 保存的响应头的字节数上限，超出部分截断；Authorization、Set-Cookie 等凭证类响应头的值不保存
+    #[serde(default = "default_max_header_bytes")]
+    pub max_header_bytes: usize,
+    /// 保存的错误信息的字节数上限，超出时省略中间部分
+    #[serde(default = "default_max_error_msg_bytes")]
+    pub max_error_msg_bytes: usize,
+    /// 保存的错误详情的字节数上限，超出时省略中间部分
+    #[serde(default = "default_max_error_detail_bytes")]
+    pub max_error_detail_bytes: usize,
+    /// 检查 file:// URL（只检查本机路径是否存在），默认关闭，此时按不支持的协议记录
+    #[serde(default)]
+    pub check_file_urls: bool,
+    /// 成功的检查读取响应体开头计算指纹，与上次不同时标记 content_changed；可按数据中心覆盖
+    #[serde(default)]
+    pub fingerprint: bool,
+    /// 计算指纹时最多读取的响应体大小（KB）
+    #[serde(default = "default_fingerprint_max_kb")]
+    pub fingerprint_max_kb: usize,
+    /// 计为成功的HTTP状态码，默认 2xx
+    #[serde(default)]
+    pub success_statuses: SuccessStatuses,
+    /// 数据中心接口响应体（解压后）的大小上限，超出时中止读取
+    #[serde(default = "default_max_response_bytes")]
+    pub max_response_bytes: usize,
+    /// 数据获取时并发请求数据集详情的数量
+    #[serde(default = "default_fetch_max_concurrent")]
+    pub fetch_max_concurrent: usize,
+    /// 一次获取所有数据中心时同时进行的数据中心数
+    #[serde(default = "default_fetch_center_concurrency")]
+    pub fetch_center_concurrency: usize,
+    /// 大于 0 时由常驻的详情工作任务（数量为此值）持续处理各数据中心待处理的ID，定时获取和 `data_fetch run` 只发现新ID；
+    /// 也可以用 `data_fetch --workers` 单独运行工作任务
+    #[serde(default)]
+    pub detail_workers: usize,
+    /// 工作任务每次从一个数据中心取出的待处理ID数
+    #[serde(default = "default_detail_worker_batch")]
+    pub detail_worker_batch: usize,
+    /// 所有数据中心都没有处理成功的ID时，工作任务休眠的秒数
+    #[serde(default = "default_detail_worker_idle_secs")]
+    pub detail_worker_idle_secs: u64,
+    /// 认领后超过多少分钟仍在处理中的ID（认领者崩溃或被中断）可以被重新认领
+    #[serde(default = "default_claim_timeout_minutes")]
+    pub claim_timeout_minutes: u32,
+    /// 任务运行期间再次触发时排队一次，而不是直接跳过
+    #[serde(default)]
+    pub queue_overlapping_runs: bool,
+    /// 任务运行状态文件目录
+    #[serde(default = "default_state_dir")]
+    pub state_dir: String,
+    /// 启动时超过调度间隔多少小时才补跑
+    #[serde(default = "default_catch_up_grace_hours")]
+    pub catch_up_grace_hours: u32,
+    /// 定时任务 cron 表达式所在时区（IANA 名称，如 Asia/Shanghai）
+    #[serde(default = "default_schedule_timezone")]
+    pub schedule_timezone: String,
+    /// 多实例部署时通过 MongoDB 租约锁避免重复运行
+    #[serde(default)]
+    pub distributed_lock: bool,
+    /// 租约有效期（不小于 3 秒），运行期间每 1/3 有效期续期一次
+    #[serde(default = "default_lock_ttl_secs")]
+    pub lock_ttl_secs: u64,
+    /// 定时触发后随机延迟 [0, N] 分钟再开始运行，手动触发不延迟
+    #[serde(default)]
+    pub schedule_jitter_minutes: u32,
+    /// 连续失败后的初始退避分钟数，每多失败一次翻倍
+    #[serde(default = "default_failure_backoff_minutes")]
+    pub failure_backoff_minutes: u32,
+    /// 退避时间上限（小时）
+    #[serde(default = "default_max_failure_backoff_hours")]
+    pub max_failure_backoff_hours: u32,
+    /// 连续失败多少次后告警
+    #[serde(default = "default_failure_alert_threshold")]
+    pub failure_alert_threshold: u32,
+    /// 将各数据中心的 token 保存到 `{state_dir}/tokens.json`，重启或下次运行时复用；
+    /// token 会以明文落盘，对安全要求高的部署保持关闭
+    #[serde(default)]
+    pub persist_tokens: bool,
+    /// 数据集详情解析失败时保存原始响应的目录
+    #[serde(default = "default_raw_responses_dir")]
+    pub raw_responses_dir: String,
+    /// 单个原始响应保存的大小上限（KB），超出部分截断，截断的响应无法重新解析
+    #[serde(default = "default_raw_response_max_kb")]
+    pub raw_response_max_kb: usize,
+    /// 原始响应保留天数
+    #[serde(default = "default_raw_response_retention_days")]
+    pub raw_response_retention_days: u32,
+    /// 所有原始响应的总大小上限（MB），清理时超出部分从最旧的开始删除
+    #[serde(default = "default_raw_response_total_mb")]
+    pub raw_response_total_mb: u64,
+    /// 每次数据获取后把各数据中心的ID积压写入该文件（Prometheus 文本格式，供 node_exporter textfile collector 采集），不配置时不写
+    #[serde(default)]
+    pub backlog_metrics_file: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DispatchOrder {
+    #[default]
+    Config,
+    Interleaved,
+    Shuffled,
+}
+
+/// 自适应并发：每 `window` 个检查计算一次超时和连接错误的比例，高于 `decrease_above` 时
+/// 并发数乘以 `decrease_factor`，低于 `increase_below` 时加 `increase_step`，始终在 [min, max] 内
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AdaptiveConcurrencyConfig {
+    #[serde(default = "default_adaptive_min")]
+    pub min: usize,
+    pub max: usize,
+    #[serde(default = "default_adaptive_window")]
+    pub window: usize,
+    #[serde(default = "default_adaptive_decrease_above")]
+    pub decrease_above: f64,
+    #[serde(default = "default_adaptive_increase_below")]
+    pub increase_below: f64,
+    #[serde(default = "default_adaptive_decrease_factor")]
+    pub decrease_factor: f64,
+    #[serde(default = "default_adaptive_increase_step")]
+    pub increase_step: usize,
+}
+
+/// 只检查带 `tag` 标签的数据集的定时任务
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TagSchedule {
+    pub tag: String,
+    /// cron 表达式，时区为 schedule_timezone
+    pub cron: String,
+}
+
+/// 检查该主机的URL时使用的超时
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SlowHost {
+    /// 主机名，不区分大小写
+    pub host: String,
+    pub timeout_secs: u64,
+}
+
+/// 每次运行结束后统计最近 `lookback_days` 天各主机成功的检查，至少 `min_checks` 次、
+/// 其中不少于 `min_share` 的响应时间超过 http_timeout_secs 的 `slow_ratio` 倍的主机，
+/// 之后的运行使用 `timeout_secs` 超时；按慢响应比例最多取 `max_hosts` 个
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SlowHostLearning {
+    pub timeout_secs: u64,
+    #[serde(default = "default_slow_ratio")]
+    pub slow_ratio: f64,
+    #[serde(default = "default_slow_min_share")]
+    pub min_share: f64,
+    #[serde(default = "default_slow_min_checks")]
+    pub min_checks: u32,
+    #[serde(default = "default_slow_lookback_days")]
+    pub lookback_days: u32,
+    #[serde(default = "default_slow_max_hosts")]
+    pub max_hosts: usize,
+}
+
+/// 数据中心本次成功检查的响应时间中位数超过基线的 `factor` 倍时记为变慢。基线为之前最近 `baseline_runs` 次
+/// 包含该数据中心的运行各自中位数的中位数，取消的运行、标签运行以及本地网络问题占一半以上的运行不计入；
+/// 可用的运行少于 `min_baseline_runs` 时不判断
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RegressionDetection {
+    pub factor: f64,
+    #[serde(default = "default_regression_baseline_runs")]
+    pub baseline_runs: usize,
+    #[serde(default = "default_regression_min_baseline_runs")]
+    pub min_baseline_runs: usize,
+}
+
+fn default_regression_baseline_runs() -> usize {
+    10
+}
+
+fn default_regression_min_baseline_runs() -> usize {
+    3
+}
+
+fn default_slow_ratio() -> f64 {
+    0.8
+}
+
+fn default_slow_min_share() -> f64 {
+    0.5
+}
+
+fn default_slow_min_checks() -> u32 {
+    5
+}
+
+fn default_slow_lookback_days() -> u32 {
+    7
+}
+
+fn default_slow_max_hosts() -> usize {
+    20
+}
+
+fn default_adaptive_min() -> usize {
+    2
+}
+
+fn default_adaptive_window() -> usize {
+    50
+}
+
+fn default_adaptive_decrease_above() -> f64 {
+    0.2
+}
+
+fn default_adaptive_increase_below() -> f64 {
+    0.05
+}
+
+fn default_adaptive_decrease_factor() -> f64 {
+    0.5
+}
+
+fn default_adaptive_increase_step() -> usize {
+    1
+}
+
+fn default_max_redirects() -> usize {
+    10
+}
+
+fn default_head_retest_runs() -> u32 {
+    10
+}
+
+fn default_circuit_breaker_failures() -> u32 {
+    10
+}
+
+fn default_result_batch_size() -> usize {
+    1000
+}
+
+fn default_max_header_bytes() -> usize {
+    4096
+}
+
+fn default_max_error_msg_bytes() -> usize {
+    1024
+}
+
+fn default_max_error_detail_bytes() -> usize {
+    4096
+}
+
+fn default_fingerprint_max_kb() -> usize {
+    64
+}
+
+fn default_raw_responses_dir() -> String {
+    "./data/raw_responses".to_string()
+}
+
+fn default_raw_response_max_kb() -> usize {
+    1024
+}
+
+fn default_raw_response_retention_days() -> u32 {
+    30
+}
+
+fn default_raw_response_total_mb() -> u64 {
+    1024
+}
+
+fn default_max_pending_age_days() -> u32 {
+    30
+}
+
+fn default_max_response_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_fetch_center_concurrency() -> usize {
+    3
+}
+
+fn default_fetch_max_concurrent() -> usize {
+    8
+}
+
+fn default_detail_worker_batch() -> usize {
+    500
+}
+
+fn default_detail_worker_idle_secs() -> u64 {
+    60
+}
+
+fn default_progress_interval_secs() -> u64 {
+    30
+}
+
+fn default_claim_timeout_minutes() -> u32 {
+    60
+}
+
+fn default_resume_max_age_hours() -> u32 {
+    24
+}
+
+fn default_spill_dir() -> String {
+    "./data/spill".to_string()
+}
+
+fn default_state_dir() -> String {
+    "./data/state".to_string()
+}
+
+fn default_catch_up_grace_hours() -> u32 {
+    6
+}
+
+fn default_schedule_timezone() -> String {
+    "UTC".to_string()
+}
+
+fn default_lock_ttl_secs() -> u64 {
+    300
+}
+
+fn default_failure_backoff_minutes() -> u32 {
+    60
+}
+
+fn default_max_failure_backoff_hours() -> u32 {
+    24
+}
+
+fn default_failure_alert_threshold() -> u32 {
+    3
+}
+
+/// 运行成功后的心跳配置
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct HeartbeatConfig {
+    /// 数据获取成功后请求的地址，数据中心可用 fetch_heartbeat_url 单独配置
+    pub fetch_url: Option<String>,
+    /// URL监测成功后请求的地址，数据中心可用 monitor_heartbeat_url 单独配置
+    pub monitor_url: Option<String>,
+    /// 运行开始时是否请求 `{url}/start`
+    pub ping_start: bool,
+    /// 本地心跳文件目录，每个数据中心的任务一个文件（如 `data_fetch-{数据中心}.heartbeat`），内容为最近一次成功的时间
+    pub file_dir: Option<String>,
+}
+
+/// 监测告警配置
+#[derive(Debug, Deserialize, Clone)]
+pub struct AlertConfig {
+    /// 数据中心成功率（百分比）低于该值时告警
+    #[serde(default)]
+    pub min_success_rate: Option<f64>,
+    /// 成功率较上一次运行下降超过该百分点时告警
+    #[serde(default)]
+    pub max_rate_drop: Option<f64>,
+    /// 同一告警的冷却时间，期间不重复发送
+    #[serde(default = "default_alert_cooldown_minutes")]
+    pub cooldown_minutes: u64,
+    /// 本次运行由成功变为失败的URL达到该数量时告警，未配置时不告警
+    #[serde(default)]
+    pub new_failure_min_count: Option<usize>,
+    /// 新失败告警是否排除疑似本地网络问题的URL
+    #[serde(default = "default_true")]
+    pub new_failure_exclude_local: bool,
+    /// 一次运行中疑似本地网络问题的占比（百分比）超过该值时告警
+    #[serde(default)]
+    pub local_issue_percent: Option<f64>,
+    /// 本地网络问题告警的最小样本数，URL数量低于该值的运行不参与判断
+    #[serde(default = "default_local_issue_min_sample")]
+    pub local_issue_min_sample: usize,
+    /// 数据集列表数量较上一次成功获取下降超过该百分比时告警
+    #[serde(default)]
+    pub fetch_count_drop_percent: Option<f64>,
+    /// 待处理ID数较上一次成功获取增加超过该数量时告警
+    #[serde(default)]
+    pub fetch_backlog_growth: Option<u64>,
+    /// 新失败告警中每个数据中心最多列出的URL数
+    #[serde(default = "default_new_failure_max_per_center")]
+    pub new_failure_max_per_center: usize,
+    /// 只针对带某个标签的数据集的告警规则，通常比全局规则更严格
+    #[serde(default)]
+    pub tag_rules: Vec<TagAlertRule>,
+    /// 数据中心响应时间变慢（见 monitor.response_time_regression）时告警
+    #[serde(default)]
+    pub response_time_regression: bool,
+    #[serde(default)]
+    pub targets: Vec<AlertTarget>,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            min_success_rate: None,
+            max_rate_drop: None,
+            cooldown_minutes: default_alert_cooldown_minutes(),
+            new_failure_min_count: None,
+            new_failure_exclude_local: true,
+            new_failure_max_per_center: default_new_failure_max_per_center(),
+            local_issue_percent: None,
+            local_issue_min_sample: default_local_issue_min_sample(),
+            fetch_count_drop_percent: None,
+            fetch_backlog_growth: None,
+            tag_rules: Vec::new(),
+            response_time_regression: false,
+            targets: Vec::new(),
+        }
+    }
+}
+
+/// 带标签 `tag` 的数据集的告警规则，每次运行（包括只检查该标签的运行）结束后评估
+#[derive(Debug, Deserialize, Clone)]
+pub struct TagAlertRule {
+    pub tag: String,
+    /// 带该标签的数据集成功率（百分比）低于该值时告警
+    #[serde(default)]
+    pub min_success_rate: Option<f64>,
+    /// 带该标签的数据集中由成功变为失败的URL达到该数量时告警
+    #[serde(default)]
+    pub new_failure_min_count: Option<usize>,
+}
+
+/// 告警接收方
+#[derive(Debug, Deserialize, Clone)]
+pub struct AlertTarget {
+    #[serde(rename = "type", default)]
+    pub kind: AlertTargetKind,
+    pub url: String,
+    /// 钉钉机器人加签密钥（SEC 开头）
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertTargetKind {
+    /// 通用 webhook，以 JSON POST 发送告警内容
+    #[default]
+    Webhook,
+    /// 钉钉自定义机器人
+    Dingtalk,
+    /// 企业微信群机器人
+    Wechat,
+}
+
+/// SMTP 邮件报告配置
+#[derive(Debug, Deserialize, Clone)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    #[serde(default = "default_smtp_port")]
+    pub smtp_port: u16,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub from: String,
+    pub recipients: Vec<String>,
+    #[serde(default)]
+    pub tls: EmailTls,
+    /// 邮件中附带的监测看板链接
+    #[serde(default)]
+    pub dashboard_url: Option<String>,
+    /// 附带本次运行各数据中心的链接报告，需要配置 report.link_report_dir
+    #[serde(default)]
+    pub attach_link_reports: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailTls {
+    /// 明文连接
+    None,
+    /// 先明文连接再升级
+    #[default]
+    Starttls,
+    /// 直接 TLS 连接
+    Tls,
+}
+
+/// 周报配置
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReportConfig {
+    /// 周报 HTML 文件输出目录
+    #[serde(default = "default_report_dir")]
+    pub output_dir: String,
+    /// 定时生成周报的 cron 表达式（按 schedule_timezone），未配置时只能用 report 子命令生成
+    #[serde(default)]
+    pub weekly_cron: Option<String>,
+    /// 每次监测运行后的 Markdown 汇总目录
+    #[serde(default = "default_run_summary_dir")]
+    pub run_summary_dir: String,
+    /// 保留最近的运行汇总文件数，为 0 时不写入
+    #[serde(default = "default_run_summary_keep")]
+    pub run_summary_keep: usize,
+    /// 每次监测运行后写入各数据中心失败链接报告（TSV）的目录，未配置时不写入
+    #[serde(default)]
+    pub link_report_dir: Option<String>,
+    /// 每次监测运行后重新生成的概览页（单个 HTML 文件）路径，未配置时不生成
+    #[serde(default)]
+    pub dashboard_path: Option<String>,
+    /// 概览页统计最近多少天
+    #[serde(default = "default_dashboard_days")]
+    pub dashboard_days: u32,
+}
+
+fn default_dashboard_days() -> u32 {
+    7
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            output_dir: default_report_dir(),
+            weekly_cron: None,
+            run_summary_dir: default_run_summary_dir(),
+            run_summary_keep: default_run_summary_keep(),
+            link_report_dir: None,
+            dashboard_path: None,
+            dashboard_days: default_dashboard_days(),
+        }
+    }
+}
+
+/// 定时清理崩溃或中断后遗留的状态，见 [`crate::janitor`]，每项清理可以单独关闭
+#[derive(Debug, Deserialize, Clone)]
+pub struct JanitorConfig {
+    /// 定时清理的 cron 表达式（按 schedule_timezone），设为 null 时只能通过 `data_monitor janitor` 手动执行
+    #[serde(default = "default_janitor_cron")]
+    pub cron: Option<String>,
+    /// 把认领超过 monitor.claim_timeout_minutes 的 in_progress ID 放回待处理
+    #[serde(default = "default_true")]
+    pub release_claims: bool,
+    /// 把开始超过 abandoned_run_hours（不少于 monitor.resume_max_age_hours）仍未结束的运行标记为 aborted，
+    /// 并删除已结束运行遗留的进度
+    #[serde(default = "default_true")]
+    pub abort_runs: bool,
+    #[serde(default = "default_abandoned_run_hours")]
+    pub abandoned_run_hours: u32,
+    /// 删除超过 file_retention_days 的溢出文件、临时文件和原始响应
+    #[serde(default = "default_true")]
+    pub remove_files: bool,
+    #[serde(default = "default_file_retention_days")]
+    pub file_retention_days: u32,
+    /// head_method_cache 超过 method_cache_max_entries 时删除最早记住的主机
+    #[serde(default = "default_true")]
+    pub compact_caches: bool,
+    #[serde(default = "default_method_cache_max_entries")]
+    pub method_cache_max_entries: usize,
+}
+
+impl Default for JanitorConfig {
+    fn default() -> Self {
+        Self {
+            cron: default_janitor_cron(),
+            release_claims: true,
+            abort_runs: true,
+            abandoned_run_hours: default_abandoned_run_hours(),
+            remove_files: true,
+            file_retention_days: default_file_retention_days(),
+            compact_caches: true,
+            method_cache_max_entries: default_method_cache_max_entries(),
+        }
+    }
+}
+
+fn default_janitor_cron() -> Option<String> {
+    Some("0 17 * * * *".to_string())
+}
+
+fn default_abandoned_run_hours() -> u32 {
+    24
+}
+
+fn default_file_retention_days() -> u32 {
+    30
+}
+
+fn default_method_cache_max_entries() -> usize {
+    10_000
+}
+
+/// 检查记录的 Parquet 导出
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExportConfig {
+    /// 本地 Parquet 文件目录，上传前先写到这里
+    #[serde(default = "default_export_dir")]
+    pub dir: String,
+    /// 每次监测运行成功后导出当天的检查记录
+    #[serde(default)]
+    pub after_run: bool,
+    /// 定时导出前一天检查记录的 cron 表达式（按 schedule_timezone）
+    #[serde(default)]
+    pub cron: Option<String>,
+    /// 配置后导出的文件上传到 S3 兼容的对象存储
+    #[serde(default)]
+    pub s3: Option<S3ExportConfig>,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            dir: default_export_dir(),
+            after_run: false,
+            cron: None,
+            s3: None,
+        }
+    }
+}
+
+/// S3 兼容对象存储（AWS S3、MinIO 等），使用路径风格的 URL：`{endpoint}/{bucket}/{key}`
+#[derive(Debug, Deserialize, Clone)]
+pub struct S3ExportConfig {
+    /// 如 https://s3.us-east-1.amazonaws.com 或 http://minio:9000
+    pub endpoint: String,
+    pub bucket: String,
+    /// 对象键前缀，完整的键为 `{prefix}/dt=YYYY-MM-DD/dataset_monitor.parquet`
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    /// 保存 access key 的环境变量名，密钥不写入配置文件
+    #[serde(default = "default_s3_access_key_env")]
+    pub access_key_env: String,
+    #[serde(default = "default_s3_secret_key_env")]
+    pub secret_key_env: String,
+    /// 临时凭证的 session token 环境变量，未设置该变量时不发送
+    #[serde(default = "default_s3_session_token_env")]
+    pub session_token_env: String,
+    /// 上传完成后在同一目录写入空的 _SUCCESS 对象，供下游判断分区已完整；当天（UTC）尚未结束的分区不写
+    #[serde(default = "default_true")]
+    pub success_marker: bool,
+    /// 每个对象最多尝试上传的次数，失败后按 2s、4s、8s... 退避重试
+    #[serde(default = "default_s3_max_attempts")]
+    pub max_attempts: u32,
+}
+
+/// 请求数据中心接口和检查数据集URL的HTTP客户端设置，两类客户端由 [`crate::http::ClientFactory`] 构建，
+/// 超时使用 monitor.http_timeout_secs
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct HttpConfig {
+    /// 请求头 User-Agent，未配置时不发送
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// 代理地址，如 "http://proxy:3128"，两类请求都经过该代理
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// 每个主机保留的空闲连接数上限，未配置时不限
+    #[serde(default)]
+    pub pool_max_idle_per_host: Option<usize>,
+    /// 空闲连接保留的秒数，未配置时为 90 秒
+    #[serde(default)]
+    pub pool_idle_timeout_secs: Option<u64>,
+    /// 请求数据中心接口时的证书校验
+    #[serde(default)]
+    pub center_api_tls: TlsPolicy,
+    /// 检查数据集URL时的证书校验
+    #[serde(default)]
+    pub url_check_tls: TlsPolicy,
+}
+
+/// HTTPS 证书校验方式
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsPolicy {
+    /// 接受无效或主机名不匹配的证书
+    #[default]
+    Lenient,
+    /// 证书无效时请求失败
+    Strict,
+}
+
+/// 向外部系统推送监测结果
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct IntegrationsConfig {
+    #[serde(default)]
+    pub cmdb: Option<CmdbConfig>,
+}
+
+/// 按数据中心 PUT 链接健康摘要到资产管理系统（CMDB）
+#[derive(Debug, Deserialize, Clone)]
+pub struct CmdbConfig {
+    /// 地址模板，`{center}` 替换为 URL 编码后的数据中心名称
+    pub endpoint: String,
+    /// 认证头，如 "Authorization: Bearer <token>"
+    #[serde(default)]
+    pub auth_header: Option<String>,
+    /// 每次监测运行后推送本次运行的数据中心
+    #[serde(default = "default_true")]
+    pub after_run: bool,
+    /// 定时推送所有数据中心最近一次运行结果的 cron 表达式（按 schedule_timezone）
+    #[serde(default)]
+    pub cron: Option<String>,
+    /// 每个数据中心最多尝试推送的次数，失败后按 2s、4s、8s... 退避重试
+    #[serde(default = "default_cmdb_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_cmdb_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl CmdbConfig {
+    /// 解析 auth_header 为 (名称, 值)
+    pub fn auth_header(&self) -> Result<Option<(&str, &str)>> {
+        self.auth_header.as_deref()
+            .map(|header| header.split_once(':')
+                .map(|(name, value)| (name.trim(), value.trim()))
+                .with_context(|| format!("无效的 integrations.cmdb.auth_header，应为 \"名称: 值\": {}", header)))
+            .transpose()
+    }
+}
+
+fn default_cmdb_max_attempts() -> u32 {
+    4
+}
+
+fn default_cmdb_timeout_secs() -> u64 {
+    30
+}
+
+fn default_export_dir() -> String {
+    "./data/export".to_string()
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_s3_access_key_env() -> String {
+    "AWS_ACCESS_KEY_ID".to_string()
+}
+
+fn default_s3_secret_key_env() -> String {
+    "AWS_SECRET_ACCESS_KEY".to_string()
+}
+
+fn default_s3_session_token_env() -> String {
+    "AWS_SESSION_TOKEN".to_string()
+}
+
+fn default_s3_max_attempts() -> u32 {
+    4
+}
+
+fn default_run_summary_dir() -> String {
+    "./reports".to_string()
+}
+
+fn default_run_summary_keep() -> usize {
+    50
+}
+
+fn default_report_dir() -> String {
+    "./data/reports".to_string()
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_local_issue_min_sample() -> usize {
+    50
+}
+
+fn default_new_failure_max_per_center() -> usize {
+    20
+}
+
+fn default_alert_cooldown_minutes() -> u64 {
+    24 * 60
+}
+
+/// 计为成功的HTTP状态码，每项为状态码（204）、状态类（"2xx"）或范围（"200-299"）
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "Vec<StatusSpec>", into = "Vec<String>")]
+pub struct SuccessStatuses(Vec<(u16, u16)>);
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StatusSpec {
+    Code(u16),
+    Pattern(String),
+}
+
+impl StatusSpec {
+    fn range(&self) -> Result<(u16, u16), String> {
+        let pattern = match self {
+            StatusSpec::Code(code) => return Ok((*code, *code)),
+            StatusSpec::Pattern(pattern) => pattern.trim(),
+        };
+        let invalid = || format!("无效的状态码: {}", pattern);
+        if let Some(class) = pattern.strip_suffix("xx").or_else(|| pattern.strip_suffix("XX")) {
+            let class: u16 = class.parse().map_err(|_| invalid())?;
+            return Ok((class * 100, class * 100 + 99));
+        }
+        let (start, end) = pattern.split_once('-').unwrap_or((pattern, pattern));
+        let start: u16 = start.trim().parse().map_err(|_| invalid())?;
+        let end: u16 = end.trim().parse().map_err(|_| invalid())?;
+        if start > end {
+            return Err(invalid());
+        }
+        Ok((start, end))
+    }
+}
+
+impl TryFrom<Vec<StatusSpec>> for SuccessStatuses {
+    type Error = String;
+
+    fn try_from(specs: Vec<StatusSpec>) -> Result<Self, Self::Error> {
+        if specs.is_empty() {
+            return Err("success_statuses 不能为空".to_string());
+        }
+        let ranges = specs.iter().map(StatusSpec::range).collect::<Result<Vec<_>, _>>()?;
+        Ok(Self(ranges))
+    }
+}
+
+impl From<SuccessStatuses> for Vec<String> {
+    fn from(statuses: SuccessStatuses) -> Self {
+        statuses.0.iter()
+            .map(|(start, end)| if start == end { start.to_string() } else { format!("{}-{}", start, end) })
+            .collect()
+    }
+}
+
+impl Default for SuccessStatuses {
+    fn default() -> Self {
+        Self(vec![(200, 299)])
+    }
+}
+
+impl SuccessStatuses {
+    pub fn contains(&self, status_code: u16) -> bool {
+        self.0.iter().any(|(start, end)| (*start..=*end).contains(&status_code))
+    }
+
+    /// 是否有状态码落在 [start, end] 内
+    pub fn overlaps(&self, start: u16, end: u16) -> bool {
+        self.0.iter().any(|(s, e)| *s <= end && start <= *e)
+    }
+
+    /// 把 [start, end] 计为成功
+    pub fn include(&mut self, start: u16, end: u16) {
+        self.0.push((start, end));
+    }
+
+    pub fn is_success(&self, status_code: Option<u16>) -> bool {
+        status_code.is_some_and(|code| self.contains(code))
+    }
+
+    /// 判断 `column` 是否为成功状态码的 SQL 条件，NULL 不成功
+    pub fn sql(&self, column: &str) -> String {
+        let conditions: Vec<String> = self.0.iter()
+            .map(|(start, end)| if start == end {
+                format!("{} = {}", column, start)
+            } else {
+                format!("{} BETWEEN {} AND {}", column, start, end)
+            })
+            .collect();
+        format!("COALESCE({}, FALSE)", conditions.join(" OR "))
+    }
+}
+
+/// 每次监测运行使用的有效配置，随运行结果保存；Center 序列化时不含 secretKey
+#[derive(Debug, Serialize)]
+pub struct ConfigSnapshot<'a> {
+    pub version: &'static str,
+    pub monitor: &'a MonitorConfig,
+    /// 本次运行监测的数据中心（含各自的调度等覆盖配置）
+    pub centers: Vec<&'a Center>,
+}
+
+impl<'a> ConfigSnapshot<'a> {
+    pub fn new(config: &'a Config, centers: Vec<&'a Center>) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            monitor: &config.monitor,
+            centers,
+        }
+    }
+
+    /// 返回 (JSON, JSON 的 SHA-256)，配置相同的运行哈希相同
+    pub fn to_json(&self) -> Result<(String, String)> {
+        use sha2::{Digest, Sha256};
+        let json = serde_json::to_string(self)?;
+        let hash = Sha256::digest(json.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect();
+        Ok((json, hash))
+    }
+}
+
+impl MonitorConfig {
+    /// 生效的 3xx 处理方式，见 [`MonitorConfig::terminal_redirect`]
+    pub fn terminal_redirect(&self) -> TerminalRedirect {
+        match self.terminal_redirect {
+            Some(policy) => policy,
+            None if self.success_statuses.overlaps(300, 399) => TerminalRedirect::Success,
+            None => TerminalRedirect::Failure,
+        }
+    }
+
+    pub fn schedule_tz(&self) -> Result<Tz> {
+        self.schedule_timezone.parse::<Tz>()
+            .map_err(|e| anyhow::anyhow!("{}", e))
+            .with_context(|| format!("无效的时区: {}", self.schedule_timezone))
+    }
 }
 
 impl Config {
     pub fn load(path: &str) -> Result<Self> {
         let content = fs::read_to_string(path)?;
-        let config = serde_yaml::from_str(&content)?;
+        let mut config: Config = serde_yaml::from_str(&content)?;
+        config.validate()?;
+        // 没有跟随的 3xx 计为成功时，统计和成功判断都按成功计
+        if config.monitor.terminal_redirect() == TerminalRedirect::Success {
+            config.monitor.success_statuses.include(300, 399);
+        }
         Ok(config)
     }
-}
\ No newline at end of file
+
+    /// 数据中心保存数据集的 MongoDB 集合名，未配置的数据中心（如已从配置中移除）按默认规则转换
+    pub fn collection_name(&self, center_name: &str) -> String {
+        let configured = self.centers.iter()
+            .find(|c| c.name == center_name)
+            .and_then(|c| c.collection.clone());
+        match configured {
+            Some(collection) => collection,
+            None if self.mongodb.legacy_collection_names => center_name.to_string(),
+            None => collection_slug(center_name),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.monitor.schedule_tz()?;
+        if self.monitor.lock_ttl_secs < 3 {
+            anyhow::bail!("monitor.lock_ttl_secs 不能小于 3");
+        }
+        if self.duckdb.storage_soft_limit_mb.is_some() && self.duckdb.prune_retention_days == 0 {
+            anyhow::bail!("duckdb.prune_retention_days 不能为 0");
+        }
+        if self.monitor.terminal_redirect() != TerminalRedirect::Success && self.monitor.success_statuses.overlaps(300, 399) {
+            anyhow::bail!("monitor.success_statuses 包含 3xx 时 terminal_redirect 只能为 success");
+        }
+        for slow_host in &self.monitor.slow_hosts {
+            if slow_host.timeout_secs == 0 {
+                anyhow::bail!("monitor.slow_hosts 中 {} 的 timeout_secs 不能为 0", slow_host.host);
+            }
+        }
+        if let Some(learning) = &self.monitor.slow_host_learning {
+            if learning.timeout_secs <= self.monitor.http_timeout_secs {
+                anyhow::bail!("monitor.slow_host_learning.timeout_secs 需要大于 http_timeout_secs");
+            }
+            if ![learning.slow_ratio, learning.min_share].iter().all(|v| *v > 0.0 && *v <= 1.0) {
+                anyhow::bail!("monitor.slow_host_learning 的 slow_ratio 和 min_share 需要在 (0, 1] 之间");
+            }
+        }
+        if let Some(adaptive) = &self.monitor.adaptive_concurrency {
+            if adaptive.min == 0 || adaptive.min > adaptive.max {
+                anyhow::bail!("monitor.adaptive_concurrency 需要 0 < min <= max");
+            }
+            if adaptive.window == 0 {
+                anyhow::bail!("monitor.adaptive_concurrency.window 不能为 0");
+            }
+            if !(adaptive.decrease_factor > 0.0 && adaptive.decrease_factor < 1.0) {
+                anyhow::bail!("monitor.adaptive_concurrency.decrease_factor 需要在 (0, 1) 之间");
+            }
+            if adaptive.increase_below > adaptive.decrease_above {
+                anyhow::bail!("monitor.adaptive_concurrency.increase_below 不能大于 decrease_above");
+            }
+        }
+        for (url, center_name) in &self.monitor.shared_urls.primary {
+            if !self.centers.iter().any(|c| &c.name == center_name) {
+                anyhow::bail!("monitor.shared_urls.primary 中 {} 的数据中心 {} 不在配置中", url, center_name);
+            }
+        }
+        for center in &self.centers {
+            if center.list_body.is_some() && center.list_method == ListMethod::Get {
+                anyhow::bail!("数据中心 {} 配置了 list_body，但 list_method 为 GET", center.name);
+            }
+        }
+        if let Some(proxy) = &self.http.proxy {
+            reqwest::Proxy::all(proxy).with_context(|| format!("无效的 http.proxy: {}", proxy))?;
+        }
+        if let Some(s3) = &self.export.s3 {
+            reqwest::Url::parse(&s3.endpoint).with_context(|| format!("无效的 export.s3.endpoint: {}", s3.endpoint))?;
+            if s3.max_attempts == 0 {
+                anyhow::bail!("export.s3.max_attempts 不能为 0");
+            }
+        }
+        for center in &self.centers {
+            for credential in &center.url_credentials {
+                if let Some(pattern) = &credential.pattern {
+                    regex::Regex::new(pattern)
+                        .with_context(|| format!("数据中心 {} 的 url_credentials.pattern 无效: {}", center.name, pattern))?;
+                }
+                credential.resolve().with_context(|| format!("数据中心 {} 的 url_credentials 无效", center.name))?;
+            }
+        }
+        let mut collections: HashMap<String, &str> = HashMap::new();
+        for center in &self.centers {
+            let collection = self.collection_name(&center.name);
+            if collection.is_empty() || collection.contains('$') || collection.starts_with("system.")
+                || RESERVED_COLLECTIONS.contains(&collection.as_str())
+            {
+                anyhow::bail!("数据中心 {} 的集合名 {} 无效", center.name, collection);
+            }
+            if let Some(other) = collections.insert(collection.clone(), &center.name) {
+                anyhow::bail!("数据中心 {} 和 {} 使用了相同的集合 {}", other, center.name, collection);
+            }
+        }
+        if let Some(cmdb) = &self.integrations.cmdb {
+            cmdb.auth_header()?;
+            if cmdb.max_attempts == 0 {
+                anyhow::bail!("integrations.cmdb.max_attempts 不能为 0");
+            }
+        }
+        Ok(())
+    }
+
+    /// 数据中心的URL检查是否计算响应体指纹
+    pub fn fingerprint_enabled(&self, center_name: &str) -> bool {
+        self.centers.iter()
+            .find(|c| c.name == center_name)
+            .and_then(|c| c.fingerprint)
+            .unwrap_or(self.monitor.fingerprint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(monitor: &str) -> Config {
+        serde_yaml::from_str(&format!(r#"
+centers:
+  - {{ name: "A", secretKey: "", url: "https://a.example.org", enabled: true }}
+mongodb: {{ uri: "mongodb://localhost:27017", database: "db" }}
+duckdb: {{ path: "./monitor.db" }}
+monitor:
+  fetch_interval_days: 30
+  check_interval_days: 7
+  http_timeout_secs: 30
+  max_concurrent: 10
+  {}
+"#, monitor)).unwrap()
+    }
+
+    #[test]
+    fn lock_ttl_must_allow_renewal() {
+        assert!(config("").validate().is_ok());
+        assert!(config("lock_ttl_secs: 3").validate().is_ok());
+        for ttl in [0, 1, 2] {
+            let err = config(&format!("lock_ttl_secs: {}", ttl)).validate().unwrap_err();
+            assert!(err.to_string().contains("lock_ttl_secs"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn success_statuses_accept_codes_classes_and_ranges() {
+        let statuses = config(r#"success_statuses: [204, "3xx", "200-202"]"#).monitor.success_statuses;
+        for code in [200, 202, 204, 300, 399] {
+            assert!(statuses.contains(code), "{}", code);
+        }
+        for code in [203, 205, 299, 400] {
+            assert!(!statuses.contains(code), "{}", code);
+        }
+        assert!(!statuses.is_success(None));
+        assert_eq!(statuses.sql("s"), "COALESCE(s = 204 OR s BETWEEN 300 AND 399 OR s BETWEEN 200 AND 202, FALSE)");
+        assert_eq!(Vec::<String>::from(statuses), ["204", "300-399", "200-202"]);
+        assert_eq!(config("").monitor.success_statuses, SuccessStatuses::default());
+
+        for invalid in ["[]", "[abc]", "[300-200]", "[9x]"] {
+            assert!(serde_yaml::from_str::<SuccessStatuses>(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    fn assert_rejected(config: &Config, message: &str) {
+        match config.validate() {
+            Ok(()) => panic!("应拒绝: {}", message),
+            Err(e) => assert!(format!("{:#}", e).contains(message), "{:#}", e),
+        }
+    }
+
+    fn center(yaml: &str) -> Center {
+        serde_yaml::from_str(&format!(r#"{{ secretKey: "", url: "https://a.example.org", enabled: true, {} }}"#, yaml)).unwrap()
+    }
+
+    #[test]
+    fn monitor_rules() {
+        assert!(config("schedule_timezone: Asia/Shanghai").validate().is_ok());
+        assert_rejected(&config("schedule_timezone: Mars/Base"), "无效的时区");
+
+        assert!(config(r#"success_statuses: ["2xx", "3xx"]"#).validate().is_ok());
+        assert!(config("terminal_redirect: failure").validate().is_ok());
+        assert_rejected(&config("success_statuses: [2xx, 3xx]\n  terminal_redirect: failure"), "terminal_redirect");
+
+        assert!(config("slow_hosts: [{ host: a.example.org, timeout_secs: 60 }]").validate().is_ok());
+        assert_rejected(&config("slow_hosts: [{ host: a.example.org, timeout_secs: 0 }]"), "slow_hosts");
+
+        assert!(config("slow_host_learning: { timeout_secs: 60 }").validate().is_ok());
+        assert_rejected(&config("slow_host_learning: { timeout_secs: 30 }"), "slow_host_learning.timeout_secs");
+        assert_rejected(&config("slow_host_learning: { timeout_secs: 60, slow_ratio: 1.5 }"), "slow_ratio");
+        assert_rejected(&config("slow_host_learning: { timeout_secs: 60, min_share: 0 }"), "min_share");
+
+        assert!(config("adaptive_concurrency: { min: 2, max: 10 }").validate().is_ok());
+        for adaptive in ["min: 0, max: 10", "min: 11, max: 10", "max: 10, window: 0", "max: 10, decrease_factor: 1.0",
+                         "max: 10, increase_below: 0.9, decrease_above: 0.5"] {
+            assert_rejected(&config(&format!("adaptive_concurrency: {{ {} }}", adaptive)), "adaptive_concurrency");
+        }
+
+        assert!(config(r#"shared_urls: { primary: { "https://x.example.org/d": A } }"#).validate().is_ok());
+        assert_rejected(&config(r#"shared_urls: { primary: { "https://x.example.org/d": B } }"#), "shared_urls.primary");
+    }
+
+    #[test]
+    fn section_rules() {
+        let mut config = config("");
+        config.duckdb.storage_soft_limit_mb = Some(100);
+        assert!(config.validate().is_ok());
+        config.duckdb.prune_retention_days = 0;
+        assert_rejected(&config, "prune_retention_days");
+        config.duckdb.storage_soft_limit_mb = None;
+        assert!(config.validate().is_ok());
+
+        config.centers[0].list_body = Some(serde_json::json!({ "page": 1 }));
+        assert_rejected(&config, "list_body");
+        config.centers[0].list_method = ListMethod::Post;
+        assert!(config.validate().is_ok());
+
+        config.http.proxy = Some("http://proxy:3128".to_string());
+        assert!(config.validate().is_ok());
+        config.http.proxy = Some("::".to_string());
+        assert_rejected(&config, "http.proxy");
+        config.http.proxy = None;
+
+        let s3 = |yaml: &str| Some(serde_yaml::from_str::<S3ExportConfig>(yaml).unwrap());
+        config.export.s3 = s3("{ endpoint: https://s3.example.org, bucket: b }");
+        assert!(config.validate().is_ok());
+        config.export.s3 = s3("{ endpoint: s3.example.org, bucket: b }");
+        assert_rejected(&config, "export.s3.endpoint");
+        config.export.s3 = s3("{ endpoint: https://s3.example.org, bucket: b, max_attempts: 0 }");
+        assert_rejected(&config, "export.s3.max_attempts");
+        config.export.s3 = None;
+
+        let cmdb = |yaml: &str| Some(serde_yaml::from_str::<CmdbConfig>(yaml).unwrap());
+        config.integrations.cmdb = cmdb(r#"{ endpoint: https://cmdb.example.org, auth_header: "Authorization: Bearer x" }"#);
+        assert!(config.validate().is_ok());
+        config.integrations.cmdb = cmdb("{ endpoint: https://cmdb.example.org, auth_header: token }");
+        assert_rejected(&config, "auth_header");
+        config.integrations.cmdb = cmdb("{ endpoint: https://cmdb.example.org, max_attempts: 0 }");
+        assert_rejected(&config, "integrations.cmdb.max_attempts");
+    }
+
+    #[test]
+    fn center_rules() {
+        let with_center = |yaml: &str| {
+            let mut config = config("");
+            config.centers.push(center(yaml));
+            config
+        };
+        assert!(with_center(r#"name: B, url_credentials: [{ pattern: "^https://b/", username: u, password: p }, { token: t }]"#).validate().is_ok());
+        assert_rejected(&with_center(r#"name: B, url_credentials: [{ pattern: "(", token: t }]"#), "url_credentials.pattern");
+        assert_rejected(&with_center("name: B, url_credentials: [{ username: u, token: t }]"), "url_credentials");
+        assert_rejected(&with_center("name: B, url_credentials: [{ password: p }]"), "url_credentials");
+
+        for collection in ["fetch_audit", "run_locks", "a$b", "system.b", "\"\""] {
+            assert_rejected(&with_center(&format!("name: B, collection: {}", collection)), "集合名");
+        }
+        // 默认集合名相同（"a"）的数据中心需要显式指定集合
+        assert_rejected(&with_center(r#"name: "a!""#), "使用了相同的集合 a");
+        assert_rejected(&with_center("name: B, collection: a"), "使用了相同的集合 a");
+        assert!(with_center(r#"name: "a!", collection: a_legacy"#).validate().is_ok());
+
+        let config = with_center(r#"name: "冰川 Data""#);
+        assert_eq!(config.collection_name("A"), "a");
+        assert!(config.collection_name("冰川 Data").starts_with("data_"));
+        // 兼容模式下使用数据中心名称
+        let mut legacy = config.clone();
+        legacy.mongodb.legacy_collection_names = true;
+        assert_eq!(legacy.collection_name("冰川 Data"), "冰川 Data");
+        assert!(legacy.validate().is_ok());
+    }
+
+    #[test]
+    fn collection_slugs() {
+        assert_eq!(collection_slug("CASDC"), "casdc");
+        assert_eq!(collection_slug("Geo Data-Center"), "geo_data_center");
+        assert_eq!(collection_slug("  --Geo__Data--  "), "geo_data");
+        // 没有字母数字时使用名称哈希
+        let empty = collection_slug("!!!");
+        assert!(empty.starts_with("center_") && empty.len() == "center_".len() + 8, "{}", empty);
+
+        // 含非 ASCII 字符的名称追加哈希，只有中文不同的名称不会冲突
+        let glacier = collection_slug("国家冰川冻土沙漠科学数据中心");
+        let ocean = collection_slug("国家海洋科学数据中心");
+        assert!(glacier.starts_with("center_") && ocean.starts_with("center_"));
+        assert_ne!(glacier, ocean);
+        let mixed = collection_slug("中科院 CAS 数据");
+        assert!(mixed.starts_with("cas_"), "{}", mixed);
+        assert_ne!(mixed, collection_slug("中国 CAS 数据"));
+        assert!(glacier.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+        // 结果稳定，同一名称每次得到相同的集合名
+        assert_eq!(glacier, collection_slug("国家冰川冻土沙漠科学数据中心"));
+    }
+
+    #[test]
+    fn config_snapshots_round_trip_without_secrets() {
+        let mut config = config("");
+        config.monitor.http_timeout_secs = 12;
+        config.centers = vec![
+            center("name: A, fetch_interval_days: 3"),
+            center(r#"name: B, url_credentials: [{ username: u, password: "p4ssw0rd" }, { token: "t0ken-value" }]"#),
+        ];
+        config.centers[0].secret_key = "s3cr3t-key".to_string();
+        config.centers[1].secret_key = "other-key".to_string();
+        let (json, hash) = ConfigSnapshot::new(&config, config.centers.iter().collect()).to_json().unwrap();
+        for secret in ["secretKey", "secret_key", "s3cr3t-key", "other-key", "url_credentials", "p4ssw0rd", "t0ken-value"] {
+            assert!(!json.contains(secret), "快照中包含 {}: {}", secret, json);
+        }
+
+        // 读回后字段齐全，重新序列化得到相同的 JSON 和哈希
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(value["monitor"]["http_timeout_secs"], 12);
+        assert_eq!(value["centers"][0]["name"], "A");
+        assert_eq!(value["centers"][0]["fetch_interval_days"], 3);
+        assert_eq!(value["centers"][1]["url"], "https://a.example.org");
+        assert_eq!(serde_json::to_string(&value).unwrap(), json);
+        let (_, same) = ConfigSnapshot::new(&config, config.centers.iter().collect()).to_json().unwrap();
+        assert_eq!(same, hash);
+
+        // 设置或监测的数据中心不同，哈希不同
+        let (_, subset) = ConfigSnapshot::new(&config, vec![&config.centers[0]]).to_json().unwrap();
+        assert_ne!(subset, hash);
+        config.monitor.http_timeout_secs = 13;
+        let (_, changed) = ConfigSnapshot::new(&config, config.centers.iter().collect()).to_json().unwrap();
+        assert_ne!(changed, hash);
+    }
+}
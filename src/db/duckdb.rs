@@ -1,15 +1,34 @@
 use anyhow::Result;
-use duckdb::{params, Connection};
+use duckdb::types::Value;
+use duckdb::{params, params_from_iter, Connection};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::models::{ErrorCategoryStats, HealthCheck, MonitorRecord, NetworkIssueTrend, ProblematicUrl, UrlHealthReport};
+use crate::circuit_breaker::host_of;
+use crate::config::{QueryGroup, SlowHostLearning, SuccessStatuses};
+use crate::db::filter::QueryFilter;
+use crate::sanitize::{TextLimits, SENSITIVE_HEADER_PATTERN};
+use crate::models::{percentage, pivot_trends, AlertState, BrokenLink, CategoryCount, CmdbDelivery, ContentChange, CenterAvailability, DailyAvailability, DataAsOf, ErrorCategoryStats, FetchMetrics, HealthCheck, HostSort, HostStats, HourlyStats, HttpVersionCount, LatestRun, LearnedSlowHost, MethodCacheEntry, MonitorRecord, MonitorSummary, NetworkIssueTrend, ProblematicUrl, RecentFailure, Reclassification, SharedUrl, SharedUrlCenter, StatusCodeStats, StorageSample, StorageSize, StatusGrouping, StoredClassification, StoredRun, StoredText, TimeBasis, TimingBreakdown, TrendGranularity, TrendPoint, TREND_BUCKET_FORMAT, UnfinishedRun, UrlHealthReport, UrlQualityIssue, UrlStatus};
+use chrono::{DateTime, NaiveDateTime, Utc};
 
 pub struct DuckDB {
     pub conn: Arc<Mutex<Connection>>,
+    /// 数据库文件路径，用于读取文件大小
+    path: String,
+    /// 统计时计为成功的状态码，即 monitor.success_statuses
+    success: SuccessStatuses,
+    /// 各查询组的超时时间，超时后中断查询，避免长时间占用连接阻塞写入
+    query_timeouts: HashMap<QueryGroup, Duration>,
 }
 
+/// 统计查询超过 duckdb.query_timeout_secs（或该查询组的 duckdb.query_timeouts）被中断
+#[derive(Debug, thiserror::Error)]
+#[error("查询超过 {0:?} 被中断，请缩小时间范围")]
+pub struct QueryTimeout(pub Duration);
+
 impl DuckDB {
     pub async fn new(path: &str) -> Result<Self> {
         let conn = Connection::open(path)?;
@@ -82,44 +101,270 @@ impl DuckDB {
         for index_sql in indices {
             conn.execute(index_sql, [])?;
         }
+        // 分阶段耗时，无法测量（复用连接、没有收到响应）时为 NULL
+        for column in ["dns_ms", "connect_ms", "ttfb_ms"] {
+            conn.execute(&format!("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS {} BIGINT", column), [])?;
+        }
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS http_version VARCHAR", [])?;
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS connection_reused BOOLEAN", [])?;
+        // 记录来源，本系统检查的为 NULL，导入的历史数据为导入时指定的来源
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS source VARCHAR", [])?;
+        // 响应体指纹，启用 fingerprint 时写入
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS content_hash VARCHAR", [])?;
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS content_changed BOOLEAN", [])?;
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS auth_used BOOLEAN DEFAULT FALSE", [])?;
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS requested_url VARCHAR", [])?;
+        // 每次尝试的结果（JSON 数组）和包含重试的总耗时
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS attempts_detail VARCHAR", [])?;
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS total_time_ms BIGINT", [])?;
+        // URL的主机名（小写），写入时由 URL 解析，按主机统计时使用；旧记录用正则从 URL 中提取
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS host VARCHAR", [])?;
+        let backfilled = conn.execute(
+            r"UPDATE dataset_monitor
+            SET host = NULLIF(lower(regexp_extract(url, '^[A-Za-z][A-Za-z0-9+.-]*://(?:[^@/?#]*@)?(\[[^\]]*\]|[^:/?#]*)', 1)), '')
+            WHERE host IS NULL",
+            [],
+        )?;
+        if backfilled > 0 {
+            info!("补齐 {} 条记录的 host", backfilled);
+        }
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_host ON dataset_monitor (host)", [])?;
+        // 失败发生在重定向链的第几跳（0 为URL本身）和该跳的URL，成功时为 NULL
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS failed_hop_index INTEGER", [])?;
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS failed_hop_url VARCHAR", [])?;
+        // 检查使用的超时（秒），慢主机可能大于全局的 http_timeout_secs
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS timeout_secs INTEGER", [])?;
+        // 检查时数据集的标签，逗号分隔，没有标签时为 NULL
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS tags VARCHAR", [])?;
+        // 没有跟随的 3xx 响应的 Location（见 monitor.terminal_redirect）
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS redirect_location VARCHAR", [])?;
+        // 写入记录的监测运行，导入和单独检查URL的记录为 NULL
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS run_id VARCHAR", [])?;
+        // 多个数据中心共用该URL时统计归属的数据中心及依据（见 monitor.shared_urls），不共用时为 NULL
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS attributed_center VARCHAR", [])?;
+        conn.execute("ALTER TABLE dataset_monitor ADD COLUMN IF NOT EXISTS attribution_rule VARCHAR", [])?;
+        // created_at/updated_at 由程序按 UTC 写入；旧版本经 appender 写入的为 NULL，
+        // 更新时用的 CURRENT_TIMESTAMP 是会话时区的时间，这里用检查时间补齐 NULL
+        let backfilled = conn.execute(
+            "UPDATE dataset_monitor
+            SET created_at = COALESCE(created_at, check_time), updated_at = COALESCE(updated_at, check_time)
+            WHERE created_at IS NULL OR updated_at IS NULL",
+            [],
+        )?;
+        if backfilled > 0 {
+            info!("补齐 {} 条记录的 created_at/updated_at", backfilled);
+        }
+
+        // 每次监测运行的汇总
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS monitor_runs (
+                run_id VARCHAR PRIMARY KEY,
+                started_at TIMESTAMP NOT NULL,
+                finished_at TIMESTAMP NOT NULL,
+                total BIGINT NOT NULL,
+                success BIGINT NOT NULL,
+                summary TEXT NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_runs_started_at ON monitor_runs (started_at)", [])?;
+        // 运行时的配置快照（不含密钥）及其哈希，用于按相同配置分组比较
+        conn.execute("ALTER TABLE monitor_runs ADD COLUMN IF NOT EXISTS config TEXT", [])?;
+        conn.execute("ALTER TABLE monitor_runs ADD COLUMN IF NOT EXISTS config_hash VARCHAR", [])?;
+        // 运行的出口 IP 和主机名，同时保存在汇总的 environment 中，单独成列便于按 IP 查找运行
+        conn.execute("ALTER TABLE monitor_runs ADD COLUMN IF NOT EXISTS egress_ip VARCHAR", [])?;
+        conn.execute("ALTER TABLE monitor_runs ADD COLUMN IF NOT EXISTS hostname VARCHAR", [])?;
+        // 运行开始和结束时数据库文件和 WAL 文件的大小（字节），文件不存在或只检查不写库时为 NULL
+        conn.execute("ALTER TABLE monitor_runs ADD COLUMN IF NOT EXISTS db_bytes_start BIGINT", [])?;
+        conn.execute("ALTER TABLE monitor_runs ADD COLUMN IF NOT EXISTS wal_bytes_start BIGINT", [])?;
+        conn.execute("ALTER TABLE monitor_runs ADD COLUMN IF NOT EXISTS db_bytes_end BIGINT", [])?;
+        conn.execute("ALTER TABLE monitor_runs ADD COLUMN IF NOT EXISTS wal_bytes_end BIGINT", [])?;
+
+        // 告警状态，用于冷却去重和恢复通知，重启后不丢失
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS alert_state (
+                rule VARCHAR NOT NULL,
+                subject VARCHAR NOT NULL,
+                is_open BOOLEAN NOT NULL,
+                last_fired_at TIMESTAMP,
+                last_value DOUBLE,
+                updated_at TIMESTAMP NOT NULL,
+                PRIMARY KEY (rule, subject)
+            )",
+            [],
+        )?;
+        // data_fetch 每个数据中心一次运行的请求性能
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS fetch_metrics (
+                center_name VARCHAR NOT NULL,
+                started_at TIMESTAMP NOT NULL,
+                finished_at TIMESTAMP NOT NULL,
+                list_requests BIGINT NOT NULL,
+                list_duration_ms BIGINT NOT NULL,
+                detail_requests BIGINT NOT NULL,
+                avg_detail_ms DOUBLE,
+                bytes BIGINT NOT NULL,
+                errors BIGINT NOT NULL
+            )",
+            [],
+        )?;
+        // 向 CMDB 推送链接健康摘要的结果
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS cmdb_deliveries (
+                center_name VARCHAR NOT NULL,
+                run_id VARCHAR NOT NULL,
+                delivered_at TIMESTAMP NOT NULL,
+                success BOOLEAN NOT NULL,
+                attempts INTEGER NOT NULL,
+                http_status INTEGER,
+                error TEXT
+            )",
+            [],
+        )?;
+        // 进行中的监测运行，异常退出或取消后可以续跑；run_progress 记录已写库的记录 id
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS monitor_run_state (
+                run_id VARCHAR PRIMARY KEY,
+                started_at TIMESTAMP NOT NULL,
+                centers VARCHAR NOT NULL,
+                status VARCHAR NOT NULL,
+                updated_at TIMESTAMP NOT NULL
+            )",
+            [],
+        )?;
+        // 已发布的运行：运行结束时与运行状态在同一事务中写入，统计默认只计入已发布运行的记录，
+        // 避免运行中途新旧结果混在一起
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS published_runs (
+                run_id VARCHAR PRIMARY KEY,
+                published_at TIMESTAMP NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS run_progress (
+                run_id VARCHAR NOT NULL,
+                id VARCHAR NOT NULL
+            )",
+            [],
+        )?;
+        // check_method 为 auto 时记住的拒绝 HEAD 的主机，这些主机直接用 GET 检查
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS head_method_cache (
+                host VARCHAR PRIMARY KEY,
+                learned_at TIMESTAMP NOT NULL,
+                runs_since_probe INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        // 自动识别的慢主机，每次识别后整体替换
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS slow_hosts (
+                host VARCHAR PRIMARY KEY,
+                timeout_secs INTEGER NOT NULL,
+                learned_at TIMESTAMP NOT NULL,
+                total_checks BIGINT NOT NULL,
+                slow_checks BIGINT NOT NULL
+            )",
+            [],
+        )?;
+        // 每个URL第一次检查和最后一次检查成功的时间，随检查结果写入更新；新建时由历史记录回填
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS url_status (
+                url VARCHAR PRIMARY KEY,
+                first_seen_at TIMESTAMP NOT NULL,
+                last_success_at TIMESTAMP
+            )",
+            [],
+        )?;
+        // 最近一次运行中无法检查的数据集URL，每次运行按数据中心整体替换
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS url_quality_issues (
+                center_name VARCHAR NOT NULL,
+                id VARCHAR NOT NULL,
+                raw_id VARCHAR,
+                name VARCHAR,
+                url VARCHAR NOT NULL,
+                reason VARCHAR NOT NULL,
+                detected_at TIMESTAMP NOT NULL
+            )",
+            [],
+        )?;
+        let tracked: i64 = conn.query_row("SELECT COUNT(*) FROM url_status", [], |row| row.get(0))?;
+        if tracked == 0 {
+            let backfilled = upsert_url_status(
+                &conn,
+                &SuccessStatuses::default(),
+                "SELECT url, check_time, status_code FROM dataset_monitor
+                WHERE url IS NOT NULL AND (status_code IS NOT NULL OR error_category IS NOT NULL)",
+            )?;
+            if backfilled > 0 {
+                info!("由历史记录回填 {} 个URL的首次检查和最后成功时间", backfilled);
+            }
+        }
         info!("DuckDB 初始化完成，数据库路径: {}", path);
         Ok(Self {
             conn: Arc::new(Mutex::new(conn)),
+            path: path.to_string(),
+            success: SuccessStatuses::default(),
+            query_timeouts: HashMap::new(),
         })
     }
 
+    /// 统计查询按配置的成功状态码计算成功数
+    pub fn with_success_statuses(mut self, statuses: &SuccessStatuses) -> Self {
+        self.success = statuses.clone();
+        self
+    }
+
+    /// 各查询组的超时时间，返回 None 的组不限制
+    pub fn with_query_timeouts(mut self, timeout: impl Fn(QueryGroup) -> Option<Duration>) -> Self {
+        self.query_timeouts = QueryGroup::ALL.into_iter()
+            .filter_map(|group| Some((group, timeout(group)?)))
+            .collect();
+        self
+    }
+
+    /// 在阻塞线程上执行统计查询，`group` 配置了超时时超时后通过 DuckDB 的中断句柄中断查询，
+    /// 等查询停止后释放连接，返回 QueryTimeout
+    async fn read<T: Send + 'static>(
+        &self,
+        group: QueryGroup,
+        query: impl FnOnce(&Connection) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let conn = self.conn.clone().lock_owned().await;
+        let interrupt = conn.interrupt_handle();
+        let mut task = tokio::task::spawn_blocking(move || query(&conn));
+        let Some(timeout) = self.query_timeouts.get(&group).copied() else {
+            return task.await?;
+        };
+        match tokio::time::timeout(timeout, &mut task).await {
+            Ok(result) => result?,
+            Err(_) => {
+                interrupt.interrupt();
+                match task.await? {
+                    Err(e) => {
+                        warn!("统计查询超过 {:?} 被中断: {}", timeout, e);
+                        Err(QueryTimeout(timeout).into())
+                    }
+                    // 查询在中断前刚好完成
+                    result => result,
+                }
+            }
+        }
+    }
+
     pub async fn insert_records(&self, records: &[MonitorRecord]) -> Result<()> {
         if records.is_empty() {
             return Ok(());
         }
+        let now = Utc::now();
         let conn = self.conn.lock().await;
         let tx = conn.unchecked_transaction()?;
         {
             let mut appender = conn.appender("dataset_monitor")?;
             for record in records {
-                let error_category_str = record.error_category.clone();
-                let created_at_str = record.created_at.as_ref().map(|dt| dt.to_rfc3339());
-                let updated_at_str = record.updated_at.as_ref().map(|dt| dt.to_rfc3339());
-
-                appender.append_row(params![
-                            &record.id,
-                            &record.raw_id,
-                            &record.url,
-                            &record.name,
-                            &record.center_name,
-                            &record.date_published,
-                            &record.check_time.to_rfc3339(),
-                            &record.status_code,
-                            &record.status_text,
-                            &error_category_str,
-                            &record.error_msg,
-                            &record.error_detail,
-                            &record.response_time_ms.map(|t| t as i64),
-                            &record.is_likely_local_issue,
-                            &record.headers,
-                            &created_at_str,
-                            &updated_at_str
-                        ])?
+                append_record(&mut appender, record, None, now)?;
             }
             appender.flush()?;
         }
@@ -128,10 +373,48 @@ impl DuckDB {
         Ok(())
     }
 
+    /// 导入外部的历史检查记录，source 列标记为 `source`；`skip_duplicates` 时跳过已有相同
+    /// (url, check_time) 的记录。返回实际插入的条数
+    pub async fn import_records(&self, records: &[MonitorRecord], source: &str, skip_duplicates: bool) -> Result<usize> {
+        if records.is_empty() {
+            return Ok(0);
+        }
+        let now = Utc::now();
+        let conn = self.conn.lock().await;
+        let tx = conn.unchecked_transaction()?;
+        tx.execute("CREATE TEMPORARY TABLE import_batch AS SELECT * FROM dataset_monitor LIMIT 0", [])?;
+        {
+            let mut appender = tx.appender("import_batch")?;
+            for record in records {
+                append_record(&mut appender, record, Some(source), now)?;
+            }
+            appender.flush()?;
+        }
+        let duplicate_filter = if skip_duplicates {
+            "WHERE NOT EXISTS (SELECT 1 FROM dataset_monitor m WHERE m.url = b.url AND m.check_time = b.check_time)"
+        } else {
+            ""
+        };
+        let inserted = tx.execute(&format!("INSERT INTO dataset_monitor SELECT b.* FROM import_batch b {}", duplicate_filter), [])?;
+        upsert_url_status(
+            &tx,
+            &self.success,
+            "SELECT url, check_time, status_code FROM import_batch
+            WHERE url IS NOT NULL AND (status_code IS NOT NULL OR error_category IS NOT NULL)",
+        )?;
+        tx.execute("DROP TABLE import_batch", [])?;
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    /// 写入本次运行的检查结果。每次运行都会插入新的一行，只更新每个 id 最新插入的一行，
+    /// 之前运行的结果作为历史保留。更新的行归入记录的运行（run_id 为 None 时不变），
+    /// 最新的一行属于之前已发布的运行时（本次没有插入新行），在本次运行发布前同样不计入统计
     pub async fn update_status(&self, records: &[MonitorRecord]) -> Result<()> {
         if records.is_empty() {
             return Ok(());
         }
+        let now = Utc::now().to_rfc3339();
         let conn = self.conn.lock().await;
         let tx = conn.unchecked_transaction()?;
         {
@@ -146,7 +429,24 @@ impl DuckDB {
                     response_time_ms BIGINT,
                     is_likely_local_issue BOOLEAN,
                     headers TEXT,
-                    check_time TIMESTAMP
+                    check_time TIMESTAMP,
+                    dns_ms BIGINT,
+                    connect_ms BIGINT,
+                    ttfb_ms BIGINT,
+                    http_version VARCHAR,
+                    connection_reused BOOLEAN,
+                    content_hash VARCHAR,
+                    content_changed BOOLEAN,
+                    auth_used BOOLEAN,
+                    requested_url VARCHAR,
+                    attempts_detail VARCHAR,
+                    total_time_ms BIGINT,
+                    failed_hop_index INTEGER,
+                    failed_hop_url VARCHAR,
+                    timeout_secs INTEGER,
+                    redirect_location VARCHAR,
+                    run_id VARCHAR,
+                    updated_at TIMESTAMP
                 )",
                 [],
             )?;
@@ -164,7 +464,24 @@ impl DuckDB {
                     &record.response_time_ms.map(|t| t as i64),
                     &record.is_likely_local_issue,
                     &record.headers,
-                    &record.check_time.to_rfc3339()
+                    &record.check_time.to_rfc3339(),
+                    &record.dns_ms.map(|t| t as i64),
+                    &record.connect_ms.map(|t| t as i64),
+                    &record.ttfb_ms.map(|t| t as i64),
+                    &record.http_version,
+                    &record.connection_reused,
+                    &record.content_hash,
+                    &record.content_changed,
+                    &record.auth_used,
+                    &record.requested_url,
+                    &attempts_json(record),
+                    &record.total_time_ms.map(|t| t as i64),
+                    &record.failed_hop_index,
+                    &record.failed_hop_url,
+                    &record.timeout_secs.map(|t| t as i64),
+                    &record.redirect_location,
+                    &record.run_id,
+                    &now
                 ])?;
             }
             appender.flush()?;
@@ -180,11 +497,49 @@ impl DuckDB {
                     is_likely_local_issue = t.is_likely_local_issue,
                     headers = t.headers,
                     check_time = t.check_time,
-                    updated_at = CURRENT_TIMESTAMP
-                FROM temp_updates AS t
-                WHERE m.id = t.id",
+                    dns_ms = t.dns_ms,
+                    connect_ms = t.connect_ms,
+                    ttfb_ms = t.ttfb_ms,
+                    http_version = t.http_version,
+                    connection_reused = t.connection_reused,
+                    content_hash = t.content_hash,
+                    content_changed = t.content_changed,
+                    auth_used = t.auth_used,
+                    requested_url = t.requested_url,
+                    attempts_detail = t.attempts_detail,
+                    total_time_ms = t.total_time_ms,
+                    failed_hop_index = t.failed_hop_index,
+                    failed_hop_url = t.failed_hop_url,
+                    timeout_secs = t.timeout_secs,
+                    redirect_location = t.redirect_location,
+                    run_id = COALESCE(t.run_id, m.run_id),
+                    updated_at = t.updated_at
+                FROM (
+                    SELECT temp_updates.*, latest.row_id
+                    FROM temp_updates
+                    JOIN (
+                        SELECT id, MAX(rowid) AS row_id
+                        FROM dataset_monitor
+                        WHERE id IN (SELECT id FROM temp_updates)
+                        GROUP BY id
+                    ) AS latest ON latest.id = temp_updates.id
+                ) AS t
+                WHERE m.rowid = t.row_id",
                 [],
             )?;
+            upsert_url_status(
+                &tx,
+                &self.success,
+                "SELECT u.url, t.check_time, t.status_code
+                FROM temp_updates t
+                JOIN (
+                    SELECT id, arg_max(url, rowid) AS url
+                    FROM dataset_monitor
+                    WHERE id IN (SELECT id FROM temp_updates)
+                    GROUP BY id
+                ) AS u ON u.id = t.id
+                WHERE t.status_code IS NOT NULL OR t.error_category IS NOT NULL",
+            )?;
             tx.execute("DROP TABLE temp_updates", [])?;
         }
         tx.commit()?;
@@ -192,66 +547,2148 @@ impl DuckDB {
         Ok(())
     }
 
-    // 获取问题URL列表
+    /// 失败率不低于 `min_failure_rate` 的URL（最多 100 个），每个带最近 `recent_failures` 次失败的检查，
+    /// last_check/last_error 取自该URL最近一次检查
     pub async fn get_problematic_urls(
         &self,
-        center_name: Option<&str>,
+        filter: &QueryFilter,
         min_failure_rate: f64,
+        recent_failures: usize,
     ) -> Result<Vec<ProblematicUrl>> {
+        let (where_sql, mut values) = filter.sql();
+        values.push(Value::Double(min_failure_rate));
+        values.push(Value::BigInt(recent_failures as i64));
+        let success = self.success.sql("status_code");
+        self.read(QueryGroup::History, move |conn| {
+            let query = format!(
+                "WITH checks AS (
+                    SELECT url, center_name, name, check_time, status_code, error_category, error_msg, response_time_ms,
+                        failed_hop_index, failed_hop_url, NOT {} AS failed
+                    FROM dataset_monitor
+                    WHERE {}
+                ),
+                url_stats AS (
+                    SELECT url, center_name,
+                        arg_max(name, check_time) AS name,
+                        COUNT(*) AS total_checks,
+                        COUNT(*) FILTER (WHERE failed) AS failed_checks,
+                        AVG(response_time_ms) AS avg_response_time,
+                        MAX(check_time) AS last_check,
+                        arg_max(error_msg, check_time) AS last_error
+                    FROM checks
+                    GROUP BY url, center_name
+                    HAVING failed_checks * 100.0 / total_checks >= ?
+                    ORDER BY failed_checks * 100.0 / total_checks DESC, url
+                    LIMIT 100
+                ),
+                recent AS (
+                    SELECT url, center_name, check_time, status_code, error_category, error_msg,
+                        failed_hop_index, failed_hop_url,
+                        row_number() OVER (PARTITION BY url, center_name ORDER BY check_time DESC) AS rn
+                    FROM checks
+                    WHERE failed
+                )
+                SELECT s.url, s.center_name, s.name, s.total_checks, s.failed_checks, s.avg_response_time,
+                    CAST(s.last_check AS VARCHAR), s.last_error,
+                    CAST(r.check_time AS VARCHAR), r.status_code, r.error_category, r.error_msg,
+                    r.failed_hop_index, r.failed_hop_url,
+                    CAST(us.first_seen_at AS VARCHAR), CAST(us.last_success_at AS VARCHAR)
+                FROM url_stats s
+                LEFT JOIN url_status us ON us.url = s.url
+                LEFT JOIN recent r ON r.url = s.url AND r.center_name = s.center_name AND r.rn <= ?
+                ORDER BY s.failed_checks * 100.0 / s.total_checks DESC, s.url, r.check_time DESC",
+                success, where_sql
+            );
+
+            let mut stmt = conn.prepare(&query)?;
+            let mut rows = stmt.query(params_from_iter(&values))?;
+            let mut urls: Vec<ProblematicUrl> = Vec::new();
+            while let Some(row) = rows.next()? {
+                let url: String = row.get(0)?;
+                let center_name: String = row.get(1)?;
+                let is_same = urls.last().is_some_and(|last| last.url == url && last.center_name == center_name);
+                if !is_same {
+                    let total_checks: i64 = row.get(3)?;
+                    let failed_checks: i64 = row.get(4)?;
+                    urls.push(ProblematicUrl {
+                        url,
+                        center_name,
+                        name: row.get(2)?,
+                        total_checks: total_checks as i32,
+                        failed_checks: failed_checks as i32,
+                        failure_rate: percentage(failed_checks, total_checks),
+                        avg_response_time_ms: row.get(5)?,
+                        last_check: row.get(6)?,
+                        last_error: row.get(7)?,
+                        first_seen_at: row.get(14)?,
+                        last_success_at: row.get(15)?,
+                        recent_failures: Vec::new(),
+                    });
+                }
+                if let Some(check_time) = row.get::<_, Option<String>>(8)? {
+                    let failure = RecentFailure {
+                        check_time,
+                        status_code: row.get(9)?,
+                        error_category: row.get(10)?,
+                        error_msg: row.get(11)?,
+                        failed_hop_index: row.get(12)?,
+                        failed_hop_url: row.get(13)?,
+                    };
+                    urls.last_mut().expect("row belongs to the last url").recent_failures.push(failure);
+                }
+            }
+            Ok(urls)
+        }).await
+    }
+
+    /// 各URL在每个数据中心最早的检查时间（含尚未完成检查的记录），用于判断共用URL的归属
+    pub async fn get_url_centers(&self, urls: &[String]) -> Result<HashMap<String, Vec<(String, DateTime<Utc>)>>> {
+        if urls.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let urls = urls.to_vec();
+        self.read(QueryGroup::Stats, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT url, center_name, CAST(MIN(check_time) AS VARCHAR)
+                FROM dataset_monitor
+                WHERE url IN ({})
+                GROUP BY url, center_name",
+                vec!["?"; urls.len()].join(", ")
+            ))?;
+            let rows = stmt.query_map(params_from_iter(urls), |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+            })?;
+            let mut centers: HashMap<String, Vec<(String, DateTime<Utc>)>> = HashMap::new();
+            for (url, center_name, first_checked) in rows.filter_map(Result::ok) {
+                if let Some(first_checked) = parse_timestamp(&first_checked) {
+                    centers.entry(url).or_default().push((center_name, first_checked));
+                }
+            }
+            Ok(centers)
+        }).await
+    }
+
+    /// 时间范围内被多个数据中心检查过的URL，按URL排序。不按统计归属过滤，列出每个数据中心
+    pub async fn get_shared_urls(&self, filter: &QueryFilter) -> Result<Vec<SharedUrl>> {
+        let (where_sql, values) = filter.clone().ignore_attribution().sql();
+        self.read(QueryGroup::Reports, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "WITH checks AS (
+                    SELECT url, center_name, check_time, attributed_center, attribution_rule
+                    FROM dataset_monitor
+                    WHERE {}
+                ),
+                shared AS (
+                    SELECT url,
+                        arg_max(attributed_center, check_time) AS attributed_center,
+                        arg_max(attribution_rule, check_time) AS attribution_rule
+                    FROM checks
+                    GROUP BY url
+                    HAVING COUNT(DISTINCT center_name) > 1
+                )
+                SELECT c.url, c.center_name, CAST(MIN(c.check_time) AS VARCHAR), COUNT(*),
+                    ANY_VALUE(s.attributed_center), ANY_VALUE(s.attribution_rule)
+                FROM checks c
+                JOIN shared s ON s.url = c.url
+                GROUP BY c.url, c.center_name
+                ORDER BY c.url, MIN(c.check_time), c.center_name",
+                where_sql
+            ))?;
+            let rows = stmt.query_map(params_from_iter(values), |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            })?;
+            let mut urls: Vec<SharedUrl> = Vec::new();
+            for (url, center_name, first_checked, checks, attributed_center, attribution_rule) in rows.filter_map(Result::ok) {
+                let Some(first_checked_at) = parse_timestamp(&first_checked) else {
+                    continue;
+                };
+                if urls.last().is_none_or(|last| last.url != url) {
+                    urls.push(SharedUrl { url, centers: Vec::new(), attributed_center, attribution_rule });
+                }
+                let center = SharedUrlCenter { center_name, first_checked_at, checks };
+                urls.last_mut().expect("row belongs to the last url").centers.push(center);
+            }
+            Ok(urls)
+        }).await
+    }
+
+    /// 各URL第一次检查和最后一次检查成功的时间，没有检查过的URL不返回
+    pub async fn get_url_status(&self, urls: &[String]) -> Result<HashMap<String, UrlStatus>> {
+        if urls.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let urls = urls.to_vec();
+        self.read(QueryGroup::Stats, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT url, CAST(first_seen_at AS VARCHAR), CAST(last_success_at AS VARCHAR)
+                FROM url_status
+                WHERE url IN ({})",
+                vec!["?"; urls.len()].join(", ")
+            ))?;
+            let rows = stmt.query_map(params_from_iter(urls), |row| {
+                let first_seen_at: String = row.get(1)?;
+                let last_success_at: Option<String> = row.get(2)?;
+                Ok((row.get::<_, String>(0)?, first_seen_at, last_success_at))
+            })?;
+            Ok(rows.filter_map(Result::ok)
+                .filter_map(|(url, first_seen_at, last_success_at)| Some((url, UrlStatus {
+                    first_seen_at: parse_timestamp(&first_seen_at)?,
+                    last_success_at: last_success_at.as_deref().and_then(parse_timestamp),
+                })))
+                .collect())
+        }).await
+    }
+
+    /// 用本次运行发现的问题替换 `centers` 中各数据中心原有的URL问题
+    pub async fn replace_url_quality_issues(&self, centers: &[String], issues: &[UrlQualityIssue]) -> Result<()> {
+        if centers.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        tx.execute(
+            &format!("DELETE FROM url_quality_issues WHERE center_name IN ({})", vec!["?"; centers.len()].join(", ")),
+            params_from_iter(centers),
+        )?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO url_quality_issues (center_name, id, raw_id, name, url, reason, detected_at)
+                VALUES (?, ?, ?, ?, ?, ?, CAST(? AS TIMESTAMP))",
+            )?;
+            for issue in issues {
+                stmt.execute(params![
+                    issue.center_name, issue.id, issue.raw_id, issue.name, issue.url,
+                    issue.reason.as_str(), issue.detected_at.to_rfc3339()
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 记录的URL问题，`center` 为 None 时返回所有数据中心，按数据中心、原因、URL排序
+    pub async fn get_url_quality_issues(&self, center: Option<&str>) -> Result<Vec<UrlQualityIssue>> {
+        let center = center.map(str::to_string);
+        self.read(QueryGroup::Reports, move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT center_name, id, raw_id, name, url, reason, CAST(detected_at AS VARCHAR)
+                FROM url_quality_issues
+                WHERE CAST(? AS VARCHAR) IS NULL OR center_name = ?
+                ORDER BY center_name, reason, url",
+            )?;
+            let rows = stmt.query_map(params![center, center], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, String>(6)?,
+                ))
+            })?;
+            Ok(rows.filter_map(Result::ok)
+                .filter_map(|(center_name, id, raw_id, name, url, reason, detected_at)| Some(UrlQualityIssue {
+                    center_name,
+                    id,
+                    raw_id,
+                    name,
+                    url,
+                    reason: reason.parse().ok()?,
+                    detected_at: parse_timestamp(&detected_at)?,
+                }))
+                .collect())
+        }).await
+    }
+
+    /// 每个记录 id 最近一次检查的状态码（尚未检查过的不返回）
+    pub async fn get_last_status_codes(&self) -> Result<HashMap<String, Option<u16>>> {
         let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT id, arg_max(status_code, check_time)
+            FROM dataset_monitor
+            WHERE id IS NOT NULL AND (status_code IS NOT NULL OR error_category IS NOT NULL)
+            GROUP BY id"
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<u16>>(1)?))
+        })?;
+        Ok(rows.filter_map(Result::ok).collect())
+    }
 
-        let where_clause = if let Some(name) = center_name {
-            format!("WHERE h.center_name = '{}'", name)
-        } else {
-            String::new()
+    /// 各数据集最近一次的响应体指纹（id -> 指纹）
+    pub async fn get_last_content_hashes(&self) -> Result<HashMap<String, String>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT id, arg_max(content_hash, check_time)
+            FROM dataset_monitor
+            WHERE id IS NOT NULL AND content_hash IS NOT NULL
+            GROUP BY id"
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        Ok(rows.filter_map(Result::ok).collect())
+    }
+
+    /// 时间范围内响应体指纹发生变化的检查，按检查时间倒序
+    pub async fn get_content_changes(&self, filter: &QueryFilter) -> Result<Vec<ContentChange>> {
+        let (where_sql, values) = filter.sql();
+        self.read(QueryGroup::History, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT url, center_name, name, CAST(check_time AS VARCHAR), status_code, content_hash
+                FROM dataset_monitor
+                WHERE {} AND content_changed
+                ORDER BY check_time DESC",
+                where_sql
+            ))?;
+            let rows = stmt.query_map(params_from_iter(&values), |row| {
+                Ok(ContentChange {
+                    url: row.get(0)?,
+                    center_name: row.get(1)?,
+                    name: row.get(2)?,
+                    check_time: row.get(3)?,
+                    status_code: row.get(4)?,
+                    content_hash: row.get(5)?,
+                })
+            })?;
+            Ok(rows.filter_map(Result::ok).collect())
+        }).await
+    }
+
+    /// 指定数据集在 `since` 之后最近一次检查的结果
+    pub async fn get_latest_record(&self, center_name: &str, raw_id: &str, since: DateTime<Utc>) -> Result<Option<MonitorRecord>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+            FROM dataset_monitor
+            WHERE center_name = ? AND raw_id = ? AND check_time >= CAST(? AS TIMESTAMP)
+                AND (status_code IS NOT NULL OR error_category IS NOT NULL)
+            ORDER BY check_time DESC
+            LIMIT 1",
+            RECORD_COLUMNS
+        ))?;
+        let mut rows = stmt.query_map(params![center_name, raw_id, since.to_rfc3339()], read_record)?;
+        Ok(rows.next().transpose()?)
+    }
+
+    /// 按数据中心自己的数据集ID（`@id`）查询最近 `limit` 次检查，按检查时间倒序，可能跨多个数据中心
+    pub async fn get_records_by_raw_id(&self, raw_id: &str, limit: usize) -> Result<Vec<MonitorRecord>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+            FROM dataset_monitor
+            WHERE raw_id = ? AND (status_code IS NOT NULL OR error_category IS NOT NULL)
+            ORDER BY check_time DESC
+            LIMIT ?",
+            RECORD_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![raw_id, limit as i64], read_record)?;
+        Ok(rows.collect::<duckdb::Result<_>>()?)
+    }
+
+    pub async fn insert_fetch_metrics(&self, metrics: &FetchMetrics) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO fetch_metrics VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &metrics.center_name,
+                &metrics.started_at.to_rfc3339(),
+                &metrics.finished_at.to_rfc3339(),
+                metrics.list_requests as i64,
+                metrics.list_duration_ms as i64,
+                metrics.detail_requests as i64,
+                metrics.avg_detail_ms,
+                metrics.bytes as i64,
+                metrics.errors as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub async fn insert_cmdb_delivery(&self, delivery: &CmdbDelivery) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO cmdb_deliveries VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &delivery.center_name,
+                &delivery.run_id,
+                &delivery.delivered_at.to_rfc3339(),
+                delivery.success,
+                delivery.attempts,
+                delivery.http_status,
+                &delivery.error
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 时间范围内的数据获取性能记录，按开始时间排序
+    pub async fn get_fetch_metrics(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<FetchMetrics>> {
+        self.read(QueryGroup::Stats, move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT center_name, CAST(started_at AS VARCHAR), CAST(finished_at AS VARCHAR), list_requests,
+                        list_duration_ms, detail_requests, avg_detail_ms, bytes, errors
+                FROM fetch_metrics
+                WHERE started_at >= CAST(? AS TIMESTAMP) AND started_at < CAST(? AS TIMESTAMP)
+                ORDER BY started_at"
+            )?;
+            let rows = stmt.query_map(params![since.to_rfc3339(), until.to_rfc3339()], |row| {
+                Ok(FetchMetrics {
+                    center_name: row.get(0)?,
+                    started_at: parse_timestamp(&row.get::<_, String>(1)?).unwrap_or_default(),
+                    finished_at: parse_timestamp(&row.get::<_, String>(2)?).unwrap_or_default(),
+                    list_requests: row.get::<_, i64>(3)? as u64,
+                    list_duration_ms: row.get::<_, i64>(4)? as u64,
+                    detail_requests: row.get::<_, i64>(5)? as u64,
+                    avg_detail_ms: row.get(6)?,
+                    bytes: row.get::<_, i64>(7)? as u64,
+                    errors: row.get::<_, i64>(8)? as u64,
+                })
+            })?;
+            Ok(rows.filter_map(Result::ok).collect())
+        }).await
+    }
+
+    /// `config` 为运行配置快照的 JSON，哈希取 summary.config_hash；续跑的运行替换取消时写入的部分汇总
+    pub async fn insert_run(&self, summary: &MonitorSummary, config: &str) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT OR REPLACE INTO monitor_runs (run_id, started_at, finished_at, total, success, summary, config, config_hash,
+                egress_ip, hostname, db_bytes_start, wal_bytes_start, db_bytes_end, wal_bytes_end)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &summary.run_id,
+                &summary.started_at.to_rfc3339(),
+                &summary.finished_at.to_rfc3339(),
+                summary.total as i64,
+                summary.success as i64,
+                serde_json::to_string(summary)?,
+                config,
+                &summary.config_hash,
+                summary.environment.as_ref().and_then(|e| e.egress_ip.as_deref()),
+                summary.environment.as_ref().and_then(|e| e.hostname.as_deref()),
+                summary.storage.as_ref().and_then(|s| s.start.db_bytes).map(|b| b as i64),
+                summary.storage.as_ref().and_then(|s| s.start.wal_bytes).map(|b| b as i64),
+                summary.storage.as_ref().and_then(|s| s.end.db_bytes).map(|b| b as i64),
+                summary.storage.as_ref().and_then(|s| s.end.wal_bytes).map(|b| b as i64)
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 单次运行的汇总和配置快照
+    pub async fn get_run(&self, run_id: &str) -> Result<Option<StoredRun>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare("SELECT summary, config, config_hash FROM monitor_runs WHERE run_id = ?")?;
+        let mut rows = stmt.query_map(params![run_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, Option<String>>(2)?))
+        })?;
+        let Some((summary, config, config_hash)) = rows.next().transpose()? else {
+            return Ok(None);
+        };
+        Ok(Some(StoredRun {
+            summary: serde_json::from_str(&summary)?,
+            config_hash,
+            config: config.map(|c| serde_json::from_str(&c)).transpose()?,
+        }))
+    }
+
+    /// 把时间范围内的检查记录导出为 Parquet 文件，返回导出的行数
+    pub async fn export_parquet(&self, since: DateTime<Utc>, until: DateTime<Utc>, path: &str) -> Result<usize> {
+        let conn = self.conn.lock().await;
+        // COPY 不支持参数绑定，时间由 chrono 格式化，路径中的单引号转义
+        let rows = conn.execute(&format!(
+            "COPY (
+                SELECT * FROM dataset_monitor
+                WHERE check_time >= CAST('{}' AS TIMESTAMP) AND check_time < CAST('{}' AS TIMESTAMP)
+                ORDER BY check_time
+            ) TO '{}' (FORMAT PARQUET, COMPRESSION ZSTD)",
+            since.to_rfc3339(),
+            until.to_rfc3339(),
+            path.replace('\'', "''"),
+        ), [])?;
+        Ok(rows)
+    }
+
+    /// 最近的运行汇总，按开始时间倒序
+    pub async fn get_recent_runs(&self, limit: usize) -> Result<Vec<MonitorSummary>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT summary FROM monitor_runs ORDER BY started_at DESC LIMIT ?"
+        )?;
+        let rows = stmt.query_map(params![limit as i64], |row| row.get::<_, String>(0))?;
+
+        let mut runs = Vec::new();
+        for row in rows {
+            match serde_json::from_str(&row?) {
+                Ok(summary) => runs.push(summary),
+                Err(e) => warn!("解析运行汇总失败: {}", e),
+            }
+        }
+        Ok(runs)
+    }
+
+    /// 数据库文件和 WAL 文件当前的大小。取文件的逻辑长度（与平台的块分配方式无关），
+    /// 文件不存在（尚未创建、内存数据库、WAL 已写回）时为 None
+    pub fn storage_size(&self) -> StorageSize {
+        StorageSize {
+            db_bytes: file_size(&self.path),
+            wal_bytes: file_size(&format!("{}.wal", self.path)),
+        }
+    }
+
+    /// 最近 `limit` 次记录了数据库大小的运行，按开始时间排序
+    pub async fn get_storage_series(&self, limit: usize) -> Result<Vec<StorageSample>> {
+        self.read(QueryGroup::Stats, move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT * FROM (
+                    SELECT run_id, CAST(started_at AS VARCHAR), CAST(finished_at AS VARCHAR), total,
+                        db_bytes_start, wal_bytes_start, db_bytes_end, wal_bytes_end
+                    FROM monitor_runs
+                    WHERE db_bytes_start IS NOT NULL OR db_bytes_end IS NOT NULL
+                    ORDER BY started_at DESC
+                    LIMIT ?
+                ) ORDER BY 2",
+            )?;
+            let bytes = |row: &duckdb::Row<'_>, index: usize| row.get::<_, Option<i64>>(index).map(|b| b.map(|b| b as u64));
+            let samples = stmt.query_map(params![limit as i64], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                    StorageSize { db_bytes: bytes(row, 4)?, wal_bytes: bytes(row, 5)? },
+                    StorageSize { db_bytes: bytes(row, 6)?, wal_bytes: bytes(row, 7)? },
+                ))
+            })?
+                .filter_map(Result::ok)
+                .filter_map(|(run_id, started_at, finished_at, total, start, end)| Some(StorageSample {
+                    run_id,
+                    started_at: parse_timestamp(&started_at)?,
+                    finished_at: parse_timestamp(&finished_at)?,
+                    total,
+                    growth_bytes: end.total_bytes() as i64 - start.total_bytes() as i64,
+                    start,
+                    end,
+                }))
+                .collect();
+            Ok(samples)
+        }).await
+    }
+
+    /// 删除检查时间早于 `before` 的检查记录（每个数据集保留最近一条，供续跑和更新状态使用），
+    /// 之后写回 WAL。DuckDB 会复用删除后空出的空间，但不会缩小数据库文件。返回删除的行数
+    pub async fn prune_history(&self, before: DateTime<Utc>) -> Result<usize> {
+        let conn = self.conn.lock().await;
+        let deleted = conn.execute(
+            "DELETE FROM dataset_monitor
+            WHERE check_time < CAST(? AS TIMESTAMP)
+                AND rowid NOT IN (SELECT MAX(rowid) FROM dataset_monitor GROUP BY id)",
+            params![before.to_rfc3339()],
+        )?;
+        conn.execute_batch("CHECKPOINT")?;
+        Ok(deleted)
+    }
+
+    /// 已完成检查的记录中最早和最晚的检查时间，没有记录时为 None
+    pub async fn get_check_time_range(&self) -> Result<(Option<DateTime<Utc>>, Option<DateTime<Utc>>)> {
+        self.read(QueryGroup::Stats, move |conn| {
+            let (earliest, latest) = conn.query_row(
+                "SELECT CAST(MIN(check_time) AS VARCHAR), CAST(MAX(check_time) AS VARCHAR)
+                FROM dataset_monitor
+                WHERE status_code IS NOT NULL OR error_category IS NOT NULL",
+                [],
+                |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<String>>(1)?)),
+            )?;
+            Ok((earliest.as_deref().and_then(parse_timestamp), latest.as_deref().and_then(parse_timestamp)))
+        }).await
+    }
+
+    /// 最近一次检查的时间（整体和各数据中心）和最近一次写入汇总的运行
+    pub async fn get_data_as_of(&self) -> Result<DataAsOf> {
+        self.read(QueryGroup::Stats, move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT center_name, CAST(MAX(check_time) AS VARCHAR)
+                FROM dataset_monitor
+                WHERE status_code IS NOT NULL OR error_category IS NOT NULL
+                GROUP BY center_name",
+            )?;
+            let centers: BTreeMap<String, DateTime<Utc>> = stmt
+                .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+                .filter_map(Result::ok)
+                .filter_map(|(center, time)| Some((center, parse_timestamp(&time)?)))
+                .collect();
+            let mut stmt = conn.prepare(
+                "SELECT r.run_id, CAST(r.started_at AS VARCHAR), CAST(r.finished_at AS VARCHAR), r.total, r.success, s.status
+                FROM monitor_runs r
+                LEFT JOIN monitor_run_state s ON s.run_id = r.run_id
+                ORDER BY r.finished_at DESC
+                LIMIT 1",
+            )?;
+            let latest_run = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            })?
+                .filter_map(Result::ok)
+                .find_map(|(run_id, started_at, finished_at, total, success, status)| Some(LatestRun {
+                    run_id,
+                    started_at: parse_timestamp(&started_at)?,
+                    finished_at: parse_timestamp(&finished_at)?,
+                    total,
+                    success,
+                    status,
+                }));
+            Ok(DataAsOf {
+                latest_check_time: centers.values().max().copied(),
+                centers,
+                latest_run,
+            })
+        }).await
+    }
+
+    /// 时间范围内各数据中心的可用率
+    pub async fn get_center_availability(&self, filter: &QueryFilter) -> Result<Vec<CenterAvailability>> {
+        let (where_sql, values) = filter.sql();
+        let success = self.success.sql("status_code");
+        self.read(QueryGroup::Stats, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT center_name, COUNT(*), COUNT(*) FILTER (WHERE {}), AVG(response_time_ms)
+                FROM dataset_monitor
+                WHERE {}
+                GROUP BY center_name
+                ORDER BY center_name",
+                success, where_sql
+            ))?;
+            let rows = stmt.query_map(params_from_iter(&values), |row| {
+                let total_checks: i64 = row.get(1)?;
+                let success_checks: i64 = row.get(2)?;
+                Ok(CenterAvailability {
+                    center_name: row.get(0)?,
+                    total_checks,
+                    success_checks,
+                    availability: percentage(success_checks, total_checks),
+                    avg_response_time_ms: row.get(3)?,
+                })
+            })?;
+            Ok(rows.filter_map(Result::ok).collect())
+        }).await
+    }
+
+    /// 时间范围内各数据中心各阶段的平均耗时，无法测量的阶段（NULL）不计入平均值
+    /// `basis` 决定 avg_total_ms 取最后一次尝试的耗时还是包含重试的总耗时
+    pub async fn get_timing_breakdown(&self, filter: &QueryFilter, basis: TimeBasis) -> Result<Vec<TimingBreakdown>> {
+        let total = match basis {
+            TimeBasis::Final => "response_time_ms",
+            // 没有 total_time_ms 的旧记录没有重试，总耗时即响应时间
+            TimeBasis::Total => "COALESCE(total_time_ms, response_time_ms)",
+        };
+        let (where_sql, values) = filter.sql();
+        self.read(QueryGroup::Stats, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT center_name, COUNT(*), COUNT(connect_ms), AVG(dns_ms), AVG(connect_ms), AVG(ttfb_ms), AVG({})
+                FROM dataset_monitor
+                WHERE {}
+                GROUP BY center_name
+                ORDER BY center_name",
+                total, where_sql
+            ))?;
+            let rows = stmt.query_map(params_from_iter(&values), |row| {
+                Ok(TimingBreakdown {
+                    center_name: row.get(0)?,
+                    checks: row.get(1)?,
+                    new_connections: row.get(2)?,
+                    avg_dns_ms: row.get(3)?,
+                    avg_connect_ms: row.get(4)?,
+                    avg_ttfb_ms: row.get(5)?,
+                    avg_total_ms: row.get(6)?,
+                })
+            })?;
+            Ok(rows.filter_map(Result::ok).collect())
+        }).await
+    }
+
+    /// 时间范围内各数据中心按HTTP版本统计的检查数，没有收到响应的检查不计入
+    pub async fn get_http_version_counts(&self, filter: &QueryFilter) -> Result<Vec<HttpVersionCount>> {
+        let (where_sql, values) = filter.sql();
+        self.read(QueryGroup::Stats, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT center_name, http_version, COUNT(*), COUNT(*) FILTER (WHERE connection_reused)
+                FROM dataset_monitor
+                WHERE {} AND http_version IS NOT NULL
+                GROUP BY center_name, http_version
+                ORDER BY center_name, http_version",
+                where_sql
+            ))?;
+            let rows = stmt.query_map(params_from_iter(&values), |row| {
+                Ok(HttpVersionCount {
+                    center_name: row.get(0)?,
+                    http_version: row.get(1)?,
+                    checks: row.get(2)?,
+                    reused: row.get(3)?,
+                })
+            })?;
+            Ok(rows.filter_map(Result::ok).collect())
+        }).await
+    }
+
+    /// 时间范围内按状态码或状态码类别统计的检查数，没有收到响应的检查归为 no_response
+    pub async fn get_status_code_stats(&self, filter: &QueryFilter, group_by: StatusGrouping) -> Result<Vec<StatusCodeStats>> {
+        let label = match group_by {
+            StatusGrouping::Class => "CAST(status_code // 100 AS VARCHAR) || 'xx'",
+            StatusGrouping::Code => "CAST(status_code AS VARCHAR)",
+        };
+        let (where_sql, values) = filter.sql();
+        self.read(QueryGroup::Stats, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT COALESCE({}, 'no_response') AS label, COUNT(*) AS count, MIN(status_code) AS code
+                FROM dataset_monitor
+                WHERE {}
+                GROUP BY label
+                ORDER BY code NULLS LAST",
+                label, where_sql
+            ))?;
+            let rows: Vec<(String, i64)> = stmt.query_map(params_from_iter(&values), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?.filter_map(Result::ok).collect();
+            let total = rows.iter().map(|(_, count)| count).sum();
+            Ok(rows.into_iter()
+                .map(|(label, count)| StatusCodeStats { label, count, percentage: percentage(count, total) })
+                .collect())
+        }).await
+    }
+
+    /// 按URL主机汇总符合条件的检查，失败数据集按每个数据集最近一次检查判断，最多返回 `limit` 个主机
+    pub async fn get_host_stats(&self, filter: &QueryFilter, sort: HostSort, limit: usize) -> Result<Vec<HostStats>> {
+        let order = match sort {
+            HostSort::Failures => "failing_datasets DESC, t.failed_checks DESC, l.host",
+            HostSort::Datasets => "datasets DESC, l.host",
+        };
+        let (where_sql, mut values) = filter.sql();
+        values.push(Value::BigInt(limit as i64));
+        let success = self.success.sql("status_code");
+        self.read(QueryGroup::Stats, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "WITH checks AS (
+                    SELECT id, host, center_name, check_time, NOT {} AS failed
+                    FROM dataset_monitor
+                    WHERE {} AND host IS NOT NULL
+                ),
+                latest AS (
+                    SELECT host, center_name, failed
+                    FROM checks
+                    QUALIFY row_number() OVER (PARTITION BY id ORDER BY check_time DESC) = 1
+                ),
+                totals AS (
+                    SELECT host, COUNT(*) AS total_checks, COUNT(*) FILTER (WHERE failed) AS failed_checks,
+                        CAST(MAX(check_time) AS VARCHAR) AS last_check
+                    FROM checks
+                    GROUP BY host
+                )
+                SELECT l.host, array_to_string(list_sort(list(DISTINCT l.center_name)), chr(10)),
+                    COUNT(*) AS datasets, COUNT(*) FILTER (WHERE l.failed) AS failing_datasets,
+                    t.total_checks, t.failed_checks, t.last_check
+                FROM latest l
+                JOIN totals t ON t.host = l.host
+                GROUP BY l.host, t.total_checks, t.failed_checks, t.last_check
+                ORDER BY {}
+                LIMIT ?",
+                success, where_sql, order
+            ))?;
+            let rows = stmt.query_map(params_from_iter(&values), |row| {
+                let total_checks: i64 = row.get(4)?;
+                let failed_checks: i64 = row.get(5)?;
+                Ok(HostStats {
+                    host: row.get(0)?,
+                    centers: row.get::<_, String>(1)?.split('\n').map(str::to_string).collect(),
+                    datasets: row.get(2)?,
+                    failing_datasets: row.get(3)?,
+                    total_checks,
+                    failed_checks,
+                    failure_rate: percentage(failed_checks, total_checks),
+                    last_check: row.get(6)?,
+                })
+            })?;
+            Ok(rows.filter_map(Result::ok).collect())
+        }).await
+    }
+
+    /// URL最近 `limit` 次检查的结果，按检查时间倒序
+    pub async fn get_url_history(&self, url: &str, limit: usize) -> Result<Vec<HealthCheck>> {
+        let url = url.to_string();
+        self.read(QueryGroup::History, move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT CAST(check_time AS VARCHAR), status_code, error_category, response_time_ms,
+                        is_likely_local_issue, http_version, connection_reused, requested_url
+                FROM dataset_monitor
+                WHERE url = ? AND (status_code IS NOT NULL OR error_category IS NOT NULL)
+                ORDER BY check_time DESC
+                LIMIT ?"
+            )?;
+            let rows = stmt.query_map(params![url, limit as i64], |row| {
+                Ok(HealthCheck {
+                    check_time: row.get(0)?,
+                    status_code: row.get(1)?,
+                    error_category: row.get(2)?,
+                    response_time_ms: row.get(3)?,
+                    is_likely_local_issue: row.get(4)?,
+                    http_version: row.get(5)?,
+                    connection_reused: row.get(6)?,
+                    requested_url: row.get(7)?,
+                })
+            })?;
+            Ok(rows.filter_map(Result::ok).collect())
+        }).await
+    }
+
+    /// 符合条件的检查中失败的URL，每个URL取最后一次检查的结果
+    pub async fn get_broken_links(&self, filter: &QueryFilter) -> Result<Vec<BrokenLink>> {
+        let (where_sql, values) = filter.sql();
+        let success = self.success.sql("status_code");
+        self.read(QueryGroup::Reports, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT url, name, status_code, status_text, error_category, error_msg, CAST(check_time AS VARCHAR)
+                FROM dataset_monitor
+                WHERE {}
+                QUALIFY row_number() OVER (PARTITION BY url ORDER BY check_time DESC) = 1 AND NOT {}
+                ORDER BY url",
+                where_sql, success
+            ))?;
+            let rows = stmt.query_map(params_from_iter(&values), |row| {
+                Ok(BrokenLink {
+                    url: row.get(0)?,
+                    name: row.get(1)?,
+                    status_code: row.get(2)?,
+                    status_text: row.get(3)?,
+                    error_category: row.get(4)?,
+                    error_msg: row.get(5)?,
+                    check_time: row.get(6)?,
+                })
+            })?;
+            Ok(rows.filter_map(Result::ok).collect())
+        }).await
+    }
+
+    /// 时间范围内每天的整体可用率
+    pub async fn get_daily_availability(&self, filter: &QueryFilter) -> Result<Vec<DailyAvailability>> {
+        let (where_sql, values) = filter.sql();
+        let success = self.success.sql("status_code");
+        self.read(QueryGroup::Trends, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT CAST(CAST(check_time AS DATE) AS VARCHAR) AS day, COUNT(*), COUNT(*) FILTER (WHERE {})
+                FROM dataset_monitor
+                WHERE {}
+                GROUP BY day
+                ORDER BY day",
+                success, where_sql
+            ))?;
+            let rows = stmt.query_map(params_from_iter(&values), |row| {
+                let total_checks: i64 = row.get(1)?;
+                let success_checks: i64 = row.get(2)?;
+                Ok(DailyAvailability {
+                    date: row.get(0)?,
+                    total_checks,
+                    success_checks,
+                    availability: percentage(success_checks, total_checks),
+                })
+            })?;
+            Ok(rows.filter_map(Result::ok).collect())
+        }).await
+    }
+
+    /// 时间范围内各数据中心每天的 (数据中心, 日期, 检查数, 成功数)，没有检查的日期不返回
+    pub async fn get_center_daily_counts(&self, filter: &QueryFilter) -> Result<Vec<(String, String, i64, i64)>> {
+        let (where_sql, values) = filter.sql();
+        let success = self.success.sql("status_code");
+        self.read(QueryGroup::Trends, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT center_name, CAST(CAST(check_time AS DATE) AS VARCHAR) AS day, COUNT(*), COUNT(*) FILTER (WHERE {})
+                FROM dataset_monitor
+                WHERE {}
+                GROUP BY center_name, day",
+                success, where_sql
+            ))?;
+            let rows = stmt.query_map(params_from_iter(&values), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?;
+            Ok(rows.filter_map(Result::ok).collect())
+        }).await
+    }
+
+    /// 时间范围内每小时（UTC）的检查数和成功率，没有检查的小时不返回
+    pub async fn get_hourly_stats(&self, filter: &QueryFilter) -> Result<Vec<HourlyStats>> {
+        let (where_sql, values) = filter.sql();
+        let success = self.success.sql("status_code");
+        self.read(QueryGroup::Trends, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT strftime(date_trunc('hour', check_time), '%Y-%m-%dT%H:00:00Z') AS hour, COUNT(*),
+                    COUNT(*) FILTER (WHERE {}), AVG(response_time_ms)
+                FROM dataset_monitor
+                WHERE {}
+                GROUP BY hour
+                ORDER BY hour",
+                success, where_sql
+            ))?;
+            let rows = stmt.query_map(params_from_iter(&values), |row| {
+                let total_checks: i64 = row.get(1)?;
+                let success_checks: i64 = row.get(2)?;
+                Ok(HourlyStats {
+                    hour: row.get(0)?,
+                    total_checks,
+                    success_checks,
+                    availability: percentage(success_checks, total_checks),
+                    avg_response_time_ms: row.get(3)?,
+                })
+            })?;
+            Ok(rows.filter_map(Result::ok).collect())
+        }).await
+    }
+
+    /// 时间范围内失败率最高的URL
+    pub async fn get_top_failing_urls(&self, filter: &QueryFilter, limit: usize) -> Result<Vec<ProblematicUrl>> {
+        let (where_sql, mut values) = filter.sql();
+        values.push(Value::BigInt(limit as i64));
+        let success = self.success.sql("status_code");
+        self.read(QueryGroup::Stats, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT f.*, CAST(us.first_seen_at AS VARCHAR), CAST(us.last_success_at AS VARCHAR)
+                FROM (
+                    SELECT url, center_name, any_value(name), COUNT(*) AS total_checks,
+                        COUNT(*) FILTER (WHERE NOT {}) AS failed_checks,
+                        AVG(response_time_ms), CAST(MAX(check_time) AS VARCHAR), arg_max(error_msg, check_time)
+                    FROM dataset_monitor
+                    WHERE {}
+                    GROUP BY url, center_name
+                    HAVING failed_checks > 0
+                    ORDER BY failed_checks * 1.0 / total_checks DESC, failed_checks DESC, url
+                    LIMIT ?
+                ) AS f
+                LEFT JOIN url_status us ON us.url = f.url
+                ORDER BY f.failed_checks * 1.0 / f.total_checks DESC, f.failed_checks DESC, f.url",
+                success, where_sql
+            ))?;
+            let rows = stmt.query_map(params_from_iter(&values), |row| {
+                let total_checks: i64 = row.get(3)?;
+                let failed_checks: i64 = row.get(4)?;
+                Ok(ProblematicUrl {
+                    url: row.get(0)?,
+                    center_name: row.get(1)?,
+                    name: row.get(2)?,
+                    total_checks: total_checks as i32,
+                    failed_checks: failed_checks as i32,
+                    failure_rate: percentage(failed_checks, total_checks),
+                    avg_response_time_ms: row.get(5)?,
+                    last_check: row.get(6)?,
+                    last_error: row.get(7)?,
+                    first_seen_at: row.get(8)?,
+                    last_success_at: row.get(9)?,
+                    recent_failures: Vec::new(),
+                })
+            })?;
+            Ok(rows.filter_map(Result::ok).collect())
+        }).await
+    }
+
+    /// 各错误类别按 `granularity` 划分的检查数时间序列，时间范围取 filter 的 since/until（必须设置），
+    /// 每个出现过的类别都包含范围内的所有时间段，没有数据的时间段为 0
+    pub async fn get_error_trends(&self, filter: &QueryFilter, granularity: TrendGranularity) -> Result<BTreeMap<String, Vec<TrendPoint>>> {
+        let (Some(since), Some(until)) = (filter.since, filter.until) else {
+            anyhow::bail!("错误类别趋势需要指定时间范围");
+        };
+        let (where_sql, values) = filter.sql();
+        let rows = self.read(QueryGroup::Trends, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT strftime(CAST(date_trunc('{}', check_time) AS TIMESTAMP), '{}') AS bucket, error_category, COUNT(*)
+                FROM dataset_monitor
+                WHERE {} AND error_category IS NOT NULL
+                GROUP BY bucket, error_category",
+                granularity.sql_unit(), TREND_BUCKET_FORMAT, where_sql
+            ))?;
+            let rows = stmt.query_map(params_from_iter(&values), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            Ok(rows.filter_map(Result::ok).collect())
+        }).await?;
+        Ok(pivot_trends(&granularity.buckets(since, until), rows))
+    }
+
+    /// 时间范围内失败检查的错误类别分布，按数量降序
+    pub async fn get_error_category_counts(&self, filter: &QueryFilter) -> Result<Vec<CategoryCount>> {
+        let (where_sql, values) = filter.sql();
+        let success = self.success.sql("status_code");
+        self.read(QueryGroup::Stats, move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT COALESCE(error_category, 'HTTP_' || CAST(status_code AS VARCHAR)) AS category, COUNT(*) AS count
+                FROM dataset_monitor
+                WHERE {} AND NOT {}
+                GROUP BY category
+                ORDER BY count DESC, category",
+                where_sql, success
+            ))?;
+            let rows = stmt.query_map(params_from_iter(&values), |row| {
+                Ok(CategoryCount {
+                    category: row.get(0)?,
+                    count: row.get::<_, i64>(1)? as usize,
+                })
+            })?;
+            Ok(rows.filter_map(Result::ok).collect())
+        }).await
+    }
+
+    pub async fn get_alert_state(&self, rule: &str, subject: &str) -> Result<Option<AlertState>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!("{} WHERE rule = ? AND subject = ?", SELECT_ALERT_STATE))?;
+        let mut rows = stmt.query_map(params![rule, subject], alert_state_from_row)?;
+        Ok(rows.next().transpose()?)
+    }
+
+    pub async fn upsert_alert_state(&self, state: &AlertState) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT OR REPLACE INTO alert_state (rule, subject, is_open, last_fired_at, last_value, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                &state.rule,
+                &state.subject,
+                state.open,
+                state.last_fired_at.map(|t| t.to_rfc3339()),
+                state.last_value,
+                state.updated_at.to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 当前未恢复的告警
+    pub async fn get_open_alerts(&self) -> Result<Vec<AlertState>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!("{} WHERE is_open ORDER BY last_fired_at DESC", SELECT_ALERT_STATE))?;
+        let rows = stmt.query_map([], alert_state_from_row)?;
+        Ok(rows.filter_map(Result::ok).collect())
+    }
+
+    /// 记录开始一次运行，续跑时只更新时间
+    pub async fn start_run_state(&self, run_id: &str, started_at: DateTime<Utc>, centers: &[String]) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO monitor_run_state (run_id, started_at, centers, status, updated_at)
+            VALUES (?, CAST(? AS TIMESTAMP), ?, 'running', CAST(? AS TIMESTAMP))
+            ON CONFLICT (run_id) DO UPDATE SET status = 'running', updated_at = excluded.updated_at",
+            params![run_id, started_at.to_rfc3339(), serde_json::to_string(centers)?, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// 记录已写库（或写入溢出文件）的记录 id
+    pub async fn record_progress(&self, run_id: &str, ids: &[&str]) -> Result<()> {
+        let conn = self.conn.lock().await;
+        let mut appender = conn.appender("run_progress")?;
+        for id in ids {
+            appender.append_row(params![run_id, id])?;
+        }
+        appender.flush()?;
+        Ok(())
+    }
+
+    /// 结束一次运行（finished 或 aborted），清除进度并发布运行的记录。
+    /// aborted 的运行不会再继续，已写入的检查结果同样发布
+    pub async fn finish_run_state(&self, run_id: &str, status: &str) -> Result<()> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        let now = Utc::now().to_rfc3339();
+        tx.execute(
+            "UPDATE monitor_run_state SET status = ?, updated_at = CAST(? AS TIMESTAMP) WHERE run_id = ?",
+            params![status, &now, run_id],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO published_runs (run_id, published_at) VALUES (?, CAST(? AS TIMESTAMP))",
+            params![run_id, &now],
+        )?;
+        tx.execute("DELETE FROM run_progress WHERE run_id = ?", params![run_id])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 删除不属于任何未结束运行的进度（结束运行时没有清除干净的），返回删除的行数
+    pub async fn delete_orphaned_progress(&self) -> Result<usize> {
+        let conn = self.conn.lock().await;
+        Ok(conn.execute(
+            "DELETE FROM run_progress WHERE run_id NOT IN (SELECT run_id FROM monitor_run_state WHERE status = 'running')",
+            [],
+        )?)
+    }
+
+    /// 尚未结束的运行，按开始时间倒序
+    pub async fn get_unfinished_runs(&self) -> Result<Vec<UnfinishedRun>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT s.run_id, CAST(s.started_at AS VARCHAR), s.centers, CAST(s.updated_at AS VARCHAR),
+                (SELECT COUNT(DISTINCT p.id) FROM run_progress p WHERE p.run_id = s.run_id)
+            FROM monitor_run_state s
+            WHERE s.status = 'running'
+            ORDER BY s.started_at DESC"
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(UnfinishedRun {
+                run_id: row.get(0)?,
+                started_at: row.get::<_, String>(1).ok().as_deref().and_then(parse_timestamp).unwrap_or_default(),
+                centers: serde_json::from_str(&row.get::<_, String>(2)?).unwrap_or_default(),
+                updated_at: row.get::<_, String>(3).ok().as_deref().and_then(parse_timestamp).unwrap_or_default(),
+                completed: row.get::<_, i64>(4)? as usize,
+            })
+        })?;
+        Ok(rows.filter_map(Result::ok).collect())
+    }
+
+    /// 运行中已完成的记录 id
+    pub async fn get_run_progress(&self, run_id: &str) -> Result<HashSet<String>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare("SELECT DISTINCT id FROM run_progress WHERE run_id = ?")?;
+        let rows = stmt.query_map(params![run_id], |row| row.get::<_, String>(0))?;
+        Ok(rows.filter_map(Result::ok).collect())
+    }
+
+    /// 运行中已完成的记录在本次运行中的检查结果（`since` 为运行开始时间），用于续跑后汇总
+    pub async fn get_run_progress_records(&self, run_id: &str, since: DateTime<Utc>) -> Result<Vec<MonitorRecord>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+            FROM dataset_monitor
+            WHERE id IN (SELECT id FROM run_progress WHERE run_id = ?) AND check_time >= CAST(? AS TIMESTAMP)
+                AND (status_code IS NOT NULL OR error_category IS NOT NULL)
+            QUALIFY row_number() OVER (PARTITION BY id ORDER BY check_time DESC) = 1",
+            RECORD_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![run_id, since.to_rfc3339()], read_record)?;
+        Ok(rows.filter_map(Result::ok).collect())
+    }
+
+    /// 记住的拒绝 HEAD 的主机
+    pub async fn get_method_cache(&self) -> Result<Vec<MethodCacheEntry>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT host, CAST(learned_at AS VARCHAR), runs_since_probe FROM head_method_cache ORDER BY host"
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(MethodCacheEntry {
+                host: row.get(0)?,
+                learned_at: row.get::<_, String>(1).ok().as_deref().and_then(parse_timestamp).unwrap_or_default(),
+                runs_since_probe: row.get(2)?,
+            })
+        })?;
+        Ok(rows.filter_map(Result::ok).collect())
+    }
+
+    /// 按一次运行的结果更新：`rejected` 为 HEAD 被拒绝而 GET 成功的主机（重新计数），
+    /// `accepted` 为 HEAD 成功的主机（删除），`skipped` 为直接用 GET 的主机（运行次数加一）
+    pub async fn update_method_cache(&self, rejected: &[String], accepted: &[String], skipped: &[String]) -> Result<()> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        let now = Utc::now().to_rfc3339();
+        for host in rejected {
+            tx.execute(
+                "INSERT INTO head_method_cache (host, learned_at, runs_since_probe) VALUES (?, CAST(? AS TIMESTAMP), 0)
+                ON CONFLICT (host) DO UPDATE SET runs_since_probe = 0",
+                params![host, now],
+            )?;
+        }
+        for host in accepted {
+            tx.execute("DELETE FROM head_method_cache WHERE host = ?", params![host])?;
+        }
+        for host in skipped {
+            tx.execute("UPDATE head_method_cache SET runs_since_probe = runs_since_probe + 1 WHERE host = ?", params![host])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 只保留最近记住的 `max_entries` 个主机，返回删除的数量
+    pub async fn compact_method_cache(&self, max_entries: usize) -> Result<usize> {
+        let conn = self.conn.lock().await;
+        Ok(conn.execute(
+            "DELETE FROM head_method_cache WHERE host IN (
+                SELECT host FROM head_method_cache ORDER BY learned_at DESC, host OFFSET ?
+            )",
+            params![max_entries as i64],
+        )?)
+    }
+
+    /// 清除记住的主机，`host` 为 None 时全部清除，返回清除的数量
+    pub async fn clear_method_cache(&self, host: Option<&str>) -> Result<usize> {
+        let conn = self.conn.lock().await;
+        Ok(conn.execute("DELETE FROM head_method_cache WHERE CAST(? AS VARCHAR) IS NULL OR host = ?", params![host, host])?)
+    }
+
+    pub async fn get_learned_slow_hosts(&self) -> Result<Vec<LearnedSlowHost>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT host, timeout_secs, CAST(learned_at AS VARCHAR), total_checks, slow_checks FROM slow_hosts ORDER BY host"
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(LearnedSlowHost {
+                host: row.get(0)?,
+                timeout_secs: row.get::<_, i64>(1)? as u64,
+                learned_at: row.get::<_, String>(2).ok().as_deref().and_then(parse_timestamp).unwrap_or_default(),
+                total_checks: row.get(3)?,
+                slow_checks: row.get(4)?,
+            })
+        })?;
+        Ok(rows.filter_map(Result::ok).collect())
+    }
+
+    /// 按 [`SlowHostLearning`] 的规则从 `since` 之后成功的检查中识别慢主机，替换 slow_hosts 表，返回识别的主机
+    pub async fn learn_slow_hosts(&self, since: DateTime<Utc>, threshold_ms: u64, learning: &SlowHostLearning) -> Result<Vec<LearnedSlowHost>> {
+        let now = Utc::now();
+        let conn = self.conn.lock().await;
+        let tx = conn.unchecked_transaction()?;
+        let learned = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT host, COUNT(*) AS total_checks, COUNT(*) FILTER (WHERE response_time_ms >= ?) AS slow_checks
+                FROM dataset_monitor
+                WHERE check_time >= CAST(? AS TIMESTAMP) AND host IS NOT NULL AND response_time_ms IS NOT NULL AND {}
+                GROUP BY host
+                HAVING total_checks >= ? AND slow_checks * 1.0 / total_checks >= ?
+                ORDER BY slow_checks * 1.0 / total_checks DESC, slow_checks DESC, host
+                LIMIT ?",
+                self.success.sql("status_code")
+            ))?;
+            let rows = stmt.query_map(
+                params![threshold_ms as i64, since.to_rfc3339(), learning.min_checks, learning.min_share, learning.max_hosts as i64],
+                |row| Ok(LearnedSlowHost {
+                    host: row.get(0)?,
+                    timeout_secs: learning.timeout_secs,
+                    learned_at: now,
+                    total_checks: row.get(1)?,
+                    slow_checks: row.get(2)?,
+                }),
+            )?;
+            rows.collect::<duckdb::Result<Vec<_>>>()?
         };
+        tx.execute("DELETE FROM slow_hosts", [])?;
+        for host in &learned {
+            tx.execute(
+                "INSERT INTO slow_hosts (host, timeout_secs, learned_at, total_checks, slow_checks) VALUES (?, ?, CAST(? AS TIMESTAMP), ?, ?)",
+                params![host.host, host.timeout_secs as i64, now.to_rfc3339(), host.total_checks, host.slow_checks],
+            )?;
+        }
+        tx.commit()?;
+        Ok(learned)
+    }
 
-        let query = format!(
-            "WITH url_stats AS (
-                SELECT
-                    h.url,
-                    h.center_name,
-                    m.name,
-                    COUNT(*) as total_checks,
-                    SUM(CASE WHEN h.status_code != 200 OR h.status_code IS NULL THEN 1 ELSE 0 END) as failed_checks,
-                    AVG(h.response_time_ms) as avg_response_time,
-                    MAX(h.check_time) as last_check,
-                    m.error_msg as last_error
-                FROM dataset_monitor_history h
-                JOIN dataset_monitor m ON h.id = m.id
-                {}
-                GROUP BY h.url, h.center_name, m.name, m.error_msg
-                HAVING (failed_checks * 100.0 / total_checks) >= {}
-            )
-            SELECT * FROM url_stats
-            ORDER BY (failed_checks * 100.0 / total_checks) DESC
-            LIMIT 100",
-            where_clause, min_failure_rate
-        );
-
-        let mut stmt = conn.prepare(&query)?;
-        let results = stmt.query_map([], |row| {
-            let total_checks: i32 = row.get(3)?;
-            let failed_checks: i32 = row.get(4)?;
-            let failure_rate = if total_checks > 0 {
-                (failed_checks as f64 / total_checks as f64) * 100.0
-            } else {
-                0.0
-            };
-
-            Ok(ProblematicUrl {
-                url: row.get(0)?,
-                center_name: row.get(1)?,
-                name: row.get(2)?,
-                total_checks,
-                failed_checks,
-                failure_rate,
-                avg_response_time_ms: row.get(5)?,
-                last_check: row.get(6)?,
-                last_error: row.get(7)?,
+    /// 按 rowid 顺序读取 `after_row_id` 之后最多 `limit` 条要重新分类的记录。
+    /// 导入的记录（source 不为空，溢出文件回放的除外）的分类来自其他工具，不读取
+    pub async fn get_classification_batch(&self, filter: &QueryFilter, after_row_id: i64, limit: usize) -> Result<Vec<StoredClassification>> {
+        let (where_sql, mut values) = filter.sql();
+        values.push(Value::BigInt(after_row_id));
+        values.push(Value::BigInt(limit as i64));
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT rowid, id, url, CAST(check_time AS VARCHAR), status_code, error_category, error_detail,
+                COALESCE(auth_used, FALSE), http_version, COALESCE(is_likely_local_issue, FALSE)
+            FROM dataset_monitor
+            WHERE {} AND (source IS NULL OR source = 'spill') AND rowid > ?
+            ORDER BY rowid
+            LIMIT ?",
+            where_sql
+        ))?;
+        let rows = stmt.query_map(params_from_iter(&values), |row| {
+            Ok(StoredClassification {
+                row_id: row.get(0)?,
+                id: row.get(1)?,
+                url: row.get(2)?,
+                check_time: row.get(3)?,
+                status_code: row.get(4)?,
+                error_category: row.get(5)?,
+                error_detail: row.get(6)?,
+                auth_used: row.get(7)?,
+                http_version: row.get(8)?,
+                is_likely_local_issue: row.get(9)?,
             })
         })?;
+        Ok(rows.collect::<duckdb::Result<Vec<_>>>()?)
+    }
+
+    /// 按 rowid 顺序读取 `after_row_id` 之后最多 `limit` 条错误信息、错误详情或响应头超出 `limits`，
+    /// 或响应头中含有凭证的记录
+    pub async fn get_oversized_text_batch(&self, limits: &TextLimits, after_row_id: i64, limit: usize) -> Result<Vec<StoredText>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT rowid, error_msg, error_detail, headers
+            FROM dataset_monitor
+            WHERE rowid > ?
+                AND (strlen(error_msg) > ? OR strlen(error_detail) > ? OR strlen(headers) > ?
+                    OR regexp_matches(headers, ?))
+            ORDER BY rowid
+            LIMIT ?",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                after_row_id,
+                limits.error_msg as i64,
+                limits.error_detail as i64,
+                limits.headers as i64,
+                SENSITIVE_HEADER_PATTERN,
+                limit as i64
+            ],
+            |row| Ok(StoredText {
+                row_id: row.get(0)?,
+                error_msg: row.get(1)?,
+                error_detail: row.get(2)?,
+                headers: row.get(3)?,
+            }),
+        )?;
+        Ok(rows.collect::<duckdb::Result<Vec<_>>>()?)
+    }
+
+    /// 在一个事务中写回压缩后的文本列，返回更新的行数
+    pub async fn update_texts(&self, texts: &[StoredText]) -> Result<usize> {
+        if texts.is_empty() {
+            return Ok(0);
+        }
+        let conn = self.conn.lock().await;
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "CREATE TEMPORARY TABLE compact_batch (row_id BIGINT, error_msg TEXT, error_detail TEXT, headers TEXT)",
+            [],
+        )?;
+        {
+            let mut appender = tx.appender("compact_batch")?;
+            for text in texts {
+                appender.append_row(params![text.row_id, &text.error_msg, &text.error_detail, &text.headers])?;
+            }
+            appender.flush()?;
+        }
+        let updated = tx.execute(
+            "UPDATE dataset_monitor AS m
+            SET error_msg = t.error_msg, error_detail = t.error_detail, headers = t.headers
+            FROM compact_batch AS t
+            WHERE m.rowid = t.row_id",
+            [],
+        )?;
+        tx.execute("DROP TABLE compact_batch", [])?;
+        tx.commit()?;
+        Ok(updated)
+    }
+
+    /// 把 WAL 写回数据库文件
+    pub async fn checkpoint(&self) -> Result<()> {
+        self.conn.lock().await.execute_batch("CHECKPOINT")?;
+        Ok(())
+    }
+
+    /// 在一个事务中写回重新分类的结果，返回更新的行数
+    pub async fn update_classifications(&self, changes: &[Reclassification]) -> Result<usize> {
+        if changes.is_empty() {
+            return Ok(0);
+        }
+        let conn = self.conn.lock().await;
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "CREATE TEMPORARY TABLE reclassify_batch (row_id BIGINT, error_category VARCHAR, is_likely_local_issue BOOLEAN)",
+            [],
+        )?;
+        {
+            let mut appender = tx.appender("reclassify_batch")?;
+            for change in changes {
+                appender.append_row(params![change.row_id, &change.error_category, change.is_likely_local_issue])?;
+            }
+            appender.flush()?;
+        }
+        let updated = tx.execute(
+            "UPDATE dataset_monitor AS m
+            SET error_category = t.error_category,
+                is_likely_local_issue = t.is_likely_local_issue,
+                updated_at = CAST(? AS TIMESTAMP)
+            FROM reclassify_batch AS t
+            WHERE m.rowid = t.row_id",
+            params![Utc::now().to_rfc3339()],
+        )?;
+        tx.execute("DROP TABLE reclassify_batch", [])?;
+        tx.commit()?;
+        Ok(updated)
+    }
+}
+
+const SELECT_ALERT_STATE: &str = "SELECT rule, subject, is_open, CAST(last_fired_at AS VARCHAR), last_value, CAST(updated_at AS VARCHAR)
+    FROM alert_state";
+
+fn alert_state_from_row(row: &duckdb::Row) -> duckdb::Result<AlertState> {
+    Ok(AlertState {
+        rule: row.get(0)?,
+        subject: row.get(1)?,
+        open: row.get(2)?,
+        last_fired_at: row.get::<_, Option<String>>(3)?.as_deref().and_then(parse_timestamp),
+        last_value: row.get(4)?,
+        updated_at: row.get::<_, String>(5).ok().as_deref().and_then(parse_timestamp).unwrap_or_default(),
+    })
+}
+
+/// 解析 DuckDB TIMESTAMP 转换出的字符串（UTC）
+/// 按 dataset_monitor 的列顺序追加一行，新增的列要同时加在这里
+/// `now` 为写入时间，记录没有 created_at/updated_at 时使用（UTC）
+fn append_record(appender: &mut duckdb::Appender<'_>, record: &MonitorRecord, source: Option<&str>, now: DateTime<Utc>) -> duckdb::Result<()> {
+    appender.append_row(params![
+        &record.id,
+        &record.raw_id,
+        &record.url,
+        &record.name,
+        &record.center_name,
+        &record.date_published,
+        &record.check_time.to_rfc3339(),
+        &record.status_code,
+        &record.status_text,
+        &record.error_category,
+        &record.error_msg,
+        &record.error_detail,
+        &record.response_time_ms.map(|t| t as i64),
+        &record.is_likely_local_issue,
+        &record.headers,
+        &record.created_at.unwrap_or(now).to_rfc3339(),
+        &record.updated_at.unwrap_or(now).to_rfc3339(),
+        &record.dns_ms.map(|t| t as i64),
+        &record.connect_ms.map(|t| t as i64),
+        &record.ttfb_ms.map(|t| t as i64),
+        &record.http_version,
+        &record.connection_reused,
+        &source,
+        &record.content_hash,
+        &record.content_changed,
+        &record.auth_used,
+        &record.requested_url,
+        &attempts_json(record),
+        &record.total_time_ms.map(|t| t as i64),
+        &host_of(&record.url),
+        &record.failed_hop_index,
+        &record.failed_hop_url,
+        &record.timeout_secs.map(|t| t as i64),
+        &tags_column(&record.tags),
+        &record.redirect_location,
+        &record.run_id,
+        &record.attributed_center,
+        &record.attribution_rule
+    ])
+}
+
+/// 文件的大小，不存在时为 None，其他错误（如没有权限）记录日志后为 None
+fn file_size(path: &str) -> Option<u64> {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => Some(metadata.len()),
+        Ok(_) => None,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            warn!("读取 {} 的大小失败: {}", path, e);
+            None
+        }
+    }
+}
+
+/// 标签写入 tags 列的格式，没有标签时为 NULL
+fn tags_column(tags: &[String]) -> Option<String> {
+    (!tags.is_empty()).then(|| tags.join(","))
+}
+
+/// 没有尝试记录（尚未检查）时为 NULL
+fn attempts_json(record: &MonitorRecord) -> Option<String> {
+    if record.attempts_detail.is_empty() {
+        return None;
+    }
+    serde_json::to_string(&record.attempts_detail).ok()
+}
+
+/// 与 [`read_record`] 对应的查询列
+const RECORD_COLUMNS: &str = "id, raw_id, url, name, center_name, date_published, CAST(check_time AS VARCHAR),
+    status_code, status_text, error_category, error_msg, error_detail,
+    response_time_ms, is_likely_local_issue, headers, dns_ms, connect_ms, ttfb_ms,
+    http_version, connection_reused, content_hash, content_changed, auth_used, requested_url,
+    attempts_detail, total_time_ms, CAST(created_at AS VARCHAR), CAST(updated_at AS VARCHAR),
+    failed_hop_index, failed_hop_url, timeout_secs, tags, redirect_location, run_id,
+    attributed_center, attribution_rule";
+
+fn read_record(row: &duckdb::Row<'_>) -> duckdb::Result<MonitorRecord> {
+    Ok(MonitorRecord {
+        id: row.get(0)?,
+        raw_id: row.get(1)?,
+        url: row.get(2)?,
+        name: row.get(3)?,
+        center_name: row.get(4)?,
+        date_published: row.get(5)?,
+        check_time: parse_timestamp(&row.get::<_, String>(6)?).unwrap_or_default(),
+        status_code: row.get(7)?,
+        status_text: row.get(8)?,
+        error_category: row.get(9)?,
+        error_msg: row.get(10)?,
+        error_detail: row.get(11)?,
+        response_time_ms: row.get::<_, Option<i64>>(12)?.map(|t| t as u64),
+        is_likely_local_issue: row.get(13)?,
+        headers: row.get(14)?,
+        dns_ms: row.get::<_, Option<i64>>(15)?.map(|t| t as u64),
+        connect_ms: row.get::<_, Option<i64>>(16)?.map(|t| t as u64),
+        ttfb_ms: row.get::<_, Option<i64>>(17)?.map(|t| t as u64),
+        http_version: row.get(18)?,
+        connection_reused: row.get(19)?,
+        content_hash: row.get(20)?,
+        content_changed: row.get(21)?,
+        auth_used: row.get::<_, Option<bool>>(22)?.unwrap_or_default(),
+        requested_url: row.get(23)?,
+        attempts_detail: row.get::<_, Option<String>>(24)?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+        total_time_ms: row.get::<_, Option<i64>>(25)?.map(|t| t as u64),
+        created_at: row.get::<_, Option<String>>(26)?.as_deref().and_then(parse_timestamp),
+        updated_at: row.get::<_, Option<String>>(27)?.as_deref().and_then(parse_timestamp),
+        failed_hop_index: row.get(28)?,
+        failed_hop_url: row.get(29)?,
+        timeout_secs: row.get::<_, Option<i64>>(30)?.map(|t| t as u64),
+        tags: row.get::<_, Option<String>>(31)?
+            .map(|tags| tags.split(',').map(String::from).collect())
+            .unwrap_or_default(),
+        redirect_location: row.get(32)?,
+        run_id: row.get(33)?,
+        attributed_center: row.get(34)?,
+        attribution_rule: row.get(35)?,
+    })
+}
+
+/// 用 `source`（列为 url、check_time、status_code 的查询）中的检查更新 url_status：
+/// first_seen_at 取最早的检查时间，last_success_at 取最晚的成功检查时间。返回更新的URL数
+fn upsert_url_status(conn: &duckdb::Connection, success: &SuccessStatuses, source: &str) -> duckdb::Result<usize> {
+    conn.execute(&format!(
+        "INSERT INTO url_status (url, first_seen_at, last_success_at)
+        SELECT url, MIN(check_time), MAX(check_time) FILTER (WHERE {})
+        FROM ({}) AS checks
+        GROUP BY url
+        ON CONFLICT (url) DO UPDATE SET
+            first_seen_at = LEAST(url_status.first_seen_at, excluded.first_seen_at),
+            last_success_at = COALESCE(GREATEST(url_status.last_success_at, excluded.last_success_at),
+                                       url_status.last_success_at, excluded.last_success_at)",
+        success.sql("status_code"), source
+    ), [])
+}
+
+fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::filter::StatusClass;
+    use crate::models::RunStorage;
+    use crate::test_support::record;
+
+    async fn status_db() -> DuckDB {
+        let db = DuckDB::new(":memory:").await.unwrap();
+        db.insert_records(&[
+            record("1", "A", Some(200), None),
+            record("2", "A", Some(204), None),
+            record("3", "A", Some(301), None),
+            record("4", "B", Some(404), Some("HTTP_4XX")),
+            record("5", "B", Some(500), Some("HTTP_5XX")),
+            record("6", "B", Some(503), Some("HTTP_5XX")),
+            record("7", "B", None, Some("TIMEOUT")),
+            // 尚未完成检查的记录不计入
+            record("8", "B", None, None),
+        ]).await.unwrap();
+        db
+    }
+
+    /// 符合过滤条件的检查数
+    async fn count(db: &DuckDB, filter: QueryFilter) -> i64 {
+        db.get_status_code_stats(&filter, StatusGrouping::Class).await.unwrap()
+            .iter().map(|s| s.count).sum()
+    }
+
+    #[tokio::test]
+    async fn query_filter_matches_records() {
+        let at = |s: &str| -> DateTime<Utc> { s.parse().unwrap() };
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let records = [
+            MonitorRecord { tags: vec!["core".to_string(), "geo".to_string()], ..record("1", "A", Some(200), None) },
+            MonitorRecord {
+                check_time: at("2026-01-15T00:00:00Z"),
+                is_likely_local_issue: true,
+                ..record("2", "A", None, Some("TIMEOUT"))
+            },
+            // until 不包含在范围内
+            MonitorRecord { check_time: at("2026-02-01T00:00:00Z"), ..record("3", "B", Some(404), Some("HTTP_4XX")) },
+            // 尚未发布的运行
+            MonitorRecord {
+                check_time: at("2026-01-20T00:00:00Z"),
+                run_id: Some("r-open".to_string()),
+                ..record("4", "B", Some(500), Some("HTTP_5XX"))
+            },
+            MonitorRecord {
+                check_time: at("2026-01-20T00:00:00Z"),
+                run_id: Some("r-done".to_string()),
+                ..record("5", "B", Some(503), Some("HTTP_5XX"))
+            },
+            // 共用URL，统计归属 B
+            MonitorRecord {
+                check_time: at("2026-01-20T00:00:00Z"),
+                attributed_center: Some("B".to_string()),
+                ..record("6", "A", Some(200), None)
+            },
+            record("7", "A", None, None),
+        ];
+        db.insert_records(&records).await.unwrap();
+        db.finish_run_state("r-done", "finished").await.unwrap();
+
+        let all = QueryFilter::default;
+        assert_eq!(count(&db, all()).await, 4);
+        assert_eq!(count(&db, all().include_in_progress()).await, 5);
+        assert_eq!(count(&db, all().ignore_attribution()).await, 5);
+        assert_eq!(count(&db, all().include_in_progress().ignore_attribution()).await, 6);
+
+        let range = || QueryFilter::range(at("2026-01-01T00:00:00Z"), at("2026-02-01T00:00:00Z"));
+        assert_eq!(count(&db, range()).await, 3);
+        assert_eq!(count(&db, range().center("B")).await, 1);
+        assert_eq!(count(&db, all().center("A")).await, 2);
+        assert_eq!(count(&db, all().center("A").ignore_attribution()).await, 3);
+        assert_eq!(count(&db, all().status_class(StatusClass::Class(5))).await, 1);
+        assert_eq!(count(&db, all().status_class(StatusClass::Class(5)).include_in_progress()).await, 2);
+        assert_eq!(count(&db, all().status_class(StatusClass::NoResponse)).await, 1);
+        assert_eq!(count(&db, all().error_category("TIMEOUT")).await, 1);
+        assert_eq!(count(&db, all().local_issue(true)).await, 1);
+        assert_eq!(count(&db, all().local_issue(false)).await, 3);
+        assert_eq!(count(&db, all().tag("geo")).await, 1);
+        // 标签按完整名称匹配
+        assert_eq!(count(&db, all().tag("cor")).await, 0);
+        assert_eq!(count(&db, range().center("A").local_issue(true).error_category("TIMEOUT")).await, 1);
+        assert_eq!(count(&db, range().center("A").local_issue(false).error_category("TIMEOUT")).await, 0);
+    }
+
+    fn counts(stats: &[StatusCodeStats]) -> Vec<(&str, i64)> {
+        stats.iter().map(|s| (s.label.as_str(), s.count)).collect()
+    }
+
+    #[tokio::test]
+    async fn status_codes_grouped_by_class() {
+        let db = status_db().await;
+        let stats = db.get_status_code_stats(&QueryFilter::default(), StatusGrouping::Class).await.unwrap();
+        assert_eq!(counts(&stats), [("2xx", 2), ("3xx", 1), ("4xx", 1), ("5xx", 2), ("no_response", 1)]);
+        let total: f64 = stats.iter().map(|s| s.percentage).sum();
+        assert!((total - 100.0).abs() < 1e-9);
+        assert!((stats[0].percentage - 200.0 / 7.0).abs() < 1e-9);
+
+        let stats = db.get_status_code_stats(&QueryFilter::default().center("A"), StatusGrouping::Class).await.unwrap();
+        assert_eq!(counts(&stats), [("2xx", 2), ("3xx", 1)]);
+    }
+
+    #[tokio::test]
+    async fn status_codes_grouped_by_code() {
+        let db = status_db().await;
+        let stats = db.get_status_code_stats(&QueryFilter::default(), StatusGrouping::Code).await.unwrap();
+        assert_eq!(counts(&stats), [
+            ("200", 1), ("204", 1), ("301", 1), ("404", 1), ("500", 1), ("503", 1), ("no_response", 1),
+        ]);
+        let total: f64 = stats.iter().map(|s| s.percentage).sum();
+        assert!((total - 100.0).abs() < 1e-9);
+
+        let empty = DuckDB::new(":memory:").await.unwrap();
+        assert!(empty.get_status_code_stats(&QueryFilter::default(), StatusGrouping::Code).await.unwrap().is_empty());
+    }
+
+    fn run_summary(run_id: &str, finished_at: &str) -> MonitorSummary {
+        serde_json::from_value(serde_json::json!({
+            "run_id": run_id,
+            "started_at": "2026-01-01T00:00:00Z",
+            "finished_at": finished_at,
+            "total": 2,
+            "success": 1,
+            "local_issues": 0,
+            "remote_issues": 1,
+            "centers": [],
+        })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn run_config_snapshots_are_stored_with_their_hash() {
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let mut summary = run_summary("r1", "2026-01-02T01:00:00Z");
+        summary.config_hash = Some("abc123".to_string());
+        db.insert_run(&summary, r#"{"version":"1.0.0","monitor":{"http_timeout_secs":12},"centers":[]}"#).await.unwrap();
+
+        let run = db.get_run("r1").await.unwrap().unwrap();
+        assert_eq!(run.config_hash.as_deref(), Some("abc123"));
+        assert_eq!(run.config.unwrap(), serde_json::json!({ "version": "1.0.0", "monitor": { "http_timeout_secs": 12 }, "centers": [] }));
+        assert!(db.get_run("r2").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn data_as_of_follows_writes() {
+        let at = |s: &str| -> DateTime<Utc> { s.parse().unwrap() };
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let empty = db.get_data_as_of().await.unwrap();
+        assert!(empty.latest_check_time.is_none() && empty.centers.is_empty() && empty.latest_run.is_none());
+
+        db.insert_records(&[record("1", "A", Some(200), None)]).await.unwrap();
+        let first = db.get_data_as_of().await.unwrap();
+        assert_eq!(first.latest_check_time, Some(at("2026-01-01T00:00:00Z")));
+        // 两次写入之间结果不变
+        let again = db.get_data_as_of().await.unwrap();
+        assert_eq!(serde_json::to_value(&first).unwrap(), serde_json::to_value(&again).unwrap());
+
+        db.insert_records(&[
+            MonitorRecord { check_time: at("2026-01-02T00:00:00Z"), ..record("2", "B", None, Some("TIMEOUT")) },
+            // 更早的检查和未完成的检查不影响时效
+            MonitorRecord { check_time: at("2025-12-01T00:00:00Z"), ..record("3", "A", Some(200), None) },
+            MonitorRecord { check_time: at("2026-01-03T00:00:00Z"), ..record("4", "A", None, None) },
+        ]).await.unwrap();
+        db.insert_run(&run_summary("r1", "2026-01-02T01:00:00Z"), "{}").await.unwrap();
+        let second = db.get_data_as_of().await.unwrap();
+        assert_eq!(second.latest_check_time, Some(at("2026-01-02T00:00:00Z")));
+        assert_eq!(second.centers.get("A"), Some(&at("2026-01-01T00:00:00Z")));
+        assert_eq!(second.centers.get("B"), Some(&at("2026-01-02T00:00:00Z")));
+        let run = second.latest_run.as_ref().unwrap();
+        assert_eq!((run.run_id.as_str(), run.total, run.success, run.status.as_deref()), ("r1", 2, 1, None));
+
+        db.insert_run(&run_summary("r2", "2026-01-03T01:00:00Z"), "{}").await.unwrap();
+        db.start_run_state("r2", at("2026-01-03T00:00:00Z"), &[]).await.unwrap();
+        db.finish_run_state("r2", "finished").await.unwrap();
+        let third = db.get_data_as_of().await.unwrap();
+        let run = third.latest_run.as_ref().unwrap();
+        assert_eq!((run.run_id.as_str(), run.status.as_deref()), ("r2", Some("finished")));
+        assert_eq!(third.latest_check_time, second.latest_check_time);
+    }
+
+    #[tokio::test]
+    async fn availability_follows_success_statuses() {
+        let availability = |statuses: Option<SuccessStatuses>| async move {
+            let mut db = DuckDB::new(":memory:").await.unwrap();
+            if let Some(statuses) = statuses {
+                db = db.with_success_statuses(&statuses);
+            }
+            db.insert_records(&[record("1", "C", Some(204), None), record("2", "C", Some(204), None)]).await.unwrap();
+            db.get_center_availability(&QueryFilter::default()).await.unwrap()[0].availability
+        };
+        let statuses = |yaml: &str| Some(serde_yaml::from_str::<SuccessStatuses>(yaml).unwrap());
+        assert_eq!(availability(None).await, 100.0);
+        assert_eq!(availability(statuses("[204]")).await, 100.0);
+        assert_eq!(availability(statuses("[200]")).await, 0.0);
+    }
+
+    #[tokio::test]
+    async fn problematic_urls_report_latest_error_and_recent_failures() {
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let check = |id: &str, time: &str, status_code: u16, error_msg: Option<&str>| MonitorRecord {
+            url: "https://a.example.org/x".to_string(),
+            check_time: time.parse().unwrap(),
+            error_msg: error_msg.map(str::to_string),
+            ..record(id, "A", Some(status_code), error_msg.map(|_| "HTTP_5XX"))
+        };
+        db.insert_records(&[
+            check("1", "2026-01-01T00:00:00Z", 200, None),
+            // 按文本取 MAX 会得到最早的这条错误
+            check("2", "2026-01-02T00:00:00Z", 500, Some("z: 最早的错误")),
+            check("3", "2026-01-03T00:00:00Z", 502, Some("b: 较早的错误")),
+            check("4", "2026-01-04T00:00:00Z", 503, Some("a: 最近的错误")),
+            record("5", "A", Some(200), None),
+        ]).await.unwrap();
+
+        let urls = db.get_problematic_urls(&QueryFilter::default(), 50.0, 2).await.unwrap();
+        assert_eq!(urls.len(), 1);
+        let url = &urls[0];
+        assert_eq!((url.total_checks, url.failed_checks, url.failure_rate), (4, 3, 75.0));
+        assert_eq!(url.last_error.as_deref(), Some("a: 最近的错误"));
+        assert!(url.last_check.starts_with("2026-01-04"), "{}", url.last_check);
+        let recent: Vec<_> = url.recent_failures.iter().map(|f| (f.status_code, f.error_msg.as_deref())).collect();
+        assert_eq!(recent, [(Some(503), Some("a: 最近的错误")), (Some(502), Some("b: 较早的错误"))]);
+
+        assert!(db.get_problematic_urls(&QueryFilter::default(), 80.0, 2).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn status_updates_keep_earlier_checks_as_history() {
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let at = |s: &str| -> DateTime<Utc> { s.parse().unwrap() };
+        // 每次运行先插入未检查的记录，检查完再更新状态
+        for (time, status_code) in [("2026-01-01T00:10:00Z", 500), ("2026-01-01T01:10:00Z", 200)] {
+            let pending = MonitorRecord { check_time: at(time), ..record("1", "A", None, None) };
+            db.insert_records(std::slice::from_ref(&pending)).await.unwrap();
+            let checked = MonitorRecord { status_code: Some(status_code), response_time_ms: Some(100), ..pending };
+            db.update_status(&[checked]).await.unwrap();
+        }
+        let hours = db.get_hourly_stats(&QueryFilter::range(at("2026-01-01T00:00:00Z"), at("2026-01-02T00:00:00Z"))).await.unwrap();
+        let hours: Vec<_> = hours.iter().map(|h| (h.hour.as_str(), h.total_checks, h.availability)).collect();
+        assert_eq!(hours, [("2026-01-01T00:00:00Z", 1, 0.0), ("2026-01-01T01:00:00Z", 1, 100.0)]);
+    }
+
+    #[tokio::test]
+    async fn aggregations_on_a_new_database_are_empty() {
+        let path = std::env::temp_dir().join(format!("dataset-monitor-empty-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = DuckDB::new(path.to_str().unwrap()).await.unwrap();
+        let filter = QueryFilter::range("2026-01-01T00:00:00Z".parse().unwrap(), "2026-02-01T00:00:00Z".parse().unwrap());
+        assert!(db.get_hourly_stats(&filter).await.unwrap().is_empty());
+        assert!(db.get_daily_availability(&filter).await.unwrap().is_empty());
+        assert!(db.get_center_availability(&filter).await.unwrap().is_empty());
+        assert!(db.get_status_code_stats(&filter, StatusGrouping::Class).await.unwrap().is_empty());
+        assert!(db.get_error_category_counts(&filter).await.unwrap().is_empty());
+        assert!(db.get_problematic_urls(&filter, 0.0, 3).await.unwrap().is_empty());
+        assert!(db.get_top_failing_urls(&filter, 10).await.unwrap().is_empty());
+        assert!(db.get_host_stats(&filter, HostSort::Failures, 10).await.unwrap().is_empty());
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("db.wal"));
+    }
+
+    /// 在 `db` 上执行超时的慢查询，确认被中断且之后连接仍可使用
+    async fn assert_slow_query_interrupted(db: DuckDB) {
+        let started = std::time::Instant::now();
+        let err = db.read(QueryGroup::Trends, |conn| {
+            Ok(conn.query_row("SELECT SUM(a.range * b.range) FROM range(100000000) a, range(100000000) b", [], |row| row.get::<_, i64>(0))?)
+        }).await.unwrap_err();
+        assert!(err.downcast_ref::<QueryTimeout>().is_some(), "{:#}", err);
+        assert!(started.elapsed() < Duration::from_secs(10), "{:?}", started.elapsed());
+
+        // 中断后连接仍可使用
+        db.insert_records(&[record("1", "A", Some(200), None)]).await.unwrap();
+        assert_eq!(count(&db, QueryFilter::default()).await, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn slow_queries_are_interrupted() {
+        let db = DuckDB::new(":memory:").await.unwrap().with_query_timeouts(|_| Some(Duration::from_millis(200)));
+        assert_slow_query_interrupted(db).await;
+    }
+
+    #[tokio::test]
+    async fn slow_queries_are_interrupted_on_a_current_thread_runtime() {
+        // 只有趋势组设置了超时
+        let db = DuckDB::new(":memory:").await.unwrap()
+            .with_query_timeouts(|group| (group == QueryGroup::Trends).then_some(Duration::from_millis(200)));
+        assert_slow_query_interrupted(db).await;
+    }
+
+    #[test]
+    fn query_timeouts_fall_back_to_the_default() {
+        let config: crate::config::DuckDBConfig = serde_yaml::from_str(
+            "{ path: ':memory:', query_timeout_secs: 60, query_timeouts: { trends: 300, reports: 0 } }",
+        ).unwrap();
+        let timeouts: Vec<_> = QueryGroup::ALL.iter().map(|g| config.query_timeout(*g).map(|t| t.as_secs())).collect();
+        assert_eq!(timeouts, [Some(60), Some(300), Some(60), None]);
+        let config: crate::config::DuckDBConfig = serde_yaml::from_str("{ path: ':memory:' }").unwrap();
+        assert_eq!(config.query_timeout(QueryGroup::History), Some(Duration::from_secs(120)));
+    }
+
+    #[tokio::test]
+    async fn write_timestamps_round_trip_in_utc() {
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let check_time: DateTime<Utc> = "2026-01-01T08:30:00Z".parse().unwrap();
+        let before = Utc::now() - chrono::Duration::seconds(1);
+        let pending = MonitorRecord { check_time, ..record("1", "A", None, None) };
+        db.insert_records(std::slice::from_ref(&pending)).await.unwrap();
+        db.update_status(&[MonitorRecord { status_code: Some(200), ..pending }]).await.unwrap();
+        let after = Utc::now() + chrono::Duration::seconds(1);
+
+        let stored = db.get_latest_record("A", "1", check_time).await.unwrap().unwrap();
+        assert_eq!(stored.check_time, check_time);
+        for written in [stored.created_at.unwrap(), stored.updated_at.unwrap()] {
+            assert!(before <= written && written <= after, "{} 不在 {} 和 {} 之间", written, before, after);
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_write_timestamps_are_backfilled_on_open() {
+        let path = std::env::temp_dir().join(format!("dataset-monitor-backfill-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let path_str = path.to_str().unwrap();
+        {
+            let db = DuckDB::new(path_str).await.unwrap();
+            db.insert_records(&[record("1", "A", Some(200), None)]).await.unwrap();
+            // 旧版本写入的记录没有 created_at/updated_at
+            db.conn.lock().await.execute("UPDATE dataset_monitor SET created_at = NULL, updated_at = NULL", []).unwrap();
+        }
+        let db = DuckDB::new(path_str).await.unwrap();
+        let stored = db.get_latest_record("A", "1", "2026-01-01T00:00:00Z".parse().unwrap()).await.unwrap().unwrap();
+        assert_eq!(stored.created_at, Some(stored.check_time));
+        assert_eq!(stored.updated_at, Some(stored.check_time));
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("db.wal"));
+    }
+
+    #[tokio::test]
+    async fn host_stats_group_latest_statuses_by_host() {
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let check = |id: &str, center: &str, url: &str, status_code: u16, time: &str| MonitorRecord {
+            url: url.to_string(),
+            check_time: time.parse().unwrap(),
+            ..record(id, center, Some(status_code), None)
+        };
+        db.insert_records(&[
+            check("1", "A", "https://shared.casdc.cn/1", 200, "2026-01-01T00:00:00Z"),
+            check("2", "B", "https://shared.casdc.cn/2", 503, "2026-01-01T00:00:00Z"),
+            check("2", "B", "https://shared.casdc.cn/2", 200, "2026-01-02T00:00:00Z"),
+            // 主机名不区分大小写，不含端口
+            check("3", "A", "HTTPS://Shared.CASDC.cn:8443/3", 500, "2026-01-02T00:00:00Z"),
+            check("4", "A", "http://solo.casdc.cn/4", 404, "2026-01-02T00:00:00Z"),
+            check("5", "A", "http://solo.casdc.cn/5", 404, "2026-01-02T00:00:00Z"),
+            check("6", "A", "https://user:pw@solo.casdc.cn/6", 200, "2026-01-03T00:00:00Z"),
+        ]).await.unwrap();
+
+        let stats = db.get_host_stats(&QueryFilter::default(), HostSort::Failures, 10).await.unwrap();
+        let rows: Vec<_> = stats.iter()
+            .map(|h| (h.host.as_str(), h.centers.join(","), h.datasets, h.failing_datasets, h.total_checks, h.failed_checks))
+            .collect();
+        assert_eq!(rows, [
+            ("solo.casdc.cn", "A".to_string(), 3, 2, 3, 2),
+            ("shared.casdc.cn", "A,B".to_string(), 3, 1, 4, 2),
+        ]);
+        assert_eq!(stats[1].failure_rate, 50.0);
+        assert!(stats[0].last_check.starts_with("2026-01-03"), "{}", stats[0].last_check);
+
+        let by_datasets = db.get_host_stats(&QueryFilter::default(), HostSort::Datasets, 1).await.unwrap();
+        assert_eq!(by_datasets.iter().map(|h| h.host.as_str()).collect::<Vec<_>>(), ["shared.casdc.cn"]);
+        let only_b = db.get_host_stats(&QueryFilter::default().center("B"), HostSort::Failures, 10).await.unwrap();
+        assert_eq!((only_b[0].host.as_str(), only_b[0].datasets, only_b[0].total_checks), ("shared.casdc.cn", 1, 2));
+    }
+
+    #[tokio::test]
+    async fn missing_hosts_are_backfilled_on_open() {
+        let path = std::env::temp_dir().join(format!("dataset-monitor-host-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let path_str = path.to_str().unwrap();
+        {
+            let db = DuckDB::new(path_str).await.unwrap();
+            db.insert_records(&[
+                MonitorRecord { url: "HTTPS://u:p@Data.CASDC.cn:8443/d?x=1".to_string(), ..record("1", "A", Some(200), None) },
+                MonitorRecord { url: "https://[2001:db8::1]/d".to_string(), ..record("2", "A", Some(200), None) },
+                MonitorRecord { url: "not a url".to_string(), ..record("3", "A", Some(200), None) },
+            ]).await.unwrap();
+            // 旧版本写入的记录没有 host
+            db.conn.lock().await.execute("UPDATE dataset_monitor SET host = NULL", []).unwrap();
+        }
+        let db = DuckDB::new(path_str).await.unwrap();
+        let mut hosts: Vec<String> = db.get_host_stats(&QueryFilter::default(), HostSort::Datasets, 10).await.unwrap()
+            .into_iter().map(|h| h.host).collect();
+        hosts.sort();
+        assert_eq!(hosts, ["[2001:db8::1]", "data.casdc.cn"]);
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("db.wal"));
+    }
+
+    #[tokio::test]
+    async fn error_trends_fill_missing_buckets_with_zero() {
+        let at = |s: &str| -> DateTime<Utc> { s.parse().unwrap() };
+        let db = DuckDB::new(":memory:").await.unwrap();
+        // SSL 证书错误逐日增加 1、2、3 次，第四天没有错误
+        let mut records = Vec::new();
+        for (day, failures) in [(1, 1), (2, 2), (3, 3)] {
+            for n in 0..failures {
+                records.push(MonitorRecord {
+                    check_time: at(&format!("2026-03-0{}T0{}:00:00Z", day, n)),
+                    ..record(&format!("ssl-{}-{}", day, n), "A", None, Some("SSL_CERTIFICATE"))
+                });
+            }
+        }
+        records.push(MonitorRecord {
+            check_time: at("2026-03-02T12:00:00Z"),
+            is_likely_local_issue: true,
+            ..record("timeout", "B", None, Some("TIMEOUT"))
+        });
+        // 成功的检查和范围外的错误不计入
+        records.push(MonitorRecord { check_time: at("2026-03-02T00:00:00Z"), ..record("ok", "A", Some(200), None) });
+        records.push(MonitorRecord { check_time: at("2026-03-05T00:00:00Z"), ..record("late", "A", None, Some("TIMEOUT")) });
+        db.insert_records(&records).await.unwrap();
+
+        let range = || QueryFilter::range(at("2026-03-01T00:00:00Z"), at("2026-03-05T00:00:00Z"));
+        let series = |trends: &BTreeMap<String, Vec<TrendPoint>>, category: &str| -> Vec<i64> {
+            trends[category].iter().map(|point| point.count).collect()
+        };
 
-        Ok(results.filter_map(Result::ok).collect())
+        let trends = db.get_error_trends(&range(), TrendGranularity::Day).await.unwrap();
+        assert_eq!(trends.keys().collect::<Vec<_>>(), ["SSL_CERTIFICATE", "TIMEOUT"]);
+        assert_eq!(series(&trends, "SSL_CERTIFICATE"), [1, 2, 3, 0]);
+        assert_eq!(series(&trends, "TIMEOUT"), [0, 1, 0, 0]);
+        assert_eq!(trends["TIMEOUT"][1].bucket, "2026-03-02T00:00:00Z");
+
+        let trends = db.get_error_trends(&range().center("A"), TrendGranularity::Day).await.unwrap();
+        assert_eq!(trends.keys().collect::<Vec<_>>(), ["SSL_CERTIFICATE"]);
+        let trends = db.get_error_trends(&range().local_issue(true), TrendGranularity::Day).await.unwrap();
+        assert_eq!(trends.keys().collect::<Vec<_>>(), ["TIMEOUT"]);
+
+        let trends = db.get_error_trends(&range(), TrendGranularity::Hour).await.unwrap();
+        assert_eq!(trends["SSL_CERTIFICATE"].len(), 96);
+        assert_eq!(series(&trends, "SSL_CERTIFICATE").iter().sum::<i64>(), 6);
+
+        assert!(db.get_error_trends(&QueryFilter::default(), TrendGranularity::Day).await.is_err());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn partial_runs_are_hidden_until_published() {
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let in_run = |id: &str, run: &str, status_code: Option<u16>| MonitorRecord {
+            run_id: Some(run.to_string()),
+            ..record(id, "A", status_code, status_code.filter(|code| *code >= 400).map(|_| "HTTP_ERROR"))
+        };
+        let errors = || QueryFilter::default().status_class(StatusClass::Class(5));
+        db.start_run_state("r1", Utc::now(), &[]).await.unwrap();
+        db.insert_records(&[in_run("1", "r1", None), in_run("2", "r1", None)]).await.unwrap();
+        db.update_status(&[in_run("1", "r1", Some(200)), in_run("2", "r1", Some(200))]).await.unwrap();
+        assert_eq!(count(&db, QueryFilter::default()).await, 0);
+        db.finish_run_state("r1", "finished").await.unwrap();
+        assert_eq!(count(&db, QueryFilter::default()).await, 2);
+
+        // 进行中的运行写入的结果在发布前不计入
+        db.start_run_state("r2", Utc::now(), &[]).await.unwrap();
+        db.insert_records(&[in_run("1", "r2", None)]).await.unwrap();
+        db.update_status(&[in_run("1", "r2", Some(500))]).await.unwrap();
+        assert_eq!((count(&db, QueryFilter::default()).await, count(&db, errors()).await), (2, 0));
+        assert_eq!(count(&db, errors().include_in_progress()).await, 1);
+
+        // 没有插入新行的记录更新的是上一次运行的行，该行改归本次运行，同样在发布前不计入
+        db.update_status(&[in_run("2", "r2", Some(503))]).await.unwrap();
+        assert_eq!((count(&db, QueryFilter::default()).await, count(&db, errors()).await), (1, 0));
+
+        db.finish_run_state("r2", "finished").await.unwrap();
+        assert_eq!((count(&db, QueryFilter::default()).await, count(&db, errors()).await), (3, 2));
+    }
+
+    #[tokio::test]
+    async fn center_daily_counts_feed_gap_filled_trends() {
+        let at = |s: &str| -> DateTime<Utc> { s.parse().unwrap() };
+        let db = DuckDB::new(":memory:").await.unwrap();
+        db.insert_records(&[
+            MonitorRecord { check_time: at("2026-01-01T01:00:00Z"), ..record("1", "A", Some(200), None) },
+            MonitorRecord { check_time: at("2026-01-01T23:59:59Z"), ..record("2", "A", Some(500), Some("HTTP_5XX")) },
+            MonitorRecord { check_time: at("2026-01-03T00:00:00Z"), ..record("3", "A", Some(200), None) },
+            MonitorRecord { check_time: at("2026-01-02T12:00:00Z"), ..record("4", "B", None, Some("TIMEOUT")) },
+            // 窗口外和尚未完成检查的记录不计入
+            MonitorRecord { check_time: at("2025-12-31T23:00:00Z"), ..record("5", "A", Some(200), None) },
+            MonitorRecord { check_time: at("2026-01-02T12:00:00Z"), ..record("6", "B", None, None) },
+        ]).await.unwrap();
+
+        let filter = QueryFilter::range(at("2026-01-01T00:00:00Z"), at("2026-01-03T12:00:00Z"));
+        let mut rows = db.get_center_daily_counts(&filter).await.unwrap();
+        rows.sort();
+        let expected = [("A", "2026-01-01", 2, 1), ("A", "2026-01-03", 1, 1), ("B", "2026-01-02", 1, 0)]
+            .map(|(center, day, total, success)| (center.to_string(), day.to_string(), total, success));
+        assert_eq!(rows, expected);
+
+        let first_day = chrono::NaiveDate::from_ymd_opt(2026, 1, 1).unwrap();
+        let trends = crate::models::center_trends(first_day, 3, 90, &["A".to_string(), "B".to_string()], rows);
+        let totals: Vec<Vec<(i64, Option<f64>)>> = trends.iter()
+            .map(|t| t.points.iter().map(|p| (p.total, p.success_rate)).collect())
+            .collect();
+        assert_eq!(totals, [
+            vec![(2, Some(50.0)), (0, None), (1, Some(100.0))],
+            vec![(0, None), (1, Some(0.0)), (0, None)],
+        ]);
+    }
+
+    #[tokio::test]
+    async fn url_status_tracks_first_seen_and_last_success_over_rounds() {
+        let at = |s: &str| -> DateTime<Utc> { s.parse().unwrap() };
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-url-status-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("monitor.db");
+        let db = DuckDB::new(path.to_str().unwrap()).await.unwrap();
+        // 与监测运行一样：先插入待检查的行，检查完成后由 update_status 写入结果
+        let round = |time: &'static str, statuses: [(&'static str, Option<u16>); 2]| {
+            let db = &db;
+            async move {
+                let pending: Vec<MonitorRecord> = statuses.iter()
+                    .map(|(id, _)| MonitorRecord { check_time: at(time), ..record(id, "A", None, None) })
+                    .collect();
+                db.insert_records(&pending).await.unwrap();
+                let checked: Vec<MonitorRecord> = statuses.iter()
+                    .map(|(id, status)| MonitorRecord {
+                        check_time: at(time),
+                        error_category: status.filter(|s| *s >= 400).map(|_| "HTTP_5XX".to_string()),
+                        ..record(id, "A", *status, None)
+                    })
+                    .collect();
+                db.update_status(&checked).await.unwrap();
+            }
+        };
+        let urls = ["https://a.example.org/1".to_string(), "https://a.example.org/2".to_string()];
+        async fn url_status(db: &DuckDB, urls: &[String]) -> Vec<Option<(DateTime<Utc>, Option<DateTime<Utc>>)>> {
+            let status = db.get_url_status(urls).await.unwrap();
+            urls.iter().map(|url| status.get(url).map(|s| (s.first_seen_at, s.last_success_at))).collect()
+        }
+
+        round("2026-01-01T00:00:00Z", [("1", Some(200)), ("2", Some(500))]).await;
+        assert_eq!(url_status(&db, &urls).await, [
+            Some((at("2026-01-01T00:00:00Z"), Some(at("2026-01-01T00:00:00Z")))),
+            Some((at("2026-01-01T00:00:00Z"), None)),
+        ]);
+
+        // 第二轮：1 失败，last_success_at 不变；2 恢复，last_success_at 前进；first_seen_at 都不变
+        round("2026-01-08T00:00:00Z", [("1", Some(503)), ("2", Some(200))]).await;
+        let second = [
+            Some((at("2026-01-01T00:00:00Z"), Some(at("2026-01-01T00:00:00Z")))),
+            Some((at("2026-01-01T00:00:00Z"), Some(at("2026-01-08T00:00:00Z")))),
+        ];
+        assert_eq!(url_status(&db, &urls).await, second);
+
+        let problematic = db.get_problematic_urls(&QueryFilter::default(), 50.0, 5).await.unwrap();
+        let first_url = problematic.iter().find(|p| p.url == urls[0]).unwrap();
+        assert_eq!(first_url.last_success_at.as_deref(), Some("2026-01-01 00:00:00"));
+
+        // 新建 url_status 时由历史记录回填出相同的结果
+        db.conn.lock().await.execute("DELETE FROM url_status", []).unwrap();
+        drop(db);
+        let reopened = DuckDB::new(path.to_str().unwrap()).await.unwrap();
+        assert_eq!(url_status(&reopened, &urls).await, second);
+        drop(reopened);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn storage_size_follows_bulk_inserts_and_pruning() {
+        assert_eq!(DuckDB::new(":memory:").await.unwrap().storage_size(), StorageSize::default());
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-storage-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("monitor.db");
+        // 文件尚未创建时没有大小
+        assert_eq!(file_size(path.to_str().unwrap()), None);
+        assert_eq!(file_size(dir.to_str().unwrap()), None);
+
+        let db = DuckDB::new(path.to_str().unwrap()).await.unwrap();
+        let start = db.storage_size();
+        assert!(start.db_bytes.is_some());
+        // 5000 个数据集各有一条旧记录，前一半另有一条新记录
+        let records: Vec<MonitorRecord> = (0..5000)
+            .map(|i| MonitorRecord { check_time: "2020-01-01T00:00:00Z".parse().unwrap(), ..record(&i.to_string(), "A", Some(200), None) })
+            .chain((0..2500).map(|i| record(&i.to_string(), "A", Some(200), None)))
+            .collect();
+        db.insert_records(&records).await.unwrap();
+        let end = db.storage_size();
+        assert!(end.total_bytes() > start.total_bytes(), "{:?} -> {:?}", start, end);
+
+        let mut summary = run_summary("r1", "2026-01-01T01:00:00Z");
+        summary.storage = Some(RunStorage { start, end, soft_limit_bytes: None, pruned_records: None });
+        db.insert_run(&summary, "{}").await.unwrap();
+        // 没有记录大小的运行不在序列中
+        db.insert_run(&run_summary("r0", "2026-01-01T00:30:00Z"), "{}").await.unwrap();
+        let series = db.get_storage_series(10).await.unwrap();
+        assert_eq!(series.len(), 1);
+        assert_eq!((series[0].run_id.as_str(), series[0].start, series[0].end), ("r1", start, end));
+        assert_eq!(series[0].growth_bytes, end.total_bytes() as i64 - start.total_bytes() as i64);
+
+        // 删除旧记录时保留每个数据集最近的一条，之后 WAL 已写回，文件不会变小
+        let deleted = db.prune_history("2025-01-01T00:00:00Z".parse().unwrap()).await.unwrap();
+        assert_eq!(deleted, 2500);
+        let count: i64 = db.conn.lock().await
+            .query_row("SELECT COUNT(DISTINCT id) FROM dataset_monitor", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 5000);
+        let pruned = db.storage_size();
+        assert_eq!(pruned.wal_bytes.unwrap_or(0), 0, "{:?}", pruned);
+        assert!(pruned.db_bytes >= end.db_bytes, "{:?} -> {:?}", end, pruned);
+        drop(db);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,246 @@
+//! 统计查询共用的 dataset_monitor 过滤条件，生成 WHERE 片段和对应的参数
+
+use chrono::{DateTime, Utc};
+use duckdb::types::Value;
+
+/// 状态码类别过滤，与状态码统计的标签一致（`4xx`、`no_response`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusClass {
+    /// 百位数字，如 4 表示 4xx
+    Class(u16),
+    /// 没有收到响应
+    NoResponse,
+}
+
+impl std::str::FromStr for StatusClass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "no_response" {
+            return Ok(Self::NoResponse);
+        }
+        match s.strip_suffix("xx").and_then(|d| d.parse::<u16>().ok()) {
+            Some(class @ 1..=5) if s.len() == 3 => Ok(Self::Class(class)),
+            _ => anyhow::bail!("未知的状态码类别 {}，可选 1xx-5xx 或 no_response", s),
+        }
+    }
+}
+
+/// dataset_monitor 的查询条件，未设置的字段不过滤。
+/// 只匹配已完成检查的记录（插入后尚未更新状态的不计入），时间范围为 [since, until)；
+/// 默认不计入尚未发布（进行中）的运行的记录，以及统计归属其他数据中心的共用URL的记录
+#[derive(Debug, Clone, Default)]
+pub struct QueryFilter {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub center_name: Option<String>,
+    pub status_class: Option<StatusClass>,
+    pub error_category: Option<String>,
+    pub local_issue: Option<bool>,
+    /// 只统计检查时带该标签的数据集
+    pub tag: Option<String>,
+    /// 包括进行中的运行已写入的记录，用于查看实时进度
+    pub include_in_progress: bool,
+    /// 共用URL的记录计入每个数据中心，不按 attributed_center 过滤（见 monitor.shared_urls）
+    pub ignore_attribution: bool,
+}
+
+impl QueryFilter {
+    pub fn range(since: DateTime<Utc>, until: DateTime<Utc>) -> Self {
+        Self { since: Some(since), until: Some(until), ..Self::default() }
+    }
+
+    pub fn center(mut self, center_name: impl Into<String>) -> Self {
+        self.center_name = Some(center_name.into());
+        self
+    }
+
+    pub fn status_class(mut self, class: StatusClass) -> Self {
+        self.status_class = Some(class);
+        self
+    }
+
+    pub fn error_category(mut self, category: impl Into<String>) -> Self {
+        self.error_category = Some(category.into());
+        self
+    }
+
+    pub fn local_issue(mut self, local_issue: bool) -> Self {
+        self.local_issue = Some(local_issue);
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    pub fn include_in_progress(mut self) -> Self {
+        self.include_in_progress = true;
+        self
+    }
+
+    pub fn ignore_attribution(mut self) -> Self {
+        self.ignore_attribution = true;
+        self
+    }
+
+    /// WHERE 后的条件片段（不含 WHERE）和按占位符顺序排列的参数，
+    /// 查询中片段之后的占位符参数追加到返回的参数后面
+    pub fn sql(&self) -> (String, Vec<Value>) {
+        let mut conditions = vec!["(status_code IS NOT NULL OR error_category IS NOT NULL)".to_string()];
+        let mut values = Vec::new();
+        if let Some(since) = self.since {
+            conditions.push("check_time >= CAST(? AS TIMESTAMP)".to_string());
+            values.push(Value::Text(since.to_rfc3339()));
+        }
+        if let Some(until) = self.until {
+            conditions.push("check_time < CAST(? AS TIMESTAMP)".to_string());
+            values.push(Value::Text(until.to_rfc3339()));
+        }
+        if let Some(center_name) = &self.center_name {
+            conditions.push("center_name = ?".to_string());
+            values.push(Value::Text(center_name.clone()));
+        }
+        match self.status_class {
+            Some(StatusClass::Class(class)) => {
+                conditions.push("status_code // 100 = ?".to_string());
+                values.push(Value::Int(class as i32));
+            }
+            Some(StatusClass::NoResponse) => conditions.push("status_code IS NULL".to_string()),
+            None => {}
+        }
+        if let Some(category) = &self.error_category {
+            conditions.push("error_category = ?".to_string());
+            values.push(Value::Text(category.clone()));
+        }
+        if let Some(local_issue) = self.local_issue {
+            conditions.push("is_likely_local_issue = ?".to_string());
+            values.push(Value::Boolean(local_issue));
+        }
+        if let Some(tag) = &self.tag {
+            conditions.push("list_contains(string_split(tags, ','), ?)".to_string());
+            values.push(Value::Text(tag.clone()));
+        }
+        if !self.include_in_progress {
+            conditions.push("(run_id IS NULL OR run_id IN (SELECT run_id FROM published_runs))".to_string());
+        }
+        if !self.ignore_attribution {
+            conditions.push("(attributed_center IS NULL OR attributed_center = center_name)".to_string());
+        }
+        (conditions.join(" AND "), values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "(status_code IS NOT NULL OR error_category IS NOT NULL)";
+    const PUBLISHED: &str = "(run_id IS NULL OR run_id IN (SELECT run_id FROM published_runs))";
+    const ATTRIBUTED: &str = "(attributed_center IS NULL OR attributed_center = center_name)";
+
+    fn parts(filter: &QueryFilter) -> (Vec<String>, Vec<Value>) {
+        let (sql, values) = filter.sql();
+        (sql.split(" AND ").map(str::to_string).collect(), values)
+    }
+
+    /// 只设置一个字段时的条件（不含固定条件）和参数
+    fn single(filter: QueryFilter) -> (String, Vec<Value>) {
+        let (conditions, values) = parts(&filter);
+        assert_eq!(conditions.len(), 4, "{:?}", conditions);
+        assert_eq!((conditions[0].as_str(), conditions[2].as_str(), conditions[3].as_str()), (BASE, PUBLISHED, ATTRIBUTED));
+        (conditions[1].clone(), values)
+    }
+
+    fn time(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn empty_filter_keeps_fixed_conditions() {
+        let (conditions, values) = parts(&QueryFilter::default());
+        assert_eq!(conditions, [BASE, PUBLISHED, ATTRIBUTED]);
+        assert!(values.is_empty());
+    }
+
+    #[test]
+    fn each_field_adds_one_condition() {
+        let since = time("2026-01-01T00:00:00Z");
+        let until = time("2026-02-01T00:00:00Z");
+        let cases = [
+            (QueryFilter { since: Some(since), ..QueryFilter::default() },
+             "check_time >= CAST(? AS TIMESTAMP)", vec![Value::Text(since.to_rfc3339())]),
+            (QueryFilter { until: Some(until), ..QueryFilter::default() },
+             "check_time < CAST(? AS TIMESTAMP)", vec![Value::Text(until.to_rfc3339())]),
+            (QueryFilter::default().center("A"), "center_name = ?", vec![Value::Text("A".to_string())]),
+            (QueryFilter::default().status_class(StatusClass::Class(4)), "status_code // 100 = ?", vec![Value::Int(4)]),
+            (QueryFilter::default().status_class(StatusClass::NoResponse), "status_code IS NULL", vec![]),
+            (QueryFilter::default().error_category("TIMEOUT"), "error_category = ?", vec![Value::Text("TIMEOUT".to_string())]),
+            (QueryFilter::default().local_issue(false), "is_likely_local_issue = ?", vec![Value::Boolean(false)]),
+            (QueryFilter::default().tag("core"), "list_contains(string_split(tags, ','), ?)", vec![Value::Text("core".to_string())]),
+        ];
+        for (filter, condition, values) in cases {
+            assert_eq!(single(filter), (condition.to_string(), values));
+        }
+    }
+
+    #[test]
+    fn combined_fields_keep_parameter_order() {
+        let since = time("2026-01-01T00:00:00Z");
+        let until = time("2026-02-01T00:00:00Z");
+        // 设置顺序与参数顺序无关
+        let filter = QueryFilter::range(since, until)
+            .tag("core")
+            .local_issue(true)
+            .error_category("HTTP_5XX")
+            .status_class(StatusClass::Class(5))
+            .center("A");
+        let (conditions, values) = parts(&filter);
+        assert_eq!(conditions, [
+            BASE,
+            "check_time >= CAST(? AS TIMESTAMP)",
+            "check_time < CAST(? AS TIMESTAMP)",
+            "center_name = ?",
+            "status_code // 100 = ?",
+            "error_category = ?",
+            "is_likely_local_issue = ?",
+            "list_contains(string_split(tags, ','), ?)",
+            PUBLISHED,
+            ATTRIBUTED,
+        ]);
+        assert_eq!(values, [
+            Value::Text(since.to_rfc3339()),
+            Value::Text(until.to_rfc3339()),
+            Value::Text("A".to_string()),
+            Value::Int(5),
+            Value::Text("HTTP_5XX".to_string()),
+            Value::Boolean(true),
+            Value::Text("core".to_string()),
+        ]);
+        // 占位符数量与参数数量一致，查询可以在片段后追加自己的参数
+        let (sql, values) = filter.sql();
+        assert_eq!(sql.matches('?').count(), values.len());
+    }
+
+    #[test]
+    fn in_progress_and_attribution_conditions() {
+        let (conditions, _) = parts(&QueryFilter::default().include_in_progress());
+        assert_eq!(conditions, [BASE, ATTRIBUTED]);
+        let (conditions, _) = parts(&QueryFilter::default().ignore_attribution());
+        assert_eq!(conditions, [BASE, PUBLISHED]);
+        let (conditions, values) = parts(&QueryFilter::default().center("A").include_in_progress().ignore_attribution());
+        assert_eq!(conditions, [BASE, "center_name = ?"]);
+        assert_eq!(values, [Value::Text("A".to_string())]);
+    }
+
+    #[test]
+    fn parses_status_class() {
+        assert_eq!("4xx".parse::<StatusClass>().unwrap(), StatusClass::Class(4));
+        assert_eq!("1xx".parse::<StatusClass>().unwrap(), StatusClass::Class(1));
+        assert_eq!("no_response".parse::<StatusClass>().unwrap(), StatusClass::NoResponse);
+        for invalid in ["0xx", "6xx", "4XX", "40x", "04xx", "4", "", "404"] {
+            assert!(invalid.parse::<StatusClass>().is_err(), "{}", invalid);
+        }
+    }
+}
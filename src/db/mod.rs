@@ -1,5 +1,6 @@
 pub mod mongodb;
 pub mod duckdb;
+pub mod filter;
 
 
 use anyhow::Result;
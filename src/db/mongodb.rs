@@ -1,14 +1,16 @@
 use crate::config::MongoDBConfig;
-use crate::models::Dataset;
+use crate::models::{Dataset, DuplicateGroup, FetchAudit, IdBacklog, PendingStats};
 use anyhow::Result;
 use futures::TryStreamExt;
 use mongodb::bson::{DateTime, Document};
-use mongodb::options::ReplaceOptions;
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::bson;
 use mongodb::{
     bson::{doc, Regex},
     options::ClientOptions,
     Client, Collection, Database,
 };
+use std::time::Duration;
 use tracing::info;
 
 pub struct MongoDB {
@@ -25,14 +27,21 @@ impl MongoDB {
         Ok(Self { client, database })
     }
 
+    /// 按 `@id` 替换或插入数据集，保留库中已有的 tags
     pub async fn upsert_dataset(&self, collection_name: &str, dataset: Dataset) -> Result<()> {
-        let collection: Collection<Dataset> = self.database.collection(collection_name);
+        let collection: Collection<Document> = self.database.collection(collection_name);
 
         let filter = doc! { "@id": &dataset.raw_id};
 
-        let options = ReplaceOptions::builder().upsert(true).build();
+        let mut replacement = bson::to_document(&dataset)?;
+        replacement.remove("_id");
+        replacement.remove("tags");
+        // $literal 避免以 $ 开头的字段值被当作表达式
+        let pipeline = vec![doc! {
+            "$replaceWith": { "$mergeObjects": [{ "_id": "$_id", "tags": "$tags" }, { "$literal": replacement }] }
+        }];
 
-        collection.replace_one(filter, dataset).with_options(options).await?;
+        collection.update_one(filter, pipeline).upsert(true).await?;
 
         Ok(())
     }
@@ -69,6 +78,75 @@ impl MongoDB {
         Ok(datasets)
     }
 
+    /// 带 `tag` 标签的数据集
+    pub async fn get_tagged_datasets(&self, collection_name: &str, tag: &str) -> Result<Vec<Dataset>> {
+        let collection: Collection<Dataset> = self.database.collection(collection_name);
+
+        let filter = doc! {
+            "@type": Regex {
+                pattern: "Dataset".to_string(),
+                options: "i".to_string(),
+            },
+            "tags": tag,
+        };
+
+        Ok(collection.find(filter).await?.try_collect().await?)
+    }
+
+    /// 给数据集添加和移除标签，返回修改后的标签；数据集不存在时返回 None
+    pub async fn update_dataset_tags(&self, collection_name: &str, raw_id: &str, add: &[String], remove: &[String]) -> Result<Option<Vec<String>>> {
+        let collection: Collection<Document> = self.database.collection(collection_name);
+        let filter = doc! { "@id": raw_id };
+        // 同一次更新中不能对 tags 既 $addToSet 又 $pull，分两次执行
+        if !add.is_empty() {
+            collection.update_one(filter.clone(), doc! { "$addToSet": { "tags": { "$each": add } } }).await?;
+        }
+        if !remove.is_empty() {
+            collection.update_one(filter.clone(), doc! { "$pull": { "tags": { "$in": remove } } }).await?;
+        }
+        let Some(document) = collection.find_one(filter).await? else {
+            return Ok(None);
+        };
+        let tags = document.get_array("tags")
+            .map(|tags| tags.iter().filter_map(|t| t.as_str().map(String::from)).collect())
+            .unwrap_or_default();
+        Ok(Some(tags))
+    }
+
+    /// 在各数据中心的集合中按 `@id` 或 casdc_id 查找数据集，`centers` 为 (数据中心, 集合名)，
+    /// 返回 (数据中心, 数据集)
+    pub async fn find_dataset(&self, centers: &[(&str, String)], id: &str) -> Result<Option<(String, Dataset)>> {
+        let filter = doc! { "$or": [{ "@id": id }, { "casdc_id": id }] };
+        for (center_name, collection_name) in centers {
+            let collection: Collection<Dataset> = self.database.collection(collection_name);
+            if let Some(dataset) = collection.find_one(filter.clone()).await? {
+                return Ok(Some((center_name.to_string(), dataset)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 把数据中心的集合迁移到新名称，`copy` 时复制并保留原集合，否则重命名。
+    /// 目标集合已有数据时不迁移。返回目标集合的文档数
+    pub async fn migrate_collection(&self, from: &str, to: &str, copy: bool) -> Result<u64> {
+        let target: Collection<Document> = self.database.collection(to);
+        if target.estimated_document_count().await? > 0 {
+            anyhow::bail!("集合 {} 已有数据，不迁移 {}", to, from);
+        }
+        if copy {
+            let source: Collection<Document> = self.database.collection(from);
+            source.aggregate(vec![doc! { "$match": {} }, doc! { "$out": to }]).await?;
+        } else {
+            let database = self.database.name();
+            self.client.database("admin").run_command(doc! {
+                "renameCollection": format!("{}.{}", database, from),
+                "to": format!("{}.{}", database, to),
+                "dropTarget": true,
+            }).await?;
+        }
+        Ok(target.count_documents(doc! {}).await?)
+    }
+
     pub async fn save_new_dataset_ids(&self, center_name: &str, new_ids: &[String]) -> Result<()> {
         let collection = self.database
             .collection::<Document>("processed_dataset_ids");
@@ -131,14 +209,20 @@ impl MongoDB {
         Ok(processed_ids)
     }
 
-    pub async fn get_unprocessed_ids(&self, center_name: &str) -> Result<Vec<String>> {
+    /// 待处理的ID，从未失败的在前，失败过的按上次失败时间从早到晚；`limit` 限制返回数量
+    pub async fn get_unprocessed_ids(&self, center_name: &str, limit: Option<usize>) -> Result<Vec<String>> {
         let collection = self.database
             .collection::<Document>("processed_dataset_ids");
         let filter = doc! {
                 "center_name": center_name,
                 "status": "pending"
             };
-        let mut cursor = collection.find(filter).await?;
+        let mut find = collection.find(filter)
+            .sort(doc! { "last_failed_at": 1, "created_at": 1 });
+        if let Some(limit) = limit {
+            find = find.limit(limit as i64);
+        }
+        let mut cursor = find.await?;
         let mut unprocessed_ids = Vec::new();
 
         while let Some(doc) = cursor.try_next().await? {
@@ -172,4 +256,641 @@ impl MongoDB {
         collection.update_many(filter, update).await?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// 认领最多 `limit` 个待处理的ID：改为 in_progress 并记下认领时间和认领者，每个ID用一次 find_one_and_update 认领，
+    /// 并发的认领者不会拿到同一个ID。`failed_since` 之后失败过的ID以及 `exclude` 中的ID不认领；认领时间早于
+    /// `expired_before` 的 in_progress（认领者崩溃或被中断）视为待处理，可以重新认领。
+    /// 失败信息写库失败时 last_failed_at 不会更新，调用方把本次已失败的ID放进 `exclude`，避免放回后又被认领
+    pub async fn claim_pending_ids(&self, center_name: &str, owner: &str, limit: usize, exclude: &[String],
+                                   failed_since: chrono::DateTime<chrono::Utc>, expired_before: chrono::DateTime<chrono::Utc>) -> Result<Vec<String>> {
+        let collection = self.database
+            .collection::<Document>("processed_dataset_ids");
+        let filter = claim_filter(center_name, exclude, failed_since, expired_before);
+        let mut claimed = Vec::new();
+        while claimed.len() < limit {
+            let update = doc! {
+                "$set": { "status": "in_progress", "claimed_at": DateTime::now(), "claimed_by": owner }
+            };
+            let Some(document) = collection.find_one_and_update(filter.clone(), update)
+                .sort(doc! { "last_failed_at": 1, "created_at": 1 })
+                .await? else {
+                break;
+            };
+            if let Ok(id) = document.get_str("dataset_id") {
+                claimed.push(id.to_string());
+            }
+        }
+        Ok(claimed)
+    }
+
+    /// 把 `owner` 认领且尚未处理完成的ID放回待处理
+    pub async fn release_claims(&self, center_name: &str, owner: &str, ids: &[String]) -> Result<()> {
+        let collection = self.database
+            .collection::<Document>("processed_dataset_ids");
+        collection.update_many(
+            doc! { "center_name": center_name, "status": "in_progress", "claimed_by": owner, "dataset_id": { "$in": ids } },
+            doc! {
+                "$set": { "status": "pending" },
+                "$unset": { "claimed_at": "", "claimed_by": "" }
+            },
+        ).await?;
+        Ok(())
+    }
+
+    /// 把所有数据中心中认领时间早于 `expired_before` 的 in_progress ID 放回待处理，返回放回的数量
+    pub async fn release_expired_claims(&self, expired_before: chrono::DateTime<chrono::Utc>) -> Result<u64> {
+        let collection = self.database
+            .collection::<Document>("processed_dataset_ids");
+        let result = collection.update_many(
+            doc! { "status": "in_progress", "claimed_at": { "$lt": DateTime::from_millis(expired_before.timestamp_millis()) } },
+            doc! {
+                "$set": { "status": "pending" },
+                "$unset": { "claimed_at": "", "claimed_by": "" }
+            },
+        ).await?;
+        Ok(result.modified_count)
+    }
+
+    /// 在 `older_than` 之前发现、至今仍待处理（包括处理中）的 ID
+    pub async fn get_stale_pending(&self, center_name: &str, older_than: chrono::DateTime<chrono::Utc>) -> Result<Vec<String>> {
+        let collection = self.database
+            .collection::<Document>("processed_dataset_ids");
+        let filter = doc! {
+            "center_name": center_name,
+            "status": { "$in": ["pending", "in_progress"] },
+            "created_at": { "$lt": DateTime::from_millis(older_than.timestamp_millis()) }
+        };
+        let documents: Vec<Document> = collection.find(filter).await?.try_collect().await?;
+        Ok(documents.into_iter()
+            .filter_map(|doc| doc.get_str("dataset_id").ok().map(String::from))
+            .collect())
+    }
+
+    /// 标记为过期，之后不再重试
+    pub async fn expire_pending_ids(&self, center_name: &str, ids: &[String]) -> Result<()> {
+        let collection = self.database
+            .collection::<Document>("processed_dataset_ids");
+        collection.update_many(
+            doc! { "center_name": center_name, "status": "pending", "dataset_id": { "$in": ids } },
+            doc! { "$set": { "status": "expired", "expired_at": DateTime::now() } },
+        ).await?;
+        Ok(())
+    }
+
+    /// 把过期的 ID 恢复为待处理（重新计算过期时间），不指定数据中心时恢复全部，返回恢复的数量
+    pub async fn resurrect_expired_ids(&self, center_name: Option<&str>) -> Result<u64> {
+        let collection = self.database
+            .collection::<Document>("processed_dataset_ids");
+        let mut filter = doc! { "status": "expired" };
+        if let Some(name) = center_name {
+            filter.insert("center_name", name);
+        }
+        let result = collection.update_many(
+            filter,
+            doc! {
+                "$set": { "status": "pending", "created_at": DateTime::now() },
+                "$unset": { "expired_at": "" }
+            },
+        ).await?;
+        Ok(result.modified_count)
+    }
+
+    /// 各数据中心待处理、失败过、已过期的 ID 数量
+    pub async fn get_pending_stats(&self) -> Result<Vec<PendingStats>> {
+        let collection = self.database
+            .collection::<Document>("processed_dataset_ids");
+        let pipeline = vec![
+            doc! { "$match": { "status": { "$in": ["pending", "expired"] } } },
+            doc! { "$group": {
+                "_id": "$center_name",
+                "pending": { "$sum": { "$cond": [{ "$eq": ["$status", "pending"] }, 1, 0] } },
+                "failed": { "$sum": { "$cond": [
+                    { "$and": [{ "$eq": ["$status", "pending"] }, { "$gt": ["$failure_count", 0] }] }, 1, 0
+                ] } },
+                "expired": { "$sum": { "$cond": [{ "$eq": ["$status", "expired"] }, 1, 0] } }
+            } },
+            doc! { "$sort": { "_id": 1 } },
+        ];
+        let documents: Vec<Document> = collection.aggregate(pipeline).await?.try_collect().await?;
+        let count = |doc: &Document, key: &str| doc.get(key).and_then(|v| match v {
+            bson::Bson::Int32(n) => Some(*n as u64),
+            bson::Bson::Int64(n) => Some(*n as u64),
+            _ => None,
+        }).unwrap_or(0);
+        Ok(documents.iter()
+            .map(|doc| PendingStats {
+                center_name: doc.get_str("_id").unwrap_or_default().to_string(),
+                pending: count(doc, "pending"),
+                failed: count(doc, "failed"),
+                expired: count(doc, "expired"),
+            })
+            .collect())
+    }
+
+    /// 单个数据中心 processed_dataset_ids 中各状态的 ID 数量
+    pub async fn count_ids_by_status(&self, center_name: &str) -> Result<IdBacklog> {
+        let collection = self.database
+            .collection::<Document>("processed_dataset_ids");
+        let pipeline = vec![
+            doc! { "$match": { "center_name": center_name } },
+            doc! { "$group": {
+                "_id": null,
+                "pending": { "$sum": { "$cond": [{ "$eq": ["$status", "pending"] }, 1, 0] } },
+                "in_progress": { "$sum": { "$cond": [{ "$eq": ["$status", "in_progress"] }, 1, 0] } },
+                "processed": { "$sum": { "$cond": [{ "$eq": ["$status", "processed"] }, 1, 0] } },
+                "failed": { "$sum": { "$cond": [
+                    { "$and": [{ "$eq": ["$status", "pending"] }, { "$gt": ["$failure_count", 0] }] }, 1, 0
+                ] } },
+                "expired": { "$sum": { "$cond": [{ "$eq": ["$status", "expired"] }, 1, 0] } }
+            } },
+        ];
+        let documents: Vec<Document> = collection.aggregate(pipeline).await?.try_collect().await?;
+        let count = |key: &str| documents.first().and_then(|doc| doc.get(key)).and_then(|v| match v {
+            bson::Bson::Int32(n) => Some(*n as u64),
+            bson::Bson::Int64(n) => Some(*n as u64),
+            _ => None,
+        }).unwrap_or(0);
+        Ok(IdBacklog {
+            center_name: center_name.to_string(),
+            pending: count("pending"),
+            in_progress: count("in_progress"),
+            processed: count("processed"),
+            failed: count("failed"),
+            expired: count("expired"),
+        })
+    }
+
+    /// 在待处理记录上记下详情获取失败的原因（解析失败时还有原始响应位置），ID 仍保持待处理状态
+    pub async fn record_detail_failure(&self, center_name: &str, dataset_id: &str, error: &str, raw_path: Option<&str>) -> Result<()> {
+        let collection = self.database
+            .collection::<Document>("processed_dataset_ids");
+        collection.update_one(
+            doc! { "center_name": center_name, "dataset_id": dataset_id },
+            doc! {
+                "$set": {
+                    "last_error": error,
+                    "raw_response_path": raw_path,
+                    "last_failed_at": DateTime::now()
+                },
+                "$inc": { "failure_count": 1 }
+            },
+        ).await?;
+        Ok(())
+    }
+
+    pub async fn insert_fetch_audit(&self, audit: &FetchAudit) -> Result<()> {
+        let collection = self.database
+            .collection::<Document>("fetch_audit");
+        collection.insert_one(bson::to_document(audit)?).await?;
+        Ok(())
+    }
+
+    /// 用本次扫描结果整体替换 dataset_duplicates
+    pub async fn replace_duplicate_groups(&self, groups: &[DuplicateGroup]) -> Result<()> {
+        let collection = self.database
+            .collection::<Document>("dataset_duplicates");
+        collection.delete_many(doc! {}).await?;
+        if groups.is_empty() {
+            return Ok(());
+        }
+        let documents = groups.iter()
+            .map(bson::to_document)
+            .collect::<Result<Vec<_>, _>>()?;
+        collection.insert_many(documents).await?;
+        Ok(())
+    }
+
+    pub async fn get_duplicate_groups(&self) -> Result<Vec<DuplicateGroup>> {
+        let collection = self.database
+            .collection::<Document>("dataset_duplicates");
+        let documents: Vec<Document> = collection.find(doc! {}).await?.try_collect().await?;
+        Ok(documents.into_iter().map(bson::from_document).collect::<Result<_, _>>()?)
+    }
+
+    /// 数据中心最近一次成功获取的审计记录，`full_only` 时跳过增量获取
+    pub async fn get_last_successful_fetch(&self, center_name: &str, full_only: bool) -> Result<Option<FetchAudit>> {
+        let collection = self.database
+            .collection::<Document>("fetch_audit");
+        let mut filter = doc! { "center_name": center_name, "success": true };
+        if full_only {
+            filter.insert("outcome.incremental", doc! { "$ne": true });
+        }
+        // finished_at 为 RFC 3339 UTC 字符串，按字符串排序即按时间排序
+        let document = collection
+            .find_one(filter)
+            .sort(doc! { "finished_at": -1 })
+            .await?;
+        Ok(document.map(bson::from_document).transpose()?)
+    }
+
+    /// 最近一次完整获取之后连续成功增量获取的次数，最多数到 `limit`
+    pub async fn count_incremental_since_full(&self, center_name: &str, limit: usize) -> Result<usize> {
+        let collection = self.database
+            .collection::<Document>("fetch_audit");
+        let documents: Vec<Document> = collection
+            .find(doc! { "center_name": center_name, "success": true })
+            .sort(doc! { "finished_at": -1 })
+            .limit(limit as i64)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(documents.iter()
+            .take_while(|doc| doc.get_document("outcome").ok()
+                .and_then(|outcome| outcome.get_bool("incremental").ok())
+                .unwrap_or(false))
+            .count())
+    }
+
+    /// 已处理的ID重新排为待处理（数据中心报告其有变化），返回实际重新排队的数量
+    pub async fn requeue_ids(&self, center_name: &str, ids: &[String]) -> Result<u64> {
+        let collection = self.database
+            .collection::<Document>("processed_dataset_ids");
+        // created_at 一并重置，避免被当作超期的待处理ID
+        let result = collection.update_many(
+            doc! { "center_name": center_name, "status": "processed", "dataset_id": { "$in": ids } },
+            doc! { "$set": { "status": "pending", "created_at": DateTime::now(), "requeued_at": DateTime::now() } },
+        ).await?;
+        Ok(result.modified_count)
+    }
+
+    /// 尝试获取分布式运行锁，持有者本身或已过期的租约都可以获取
+    pub async fn try_acquire_lock(&self, name: &str, owner: &str, ttl: Duration) -> Result<bool> {
+        let collection = self.database
+            .collection::<Document>("run_locks");
+        let now = DateTime::now();
+        let expires_at = DateTime::from_millis(now.timestamp_millis() + ttl.as_millis() as i64);
+        let filter = doc! {
+            "_id": name,
+            "$or": [
+                { "expires_at": { "$lt": now } },
+                { "owner": owner }
+            ]
+        };
+        let update = doc! {
+            "$set": {
+                "owner": owner,
+                "expires_at": expires_at,
+                "renewed_at": now
+            }
+        };
+        // 锁被其他实例持有且未过期时，upsert 会因 _id 冲突而失败
+        match collection.find_one_and_update(filter, update).upsert(true).await {
+            Ok(_) => Ok(true),
+            Err(e) if is_duplicate_key(&e) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 续期运行锁，返回 false 表示锁已被其他实例抢占
+    pub async fn renew_lock(&self, name: &str, owner: &str, ttl: Duration) -> Result<bool> {
+        let collection = self.database
+            .collection::<Document>("run_locks");
+        let now = DateTime::now();
+        let expires_at = DateTime::from_millis(now.timestamp_millis() + ttl.as_millis() as i64);
+        let result = collection.update_one(
+            doc! { "_id": name, "owner": owner },
+            doc! { "$set": { "expires_at": expires_at, "renewed_at": now } },
+        ).await?;
+        Ok(result.matched_count > 0)
+    }
+
+    pub async fn release_lock(&self, name: &str, owner: &str) -> Result<()> {
+        let collection = self.database
+            .collection::<Document>("run_locks");
+        collection.delete_one(doc! { "_id": name, "owner": owner }).await?;
+        Ok(())
+    }
+}
+
+/// 可认领的ID：待处理或认领已超时，`failed_since` 之后没有失败过，且不在 `exclude` 中
+fn claim_filter(center_name: &str, exclude: &[String], failed_since: chrono::DateTime<chrono::Utc>,
+                expired_before: chrono::DateTime<chrono::Utc>) -> Document {
+    doc! {
+        "center_name": center_name,
+        "dataset_id": { "$nin": exclude },
+        "$and": [
+            { "$or": [
+                { "status": "pending" },
+                { "status": "in_progress", "claimed_at": { "$lt": DateTime::from_millis(expired_before.timestamp_millis()) } }
+            ] },
+            { "$or": [
+                { "last_failed_at": { "$exists": false } },
+                { "last_failed_at": { "$lt": DateTime::from_millis(failed_since.timestamp_millis()) } }
+            ] }
+        ]
+    }
+}
+
+fn is_duplicate_key(e: &mongodb::error::Error) -> bool {
+    match e.kind.as_ref() {
+        ErrorKind::Command(err) => err.code == 11000,
+        ErrorKind::Write(WriteFailure::WriteError(err)) => err.code == 11000,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    /// 需要 MongoDB 的测试在设置了 DATASET_MONITOR_TEST_MONGODB_URI 时才运行，每个测试使用单独的库
+    async fn test_db(tag: &str) -> Option<MongoDB> {
+        let uri = std::env::var("DATASET_MONITOR_TEST_MONGODB_URI").ok()?;
+        let config = MongoDBConfig {
+            uri,
+            database: format!("dataset_monitor_test_{}_{}", tag, std::process::id()),
+            legacy_collection_names: false,
+        };
+        let db = MongoDB::new(&config).await.unwrap();
+        db.database.drop().await.unwrap();
+        Some(db)
+    }
+
+    fn ids(range: std::ops::Range<usize>) -> Vec<String> {
+        range.map(|i| format!("id-{:03}", i)).collect()
+    }
+
+    #[test]
+    fn claim_filter_excludes_ids_and_recent_failures() {
+        let now = Utc::now();
+        let filter = claim_filter("c", &ids(0..2), now, now - chrono::Duration::minutes(30));
+        assert_eq!(filter.get_str("center_name").unwrap(), "c");
+        let excluded = filter.get_document("dataset_id").unwrap().get_array("$nin").unwrap();
+        assert_eq!(excluded, &vec![bson::Bson::from("id-000"), bson::Bson::from("id-001")]);
+        let conditions = filter.get_array("$and").unwrap();
+        assert_eq!(conditions.len(), 2);
+        let failed = conditions[1].as_document().unwrap().get_array("$or").unwrap();
+        assert_eq!(failed[1].as_document().unwrap().get_document("last_failed_at").unwrap().get_datetime("$lt").unwrap(),
+                   &DateTime::from_millis(now.timestamp_millis()));
+    }
+
+    #[tokio::test]
+    async fn concurrent_claimers_never_share_ids() {
+        let Some(db) = test_db("race").await else { return };
+        let all = ids(0..40);
+        db.save_new_dataset_ids("c", &all).await.unwrap();
+        let now = Utc::now();
+        let expired_before = now - chrono::Duration::minutes(30);
+        let claimer = |owner: &'static str| {
+            let db = &db;
+            async move {
+                let mut claimed = Vec::new();
+                loop {
+                    let batch = db.claim_pending_ids("c", owner, 3, &[], now, expired_before).await.unwrap();
+                    if batch.is_empty() {
+                        break claimed;
+                    }
+                    claimed.extend(batch);
+                }
+            }
+        };
+        let (a, b) = tokio::join!(claimer("a"), claimer("b"));
+        let mut union: Vec<String> = a.iter().chain(&b).cloned().collect();
+        union.sort();
+        assert_eq!(union, all, "每个ID只被一个认领者拿到");
+        db.database.drop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn released_ids_are_reclaimable_unless_excluded_or_failed() {
+        let Some(db) = test_db("release").await else { return };
+        let all = ids(0..6);
+        db.save_new_dataset_ids("c", &all).await.unwrap();
+        let started_at = Utc::now();
+        let expired_before = started_at - chrono::Duration::minutes(30);
+        let claimed = db.claim_pending_ids("c", "a", 10, &[], started_at, expired_before).await.unwrap();
+        assert_eq!(claimed.len(), 6);
+        assert!(db.claim_pending_ids("c", "b", 10, &[], started_at, expired_before).await.unwrap().is_empty());
+
+        // 其他认领者不能放回不属于自己的ID
+        db.release_claims("c", "b", &all).await.unwrap();
+        assert!(db.claim_pending_ids("c", "b", 10, &[], started_at, expired_before).await.unwrap().is_empty());
+
+        db.record_detail_failure("c", "id-000", "解析失败", None).await.unwrap();
+        db.release_claims("c", "a", &all).await.unwrap();
+        let mut reclaimed = db.claim_pending_ids("c", "a", 10, &all[1..2], started_at, expired_before).await.unwrap();
+        reclaimed.sort();
+        assert_eq!(reclaimed, all[2..].to_vec());
+
+        // 下次运行（开始时间晚于失败时间）重新认领失败过的ID
+        db.release_claims("c", "a", &all).await.unwrap();
+        let mut next_run = db.claim_pending_ids("c", "a", 10, &[], Utc::now(), expired_before).await.unwrap();
+        next_run.sort();
+        assert_eq!(next_run, all);
+        db.database.drop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn backlog_counts_each_status() {
+        let Some(db) = test_db("backlog").await else { return };
+        db.save_new_dataset_ids("c", &ids(0..8)).await.unwrap();
+        db.save_new_dataset_ids("other", &ids(0..3)).await.unwrap();
+        db.update_processed_ids("c", &ids(0..3)).await.unwrap();
+        db.expire_pending_ids("c", &ids(3..4)).await.unwrap();
+        db.record_detail_failure("c", "id-004", "解析失败", None).await.unwrap();
+        let now = Utc::now();
+        db.claim_pending_ids("c", "a", 1, &ids(4..5), now, now - chrono::Duration::minutes(30)).await.unwrap();
+
+        let backlog = db.count_ids_by_status("c").await.unwrap();
+        assert_eq!(
+            (backlog.pending, backlog.in_progress, backlog.processed, backlog.failed, backlog.expired),
+            (3, 1, 3, 1, 1)
+        );
+        let empty = db.count_ids_by_status("missing").await.unwrap();
+        assert_eq!((empty.center_name.as_str(), empty.pending, empty.processed), ("missing", 0, 0));
+        db.database.drop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stale_ids_expire_and_resurrect() {
+        let Some(db) = test_db("expire").await else { return };
+        db.save_new_dataset_ids("c", &ids(0..4)).await.unwrap();
+        db.save_new_dataset_ids("other", &ids(0..2)).await.unwrap();
+        let old = DateTime::from_millis((Utc::now() - chrono::Duration::days(40)).timestamp_millis());
+        db.database.collection::<Document>("processed_dataset_ids").update_many(
+            doc! { "center_name": "c", "dataset_id": { "$in": ["id-000", "id-001", "id-003"] } },
+            doc! { "$set": { "created_at": old } },
+        ).await.unwrap();
+        db.update_processed_ids("c", &ids(3..4)).await.unwrap();
+        db.record_detail_failure("c", "id-002", "解析失败", None).await.unwrap();
+
+        let older_than = Utc::now() - chrono::Duration::days(30);
+        let mut stale = db.get_stale_pending("c", older_than).await.unwrap();
+        stale.sort();
+        assert_eq!(stale, ids(0..2));
+        // 已处理的ID不会被标记为过期
+        db.expire_pending_ids("c", &["id-000".to_string(), "id-003".to_string()]).await.unwrap();
+        let stats: Vec<(String, u64, u64, u64)> = db.get_pending_stats().await.unwrap().into_iter()
+            .map(|s| (s.center_name, s.pending, s.failed, s.expired))
+            .collect();
+        assert_eq!(stats, [("c".to_string(), 2, 1, 1), ("other".to_string(), 2, 0, 0)]);
+
+        assert_eq!(db.resurrect_expired_ids(Some("other")).await.unwrap(), 0);
+        assert_eq!(db.resurrect_expired_ids(None).await.unwrap(), 1);
+        // 恢复后重新计算过期时间
+        assert_eq!(db.get_stale_pending("c", older_than).await.unwrap(), ["id-001"]);
+        db.database.drop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn locks_are_exclusive_until_released() {
+        let Some(db) = test_db("lock").await else { return };
+        let ttl = std::time::Duration::from_secs(60);
+        assert!(db.try_acquire_lock("job", "a", ttl).await.unwrap());
+        // 持有者可以重新获取，其他实例不能
+        assert!(db.try_acquire_lock("job", "a", ttl).await.unwrap());
+        assert!(!db.try_acquire_lock("job", "b", ttl).await.unwrap());
+        assert!(db.try_acquire_lock("other", "b", ttl).await.unwrap());
+        assert!(db.renew_lock("job", "a", ttl).await.unwrap());
+        assert!(!db.renew_lock("job", "b", ttl).await.unwrap());
+
+        // 不能释放其他实例持有的锁
+        db.release_lock("job", "b").await.unwrap();
+        assert!(!db.try_acquire_lock("job", "b", ttl).await.unwrap());
+        db.release_lock("job", "a").await.unwrap();
+        assert!(!db.renew_lock("job", "a", ttl).await.unwrap());
+        assert!(db.try_acquire_lock("job", "b", ttl).await.unwrap());
+        db.database.drop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn racing_instances_get_one_lock() {
+        let Some(db) = test_db("lock_race").await else { return };
+        let ttl = std::time::Duration::from_secs(60);
+        let acquired = futures::future::join_all(
+            ["a", "b", "c", "d"].map(|owner| db.try_acquire_lock("job", owner, ttl))
+        ).await;
+        let winners = acquired.into_iter().map(|r| r.unwrap()).filter(|won| *won).count();
+        assert_eq!(winners, 1);
+        db.database.drop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn expired_locks_can_be_stolen() {
+        let Some(db) = test_db("lock_expire").await else { return };
+        assert!(db.try_acquire_lock("job", "a", std::time::Duration::from_millis(50)).await.unwrap());
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let ttl = std::time::Duration::from_secs(60);
+        assert!(db.try_acquire_lock("job", "b", ttl).await.unwrap());
+        // 原持有者续期失败，也不能释放新持有者的锁
+        assert!(!db.renew_lock("job", "a", ttl).await.unwrap());
+        db.release_lock("job", "a").await.unwrap();
+        assert!(!db.try_acquire_lock("job", "c", ttl).await.unwrap());
+        db.database.drop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn lost_leases_stop_the_job() {
+        let Some(db) = test_db("lease_lost").await else { return };
+        let db = std::sync::Arc::new(db);
+        let ttl = std::time::Duration::from_secs(3);
+        let steal = async {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            db.database.collection::<Document>("run_locks")
+                .update_one(doc! { "_id": "job" }, doc! { "$set": { "owner": "other" } }).await.unwrap();
+        };
+        let (result, ()) = tokio::join!(
+            crate::scheduler::run_with_lease(&db, "job", ttl, std::future::pending::<Result<()>>()),
+            steal,
+        );
+        assert!(result.is_err());
+        // 被抢占的锁不会被原持有者释放
+        assert!(!db.try_acquire_lock("job", "b", ttl).await.unwrap());
+        db.database.drop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn panicking_jobs_stop_renewing_the_lease() {
+        let Some(db) = test_db("lease_panic").await else { return };
+        let db = std::sync::Arc::new(db);
+        let ttl = std::time::Duration::from_secs(3);
+        let job = {
+            let db = db.clone();
+            tokio::spawn(async move {
+                crate::scheduler::run_with_lease(&db, "job", ttl, async { panic!("任务失败") as Result<()> }).await
+            })
+        };
+        assert!(job.await.unwrap_err().is_panic());
+        assert!(!db.try_acquire_lock("job", "b", ttl).await.unwrap());
+        // 不再续期，租约过期后其他实例可以接手
+        tokio::time::sleep(ttl + std::time::Duration::from_millis(500)).await;
+        assert!(db.try_acquire_lock("job", "b", ttl).await.unwrap());
+        db.database.drop().await.unwrap();
+    }
+
+    fn audit(minutes: i64, success: bool, incremental: bool) -> FetchAudit {
+        let at = Utc::now() + chrono::Duration::minutes(minutes);
+        FetchAudit {
+            center_name: "A".to_string(),
+            started_at: at,
+            finished_at: at,
+            success,
+            outcome: Some(crate::models::CenterFetchReport { incremental, ..Default::default() }),
+            error: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn incremental_runs_are_counted_since_the_last_full_fetch() {
+        let Some(db) = test_db("incremental").await else {
+            return;
+        };
+        assert!(db.get_last_successful_fetch("A", false).await.unwrap().is_none());
+        assert_eq!(db.count_incremental_since_full("A", 5).await.unwrap(), 0);
+
+        // 完整获取后两次成功的增量获取，中间一次失败的增量获取不计
+        for (minutes, success, incremental) in [(0, true, false), (1, true, true), (2, false, true), (3, true, true)] {
+            db.insert_fetch_audit(&audit(minutes, success, incremental)).await.unwrap();
+        }
+        assert_eq!(db.count_incremental_since_full("A", 5).await.unwrap(), 2);
+        assert_eq!(db.count_incremental_since_full("A", 1).await.unwrap(), 1);
+        assert_eq!(db.count_incremental_since_full("B", 5).await.unwrap(), 0);
+        let last = db.get_last_successful_fetch("A", false).await.unwrap().unwrap();
+        assert!(last.outcome.unwrap().incremental);
+        let full = db.get_last_successful_fetch("A", true).await.unwrap().unwrap();
+        assert!(!full.outcome.unwrap().incremental);
+
+        db.insert_fetch_audit(&audit(4, true, false)).await.unwrap();
+        assert_eq!(db.count_incremental_since_full("A", 5).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn dataset_tags_are_edited_and_survive_fetches() {
+        let Some(db) = test_db("tags").await else {
+            return;
+        };
+        let dataset = |name: &str| Dataset {
+            _id: None,
+            raw_id: "raw-1".to_string(),
+            casdc_id: None,
+            data_type: Some(bson::Bson::String("Dataset".to_string())),
+            url: Some(bson::Bson::String("https://data.casdc.cn/1".to_string())),
+            name: Some(bson::Bson::String(name.to_string())),
+            date_published: None,
+            sync_date: None,
+            center_name: Some("A".to_string()),
+            identifier: None,
+            tags: Vec::new(),
+        };
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        db.upsert_dataset("c", dataset("v1")).await.unwrap();
+        db.upsert_dataset("c", Dataset { raw_id: "raw-2".to_string(), ..dataset("other") }).await.unwrap();
+
+        let edited = db.update_dataset_tags("c", "raw-1", &tags(&["critical", "core"]), &[]).await.unwrap();
+        assert_eq!(edited, Some(tags(&["critical", "core"])));
+        // 重复添加不产生重复标签
+        let edited = db.update_dataset_tags("c", "raw-1", &tags(&["critical"]), &tags(&["core"])).await.unwrap();
+        assert_eq!(edited, Some(tags(&["critical"])));
+        assert_eq!(db.update_dataset_tags("c", "missing", &tags(&["critical"]), &[]).await.unwrap(), None);
+
+        // 数据获取替换文档时保留标签
+        db.upsert_dataset("c", dataset("v2")).await.unwrap();
+        let tagged = db.get_tagged_datasets("c", "critical").await.unwrap();
+        assert_eq!(tagged.len(), 1);
+        assert_eq!((tagged[0].raw_id.as_str(), tagged[0].extract_name(), &tagged[0].tags), ("raw-1", "v2".to_string(), &tags(&["critical"])));
+        assert!(db.get_tagged_datasets("c", "crit").await.unwrap().is_empty());
+        assert_eq!(db.get_datasets("c").await.unwrap().len(), 2);
+        db.database.drop().await.unwrap();
+    }
+}
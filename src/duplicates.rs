@@ -0,0 +1,236 @@
+use crate::config::Config;
+use crate::db::mongodb::MongoDB;
+use crate::models::{Dataset, DuplicateGroup, DuplicateMember, DuplicatePairCount};
+use anyhow::Result;
+use chrono::Utc;
+use std::collections::{BTreeSet, HashMap};
+use tracing::info;
+
+/// DOI 常见的前缀写法，匹配前已转为小写
+const DOI_PREFIXES: [&str; 5] = ["https://doi.org/", "http://doi.org/", "https://dx.doi.org/", "http://dx.doi.org/", "doi:"];
+
+/// 规范化 DOI：去掉空白和 `https://doi.org/`、`doi:` 等前缀并转为小写，不是 DOI 时返回 None
+pub fn normalize_doi(s: &str) -> Option<String> {
+    let mut doi = s.trim().to_lowercase();
+    for prefix in DOI_PREFIXES {
+        if let Some(rest) = doi.strip_prefix(prefix) {
+            doi = rest.trim().to_string();
+            break;
+        }
+    }
+    doi.starts_with("10.").then_some(doi)
+}
+
+/// 按 raw_id 或 DOI 相同把各数据中心的数据集分组，只保留跨数据中心的组
+///
+/// 两个数据集只要 raw_id 或 DOI 之一相同即属于同一组，组内可通过不同的键传递相连。
+pub fn find_duplicate_groups(datasets: &[(String, Vec<Dataset>)]) -> Vec<DuplicateGroup> {
+    let mut members = Vec::new();
+    let mut keys: Vec<Vec<String>> = Vec::new();
+    for (center_name, center_datasets) in datasets {
+        for dataset in center_datasets {
+            if dataset.raw_id.is_empty() {
+                continue;
+            }
+            let mut member_keys = vec![format!("raw_id:{}", dataset.raw_id)];
+            member_keys.extend(dataset.extract_dois().into_iter().map(|doi| format!("doi:{}", doi)));
+            members.push(DuplicateMember {
+                center_name: center_name.clone(),
+                raw_id: dataset.raw_id.clone(),
+                url: dataset.extract_url(),
+            });
+            keys.push(member_keys);
+        }
+    }
+
+    // 并查集：共用任意一个键的成员合并到同一组
+    let mut parent: Vec<usize> = (0..members.len()).collect();
+    let mut owner: HashMap<&str, usize> = HashMap::new();
+    for (i, member_keys) in keys.iter().enumerate() {
+        for key in member_keys {
+            match owner.get(key.as_str()) {
+                Some(&j) => union(&mut parent, i, j),
+                None => {
+                    owner.insert(key, i);
+                }
+            }
+        }
+    }
+
+    let mut grouped: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..members.len() {
+        let root = find(&mut parent, i);
+        grouped.entry(root).or_default().push(i);
+    }
+
+    let detected_at = Utc::now();
+    let mut groups: Vec<DuplicateGroup> = grouped
+        .into_values()
+        .filter(|indices| {
+            indices.iter().map(|&i| &members[i].center_name).collect::<BTreeSet<_>>().len() > 1
+        })
+        .map(|indices| {
+            // 只记录组内被多个成员共用的键
+            let mut key_counts: HashMap<&str, usize> = HashMap::new();
+            for &i in &indices {
+                for key in &keys[i] {
+                    *key_counts.entry(key).or_default() += 1;
+                }
+            }
+            let mut shared_keys: Vec<String> = key_counts.into_iter()
+                .filter(|(_, count)| *count > 1)
+                .map(|(key, _)| key.to_string())
+                .collect();
+            shared_keys.sort();
+            let mut group_members: Vec<DuplicateMember> = indices.iter().map(|&i| members[i].clone()).collect();
+            group_members.sort_by(|a, b| (&a.center_name, &a.raw_id).cmp(&(&b.center_name, &b.raw_id)));
+            DuplicateGroup {
+                keys: shared_keys,
+                members: group_members,
+                detected_at,
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| a.members[0].raw_id.cmp(&b.members[0].raw_id));
+    groups
+}
+
+fn find(parent: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    let mut node = i;
+    while parent[node] != root {
+        let next = parent[node];
+        parent[node] = root;
+        node = next;
+    }
+    root
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    if a != b {
+        parent[a.max(b)] = a.min(b);
+    }
+}
+
+/// 每对数据中心之间重复的数据集组数
+pub fn duplicate_pair_counts(groups: &[DuplicateGroup]) -> Vec<DuplicatePairCount> {
+    let mut counts: HashMap<(String, String), usize> = HashMap::new();
+    for group in groups {
+        let centers: BTreeSet<&str> = group.members.iter().map(|m| m.center_name.as_str()).collect();
+        let centers: Vec<&str> = centers.into_iter().collect();
+        for (i, a) in centers.iter().enumerate() {
+            for b in &centers[i + 1..] {
+                *counts.entry((a.to_string(), b.to_string())).or_default() += 1;
+            }
+        }
+    }
+    let mut pairs: Vec<DuplicatePairCount> = counts.into_iter()
+        .map(|((center_a, center_b), groups)| DuplicatePairCount { center_a, center_b, groups })
+        .collect();
+    pairs.sort_by(|a, b| b.groups.cmp(&a.groups).then_with(|| (&a.center_a, &a.center_b).cmp(&(&b.center_a, &b.center_b))));
+    pairs
+}
+
+/// 扫描所有启用的数据中心，重新计算重复组并写入 dataset_duplicates
+pub async fn reconcile_duplicates(config: &Config, db: &MongoDB) -> Result<Vec<DuplicateGroup>> {
+    let mut datasets = Vec::new();
+    for center in config.centers.iter().filter(|c| c.enabled) {
+        datasets.push((center.name.clone(), db.get_datasets(&config.collection_name(&center.name)).await?));
+    }
+    let groups = find_duplicate_groups(&datasets);
+    db.replace_duplicate_groups(&groups).await?;
+    info!("跨数据中心重复的数据集共 {} 组", groups.len());
+    Ok(groups)
+}
+
+/// 每个重复组的代表成员（排序后的第一个），键为 (数据中心, raw_id)
+pub fn primary_members(groups: &[DuplicateGroup]) -> HashMap<(String, String), (String, String)> {
+    let mut primaries = HashMap::new();
+    for group in groups {
+        let Some(primary) = group.members.first() else {
+            continue;
+        };
+        let primary_key = (primary.center_name.clone(), primary.raw_id.clone());
+        for member in &group.members[1..] {
+            primaries.insert((member.center_name.clone(), member.raw_id.clone()), primary_key.clone());
+        }
+    }
+    primaries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dataset(raw_id: &str, identifier: serde_json::Value) -> Dataset {
+        serde_json::from_value(serde_json::json!({
+            "@id": raw_id,
+            "schema:url": format!("https://example.org/{}", raw_id),
+            "schema:identifier": identifier,
+        })).unwrap()
+    }
+
+    fn center(name: &str, datasets: Vec<Dataset>) -> (String, Vec<Dataset>) {
+        (name.to_string(), datasets)
+    }
+
+    #[test]
+    fn normalize_doi_strips_prefixes() {
+        assert_eq!(normalize_doi(" https://doi.org/10.1234/ABC ").as_deref(), Some("10.1234/abc"));
+        assert_eq!(normalize_doi("http://dx.doi.org/10.1/x").as_deref(), Some("10.1/x"));
+        assert_eq!(normalize_doi("DOI: 10.5/Y").as_deref(), Some("10.5/y"));
+        assert_eq!(normalize_doi("10.5/y").as_deref(), Some("10.5/y"));
+        assert_eq!(normalize_doi("https://example.org/10.1/x"), None);
+        assert_eq!(normalize_doi("cstr:123"), None);
+    }
+
+    #[test]
+    fn groups_join_across_centers_by_raw_id_or_doi() {
+        let groups = find_duplicate_groups(&[
+            center("A", vec![
+                dataset("a1", serde_json::json!("https://doi.org/10.1/one")),
+                dataset("shared", serde_json::Value::Null),
+                // 同一数据中心内的重复不算
+                dataset("a2", serde_json::json!("doi:10.1/local")),
+                dataset("", serde_json::json!("doi:10.1/one")),
+            ]),
+            center("B", vec![
+                dataset("b1", serde_json::json!([{ "@value": "10.1/ONE" }, "doi:10.1/two"])),
+                dataset("shared", serde_json::Value::Null),
+                dataset("b2", serde_json::json!("doi:10.1/local-b")),
+            ]),
+            center("C", vec![dataset("c1", serde_json::json!({ "schema:value": "doi:10.1/two" }))]),
+            center("D", vec![dataset("a2", serde_json::json!("doi:10.1/local"))]),
+        ]);
+        let summary: Vec<(String, String)> = groups.iter()
+            .map(|g| (g.keys.join(" "), g.members.iter().map(|m| format!("{}/{}", m.center_name, m.raw_id)).collect::<Vec<_>>().join(" ")))
+            .collect();
+        let expected = [
+            // a1 与 b1 共用 DOI one，b1 与 c1 共用 DOI two，传递相连
+            ("doi:10.1/one doi:10.1/two", "A/a1 B/b1 C/c1"),
+            ("doi:10.1/local raw_id:a2", "A/a2 D/a2"),
+            ("raw_id:shared", "A/shared B/shared"),
+        ];
+        assert_eq!(summary, expected.map(|(keys, members)| (keys.to_string(), members.to_string())));
+        let pair = |c: &str, id: &str| (c.to_string(), id.to_string());
+
+        let pairs: Vec<(String, String, usize)> = duplicate_pair_counts(&groups).into_iter()
+            .map(|p| (p.center_a, p.center_b, p.groups))
+            .collect();
+        assert_eq!(pairs, vec![
+            ("A".to_string(), "B".to_string(), 2),
+            ("A".to_string(), "C".to_string(), 1),
+            ("A".to_string(), "D".to_string(), 1),
+            ("B".to_string(), "C".to_string(), 1),
+        ]);
+
+        let primaries = primary_members(&groups);
+        assert_eq!(primaries.get(&pair("C", "c1")), Some(&pair("A", "a1")));
+        assert_eq!(primaries.get(&pair("B", "shared")), Some(&pair("A", "shared")));
+        assert!(!primaries.contains_key(&pair("A", "a1")));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::config::EmailConfig;
+use crate::models::MonitorSummary;
+use std::fmt::Write;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// 邮件中列出的新失败URL上限
+const MAX_LISTED_FAILURES: usize = 50;
+
+pub fn render_subject(summary: &MonitorSummary) -> String {
+    format!(
+        "[数据集监测] {} 成功 {}/{}，新失败 {}",
+        summary.finished_at.format("%Y-%m-%d"),
+        summary.success,
+        summary.total,
+        summary.new_failure_count
+    )
+}
+
+/// 纯文本正文
+pub fn render_text(summary: &MonitorSummary, dashboard_url: Option<&str>) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "运行 {} ({} - {})", summary.run_id,
+                     summary.started_at.format("%Y-%m-%d %H:%M:%S"),
+                     summary.finished_at.format("%Y-%m-%d %H:%M:%S"));
+    let _ = writeln!(out, "总计: 成功 {}/{}，本地网络问题 {}，远程问题 {}",
+                     summary.success, summary.total, summary.local_issues, summary.remote_issues);
+    let _ = writeln!(out);
+    let _ = writeln!(out, "{:<40} {:>8} {:>8} {:>8}", "数据中心", "总数", "成功", "成功率");
+    for center in &summary.centers {
+        let _ = writeln!(out, "{:<40} {:>8} {:>8} {:>7.1}%",
+                         center.center_name, center.total, center.success, center.success_rate);
+    }
+
+    if summary.new_failure_count > 0 {
+        let _ = writeln!(out);
+        let _ = writeln!(out, "新失败的URL ({}):", summary.new_failure_count);
+        for failure in summary.new_failures.iter().take(MAX_LISTED_FAILURES) {
+            let _ = writeln!(out, "- [{}] {} {} ({})",
+                             failure.center_name,
+                             failure.name.as_deref().unwrap_or("-"),
+                             failure.url,
+                             failure.error_category.as_deref().unwrap_or("-"));
+        }
+        if summary.new_failure_count > MAX_LISTED_FAILURES {
+            let _ = writeln!(out, "... 另有 {} 条未列出", summary.new_failure_count - MAX_LISTED_FAILURES);
+        }
+    }
+
+    if let Some(url) = dashboard_url {
+        let _ = writeln!(out);
+        let _ = writeln!(out, "监测看板: {}", url);
+    }
+    out
+}
+
+/// HTML 正文（简单表格）
+pub fn render_html(summary: &MonitorSummary, dashboard_url: Option<&str>) -> String {
+    let mut out = String::new();
+    out.push_str("<html><body style=\"font-family: sans-serif\">");
+    let _ = write!(out, "<p>运行 {} ({} - {})</p>", escape_html(&summary.run_id),
+                   summary.started_at.format("%Y-%m-%d %H:%M:%S"),
+                   summary.finished_at.format("%Y-%m-%d %H:%M:%S"));
+    let _ = write!(out, "<p>总计: 成功 {}/{}，本地网络问题 {}，远程问题 {}</p>",
+                   summary.success, summary.total, summary.local_issues, summary.remote_issues);
+
+    out.push_str("<table border=\"1\" cellpadding=\"4\" cellspacing=\"0\">");
+    out.push_str("<tr><th>数据中心</th><th>总数</th><th>成功</th><th>成功率</th></tr>");
+    for center in &summary.centers {
+        let _ = write!(out, "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.1}%</td></tr>",
+                       escape_html(&center.center_name), center.total, center.success, center.success_rate);
+    }
+    out.push_str("</table>");
+
+    if summary.new_failure_count > 0 {
+        let _ = write!(out, "<h3>新失败的URL ({})</h3><ul>", summary.new_failure_count);
+        for failure in summary.new_failures.iter().take(MAX_LISTED_FAILURES) {
+            let _ = write!(out, "<li>[{}] {} <a href=\"{}\">{}</a> ({})</li>",
+                           escape_html(&failure.center_name),
+                           escape_html(failure.name.as_deref().unwrap_or("-")),
+                           escape_html(&failure.url),
+                           escape_html(&failure.url),
+                           escape_html(failure.error_category.as_deref().unwrap_or("-")));
+        }
+        out.push_str("</ul>");
+        if summary.new_failure_count > MAX_LISTED_FAILURES {
+            let _ = write!(out, "<p>... 另有 {} 条未列出</p>", summary.new_failure_count - MAX_LISTED_FAILURES);
+        }
+    }
+
+    if let Some(url) = dashboard_url {
+        let _ = write!(out, "<p><a href=\"{}\">监测看板</a></p>", escape_html(url));
+    }
+    out.push_str("</body></html>");
+    out
+}
+
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// 监测报告邮件发送，发送失败重试一次，始终不影响监测任务本身
+pub struct EmailReporter {
+    config: Option<EmailConfig>,
+}
+
+impl EmailReporter {
+    pub fn new(config: Option<EmailConfig>) -> Self {
+        if config.is_some() && cfg!(not(feature = "email")) {
+            warn!("已配置 email，但编译时未启用 email feature，不会发送邮件报告");
+            return Self { config: None };
+        }
+        Self { config }
+    }
+
+    /// `link_reports` 为本次运行写入的链接报告，配置了 attach_link_reports 时作为附件发送
+    pub async fn send_report(&self, summary: &MonitorSummary, link_reports: &[PathBuf]) {
+        let Some(config) = &self.config else {
+            return;
+        };
+        let dashboard_url = config.dashboard_url.as_deref();
+        let subject = render_subject(summary);
+        let text = render_text(summary, dashboard_url);
+        let html = render_html(summary, dashboard_url);
+        let attachments = if config.attach_link_reports { link_reports } else { &[] };
+        for attempt in 1..=2 {
+            match send(config, &subject, &text, &html, attachments).await {
+                Ok(()) => {
+                    info!("监测报告邮件已发送至 {} 个收件人", config.recipients.len());
+                    return;
+                }
+                Err(e) => warn!("第 {} 次发送监测报告邮件失败: {:#}", attempt, e),
+            }
+        }
+    }
+
+    /// 发送测试邮件，未配置 email 时返回 None
+    pub async fn send_test(&self) -> Option<anyhow::Result<()>> {
+        let config = self.config.as_ref()?;
+        let text = "这是一封测试邮件，用于验证监测报告邮件配置，请忽略。";
+        Some(send(config, "[数据集监测]【测试】邮件配置测试", text, &format!("<p>{}</p>", text), &[]).await)
+    }
+}
+
+#[cfg(feature = "email")]
+async fn send(config: &EmailConfig, subject: &str, text: &str, html: &str, attachments: &[PathBuf]) -> anyhow::Result<()> {
+    use crate::config::EmailTls;
+    use lettre::message::header::ContentType;
+    use lettre::message::{Attachment, MultiPart};
+    use lettre::transport::smtp::authentication::Credentials;
+    use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+    let mut message = Message::builder()
+        .from(config.from.parse()?)
+        .subject(subject);
+    for recipient in &config.recipients {
+        message = message.to(recipient.parse()?);
+    }
+    let body = MultiPart::alternative_plain_html(text.to_string(), html.to_string());
+    let message = if attachments.is_empty() {
+        message.multipart(body)?
+    } else {
+        let mut mixed = MultiPart::mixed().multipart(body);
+        for path in attachments {
+            let content = std::fs::read(path)?;
+            let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("attachment").to_string();
+            mixed = mixed.singlepart(Attachment::new(file_name).body(content, ContentType::parse("text/tab-separated-values; charset=utf-8")?));
+        }
+        message.multipart(mixed)?
+    };
+
+    let mut builder = match config.tls {
+        EmailTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.smtp_host),
+        EmailTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)?,
+        EmailTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.smtp_host)?,
+    }
+    .port(config.smtp_port);
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+    builder.build().send(message).await?;
+    Ok(())
+}
+
+#[cfg(not(feature = "email"))]
+async fn send(_config: &EmailConfig, _subject: &str, _text: &str, _html: &str, _attachments: &[PathBuf]) -> anyhow::Result<()> {
+    anyhow::bail!("未启用 email feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary() -> MonitorSummary {
+        serde_json::from_value(serde_json::json!({
+            "run_id": "run-1",
+            "started_at": "2026-01-01T00:00:00Z",
+            "finished_at": "2026-01-01T01:30:00Z",
+            "total": 3,
+            "success": 2,
+            "local_issues": 0,
+            "remote_issues": 1,
+            "centers": [{
+                "center_name": "A & B",
+                "total": 3,
+                "success": 2,
+                "success_rate": 66.666,
+                "local_issues": 0,
+                "remote_issues": 1,
+                "error_categories": [{ "category": "HTTP_5XX", "count": 1 }],
+                "sample_failures": [],
+            }],
+            "new_failure_count": 1,
+            "new_failures": [{
+                "id": "d1",
+                "url": "https://example.org/data?a=1&b=<2>",
+                "name": null,
+                "center_name": "A & B",
+                "status_code": 503,
+                "error_category": "HTTP_5XX",
+                "error_msg": null,
+                "is_likely_local_issue": false,
+            }],
+        })).unwrap()
+    }
+
+    #[test]
+    fn subject_snapshot() {
+        assert_eq!(render_subject(&summary()), "[数据集监测] 2026-01-01 成功 2/3，新失败 1");
+    }
+
+    #[test]
+    fn text_body_snapshot() {
+        let expected = format!(
+            "运行 run-1 (2026-01-01 00:00:00 - 2026-01-01 01:30:00)\n\
+             总计: 成功 2/3，本地网络问题 0，远程问题 1\n\
+             \n\
+             {:<40} {:>8} {:>8} {:>8}\n\
+             {:<40} {:>8} {:>8} {:>7.1}%\n\
+             \n\
+             新失败的URL (1):\n\
+             - [A & B] - https://example.org/data?a=1&b=<2> (HTTP_5XX)\n\
+             \n\
+             监测看板: https://monitor.example.org/\n",
+            "数据中心", "总数", "成功", "成功率", "A & B", 3, 2, 66.666,
+        );
+        assert_eq!(render_text(&summary(), Some("https://monitor.example.org/")), expected);
+    }
+
+    #[test]
+    fn html_body_snapshot() {
+        let expected = concat!(
+            "<html><body style=\"font-family: sans-serif\">",
+            "<p>运行 run-1 (2026-01-01 00:00:00 - 2026-01-01 01:30:00)</p>",
+            "<p>总计: 成功 2/3，本地网络问题 0，远程问题 1</p>",
+            "<table border=\"1\" cellpadding=\"4\" cellspacing=\"0\">",
+            "<tr><th>数据中心</th><th>总数</th><th>成功</th><th>成功率</th></tr>",
+            "<tr><td>A &amp; B</td><td>3</td><td>2</td><td>66.7%</td></tr>",
+            "</table>",
+            "<h3>新失败的URL (1)</h3><ul>",
+            "<li>[A &amp; B] - <a href=\"https://example.org/data?a=1&amp;b=&lt;2&gt;\">",
+            "https://example.org/data?a=1&amp;b=&lt;2&gt;</a> (HTTP_5XX)</li>",
+            "</ul>",
+            "<p><a href=\"https://monitor.example.org/?a=&quot;\">监测看板</a></p>",
+            "</body></html>",
+        );
+        assert_eq!(render_html(&summary(), Some("https://monitor.example.org/?a=\"")), expected);
+    }
+
+    #[test]
+    fn long_failure_lists_are_capped() {
+        let mut summary = summary();
+        summary.new_failures = vec![summary.new_failures[0].clone(); MAX_LISTED_FAILURES];
+        summary.new_failure_count = MAX_LISTED_FAILURES + 7;
+        let text = render_text(&summary, None);
+        assert_eq!(text.matches("\n- [A & B]").count(), MAX_LISTED_FAILURES);
+        assert!(text.ends_with("... 另有 7 条未列出\n"));
+        assert!(!text.contains("监测看板"));
+        assert!(render_html(&summary, None).ends_with("<p>... 另有 7 条未列出</p></body></html>"));
+    }
+}
@@ -1,22 +1,188 @@
-use crate::config::Config;
+use crate::backlog;
+use crate::config::{Center, Config, ListMethod, PaginationConfig, TokenExpires};
+use crate::db::duckdb::DuckDB;
 use crate::db::mongodb::MongoDB;
-use crate::models::{AuthResponse, Dataset};
+use crate::http::{ClientFactory, ClientProfile};
+use crate::raw_store::{self, RawStore};
+use crate::scheduler::instance_id;
+use crate::models::{AuthResponse, CenterFetchReport, Dataset, FetchAudit, FetchMetrics, FetchPlan, FetchPlanFailure, FetchReport};
 use anyhow::{Context, Result};
 use chrono::Utc;
 use dashmap::DashMap;
+use futures::{stream, FutureExt, StreamExt};
 use reqwest::header::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
-use tracing::{error, info};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+/// token 到期前多久主动刷新
+const TOKEN_REFRESH_MARGIN_SECS: i64 = 300;
+/// token 有效期的下限与上限，超出时截断
+const MIN_TOKEN_VALIDITY_SECS: i64 = 600;
+const MAX_TOKEN_VALIDITY_SECS: i64 = 24 * 3600;
+/// ticket.expires 缺失或为 0 时使用的有效期
+const DEFAULT_TOKEN_VALIDITY_SECS: i64 = 3600;
+/// 认证被拒绝后多久内不再请求认证接口，直接返回上次的错误
+const AUTH_FAILURE_CACHE_SECS: u64 = 60;
+/// 数据获取必需的服务
+pub const SERVICE_DATASET_LIST: &str = "DATASET_LIST";
+pub const SERVICE_DATASET_DETAILS: &str = "GET_DATASET_DETAILS";
+const REQUIRED_SERVICES: [&str; 2] = [SERVICE_DATASET_LIST, SERVICE_DATASET_DETAILS];
+/// 数据集详情每批写库的数量
+const DETAIL_BATCH_SIZE: usize = 50;
+/// 认领待处理ID时区分同一进程中的各次处理
+static CLAIM_SEQ: AtomicU64 = AtomicU64::new(0);
+/// 同一进程中保存 token 缓存文件的锁，以及区分各次保存的临时文件名
+static TOKEN_FILE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+static TOKEN_FILE_SEQ: AtomicU64 = AtomicU64::new(0);
 
 pub struct DataFetcher {
     config: Arc<Config>,
     client: reqwest::Client,
     tokens: Arc<DashMap<String, TokenInfo>>,
+    /// 每个数据中心一把锁，同一时间只有一个任务请求认证接口，其他任务等待后复用新 token
+    token_locks: DashMap<String, Arc<tokio::sync::Mutex<()>>>,
+    /// 最近一次认证被拒绝的时间和错误，AUTH_FAILURE_CACHE_SECS 内直接返回该错误
+    auth_failures: DashMap<String, (Instant, String)>,
+    /// 各数据中心下一次允许请求详情接口的时间，配置了 detail_requests_per_sec 时使用
+    detail_slots: DashMap<String, Arc<tokio::sync::Mutex<Instant>>>,
+    /// 开启 persist_tokens 时 token 的保存位置
+    token_file: Option<PathBuf>,
+    /// 解析失败的原始响应
+    raw_store: RawStore,
+    /// 取消后不再发起新的请求
+    cancel: CancellationToken,
+    /// 各数据中心本次运行的请求计数，运行结束时写入 DuckDB 的 fetch_metrics
+    counters: DashMap<String, Arc<RequestCounters>>,
+}
+
+/// 单个数据中心一次运行的请求计数与累计耗时
+#[derive(Default)]
+struct RequestCounters {
+    list_requests: AtomicU64,
+    list_ms: AtomicU64,
+    detail_requests: AtomicU64,
+    detail_ms: AtomicU64,
+    bytes: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl RequestCounters {
+    fn record_list(&self, elapsed: Duration, failed: bool) {
+        self.list_requests.fetch_add(1, Ordering::Relaxed);
+        self.list_ms.fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+        if failed {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn record_detail(&self, elapsed: Duration, failed: bool) {
+        self.detail_requests.fetch_add(1, Ordering::Relaxed);
+        self.detail_ms.fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+        if failed {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn to_metrics(&self, center_name: &str, started_at: chrono::DateTime<Utc>) -> FetchMetrics {
+        let detail_requests = self.detail_requests.load(Ordering::Relaxed);
+        FetchMetrics {
+            center_name: center_name.to_string(),
+            started_at,
+            finished_at: Utc::now(),
+            list_requests: self.list_requests.load(Ordering::Relaxed),
+            list_duration_ms: self.list_ms.load(Ordering::Relaxed),
+            detail_requests,
+            avg_detail_ms: (detail_requests > 0)
+                .then(|| self.detail_ms.load(Ordering::Relaxed) as f64 / detail_requests as f64),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 数据集详情解析失败，原始响应已另存，错误信息中只保留文件位置
+#[derive(Debug)]
+pub struct ParseFailure {
+    pub reason: String,
+    pub raw_path: Option<PathBuf>,
+    /// 解析前从响应中清理掉的字符数（BOM、无效UTF-8序列、控制字符）
+    pub sanitized: usize,
+}
+
+impl std::fmt::Display for ParseFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.raw_path {
+            Some(path) => write!(f, "{}，原始响应: {}", self.reason, path.display()),
+            None => write!(f, "{}，原始响应未保存", self.reason),
+        }
+    }
+}
+
+impl std::error::Error for ParseFailure {}
+
+/// 认证响应的 serviceList 缺少必需的服务，或服务URL无效
+#[derive(Debug, thiserror::Error)]
+#[error("{center} 服务列表不可用: 缺少 {missing:?}，URL无效 {invalid:?}，已有服务 {present:?}")]
+pub struct MissingService {
+    pub center: String,
+    pub missing: Vec<String>,
+    /// (服务名, URL)
+    pub invalid: Vec<(String, String)>,
+    pub present: Vec<String>,
+}
+
+/// 检查必需的服务都存在且URL为 http(s)
+fn validate_services(center: &str, services: &[ServiceInfo]) -> Result<(), MissingService> {
+    let mut missing = Vec::new();
+    let mut invalid = Vec::new();
+    for required in REQUIRED_SERVICES {
+        match services.iter().find(|s| s.name == required) {
+            None => missing.push(required.to_string()),
+            Some(service) => {
+                let valid = reqwest::Url::parse(&service.url)
+                    .is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+                if !valid {
+                    invalid.push((service.name.clone(), service.url.clone()));
+                }
+            }
+        }
+    }
+    if missing.is_empty() && invalid.is_empty() {
+        return Ok(());
+    }
+    Err(MissingService {
+        center: center.to_string(),
+        missing,
+        invalid,
+        present: services.iter().map(|s| s.name.clone()).collect(),
+    })
+}
+
+/// 认证接口暂时不可用（5xx、429），不计入认证失败的缓存
+#[derive(Debug, thiserror::Error)]
+#[error("{center} 认证接口暂时不可用，HTTP状态码: {status}，响应内容: {body}")]
+pub struct AuthUnavailable {
+    pub center: String,
+    pub status: u16,
+    pub body: String,
 }
 
+/// 响应体超过 max_response_bytes，已中止读取
+#[derive(Debug, thiserror::Error)]
+#[error("响应体超过大小上限 {limit} 字节")]
+pub struct ResponseTooLarge {
+    pub limit: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 struct TokenInfo {
     token: String,
     version: String,
@@ -24,115 +190,330 @@ struct TokenInfo {
     expires_at: chrono::DateTime<Utc>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 struct ServiceInfo {
     name: String,
     url: String,
 }
 
+impl TokenInfo {
+    fn is_fresh(&self) -> bool {
+        self.expires_at - chrono::Duration::seconds(TOKEN_REFRESH_MARGIN_SECS) > Utc::now()
+    }
+
+    /// 按标准服务名查找URL，获取 token 时已校验过必需的服务
+    fn service_url(&self, service: &str) -> Result<String> {
+        self.services.iter()
+            .find(|s| s.name == service)
+            .map(|s| s.url.clone())
+            .with_context(|| format!("未找到 {} 服务", service))
+    }
+}
+
 impl DataFetcher {
     pub fn new(config: Arc<Config>) -> Self {
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(config.monitor.http_timeout_secs))
-            .danger_accept_invalid_certs(true)
-            .danger_accept_invalid_hostnames(true)
-            .redirect(reqwest::redirect::Policy::limited(10))
-            .build()
-            .expect("failed to build http client");
+        let client = Self::build_client(&config);
+        Self::with_client(config, client)
+    }
+
+    /// 默认的数据中心接口客户端，按 monitor.max_redirects 跟随重定向
+    pub fn build_client(config: &Config) -> reqwest::Client {
+        ClientFactory::new(config).build(ClientProfile::CenterApi)
+    }
+
+    /// 使用传入的客户端请求数据中心接口，用于共用连接池或在测试中指向桩服务；超时和重定向由该客户端决定
+    pub fn with_client(config: Arc<Config>, client: reqwest::Client) -> Self {
+        let token_file = config.monitor.persist_tokens
+            .then(|| Path::new(&config.monitor.state_dir).join("tokens.json"));
+        let mut tokens = DashMap::new();
+        if let Some(path) = &token_file {
+            match load_tokens(path) {
+                Ok(loaded) => {
+                    info!("从 {} 加载 {} 个数据中心的 token", path.display(), loaded.len());
+                    tokens.extend(loaded);
+                }
+                Err(e) => warn!("加载 token 缓存 {} 失败: {}", path.display(), e),
+            }
+        }
+        let raw_store = RawStore::from_config(&config.monitor);
         Self {
             config,
             client,
-            tokens: Arc::new(DashMap::new()),
+            tokens: Arc::new(tokens),
+            token_locks: DashMap::new(),
+            auth_failures: DashMap::new(),
+            detail_slots: DashMap::new(),
+            token_file,
+            raw_store,
+            cancel: CancellationToken::new(),
+            counters: DashMap::new(),
         }
     }
 
-    pub async fn fetch_all_center(&self, db: &MongoDB) -> Result<()> {
-        for center in &self.config.centers {
-            if !center.enabled || (center.name != "中国科学院干细胞与再生医学科学数据中心") {
-                info!("跳过禁用的 {}", center.name);
-                continue;
-            }
-            info!("开始获取数据中心 {} 的数据", center.name);
-            match self.fetch_center_data(&center.name, &center.url, &center.secret_key, &db).await {
-                Ok(count) => info!("中心 {} 获取数据 {} 条", center.name, count),
-                Err(e) => error!("中心 {} 获取失败: {:#?}\nBacktrace: {:?}", center.name, e, e.backtrace())
+    fn counters(&self, name: &str) -> Arc<RequestCounters> {
+        self.counters.entry(name.to_string()).or_default().clone()
+    }
+
+    pub fn with_cancel_token(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// 保存当前所有 token，先写临时文件再改名。
+    /// 各数据中心和详情工作任务的 DataFetcher 共用同一个文件，保存时与文件中已有的 token 合并，
+    /// 同一数据中心保留过期时间较晚的一个，读改写在进程内的锁中进行，临时文件名各不相同
+    fn save_tokens(&self) -> Result<()> {
+        let Some(path) = &self.token_file else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let _lock = TOKEN_FILE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Utc::now();
+        let mut tokens = load_tokens(path).unwrap_or_else(|e| {
+            warn!("读取 token 缓存 {} 失败，将覆盖: {}", path.display(), e);
+            HashMap::new()
+        });
+        tokens.retain(|_, token_info| token_info.expires_at > now);
+        for entry in self.tokens.iter() {
+            if tokens.get(entry.key()).is_none_or(|saved| saved.expires_at < entry.value().expires_at) {
+                tokens.insert(entry.key().clone(), entry.value().clone());
             }
         }
+        let tmp = path.with_extension(format!("json.{}-{}.tmp", std::process::id(), TOKEN_FILE_SEQ.fetch_add(1, Ordering::Relaxed)));
+        std::fs::write(&tmp, serde_json::to_string_pretty(&tokens)?)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::rename(&tmp, path)?;
         Ok(())
     }
 
-    async fn fetch_center_data(&self, name: &str, url: &str, secret_key: &str, db: &MongoDB) -> Result<usize> {
-        let discovered = self.discover_new_ids(name, url, secret_key, db).await?;
-        info!("数据中心 {} 本次发现新数据 {} 条", name, discovered);
-
-        // 获取库中 状态为还未处理的数据集(刚发现+历史遗留的)
-        let processed = self.process_pending_datasets(name, url, secret_key, db).await?;
-        info!("数据中心 {} 本次处理数据 {} 条", name, processed);
-
-        Ok(processed)
+    /// 接口返回 401 时丢弃缓存的 token，下次调用会重新认证；
+    /// 缓存的已经不是 `stale` 时（其他任务已刷新）保留，避免并发请求反复刷新
+    fn invalidate_token(&self, name: &str, stale: &TokenInfo) {
+        self.tokens.remove_if(name, |_, cached| cached.token == stale.token);
     }
 
-    async fn discover_new_ids(&self, name: &str, url: &str, secret_key: &str, db: &MongoDB) -> Result<usize> {
-        let token_info = self.get_or_refresh_token(name, url, secret_key).await?;
-        let dataset_list_url = token_info.services.iter()
-            .find(|s| s.name == "DATASET_LIST")
-            .context("未找到数据集服务")?
-            .url.clone();
-
+    fn auth_headers(token_info: &TokenInfo) -> Result<HeaderMap> {
         let mut headers = HeaderMap::new();
         headers.insert("token", HeaderValue::from_str(&token_info.token)?);
         headers.insert("version", HeaderValue::from_str(&token_info.version)?);
+        Ok(headers)
+    }
 
-        let method = match name {
-            n if n.contains("海洋科学") || n.contains("微生物") => reqwest::Method::POST,
-            _ => reqwest::Method::GET,
+    /// 并发获取所有启用的数据中心（最多 fetch_center_concurrency 个同时进行），
+    /// 每个中心的错误和 panic 记录在各自的结果中，不影响其他中心
+    pub async fn fetch_all_center(&self, db: &MongoDB) -> FetchReport {
+        for center in self.config.centers.iter().filter(|c| !c.enabled) {
+            info!("跳过禁用的 {}", center.name);
+        }
+        let per_center = stream::iter(self.config.centers.iter().filter(|c| c.enabled))
+            .map(|center| async move {
+                if self.cancel.is_cancelled() {
+                    info!("数据获取已取消，跳过数据中心 {}", center.name);
+                    return None;
+                }
+                let report = AssertUnwindSafe(self.fetch_center(center, db))
+                    .catch_unwind()
+                    .await
+                    .unwrap_or_else(|panic| {
+                        let message = panic_message(panic.as_ref());
+                        error!("数据中心 {} 数据获取 panic: {}", center.name, message);
+                        CenterFetchReport {
+                            name: center.name.clone(),
+                            list_error: Some(format!("panic: {}", message)),
+                            ..Default::default()
+                        }
+                    });
+                Some(report)
+            })
+            .buffer_unordered(self.config.monitor.fetch_center_concurrency.max(1))
+            .filter_map(std::future::ready)
+            .collect()
+            .await;
+        FetchReport { per_center }
+    }
+
+    /// 试运行所有启用的数据中心，不写 MongoDB
+    pub async fn plan_all_center(&self, db: &MongoDB) -> Vec<FetchPlan> {
+        let mut plans = Vec::new();
+        for center in self.config.centers.iter().filter(|c| c.enabled) {
+            match self.plan_center(center, db).await {
+                Ok(plan) => plans.push(plan),
+                Err(e) => error!("中心 {} 试运行失败: {:#}", center.name, e),
+            }
+        }
+        plans
+    }
+
+    /// 试运行单个数据中心：照常请求列表和详情并解析，但跳过所有 MongoDB 写入
+    pub async fn plan_center(&self, center: &Center, db: &MongoDB) -> Result<FetchPlan> {
+        info!("开始试运行数据中心 {} 的数据获取", center.name);
+        let mut plan = FetchPlan {
+            center_name: center.name.clone(),
+            ..Default::default()
         };
+        let mut report = CenterFetchReport::default();
+        self.fetch_center_data(center, db, &mut report, Some(&mut plan)).await?;
+        info!("[试运行] 数据中心 {}: 列表 {} 条，新ID {} 个，解析成功 {} 个，失败 {} 个",
+              center.name, plan.listed, plan.new_ids.len(), plan.parsed, plan.failures.len());
+        Ok(plan)
+    }
 
-        // 请求数据集 ID 列表
-        let response = self.client.request(method, &dataset_list_url)
-            .headers(headers)
-            .send()
-            .await
-            .with_context(|| format!("{} 获取数据集列表失败", name))?;
-        // 检查是否意外重定向到登录页面或其他错误页面
-        let status = response.status();
-        let response_text = response.text().await
-            .with_context(|| format!("{} 读取响应内容失败", name))?;
+    /// 获取单个数据中心的数据，结果写入 fetch_audit
+    pub async fn fetch_center(&self, center: &Center, db: &MongoDB) -> CenterFetchReport {
+        info!("开始获取数据中心 {} 的数据", center.name);
+        let started_at = Utc::now();
+        let started = Instant::now();
+        if let Err(e) = self.raw_store.prune() {
+            warn!("清理过期的原始响应失败: {}", e);
+        }
+        let mut report = CenterFetchReport {
+            name: center.name.clone(),
+            ..Default::default()
+        };
+        self.counters.remove(&center.name);
+        if let Err(e) = self.fetch_center_data(center, db, &mut report, None).await {
+            error!("中心 {} 获取失败: {:#}", center.name, e);
+            report.list_error = Some(format!("{:#}", e));
+        }
+        report.duration_ms = started.elapsed().as_millis() as u64;
+        report.cancelled = self.cancel.is_cancelled();
+        if report.cancelled {
+            warn!("数据中心 {} 数据获取已取消，已处理 {} 条", center.name, report.processed);
+        }
 
-        // 检查常见的错误情况
-        if status == 401 || status == 403 {
-            anyhow::bail!("{} 认证失败，HTTP状态码: {}，可能token已过期", name, status);
+        match db.count_ids_by_status(&center.name).await {
+            Ok(backlog) => report.backlog = Some(backlog),
+            Err(e) => warn!("统计数据中心 {} 的ID积压失败: {}", center.name, e),
         }
 
-        if status.is_redirection() {
-            anyhow::bail!("{} 意外重定向，HTTP状态码: {}，响应内容: {}", name, status, response_text);
+        let audit = FetchAudit {
+            center_name: center.name.clone(),
+            started_at,
+            finished_at: Utc::now(),
+            success: report.is_success(),
+            outcome: Some(report.clone()),
+            error: report.list_error.clone(),
+        };
+        if let Err(e) = db.insert_fetch_audit(&audit).await {
+            warn!("{} 写入数据获取审计记录失败: {}", center.name, e);
+        }
+        if self.config.monitor.backlog_metrics_file.is_some()
+            && let Err(e) = backlog::refresh(&self.config, db).await
+        {
+            warn!("刷新ID积压指标失败: {:#}", e);
+        }
+        let metrics = self.counters(&center.name).to_metrics(&center.name, started_at);
+        if let Err(e) = self.write_metrics(&metrics).await {
+            warn!("{} 写入数据获取性能指标失败: {:#}", center.name, e);
         }
+        report
+    }
 
-        if status.is_client_error() || status.is_server_error() {
-            anyhow::bail!("{} 请求失败，HTTP状态码: {}，响应内容: {}", name, status, response_text);
+    /// DuckDB 文件可能被 data_monitor 进程占用，因此每次写入时才打开
+    async fn write_metrics(&self, metrics: &FetchMetrics) -> Result<()> {
+        let duckdb = DuckDB::new(&self.config.duckdb.path).await?;
+        duckdb.insert_fetch_metrics(metrics).await
+    }
+
+    /// 结果逐步记入 `report`，中途出错时已完成的部分仍保留；`plan` 不为 None 时为试运行，结果记入 plan 而不写库
+    async fn fetch_center_data(&self, center: &Center, db: &MongoDB, report: &mut CenterFetchReport, mut plan: Option<&mut FetchPlan>) -> Result<()> {
+        let name = center.name.as_str();
+        self.discover_new_ids(center, db, report, plan.as_deref_mut()).await?;
+        info!("数据中心 {} 列表共 {} 条，本次发现新数据 {} 条", name, report.listed, report.discovered);
+
+        // 配置了详情工作任务时由工作任务处理待处理的ID，试运行仍在这里处理
+        if plan.is_none() && self.config.monitor.detail_workers > 0 {
+            info!("数据中心 {} 的待处理ID由详情工作任务处理", name);
+            return Ok(());
+        }
+        // 获取库中 状态为还未处理的数据集(刚发现+历史遗留的)
+        self.process_pending_datasets(center, db, report, plan, None).await?;
+        info!("数据中心 {} 本次处理数据 {} 条，失败 {} 条，过期 {} 条", name, report.processed, report.detail_failures, report.expired);
+        Ok(())
+    }
+
+    /// 支持 updatedSince 的数据中心在上次成功获取后只请求有变化的ID，每 full_fetch_every 次增量获取后做一次完整获取；
+    /// 返回 None 表示本次完整获取
+    async fn updated_since(&self, center: &Center, db: &MongoDB) -> Result<Option<chrono::DateTime<Utc>>> {
+        if !center.supports_updated_since {
+            return Ok(None);
         }
+        let Some(last) = db.get_last_successful_fetch(&center.name, false).await? else {
+            return Ok(None);
+        };
+        let incremental_runs = db.count_incremental_since_full(&center.name, center.full_fetch_every).await?;
+        Ok(incremental_since(center, Some(&last), incremental_runs))
+    }
 
-        let all_dataset_ids: Vec<String> = serde_json::from_str::<Value>(&response_text)
-            .with_context(|| format!("{} 响应不是有效的JSON，内容: {}", name, response_text))?
-            .as_array()
-            .with_context(|| format!("{} 响应不是数组，内容: {}", name, response_text))?
-            .iter()
-            .filter_map(|v| v.get("id").and_then(|id| id.as_str()).map(String::from))
+    /// 结果记入 report 的 listed、discovered、incremental、requeued、missing_from_list
+    async fn discover_new_ids(&self, center: &Center, db: &MongoDB, report: &mut CenterFetchReport, plan: Option<&mut FetchPlan>) -> Result<()> {
+        let name = center.name.as_str();
+        let since = self.updated_since(center, db).await?;
+        let since_query: Vec<(&str, String)> = since.iter()
+            .map(|since| ("updatedSince", since.to_rfc3339()))
             .collect();
+        report.incremental = since.is_some();
+        if let Some(since) = since {
+            info!("{} 增量获取 {} 之后有变化的数据集", name, since.to_rfc3339());
+        }
+
+        // 配置了分页时从第一页开始带分页参数请求；未配置但返回分页结构时按默认方式翻页，每页大小以第一页为准
+        let first_query = match &center.pagination {
+            Some(p) => p.style.query(0, p.page_size),
+            None => Vec::new(),
+        };
+        let first = self.request_dataset_list(center, &[first_query, since_query.clone()].concat()).await?;
+        let DatasetList { ids: all_dataset_ids, complete } = collect_dataset_pages(
+            name, first, center.pagination.as_ref(), &self.cancel,
+            |query| {
+                let query = [query, since_query.clone()].concat();
+                async move { self.request_dataset_list(center, &query).await }
+            }).await?;
+        let listed = all_dataset_ids.len();
+        report.listed = listed;
 
         // DB 已有的 ID（不管是否 processed）
         let existing_ids: HashSet<String> = db.get_dataset_by_center(name).await?
             .into_iter()
             .collect();
 
-        // 过滤掉 DB 已有的，剩下的才是全新 ID
-        let new_ids: Vec<String> = all_dataset_ids
+        // 完整获取时统计库中有、列表中已没有的ID（数据中心可能已删除）
+        if since.is_none() && complete {
+            let listed_ids: HashSet<&String> = all_dataset_ids.iter().collect();
+            report.missing_from_list = existing_ids.iter().filter(|id| !listed_ids.contains(id)).count();
+            if report.missing_from_list > 0 {
+                warn!("{} 有 {} 个已知 ID 不在本次完整列表中", name, report.missing_from_list);
+            }
+        }
+
+        // 过滤掉 DB 已有的，剩下的才是全新 ID；增量获取时已有的ID有变化，需要重新获取详情
+        let (known_ids, new_ids): (Vec<String>, Vec<String>) = all_dataset_ids
             .into_iter()
-            .filter(|id| !existing_ids.contains(id))
-            .collect();
+            .partition(|id| existing_ids.contains(id));
+        report.discovered = new_ids.len();
+
+        if let Some(plan) = plan {
+            plan.listed = listed;
+            plan.new_ids = new_ids.clone();
+            info!("[试运行] {} 发现 {} 个新 ID，不写入", name, new_ids.len());
+            return Ok(());
+        }
+
+        if report.incremental && !known_ids.is_empty() {
+            report.requeued = db.requeue_ids(name, &known_ids).await
+                .with_context(|| format!("{} 重新排队有变化的数据集失败", name))? as usize;
+            info!("{} 有 {} 个已处理的数据集有变化，重新排队获取详情", name, report.requeued);
+        }
 
         if new_ids.is_empty() {
             info!("{} 没有新 ID", name);
-            return Ok(0);
+            return Ok(());
         }
 
         // 保存为未处理状态
@@ -140,73 +521,474 @@ impl DataFetcher {
             .with_context(|| format!("{} 保存数据集ID失败", name))?;
 
         info!("{} 发现并保存了 {} 个新 ID", name, new_ids.len());
-        Ok(new_ids.len())
+        Ok(())
     }
-    fn clean_json_string(input: &str) -> String {
-        input.chars().filter(|&c| {
-            // 允许的字符: 换行符、回车符、制表符以外的控制字符
-            c >= ' ' || c == '\n' || c == '\r' || c == '\t'
-        }).collect()
+    /// 请求一页数据集列表并记录耗时
+    async fn request_dataset_list(&self, center: &Center, query: &[(&str, String)]) -> Result<DatasetListPage> {
+        let started = Instant::now();
+        let result = self.send_dataset_list(center, query).await;
+        self.counters(&center.name).record_list(started.elapsed(), result.is_err());
+        result
     }
-    async fn process_pending_datasets(&self, name: &str, url: &str, secret_key: &str, db: &MongoDB) -> Result<usize> {
-        let token_info = self.get_or_refresh_token(name, url, secret_key).await?;
-        let details_url = token_info.services.iter()
-            .find(|s| s.name == "GET_DATASET_DETAILS")
-            .with_context(|| format!("{} 未找到GET_DATASET_DETAILS服务", name))?
-            .url.clone();
-        let mut headers = HeaderMap::new();
-        headers.insert("token", HeaderValue::from_str(&token_info.token)?);
-        headers.insert("version", HeaderValue::from_str(&token_info.version)?);
 
-        // 查找所有未处理的 ID
-        let pending_ids = db.get_unprocessed_ids(name).await?;
-        if pending_ids.is_empty() {
+    /// token 失效（401）时强制刷新后重试一次
+    async fn send_dataset_list(&self, center: &Center, query: &[(&str, String)]) -> Result<DatasetListPage> {
+        let name = center.name.as_str();
+        let mut attempt = 0;
+        let (status, response_text) = loop {
+            attempt += 1;
+            let token_info = self.get_or_refresh_token(name, &center.url, &center.secret_key).await?;
+            let dataset_list_url = token_info.service_url(SERVICE_DATASET_LIST)?;
+
+            let mut request = match center.list_method {
+                ListMethod::Get => self.client.get(&dataset_list_url),
+                ListMethod::Post => self.client.post(&dataset_list_url),
+            };
+            if let Some(body) = &center.list_body {
+                request = request.json(body);
+            }
+            let response = request
+                .headers(Self::auth_headers(&token_info)?)
+                .query(query)
+                .send()
+                .await
+                .with_context(|| format!("{} 获取数据集列表失败", name))?;
+            // 检查是否意外重定向到登录页面或其他错误页面
+            let status = response.status();
+            let response_text = self.read_body(name, response).await
+                .with_context(|| format!("{} 读取数据集列表响应失败", name))?;
+            if status == 401 && attempt == 1 && !self.cancel.is_cancelled() {
+                warn!("{} 获取数据集列表返回 401，刷新 token 后重试", name);
+                self.invalidate_token(name, &token_info);
+                continue;
+            }
+            break (status, response_text);
+        };
+
+        // 检查常见的错误情况
+        if status == 401 || status == 403 {
+            anyhow::bail!("{} 认证失败，HTTP状态码: {}，可能token已过期，响应内容: {}", name, status, response_text);
+        }
+
+        if status.is_redirection() {
+            anyhow::bail!("{} 意外重定向，HTTP状态码: {}，响应内容: {}", name, status, response_text);
+        }
+
+        if status.is_client_error() || status.is_server_error() {
+            anyhow::bail!("{} 请求失败，HTTP状态码: {}，响应内容: {}", name, status, response_text);
+        }
+
+        let value = serde_json::from_str::<Value>(&response_text)
+            .with_context(|| format!("{} 响应不是有效的JSON，内容: {}", name, response_text))?;
+        parse_dataset_list(&value)
+            .with_context(|| format!("{} 响应不是数组或分页结构，内容: {}", name, response_text))
+    }
+
+    /// 流式读取响应体，gzip/deflate 由 reqwest 解压，超过 max_response_bytes（按解压后计）时中止
+    async fn read_bytes(&self, name: &str, mut response: reqwest::Response) -> Result<Vec<u8>> {
+        let limit = self.config.monitor.max_response_bytes;
+        // 压缩的响应解压后 content_length 为 None，只能边读边检查
+        if response.content_length().is_some_and(|length| length as usize > limit) {
+            return Err(ResponseTooLarge { limit }.into());
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > limit {
+                return Err(ResponseTooLarge { limit }.into());
+            }
+            body.extend_from_slice(&chunk);
+        }
+        self.counters(name).bytes.fetch_add(body.len() as u64, Ordering::Relaxed);
+        Ok(body)
+    }
+
+    async fn read_body(&self, name: &str, response: reqwest::Response) -> Result<String> {
+        let body = self.read_bytes(name, response).await?;
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+
+    /// 处理结果记入 report 的 processed、detail_failures、expired；`limit` 限制本次处理的ID数。
+    /// 每轮认领最多 detail_worker_batch 个待处理的ID（改为 in_progress），处理后把未成功的放回待处理，
+    /// 并发运行的其他实例或工作任务不会处理同一个ID；本次运行中失败过的ID不再认领
+    async fn process_pending_datasets(&self, center: &Center, db: &MongoDB, report: &mut CenterFetchReport,
+                                      plan: Option<&mut FetchPlan>, limit: Option<usize>) -> Result<()> {
+        let name = center.name.as_str();
+        let token_info = self.get_or_refresh_token(name, &center.url, &center.secret_key).await?;
+        let details_url = token_info.service_url(SERVICE_DATASET_DETAILS)?;
+
+        // 试运行不认领，只读取待处理的ID，新发现的 ID 未入库，一并处理
+        if let Some(plan) = plan {
+            let mut pending_ids = db.get_unprocessed_ids(name, limit).await?;
+            let pending: HashSet<String> = pending_ids.iter().cloned().collect();
+            pending_ids.extend(plan.new_ids.iter().filter(|id| !pending.contains(*id)).cloned());
+            info!("{} 待处理的 ID 数量: {}", name, pending_ids.len());
+            self.fetch_pending_details(center, db, &details_url, pending_ids, Some(plan)).await?;
+            return Ok(());
+        }
+
+        // 超过 max_pending_age_days 的 ID 本次是最后一次重试，仍失败则标记为过期
+        let max_age_days = self.config.monitor.max_pending_age_days;
+        let stale_ids: HashSet<String> = if max_age_days > 0 {
+            let older_than = Utc::now() - chrono::Duration::days(max_age_days as i64);
+            db.get_stale_pending(name, older_than).await?.into_iter().collect()
+        } else {
+            HashSet::new()
+        };
+
+        let started_at = Utc::now();
+        let claim_expired_before = started_at - chrono::Duration::minutes(self.config.monitor.claim_timeout_minutes as i64);
+        let owner = format!("{}#{}", instance_id(), CLAIM_SEQ.fetch_add(1, Ordering::Relaxed));
+        let round_size = self.config.monitor.detail_worker_batch.max(1);
+        let (owner, details_url, stale_ids) = (&owner, &details_url, &stale_ids);
+        let (claimed, count, failed_ids) = drain_claims(&self.cancel, limit, round_size,
+            |size, exclude| async move {
+                db.claim_pending_ids(name, owner, size, &exclude, started_at, claim_expired_before).await
+            },
+            |ids, total| async move {
+                info!("{} 认领 {} 个待处理的 ID（本次共 {} 个，其中超期 {} 个）", name, ids.len(), total, stale_ids.len());
+                let result = self.fetch_pending_details(center, db, details_url, ids.clone(), None).await;
+                // 失败的、取消后跳过的以及出错中断时未处理的 ID 放回待处理，下次运行重试
+                if let Err(e) = db.release_claims(name, owner, &ids).await {
+                    warn!("{} 放回未处理的 ID 失败，认领超时后会被重新认领: {}", name, e);
+                }
+                result
+            }).await?;
+        if claimed == 0 {
             info!("{} 没有待处理的 ID", name);
-            return Ok(0);
+            return Ok(());
+        }
+
+        info!("{} 成功处理 {} 个数据集详情", name, count);
+        report.processed = count;
+        report.detail_failures = failed_ids.len();
+
+        let expired_ids: Vec<String> = failed_ids.into_iter().filter(|id| stale_ids.contains(id)).collect();
+        if !expired_ids.is_empty() {
+            db.expire_pending_ids(name, &expired_ids).await?;
+            warn!("{} 有 {} 个 ID 超过 {} 天仍无法获取详情，已标记为过期", name, expired_ids.len(), max_age_days);
+            report.expired = expired_ids.len();
         }
+        Ok(())
+    }
 
-        info!("{} 待处理的 ID 数量: {}", name, pending_ids.len());
+    /// 获取一组待处理ID的详情并写库，返回 (成功数, 失败的ID)；`plan` 不为 None 时只解析，结果记入 plan
+    async fn fetch_pending_details(&self, center: &Center, db: &MongoDB, details_url: &str, pending_ids: Vec<String>,
+                                   mut plan: Option<&mut FetchPlan>) -> Result<(usize, Vec<String>)> {
+        let name = center.name.as_str();
+        let collection = self.config.collection_name(name);
         let mut count = 0;
+        let mut failed_ids = Vec::new();
+
+        // 并发获取详情（支持批量接口的数据中心每次请求 detail_batch_size 个ID），按批写库；
+        // 失败的 ID 保持未处理状态，下次运行重试
+        let id_groups: Vec<Vec<String>> = pending_ids.chunks(center.detail_batch_size.max(1))
+            .map(<[String]>::to_vec)
+            .collect();
+        let mut batches = stream::iter(id_groups)
+            .map(|ids| async move {
+                // 取消后跳过尚未开始的ID，它们保持待处理状态
+                if self.cancel.is_cancelled() {
+                    return Vec::new();
+                }
+                self.fetch_dataset_details(center, details_url, ids).await
+            })
+            .buffer_unordered(self.config.monitor.fetch_max_concurrent.max(1))
+            .flat_map(stream::iter)
+            .ready_chunks(DETAIL_BATCH_SIZE);
+
+        while let Some(batch) = batches.next().await {
+            if let Some(plan) = plan.as_deref_mut() {
+                for (id, result) in batch {
+                    match result {
+                        Ok(_) => plan.parsed += 1,
+                        Err(e) => plan.failures.push(FetchPlanFailure { id, reason: format!("{:#}", e) }),
+                    }
+                }
+                continue;
+            }
+            let mut processed_ids = Vec::new();
+            for (id, result) in batch {
+                match result {
+                    Ok(dataset) => match db.upsert_dataset(&collection, dataset).await {
+                        Ok(()) => processed_ids.push(id),
+                        Err(e) => {
+                            error!("{} 保存数据集 {} 失败: {}", name, id, e);
+                            if let Err(e) = db.record_detail_failure(name, &id, &format!("保存失败: {:#}", e), None).await {
+                                warn!("{} 记录数据集 {} 失败信息失败: {}", name, id, e);
+                            }
+                            failed_ids.push(id);
+                        }
+                    },
+                    Err(e) => {
+                        error!("{:#}", e);
+                        let (reason, raw_path) = match e.downcast_ref::<ParseFailure>() {
+                            Some(failure) => (failure.reason.clone(), failure.raw_path.as_ref().map(|p| p.display().to_string())),
+                            None => (format!("{:#}", e), None),
+                        };
+                        if let Err(e) = db.record_detail_failure(name, &id, &reason, raw_path.as_deref()).await {
+                            warn!("{} 记录数据集 {} 失败信息失败: {}", name, id, e);
+                        }
+                        failed_ids.push(id);
+                    }
+                }
+            }
+            if !processed_ids.is_empty() {
+                db.update_processed_ids(name, &processed_ids).await?;
+                count += processed_ids.len();
+            }
+        }
+        Ok((count, failed_ids))
+    }
+
+    /// 常驻处理各数据中心的待处理ID，直到取消。`workers` 个工作任务依次从启用的数据中心取出最多
+    /// detail_worker_batch 个ID获取详情，同一数据中心同一时间只由一个工作任务处理；
+    /// 一轮下来没有成功处理任何ID时休眠 detail_worker_idle_secs 秒。取消后进行中的批次写库后返回
+    pub async fn run_detail_workers(&self, db: &MongoDB, workers: usize) {
+        let workers = workers.max(1);
+        info!("启动 {} 个详情工作任务", workers);
+        let busy = std::sync::Mutex::new(HashSet::new());
+        futures::future::join_all((0..workers).map(|_| self.detail_worker(db, &busy))).await;
+        info!("详情工作任务已停止");
+    }
+
+    async fn detail_worker(&self, db: &MongoDB, busy: &std::sync::Mutex<HashSet<String>>) {
+        let idle = Duration::from_secs(self.config.monitor.detail_worker_idle_secs);
+        while !self.cancel.is_cancelled() {
+            let mut processed = 0;
+            for center in self.config.centers.iter().filter(|c| c.enabled) {
+                if self.cancel.is_cancelled() {
+                    break;
+                }
+                // 其他工作任务正在处理该数据中心
+                if !busy.lock().unwrap().insert(center.name.clone()) {
+                    continue;
+                }
+                processed += self.drain_center(center, db).await;
+                busy.lock().unwrap().remove(&center.name);
+            }
+            if processed == 0 {
+                tokio::select! {
+                    _ = self.cancel.cancelled() => break,
+                    _ = tokio::time::sleep(idle) => {}
+                }
+            }
+        }
+    }
+
+    /// 处理一个数据中心最多 detail_worker_batch 个待处理ID，返回成功处理的数量；错误和 panic 只记录日志
+    async fn drain_center(&self, center: &Center, db: &MongoDB) -> usize {
+        let mut report = CenterFetchReport {
+            name: center.name.clone(),
+            ..Default::default()
+        };
+        let limit = Some(self.config.monitor.detail_worker_batch.max(1));
+        match AssertUnwindSafe(self.process_pending_datasets(center, db, &mut report, None, limit)).catch_unwind().await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("详情工作任务处理数据中心 {} 失败: {:#}", center.name, e),
+            Err(panic) => error!("详情工作任务处理数据中心 {} panic: {}", center.name, panic_message(panic.as_ref())),
+        }
+        if report.processed + report.detail_failures > 0 {
+            info!("详情工作任务: 数据中心 {} 处理 {} 条，失败 {} 条，过期 {} 条",
+                  center.name, report.processed, report.detail_failures, report.expired);
+            if self.config.monitor.backlog_metrics_file.is_some()
+                && let Err(e) = backlog::refresh(&self.config, db).await
+            {
+                warn!("刷新ID积压指标失败: {:#}", e);
+            }
+        }
+        report.processed
+    }
+
+    /// 配置了 detail_requests_per_sec 时等待到该数据中心下一次允许请求详情接口的时间
+    async fn throttle_detail(&self, name: &str) {
+        let Some(rate) = self.config.centers.iter()
+            .find(|c| c.name == name)
+            .and_then(|c| c.detail_requests_per_sec)
+            .filter(|rate| *rate > 0.0) else {
+            return;
+        };
+        let slot = self.detail_slots.entry(name.to_string())
+            .or_insert_with(|| Arc::new(tokio::sync::Mutex::new(Instant::now())))
+            .clone();
+        let mut next = slot.lock().await;
+        tokio::time::sleep_until((*next).into()).await;
+        *next = (*next).max(Instant::now()) + Duration::from_secs_f64(1.0 / rate);
+    }
 
-        for id in pending_ids {
-            let response = self.client.get(&details_url)
-                .headers(headers.clone())
-                .query(&[("id", &id)])
+    /// 请求详情接口并读取响应体，`id` 可以是逗号分隔的多个ID
+    async fn request_detail(&self, name: &str, url: &str, secret_key: &str, details_url: &str, id: &str) -> Result<CleanedBody> {
+        self.throttle_detail(name).await;
+        let started = Instant::now();
+        let result = self.send_detail(name, url, secret_key, details_url, id).await;
+        self.counters(name).record_detail(started.elapsed(), result.is_err());
+        result
+    }
+
+    /// token 失效（401）时强制刷新后重试一次
+    async fn send_detail(&self, name: &str, url: &str, secret_key: &str, details_url: &str, id: &str) -> Result<CleanedBody> {
+        let token_info = self.get_or_refresh_token(name, url, secret_key).await?;
+        let mut response = self.client.get(details_url)
+            .headers(Self::auth_headers(&token_info)?)
+            .query(&[("id", id)])
+            .send()
+            .await
+            .with_context(|| format!("{} 获取数据集 {} 详情失败", name, id))?;
+        // 运行中途 token 失效时强制刷新后重试一次
+        if response.status() == 401 && !self.cancel.is_cancelled() {
+            warn!("{} 获取数据集 {} 详情返回 401，刷新 token 后重试", name, id);
+            self.invalidate_token(name, &token_info);
+            let token_info = self.get_or_refresh_token(name, url, secret_key).await?;
+            response = self.client.get(details_url)
+                .headers(Self::auth_headers(&token_info)?)
+                .query(&[("id", id)])
                 .send()
                 .await
                 .with_context(|| format!("{} 获取数据集 {} 详情失败", name, id))?;
+        }
 
-            if response.status().is_success() {
-                let response_text = response.text().await
-                    .with_context(|| format!("{} 读取数据集 {} 响应内容失败", name, id))?;
-                let cleaned_text = Self::clean_json_string(&response_text);
-                let value: Value = serde_json::from_str(&cleaned_text)
-                    .with_context(|| format!("{} 解析数据集 {} 详情失败: {}", name, id, cleaned_text))?;
-                let mut dataset: Dataset = serde_json::from_value(value)
-                    .with_context(|| format!("{} 解析数据集 {} 详情失败: {}", name, id, cleaned_text))?;
-                dataset.casdc_id = Some(id.clone());
-                db.upsert_dataset(name, dataset).await
-                    .with_context(|| format!("{} 保存数据集 {} 失败", name, id))?;
-                db.update_processed_ids(name, &[id.clone()]).await?;
-                count += 1;
-            } else {
-                error!("{} 获取数据集 {} 详情失败，HTTP状态码: {}", name, id, response.status());
+        if !response.status().is_success() {
+            anyhow::bail!("{} 获取数据集 {} 详情失败，HTTP状态码: {}", name, id, response.status());
+        }
+        let bytes = self.read_bytes(name, response).await
+            .with_context(|| format!("{} 读取数据集 {} 响应内容失败", name, id))?;
+        let body = clean_body(&bytes)
+            .with_context(|| format!("{} 数据集 {} 响应无法解码", name, id))?;
+        if body.invalid_sequences > 0 {
+            warn!("{} 数据集 {} 响应含 {} 处无效的UTF-8序列，已替换为 U+FFFD", name, id, body.invalid_sequences);
+        }
+        Ok(body)
+    }
+
+    async fn fetch_dataset_detail(&self, name: &str, url: &str, secret_key: &str, details_url: &str, id: &str) -> Result<Dataset> {
+        let body = self.request_detail(name, url, secret_key, details_url, id).await?;
+        self.parse_detail(name, id, &body.text, &body)
+    }
+
+    /// 解析失败时保存原始响应，`body` 为 `text` 所在响应的清理统计
+    fn parse_detail(&self, name: &str, id: &str, text: &str, body: &CleanedBody) -> Result<Dataset> {
+        parse_dataset(text, id).map_err(|e| {
+            let raw_path = self.raw_store.save(name, id, text)
+                .map_err(|e| warn!("{} 保存数据集 {} 原始响应失败: {}", name, id, e))
+                .ok();
+            let mut reason = format!("{} 解析数据集 {} 详情失败: {}", name, id, e);
+            if body.sanitized() > 0 {
+                reason.push_str(&format!("（{}）", body.describe()));
+            }
+            ParseFailure {
+                reason,
+                raw_path,
+                sanitized: body.sanitized(),
             }
+            .into()
+        })
+    }
+
+    /// 获取一组ID的详情。多个ID时以逗号分隔一次请求，响应应为数组；
+    /// 响应中缺少的ID以及整批请求或解析失败时，逐个单独请求
+    async fn fetch_dataset_details(&self, center: &Center, details_url: &str, ids: Vec<String>) -> Vec<(String, Result<Dataset>)> {
+        let (name, url, secret_key) = (center.name.as_str(), center.url.as_str(), center.secret_key.as_str());
+        let mut results = Vec::new();
+        let mut remaining = ids;
+        if remaining.len() > 1 {
+            match self.request_detail(name, url, secret_key, details_url, &remaining.join(",")).await {
+                Ok(body) => match serde_json::from_str::<Value>(&body.text) {
+                    Ok(Value::Array(items)) => {
+                        for item in items {
+                            let Some(id) = batch_item_id(&item, &remaining) else {
+                                warn!("{} 批量详情响应中有无法对应请求ID的条目，已忽略", name);
+                                continue;
+                            };
+                            remaining.retain(|r| *r != id);
+                            let result = self.parse_detail(name, &id, &item.to_string(), &body);
+                            results.push((id, result));
+                        }
+                        if !remaining.is_empty() {
+                            warn!("{} 批量详情响应缺少 {} 个ID，逐个重新请求", name, remaining.len());
+                        }
+                    }
+                    Ok(_) => warn!("{} 批量详情响应不是数组，逐个重新请求", name),
+                    Err(e) => warn!("{} 批量详情响应不是有效的JSON: {}，逐个重新请求", name, e),
+                },
+                Err(e) => warn!("{:#}，逐个重新请求", e),
+            }
+        }
+        for id in remaining {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            let result = self.fetch_dataset_detail(name, url, secret_key, details_url, &id).await;
+            results.push((id, result));
         }
+        results
+    }
 
-        info!("{} 成功处理 {} 个数据集详情", name, count);
-        Ok(count)
+    /// 重新解析已保存的原始响应（修复 Dataset 模型后使用），成功的写库并删除原始响应，
+    /// 返回 (成功数, 失败数)
+    pub async fn reparse_raw_responses(&self, db: &MongoDB) -> Result<(usize, usize)> {
+        let (mut succeeded, mut failed) = (0, 0);
+        for (path, raw) in self.raw_store.entries()? {
+            if raw.truncated {
+                warn!("{} 数据集 {} 的原始响应保存时已截断，无法重新解析: {}", raw.center_name, raw.dataset_id, path.display());
+                failed += 1;
+                continue;
+            }
+            let dataset = match parse_dataset(&raw.body, &raw.dataset_id) {
+                Ok(dataset) => dataset,
+                Err(e) => {
+                    warn!("{} 数据集 {} 仍然解析失败: {}", raw.center_name, raw.dataset_id, e);
+                    failed += 1;
+                    continue;
+                }
+            };
+            db.upsert_dataset(&self.config.collection_name(&raw.center_name), dataset).await
+                .with_context(|| format!("{} 保存数据集 {} 失败", raw.center_name, raw.dataset_id))?;
+            db.update_processed_ids(&raw.center_name, std::slice::from_ref(&raw.dataset_id)).await?;
+            raw_store::remove(&path)?;
+            info!("{} 数据集 {} 重新解析成功", raw.center_name, raw.dataset_id);
+            succeeded += 1;
+        }
+        Ok((succeeded, failed))
+    }
+
+    /// 返回缓存中未临近过期的 token
+    fn cached_token(&self, name: &str) -> Option<TokenInfo> {
+        self.tokens.get(name).filter(|token_info| token_info.is_fresh()).map(|token_info| token_info.clone())
     }
 
+    /// 返回可用的 token，缓存中没有或临近过期时请求认证接口。
+    /// 同一数据中心同时只有一个刷新请求，等待的任务复用其结果；认证被拒绝后短时间内直接返回失败
     async fn get_or_refresh_token(&self, name: &str, url: &str, key: &str) -> Result<TokenInfo> {
-        // 检查缓存
-        if let Some(token_info) = self.tokens.get(name) {
-            if token_info.expires_at > Utc::now() {
-                return Ok(token_info.clone());
+        if let Some(token_info) = self.cached_token(name) {
+            return Ok(token_info);
+        }
+        let lock = self.token_locks.entry(name.to_string()).or_default().clone();
+        let _guard = lock.lock().await;
+        // 等锁期间其他任务可能已经刷新
+        if let Some(token_info) = self.cached_token(name) {
+            return Ok(token_info);
+        }
+        if let Some(failure) = self.auth_failures.get(name)
+            && failure.0.elapsed() < Duration::from_secs(AUTH_FAILURE_CACHE_SECS)
+        {
+            anyhow::bail!("{} 认证在 {} 秒前失败，暂不重试: {}", name, failure.0.elapsed().as_secs(), failure.1);
+        }
+        match self.refresh_token(name, url, key).await {
+            Ok(token_info) => {
+                self.auth_failures.remove(name);
+                Ok(token_info)
+            }
+            Err(e) => {
+                // 网络错误和接口暂时不可用下次照常重试，只缓存认证被拒绝（401/403、响应中没有可用的 token）
+                if !e.chain().any(|cause| cause.is::<reqwest::Error>() || cause.is::<AuthUnavailable>()) {
+                    self.auth_failures.insert(name.to_string(), (Instant::now(), format!("{:#}", e)));
+                }
+                Err(e)
             }
         }
+    }
 
-        // 获取新token
+    /// 请求认证接口获取新 token 并写入缓存
+    async fn refresh_token(&self, name: &str, url: &str, key: &str) -> Result<TokenInfo> {
         info!("获取中心 {} 的新token", name);
 
         let mut headers = HeaderMap::new();
@@ -220,47 +1002,1389 @@ impl DataFetcher {
             .with_context(|| "请求token失败")?;
 
         let status = response.status();
-        let response_text = response.text().await?;
+        let response_text = self.read_body(name, response).await
+            .with_context(|| format!("{} 读取认证响应失败", name))?;
         info!("Token response status: {}, body: {}", status, response_text);
+        if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(AuthUnavailable { center: name.to_string(), status: status.as_u16(), body: response_text }.into());
+        }
 
         let auth_resp: AuthResponse = serde_json::from_str(&response_text)
             .with_context(|| format!("解析认证响应失败，响应内容: {}", response_text))?;
 
+        if auth_resp.ticket.token.is_empty() {
+            anyhow::bail!("{} 认证响应中没有 token，响应内容: {}", name, response_text);
+        }
+        let Some(first_service) = auth_resp.service_list.first() else {
+            anyhow::bail!("{} 认证响应中 serviceList 为空，响应内容: {}", name, response_text);
+        };
+
+        let center = self.config.centers.iter().find(|c| c.name == name);
+        let mode = center.map(|c| c.token_expires).unwrap_or_default();
+        let now = Utc::now();
+        let expires_at = token_expiry(auth_resp.ticket.expires, mode, now);
+        info!("中心 {} 的 token expires={}（{:?}），按 {} 过期",
+              name, auth_resp.ticket.expires, mode, expires_at.to_rfc3339());
+
+        // 按 service_names 把数据中心自己的服务名换成标准名
+        let services: Vec<ServiceInfo> = auth_resp.service_list.iter()
+            .map(|s| ServiceInfo {
+                name: center
+                    .and_then(|c| c.service_names.iter().find(|(_, alias)| **alias == s.name))
+                    .map(|(standard, _)| standard.clone())
+                    .unwrap_or_else(|| s.name.clone()),
+                url: s.url.clone(),
+            })
+            .collect();
+        validate_services(name, &services)?;
+
         let token_info = TokenInfo {
             token: auth_resp.ticket.token,
-            version: auth_resp.service_list.first()
-                .map(|s| s.version.clone())
-                .unwrap_or_else(|| "1.0".to_string()),
-            services: auth_resp.service_list.iter()
-                .map(|s| ServiceInfo {
-                    name: s.name.clone(),
-                    url: s.url.clone(),
-                })
-                .collect(),
-            expires_at: Utc::now() + chrono::Duration::seconds(auth_resp.ticket.expires - 300),
+            version: first_service.version.clone(),
+            services,
+            expires_at,
         };
 
         self.tokens.insert(name.to_string(), token_info.clone());
+        if let Err(e) = self.save_tokens() {
+            warn!("保存 token 缓存失败: {}", e);
+        }
         Ok(token_info)
     }
 }
 
-impl Clone for TokenInfo {
-    fn clone(&self) -> Self {
-        Self {
-            token: self.token.clone(),
-            version: self.version.clone(),
-            services: self.services.clone(),
-            expires_at: self.expires_at,
+/// 把 ticket.expires 换算为过期时间，有效期截断到 [MIN_TOKEN_VALIDITY_SECS, MAX_TOKEN_VALIDITY_SECS]
+///
+/// 自动推断时：>= 1e12 视为毫秒时间戳，>= 1e9 视为秒时间戳，超过 7 天的时长视为毫秒，其余视为秒；
+/// 0 或负数视为未提供，使用 DEFAULT_TOKEN_VALIDITY_SECS。
+pub fn token_expiry(expires: i64, mode: TokenExpires, now: chrono::DateTime<Utc>) -> chrono::DateTime<Utc> {
+    let validity_secs = if expires <= 0 {
+        DEFAULT_TOKEN_VALIDITY_SECS
+    } else {
+        let mode = match mode {
+            TokenExpires::Auto if expires >= 1_000_000_000_000 => TokenExpires::EpochMillis,
+            TokenExpires::Auto if expires >= 1_000_000_000 => TokenExpires::EpochSeconds,
+            TokenExpires::Auto if expires > 7 * 24 * 3600 => TokenExpires::Millis,
+            TokenExpires::Auto => TokenExpires::Seconds,
+            mode => mode,
+        };
+        match mode {
+            TokenExpires::EpochMillis => expires / 1000 - now.timestamp(),
+            TokenExpires::EpochSeconds => expires - now.timestamp(),
+            TokenExpires::Millis => expires / 1000,
+            _ => expires,
         }
+    };
+    now + chrono::Duration::seconds(validity_secs.clamp(MIN_TOKEN_VALIDITY_SECS, MAX_TOKEN_VALIDITY_SECS))
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    panic.downcast_ref::<&str>().map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// 按上一次成功获取 `last` 和之后连续增量获取的次数决定本次的 updatedSince，None 表示完整获取
+fn incremental_since(center: &Center, last: Option<&FetchAudit>, incremental_runs: usize) -> Option<chrono::DateTime<Utc>> {
+    let last = last.filter(|_| center.supports_updated_since)?;
+    if incremental_runs >= center.full_fetch_every {
+        info!("{} 已连续增量获取 {} 次，本次完整获取", center.name, incremental_runs);
+        return None;
     }
+    Some(last.started_at)
 }
 
-impl Clone for ServiceInfo {
-    fn clone(&self) -> Self {
-        Self {
-            name: self.name.clone(),
-            url: self.url.clone(),
+/// 认领循环：每轮用 `claim(数量, 本次已失败的ID)` 认领一批ID，交给 `process(ID, 累计认领数)` 处理（由它放回未成功的ID），
+/// 直到没有可认领的ID、达到 `limit` 或取消，返回 (认领数, 成功数, 失败的ID)。本次失败过的ID不再认领，
+/// 失败信息没能写库时循环也会结束
+async fn drain_claims<C, CF, P, PF>(cancel: &CancellationToken, limit: Option<usize>, round_size: usize,
+                                    mut claim: C, mut process: P) -> Result<(usize, usize, Vec<String>)>
+where
+    C: FnMut(usize, Vec<String>) -> CF,
+    CF: Future<Output = Result<Vec<String>>>,
+    P: FnMut(Vec<String>, usize) -> PF,
+    PF: Future<Output = Result<(usize, Vec<String>)>>,
+{
+    let (mut claimed, mut count) = (0, 0);
+    let mut failed_ids = Vec::new();
+    while !cancel.is_cancelled() {
+        let size = match limit {
+            Some(limit) if claimed >= limit => break,
+            Some(limit) => round_size.min(limit - claimed),
+            None => round_size,
+        };
+        let ids = claim(size, failed_ids.clone()).await?;
+        if ids.is_empty() {
+            break;
+        }
+        claimed += ids.len();
+        let (processed, failed) = process(ids, claimed).await?;
+        count += processed;
+        failed_ids.extend(failed);
+    }
+    Ok((claimed, count, failed_ids))
+}
+
+/// 批量详情响应中的条目对应的请求ID：依次比较 `id`、`casdc_id`、`@id` 字段
+fn batch_item_id(item: &Value, requested: &[String]) -> Option<String> {
+    ["id", "casdc_id", "@id"].iter()
+        .filter_map(|key| item.get(*key).and_then(Value::as_str))
+        .find(|value| requested.iter().any(|r| r == value))
+        .map(String::from)
+}
+
+/// 解码并清理后的响应体
+#[derive(Debug, Clone, Default)]
+pub struct CleanedBody {
+    pub text: String,
+    /// 开头的 UTF-8 BOM 已去掉
+    pub bom_stripped: bool,
+    /// 替换为 U+FFFD 的无效UTF-8序列数
+    pub invalid_sequences: usize,
+    /// 去掉的控制字符数（含 NUL，不含换行、回车、制表符）
+    pub control_chars: usize,
+}
+
+impl CleanedBody {
+    pub fn sanitized(&self) -> usize {
+        self.bom_stripped as usize + self.invalid_sequences + self.control_chars
+    }
+
+    pub fn describe(&self) -> String {
+        format!("响应清理了 {} 个字符: BOM {}，无效UTF-8序列 {}，控制字符 {}",
+                self.sanitized(), if self.bom_stripped { 1 } else { 0 }, self.invalid_sequences, self.control_chars)
+    }
+}
+
+/// 按 UTF-8 解码响应：去掉开头的 BOM，无效序列替换为 U+FFFD，去掉换行、回车、制表符以外的控制字符。
+/// 以 UTF-16 BOM 开头的响应直接报错
+pub fn clean_body(bytes: &[u8]) -> Result<CleanedBody> {
+    if bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF]) {
+        anyhow::bail!("响应以 UTF-16 BOM 开头，只支持 UTF-8");
+    }
+    let mut body = CleanedBody::default();
+    let bytes = match bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        Some(rest) => {
+            body.bom_stripped = true;
+            rest
+        }
+        None => bytes,
+    };
+    body.text.reserve(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c >= ' ' || c == '\n' || c == '\r' || c == '\t' {
+                body.text.push(c);
+            } else {
+                body.control_chars += 1;
+            }
+        }
+        if !chunk.invalid().is_empty() {
+            body.invalid_sequences += 1;
+            body.text.push(char::REPLACEMENT_CHARACTER);
+        }
+    }
+    Ok(body)
+}
+
+fn parse_dataset(text: &str, id: &str) -> Result<Dataset> {
+    let value: Value = serde_json::from_str(text)?;
+    let mut dataset: Dataset = serde_json::from_value(value)?;
+    dataset.casdc_id = Some(id.to_string());
+    Ok(dataset)
+}
+
+/// 数据集列表的一页，`total` 仅分页结构有
+#[derive(Debug)]
+struct DatasetListPage {
+    ids: Vec<String>,
+    total: Option<usize>,
+}
+
+/// 解析数据集列表：直接返回数组，或 `{"total": N, "page": i, "items": [...]}` 分页结构
+fn parse_dataset_list(value: &Value) -> Option<DatasetListPage> {
+    let (items, total) = match value {
+        Value::Array(items) => (items, None),
+        Value::Object(map) => {
+            let items = map.get("items")?.as_array()?;
+            let total = map.get("total").and_then(Value::as_u64).map(|t| t as usize);
+            (items, Some(total.unwrap_or(items.len())))
+        }
+        _ => return None,
+    };
+    let ids = items.iter()
+        .filter_map(|v| v.get("id").and_then(|id| id.as_str()).map(String::from))
+        .collect();
+    Some(DatasetListPage { ids, total })
+}
+
+/// 数据集列表的全部ID（已去重）以及是否完整获取（未达到最大页数）
+#[derive(Debug)]
+struct DatasetList {
+    ids: Vec<String>,
+    complete: bool,
+}
+
+/// 第一页是分页结构时按 `pagination` 继续请求后续页，`fetch` 请求给定分页参数的一页。未配置分页时按默认方式翻页，
+/// 每页大小取第一页返回的ID数，保证后续页与第一页衔接。ID 去重，遇到空页或没有新ID的页（接口忽略分页参数）时停止
+async fn collect_dataset_pages<F, Fut>(name: &str, first: DatasetListPage, pagination: Option<&PaginationConfig>,
+                                       cancel: &CancellationToken, mut fetch: F) -> Result<DatasetList>
+where
+    F: FnMut(Vec<(&'static str, String)>) -> Fut,
+    Fut: Future<Output = Result<DatasetListPage>>,
+{
+    let mut seen = HashSet::new();
+    let mut ids: Vec<String> = first.ids.into_iter().filter(|id| seen.insert(id.clone())).collect();
+    let Some(total) = first.total else {
+        return Ok(DatasetList { ids, complete: true });
+    };
+    let (style, page_size, max_pages) = match pagination {
+        Some(p) => (p.style, p.page_size, p.max_pages),
+        None => {
+            let defaults = PaginationConfig::default();
+            (defaults.style, ids.len(), defaults.max_pages)
+        }
+    };
+    let mut complete = true;
+    let mut page = 1;
+    while ids.len() < total && page_size > 0 {
+        if cancel.is_cancelled() {
+            anyhow::bail!("{} 获取数据集列表时已取消", name);
+        }
+        if page >= max_pages {
+            warn!("{} 数据集列表已达到最大页数 {}，共获取 {}/{} 个 ID", name, max_pages, ids.len(), total);
+            complete = false;
+            break;
+        }
+        let next = fetch(style.query(page, page_size)).await?;
+        page += 1;
+        let before = ids.len();
+        ids.extend(next.ids.into_iter().filter(|id| seen.insert(id.clone())));
+        if ids.len() == before {
+            if ids.len() < total {
+                warn!("{} 数据集列表第 {} 页没有新的 ID，停止翻页，共获取 {}/{} 个 ID", name, page, ids.len(), total);
+            }
+            break;
         }
     }
-}
\ No newline at end of file
+    info!("{} 数据集列表共 {} 页，{} 个 ID", name, page, ids.len());
+    Ok(DatasetList { ids, complete })
+}
+
+fn load_tokens(path: &Path) -> Result<HashMap<String, TokenInfo>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PaginationStyle;
+    use crate::test_support::{self, response, Stub};
+    use std::sync::Mutex;
+    use std::sync::atomic::AtomicUsize;
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum ClaimState {
+        Pending,
+        Claimed(&'static str),
+        Done,
+    }
+
+    /// 内存中的 processed_dataset_ids：认领在锁内完成，和 find_one_and_update 一样不会把同一个ID交给两个认领者
+    struct Store {
+        ids: Mutex<Vec<(String, ClaimState)>>,
+        attempts: Mutex<Vec<(&'static str, String)>>,
+    }
+
+    impl Store {
+        fn new(n: usize) -> Self {
+            Store {
+                ids: Mutex::new((0..n).map(|i| (format!("id-{:02}", i), ClaimState::Pending)).collect()),
+                attempts: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn claim(&self, owner: &'static str, size: usize, exclude: &[String]) -> Vec<String> {
+            let mut ids = self.ids.lock().unwrap();
+            ids.iter_mut()
+                .filter(|(id, state)| *state == ClaimState::Pending && !exclude.contains(id))
+                .take(size)
+                .map(|(id, state)| {
+                    *state = ClaimState::Claimed(owner);
+                    id.clone()
+                })
+                .collect()
+        }
+
+        fn release(&self, owner: &'static str, released: &[String]) {
+            for (id, state) in self.ids.lock().unwrap().iter_mut() {
+                if *state == ClaimState::Claimed(owner) && released.contains(id) {
+                    *state = ClaimState::Pending;
+                }
+            }
+        }
+
+        /// 以 `fail` 结尾的ID（`fail` 为空时所有ID）处理失败且失败信息没能写库（last_failed_at 未更新），其余处理成功
+        async fn process(&self, owner: &'static str, ids: Vec<String>, fail: &str) -> Result<(usize, Vec<String>)> {
+            tokio::task::yield_now().await;
+            let (failed, processed): (Vec<String>, Vec<String>) = ids.iter().cloned().partition(|id| id.ends_with(fail));
+            self.attempts.lock().unwrap().extend(ids.iter().map(|id| (owner, id.clone())));
+            for (id, state) in self.ids.lock().unwrap().iter_mut() {
+                if processed.contains(id) {
+                    *state = ClaimState::Done;
+                }
+            }
+            self.release(owner, &ids);
+            Ok((processed.len(), failed))
+        }
+
+        async fn run(&self, owner: &'static str, limit: Option<usize>, fail: &str) -> (usize, usize, Vec<String>) {
+            drain_claims(&CancellationToken::new(), limit, 3,
+                         |size, exclude| async move { Ok(self.claim(owner, size, &exclude)) },
+                         |ids, _| self.process(owner, ids, fail)).await.unwrap()
+        }
+
+        fn states(&self) -> Vec<ClaimState> {
+            self.ids.lock().unwrap().iter().map(|(_, state)| state.clone()).collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn released_failures_are_not_reclaimed_in_the_same_run() {
+        let store = Store::new(7);
+        // 全部失败时每个ID本次只认领一次，循环结束，ID 都放回待处理
+        let (claimed, count, failed) = store.run("a", None, "").await;
+        assert_eq!((claimed, count, failed.len()), (7, 0, 7));
+        assert!(store.states().iter().all(|state| *state == ClaimState::Pending));
+
+        // 下次运行重新认领
+        let (claimed, _, _) = store.run("a", None, "").await;
+        assert_eq!(claimed, 7);
+        assert_eq!(store.attempts.lock().unwrap().len(), 14);
+    }
+
+    #[tokio::test]
+    async fn concurrent_runs_process_each_id_once() {
+        let store = Store::new(30);
+        let ((claimed_a, count_a, failed_a), (claimed_b, count_b, failed_b)) =
+            tokio::join!(store.run("a", None, "7"), store.run("b", None, "7"));
+        assert_eq!(count_a + count_b, 27);
+        assert_eq!(claimed_a + claimed_b, store.attempts.lock().unwrap().len());
+
+        let attempts = store.attempts.lock().unwrap();
+        let mut succeeded: Vec<&String> = attempts.iter().map(|(_, id)| id).filter(|id| !id.ends_with('7')).collect();
+        succeeded.sort();
+        succeeded.dedup();
+        assert_eq!(succeeded.len(), 27, "成功的ID只处理一次");
+        assert_eq!(attempts.len() - 27, failed_a.len() + failed_b.len());
+        // 失败的ID每次运行最多认领一次
+        for (owner, failed) in [("a", &failed_a), ("b", &failed_b)] {
+            let mut own: Vec<&String> = attempts.iter().filter(|(o, _)| *o == owner).map(|(_, id)| id).collect();
+            let total = own.len();
+            own.sort();
+            own.dedup();
+            assert_eq!(own.len(), total, "{} 重复认领了ID: {:?}", owner, failed);
+        }
+        let states = store.states();
+        assert_eq!(states.iter().filter(|state| **state == ClaimState::Done).count(), 27);
+        assert_eq!(states.iter().filter(|state| **state == ClaimState::Pending).count(), 3);
+    }
+
+    #[tokio::test]
+    async fn claims_stop_at_limit_and_on_cancel() {
+        let store = Store::new(10);
+        let (claimed, count, _) = store.run("a", Some(4), "x").await;
+        assert_eq!((claimed, count), (4, 4));
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let store = &store;
+        let (claimed, _, _) = drain_claims(&cancel, None, 3,
+                                           |size, exclude| async move { Ok(store.claim("a", size, &exclude)) },
+                                           |ids, _| store.process("a", ids, "x")).await.unwrap();
+        assert_eq!(claimed, 0);
+    }
+
+    fn ids(range: std::ops::Range<usize>) -> Vec<String> {
+        range.map(|i| format!("d{}", i)).collect()
+    }
+
+    /// 模拟数据集列表接口：按 page/pageSize 或 offset/limit 返回对应的分页结构，不带分页参数时每页 `default_size` 个
+    fn list_response(query: &[(&str, String)], total: usize, default_size: usize) -> Value {
+        let param = |key: &str| query.iter().find(|(k, _)| *k == key).map(|(_, v)| v.parse::<usize>().unwrap());
+        let items = |start: usize, size: usize| -> Vec<Value> {
+            ids(start.min(total)..(start + size).min(total)).into_iter().map(|id| serde_json::json!({ "id": id })).collect()
+        };
+        match (param("page"), param("pageSize"), param("offset"), param("limit")) {
+            (Some(page), Some(size), _, _) => serde_json::json!({
+                "total": total, "page": page, "pageSize": size, "items": items((page - 1) * size, size)
+            }),
+            (_, _, Some(offset), Some(limit)) => serde_json::json!({
+                "total": total, "offset": offset, "limit": limit, "items": items(offset, limit)
+            }),
+            _ => serde_json::json!({ "total": total, "page": 1, "items": items(0, default_size) }),
+        }
+    }
+
+    fn pagination(style: PaginationStyle, page_size: usize, max_pages: usize) -> PaginationConfig {
+        PaginationConfig { style, page_size, max_pages }
+    }
+
+    /// 按 `pagination` 请求第一页并翻页，返回结果和每次请求的分页参数
+    async fn collect(pagination: Option<PaginationConfig>, respond: impl Fn(&[(&str, String)]) -> Value) -> (DatasetList, Vec<Vec<(&'static str, String)>>) {
+        let first_query = pagination.as_ref().map(|p| p.style.query(0, p.page_size)).unwrap_or_default();
+        let first = parse_dataset_list(&respond(&first_query)).unwrap();
+        let mut queries = vec![first_query];
+        let list = collect_dataset_pages("c", first, pagination.as_ref(), &CancellationToken::new(), |query| {
+            let page = parse_dataset_list(&respond(&query)).unwrap();
+            queries.push(query);
+            async move { Ok(page) }
+        }).await.unwrap();
+        (list, queries)
+    }
+
+    #[test]
+    fn parse_dataset_list_fixtures() {
+        let plain = parse_dataset_list(&serde_json::json!([{ "id": "a" }, { "id": "b" }, { "name": "没有 id" }])).unwrap();
+        assert_eq!((plain.ids, plain.total), (vec!["a".to_string(), "b".to_string()], None));
+
+        let page = parse_dataset_list(&serde_json::json!({
+            "total": 5, "page": 1, "pageSize": 2, "items": [{ "id": "a" }, { "id": "b" }]
+        })).unwrap();
+        assert_eq!((page.ids.len(), page.total), (2, Some(5)));
+
+        let offset = parse_dataset_list(&serde_json::json!({
+            "total": 5, "offset": 4, "limit": 2, "items": [{ "id": "e" }]
+        })).unwrap();
+        assert_eq!((offset.ids, offset.total), (vec!["e".to_string()], Some(5)));
+
+        // 没有 total 时按本页数量
+        let no_total = parse_dataset_list(&serde_json::json!({ "items": [{ "id": "a" }] })).unwrap();
+        assert_eq!(no_total.total, Some(1));
+
+        assert!(parse_dataset_list(&serde_json::json!({ "data": [] })).is_none());
+        assert!(parse_dataset_list(&serde_json::json!("oops")).is_none());
+    }
+
+    #[tokio::test]
+    async fn collects_page_and_offset_envelopes() {
+        for style in [PaginationStyle::Page, PaginationStyle::Offset] {
+            let (list, queries) = collect(Some(pagination(style, 2, 10)), |q| list_response(q, 5, 100)).await;
+            assert_eq!(list.ids, ids(0..5), "{:?}", style);
+            assert!(list.complete);
+            assert_eq!(queries.len(), 3);
+        }
+        let (_, queries) = collect(Some(pagination(PaginationStyle::Offset, 2, 10)), |q| list_response(q, 5, 100)).await;
+        assert_eq!(queries[2], vec![("offset", "4".to_string()), ("limit", "2".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn unconfigured_pagination_follows_first_page_size() {
+        // 接口默认每页 3 个，和默认的 page_size 100 不同，后续页按 3 个请求才不会跳过或重复
+        let (list, queries) = collect(None, |q| list_response(q, 7, 3)).await;
+        assert_eq!(list.ids, ids(0..7));
+        assert!(list.complete);
+        assert_eq!(queries[1], vec![("page", "2".to_string()), ("pageSize", "3".to_string())]);
+        assert_eq!(queries.len(), 3);
+
+        let plain = parse_dataset_list(&serde_json::json!([{ "id": "a" }])).unwrap();
+        let list = collect_dataset_pages("c", plain, None, &CancellationToken::new(),
+                                         |_| async { panic!("没有分页结构时不翻页") }).await.unwrap();
+        assert_eq!(list.ids, vec!["a".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn duplicate_pages_stop_paging() {
+        // 接口忽略分页参数，每次都返回第一页：去重后停止，而不是用重复的ID凑够 total
+        let (list, queries) = collect(Some(pagination(PaginationStyle::Page, 2, 10)), |_| list_response(&[], 6, 2)).await;
+        assert_eq!(list.ids, ids(0..2));
+        assert_eq!(queries.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn max_pages_caps_the_listing() {
+        let (list, queries) = collect(Some(pagination(PaginationStyle::Page, 2, 3)), |q| list_response(q, 10, 100)).await;
+        assert_eq!(list.ids, ids(0..6));
+        assert!(!list.complete);
+        assert_eq!(queries.len(), 3);
+    }
+
+    #[test]
+    fn clean_body_strips_bom_and_control_chars() {
+        let body = clean_body(b"\xEF\xBB\xBF{\"title\":\"a\x00b\x1Fc\"}\r\n\t").unwrap();
+        assert_eq!(body.text, "{\"title\":\"abc\"}\r\n\t");
+        assert!(body.bom_stripped);
+        assert_eq!((body.control_chars, body.invalid_sequences), (2, 0));
+        assert_eq!(body.sanitized(), 3);
+
+        // BOM 只在开头时去掉
+        let body = clean_body("a\u{FEFF}".as_bytes()).unwrap();
+        assert_eq!((body.text.as_str(), body.bom_stripped), ("a\u{FEFF}", false));
+        assert_eq!(body.sanitized(), 0);
+    }
+
+    #[test]
+    fn clean_body_replaces_broken_multibyte_sequences() {
+        // "中" 是 E4 B8 AD：截断的序列、孤立的续字节各算一个无效序列
+        let body = clean_body(b"\xE4\xB8\xAD\xE4\xB8,\x80\x00x").unwrap();
+        assert_eq!(body.text, "中\u{FFFD},\u{FFFD}x");
+        assert_eq!((body.invalid_sequences, body.control_chars, body.bom_stripped), (2, 1, false));
+        assert_eq!(body.sanitized(), 3);
+        assert_eq!(body.describe(), "响应清理了 3 个字符: BOM 0，无效UTF-8序列 2，控制字符 1");
+    }
+
+    #[test]
+    fn clean_body_rejects_utf16() {
+        assert!(clean_body(b"\xFF\xFE{\x00}\x00").is_err());
+        assert!(clean_body(b"\xFE\xFF\x00{\x00}").is_err());
+        assert_eq!(clean_body(b"").unwrap().sanitized(), 0);
+    }
+
+    #[test]
+    fn token_expiry_interprets_expires() {
+        let now: chrono::DateTime<Utc> = "2026-03-10T12:00:00Z".parse().unwrap();
+        let after = |expires, mode| (token_expiry(expires, mode, now) - now).num_seconds();
+        // 0 或负数视为未提供
+        assert_eq!(after(0, TokenExpires::Auto), DEFAULT_TOKEN_VALIDITY_SECS);
+        assert_eq!(after(-1, TokenExpires::EpochMillis), DEFAULT_TOKEN_VALIDITY_SECS);
+        // 秒、毫秒时间戳
+        assert_eq!(after(now.timestamp() + 7200, TokenExpires::Auto), 7200);
+        assert_eq!(after((now.timestamp() + 7200) * 1000, TokenExpires::Auto), 7200);
+        // 时长：超过 7 天的按毫秒
+        assert_eq!(after(1800, TokenExpires::Auto), 1800);
+        assert_eq!(after(3_600_000, TokenExpires::Auto), 3600);
+        // 截断到 [MIN, MAX]
+        assert_eq!(after(60, TokenExpires::Auto), MIN_TOKEN_VALIDITY_SECS);
+        assert_eq!(after(now.timestamp() - 100, TokenExpires::Auto), MIN_TOKEN_VALIDITY_SECS);
+        assert_eq!(after(now.timestamp() + 7 * 24 * 3600, TokenExpires::Auto), MAX_TOKEN_VALIDITY_SECS);
+    }
+
+    #[test]
+    fn token_expires_override_skips_inference() {
+        let now: chrono::DateTime<Utc> = "2026-03-10T12:00:00Z".parse().unwrap();
+        let after = |expires, mode| (token_expiry(expires, mode, now) - now).num_seconds();
+        // 自动推断会把 7200000 当作毫秒时长，配置为秒后按秒时长（截断到上限）
+        assert_eq!(after(7_200_000, TokenExpires::Auto), 7200);
+        assert_eq!(after(7_200_000, TokenExpires::Seconds), MAX_TOKEN_VALIDITY_SECS);
+        assert_eq!(after(7_200_000, TokenExpires::Millis), 7200);
+        // 小数值按时间戳解释时已过期
+        assert_eq!(after(7200, TokenExpires::EpochSeconds), MIN_TOKEN_VALIDITY_SECS);
+        assert_eq!(after((now.timestamp() + 3600) * 1000, TokenExpires::EpochMillis), 3600);
+        assert_eq!(after(now.timestamp() + 3600, TokenExpires::EpochSeconds), 3600);
+
+        let center: Center = serde_yaml::from_str(
+            r#"{ name: "A", secretKey: "", url: "https://a.example.org", enabled: true, token_expires: epoch_millis }"#).unwrap();
+        assert_eq!(center.token_expires, TokenExpires::EpochMillis);
+    }
+
+    #[test]
+    fn validate_services_reports_missing_and_invalid() {
+        let service = |name: &str, url: &str| ServiceInfo { name: name.to_string(), url: url.to_string() };
+        assert!(validate_services("A", &[
+            service(SERVICE_DATASET_LIST, "https://a.example.org/list"),
+            service(SERVICE_DATASET_DETAILS, "http://a.example.org/details"),
+        ]).is_ok());
+
+        let err = validate_services("A", &[
+            service(SERVICE_DATASET_LIST, "ftp://a.example.org/list"),
+            service("OTHER", "https://a.example.org/other"),
+        ]).unwrap_err();
+        assert_eq!(err.missing, [SERVICE_DATASET_DETAILS]);
+        assert_eq!(err.invalid, [(SERVICE_DATASET_LIST.to_string(), "ftp://a.example.org/list".to_string())]);
+        assert_eq!(err.present, [SERVICE_DATASET_LIST, "OTHER"]);
+        assert_eq!(validate_services("A", &[]).unwrap_err().missing, REQUIRED_SERVICES);
+    }
+
+    /// 以 JSON 应答的桩服务：按请求目标（路径和查询参数）由 `respond` 返回响应体，None 时返回 404
+    async fn json_server(respond: fn(&str) -> Option<String>) -> Stub {
+        status_server(move |target| match respond(target) {
+            Some(body) => ("200 OK", body),
+            None => ("404 Not Found", String::new()),
+        }).await
+    }
+
+    /// 同 [`json_server`]，由 `respond` 同时给出状态行（如 "401 Unauthorized"）和响应体
+    async fn status_server(respond: impl Fn(&str) -> (&'static str, String) + Send + Sync + 'static) -> Stub {
+        delayed_server(Duration::ZERO, respond).await
+    }
+
+    /// 同 [`status_server`]，每个响应延迟 `delay` 后发送
+    async fn delayed_server(delay: Duration, respond: impl Fn(&str) -> (&'static str, String) + Send + Sync + 'static) -> Stub {
+        test_support::delayed_server(delay, move |request| {
+            let (status, body) = respond(&request.target);
+            response(status, &["Content-Type: application/json"], &body)
+        }).await
+    }
+
+    /// 数据中心指向桩服务的配置，MongoDB 指向不可用的地址（请求很快失败）
+    fn stub_config(centers: &str, dir: &Path) -> Config {
+        serde_yaml::from_str(&format!(r#"
+centers:
+{centers}
+mongodb: {{ uri: "mongodb://127.0.0.1:9/?serverSelectionTimeoutMS=100&connectTimeoutMS=100", database: "db" }}
+duckdb: {{ path: ":memory:" }}
+monitor:
+  fetch_interval_days: 30
+  check_interval_days: 7
+  http_timeout_secs: 5
+  max_concurrent: 10
+  persist_tokens: false
+  state_dir: "{dir}"
+  raw_responses_dir: "{dir}/raw"
+"#, centers = centers, dir = dir.display())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn unusable_tickets_become_center_errors() {
+        let stub = json_server(|target| Some(match target {
+            "/no-token" => r#"{"ticket":{"expires":3600},"serviceList":[{"name":"DATASET_LIST","version":"1","url":"http://x/list"}]}"#,
+            "/no-services" => r#"{"ticket":{"token":"t","expires":3600},"serviceList":[]}"#,
+            "/partial" => r#"{"ticket":{"token":"t","expires":3600},"serviceList":[{"name":"DATASET_LIST","version":"1","url":"http://x/list"}]}"#,
+            _ => return None,
+        }.to_string())).await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-auth-{}", std::process::id()));
+        let config = stub_config(&format!(r#"
+  - {{ name: "NoToken", secretKey: "k", url: "{base}/no-token", enabled: true }}
+  - {{ name: "NoServices", secretKey: "k", url: "{base}/no-services", enabled: true }}
+  - {{ name: "Partial", secretKey: "k", url: "{base}/partial", enabled: true }}"#, base = stub.base), &dir);
+        let db = MongoDB::new(&config.mongodb).await.unwrap();
+        let fetcher = DataFetcher::new(Arc::new(config));
+        let report = fetcher.fetch_all_center(&db).await;
+
+        let error = |name: &str| report.per_center.iter().find(|r| r.name == name).and_then(|r| r.list_error.clone()).unwrap();
+        assert_eq!(report.per_center.len(), 3);
+        assert!(error("NoToken").contains("认证响应中没有 token"), "{}", error("NoToken"));
+        assert!(error("NoServices").contains("serviceList 为空"), "{}", error("NoServices"));
+        assert!(error("Partial").contains(SERVICE_DATASET_DETAILS), "{}", error("Partial"));
+        assert!(report.per_center.iter().all(|r| !r.is_success()));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn renamed_services_map_to_standard_names() {
+        let stub = json_server(|target| Some(match target {
+            "/auth" => r#"{"ticket":{"token":"t","expires":3600},"serviceList":[
+                {"name":"DATASETLIST","version":"1","url":"http://x/list"},
+                {"name":"GET_DATASET_DETAILS","version":"1","url":"http://x/details"}]}"#,
+            _ => return None,
+        }.to_string())).await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-rename-{}", std::process::id()));
+        let center = |extra: &str| format!(r#"
+  - {{ name: "A", secretKey: "k", url: "{}/auth", enabled: true{} }}"#, stub.base, extra);
+
+        let fetcher = DataFetcher::new(Arc::new(stub_config(&center(", service_names: { DATASET_LIST: DATASETLIST }"), &dir)));
+        let token = fetcher.refresh_token("A", &format!("{}/auth", stub.base), "k").await.unwrap();
+        let names: Vec<&str> = token.services.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, [SERVICE_DATASET_LIST, SERVICE_DATASET_DETAILS]);
+        assert_eq!(token.services[0].url, "http://x/list");
+
+        // 没有配置映射时按缺少服务报错，并列出实际提供的服务名
+        let fetcher = DataFetcher::new(Arc::new(stub_config(&center(""), &dir)));
+        let Err(err) = fetcher.refresh_token("A", &format!("{}/auth", stub.base), "k").await else {
+            panic!("缺少 DATASET_LIST 时应报错");
+        };
+        let missing = err.downcast_ref::<MissingService>().unwrap();
+        assert_eq!(missing.missing, [SERVICE_DATASET_LIST]);
+        assert_eq!(missing.present, ["DATASETLIST", SERVICE_DATASET_DETAILS]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn batch_details_fall_back_to_single_requests() {
+        let stub = json_server(|target| Some(match target.replace("%2C", ",").as_str() {
+            "/auth" => r#"{"ticket":{"token":"t","expires":3600},"serviceList":[
+                {"name":"DATASET_LIST","version":"1","url":"http://x/list"},
+                {"name":"GET_DATASET_DETAILS","version":"1","url":"http://x/details"}]}"#,
+            // b 缺失，最后一条无法对应请求ID
+            "/details?id=a,b,c" => r#"[{"id":"a","@id":"raw-a"},{"@id":"c"},{"id":"zzz","@id":"raw-z"}]"#,
+            "/details?id=b" => r#"{"@id":"raw-b"}"#,
+            "/details?id=x,y" => r#"{"items":[]}"#,
+            "/details?id=x" => r#"{"@id":"raw-x"}"#,
+            _ => return None,
+        }.to_string())).await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-batch-{}", std::process::id()));
+        let config = stub_config(&format!(r#"
+  - {{ name: "Batch", secretKey: "k", url: "{}/auth", enabled: true, detail_batch_size: 3 }}"#, stub.base), &dir);
+        let center = config.centers[0].clone();
+        let fetcher = DataFetcher::new(Arc::new(config));
+        let details_url = format!("{}/details", stub.base);
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        let results = fetcher.fetch_dataset_details(&center, &details_url, ids(&["a", "b", "c"])).await;
+        let parsed: Vec<(String, String, Option<String>)> = results.into_iter()
+            .map(|(id, result)| {
+                let dataset = result.unwrap();
+                (id, dataset.raw_id, dataset.casdc_id)
+            })
+            .collect();
+        assert_eq!(parsed, vec![
+            ("a".to_string(), "raw-a".to_string(), Some("a".to_string())),
+            ("c".to_string(), "c".to_string(), Some("c".to_string())),
+            ("b".to_string(), "raw-b".to_string(), Some("b".to_string())),
+        ]);
+
+        // 批量响应不是数组时逐个请求，单个请求失败只影响该ID
+        let results = fetcher.fetch_dataset_details(&center, &details_url, ids(&["x", "y"])).await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].1.as_ref().unwrap().raw_id, "raw-x");
+        assert!(results[1].1.as_ref().unwrap_err().to_string().contains("404"));
+
+        let requests: Vec<String> = stub.targets().iter().map(|t| t.replace("%2C", ",")).collect();
+        assert_eq!(requests, ["/auth", "/details?id=a,b,c", "/details?id=b", "/details?id=x,y", "/details?id=x", "/details?id=y"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn center_reports_keep_progress_made_before_an_error() {
+        let stub = json_server(|target| Some(match target {
+            "/auth" => r#"{"ticket":{"token":"t","expires":3600},"serviceList":[
+                {"name":"DATASET_LIST","version":"1","url":"http://x/list"},
+                {"name":"GET_DATASET_DETAILS","version":"1","url":"http://x/details"}]}"#.to_string(),
+            "/list" => r#"[{"id":"a"},{"id":"b"},{"id":"c"}]"#.to_string(),
+            _ => return None,
+        })).await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-report-{}", std::process::id()));
+        let auth = format!("{}/auth", stub.base);
+        let config = stub_config(&format!(r#"
+  - {{ name: "Listed", secretKey: "k", url: "{auth}", enabled: true }}
+  - {{ name: "Disabled", secretKey: "k", url: "{auth}", enabled: false }}
+  - {{ name: "Down", secretKey: "k", url: "{base}/missing", enabled: true }}"#, auth = auth, base = stub.base), &dir);
+        let db = MongoDB::new(&config.mongodb).await.unwrap();
+        let fetcher = DataFetcher::new(Arc::new(config));
+        // 服务列表中的地址替换为桩服务
+        let report = {
+            let mut token = fetcher.get_or_refresh_token("Listed", &auth, "k").await.unwrap();
+            for service in &mut token.services {
+                service.url = service.url.replace("http://x", &stub.base);
+            }
+            fetcher.tokens.insert("Listed".to_string(), token);
+            fetcher.fetch_all_center(&db).await
+        };
+
+        let mut names: Vec<&str> = report.per_center.iter().map(|r| r.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["Down", "Listed"]);
+        let listed = report.per_center.iter().find(|r| r.name == "Listed").unwrap();
+        // 列表已获取，之后读取 MongoDB 失败
+        assert_eq!((listed.listed, listed.discovered, listed.processed), (3, 0, 0), "{:?}", listed.list_error);
+        assert!(!listed.is_success() && !listed.cancelled);
+        let down = report.per_center.iter().find(|r| r.name == "Down").unwrap();
+        assert_eq!(down.listed, 0);
+        assert!(down.list_error.as_deref().unwrap().contains("解析认证响应失败"), "{:?}", down.list_error);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn cancelled_fetch_starts_no_centers() {
+        let stub = json_server(|_| None).await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-cancel-{}", std::process::id()));
+        let config = stub_config(&format!(r#"
+  - {{ name: "A", secretKey: "k", url: "{base}/auth", enabled: true }}
+  - {{ name: "B", secretKey: "k", url: "{base}/auth", enabled: true }}"#, base = stub.base), &dir);
+        let db = MongoDB::new(&config.mongodb).await.unwrap();
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let fetcher = DataFetcher::new(Arc::new(config)).with_cancel_token(cancel);
+        assert!(fetcher.fetch_all_center(&db).await.per_center.is_empty());
+        assert!(stub.requests.lock().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// 对每个请求原样返回 `response` 的桩服务
+    async fn raw_server(response: &'static str) -> String {
+        test_support::stub_server(move |_| response.to_string()).await.base
+    }
+
+    #[tokio::test]
+    async fn response_size_is_capped() {
+        let stub = json_server(|target| match target {
+            "/small" => Some("0123456789".to_string()),
+            "/big" => Some("x".repeat(17)),
+            _ => None,
+        }).await;
+        // 没有 Content-Length 的分块响应只能边读边检查
+        let chunked = raw_server("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+            8\r\n01234567\r\n8\r\n89abcdef\r\n8\r\n01234567\r\n0\r\n\r\n").await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-cap-{}", std::process::id()));
+        let mut config = stub_config("  []", &dir);
+        config.monitor.max_response_bytes = 16;
+        let fetcher = DataFetcher::new(Arc::new(config));
+        let read = |url: String| {
+            let fetcher = &fetcher;
+            async move { fetcher.read_body("A", fetcher.client.get(url).send().await.unwrap()).await }
+        };
+
+        assert_eq!(read(format!("{}/small", stub.base)).await.unwrap(), "0123456789");
+        for url in [format!("{}/big", stub.base), chunked] {
+            let err = read(url).await.unwrap_err();
+            assert_eq!(err.downcast_ref::<ResponseTooLarge>().map(|e| e.limit), Some(16), "{:#}", err);
+        }
+        // 只计入读完的响应
+        assert_eq!(fetcher.counters("A").bytes.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn panic_messages_are_extracted() {
+        let message = |payload: Box<dyn std::any::Any + Send>| panic_message(payload.as_ref());
+        assert_eq!(message(Box::new("boom")), "boom");
+        assert_eq!(message(Box::new(format!("index {}", 3))), "index 3");
+        assert_eq!(message(Box::new(42)), "unknown panic");
+    }
+
+    #[tokio::test]
+    async fn centers_are_fetched_concurrently_up_to_the_limit() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        // 每个认证请求延迟返回，记录同时进行的请求数的峰值
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let (active, peak) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        {
+            let (active, peak) = (active.clone(), peak.clone());
+            tokio::spawn(async move {
+                while let Ok((mut socket, _)) = listener.accept().await {
+                    let (active, peak) = (active.clone(), peak.clone());
+                    tokio::spawn(async move {
+                        let mut buf = [0u8; 1024];
+                        let _ = socket.read(&mut buf).await;
+                        peak.fetch_max(active.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        active.fetch_sub(1, Ordering::SeqCst);
+                        let _ = socket.write_all(b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").await;
+                    });
+                }
+            });
+        }
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-concurrent-{}", std::process::id()));
+        let centers: String = (0..5)
+            .map(|i| format!("\n  - {{ name: \"C{}\", secretKey: \"k\", url: \"{}/auth\", enabled: true }}", i, base))
+            .collect();
+        for (concurrency, expected_peak) in [(2, 2), (8, 5)] {
+            peak.store(0, Ordering::SeqCst);
+            let mut config = stub_config(&centers, &dir);
+            config.monitor.fetch_center_concurrency = concurrency;
+            let db = MongoDB::new(&config.mongodb).await.unwrap();
+            let report = DataFetcher::new(Arc::new(config)).fetch_all_center(&db).await;
+            assert_eq!(report.per_center.len(), 5);
+            assert!(report.per_center.iter().all(|r| r.list_error.is_some()));
+            assert_eq!(peak.load(Ordering::SeqCst), expected_peak);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// 认证接口的响应，列表和详情服务在 center.invalid 上，经 [`proxied_fetcher`] 的代理发到桩服务
+    fn ticket(token: &str) -> String {
+        format!(r#"{{"ticket":{{"token":"{}","expires":3600}},"serviceList":[
+            {{"name":"DATASET_LIST","version":"1","url":"http://center.invalid/list"}},
+            {{"name":"GET_DATASET_DETAILS","version":"1","url":"http://center.invalid/details"}}]}}"#, token)
+    }
+
+    const AUTH_URL: &str = "http://center.invalid/auth";
+
+    /// 所有请求经代理发到桩服务的数据获取器，数据中心和服务列表可以使用固定的地址
+    fn proxied_fetcher(config: Config, stub: &Stub) -> DataFetcher {
+        let client = reqwest::Client::builder().proxy(reqwest::Proxy::all(&stub.base).unwrap()).build().unwrap();
+        DataFetcher::with_client(Arc::new(config), client)
+    }
+
+    /// 每次认证返回新的 token：t1、t2……
+    async fn counting_auth_server() -> Stub {
+        let issued = AtomicUsize::new(0);
+        status_server(move |target| match target {
+            AUTH_URL => ("200 OK", ticket(&format!("t{}", issued.fetch_add(1, Ordering::SeqCst) + 1))),
+            _ => ("404 Not Found", String::new()),
+        }).await
+    }
+
+    fn persisting_config(dir: &Path) -> Config {
+        let mut config = stub_config(&format!(r#"
+  - {{ name: "A", secretKey: "k", url: "{AUTH_URL}", enabled: true }}
+  - {{ name: "B", secretKey: "k", url: "{AUTH_URL}", enabled: true }}"#), dir);
+        config.monitor.persist_tokens = true;
+        config
+    }
+
+    #[tokio::test]
+    async fn persisted_tokens_are_reused_until_they_near_expiry() {
+        let stub = counting_auth_server().await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-persist-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let first = proxied_fetcher(persisting_config(&dir), &stub);
+        assert_eq!(first.get_or_refresh_token("A", AUTH_URL, "k").await.unwrap().token, "t1");
+
+        // 下一次运行新建的 DataFetcher 从文件加载，不再认证
+        let second = proxied_fetcher(persisting_config(&dir), &stub);
+        assert_eq!(second.get_or_refresh_token("A", AUTH_URL, "k").await.unwrap().token, "t1");
+        assert_eq!(stub.requests.lock().unwrap().len(), 1);
+
+        // 临近过期的 token 提前刷新
+        let path = dir.join("tokens.json");
+        let mut saved = load_tokens(&path).unwrap();
+        saved.get_mut("A").unwrap().expires_at = Utc::now() + chrono::Duration::seconds(TOKEN_REFRESH_MARGIN_SECS - 60);
+        std::fs::write(&path, serde_json::to_string(&saved).unwrap()).unwrap();
+        let third = proxied_fetcher(persisting_config(&dir), &stub);
+        assert_eq!(third.get_or_refresh_token("A", AUTH_URL, "k").await.unwrap().token, "t2");
+        assert_eq!(load_tokens(&path).unwrap()["A"].token, "t2");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn fetchers_sharing_a_token_file_keep_each_others_tokens() {
+        let stub = counting_auth_server().await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-shared-tokens-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (a, b) = (proxied_fetcher(persisting_config(&dir), &stub), proxied_fetcher(persisting_config(&dir), &stub));
+        let (token_a, token_b) = tokio::join!(a.get_or_refresh_token("A", AUTH_URL, "k"), b.get_or_refresh_token("B", AUTH_URL, "k"));
+        let (token_a, token_b) = (token_a.unwrap().token, token_b.unwrap().token);
+
+        let saved = load_tokens(&dir.join("tokens.json")).unwrap();
+        let mut names: Vec<&String> = saved.keys().collect();
+        names.sort();
+        assert_eq!(names, ["A", "B"]);
+        assert_eq!((saved["A"].token.as_str(), saved["B"].token.as_str()), (token_a.as_str(), token_b.as_str()));
+        // 较早加载的 DataFetcher 再次保存时不会用旧的 token 覆盖较新的
+        a.save_tokens().unwrap();
+        assert_eq!(load_tokens(&dir.join("tokens.json")).unwrap().len(), 2);
+        let leftovers: Vec<_> = std::fs::read_dir(&dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn unauthorized_responses_force_a_refresh() {
+        let issued = AtomicUsize::new(0);
+        let (list_calls, detail_calls) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        // 列表和详情接口第一次请求都返回 401
+        let stub = status_server(move |target| match target {
+            AUTH_URL => ("200 OK", ticket(&format!("t{}", issued.fetch_add(1, Ordering::SeqCst) + 1))),
+            "http://center.invalid/list" if list_calls.fetch_add(1, Ordering::SeqCst) == 0 => ("401 Unauthorized", String::new()),
+            "http://center.invalid/list" => ("200 OK", r#"[{"id":"a"}]"#.to_string()),
+            "http://center.invalid/details?id=a" if detail_calls.fetch_add(1, Ordering::SeqCst) == 0 => ("401 Unauthorized", String::new()),
+            "http://center.invalid/details?id=a" => ("200 OK", r#"{"@id":"raw-a"}"#.to_string()),
+            _ => ("404 Not Found", String::new()),
+        }).await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-unauthorized-{}", std::process::id()));
+        let config = persisting_config(&dir);
+        let center = config.centers[0].clone();
+        let fetcher = proxied_fetcher(config, &stub);
+
+        let page = fetcher.request_dataset_list(&center, &[]).await.unwrap();
+        assert_eq!(page.ids, ["a"]);
+        assert_eq!(fetcher.cached_token("A").unwrap().token, "t2");
+        let results = fetcher.fetch_dataset_details(&center, "http://center.invalid/details", vec!["a".to_string()]).await;
+        assert_eq!(results[0].1.as_ref().unwrap().raw_id, "raw-a");
+        assert_eq!(fetcher.cached_token("A").unwrap().token, "t3");
+        assert_eq!(stub.targets(), [
+            AUTH_URL, "http://center.invalid/list", AUTH_URL, "http://center.invalid/list",
+            "http://center.invalid/details?id=a", AUTH_URL, "http://center.invalid/details?id=a",
+        ]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn concurrent_token_requests_share_one_refresh() {
+        let stub = counting_auth_server().await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-stampede-{}", std::process::id()));
+        let fetcher = proxied_fetcher(stub_config("  []", &dir), &stub);
+        let tokens = futures::future::join_all((0..50).map(|_| fetcher.get_or_refresh_token("A", AUTH_URL, "k"))).await;
+        assert!(tokens.into_iter().all(|token| token.unwrap().token == "t1"));
+        assert_eq!(stub.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn only_rejected_authentication_is_cached() {
+        let stub = status_server(|target| match target {
+            "http://center.invalid/rejected" => ("401 Unauthorized", r#"{"error":"invalid secretKey"}"#.to_string()),
+            "http://center.invalid/empty" => ("200 OK", r#"{"ticket":{"token":""},"serviceList":[]}"#.to_string()),
+            _ => ("503 Service Unavailable", String::new()),
+        }).await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-auth-cache-{}", std::process::id()));
+        let fetcher = proxied_fetcher(stub_config("  []", &dir), &stub);
+        let requests = || stub.requests.lock().unwrap().len();
+
+        for (name, url) in [("R", "http://center.invalid/rejected"), ("E", "http://center.invalid/empty")] {
+            let before = requests();
+            assert!(fetcher.get_or_refresh_token(name, url, "k").await.is_err());
+            let err = fetcher.get_or_refresh_token(name, url, "k").await.err().unwrap();
+            assert!(err.to_string().contains("暂不重试"), "{:#}", err);
+            assert_eq!(requests() - before, 1, "{}", name);
+        }
+
+        // 接口暂时不可用和网络错误每次都重试
+        let before = requests();
+        for _ in 0..2 {
+            let err = fetcher.get_or_refresh_token("U", "http://center.invalid/unavailable", "k").await.err().unwrap();
+            assert_eq!(err.downcast_ref::<AuthUnavailable>().map(|e| e.status), Some(503), "{:#}", err);
+        }
+        assert_eq!(requests() - before, 2);
+        let down = DataFetcher::new(Arc::new(stub_config("  []", &dir)));
+        for _ in 0..2 {
+            let err = down.get_or_refresh_token("N", "http://127.0.0.1:9/auth", "k").await.err().unwrap();
+            assert!(!err.to_string().contains("暂不重试"), "{:#}", err);
+        }
+        assert!(down.auth_failures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn injected_client_serves_token_cache_branches() {
+        let stub = counting_auth_server().await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-token-cache-{}", std::process::id()));
+        let fetcher = proxied_fetcher(stub_config("  []", &dir), &stub);
+        let requests = || stub.requests.lock().unwrap().len();
+
+        // 没有缓存时请求认证接口，之后命中缓存
+        assert_eq!(fetcher.get_or_refresh_token("A", AUTH_URL, "k").await.unwrap().token, "t1");
+        assert_eq!(fetcher.get_or_refresh_token("A", AUTH_URL, "k").await.unwrap().token, "t1");
+        assert_eq!(requests(), 1);
+
+        // 临近过期时刷新
+        fetcher.tokens.get_mut("A").unwrap().expires_at = Utc::now() + chrono::Duration::seconds(60);
+        assert_eq!(fetcher.get_or_refresh_token("A", AUTH_URL, "k").await.unwrap().token, "t2");
+        assert_eq!(requests(), 2);
+        // 其他数据中心各自缓存
+        assert_eq!(fetcher.get_or_refresh_token("B", AUTH_URL, "k").await.unwrap().token, "t3");
+        assert_eq!(fetcher.get_or_refresh_token("A", AUTH_URL, "k").await.unwrap().token, "t2");
+        assert_eq!(requests(), 3);
+    }
+
+    /// 详情接口每个请求耗时 100ms，d7 不存在
+    async fn slow_details_server() -> Stub {
+        delayed_server(Duration::from_millis(100), |target| match target {
+            AUTH_URL => ("200 OK", ticket("t")),
+            "http://center.invalid/list" => ("200 OK", serde_json::to_string(&(0..12).map(|i| serde_json::json!({ "id": format!("d{}", i) })).collect::<Vec<_>>()).unwrap()),
+            "http://center.invalid/details?id=d7" => ("404 Not Found", String::new()),
+            target => match target.strip_prefix("http://center.invalid/details?id=") {
+                Some(id) => ("200 OK", format!(r#"{{"@id":"raw-{}"}}"#, id)),
+                None => ("404 Not Found", String::new()),
+            },
+        }).await
+    }
+
+    fn slow_details_config(dir: &Path, fetch_max_concurrent: usize) -> Config {
+        let mut config = stub_config(&format!(r#"
+  - {{ name: "A", secretKey: "k", url: "{AUTH_URL}", enabled: true }}"#), dir);
+        config.monitor.fetch_max_concurrent = fetch_max_concurrent;
+        config
+    }
+
+    /// 试运行获取 d0..d11 的详情，返回 (解析成功数, 失败的ID, 耗时)
+    async fn plan_details(stub: &Stub, dir: &Path, fetch_max_concurrent: usize) -> (usize, Vec<String>, Duration) {
+        let config = slow_details_config(dir, fetch_max_concurrent);
+        let center = config.centers[0].clone();
+        let db = MongoDB::new(&config.mongodb).await.unwrap();
+        let fetcher = proxied_fetcher(config, stub);
+        let mut plan = FetchPlan { center_name: "A".to_string(), ..FetchPlan::default() };
+        let started = std::time::Instant::now();
+        let (count, _) = fetcher.fetch_pending_details(&center, &db, "http://center.invalid/details", ids(0..12), Some(&mut plan)).await.unwrap();
+        assert_eq!(count, 0, "试运行不写库");
+        (plan.parsed, plan.failures.into_iter().map(|f| f.id).collect(), started.elapsed())
+    }
+
+    #[tokio::test]
+    async fn pending_details_are_fetched_concurrently() {
+        let stub = slow_details_server().await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-slow-details-{}", std::process::id()));
+        let (parsed, failed, sequential) = plan_details(&stub, &dir, 1).await;
+        assert_eq!((parsed, failed), (11, vec!["d7".to_string()]));
+        assert!(sequential >= Duration::from_millis(1200), "{:?}", sequential);
+
+        // 6 个并发时约两轮请求的时间，完成顺序不影响结果
+        let (parsed, failed, concurrent) = plan_details(&stub, &dir, 6).await;
+        assert_eq!((parsed, failed), (11, vec!["d7".to_string()]));
+        assert!(concurrent < sequential / 2, "{:?} / {:?}", concurrent, sequential);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// 需要 MongoDB 的测试在设置了 DATASET_MONITOR_TEST_MONGODB_URI 时才运行：把 `config` 指向单独的空库，
+    /// 返回直接访问该库的句柄
+    async fn test_database(config: &mut Config, tag: &str) -> Option<mongodb::Database> {
+        config.mongodb.uri = std::env::var("DATASET_MONITOR_TEST_MONGODB_URI").ok()?;
+        config.mongodb.database = format!("dataset_monitor_test_{}_{}", tag, std::process::id());
+        let database = mongodb::Client::with_uri_str(&config.mongodb.uri).await.unwrap().database(&config.mongodb.database);
+        database.drop().await.unwrap();
+        Some(database)
+    }
+
+    #[tokio::test]
+    async fn concurrent_details_keep_failed_ids_pending() {
+        let stub = slow_details_server().await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-pending-details-{}", std::process::id()));
+        let mut config = slow_details_config(&dir, 6);
+        let Some(database) = test_database(&mut config, "details").await else {
+            return;
+        };
+        let center = config.centers[0].clone();
+        let db = MongoDB::new(&config.mongodb).await.unwrap();
+        let fetcher = proxied_fetcher(config, &stub);
+
+        let report = fetcher.fetch_center(&center, &db).await;
+        assert!(report.is_success(), "{:?}", report.list_error);
+        assert_eq!((report.listed, report.discovered, report.processed, report.detail_failures), (12, 12, 11, 1));
+        assert_eq!(db.get_unprocessed_ids("A", None).await.unwrap(), ["d7"]);
+        database.drop().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn dataset_list_requests_follow_the_configured_method() {
+        let stub = status_server(|target| match target {
+            AUTH_URL => ("200 OK", ticket("t")),
+            "http://center.invalid/list" | "http://center.invalid/list?page=1&pageSize=2" => ("200 OK", r#"[{"id":"a"}]"#.to_string()),
+            _ => ("404 Not Found", String::new()),
+        }).await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-list-method-{}", std::process::id()));
+        let config = stub_config(&format!(r#"
+  - {{ name: "Get", secretKey: "k", url: "{AUTH_URL}", enabled: true }}
+  - {{ name: "PostEmpty", secretKey: "k", url: "{AUTH_URL}", enabled: true, list_method: POST }}
+  - {{ name: "Post", secretKey: "k", url: "{AUTH_URL}", enabled: true, list_method: POST, list_body: {{ status: "published", page: {{ size: 2 }} }} }}"#), &dir);
+        config.validate().unwrap();
+        let centers = config.centers.clone();
+        let fetcher = proxied_fetcher(config, &stub);
+
+        for center in &centers {
+            let page = fetcher.request_dataset_list(center, &[]).await.unwrap();
+            assert_eq!(page.ids, ["a"], "{}", center.name);
+        }
+        // 查询参数（如分页）与请求体一起发送
+        fetcher.request_dataset_list(&centers[2], &[("page", "1".to_string()), ("pageSize", "2".to_string())]).await.unwrap();
+
+        let requests = stub.requests.lock().unwrap();
+        let lists: Vec<(&str, &str, Option<&str>, &str)> = requests.iter()
+            .filter(|request| request.target != AUTH_URL)
+            .map(|request| (request.method.as_str(), request.target.as_str(), request.header("content-type"), std::str::from_utf8(&request.body).unwrap()))
+            .collect();
+        assert_eq!(lists, [
+            ("GET", "http://center.invalid/list", None, ""),
+            ("POST", "http://center.invalid/list", None, ""),
+            ("POST", "http://center.invalid/list", Some("application/json"), r#"{"status":"published","page":{"size":2}}"#),
+            ("POST", "http://center.invalid/list?page=1&pageSize=2", Some("application/json"), r#"{"status":"published","page":{"size":2}}"#),
+        ]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// 库中所有集合的全部文档，按集合名和 _id 排序
+    async fn database_contents(database: &mongodb::Database) -> Vec<(String, Vec<mongodb::bson::Document>)> {
+        use futures::TryStreamExt;
+        let mut names = database.list_collection_names().await.unwrap();
+        names.sort();
+        let mut contents = Vec::new();
+        for name in names {
+            let documents = database.collection::<mongodb::bson::Document>(&name)
+                .find(mongodb::bson::doc! {})
+                .sort(mongodb::bson::doc! { "_id": 1 })
+                .await.unwrap()
+                .try_collect().await.unwrap();
+            contents.push((name, documents));
+        }
+        contents
+    }
+
+    #[tokio::test]
+    async fn dry_runs_leave_mongodb_untouched() {
+        let stub = slow_details_server().await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-dry-run-{}", std::process::id()));
+        let mut config = slow_details_config(&dir, 4);
+        let Some(database) = test_database(&mut config, "dry_run").await else {
+            return;
+        };
+        let center = config.centers[0].clone();
+        let db = MongoDB::new(&config.mongodb).await.unwrap();
+        // d0、d1 已处理，d2、d7 待处理，其余ID库中没有
+        db.save_new_dataset_ids("A", &ids(0..3)).await.unwrap();
+        db.save_new_dataset_ids("A", &["d7".to_string()]).await.unwrap();
+        db.update_processed_ids("A", &ids(0..2)).await.unwrap();
+        let before = database_contents(&database).await;
+        let fetcher = proxied_fetcher(config, &stub);
+
+        let plan = fetcher.plan_center(&center, &db).await.unwrap();
+        assert_eq!(database_contents(&database).await, before);
+        let mut new_ids = plan.new_ids.clone();
+        new_ids.sort();
+        assert_eq!((plan.listed, new_ids), (12, ["d10", "d11", "d3", "d4", "d5", "d6", "d8", "d9"].map(String::from).to_vec()));
+        // 待处理的 d2、d7 和新ID都会请求详情，d7 不存在
+        assert_eq!(plan.parsed, 9);
+        assert_eq!(plan.failures.iter().map(|f| f.id.as_str()).collect::<Vec<_>>(), ["d7"]);
+        database.drop().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn fetch_metrics_are_written_per_center() {
+        const LIST: &str = r#"[{"id":"a"},{"id":"b"}]"#;
+        const REJECTED: &str = r#"{"error":"token expired"}"#;
+        let stub = status_server(|target| match target {
+            AUTH_URL => ("200 OK", ticket("t")),
+            "http://center.invalid/auth-b" => ("200 OK", ticket("t").replace("/list", "/list-b")),
+            "http://center.invalid/list" => ("200 OK", LIST.to_string()),
+            "http://center.invalid/list-b" => ("401 Unauthorized", REJECTED.to_string()),
+            _ => ("404 Not Found", String::new()),
+        }).await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-fetch-metrics-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut config = stub_config(&format!(r#"
+  - {{ name: "A", secretKey: "k", url: "{AUTH_URL}", enabled: true }}
+  - {{ name: "B", secretKey: "k", url: "http://center.invalid/auth-b", enabled: true }}"#), &dir);
+        config.duckdb.path = dir.join("monitor.db").display().to_string();
+        let db = MongoDB::new(&config.mongodb).await.unwrap();
+        let fetcher = proxied_fetcher(config.clone(), &stub);
+
+        let started = Utc::now();
+        let report = fetcher.fetch_all_center(&db).await;
+        assert_eq!(report.per_center.len(), 2);
+
+        let duckdb = DuckDB::new(&config.duckdb.path).await.unwrap();
+        let mut rows = duckdb.get_fetch_metrics(started - chrono::Duration::minutes(1), Utc::now() + chrono::Duration::minutes(1)).await.unwrap();
+        rows.sort_by(|a, b| a.center_name.cmp(&b.center_name));
+        let rows: Vec<_> = rows.iter()
+            .map(|m| (m.center_name.as_str(), m.list_requests, m.detail_requests, m.avg_detail_ms, m.bytes, m.errors))
+            .collect();
+        let ticket_len = ticket("t").len() as u64;
+        assert_eq!(rows, [
+            // 列表已获取，之后读取 MongoDB 失败，没有请求详情
+            ("A", 1, 0, None, ticket_len + LIST.len() as u64, 0),
+            // 401 后刷新 token 重试一次，两次认证和两次被拒绝的响应都计入字节数，整个列表请求算一次失败
+            ("B", 1, 0, None, 2 * (ticket_len + "-b".len() as u64 + REJECTED.len() as u64), 1),
+        ]);
+        drop(duckdb);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn updated_since_follows_the_reconciliation_cadence() {
+        let center = |yaml: &str| -> Center {
+            serde_yaml::from_str(&format!(r#"{{ name: "A", secretKey: "", url: "https://a.example.org", enabled: true, {} }}"#, yaml)).unwrap()
+        };
+        let incremental = center("supports_updated_since: true, full_fetch_every: 3");
+        // 审计记录，最近的在前；与 MongoDB::count_incremental_since_full 一样最多数到 full_fetch_every
+        let mut audits: Vec<FetchAudit> = Vec::new();
+        let mut kinds = String::new();
+        for run in 0..9 {
+            let runs = audits.iter().take(incremental.full_fetch_every)
+                .take_while(|a| a.outcome.as_ref().is_some_and(|o| o.incremental))
+                .count();
+            let since = incremental_since(&incremental, audits.first(), runs);
+            if let Some(since) = since {
+                assert_eq!(since, audits[0].started_at, "增量获取从上次成功获取的开始时间起");
+            }
+            kinds.push(if since.is_some() { 'I' } else { 'F' });
+            let started_at = Utc::now() + chrono::Duration::hours(run);
+            audits.insert(0, FetchAudit {
+                center_name: "A".to_string(),
+                started_at,
+                finished_at: started_at,
+                success: true,
+                outcome: Some(CenterFetchReport { incremental: since.is_some(), ..CenterFetchReport::default() }),
+                error: None,
+            });
+        }
+        // 第一次没有成功获取的记录，完整获取；之后每 3 次增量获取做一次完整获取
+        assert_eq!(kinds, "FIIIFIIIF");
+
+        // 不支持 updatedSince 时总是完整获取
+        let full = center("full_fetch_every: 3");
+        assert_eq!(incremental_since(&full, audits.first(), 0), None);
+    }
+
+    #[tokio::test]
+    async fn incremental_fetches_requeue_changed_ids() {
+        let stub = status_server(|target| match target.split_once('?').map_or(target, |(path, _)| path) {
+            AUTH_URL => ("200 OK", ticket("t")),
+            "http://center.invalid/list" => ("200 OK", r#"[{"id":"a"},{"id":"b"}]"#.to_string()),
+            "http://center.invalid/details" => ("200 OK", format!(r#"{{"@id":"raw-{}"}}"#, target.rsplit('=').next().unwrap())),
+            _ => ("404 Not Found", String::new()),
+        }).await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-incremental-{}", std::process::id()));
+        let mut config = stub_config(&format!(r#"
+  - {{ name: "A", secretKey: "k", url: "{AUTH_URL}", enabled: true, supports_updated_since: true, full_fetch_every: 1 }}"#), &dir);
+        let Some(database) = test_database(&mut config, "incremental").await else {
+            return;
+        };
+        let center = config.centers[0].clone();
+        let db = MongoDB::new(&config.mongodb).await.unwrap();
+        let fetcher = proxied_fetcher(config, &stub);
+
+        // 完整获取、增量获取，之后达到 full_fetch_every 再次完整获取
+        let mut runs = Vec::new();
+        for _ in 0..3 {
+            let report = fetcher.fetch_center(&center, &db).await;
+            assert!(report.is_success(), "{:?}", report.list_error);
+            runs.push((report.incremental, report.discovered, report.requeued, report.processed));
+        }
+        assert_eq!(runs, [(false, 2, 0, 2), (true, 0, 2, 2), (false, 0, 0, 0)]);
+        let lists: Vec<bool> = stub.targets().iter()
+            .filter(|target| target.starts_with("http://center.invalid/list"))
+            .map(|target| target.contains("updatedSince="))
+            .collect();
+        assert_eq!(lists, [false, true, false]);
+        database.drop().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn two_worker_pools_drain_a_backlog_once() {
+        let stub = delayed_server(Duration::from_millis(20), |target| match target.split_once('?') {
+            None if target == AUTH_URL => ("200 OK", ticket("t")),
+            Some(("http://center.invalid/details", query)) => ("200 OK", format!(r#"{{"@id":"raw-{}","@type":"Dataset"}}"#, query.trim_start_matches("id="))),
+            _ => ("404 Not Found", String::new()),
+        }).await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-detail-workers-{}", std::process::id()));
+        let mut config = stub_config(&format!(r#"
+  - {{ name: "A", secretKey: "k", url: "{AUTH_URL}", enabled: true }}
+  - {{ name: "B", secretKey: "k", url: "{AUTH_URL}", enabled: true }}"#), &dir);
+        config.monitor.detail_workers = 2;
+        config.monitor.detail_worker_batch = 4;
+        config.monitor.detail_worker_idle_secs = 1;
+        let Some(database) = test_database(&mut config, "workers").await else {
+            return;
+        };
+        let db = MongoDB::new(&config.mongodb).await.unwrap();
+        db.save_new_dataset_ids("A", &ids(0..15)).await.unwrap();
+        db.save_new_dataset_ids("B", &ids(15..30)).await.unwrap();
+
+        // 两个进程各运行两个工作任务，积压处理完后取消
+        let cancel = CancellationToken::new();
+        let first = proxied_fetcher(config.clone(), &stub).with_cancel_token(cancel.clone());
+        let second = proxied_fetcher(config.clone(), &stub).with_cancel_token(cancel.clone());
+        let drained = async {
+            loop {
+                let (a, b) = (db.count_ids_by_status("A").await.unwrap(), db.count_ids_by_status("B").await.unwrap());
+                if a.processed + b.processed == 30 {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            cancel.cancel();
+        };
+        tokio::time::timeout(Duration::from_secs(30), async {
+            tokio::join!(first.run_detail_workers(&db, 2), second.run_detail_workers(&db, 2), drained)
+        }).await.expect("积压没有在 30 秒内处理完");
+
+        let mut requested: Vec<String> = stub.targets().iter()
+            .filter_map(|target| target.strip_prefix("http://center.invalid/details?id="))
+            .map(String::from)
+            .collect();
+        requested.sort();
+        let mut expected = ids(0..30);
+        expected.sort();
+        assert_eq!(requested, expected, "每个ID只请求一次详情");
+        for (center, range) in [("A", 0..15), ("B", 15..30)] {
+            let backlog = db.count_ids_by_status(center).await.unwrap();
+            assert_eq!((backlog.pending, backlog.in_progress, backlog.processed), (0, 0, 15), "{}", center);
+            let mut stored: Vec<String> = db.get_datasets(&config.collection_name(center)).await.unwrap()
+                .into_iter().map(|d| d.raw_id).collect();
+            stored.sort();
+            let mut raw_ids: Vec<String> = range.map(|i| format!("raw-d{}", i)).collect();
+            raw_ids.sort();
+            assert_eq!(stored, raw_ids);
+        }
+        database.drop().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
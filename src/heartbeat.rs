@@ -0,0 +1,193 @@
+use crate::config::{Center, HeartbeatConfig};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// 发送心跳的任务种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeartbeatJob {
+    Fetch,
+    Monitor,
+}
+
+impl HeartbeatJob {
+    pub fn name(self) -> &'static str {
+        match self {
+            HeartbeatJob::Fetch => "data_fetch",
+            HeartbeatJob::Monitor => "data_monitor",
+        }
+    }
+}
+
+/// 外部存活监控（healthchecks.io 风格）心跳
+///
+/// 运行成功后请求心跳地址并写入本地心跳文件；心跳失败只记录日志，不影响任务本身。
+/// 每个数据中心的任务各自一个心跳，某个数据中心停止运行时不会被其他数据中心的心跳掩盖。
+pub struct Heartbeat {
+    client: reqwest::Client,
+    url: Option<String>,
+    ping_start: bool,
+    file: Option<PathBuf>,
+}
+
+impl Heartbeat {
+    pub fn new(url: Option<String>, ping_start: bool, file: Option<PathBuf>) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .expect("failed to build http client");
+        Self { client, url, ping_start, file }
+    }
+
+    /// `center` 的 `job` 任务的心跳：地址优先使用数据中心单独配置的，心跳文件为 `{任务名}.heartbeat`，
+    /// 任务名与调度任务相同（如 `data_fetch-{数据中心}`）
+    pub fn from_config(config: &HeartbeatConfig, job: HeartbeatJob, center: &Center) -> Self {
+        let url = match job {
+            HeartbeatJob::Fetch => center.fetch_heartbeat_url.clone().or_else(|| config.fetch_url.clone()),
+            HeartbeatJob::Monitor => center.monitor_heartbeat_url.clone().or_else(|| config.monitor_url.clone()),
+        };
+        let file = config.file_dir.as_ref()
+            .map(|dir| PathBuf::from(dir).join(format!("{}-{}.heartbeat", job.name(), center.name)));
+        Self::new(url, config.ping_start, file)
+    }
+
+    /// 运行开始时请求 `{url}/start`，便于外部服务统计运行时长
+    pub async fn start(&self) {
+        if !self.ping_start {
+            return;
+        }
+        if let Some(url) = &self.url {
+            self.ping(&format!("{}/start", url.trim_end_matches('/'))).await;
+        }
+    }
+
+    pub async fn success(&self) {
+        if let Some(url) = &self.url {
+            self.ping(url).await;
+        }
+        if let Some(file) = &self.file
+            && let Err(e) = Self::write_file(file)
+        {
+            warn!("写入心跳文件 {} 失败: {}", file.display(), e);
+        }
+    }
+
+    async fn ping(&self, url: &str) {
+        match self.client.get(url).send().await {
+            Ok(response) if response.status().is_success() => info!("心跳发送成功: {}", url),
+            Ok(response) => warn!("心跳发送失败: {}, HTTP状态码: {}", url, response.status()),
+            Err(e) => warn!("心跳发送失败: {}, 错误: {}", url, e),
+        }
+    }
+
+    fn write_file(file: &PathBuf) -> std::io::Result<()> {
+        if let Some(parent) = file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(file, chrono::Utc::now().to_rfc3339())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{response, stub_server, Stub};
+
+    /// 心跳桩服务，返回心跳 URL 和桩服务
+    async fn ping_server() -> (String, Stub) {
+        let stub = stub_server(|_| response("200 OK", &[], "OK")).await;
+        (format!("{}/ping/abc", stub.base), stub)
+    }
+
+    fn center(yaml: &str) -> Center {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn each_center_has_its_own_url_and_file() {
+        let config: HeartbeatConfig = serde_yaml::from_str(
+            "{ fetch_url: 'http://hc/fetch', monitor_url: 'http://hc/monitor', file_dir: /var/lib/dm }").unwrap();
+        let a = center(r#"{ name: "A", secretKey: "", url: "", enabled: true, fetch_heartbeat_url: "http://hc/fetch-a" }"#);
+        let b = center(r#"{ name: "B", secretKey: "", url: "", enabled: true }"#);
+
+        let heartbeat = Heartbeat::from_config(&config, HeartbeatJob::Fetch, &a);
+        assert_eq!(heartbeat.url.as_deref(), Some("http://hc/fetch-a"));
+        assert_eq!(heartbeat.file, Some(PathBuf::from("/var/lib/dm/data_fetch-A.heartbeat")));
+        let heartbeat = Heartbeat::from_config(&config, HeartbeatJob::Fetch, &b);
+        assert_eq!(heartbeat.url.as_deref(), Some("http://hc/fetch"));
+        assert_eq!(heartbeat.file, Some(PathBuf::from("/var/lib/dm/data_fetch-B.heartbeat")));
+        // 数据中心只配置了数据获取的地址，URL监测仍使用全局地址
+        let heartbeat = Heartbeat::from_config(&config, HeartbeatJob::Monitor, &a);
+        assert_eq!(heartbeat.url.as_deref(), Some("http://hc/monitor"));
+        assert_eq!(heartbeat.file, Some(PathBuf::from("/var/lib/dm/data_monitor-A.heartbeat")));
+    }
+
+    #[tokio::test]
+    async fn start_and_success_ping_and_write_the_file() {
+        let (url, stub) = ping_server().await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-heartbeat-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = HeartbeatConfig {
+            fetch_url: Some(url),
+            monitor_url: None,
+            ping_start: true,
+            file_dir: Some(dir.display().to_string()),
+        };
+        let heartbeat = Heartbeat::from_config(&config, HeartbeatJob::Fetch, &center(r#"{ name: "A", secretKey: "", url: "", enabled: true }"#));
+
+        heartbeat.start().await;
+        let file = dir.join("data_fetch-A.heartbeat");
+        assert!(!file.exists());
+        let before = chrono::Utc::now();
+        heartbeat.success().await;
+        assert_eq!(stub.targets(), ["/ping/abc/start", "/ping/abc"]);
+        let written: chrono::DateTime<chrono::Utc> = std::fs::read_to_string(&file).unwrap().parse().unwrap();
+        assert!(written >= before);
+
+        // 不请求开始心跳时 start 什么也不做；没有地址时 success 仍写文件
+        let quiet = Heartbeat::from_config(&HeartbeatConfig { ping_start: false, ..config.clone() }, HeartbeatJob::Fetch,
+            &center(r#"{ name: "B", secretKey: "", url: "", enabled: true }"#));
+        quiet.start().await;
+        let monitor = Heartbeat::from_config(&config, HeartbeatJob::Monitor, &center(r#"{ name: "A", secretKey: "", url: "", enabled: true }"#));
+        monitor.start().await;
+        monitor.success().await;
+        assert_eq!(stub.requests.lock().unwrap().len(), 2);
+        assert!(dir.join("data_monitor-A.heartbeat").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// 与 data_fetch 和 data_monitor 一样在租约内发送开始心跳：锁被其他实例持有时不发送
+    #[tokio::test]
+    async fn start_is_only_sent_after_the_lease_is_acquired() {
+        let Ok(uri) = std::env::var("DATASET_MONITOR_TEST_MONGODB_URI") else {
+            return;
+        };
+        let (url, stub) = ping_server().await;
+        let config = crate::config::MongoDBConfig {
+            uri,
+            database: format!("dataset_monitor_test_heartbeat_{}", std::process::id()),
+            legacy_collection_names: false,
+        };
+        let db = std::sync::Arc::new(crate::db::mongodb::MongoDB::new(&config).await.unwrap());
+        let heartbeat = Heartbeat::new(Some(url), true, None);
+        let ttl = Duration::from_secs(30);
+        assert!(db.try_acquire_lock("data_fetch-A", "other-instance", ttl).await.unwrap());
+
+        let run = |name: &'static str| {
+            let (db, heartbeat) = (db.clone(), &heartbeat);
+            async move {
+                crate::scheduler::run_with_lease(&db, name, ttl, async {
+                    heartbeat.start().await;
+                    Ok(())
+                }).await.unwrap()
+            }
+        };
+        assert_eq!(run("data_fetch-A").await, None);
+        assert!(stub.requests.lock().unwrap().is_empty());
+        assert_eq!(run("data_fetch-B").await, Some(()));
+        assert_eq!(stub.targets(), ["/ping/abc/start"]);
+
+        db.release_lock("data_fetch-A", "other-instance").await.unwrap();
+        mongodb::Client::with_uri_str(&config.uri).await.unwrap().database(&config.database).drop().await.unwrap();
+    }
+}
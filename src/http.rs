@@ -0,0 +1,230 @@
+//! HTTP客户端的构建：请求数据中心接口和检查数据集URL两类客户端的超时、代理、证书校验、重定向策略集中在这里，
+//! 两者的差异只在 [`ClientFactory::builder`] 中按 [`ClientProfile`] 区分
+
+use crate::config::{Config, HttpConfig, TlsPolicy};
+use crate::models::redirect_policy;
+use crate::timing::{ConnectTimingLayer, TimedResolver};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// 客户端的用途
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientProfile {
+    /// 请求数据中心的认证、列表和详情接口，由客户端按 monitor.max_redirects 跟随重定向
+    CenterApi,
+    /// 检查数据集URL：不自动跟随重定向（由 HttpChecker 逐跳跟随），记录 DNS 和连接耗时
+    UrlCheck,
+}
+
+pub struct ClientFactory<'a> {
+    http: &'a HttpConfig,
+    timeout: Duration,
+    max_redirects: usize,
+}
+
+impl<'a> ClientFactory<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Self {
+            http: &config.http,
+            timeout: Duration::from_secs(config.monitor.http_timeout_secs),
+            max_redirects: config.monitor.max_redirects,
+        }
+    }
+
+    /// 该用途的证书校验方式
+    pub fn tls(&self, profile: ClientProfile) -> TlsPolicy {
+        match profile {
+            ClientProfile::CenterApi => self.http.center_api_tls,
+            ClientProfile::UrlCheck => self.http.url_check_tls,
+        }
+    }
+
+    /// 按配置设置好的 builder，调用方可以在构建前继续调整
+    pub fn builder(&self, profile: ClientProfile) -> reqwest::ClientBuilder {
+        let mut builder = reqwest::Client::builder().timeout(self.timeout);
+        if let Some(user_agent) = &self.http.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        // 配置加载时已校验
+        if let Some(proxy) = &self.http.proxy {
+            match reqwest::Proxy::all(proxy) {
+                Ok(proxy) => builder = builder.proxy(proxy),
+                Err(e) => warn!("http.proxy 无效，已忽略: {}", e),
+            }
+        }
+        if let Some(max_idle) = self.http.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(secs) = self.http.pool_idle_timeout_secs {
+            builder = builder.pool_idle_timeout(Duration::from_secs(secs));
+        }
+        let lenient = self.tls(profile) == TlsPolicy::Lenient;
+        builder = builder
+            .danger_accept_invalid_certs(lenient)
+            .danger_accept_invalid_hostnames(lenient);
+        match profile {
+            ClientProfile::CenterApi => builder.redirect(redirect_policy(self.max_redirects)),
+            ClientProfile::UrlCheck => builder
+                .redirect(reqwest::redirect::Policy::none())
+                .dns_resolver(Arc::new(TimedResolver))
+                .connector_layer(ConnectTimingLayer),
+        }
+    }
+
+    pub fn build(&self, profile: ClientProfile) -> reqwest::Client {
+        self.builder(profile).build().expect("failed to build http client")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{keep_alive, serve, stub_server, Requests};
+
+    fn config(http: &str) -> Config {
+        serde_yaml::from_str(&format!(r#"
+centers: []
+mongodb: {{ uri: "mongodb://127.0.0.1:9", database: "db" }}
+duckdb: {{ path: ":memory:" }}
+monitor:
+  fetch_interval_days: 30
+  check_interval_days: 7
+  http_timeout_secs: 1
+  max_concurrent: 4
+  max_redirects: 2
+http: {http}
+"#)).unwrap()
+    }
+
+    const PROFILES: [ClientProfile; 2] = [ClientProfile::CenterApi, ClientProfile::UrlCheck];
+
+    /// 使用只对 localhost 签发的自签名证书的 HTTPS 桩服务，通过 127.0.0.1 访问时证书既不受信任、主机名也不匹配
+    async fn tls_server() -> String {
+        use tokio_rustls::rustls::{pki_types::PrivatePkcs8KeyDer, ServerConfig};
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let key = PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der());
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![certified.cert.der().clone()], key.into())
+            .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("https://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    // 客户端拒绝证书时握手失败
+                    if let Ok(socket) = acceptor.accept(socket).await {
+                        serve(socket, 0, Requests::default(), Arc::new(|_| keep_alive("200 OK", &[], "")), Duration::ZERO).await;
+                    }
+                });
+            }
+        });
+        base
+    }
+
+    #[tokio::test]
+    async fn profiles_send_the_user_agent_through_the_proxy() {
+        let stub = stub_server(|_| keep_alive("200 OK", &[], "")).await;
+        let configured = config(&format!(r#"{{ user_agent: "dataset-monitor-test/1.0", proxy: "{}" }}"#, stub.base));
+        let unset = config(&format!(r#"{{ proxy: "{}" }}"#, stub.base));
+        for profile in PROFILES {
+            for config in [&configured, &unset] {
+                let client = ClientFactory::new(config).build(profile);
+                let response = client.get("http://center.invalid/ping").send().await.unwrap();
+                assert_eq!(response.status(), 200, "{:?}", profile);
+            }
+        }
+
+        // 经代理发出的请求目标为完整URL；未配置 user_agent 时不发送该请求头
+        let requests = stub.requests.lock().unwrap();
+        let seen: Vec<(&str, Option<&str>)> = requests.iter()
+            .map(|request| (request.target.as_str(), request.header("user-agent")))
+            .collect();
+        assert_eq!(seen, [
+            ("http://center.invalid/ping", Some("dataset-monitor-test/1.0")),
+            ("http://center.invalid/ping", None),
+            ("http://center.invalid/ping", Some("dataset-monitor-test/1.0")),
+            ("http://center.invalid/ping", None),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn center_api_follows_redirects_and_url_check_does_not() {
+        let stub = stub_server(|request| match request.target.as_str() {
+            "/short/1" => keep_alive("302 Found", &["Location: /short/2"], ""),
+            "/long/1" => keep_alive("302 Found", &["Location: /long/2"], ""),
+            "/long/2" => keep_alive("302 Found", &["Location: /long/3"], ""),
+            "/long/3" => keep_alive("302 Found", &["Location: /long/4"], ""),
+            _ => keep_alive("200 OK", &[], ""),
+        }).await;
+        let config = config("{}");
+        let factory = ClientFactory::new(&config);
+        let url = |path: &str| format!("{}{}", stub.base, path);
+
+        // 数据中心接口最多跟随 monitor.max_redirects（2）次
+        let center_api = factory.build(ClientProfile::CenterApi);
+        let followed = center_api.get(url("/short/1")).send().await.unwrap();
+        assert_eq!((followed.status().as_u16(), followed.url().path()), (200, "/short/2"));
+        let error = center_api.get(url("/long/1")).send().await.unwrap_err();
+        assert!(error.is_redirect(), "{:?}", error);
+
+        // 检查URL的客户端不跟随，由 HttpChecker 逐跳处理
+        let url_check = factory.build(ClientProfile::UrlCheck);
+        let unfollowed = url_check.get(url("/short/1")).send().await.unwrap();
+        assert_eq!(unfollowed.status(), 302);
+
+        assert_eq!(stub.targets(), ["/short/1", "/short/2", "/long/1", "/long/2", "/long/3", "/short/1"]);
+    }
+
+    #[tokio::test]
+    async fn requests_time_out_after_http_timeout_secs() {
+        // 接受连接但从不响应
+        let silent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/slow", silent.local_addr().unwrap());
+        let config = config("{}");
+        for profile in PROFILES {
+            let started = std::time::Instant::now();
+            let error = ClientFactory::new(&config).build(profile).get(&url).send().await.unwrap_err();
+            assert!(error.is_timeout(), "{:?}: {:?}", profile, error);
+            assert!(started.elapsed() < Duration::from_secs(3), "{:?}", profile);
+        }
+        drop(silent);
+    }
+
+    #[tokio::test]
+    async fn idle_connections_follow_the_pool_settings() {
+        let stub = stub_server(|_| keep_alive("200 OK", &[], "")).await;
+        let url = format!("{}/ping", stub.base);
+        for (http, profile) in [("{}", ClientProfile::CenterApi), ("{ pool_max_idle_per_host: 0 }", ClientProfile::UrlCheck)] {
+            let client = ClientFactory::new(&config(http)).build(profile);
+            for _ in 0..2 {
+                client.get(&url).send().await.unwrap();
+            }
+        }
+        // 默认复用空闲连接；不保留空闲连接时每个请求新建连接
+        let connections: Vec<usize> = stub.requests.lock().unwrap().iter().map(|request| request.connection).collect();
+        assert_eq!(connections, [1, 1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn tls_policy_decides_whether_invalid_certificates_are_accepted() {
+        let base = tls_server().await;
+        for (center_api_tls, url_check_tls) in [("lenient", "strict"), ("strict", "lenient")] {
+            let config = config(&format!("{{ center_api_tls: {}, url_check_tls: {} }}", center_api_tls, url_check_tls));
+            let factory = ClientFactory::new(&config);
+            for profile in PROFILES {
+                let result = factory.build(profile).get(format!("{}/ping", base)).send().await;
+                match factory.tls(profile) {
+                    TlsPolicy::Lenient => assert_eq!(result.unwrap().status(), 200, "{:?}", profile),
+                    TlsPolicy::Strict => assert!(result.unwrap_err().is_connect(), "{:?}", profile),
+                }
+            }
+        }
+        // 未配置时两类客户端都接受无效证书
+        let defaults = config("{}");
+        assert_eq!(PROFILES.map(|p| ClientFactory::new(&defaults).tls(p)), [TlsPolicy::Lenient; 2]);
+    }
+}
@@ -0,0 +1,321 @@
+//! 从其他链接检查工具导出的 CSV 导入历史检查记录
+
+use crate::db::duckdb::DuckDB;
+use crate::models::MonitorRecord;
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// 每批写入 DuckDB 的记录数
+const IMPORT_BATCH_SIZE: usize = 1000;
+
+/// CSV 列到检查记录字段的映射，从 YAML 文件加载
+#[derive(Debug, Deserialize)]
+pub struct ImportMapping {
+    pub columns: ColumnMapping,
+    /// check_time 的 chrono 格式，如 "%Y-%m-%d %H:%M:%S"，未配置时按 RFC 3339 解析；不含时区的时间按 UTC
+    #[serde(default)]
+    pub date_format: Option<String>,
+    /// CSV 中没有数据中心列，或该列为空时使用的数据中心名称
+    #[serde(default)]
+    pub default_center: Option<String>,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+    /// 写入 source 列的来源标记
+    #[serde(default = "default_source")]
+    pub source: String,
+}
+
+/// 各字段对应的 CSV 列名，url 和 check_time 必须有
+#[derive(Debug, Deserialize)]
+pub struct ColumnMapping {
+    pub url: String,
+    pub check_time: String,
+    #[serde(default)]
+    pub center_name: Option<String>,
+    #[serde(default)]
+    pub raw_id: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub status_code: Option<String>,
+    #[serde(default)]
+    pub status_text: Option<String>,
+    #[serde(default)]
+    pub error_category: Option<String>,
+    #[serde(default)]
+    pub error_msg: Option<String>,
+    #[serde(default)]
+    pub response_time_ms: Option<String>,
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_source() -> String {
+    "imported".to_string()
+}
+
+impl ImportMapping {
+    pub fn load(path: &str) -> Result<Self> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("读取映射文件失败: {}", path))?;
+        let mapping: Self = serde_yaml::from_str(&content).with_context(|| format!("解析映射文件失败: {}", path))?;
+        if !mapping.delimiter.is_ascii() {
+            anyhow::bail!("分隔符必须是 ASCII 字符: {:?}", mapping.delimiter);
+        }
+        Ok(mapping)
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    /// 写入的行数
+    pub accepted: usize,
+    /// 校验失败的行数，写入 rejects_file
+    pub rejected: usize,
+    /// 已有相同 (url, check_time) 而跳过的行数
+    pub duplicates: usize,
+    pub rejects_file: Option<String>,
+}
+
+/// 映射后各字段所在的列序号
+struct ColumnIndices {
+    url: usize,
+    check_time: usize,
+    center_name: Option<usize>,
+    raw_id: Option<usize>,
+    name: Option<usize>,
+    status_code: Option<usize>,
+    status_text: Option<usize>,
+    error_category: Option<usize>,
+    error_msg: Option<usize>,
+    response_time_ms: Option<usize>,
+}
+
+impl ColumnIndices {
+    fn resolve(columns: &ColumnMapping, headers: &csv::StringRecord) -> Result<Self> {
+        let find = |name: &str| headers.iter().position(|h| h.trim() == name)
+            .with_context(|| format!("CSV 中没有列 {}", name));
+        let find_optional = |name: &Option<String>| name.as_deref().map(find).transpose();
+        Ok(Self {
+            url: find(&columns.url)?,
+            check_time: find(&columns.check_time)?,
+            center_name: find_optional(&columns.center_name)?,
+            raw_id: find_optional(&columns.raw_id)?,
+            name: find_optional(&columns.name)?,
+            status_code: find_optional(&columns.status_code)?,
+            status_text: find_optional(&columns.status_text)?,
+            error_category: find_optional(&columns.error_category)?,
+            error_msg: find_optional(&columns.error_msg)?,
+            response_time_ms: find_optional(&columns.response_time_ms)?,
+        })
+    }
+}
+
+/// 流式读取 CSV，校验通过的行分批写入 dataset_monitor，校验失败的行连同原因写入 `{file}.rejects.csv`
+pub async fn import_csv(duckdb: &DuckDB, file: &str, mapping: &ImportMapping, skip_duplicates: bool) -> Result<ImportReport> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(mapping.delimiter as u8)
+        .flexible(true)
+        .from_path(file)
+        .with_context(|| format!("打开 CSV 文件失败: {}", file))?;
+    let headers = reader.headers()?.clone();
+    let indices = ColumnIndices::resolve(&mapping.columns, &headers)?;
+
+    let rejects_path = format!("{}.rejects.csv", file);
+    let mut rejects: Option<csv::Writer<std::fs::File>> = None;
+    let mut report = ImportReport::default();
+    let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    for (index, row) in reader.records().enumerate() {
+        let (row, reason) = match row {
+            Ok(row) => match map_row(&row, &indices, mapping) {
+                Ok(record) => {
+                    batch.push(record);
+                    if batch.len() >= IMPORT_BATCH_SIZE {
+                        write_batch(duckdb, &mut batch, mapping, skip_duplicates, &mut report).await?;
+                    }
+                    continue;
+                }
+                Err(reason) => (Some(row), reason),
+            },
+            Err(e) => (None, format!("无法读取: {}", e)),
+        };
+        report.rejected += 1;
+        let writer = match &mut rejects {
+            Some(writer) => writer,
+            None => {
+                let mut writer = csv::Writer::from_path(&rejects_path)
+                    .with_context(|| format!("创建拒绝文件失败: {}", rejects_path))?;
+                let mut header = vec!["line", "reason"];
+                header.extend(headers.iter());
+                writer.write_record(&header)?;
+                rejects.insert(writer)
+            }
+        };
+        // 表头是第 1 行
+        let mut rejected = vec![(index + 2).to_string(), reason];
+        if let Some(row) = row {
+            rejected.extend(row.iter().map(str::to_string));
+        }
+        writer.write_record(&rejected)?;
+    }
+    write_batch(duckdb, &mut batch, mapping, skip_duplicates, &mut report).await?;
+    if let Some(mut writer) = rejects {
+        writer.flush()?;
+        report.rejects_file = Some(rejects_path);
+    }
+    info!("导入 {} 完成: 写入 {}，拒绝 {}，重复 {}", file, report.accepted, report.rejected, report.duplicates);
+    Ok(report)
+}
+
+async fn write_batch(duckdb: &DuckDB, batch: &mut Vec<MonitorRecord>, mapping: &ImportMapping,
+                     skip_duplicates: bool, report: &mut ImportReport) -> Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    let inserted = duckdb.import_records(batch, &mapping.source, skip_duplicates).await?;
+    report.accepted += inserted;
+    report.duplicates += batch.len() - inserted;
+    batch.clear();
+    Ok(())
+}
+
+fn map_row(row: &csv::StringRecord, indices: &ColumnIndices, mapping: &ImportMapping) -> Result<MonitorRecord, String> {
+    let field = |index: Option<usize>| index
+        .and_then(|i| row.get(i))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string);
+
+    let url = field(Some(indices.url)).ok_or("url 为空")?;
+    if reqwest::Url::parse(&url).is_err() {
+        return Err(format!("无效的 url: {}", url));
+    }
+    let check_time = field(Some(indices.check_time)).ok_or("check_time 为空")?;
+    let check_time = parse_check_time(&check_time, mapping.date_format.as_deref())
+        .ok_or_else(|| format!("无法解析 check_time: {}", check_time))?;
+    let center_name = field(indices.center_name)
+        .or_else(|| mapping.default_center.clone())
+        .ok_or("center_name 为空，且映射中没有配置 default_center")?;
+    let status_code = field(indices.status_code)
+        .map(|code| code.parse::<u16>().map_err(|_| format!("无效的 status_code: {}", code)))
+        .transpose()?;
+    let response_time_ms = field(indices.response_time_ms)
+        .map(|ms| ms.parse::<f64>().ok().filter(|ms| *ms >= 0.0).ok_or_else(|| format!("无效的 response_time_ms: {}", ms)))
+        .transpose()?;
+    if status_code.is_none() && field(indices.error_category).is_none() && field(indices.error_msg).is_none() {
+        return Err("没有 status_code，也没有错误信息".to_string());
+    }
+
+    Ok(MonitorRecord {
+        raw_id: Some(field(indices.raw_id).unwrap_or_default()),
+        url,
+        name: field(indices.name),
+        center_name,
+        check_time,
+        status_code,
+        status_text: field(indices.status_text).or_else(|| {
+            status_code
+                .and_then(|code| reqwest::StatusCode::from_u16(code).ok())
+                .and_then(|code| code.canonical_reason())
+                .map(str::to_string)
+        }),
+        error_category: field(indices.error_category),
+        error_msg: field(indices.error_msg),
+        response_time_ms: response_time_ms.map(|ms| ms.round() as u64),
+        ..Default::default()
+    })
+}
+
+fn parse_check_time(value: &str, format: Option<&str>) -> Option<DateTime<Utc>> {
+    match format {
+        Some(format) => DateTime::parse_from_str(value, format)
+            .map(|t| t.with_timezone(&Utc))
+            .or_else(|_| NaiveDateTime::parse_from_str(value, format).map(|t| t.and_utc()))
+            .ok(),
+        None => DateTime::parse_from_rfc3339(value).map(|t| t.with_timezone(&Utc)).ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(yaml: &str) -> ImportMapping {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    const MAPPING: &str = r#"
+columns: { url: link, check_time: time, center_name: center, status_code: code, error_category: category, response_time_ms: ms }
+date_format: "%Y-%m-%d %H:%M:%S"
+default_center: Imported
+"#;
+
+    #[test]
+    fn parse_check_time_formats() {
+        let at = |s: &str| -> DateTime<Utc> { s.parse().unwrap() };
+        assert_eq!(parse_check_time("2026-01-02T03:04:05+08:00", None), Some(at("2026-01-01T19:04:05Z")));
+        assert_eq!(parse_check_time("2026-01-02 03:04:05", None), None);
+        // 不含时区的时间按 UTC
+        assert_eq!(parse_check_time("2026-01-02 03:04:05", Some("%Y-%m-%d %H:%M:%S")), Some(at("2026-01-02T03:04:05Z")));
+        assert_eq!(parse_check_time("2026-01-02 03:04:05 +0100", Some("%Y-%m-%d %H:%M:%S %z")), Some(at("2026-01-02T02:04:05Z")));
+        assert_eq!(parse_check_time("02/01/2026", Some("%Y-%m-%d %H:%M:%S")), None);
+    }
+
+    #[test]
+    fn missing_columns_are_reported() {
+        let headers = csv::StringRecord::from(vec!["link", " time ", "center"]);
+        let missing = ColumnIndices::resolve(&mapping(MAPPING).columns, &headers).err().unwrap();
+        assert_eq!(missing.to_string(), "CSV 中没有列 code");
+        let minimal = mapping("columns: { url: link, check_time: time }");
+        let indices = ColumnIndices::resolve(&minimal.columns, &headers).unwrap();
+        assert_eq!((indices.url, indices.check_time, indices.center_name), (0, 1, None));
+        assert_eq!((minimal.delimiter, minimal.source.as_str()), (',', "imported"));
+    }
+
+    #[tokio::test]
+    async fn import_accepts_valid_rows_and_rejects_the_rest() {
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("checks.csv").display().to_string();
+        std::fs::write(&file, "\
+link,time,center,code,category,ms
+https://a.example.org/1,2026-01-02 03:04:05,A,404,HTTP_4XX,120.4
+https://a.example.org/2,2026-01-02 03:05:00,,,TIMEOUT,
+not a url,2026-01-02 03:04:05,A,200,,
+https://a.example.org/3,yesterday,A,200,,
+https://a.example.org/4,2026-01-02 03:04:05,A,,,
+https://a.example.org/5,2026-01-02 03:04:05,A,abc,,
+https://a.example.org/6,2026-01-02 03:04:05,A,200,,-1
+").unwrap();
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let mapping = mapping(MAPPING);
+
+        let report = import_csv(&db, &file, &mapping, true).await.unwrap();
+        assert_eq!((report.accepted, report.rejected, report.duplicates), (2, 5, 0));
+        let history = db.get_url_history("https://a.example.org/1", 10).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!((history[0].status_code, history[0].response_time_ms), (Some(404), Some(120)));
+        let history = db.get_url_history("https://a.example.org/2", 10).await.unwrap();
+        assert_eq!(history[0].error_category.as_deref(), Some("TIMEOUT"));
+
+        let rejects = std::fs::read_to_string(report.rejects_file.as_ref().unwrap()).unwrap();
+        let lines: Vec<&str> = rejects.lines().collect();
+        assert_eq!(lines[0], "line,reason,link,time,center,code,category,ms");
+        let reasons: Vec<String> = lines[1..].iter().map(|l| l.splitn(3, ',').take(2).collect::<Vec<_>>().join(",")).collect();
+        assert_eq!(reasons, [
+            "4,无效的 url: not a url",
+            "5,无法解析 check_time: yesterday",
+            "6,没有 status_code，也没有错误信息",
+            "7,无效的 status_code: abc",
+            "8,无效的 response_time_ms: -1",
+        ]);
+
+        // 再次导入时跳过已有的 (url, check_time)
+        let again = import_csv(&db, &file, &mapping, true).await.unwrap();
+        assert_eq!((again.accepted, again.duplicates), (0, 2));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
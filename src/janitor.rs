@@ -0,0 +1,202 @@
+//! 定时清理崩溃或中断后遗留的状态：过期的认领、没有结束的运行、溢出和临时文件、过大的缓存
+
+use crate::config::Config;
+use crate::db::duckdb::DuckDB;
+use crate::db::mongodb::MongoDB;
+use crate::raw_store::RawStore;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+/// 一次清理的结果，关闭的清理项为 None
+#[derive(Debug, Default, Serialize)]
+pub struct JanitorReport {
+    pub started_at: DateTime<Utc>,
+    /// 放回待处理的过期认领数
+    pub released_claims: Option<u64>,
+    /// 标记为 aborted 的运行
+    pub aborted_runs: Option<Vec<String>>,
+    /// 删除的遗留运行进度行数
+    pub orphaned_progress: Option<usize>,
+    /// 删除的溢出文件和临时文件
+    pub removed_files: Option<Vec<String>>,
+    /// 删除的过期原始响应数
+    pub removed_raw_responses: Option<usize>,
+    /// head_method_cache 中删除的主机数
+    pub evicted_method_cache: Option<usize>,
+    /// 失败的清理项及原因，某一项失败不影响其他项
+    pub errors: Vec<String>,
+}
+
+/// 按 `config.janitor` 执行各项清理，某一项失败时记录到报告中并继续其他项
+pub async fn run(config: &Config, duckdb: &DuckDB) -> JanitorReport {
+    let janitor = &config.janitor;
+    let mut report = JanitorReport { started_at: Utc::now(), ..Default::default() };
+
+    if janitor.release_claims {
+        let expired_before = report.started_at - chrono::Duration::minutes(config.monitor.claim_timeout_minutes as i64);
+        let released = match MongoDB::new(&config.mongodb).await {
+            Ok(mongo) => mongo.release_expired_claims(expired_before).await,
+            Err(e) => Err(e),
+        };
+        match released {
+            Ok(released) => report.released_claims = Some(released),
+            Err(e) => report.errors.push(format!("release_claims: {:#}", e)),
+        }
+    }
+
+    if janitor.abort_runs {
+        // 不关闭仍可以续跑的运行
+        let hours = janitor.abandoned_run_hours.max(config.monitor.resume_max_age_hours);
+        let cutoff = report.started_at - chrono::Duration::hours(hours as i64);
+        match abort_runs(duckdb, cutoff).await {
+            Ok(aborted) => report.aborted_runs = Some(aborted),
+            Err(e) => report.errors.push(format!("abort_runs: {:#}", e)),
+        }
+        match duckdb.delete_orphaned_progress().await {
+            Ok(deleted) => report.orphaned_progress = Some(deleted),
+            Err(e) => report.errors.push(format!("orphaned_progress: {:#}", e)),
+        }
+    }
+
+    if janitor.remove_files {
+        let retention = Duration::from_secs(janitor.file_retention_days as u64 * 24 * 3600);
+        let mut removed = Vec::new();
+        for (dir, extension) in [(&config.monitor.spill_dir, "ndjson"), (&config.monitor.state_dir, "tmp")] {
+            if let Err(e) = remove_expired(Path::new(dir), extension, retention, &mut removed) {
+                report.errors.push(format!("remove_files {}: {:#}", dir, e));
+            }
+        }
+        report.removed_files = Some(removed);
+        match RawStore::from_config(&config.monitor).prune() {
+            Ok(pruned) => report.removed_raw_responses = Some(pruned),
+            Err(e) => report.errors.push(format!("raw_responses: {:#}", e)),
+        }
+    }
+
+    if janitor.compact_caches {
+        match duckdb.compact_method_cache(janitor.method_cache_max_entries).await {
+            Ok(evicted) => report.evicted_method_cache = Some(evicted),
+            Err(e) => report.errors.push(format!("method_cache: {:#}", e)),
+        }
+    }
+
+    let summary = serde_json::to_string(&report).unwrap_or_default();
+    if report.errors.is_empty() {
+        info!("清理完成: {}", summary);
+    } else {
+        warn!("清理完成，部分清理项失败: {}", summary);
+    }
+    report
+}
+
+/// 把开始时间早于 `cutoff` 的未结束运行标记为 aborted
+async fn abort_runs(duckdb: &DuckDB, cutoff: DateTime<Utc>) -> Result<Vec<String>> {
+    let mut aborted = Vec::new();
+    for run in duckdb.get_unfinished_runs().await? {
+        if run.started_at < cutoff {
+            warn!("运行 {}（{}）开始于 {}，至今未结束，标记为 aborted", run.run_id, run.centers.join(", "), run.started_at);
+            duckdb.finish_run_state(&run.run_id, "aborted").await?;
+            aborted.push(run.run_id);
+        }
+    }
+    Ok(aborted)
+}
+
+/// 删除 `dir` 下扩展名为 `extension` 且修改时间超过 `retention` 的文件（不递归）
+fn remove_expired(dir: &Path, extension: &str, retention: Duration, removed: &mut Vec<String>) -> Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    let now = SystemTime::now();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != extension) {
+            continue;
+        }
+        let expired = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() > retention);
+        if expired {
+            std::fs::remove_file(&path)?;
+            removed.push(path.display().to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(dir: &Path, janitor: &str) -> Config {
+        serde_yaml::from_str(&format!(r#"
+centers: []
+mongodb: {{ uri: "mongodb://127.0.0.1:9/?serverSelectionTimeoutMS=100&connectTimeoutMS=100", database: "db" }}
+duckdb: {{ path: ":memory:" }}
+monitor:
+  fetch_interval_days: 30
+  check_interval_days: 7
+  http_timeout_secs: 30
+  max_concurrent: 10
+  resume_max_age_hours: 6
+  spill_dir: "{dir}/spill"
+  state_dir: "{dir}/state"
+  raw_responses_dir: "{dir}/raw"
+janitor: {janitor}
+"#, dir = dir.display(), janitor = janitor)).unwrap()
+    }
+
+    /// 创建修改时间为 `days_ago` 天前的文件
+    fn file(path: &Path, days_ago: u64) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let file = std::fs::File::create(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(days_ago * 24 * 3600)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn cleanup_continues_after_a_failing_step() {
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-janitor-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        file(&dir.join("spill/old.ndjson"), 10);
+        file(&dir.join("spill/new.ndjson"), 1);
+        file(&dir.join("spill/old.json"), 10);
+        file(&dir.join("state/old.tmp"), 10);
+        file(&dir.join("state/old.ndjson"), 10);
+        let enabled = config(&dir, "{ abandoned_run_hours: 2, file_retention_days: 7, method_cache_max_entries: 1 }");
+
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let now = Utc::now();
+        // 开始时间在 abandoned_run_hours 之前但仍可续跑的运行不关闭
+        db.start_run_state("old", now - chrono::Duration::hours(7), &["A".to_string()]).await.unwrap();
+        db.start_run_state("resumable", now - chrono::Duration::hours(3), &["A".to_string()]).await.unwrap();
+        db.record_progress("finished", &["1"]).await.unwrap();
+        db.update_method_cache(&["a.example.org".to_string(), "b.example.org".to_string()], &[], &[]).await.unwrap();
+
+        let report = run(&enabled, &db).await;
+        // MongoDB 不可用时只有 release_claims 失败
+        assert_eq!(report.errors.len(), 1, "{:?}", report.errors);
+        assert!(report.errors[0].starts_with("release_claims: "));
+        assert_eq!(report.released_claims, None);
+        assert_eq!(report.aborted_runs, Some(vec!["old".to_string()]));
+        assert_eq!(report.orphaned_progress, Some(1));
+        let mut removed = report.removed_files.unwrap();
+        removed.sort();
+        assert_eq!(removed, [dir.join("spill/old.ndjson").display().to_string(), dir.join("state/old.tmp").display().to_string()]);
+        assert!(dir.join("spill/new.ndjson").exists() && dir.join("spill/old.json").exists() && dir.join("state/old.ndjson").exists());
+        assert_eq!(report.removed_raw_responses, Some(0));
+        assert_eq!(report.evicted_method_cache, Some(1));
+        let unfinished: Vec<String> = db.get_unfinished_runs().await.unwrap().into_iter().map(|r| r.run_id).collect();
+        assert_eq!(unfinished, ["resumable"]);
+
+        // 关闭的清理项不执行
+        let disabled = config(&dir, "{ release_claims: false, abort_runs: false, remove_files: false, compact_caches: false }");
+        let report = run(&disabled, &db).await;
+        assert!(report.errors.is_empty());
+        assert!(report.aborted_runs.is_none() && report.removed_files.is_none() && report.evicted_method_cache.is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,8 +1,42 @@
+pub mod alert;
+pub mod backlog;
+pub mod build_info;
+pub mod check_file;
+pub mod check_method;
+pub mod checker;
+pub mod circuit_breaker;
+pub mod cmdb;
+pub mod concurrency;
 pub mod config;
 pub mod models;
 pub mod db;
+pub mod duplicates;
+pub mod email;
+pub mod export;
 pub mod fetcher;
+pub mod heartbeat;
+pub mod http;
+pub mod import;
+pub mod janitor;
+pub mod meta;
 pub mod monitor;
+pub mod progress;
+pub mod notify;
+pub mod raw_store;
+pub mod reclassify;
+pub mod regression;
+pub mod report;
+pub mod sanitize;
+pub mod scheduler;
+pub mod shared_urls;
+pub mod slow_hosts;
+pub mod spill;
+pub mod systemd;
+pub mod timing;
+pub mod url_quality;
+
+#[cfg(test)]
+pub(crate) mod test_support;
 
 // 重新导出常用的类型和函数
 pub use crate::config::Config;
@@ -0,0 +1,83 @@
+//! 统计命令可用的过滤值：错误类别、数据中心、时间粒度、成功的判定方式和已有数据的时间范围，
+//! 前端构建筛选项时以此为准，不再各自维护列表
+
+use crate::config::{Config, SuccessStatuses, TerminalRedirect};
+use crate::db::duckdb::DuckDB;
+use crate::models::{ErrorCategory, TrendGranularity};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Meta {
+    pub error_categories: Vec<CategoryMeta>,
+    pub centers: Vec<CenterMeta>,
+    /// 趋势统计的时间粒度
+    pub granularities: Vec<&'static str>,
+    /// 状态码类别过滤的取值
+    pub status_classes: Vec<&'static str>,
+    pub success: SuccessMeta,
+    /// 已完成检查的记录中最早和最晚的检查时间，没有记录时为 None
+    pub earliest_check_time: Option<DateTime<Utc>>,
+    pub latest_check_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryMeta {
+    /// 记录中 error_category 的值
+    pub name: String,
+    /// 是否计为本地网络问题
+    pub local_issue: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CenterMeta {
+    pub name: String,
+    pub enabled: bool,
+}
+
+/// 成功的判定方式：当前配置和可选的取值
+#[derive(Debug, Clone, Serialize)]
+pub struct SuccessMeta {
+    /// 生效的 monitor.success_statuses（terminal_redirect 为 success 时包含 3xx）
+    pub success_statuses: SuccessStatuses,
+    pub terminal_redirect: TerminalRedirect,
+    pub terminal_redirect_options: [TerminalRedirect; 3],
+}
+
+const STATUS_CLASSES: [&str; 6] = ["1xx", "2xx", "3xx", "4xx", "5xx", "no_response"];
+
+/// 由配置和一次 DuckDB 查询（检查时间的范围）组成
+pub async fn build(config: &Config, duckdb: &DuckDB) -> Result<Meta> {
+    let (earliest_check_time, latest_check_time) = duckdb.get_check_time_range().await?;
+    Ok(Meta {
+        error_categories: ErrorCategory::ALL.iter()
+            .map(|category| CategoryMeta { name: category.to_string(), local_issue: category.is_likely_local_issue() })
+            .collect(),
+        centers: config.centers.iter()
+            .map(|center| CenterMeta { name: center.name.clone(), enabled: center.enabled })
+            .collect(),
+        granularities: TrendGranularity::ALL.iter().map(TrendGranularity::sql_unit).collect(),
+        status_classes: STATUS_CLASSES.to_vec(),
+        success: SuccessMeta {
+            success_statuses: config.monitor.success_statuses.clone(),
+            terminal_redirect: config.monitor.terminal_redirect(),
+            terminal_redirect_options: TerminalRedirect::ALL,
+        },
+        earliest_check_time,
+        latest_check_time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::filter::StatusClass;
+
+    #[test]
+    fn status_classes_are_accepted_by_filters() {
+        for class in STATUS_CLASSES {
+            assert!(class.parse::<StatusClass>().is_ok(), "{}", class);
+        }
+    }
+}
@@ -1,7 +1,9 @@
-use chrono::{DateTime, Utc};
+use crate::config::{DispatchOrder, SuccessStatuses};
+use chrono::{DateTime, NaiveDate, Utc};
 use mongodb::bson::oid::ObjectId;
 use mongodb::bson::Bson;
 use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::fmt;
 use std::fmt::Display;
@@ -10,16 +12,32 @@ use tracing::warn;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthResponse {
     pub ticket: Ticket,
-    #[serde(rename = "serviceList")]
+    #[serde(rename = "serviceList", default)]
     pub service_list: Vec<Service>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Ticket {
+    /// 有的数据中心缺失该字段或以字符串返回
+    #[serde(default, deserialize_with = "deserialize_lenient_i64")]
     pub expires: i64,
+    #[serde(default)]
     pub token: String,
 }
 
+/// 数字或数字字符串，无法解析时为 0
+fn deserialize_lenient_i64<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value: Option<serde_json::Value> = Option::deserialize(deserializer)?;
+    Ok(match value {
+        Some(serde_json::Value::Number(n)) => n.as_i64().or_else(|| n.as_f64().map(|f| f as i64)).unwrap_or(0),
+        Some(serde_json::Value::String(s)) => s.trim().parse().unwrap_or(0),
+        _ => 0,
+    })
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Service {
     pub name: String,
@@ -91,9 +109,14 @@ pub struct Dataset {
     pub sync_date: Option<DateTime<Utc>>,
     #[serde(rename = "centerName", default)]
     pub center_name: Option<String>,
+    #[serde(rename = "schema:identifier", default, skip_serializing_if = "Option::is_none")]
+    pub identifier: Option<Bson>,
+    /// 人工设置的标签（如 critical），数据获取更新数据集时保留
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MonitorRecord {
     pub id: String,
     pub raw_id: Option<String>,
@@ -114,6 +137,64 @@ pub struct MonitorRecord {
 
     // 请求元数据
     pub response_time_ms: Option<u64>,
+    /// DNS 解析耗时，复用连接或URL为IP时为 None
+    #[serde(default)]
+    pub dns_ms: Option<u64>,
+    /// 建立连接耗时（TCP + TLS 握手），复用连接时为 None
+    #[serde(default)]
+    pub connect_ms: Option<u64>,
+    /// 发出请求到收到响应头的耗时，包含 DNS 和建立连接
+    #[serde(default)]
+    pub ttfb_ms: Option<u64>,
+    /// 协商的HTTP版本，如 HTTP/1.1、HTTP/2.0
+    #[serde(default)]
+    pub http_version: Option<String>,
+    /// 没有新建连接（复用了连接池中的连接），没有收到响应时为 None
+    #[serde(default)]
+    pub connection_reused: Option<bool>,
+    /// 响应体开头（最多 fingerprint_max_kb）的 SHA-256，未启用指纹或读取失败时为 None
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    /// 指纹与该数据集上一次的指纹不同，没有可比较的指纹时为 None
+    #[serde(default)]
+    pub content_changed: Option<bool>,
+    /// 请求时使用了配置的认证凭证（凭证本身不保存）
+    #[serde(default)]
+    pub auth_used: bool,
+    /// 实际请求的URL（去掉 strip_query_params 中的参数后），尚未检查时为 None
+    #[serde(default)]
+    pub requested_url: Option<String>,
+    /// 每次尝试的结果，配置了 check_retries 时可能有多次；response_time_ms 为最后一次尝试的耗时
+    #[serde(default)]
+    pub attempts_detail: Vec<CheckAttempt>,
+    /// 所有尝试加上重试前等待的总耗时
+    #[serde(default)]
+    pub total_time_ms: Option<u64>,
+    /// 失败发生在第几跳：0 为请求的URL，n 为第 n 次重定向后的URL；成功或没有发出请求时为 None
+    #[serde(default)]
+    pub failed_hop_index: Option<u32>,
+    /// 失败的那一跳的URL
+    #[serde(default)]
+    pub failed_hop_url: Option<String>,
+    /// 没有跟随的 3xx 响应的 Location（见 monitor.terminal_redirect），跟随了全部重定向时为 None
+    #[serde(default)]
+    pub redirect_location: Option<String>,
+    /// 本次检查使用的超时（秒），慢主机可能大于 http_timeout_secs；尚未检查时为 None
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// 检查时数据集的标签
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 写入该记录的运行，运行发布（见 published_runs）前统计默认不计入；导入和单独检查URL的记录为 None
+    #[serde(default)]
+    pub run_id: Option<String>,
+    /// 多个数据中心共用该URL时统计归属的数据中心（见 monitor.shared_urls），与 center_name 不同时统计默认不计入；
+    /// 不共用或计入每个数据中心时为 None
+    #[serde(default)]
+    pub attributed_center: Option<String>,
+    /// 归属的依据：first_seen 或 mapped（monitor.shared_urls.primary 指定）
+    #[serde(default)]
+    pub attribution_rule: Option<String>,
     // 诊断信息
     pub is_likely_local_issue: bool,
     pub headers: Option<String>,
@@ -121,6 +202,16 @@ pub struct MonitorRecord {
     pub updated_at: Option<DateTime<Utc>>,
 }
 
+impl MonitorRecord {
+    /// 失败发生在重定向之后时，返回第几次重定向和失败的URL
+    pub fn failed_after_redirect(&self) -> Option<(u32, &str)> {
+        match (self.failed_hop_index, &self.failed_hop_url) {
+            (Some(index), Some(url)) if index > 0 => Some((index, url)),
+            _ => None,
+        }
+    }
+}
+
 /// 错误分类枚举
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ErrorCategory {
@@ -142,6 +233,16 @@ pub enum ErrorCategory {
     TooManyRedirects,
     /// 请求被取消
     RequestCanceled,
+    /// 主机已熔断，本次运行没有实际请求该URL
+    HostCircuitOpen,
+    /// 使用配置的凭证请求仍返回 401，凭证可能已失效
+    AuthRejected,
+    /// 没有对应检查器的URL协议，未请求
+    UnsupportedScheme,
+    /// 没有跟随的 3xx 响应，monitor.terminal_redirect 为 redirected 时使用
+    Redirected,
+    /// HTTPS 重定向到 HTTP，monitor.follow_insecure_redirects 为 false 时不跟随
+    InsecureRedirect,
     /// 未知错误
     Unknown,
 }
@@ -158,18 +259,69 @@ impl Display for ErrorCategory {
             ErrorCategory::Unknown => write!(f, "UNKNOWN_ERROR"),
             ErrorCategory::SslCertificate => write!(f, "SSL_ERROR"),
             ErrorCategory::RequestCanceled => write!(f, "REQUEST_CANCELED_ERROR"),
+            ErrorCategory::HostCircuitOpen => write!(f, "HOST_CIRCUIT_OPEN"),
+            ErrorCategory::AuthRejected => write!(f, "AUTH_REJECTED"),
+            ErrorCategory::UnsupportedScheme => write!(f, "UNSUPPORTED_SCHEME"),
+            ErrorCategory::Redirected => write!(f, "REDIRECTED"),
+            ErrorCategory::InsecureRedirect => write!(f, "INSECURE_REDIRECT"),
         }
     }
 }
 impl ErrorCategory {
+    /// 所有类别，顺序与 [`ErrorCategory::index`] 一致
+    pub const ALL: [ErrorCategory; 15] = [
+        ErrorCategory::NetworkConnection,
+        ErrorCategory::DnsResolution,
+        ErrorCategory::Timeout,
+        ErrorCategory::SslCertificate,
+        ErrorCategory::ConnectionRefused,
+        ErrorCategory::ServerError,
+        ErrorCategory::ClientError,
+        ErrorCategory::TooManyRedirects,
+        ErrorCategory::RequestCanceled,
+        ErrorCategory::HostCircuitOpen,
+        ErrorCategory::AuthRejected,
+        ErrorCategory::UnsupportedScheme,
+        ErrorCategory::Redirected,
+        ErrorCategory::InsecureRedirect,
+        ErrorCategory::Unknown,
+    ];
+
+    /// 在 [`ErrorCategory::ALL`] 中的位置。新增类别时这里的 match 无法编译，
+    /// 需要同时加入 ALL，否则下面的编译期检查失败
+    const fn index(&self) -> usize {
+        match self {
+            ErrorCategory::NetworkConnection => 0,
+            ErrorCategory::DnsResolution => 1,
+            ErrorCategory::Timeout => 2,
+            ErrorCategory::SslCertificate => 3,
+            ErrorCategory::ConnectionRefused => 4,
+            ErrorCategory::ServerError => 5,
+            ErrorCategory::ClientError => 6,
+            ErrorCategory::TooManyRedirects => 7,
+            ErrorCategory::RequestCanceled => 8,
+            ErrorCategory::HostCircuitOpen => 9,
+            ErrorCategory::AuthRejected => 10,
+            ErrorCategory::UnsupportedScheme => 11,
+            ErrorCategory::Redirected => 12,
+            ErrorCategory::InsecureRedirect => 13,
+            ErrorCategory::Unknown => 14,
+        }
+    }
+
     /// 根据reqwest错误判断错误类别
     pub fn from_request_error(e: &reqwest::Error) -> Self {
-        if e.is_timeout() {
+        Self::from_signals(&RequestErrorSignals::from_error(e))
+    }
+
+    /// 根据请求错误的特征判断错误类别
+    pub fn from_signals(signals: &RequestErrorSignals) -> Self {
+        if signals.timeout {
             ErrorCategory::Timeout
-        } else if e.is_connect() {
+        } else if signals.connect {
             // 连接错误，可能是网络问题或服务器拒绝
-            if let Some(source) = e.source() {
-                let error_str = source.to_string().to_lowercase();
+            if let Some(source) = &signals.source {
+                let error_str = source.to_lowercase();
                 if error_str.contains("connection refused") {
                     ErrorCategory::ConnectionRefused
                 } else if error_str.contains("dns") || error_str.contains("resolve") {
@@ -183,12 +335,12 @@ impl ErrorCategory {
             } else {
                 ErrorCategory::NetworkConnection
             }
-        } else if e.is_redirect() {
+        } else if signals.redirect {
             ErrorCategory::TooManyRedirects
-        } else if e.is_request() {
+        } else if signals.request {
             ErrorCategory::RequestCanceled
-        } else if let Some(source) = e.source() {
-            let error_str = source.to_string().to_lowercase();
+        } else if let Some(source) = &signals.source {
+            let error_str = source.to_lowercase();
             if error_str.contains("ssl") || error_str.contains("tls") ||
                 error_str.contains("certificate") {
                 ErrorCategory::SslCertificate
@@ -200,6 +352,16 @@ impl ErrorCategory {
         }
     }
 
+    /// 收到响应时按HTTP状态码判断，不是错误状态时为 None。`auth_used` 时 401 说明配置的凭证被拒绝
+    pub fn from_status(status_code: u16, auth_used: bool) -> Option<Self> {
+        match status_code {
+            500..=599 => Some(ErrorCategory::ServerError),
+            401 if auth_used => Some(ErrorCategory::AuthRejected),
+            400..=499 => Some(ErrorCategory::ClientError),
+            _ => None,
+        }
+    }
+
     /// 判断是否可能是本地网络问题
     pub fn is_likely_local_issue(&self) -> bool {
         matches!(self,
@@ -211,6 +373,764 @@ impl ErrorCategory {
     }
 }
 
+// ALL 中每个类别恰好出现一次（下标与 index 一致）
+const _: () = {
+    let mut i = 0;
+    while i < ErrorCategory::ALL.len() {
+        assert!(ErrorCategory::ALL[i].index() == i, "ErrorCategory::ALL 与 index 不一致");
+        i += 1;
+    }
+};
+
+impl std::str::FromStr for ErrorCategory {
+    type Err = anyhow::Error;
+
+    /// 按 Display 的名称（如 `TIMEOUT_ERROR`）解析
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter()
+            .find(|category| category.to_string() == s)
+            .ok_or_else(|| anyhow::anyhow!("未知的错误类别 {}", s))
+    }
+}
+
+/// 判断错误类别用到的请求错误特征，从 reqwest 错误取得，或从保存的错误详情中解析
+#[derive(Debug, Default)]
+pub struct RequestErrorSignals {
+    pub timeout: bool,
+    pub connect: bool,
+    pub redirect: bool,
+    pub request: bool,
+    /// 错误链中下一层错误的描述
+    pub source: Option<String>,
+}
+
+impl RequestErrorSignals {
+    pub fn from_error(e: &reqwest::Error) -> Self {
+        Self {
+            timeout: e.is_timeout(),
+            connect: e.is_connect(),
+            redirect: e.is_redirect(),
+            request: e.is_request(),
+            source: e.source().map(|s| s.to_string()),
+        }
+    }
+
+    /// 写入错误详情的特征，与 [`RequestErrorSignals::parse_detail`] 对应
+    pub fn detail(&self) -> String {
+        format!(
+            "错误链: {:?}\n是否超时: {}\n是否连接错误: {}\n是否重定向错误: {}\n是否请求错误: {}",
+            self.source.as_deref().unwrap_or_default(),
+            self.timeout,
+            self.connect,
+            self.redirect,
+            self.request
+        )
+    }
+
+    /// 从保存的错误详情中解析，不是请求错误的详情时返回 None。
+    /// 旧记录没有"是否请求错误"，此时 `request` 为 None
+    pub fn parse_detail(detail: &str) -> Option<(Self, Option<bool>)> {
+        let field = |name: &str| detail.lines().find_map(|line| line.strip_prefix(name));
+        let flag = |name: &str| field(name).map(|value| value.trim() == "true");
+        let timeout = flag("是否超时: ")?;
+        let source = field("错误链: ")
+            .map(|chain| chain.trim().trim_matches('"').replace("\\\"", "\""))
+            .filter(|chain| !chain.is_empty());
+        let request = flag("是否请求错误: ");
+        let signals = Self {
+            timeout,
+            connect: flag("是否连接错误: ").unwrap_or_default(),
+            redirect: flag("是否重定向错误: ").unwrap_or_default(),
+            request: request.unwrap_or_default(),
+            source,
+        };
+        Some((signals, request))
+    }
+}
+
+/// 重定向次数超过上限，`chain` 为依次访问的URL，最后一个是超限时将要跳转到的URL
+#[derive(Debug, thiserror::Error)]
+#[error("重定向超过 {max} 次: {}", .chain.join(" -> "))]
+pub struct RedirectLimitExceeded {
+    pub max: usize,
+    pub chain: Vec<String>,
+}
+
+impl RedirectLimitExceeded {
+    /// 从请求错误的错误链中取出
+    pub fn find(e: &reqwest::Error) -> Option<&Self> {
+        let mut source = e.source();
+        while let Some(error) = source {
+            if let Some(exceeded) = error.downcast_ref::<Self>() {
+                return Some(exceeded);
+            }
+            source = error.source();
+        }
+        None
+    }
+}
+
+/// 最多跟随 `max_redirects` 次重定向，超出时返回带重定向链的 [`RedirectLimitExceeded`]
+pub fn redirect_policy(max_redirects: usize) -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() > max_redirects {
+            let chain = attempt.previous().iter()
+                .chain([attempt.url()])
+                .map(|url| url.to_string())
+                .collect();
+            attempt.error(RedirectLimitExceeded { max: max_redirects, chain })
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+/// 单次监测运行的汇总
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitorSummary {
+    pub run_id: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub total: usize,
+    pub success: usize,
+    pub local_issues: usize,
+    pub remote_issues: usize,
+    pub centers: Vec<CenterSummary>,
+    /// 上一次检查成功、本次失败的URL数量
+    #[serde(default)]
+    pub new_failure_count: usize,
+    /// 上一次检查成功、本次失败的URL（最多 MAX_NEW_FAILURES 条）
+    #[serde(default)]
+    pub new_failures: Vec<NewFailure>,
+    /// 响应时间统计，没有任何响应时间时为 None
+    #[serde(default)]
+    pub response_times: Option<ResponseTimeStats>,
+    /// 运行被中途取消，只包含取消前已完成的URL
+    #[serde(default)]
+    pub cancelled: bool,
+    /// 本次运行配置快照的哈希，相同配置的运行哈希相同
+    #[serde(default)]
+    pub config_hash: Option<String>,
+    /// 各数据中心从 MongoDB 读取的数据集中有多少有可检查的URL
+    #[serde(default)]
+    pub coverage: Vec<CenterCoverage>,
+    /// 本次运行检查URL的顺序
+    #[serde(default)]
+    pub dispatch_order: DispatchOrder,
+    /// dispatch_order 为 shuffled 时打乱顺序的种子
+    #[serde(default)]
+    pub dispatch_seed: Option<u64>,
+    /// 启用自适应并发时的初始并发数和运行中的调整，未启用时为 None
+    #[serde(default)]
+    pub adaptive_concurrency: Option<AdaptiveConcurrencyStats>,
+    /// 写库失败（重试后仍失败）而写入溢出文件的记录数
+    #[serde(default)]
+    pub spilled: usize,
+    /// 溢出文件路径，可用 `data_monitor replay-spill` 重新写库
+    #[serde(default)]
+    pub spill_file: Option<String>,
+    /// 续跑的运行中，中断前已完成、本次没有重新检查的记录数
+    #[serde(default)]
+    pub resumed_records: usize,
+    /// 运行开始时检查所用的出口 IP、主机名和程序版本
+    #[serde(default)]
+    pub environment: Option<RunEnvironment>,
+    /// 只检查带该标签的数据集的运行，完整运行为 None
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// 按数据集标签的汇总，按标签排序
+    #[serde(default)]
+    pub tags: Vec<TagSummary>,
+    /// 响应时间中位数明显高于之前运行的数据中心，未配置 monitor.response_time_regression 时为空
+    #[serde(default)]
+    pub response_time_regressions: Vec<ResponseTimeRegression>,
+    /// 运行开始和结束时 DuckDB 文件的大小，只检查不写库的运行为 None
+    #[serde(default)]
+    pub storage: Option<RunStorage>,
+}
+
+/// DuckDB 数据库文件和 WAL 文件的大小（字节），文件不存在（如内存数据库、WAL 已写回）时为 None
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageSize {
+    pub db_bytes: Option<u64>,
+    pub wal_bytes: Option<u64>,
+}
+
+impl StorageSize {
+    pub fn total_bytes(&self) -> u64 {
+        self.db_bytes.unwrap_or(0) + self.wal_bytes.unwrap_or(0)
+    }
+}
+
+/// 一次运行前后的数据库大小
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunStorage {
+    pub start: StorageSize,
+    pub end: StorageSize,
+    /// duckdb.storage_soft_limit_mb 换算的字节数，未配置时为 None
+    #[serde(default)]
+    pub soft_limit_bytes: Option<u64>,
+    /// 超过软限制后按 duckdb.prune_retention_days 删除的检查记录数，没有清理时为 None
+    #[serde(default)]
+    pub pruned_records: Option<usize>,
+}
+
+impl RunStorage {
+    /// 本次运行数据库（含 WAL）增长的字节数，可能为负（如 WAL 写回后）
+    pub fn growth_bytes(&self) -> i64 {
+        self.end.total_bytes() as i64 - self.start.total_bytes() as i64
+    }
+
+    /// 运行结束时的大小是否超过软限制
+    pub fn over_soft_limit(&self) -> bool {
+        self.soft_limit_bytes.is_some_and(|limit| self.end.total_bytes() > limit)
+    }
+}
+
+/// monitor_runs 中记录的一次运行的数据库大小，用于查看增长趋势
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageSample {
+    pub run_id: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub total: i64,
+    pub start: StorageSize,
+    pub end: StorageSize,
+    /// end 与 start 之差（字节，含 WAL）
+    pub growth_bytes: i64,
+}
+
+/// 数据中心本次运行成功检查的响应时间中位数超过基线的 factor 倍
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseTimeRegression {
+    pub center_name: String,
+    pub median_ms: u64,
+    /// 之前各次运行中位数的中位数
+    pub baseline_ms: u64,
+    /// median_ms / baseline_ms
+    pub ratio: f64,
+    /// 参与计算基线的运行数
+    pub baseline_runs: usize,
+}
+
+/// 某次运行中检测到的响应时间变慢
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordedRegression {
+    pub run_id: String,
+    pub finished_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub regression: ResponseTimeRegression,
+}
+
+/// 监测运行的环境，数据中心反馈请求被拦截时用于核对出口 IP
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunEnvironment {
+    /// 配置的静态值或查询 monitor.egress_ip_url 得到，无法确定时为 None
+    pub egress_ip: Option<String>,
+    pub hostname: Option<String>,
+    pub version: String,
+    pub git_commit: String,
+}
+
+/// 一次检查中的单次请求尝试
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckAttempt {
+    /// 从 1 开始
+    pub attempt: u32,
+    pub duration_ms: u64,
+    pub status_code: Option<u16>,
+    pub error_category: Option<String>,
+}
+
+/// 响应时间统计使用的耗时
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeBasis {
+    /// 最后一次尝试的耗时（response_time_ms）
+    #[default]
+    Final,
+    /// 所有尝试和重试等待的总耗时（total_time_ms）
+    Total,
+}
+
+impl std::str::FromStr for TimeBasis {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "final" => Ok(Self::Final),
+            "total" => Ok(Self::Total),
+            other => anyhow::bail!("未知的耗时口径 {}，可选 final 或 total", other),
+        }
+    }
+}
+
+/// 数据集没有被检查的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// 没有 url 字段或为 null
+    NoUrl,
+    /// url 字段不是字符串（数组、对象等）
+    UnsupportedUrlType,
+    /// url 是字符串但无法检查，原因见 [`UrlIssue`]
+    UnusableUrl,
+}
+
+/// 数据集URL无法检查的原因，记录在 url_quality_issues 表中供数据中心修正
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UrlIssue {
+    /// 含空白字符、缺少协议等无法解析为URL
+    Unparseable,
+    /// 没有对应检查器的协议
+    UnsupportedScheme,
+    /// localhost、回环地址或 RFC1918 内网地址
+    PrivateAddress,
+    /// 空字符串、TODO、N/A、example.com 等占位内容
+    Placeholder,
+}
+
+impl UrlIssue {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UrlIssue::Unparseable => "unparseable",
+            UrlIssue::UnsupportedScheme => "unsupported_scheme",
+            UrlIssue::PrivateAddress => "private_address",
+            UrlIssue::Placeholder => "placeholder",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            UrlIssue::Unparseable => "URL格式错误，无法解析",
+            UrlIssue::UnsupportedScheme => "URL协议不受支持",
+            UrlIssue::PrivateAddress => "URL指向本机或内网地址",
+            UrlIssue::Placeholder => "URL为占位内容",
+        }
+    }
+}
+
+impl std::str::FromStr for UrlIssue {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unparseable" => Ok(UrlIssue::Unparseable),
+            "unsupported_scheme" => Ok(UrlIssue::UnsupportedScheme),
+            "private_address" => Ok(UrlIssue::PrivateAddress),
+            "placeholder" => Ok(UrlIssue::Placeholder),
+            other => Err(anyhow::anyhow!("未知的URL问题类型: {}", other)),
+        }
+    }
+}
+
+/// 最近一次运行中发现的无法检查的数据集URL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UrlQualityIssue {
+    pub center_name: String,
+    pub id: String,
+    pub raw_id: Option<String>,
+    pub name: Option<String>,
+    pub url: String,
+    pub reason: UrlIssue,
+    pub detected_at: DateTime<Utc>,
+}
+
+/// 单个数据中心按原因统计的URL问题数
+#[derive(Debug, Clone, Serialize)]
+pub struct UrlQualitySummary {
+    pub center_name: String,
+    pub total: usize,
+    pub reasons: BTreeMap<UrlIssue, usize>,
+}
+
+/// 多个数据中心登记的同一URL
+#[derive(Debug, Clone, Serialize)]
+pub struct SharedUrl {
+    pub url: String,
+    /// 按最早检查时间排序
+    pub centers: Vec<SharedUrlCenter>,
+    /// 最近一次检查记录的统计归属，计入每个数据中心时为 None
+    pub attributed_center: Option<String>,
+    pub attribution_rule: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SharedUrlCenter {
+    pub center_name: String,
+    pub first_checked_at: DateTime<Utc>,
+    pub checks: i64,
+}
+
+/// 单个数据中心在一次运行中的数据集覆盖情况
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CenterCoverage {
+    pub center_name: String,
+    /// 从 MongoDB 读取的数据集数
+    pub datasets: usize,
+    /// 有可检查URL的数据集数
+    pub with_url: usize,
+    /// 按原因统计的跳过数
+    pub skipped: std::collections::BTreeMap<SkipReason, usize>,
+    /// 有可检查URL的数据集占比（百分比）
+    pub coverage: f64,
+}
+
+impl CenterCoverage {
+    pub fn new(center_name: &str) -> Self {
+        Self {
+            center_name: center_name.to_string(),
+            datasets: 0,
+            with_url: 0,
+            skipped: Default::default(),
+            coverage: 0.0,
+        }
+    }
+
+    pub fn add(&mut self, skip: Option<SkipReason>) {
+        self.datasets += 1;
+        match skip {
+            Some(reason) => *self.skipped.entry(reason).or_default() += 1,
+            None => self.with_url += 1,
+        }
+        self.coverage = percentage(self.with_url as i64, self.datasets as i64);
+    }
+}
+
+/// 某次运行中一个数据中心的覆盖情况，用于查看趋势
+#[derive(Debug, Clone, Serialize)]
+pub struct CoveragePoint {
+    pub run_id: String,
+    pub finished_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub coverage: CenterCoverage,
+}
+
+/// 各数据中心最近一次运行的覆盖情况和历次运行的趋势（按时间倒序）
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverageReport {
+    pub latest: Vec<CoveragePoint>,
+    pub trend: Vec<CoveragePoint>,
+}
+
+impl CoverageReport {
+    /// `runs` 按时间倒序
+    pub fn from_runs(runs: &[MonitorSummary]) -> Self {
+        let trend: Vec<CoveragePoint> = runs.iter()
+            .flat_map(|run| run.coverage.iter().map(|coverage| CoveragePoint {
+                run_id: run.run_id.clone(),
+                finished_at: run.finished_at,
+                coverage: coverage.clone(),
+            }))
+            .collect();
+        let mut latest: Vec<CoveragePoint> = Vec::new();
+        for point in &trend {
+            if !latest.iter().any(|p| p.coverage.center_name == point.coverage.center_name) {
+                latest.push(point.clone());
+            }
+        }
+        Self { latest, trend }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdaptiveConcurrencyStats {
+    pub initial: usize,
+    #[serde(rename = "final")]
+    pub final_limit: usize,
+    pub adjustments: Vec<ConcurrencyAdjustment>,
+}
+
+/// 一次并发数调整
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConcurrencyAdjustment {
+    /// 调整时已完成的检查数
+    pub after_checks: usize,
+    /// 距运行开始检查的时间
+    pub elapsed_ms: u64,
+    pub from: usize,
+    pub to: usize,
+    /// 触发调整的超时和连接错误比例（0-1）
+    pub error_rate: f64,
+}
+
+/// 保存的监测运行：汇总和运行时的配置快照
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredRun {
+    pub summary: MonitorSummary,
+    pub config_hash: Option<String>,
+    pub config: Option<serde_json::Value>,
+}
+
+/// 一次运行中URL响应时间的统计（毫秒）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseTimeStats {
+    pub avg_ms: f64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+}
+
+impl ResponseTimeStats {
+    pub fn from_millis(mut times: Vec<u64>) -> Option<Self> {
+        if times.is_empty() {
+            return None;
+        }
+        times.sort_unstable();
+        let percentile = |p: usize| times[(times.len() - 1) * p / 100];
+        Some(Self {
+            avg_ms: times.iter().sum::<u64>() as f64 / times.len() as f64,
+            p50_ms: percentile(50),
+            p95_ms: percentile(95),
+            max_ms: times[times.len() - 1],
+        })
+    }
+}
+
+/// 由成功变为失败的URL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewFailure {
+    pub id: String,
+    pub url: String,
+    pub name: Option<String>,
+    pub center_name: String,
+    pub status_code: Option<u16>,
+    pub error_category: Option<String>,
+    pub error_msg: Option<String>,
+    pub is_likely_local_issue: bool,
+    #[serde(default)]
+    pub failed_hop_index: Option<u32>,
+    #[serde(default)]
+    pub failed_hop_url: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 该URL之前最后一次检查成功的时间
+    #[serde(default)]
+    pub last_success_at: Option<DateTime<Utc>>,
+}
+
+/// 带某个标签的数据集在一次运行中的汇总
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagSummary {
+    pub tag: String,
+    pub total: usize,
+    pub success: usize,
+    /// 成功率（百分比）
+    pub success_rate: f64,
+}
+
+/// 单个数据中心在一次运行中的汇总
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CenterSummary {
+    pub center_name: String,
+    pub total: usize,
+    pub success: usize,
+    /// 成功率（百分比）
+    pub success_rate: f64,
+    pub local_issues: usize,
+    pub remote_issues: usize,
+    /// 按数量降序排列的错误类别
+    pub error_categories: Vec<CategoryCount>,
+    /// 部分失败的URL样例
+    pub sample_failures: Vec<String>,
+    /// 该数据中心最后一个URL检查完成的时间，用于比较各数据中心结果的时效
+    #[serde(default)]
+    pub finished_at: Option<DateTime<Utc>>,
+    /// 本次运行中该数据中心的并发统计，没有实际检查的URL时为 None
+    #[serde(default)]
+    pub concurrency: Option<CenterConcurrency>,
+    /// 成功检查的响应时间中位数（毫秒），没有成功检查时为 None
+    #[serde(default)]
+    pub median_response_ms: Option<u64>,
+}
+
+/// 单个数据中心在一次运行中的检查并发情况，用于调整 max_concurrent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CenterConcurrency {
+    /// 同时进行的检查数峰值
+    pub peak_concurrent: usize,
+    pub checks: usize,
+    /// 第一个检查开始到最后一个检查结束的时间
+    pub wall_time_ms: u64,
+    /// 记录创建到开始检查的平均等待时间
+    pub avg_queue_wait_ms: f64,
+    /// 每秒完成的检查数
+    pub throughput_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryCount {
+    pub category: String,
+    pub count: usize,
+}
+
+/// 每个数据中心保留的失败URL样例数
+const SAMPLE_FAILURES: usize = 10;
+/// 汇总中保留的新失败URL数
+pub const MAX_NEW_FAILURES: usize = 500;
+
+impl MonitorSummary {
+    /// `previous_status` 为各记录 id 上一次检查的状态码，用于找出新失败的URL
+    pub fn from_records(
+        run_id: &str,
+        started_at: DateTime<Utc>,
+        records: &[MonitorRecord],
+        previous_status: &HashMap<String, Option<u16>>,
+        success: &SuccessStatuses,
+    ) -> Self {
+        let mut centers: Vec<CenterSummary> = Vec::new();
+        let mut tags: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        let mut new_failures = Vec::new();
+        let mut new_failure_count = 0;
+        for record in records {
+            for tag in &record.tags {
+                let (total, succeeded) = tags.entry(tag.as_str()).or_default();
+                *total += 1;
+                if success.is_success(record.status_code) {
+                    *succeeded += 1;
+                }
+            }
+            if !success.is_success(record.status_code)
+                && previous_status.get(&record.id).is_some_and(|previous| success.is_success(*previous))
+            {
+                new_failure_count += 1;
+                if new_failures.len() < MAX_NEW_FAILURES {
+                    new_failures.push(NewFailure::from_record(record));
+                }
+            }
+            let index = match centers.iter().position(|c| c.center_name == record.center_name) {
+                Some(index) => index,
+                None => {
+                    centers.push(CenterSummary::new(&record.center_name));
+                    centers.len() - 1
+                }
+            };
+            centers[index].add(record, success);
+        }
+        for center in &mut centers {
+            center.finish();
+            let times = records.iter()
+                .filter(|r| r.center_name == center.center_name && success.is_success(r.status_code))
+                .filter_map(|r| r.response_time_ms)
+                .collect();
+            center.median_response_ms = ResponseTimeStats::from_millis(times).map(|stats| stats.p50_ms);
+        }
+
+        Self {
+            run_id: run_id.to_string(),
+            started_at,
+            finished_at: Utc::now(),
+            total: records.len(),
+            success: centers.iter().map(|c| c.success).sum(),
+            local_issues: centers.iter().map(|c| c.local_issues).sum(),
+            remote_issues: centers.iter().map(|c| c.remote_issues).sum(),
+            centers,
+            new_failure_count,
+            new_failures,
+            response_times: ResponseTimeStats::from_millis(records.iter().filter_map(|r| r.response_time_ms).collect()),
+            cancelled: false,
+            config_hash: None,
+            coverage: Vec::new(),
+            dispatch_order: DispatchOrder::default(),
+            dispatch_seed: None,
+            adaptive_concurrency: None,
+            spilled: 0,
+            spill_file: None,
+            resumed_records: 0,
+            environment: None,
+            tag: None,
+            response_time_regressions: Vec::new(),
+            storage: None,
+            tags: tags.into_iter()
+                .map(|(tag, (total, success))| TagSummary {
+                    tag: tag.to_string(),
+                    total,
+                    success,
+                    success_rate: percentage(success as i64, total as i64),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn center(&self, name: &str) -> Option<&CenterSummary> {
+        self.centers.iter().find(|c| c.center_name == name)
+    }
+}
+
+impl NewFailure {
+    fn from_record(record: &MonitorRecord) -> Self {
+        Self {
+            id: record.id.clone(),
+            url: record.url.clone(),
+            name: record.name.clone(),
+            center_name: record.center_name.clone(),
+            status_code: record.status_code,
+            error_category: record.error_category.clone(),
+            error_msg: record.error_msg.clone(),
+            is_likely_local_issue: record.is_likely_local_issue,
+            failed_hop_index: record.failed_hop_index,
+            failed_hop_url: record.failed_hop_url.clone(),
+            tags: record.tags.clone(),
+            last_success_at: None,
+        }
+    }
+}
+
+impl CenterSummary {
+    fn new(center_name: &str) -> Self {
+        Self {
+            center_name: center_name.to_string(),
+            total: 0,
+            success: 0,
+            success_rate: 0.0,
+            local_issues: 0,
+            remote_issues: 0,
+            error_categories: Vec::new(),
+            sample_failures: Vec::new(),
+            finished_at: None,
+            concurrency: None,
+            median_response_ms: None,
+        }
+    }
+
+    fn add(&mut self, record: &MonitorRecord, success: &SuccessStatuses) {
+        self.total += 1;
+        self.finished_at = self.finished_at.max(Some(record.check_time));
+        if success.is_success(record.status_code) {
+            self.success += 1;
+            return;
+        }
+        if record.is_likely_local_issue {
+            self.local_issues += 1;
+        } else if record.error_category.is_some() {
+            self.remote_issues += 1;
+        }
+        let category = record.error_category.clone().unwrap_or_else(|| match record.status_code {
+            Some(code) => format!("HTTP_{}", code),
+            None => ErrorCategory::Unknown.to_string(),
+        });
+        match self.error_categories.iter_mut().find(|c| c.category == category) {
+            Some(c) => c.count += 1,
+            None => self.error_categories.push(CategoryCount { category, count: 1 }),
+        }
+        if self.sample_failures.len() < SAMPLE_FAILURES {
+            self.sample_failures.push(match record.failed_after_redirect() {
+                Some((index, hop_url)) => format!("{} (第 {} 次重定向后失败: {})", record.url, index, hop_url),
+                None => record.url.clone(),
+            });
+        }
+    }
+
+    fn finish(&mut self) {
+        self.success_rate = if self.total > 0 {
+            self.success as f64 * 100.0 / self.total as f64
+        } else {
+            0.0
+        };
+        self.error_categories.sort_by_key(|c| std::cmp::Reverse(c.count));
+    }
+}
+
 // 辅助结构体定义
 #[derive(Debug)]
 pub struct ErrorCategoryStats {
@@ -231,13 +1151,116 @@ pub struct UrlHealthReport {
     pub recent_checks: Vec<HealthCheck>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct HealthCheck {
     pub check_time: String,
     pub status_code: Option<i32>,
     pub error_category: Option<String>,
     pub response_time_ms: Option<i64>,
     pub is_likely_local_issue: bool,
+    pub http_version: Option<String>,
+    pub connection_reused: Option<bool>,
+    /// 实际请求的URL，与 url 不同说明去掉了查询参数
+    pub requested_url: Option<String>,
+}
+
+/// 按数据中心自己的ID查询到的数据集及其检查结果
+#[derive(Debug, Clone, Serialize)]
+pub struct DatasetChecks {
+    /// `@id`，查询时传入的是 casdc_id 时由 MongoDB 解析得到
+    pub raw_id: String,
+    pub casdc_id: Option<String>,
+    pub name: Option<String>,
+    pub center_name: Option<String>,
+    pub latest: Option<MonitorRecord>,
+    /// 最近的检查，按检查时间倒序，包含 latest
+    pub history: Vec<MonitorRecord>,
+    /// latest 的URL第一次检查的时间
+    pub first_seen_at: Option<DateTime<Utc>>,
+    /// latest 的URL最后一次检查成功的时间
+    pub last_success_at: Option<DateTime<Utc>>,
+}
+
+/// URL第一次检查和最后一次检查成功的时间，保存在 DuckDB 的 url_status 表
+#[derive(Debug, Clone, Serialize)]
+pub struct UrlStatus {
+    pub first_seen_at: DateTime<Utc>,
+    /// 从未成功时为 None
+    pub last_success_at: Option<DateTime<Utc>>,
+}
+
+/// 统计数据的时效：最近一次检查的时间（整体和各数据中心）和最近一次完成的运行
+#[derive(Debug, Clone, Serialize)]
+pub struct DataAsOf {
+    pub latest_check_time: Option<DateTime<Utc>>,
+    pub centers: BTreeMap<String, DateTime<Utc>>,
+    pub latest_run: Option<LatestRun>,
+}
+
+/// 最近一次写入汇总的运行
+#[derive(Debug, Clone, Serialize)]
+pub struct LatestRun {
+    pub run_id: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub total: i64,
+    pub success: i64,
+    /// monitor_run_state 中的状态（finished、running、aborted），没有运行状态的早期运行为 None
+    pub status: Option<String>,
+}
+
+impl Display for DataAsOf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.latest_check_time {
+            Some(time) => write!(f, "数据截至 {}", time.format("%Y-%m-%d %H:%M:%S UTC"))?,
+            None => write!(f, "还没有检查记录")?,
+        }
+        if let Some(run) = &self.latest_run {
+            write!(f, "（最近的运行 {} 结束于 {}", run.run_id, run.finished_at.format("%Y-%m-%d %H:%M:%S UTC"))?;
+            if let Some(status) = &run.status {
+                write!(f, "，{}", status)?;
+            }
+            write!(f, "）")?;
+        }
+        Ok(())
+    }
+}
+
+impl DataAsOf {
+    /// 给统计命令的输出加上 `data_as_of` 字段：对象直接加字段，数组等其他值放到 `items` 中
+    pub fn attach<T: Serialize>(as_of: Option<&DataAsOf>, output: &T) -> serde_json::Result<serde_json::Value> {
+        let as_of = serde_json::to_value(as_of)?;
+        Ok(match serde_json::to_value(output)? {
+            serde_json::Value::Object(mut map) => {
+                map.insert("data_as_of".to_string(), as_of);
+                serde_json::Value::Object(map)
+            }
+            items => serde_json::json!({ "data_as_of": as_of, "items": items }),
+        })
+    }
+}
+
+/// 响应体指纹发生变化的检查
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentChange {
+    pub url: String,
+    pub center_name: String,
+    pub name: Option<String>,
+    pub check_time: String,
+    pub status_code: Option<i32>,
+    pub content_hash: String,
+}
+
+/// 链接报告中的一条失败链接：URL在一次运行中最后一次检查的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct BrokenLink {
+    pub url: String,
+    pub name: Option<String>,
+    pub status_code: Option<i32>,
+    pub status_text: Option<String>,
+    pub error_category: Option<String>,
+    pub error_msg: Option<String>,
+    pub check_time: String,
 }
 
 #[derive(Debug)]
@@ -249,7 +1272,7 @@ pub struct NetworkIssueTrend {
     pub local_issue_rate: f64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ProblematicUrl {
     pub url: String,
     pub center_name: String,
@@ -260,12 +1283,586 @@ pub struct ProblematicUrl {
     pub avg_response_time_ms: Option<f64>,
     pub last_check: String,
     pub last_error: Option<String>,
+    /// 该URL第一次检查的时间
+    pub first_seen_at: Option<String>,
+    /// 该URL最后一次检查成功的时间，从未成功时为 None
+    pub last_success_at: Option<String>,
+    /// 最近几次失败的检查，按时间倒序
+    pub recent_failures: Vec<RecentFailure>,
+}
+
+/// 问题URL的一次失败检查
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentFailure {
+    pub check_time: String,
+    pub status_code: Option<i32>,
+    pub error_category: Option<String>,
+    pub error_msg: Option<String>,
+    /// 失败发生在第几跳，0 为URL本身，大于 0 时失败发生在重定向之后
+    pub failed_hop_index: Option<i32>,
+    pub failed_hop_url: Option<String>,
+}
+
+/// 周报中单个数据中心的可用性
+#[derive(Debug, Clone, Serialize)]
+pub struct CenterAvailability {
+    pub center_name: String,
+    pub total_checks: i64,
+    pub success_checks: i64,
+    /// 可用率（百分比）
+    pub availability: f64,
+    pub avg_response_time_ms: Option<f64>,
+}
+
+/// 单个数据中心各阶段的平均耗时
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingBreakdown {
+    pub center_name: String,
+    pub checks: i64,
+    /// 新建连接（测量到建连耗时）的检查数
+    pub new_connections: i64,
+    pub avg_dns_ms: Option<f64>,
+    pub avg_connect_ms: Option<f64>,
+    pub avg_ttfb_ms: Option<f64>,
+    pub avg_total_ms: Option<f64>,
+}
+
+/// 单个数据中心按HTTP版本统计的检查数
+#[derive(Debug, Clone, Serialize)]
+pub struct HttpVersionCount {
+    pub center_name: String,
+    pub http_version: String,
+    pub checks: i64,
+    /// 其中复用连接的检查数
+    pub reused: i64,
+}
+
+/// 状态码统计的分组方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusGrouping {
+    /// 按 2xx/3xx/4xx/5xx 分组
+    #[default]
+    Class,
+    /// 按具体状态码分组
+    Code,
+}
+
+impl std::str::FromStr for StatusGrouping {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "class" => Ok(Self::Class),
+            "code" => Ok(Self::Code),
+            other => anyhow::bail!("未知的分组方式 {}，可选 class 或 code", other),
+        }
+    }
+}
+
+/// 状态码（或状态码类别）的检查数，没有收到响应的检查标记为 no_response 并排在最后
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusCodeStats {
+    /// `2xx` 等类别、具体状态码或 `no_response`
+    pub label: String,
+    pub count: i64,
+    /// 占所有检查的百分比
+    pub percentage: f64,
+}
+
+/// 按主机统计的排序方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HostSort {
+    /// 按最近一次检查失败的数据集数
+    #[default]
+    Failures,
+    /// 按数据集数
+    Datasets,
+}
+
+impl std::str::FromStr for HostSort {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "failures" => Ok(Self::Failures),
+            "datasets" => Ok(Self::Datasets),
+            other => anyhow::bail!("未知的排序方式 {}，可选 failures 或 datasets", other),
+        }
+    }
+}
+
+/// URL主机的汇总，一个主机可能托管多个数据中心的数据集
+#[derive(Debug, Clone, Serialize)]
+pub struct HostStats {
+    pub host: String,
+    /// 有数据集在该主机上的数据中心，按名称排序
+    pub centers: Vec<String>,
+    pub datasets: i64,
+    /// 最近一次检查失败的数据集数
+    pub failing_datasets: i64,
+    pub total_checks: i64,
+    pub failed_checks: i64,
+    /// 失败检查占所有检查的百分比
+    pub failure_rate: f64,
+    pub last_check: String,
+}
+
+/// 周报中每天的整体可用性
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyAvailability {
+    /// YYYY-MM-DD
+    pub date: String,
+    pub total_checks: i64,
+    pub success_checks: i64,
+    pub availability: f64,
+}
+
+/// 一小时内的检查统计
+#[derive(Debug, Clone, Serialize)]
+pub struct HourlyStats {
+    /// 小时开始时间（UTC），如 2024-11-02T13:00:00Z
+    pub hour: String,
+    pub total_checks: i64,
+    pub success_checks: i64,
+    pub availability: f64,
+    /// 没有响应时间的检查不计入
+    pub avg_response_time_ms: Option<f64>,
+}
+
+/// 趋势统计的时间粒度，按 UTC 划分，周从周一开始
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrendGranularity {
+    Hour,
+    #[default]
+    Day,
+    Week,
+}
+
+impl std::str::FromStr for TrendGranularity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hour" => Ok(Self::Hour),
+            "day" => Ok(Self::Day),
+            "week" => Ok(Self::Week),
+            other => anyhow::bail!("未知的时间粒度 {}，可选 hour、day 或 week", other),
+        }
+    }
+}
+
+impl TrendGranularity {
+    pub const ALL: [TrendGranularity; 3] = [Self::Hour, Self::Day, Self::Week];
+
+    /// DuckDB date_trunc 的单位
+    pub fn sql_unit(&self) -> &'static str {
+        match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+            Self::Week => "week",
+        }
+    }
+
+    fn truncate(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        use chrono::{Datelike, Timelike};
+        let hour = time.date_naive().and_hms_opt(time.hour(), 0, 0).unwrap_or_default().and_utc();
+        let day = time.date_naive().and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+        match self {
+            Self::Hour => hour,
+            Self::Day => day,
+            Self::Week => day - chrono::Duration::days(time.weekday().num_days_from_monday() as i64),
+        }
+    }
+
+    fn step(&self) -> chrono::Duration {
+        match self {
+            Self::Hour => chrono::Duration::hours(1),
+            Self::Day => chrono::Duration::days(1),
+            Self::Week => chrono::Duration::weeks(1),
+        }
+    }
+
+    /// [since, until) 内的所有时间段开始时间，格式与查询中 [`TREND_BUCKET_FORMAT`] 一致
+    pub fn buckets(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Vec<String> {
+        let mut buckets = Vec::new();
+        let mut bucket = self.truncate(since);
+        while bucket < until {
+            buckets.push(bucket.format(TREND_BUCKET_FORMAT).to_string());
+            bucket += self.step();
+        }
+        buckets
+    }
+}
+
+/// 查询中时间段开始时间的 strftime 格式
+pub const TREND_BUCKET_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// 一个时间段内的数量
+#[derive(Debug, Clone, Serialize)]
+pub struct TrendPoint {
+    /// 时间段开始时间（UTC）
+    pub bucket: String,
+    pub count: i64,
+}
+
+/// 按 (时间段, 类别, 数量) 的分组结果整理为每个类别的时间序列，
+/// 每个类别都包含 `buckets` 中的所有时间段，没有数据的为 0
+pub fn pivot_trends(buckets: &[String], rows: Vec<(String, String, i64)>) -> BTreeMap<String, Vec<TrendPoint>> {
+    let mut counts: BTreeMap<String, HashMap<String, i64>> = BTreeMap::new();
+    for (bucket, category, count) in rows {
+        *counts.entry(category).or_default().entry(bucket).or_default() += count;
+    }
+    counts.into_iter()
+        .map(|(category, by_bucket)| {
+            let series = buckets.iter()
+                .map(|bucket| TrendPoint { bucket: bucket.clone(), count: by_bucket.get(bucket).copied().unwrap_or(0) })
+                .collect();
+            (category, series)
+        })
+        .collect()
+}
+
+/// 数据中心一天（或合并后的连续多天）的检查数和成功率
+#[derive(Debug, Clone, Serialize)]
+pub struct CenterTrendPoint {
+    /// 时间段第一天，YYYY-MM-DD（UTC）
+    pub date: String,
+    pub total: i64,
+    /// 没有检查时为 None
+    pub success_rate: Option<f64>,
+}
+
+/// 单个数据中心的成功率序列
+#[derive(Debug, Clone, Serialize)]
+pub struct CenterTrend {
+    pub center: String,
+    pub points: Vec<CenterTrendPoint>,
+}
+
+/// 把按 (数据中心, 日期, 检查数, 成功数) 分组的结果整理为每个数据中心从 `first_day` 起 `days` 天的序列，
+/// 没有检查的日期补 0。天数超过 `max_points` 时每 ceil(days / max_points) 天合并为一个点，
+/// 点的日期为合并的第一天。`centers` 中的数据中心没有检查时也输出
+pub fn center_trends(first_day: NaiveDate, days: usize, max_points: usize, centers: &[String],
+                     rows: Vec<(String, String, i64, i64)>) -> Vec<CenterTrend> {
+    let bucket_days = days.div_ceil(max_points.max(1)).max(1);
+    let bucket_count = days.div_ceil(bucket_days);
+    let mut counts: BTreeMap<String, Vec<(i64, i64)>> = centers.iter()
+        .map(|center| (center.clone(), vec![(0, 0); bucket_count]))
+        .collect();
+    for (center, date, total, success) in rows {
+        let Ok(date) = NaiveDate::parse_from_str(&date, "%Y-%m-%d") else {
+            continue;
+        };
+        let offset = (date - first_day).num_days();
+        if offset < 0 || offset as usize >= days {
+            continue;
+        }
+        let bucket = &mut counts.entry(center).or_insert_with(|| vec![(0, 0); bucket_count])[offset as usize / bucket_days];
+        bucket.0 += total;
+        bucket.1 += success;
+    }
+    counts.into_iter()
+        .map(|(center, buckets)| CenterTrend {
+            center,
+            points: buckets.into_iter().enumerate()
+                .map(|(i, (total, success))| CenterTrendPoint {
+                    date: (first_day + chrono::Duration::days((i * bucket_days) as i64)).format("%Y-%m-%d").to_string(),
+                    total,
+                    success_rate: (total > 0).then(|| percentage(success, total)),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// 周报数据，时间范围为 [period_start, period_end)
+#[derive(Debug, Clone, Serialize)]
+pub struct WeeklyReport {
+    /// ISO 周，如 2025-W07
+    pub week: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub generated_at: DateTime<Utc>,
+    pub total_checks: i64,
+    pub success_checks: i64,
+    pub availability: f64,
+    pub centers: Vec<CenterAvailability>,
+    pub daily: Vec<DailyAvailability>,
+    pub top_urls: Vec<ProblematicUrl>,
+    pub error_categories: Vec<CategoryCount>,
+}
+
+/// 概览页数据，时间范围为最近 `days` 天
+#[derive(Debug, Clone, Serialize)]
+pub struct Dashboard {
+    pub days: u32,
+    pub generated_at: DateTime<Utc>,
+    pub total_checks: i64,
+    pub success_checks: i64,
+    pub availability: f64,
+    pub centers: Vec<CenterAvailability>,
+    pub daily: Vec<DailyAvailability>,
+    pub problematic_urls: Vec<ProblematicUrl>,
+    pub last_run: Option<MonitorSummary>,
 }
+
+/// 单个数据中心一次数据获取的结果，失败时错误记录在 `list_error` 中
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CenterFetchReport {
+    pub name: String,
+    /// 数据集列表接口返回的ID总数
+    pub listed: usize,
+    /// 其中新发现的ID数
+    pub discovered: usize,
+    /// 本次只请求了 updatedSince 之后有变化的ID
+    pub incremental: bool,
+    /// 增量获取时有变化、重新排队获取详情的已处理ID数
+    pub requeued: usize,
+    /// 完整获取时库中有、列表中已没有的ID数
+    pub missing_from_list: usize,
+    /// 本次成功处理的数据集详情数
+    pub processed: usize,
+    /// 详情获取、解析或保存失败的数量，这些ID保持待处理状态
+    pub detail_failures: usize,
+    /// 其中超过 max_pending_age_days 而被标记为过期的数量
+    pub expired: usize,
+    /// 获取列表、token 等导致整个数据中心中止的错误
+    pub list_error: Option<String>,
+    pub duration_ms: u64,
+    /// 运行被中途取消，只包含取消前已完成的部分
+    pub cancelled: bool,
+    /// 本次获取结束后各状态的ID数，统计失败时为 None
+    pub backlog: Option<IdBacklog>,
+}
+
+impl CenterFetchReport {
+    pub fn is_success(&self) -> bool {
+        self.list_error.is_none()
+    }
+}
+
+/// 一次数据获取运行的结果，按数据中心列出
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FetchReport {
+    pub per_center: Vec<CenterFetchReport>,
+}
+
+/// 试运行报告：执行所有请求和解析，但不写 MongoDB
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FetchPlan {
+    pub center_name: String,
+    /// 数据集列表接口返回的ID总数
+    pub listed: usize,
+    /// 库中没有、会被新增为待处理的ID
+    pub new_ids: Vec<String>,
+    /// 详情获取并解析成功的数量
+    pub parsed: usize,
+    pub failures: Vec<FetchPlanFailure>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchPlanFailure {
+    pub id: String,
+    pub reason: String,
+}
+
+/// 数据获取审计记录，保存在 MongoDB 的 fetch_audit 集合
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchAudit {
+    pub center_name: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub success: bool,
+    pub outcome: Option<CenterFetchReport>,
+    pub error: Option<String>,
+}
+
+/// 单个数据中心一次数据获取的请求性能，保存在 DuckDB 的 fetch_metrics 表
+#[derive(Debug, Clone, Serialize)]
+pub struct FetchMetrics {
+    pub center_name: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub list_requests: u64,
+    /// 所有列表请求的总耗时
+    pub list_duration_ms: u64,
+    pub detail_requests: u64,
+    pub avg_detail_ms: Option<f64>,
+    /// 读取的响应体字节数（解压后）
+    pub bytes: u64,
+    /// 失败的请求数
+    pub errors: u64,
+}
+
+/// 一次 CMDB 推送的结果，保存在 DuckDB 的 cmdb_deliveries 表
+#[derive(Debug, Clone, Serialize)]
+pub struct CmdbDelivery {
+    pub center_name: String,
+    pub run_id: String,
+    pub delivered_at: DateTime<Utc>,
+    pub success: bool,
+    pub attempts: u32,
+    /// 最后一次请求的HTTP状态码，没有收到响应时为 None
+    pub http_status: Option<u16>,
+    pub error: Option<String>,
+}
+
+/// 单个数据中心待处理ID的统计
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PendingStats {
+    pub center_name: String,
+    pub pending: u64,
+    /// 待处理且至少失败过一次
+    pub failed: u64,
+    pub expired: u64,
+}
+
+/// 单个数据中心 processed_dataset_ids 中各状态的ID数，即已发现但尚未处理的积压
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IdBacklog {
+    pub center_name: String,
+    pub pending: u64,
+    /// 已被数据获取实例或详情工作任务认领、正在处理
+    #[serde(default)]
+    pub in_progress: u64,
+    pub processed: u64,
+    /// 待处理且至少失败过一次，包含在 pending 中
+    pub failed: u64,
+    pub expired: u64,
+}
+
+/// 跨数据中心重复的一组数据集，保存在 MongoDB 的 dataset_duplicates 集合
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    /// 组内成员共用的键，如 `raw_id:...`、`doi:10.xxx/...`
+    pub keys: Vec<String>,
+    /// 按 (数据中心, raw_id) 排序，第一个为代表成员
+    pub members: Vec<DuplicateMember>,
+    pub detected_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateMember {
+    pub center_name: String,
+    pub raw_id: String,
+    pub url: Option<String>,
+}
+
+/// 两个数据中心之间重复的数据集组数
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicatePairCount {
+    pub center_a: String,
+    pub center_b: String,
+    pub groups: usize,
+}
+
+/// 告警状态，按 (rule, subject) 去重
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertState {
+    pub rule: String,
+    pub subject: String,
+    /// 仍处于告警中（尚未恢复）
+    pub open: bool,
+    pub last_fired_at: Option<DateTime<Utc>>,
+    pub last_value: Option<f64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 尚未结束的监测运行（异常退出或被取消），可以续跑
+#[derive(Debug, Clone, Serialize)]
+pub struct UnfinishedRun {
+    pub run_id: String,
+    pub started_at: DateTime<Utc>,
+    /// 运行监测的数据中心
+    pub centers: Vec<String>,
+    pub updated_at: DateTime<Utc>,
+    /// 已完成的记录数
+    pub completed: usize,
+}
+
+/// check_method 为 auto 时记住的拒绝 HEAD 的主机
+#[derive(Debug, Clone, Serialize)]
+pub struct MethodCacheEntry {
+    pub host: String,
+    pub learned_at: DateTime<Utc>,
+    /// 上次尝试 HEAD 之后直接用 GET 的运行次数，达到 monitor.head_retest_runs 时重新尝试
+    pub runs_since_probe: u32,
+}
+
+/// 自动识别的慢主机
+#[derive(Debug, Clone, Serialize)]
+pub struct LearnedSlowHost {
+    pub host: String,
+    pub timeout_secs: u64,
+    pub learned_at: DateTime<Utc>,
+    /// 识别时统计范围内成功的检查数和其中慢响应的次数
+    pub total_checks: i64,
+    pub slow_checks: i64,
+}
+
+/// 重新分类时读取的一条检查记录
+#[derive(Debug, Clone)]
+pub struct StoredClassification {
+    pub row_id: i64,
+    pub id: String,
+    pub url: String,
+    pub check_time: String,
+    pub status_code: Option<u16>,
+    pub error_category: Option<String>,
+    pub error_detail: Option<String>,
+    pub auth_used: bool,
+    pub http_version: Option<String>,
+    pub is_likely_local_issue: bool,
+}
+
+/// 压缩时读取和写回的文本列
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredText {
+    pub row_id: i64,
+    pub error_msg: Option<String>,
+    pub error_detail: Option<String>,
+    pub headers: Option<String>,
+}
+
+impl StoredText {
+    /// 三列的总字节数
+    pub fn bytes(&self) -> usize {
+        [&self.error_msg, &self.error_detail, &self.headers].iter()
+            .filter_map(|text| text.as_ref().map(String::len))
+            .sum()
+    }
+}
+
+/// 重新分类后要写回的值
+#[derive(Debug, Clone)]
+pub struct Reclassification {
+    pub row_id: i64,
+    pub error_category: Option<String>,
+    pub is_likely_local_issue: bool,
+}
+
+/// 百分比，总数为 0 时返回 0
+pub fn percentage(part: i64, total: i64) -> f64 {
+    if total > 0 {
+        part as f64 * 100.0 / total as f64
+    } else {
+        0.0
+    }
+}
+
 #[derive(Debug)]
 pub struct ResponseInfo {
     pub(crate) status_code: u16,
     pub(crate) status_text: String,
     pub(crate) headers: Option<String>,
+    pub(crate) http_version: String,
+    pub(crate) content_hash: Option<String>,
+    /// 读取响应体的耗时，不读取时为 None
+    pub(crate) body_read: Option<std::time::Duration>,
+    /// 没有跟随的 3xx 响应的 Location
+    pub(crate) redirect_location: Option<String>,
 }
 
 #[derive(Debug)]
@@ -274,8 +1871,30 @@ pub struct CheckError {
     pub(crate) message: String,
     pub(crate) detail: String,
     pub(crate) status_code: Option<u16>,
+    /// 收到响应（4xx/5xx）时的HTTP版本
+    pub(crate) http_version: Option<String>,
+    /// 发出请求后失败时，失败的那一跳
+    pub(crate) failed_hop: Option<FailedHop>,
+}
+
+/// 重定向链中失败的一跳，`index` 为 0 表示请求的URL本身
+#[derive(Debug)]
+pub struct FailedHop {
+    pub(crate) index: usize,
+    pub(crate) url: String,
+    /// 该跳返回了没有跟随的重定向时的 Location
+    pub(crate) location: Option<String>,
 }
 impl Dataset {
+    /// 没有可检查的URL时的原因，与 [`Dataset::extract_url`] 返回 None 的情况对应
+    pub fn url_skip_reason(&self) -> Option<SkipReason> {
+        match &self.url {
+            Some(Bson::String(_)) => None,
+            None | Some(Bson::Null) => Some(SkipReason::NoUrl),
+            Some(_) => Some(SkipReason::UnsupportedUrlType),
+        }
+    }
+
     pub fn extract_url(&self) -> Option<String> {
         match &self.url {
             Some(Bson::String(s)) => Some(s.clone()),
@@ -306,6 +1925,31 @@ impl Dataset {
         }
     }
 
+    /// `@id` 与 `schema:identifier` 中所有规范化后的 DOI
+    pub fn extract_dois(&self) -> Vec<String> {
+        fn collect(value: &Bson, out: &mut Vec<String>) {
+            match value {
+                Bson::String(s) => out.extend(crate::duplicates::normalize_doi(s)),
+                Bson::Array(arr) => arr.iter().for_each(|item| collect(item, out)),
+                Bson::Document(doc) => {
+                    for key in ["@value", "@id", "schema:value"] {
+                        if let Some(value) = doc.get(key) {
+                            collect(value, out);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let mut dois: Vec<String> = crate::duplicates::normalize_doi(&self.raw_id).into_iter().collect();
+        if let Some(identifier) = &self.identifier {
+            collect(identifier, &mut dois);
+        }
+        dois.sort();
+        dois.dedup();
+        dois
+    }
+
     pub fn extract_date_published(&self) -> String {
         match &self.date_published {
             Some(Bson::String(s)) => s.clone(),
@@ -313,4 +1957,129 @@ impl Dataset {
             _ => "unknown".to_string(),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_grouping_parses_modes() {
+        assert_eq!("class".parse::<StatusGrouping>().unwrap(), StatusGrouping::Class);
+        assert_eq!("code".parse::<StatusGrouping>().unwrap(), StatusGrouping::Code);
+        assert_eq!(StatusGrouping::default(), StatusGrouping::Class);
+        assert!("Class".parse::<StatusGrouping>().is_err());
+        assert!("".parse::<StatusGrouping>().is_err());
+    }
+
+    #[test]
+    fn error_category_all_is_exhaustive() {
+        let mut names = std::collections::HashSet::new();
+        for (i, category) in ErrorCategory::ALL.iter().enumerate() {
+            assert_eq!(category.index(), i);
+            let name = category.to_string();
+            assert!(names.insert(name.clone()), "重复的类别名称 {}", name);
+            assert_eq!(name.parse::<ErrorCategory>().unwrap().index(), i);
+        }
+        // 按 Display 的名称区分大小写，不接受变体名
+        for invalid in ["timeout_error", "Timeout", "TIMEOUT", ""] {
+            assert!(invalid.parse::<ErrorCategory>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn trend_granularity_all_round_trips() {
+        for granularity in TrendGranularity::ALL {
+            assert_eq!(granularity.sql_unit().parse::<TrendGranularity>().unwrap(), granularity);
+        }
+    }
+
+    #[test]
+    fn data_as_of_is_attached_to_stats_output() {
+        let as_of = DataAsOf {
+            latest_check_time: Some("2026-01-02T00:00:00Z".parse().unwrap()),
+            centers: BTreeMap::new(),
+            latest_run: None,
+        };
+        let object = DataAsOf::attach(Some(&as_of), &serde_json::json!({ "centers": [] })).unwrap();
+        assert_eq!(object["centers"], serde_json::json!([]));
+        assert_eq!(object["data_as_of"]["latest_check_time"], "2026-01-02T00:00:00Z");
+
+        let array = DataAsOf::attach(Some(&as_of), &vec![1, 2]).unwrap();
+        assert_eq!(array["items"], serde_json::json!([1, 2]));
+        assert_eq!(array["data_as_of"]["latest_run"], serde_json::Value::Null);
+
+        // 读取时效失败时为 null
+        let missing = DataAsOf::attach(None, &serde_json::json!({ "total": 1 })).unwrap();
+        assert_eq!(missing, serde_json::json!({ "total": 1, "data_as_of": null }));
+    }
+
+    #[test]
+    fn audits_written_before_fetch_reports_still_load() {
+        let audit: FetchAudit = serde_json::from_value(serde_json::json!({
+            "center_name": "A", "started_at": "2026-01-01T00:00:00Z", "finished_at": "2026-01-01T00:10:00Z",
+            "success": true, "outcome": { "listed": 10, "discovered": 2, "processed": 2 }, "error": null,
+        })).unwrap();
+        let outcome = audit.outcome.unwrap();
+        assert_eq!((outcome.listed, outcome.discovered, outcome.processed, outcome.detail_failures), (10, 2, 2, 0));
+        assert!(outcome.name.is_empty() && outcome.backlog.is_none() && !outcome.cancelled);
+        assert!(outcome.is_success());
+    }
+
+    fn trend_points(trend: &CenterTrend) -> Vec<(&str, i64, Option<f64>)> {
+        trend.points.iter().map(|p| (p.date.as_str(), p.total, p.success_rate)).collect()
+    }
+
+    #[test]
+    fn center_trends_fill_days_without_checks() {
+        let first_day = NaiveDate::from_ymd_opt(2026, 1, 1).unwrap();
+        let rows = [
+            ("A", "2026-01-01", 4, 3),
+            ("A", "2026-01-03", 2, 2),
+            ("B", "2026-01-02", 5, 0),
+            // 窗口外的日期和无法解析的日期忽略
+            ("A", "2025-12-31", 9, 9),
+            ("A", "2026-01-04", 9, 9),
+            ("B", "not a date", 9, 9),
+        ].map(|(center, date, total, success)| (center.to_string(), date.to_string(), total, success)).to_vec();
+        let centers = ["A", "B", "D"].map(String::from);
+        let trends = center_trends(first_day, 3, 90, &centers, rows);
+
+        assert_eq!(trends.iter().map(|t| t.center.as_str()).collect::<Vec<_>>(), ["A", "B", "D"]);
+        assert_eq!(trend_points(&trends[0]), [("2026-01-01", 4, Some(75.0)), ("2026-01-02", 0, None), ("2026-01-03", 2, Some(100.0))]);
+        assert_eq!(trend_points(&trends[1]), [("2026-01-01", 0, None), ("2026-01-02", 5, Some(0.0)), ("2026-01-03", 0, None)]);
+        // 窗口内没有检查的数据中心也输出完整的序列
+        assert_eq!(trend_points(&trends[2]), [("2026-01-01", 0, None), ("2026-01-02", 0, None), ("2026-01-03", 0, None)]);
+    }
+
+    #[test]
+    fn center_trends_merge_days_above_the_point_limit() {
+        let first_day = NaiveDate::from_ymd_opt(2026, 1, 1).unwrap();
+        let day = |offset: i64| (first_day + chrono::Duration::days(offset)).format("%Y-%m-%d").to_string();
+        let rows = || vec![
+            ("A".to_string(), day(0), 4, 3),
+            ("A".to_string(), day(1), 6, 1),
+            ("A".to_string(), day(90), 2, 1),
+        ];
+        let centers = ["A".to_string()];
+
+        // 天数不超过点数上限时每天一个点
+        let daily = center_trends(first_day, 90, 90, &centers, rows());
+        assert_eq!(daily[0].points.len(), 90);
+        assert!(daily[0].points.iter().enumerate().all(|(i, p)| p.date == day(i as i64)));
+        assert_eq!(trend_points(&daily[0])[..2], [(day(0).as_str(), 4, Some(75.0)), (day(1).as_str(), 6, Some(100.0 / 6.0))]);
+
+        // 多一天就每两天合并为一个点，最后一个点只有一天，点的日期为合并的第一天
+        let merged = center_trends(first_day, 91, 90, &centers, rows());
+        let points = trend_points(&merged[0]);
+        assert_eq!(points.len(), 46);
+        assert_eq!(points[0], (day(0).as_str(), 10, Some(40.0)));
+        assert_eq!(points[1], (day(2).as_str(), 0, None));
+        assert_eq!(points[45], (day(90).as_str(), 2, Some(50.0)));
+
+        assert_eq!(center_trends(first_day, 180, 90, &centers, rows())[0].points.len(), 90);
+        assert_eq!(center_trends(first_day, 181, 90, &centers, rows())[0].points.len(), 61);
+        let single = center_trends(first_day, 91, 0, &centers, rows());
+        assert_eq!(trend_points(&single[0]), [(day(0).as_str(), 12, Some(5.0 * 100.0 / 12.0))]);
+    }
+}
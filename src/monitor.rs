@@ -1,95 +1,804 @@
-use crate::config::Config;
+use crate::build_info;
+use crate::check_method::HeadLearning;
+use crate::checker::{unsupported_scheme, CheckOutcome, CheckRequest, FileChecker, FtpChecker, HttpChecker, UrlChecker};
+use crate::circuit_breaker::{host_of, CircuitBreaker};
+use crate::concurrency::{AdaptiveLimiter, ConcurrencyTracker};
+use crate::config::{Center, CheckMethod, Config, ConfigSnapshot, DispatchOrder, SharedUrlAttribution, UrlAuth};
 use crate::db::duckdb::DuckDB;
 use crate::db::mongodb::MongoDB;
-use crate::models::{CheckError, Dataset, ErrorCategory, MonitorRecord, ResponseInfo};
+use crate::duplicates;
+use crate::http::{ClientFactory, ClientProfile};
+use crate::progress::{self, Progress};
+use crate::regression;
+use crate::shared_urls;
+use crate::sanitize::TextLimits;
+use crate::slow_hosts::{self, HostTimeouts};
+use crate::spill::StatusWriter;
+use crate::models::{AdaptiveConcurrencyStats, CenterCoverage, CheckAttempt, CheckError, Dataset, DatasetChecks, ErrorCategory, MonitorRecord, MonitorSummary, ResponseInfo, RunStorage, SkipReason, StorageSize, UnfinishedRun, UrlQualityIssue};
+use crate::timing;
+use crate::url_quality;
 use anyhow::Result;
 use chrono::Utc;
 use futures::{stream, StreamExt};
-use std::error::Error;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::Bson;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{BuildHasher, RandomState};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
-use tracing::{info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, trace, warn};
 
 pub struct DataMonitor {
     config: Arc<Config>,
-    client: reqwest::Client,
+    http: HttpChecker,
+    /// 配置了不同 max_redirects 的数据中心使用的HTTP检查器，按重定向次数上限区分
+    redirect_http: HashMap<usize, HttpChecker>,
+    /// HTTP(S) 以外协议的检查器
+    checkers: Vec<Box<dyn UrlChecker>>,
+    /// 由调用方创建并共享的 DuckDB 连接，避免每次运行重新打开同一文件
+    duckdb: Arc<DuckDB>,
+    /// 取消后不再发起新的URL检查
+    cancel: CancellationToken,
+    /// 已完成检查、尚未写库的记录数
+    buffered: AtomicUsize,
+    /// 各数据中心的URL认证凭证，按配置顺序匹配
+    credentials: Vec<CredentialRule>,
+}
+
+/// 续跑的运行：沿用原来的 run_id 和开始时间，跳过已完成的记录
+struct Resume {
+    run_id: String,
+    started_at: chrono::DateTime<Utc>,
+    completed: HashSet<String>,
+}
+
+struct CredentialRule {
+    center_name: String,
+    pattern: Option<regex::Regex>,
+    auth: UrlAuth,
 }
 
 impl DataMonitor {
-    pub fn new(config: Arc<Config>) -> Self {
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(config.monitor.http_timeout_secs))
-            .redirect(reqwest::redirect::Policy::limited(10))
-            .danger_accept_invalid_hostnames(true)
-            .danger_accept_invalid_certs(true)
-            .build()
-            .expect("failed to build http client");
-        Self { config, client }
-    }
-
-    pub async fn check_all_urls(&self) -> Result<()> {
+    pub fn new(config: Arc<Config>, duckdb: Arc<DuckDB>) -> Self {
+        let client = Self::build_client(&config);
+        Self::with_client(config, duckdb, client)
+    }
+
+    /// 默认的检查客户端：关闭自动重定向，记录 DNS 解析和建立连接的耗时
+    pub fn build_client(config: &Config) -> reqwest::Client {
+        ClientFactory::new(config).build(ClientProfile::UrlCheck)
+    }
+
+    /// 使用传入的客户端检查 http(s) URL，用于共用连接池或在测试中指向桩服务。
+    /// 客户端需要关闭自动重定向（见 [`HttpChecker`]），没有 [`DataMonitor::build_client`] 的计时层时不记录 DNS 和连接耗时
+    pub fn with_client(config: Arc<Config>, duckdb: Arc<DuckDB>, client: reqwest::Client) -> Self {
+        // 重定向由 HttpChecker 逐跳跟随，各重定向上限的检查器共用同一个客户端和连接池
+        let build_http = |max_redirects: usize| HttpChecker::new(client.clone(), max_redirects, config.monitor.max_header_bytes)
+            .with_redirect_policy(config.monitor.terminal_redirect(), config.monitor.follow_insecure_redirects);
+        let http = build_http(config.monitor.max_redirects);
+        let redirect_http = config.centers.iter()
+            .filter_map(|center| center.max_redirects)
+            .filter(|&max_redirects| max_redirects != config.monitor.max_redirects)
+            .map(|max_redirects| (max_redirects, build_http(max_redirects)))
+            .collect();
+        let mut checkers: Vec<Box<dyn UrlChecker>> = vec![Box::new(FtpChecker)];
+        if config.monitor.check_file_urls {
+            checkers.push(Box::new(FileChecker));
+        }
+        let mut credentials = Vec::new();
+        for center in &config.centers {
+            for credential in &center.url_credentials {
+                // 配置加载时已校验，这里失败（如环境变量在启动后被移除）只跳过该条
+                let pattern = credential.pattern.as_deref().map(regex::Regex::new).transpose();
+                match (pattern, credential.resolve()) {
+                    (Ok(pattern), Ok(auth)) => credentials.push(CredentialRule { center_name: center.name.clone(), pattern, auth }),
+                    (Err(e), _) => warn!("数据中心 {} 的 url_credentials.pattern 无效，已忽略: {}", center.name, e),
+                    (_, Err(e)) => warn!("数据中心 {} 的 url_credentials 无效，已忽略: {:#}", center.name, e),
+                }
+            }
+        }
+        Self { config, http, redirect_http, checkers, duckdb, cancel: CancellationToken::new(), buffered: AtomicUsize::new(0), credentials }
+    }
+
+    /// URL匹配的第一条认证凭证
+    fn credentials_for(&self, record: &MonitorRecord) -> Option<&UrlAuth> {
+        self.credentials.iter()
+            .find(|rule| rule.center_name == record.center_name
+                && rule.pattern.as_ref().is_none_or(|pattern| pattern.is_match(&record.url)))
+            .map(|rule| &rule.auth)
+    }
+
+    /// 按URL协议选择检查器，http(s) 使用数据中心对应重定向上限的检查器。
+    /// 无法解析的URL交给HTTP检查器，由请求错误说明原因
+    fn checker_for(&self, center: Option<&Center>, url: &str) -> Result<&dyn UrlChecker, Box<CheckError>> {
+        let scheme = match reqwest::Url::parse(url) {
+            Ok(parsed) => parsed.scheme().to_string(),
+            Err(_) => "http".to_string(),
+        };
+        if self.http.schemes().contains(&scheme.as_str()) {
+            let http = center.and_then(|c| c.max_redirects)
+                .and_then(|max_redirects| self.redirect_http.get(&max_redirects))
+                .unwrap_or(&self.http);
+            return Ok(http);
+        }
+        self.checkers.iter()
+            .find(|checker| checker.schemes().contains(&scheme.as_str()))
+            .map(|checker| checker.as_ref())
+            .ok_or_else(|| Box::new(unsupported_scheme(url, &scheme)))
+    }
+
+    /// 是否有检查器处理该协议
+    pub fn supports_scheme(&self, scheme: &str) -> bool {
+        self.http.schemes().contains(&scheme)
+            || self.checkers.iter().any(|checker| checker.schemes().contains(&scheme))
+    }
+
+    pub fn with_cancel_token(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// 当前已完成检查、尚未写库的记录数
+    pub fn buffered_records(&self) -> usize {
+        self.buffered.load(Ordering::Relaxed)
+    }
+
+    pub async fn check_all_urls(&self) -> Result<MonitorSummary> {
         info!("开始数据监测任务");
         let mongo = MongoDB::new(&self.config.mongodb).await?;
 
         let mut all_datasets = Vec::new();
-        let duckdb_ = DuckDB::new(&self.config.duckdb.path).await?;
         for center in &self.config.centers {
-            let datasets = mongo.get_datasets(&center.name).await?;
+            let datasets = mongo.get_datasets(&self.config.collection_name(&center.name)).await?;
             info!("数据中心 {} 有 {} 个数据集", center.name, datasets.len());
             all_datasets.extend(datasets);
         }
-        info!("总共需要监测 {} 个数据集", all_datasets.len());
+        let centers: Vec<&Center> = self.config.centers.iter().collect();
+        let resume = self.resumable_run(&centers).await?;
+        self.check_datasets(&mongo, all_datasets, centers, resume, None).await
+    }
 
-        let records: Vec<MonitorRecord> = all_datasets
-            .into_iter()
-            .filter_map(|ds| self.dataset_to_record(ds))
-            .collect();
+    /// 只监测单个数据中心，供按中心调度的任务使用
+    pub async fn check_center(&self, center: &Center) -> Result<MonitorSummary> {
+        info!("开始数据中心 {} 的监测任务", center.name);
+        let mongo = MongoDB::new(&self.config.mongodb).await?;
+        let datasets = mongo.get_datasets(&self.config.collection_name(&center.name)).await?;
+        info!("数据中心 {} 有 {} 个数据集", center.name, datasets.len());
+        let resume = self.resumable_run(&[center]).await?;
+        self.check_datasets(&mongo, datasets, vec![center], resume, None).await
+    }
 
-        info!("有效URL数量: {}", records.len());
-        duckdb_.insert_records(&records).await?;
+    /// 只检查所有数据中心中带 `tag` 标签的数据集，用于比完整运行更频繁地检查重点数据集；不续跑未结束的运行
+    pub async fn check_tagged(&self, tag: &str) -> Result<MonitorSummary> {
+        info!("开始标签 {} 的数据集监测任务", tag);
+        let mongo = MongoDB::new(&self.config.mongodb).await?;
+        let mut datasets = Vec::new();
+        for center in self.config.centers.iter().filter(|c| c.enabled) {
+            datasets.extend(mongo.get_tagged_datasets(&self.config.collection_name(&center.name), tag).await?);
+        }
+        info!("带标签 {} 的数据集有 {} 个", tag, datasets.len());
+        let centers: Vec<&Center> = self.config.centers.iter().filter(|c| c.enabled).collect();
+        self.check_datasets(&mongo, datasets, centers, None, Some(tag)).await
+    }
 
-        // 并发监测URL
-        let results = stream::iter(records)
-            .map(|record| self.process_record(record))
+    /// 检查不在 MongoDB 中的URL（如数据中心另行提供的列表），记录的数据中心为 `center_name`，
+    /// 与配置中的数据中心同名时使用其检查方式和凭证。`save` 时结果写入 DuckDB 并记为一次运行，
+    /// 否则不写库（不更新方法缓存和慢主机）。返回运行汇总和每个URL的结果
+    pub async fn check_urls(&self, center_name: &str, urls: Vec<String>, save: bool) -> Result<(MonitorSummary, Vec<MonitorRecord>)> {
+        let run_id = ObjectId::new().to_hex();
+        let started_at = Utc::now();
+        let records: Vec<MonitorRecord> = urls.into_iter()
+            .filter_map(|url| self.dataset_to_record(Dataset {
+                _id: Some(ObjectId::new()),
+                raw_id: url.clone(),
+                casdc_id: None,
+                data_type: None,
+                url: Some(Bson::String(url)),
+                name: None,
+                date_published: None,
+                sync_date: None,
+                center_name: Some(center_name.to_string()),
+                identifier: None,
+                tags: Vec::new(),
+            }))
+            .map(|record| MonitorRecord { name: None, ..record })
+            .collect();
+        info!("检查文件中的 {} 个URL，数据中心 {}{}", records.len(), center_name, if save { "，结果写入 DuckDB" } else { "" });
+        let storage_start = self.duckdb.storage_size();
+        if save {
+            self.duckdb.insert_records(&records).await?;
+        }
+        let head_learning = HeadLearning::new(&[], 0);
+        let head_learning = &head_learning;
+        let timeouts = HostTimeouts::new(&self.config.monitor, &self.duckdb.get_learned_slow_hosts().await?);
+        let timeouts = &timeouts;
+        let breaker = CircuitBreaker::new(self.config.monitor.circuit_breaker_failures);
+        let breaker = &breaker;
+        let progress = Arc::new(Progress::new(records.len()));
+        let ticker = (self.config.monitor.progress_interval_secs > 0)
+            .then(|| progress::spawn(progress.clone(), Duration::from_secs(self.config.monitor.progress_interval_secs)));
+        let progress = &progress;
+        let results: Vec<MonitorRecord> = stream::iter(records)
+            .map(|record| async move {
+                if self.cancel.is_cancelled() {
+                    return None;
+                }
+                let record = self.process_record(record, Some(breaker), head_learning, timeouts).await;
+                progress.record(self.config.monitor.success_statuses.is_success(record.status_code));
+                Some(record)
+            })
             .buffer_unordered(self.config.monitor.max_concurrent)
-            .collect::<Vec<_>>()
+            .filter_map(std::future::ready)
+            .collect()
             .await;
+        drop(ticker);
+
+        let mut summary = MonitorSummary::from_records(&run_id, started_at, &results, &HashMap::new(), &self.config.monitor.success_statuses);
+        summary.cancelled = self.cancel.is_cancelled();
+        if save {
+            self.duckdb.update_status(&results).await?;
+            let centers: Vec<&Center> = self.config.centers.iter().filter(|c| c.name == center_name).collect();
+            let (config_json, config_hash) = ConfigSnapshot::new(&self.config, centers).to_json()?;
+            summary.config_hash = Some(config_hash);
+            summary.storage = Some(self.finish_storage(storage_start).await);
+            self.duckdb.insert_run(&summary, &config_json).await?;
+            info!("检查结果已写入 DuckDB，运行 {}", run_id);
+        }
+        Ok((summary, results))
+    }
+
+    /// 运行结束时的数据库大小，超过 duckdb.storage_soft_limit_mb 时删除早于 prune_retention_days 的检查记录
+    async fn finish_storage(&self, start: StorageSize) -> RunStorage {
+        let duckdb = &self.config.duckdb;
+        let mut storage = RunStorage {
+            start,
+            end: self.duckdb.storage_size(),
+            soft_limit_bytes: duckdb.storage_soft_limit_bytes(),
+            pruned_records: None,
+        };
+        info!("数据库大小: {} -> {} 字节（含 WAL），增长 {} 字节",
+              storage.start.total_bytes(), storage.end.total_bytes(), storage.growth_bytes());
+        if storage.over_soft_limit() {
+            let before = Utc::now() - chrono::Duration::days(duckdb.prune_retention_days as i64);
+            warn!("数据库大小 {} 字节超过软限制 {} MB，删除 {} 之前的检查记录",
+                  storage.end.total_bytes(), duckdb.storage_soft_limit_mb.unwrap_or_default(), before.format("%Y-%m-%d"));
+            match self.duckdb.prune_history(before).await {
+                Ok(deleted) => {
+                    info!("已删除 {} 条检查记录", deleted);
+                    storage.pruned_records = Some(deleted);
+                }
+                Err(e) => warn!("删除过期检查记录失败: {:#}", e),
+            }
+        }
+        storage
+    }
+
+    /// 续跑指定的未结束运行，监测的数据中心与原运行相同
+    pub async fn resume_run(&self, run_id: &str) -> Result<MonitorSummary> {
+        let run = self.duckdb.get_unfinished_runs().await?
+            .into_iter()
+            .find(|r| r.run_id == run_id)
+            .ok_or_else(|| anyhow::anyhow!("运行 {} 不存在或已结束", run_id))?;
+        let mongo = MongoDB::new(&self.config.mongodb).await?;
+        let mut centers = Vec::new();
+        let mut datasets = Vec::new();
+        for name in &run.centers {
+            let center = self.config.centers.iter()
+                .find(|c| &c.name == name)
+                .ok_or_else(|| anyhow::anyhow!("运行 {} 的数据中心 {} 已不在配置中", run_id, name))?;
+            datasets.extend(mongo.get_datasets(&self.config.collection_name(&center.name)).await?);
+            centers.push(center);
+        }
+        let completed = self.duckdb.get_run_progress(run_id).await?;
+        info!("续跑运行 {}，已完成 {} 条记录", run_id, completed.len());
+        let resume = Resume { run_id: run.run_id, started_at: run.started_at, completed };
+        self.check_datasets(&mongo, datasets, centers, Some(resume), None).await
+    }
+
+    /// 把开始超过 resume_max_age_hours 的未结束运行标记为 aborted，返回其余可以续跑的运行
+    pub async fn close_stale_runs(&self) -> Result<Vec<UnfinishedRun>> {
+        let max_age = self.config.monitor.resume_max_age_hours;
+        if max_age == 0 {
+            return Ok(Vec::new());
+        }
+        let cutoff = Utc::now() - chrono::Duration::hours(max_age as i64);
+        let mut resumable = Vec::new();
+        for run in self.duckdb.get_unfinished_runs().await? {
+            if run.started_at < cutoff {
+                warn!("运行 {}（{}）开始于 {}，超过 {} 小时未完成，标记为 aborted",
+                      run.run_id, run.centers.join(", "), run.started_at, max_age);
+                self.duckdb.finish_run_state(&run.run_id, "aborted").await?;
+            } else {
+                resumable.push(run);
+            }
+        }
+        Ok(resumable)
+    }
+
+    /// 监测相同数据中心的未结束运行，有则续跑
+    async fn resumable_run(&self, centers: &[&Center]) -> Result<Option<Resume>> {
+        let names: Vec<&str> = centers.iter().map(|c| c.name.as_str()).collect();
+        let Some(run) = self.close_stale_runs().await?.into_iter().find(|r| r.centers == names) else {
+            return Ok(None);
+        };
+        let completed = self.duckdb.get_run_progress(&run.run_id).await?;
+        info!("发现未完成的运行 {}（开始于 {}，已完成 {} 条记录），继续该运行", run.run_id, run.started_at, completed.len());
+        Ok(Some(Resume { run_id: run.run_id, started_at: run.started_at, completed }))
+    }
+
+    /// `centers` 为本次运行监测的数据中心，写入运行配置快照；`tag` 为只检查带该标签的数据集的运行
+    async fn check_datasets(&self, mongo: &MongoDB, all_datasets: Vec<Dataset>, centers: Vec<&Center>, resume: Option<Resume>, tag: Option<&str>) -> Result<MonitorSummary> {
+        let (run_id, started_at, completed) = match resume {
+            Some(resume) => (resume.run_id, resume.started_at, resume.completed),
+            None => (ObjectId::new().to_hex(), Utc::now(), HashSet::new()),
+        };
+        let storage_start = self.duckdb.storage_size();
+        info!("总共需要监测 {} 个数据集", all_datasets.len());
+        let environment = build_info::run_environment(&self.config.monitor).await;
+        info!("运行 {} 出口 IP: {}，主机: {}", run_id,
+              environment.egress_ip.as_deref().unwrap_or("-"), environment.hostname.as_deref().unwrap_or("-"));
+
+        let mut coverage: Vec<CenterCoverage> = centers.iter().map(|c| CenterCoverage::new(&c.name)).collect();
+        let mut records = Vec::new();
+        let mut url_issues = Vec::new();
+        let detected_at = Utc::now();
+        for dataset in all_datasets {
+            let center_name = dataset.center_name.as_deref().unwrap_or("Unknown");
+            let index = match coverage.iter().position(|c| c.center_name == center_name) {
+                Some(index) => index,
+                None => {
+                    coverage.push(CenterCoverage::new(center_name));
+                    coverage.len() - 1
+                }
+            };
+            let mut skip = dataset.url_skip_reason();
+            if let Some(record) = self.dataset_to_record(dataset) {
+                match url_quality::classify(&record.url, |scheme| self.supports_scheme(scheme)) {
+                    Some(reason) => {
+                        skip = Some(SkipReason::UnusableUrl);
+                        url_issues.push(UrlQualityIssue {
+                            center_name: record.center_name,
+                            id: record.id,
+                            raw_id: record.raw_id,
+                            name: record.name,
+                            url: record.url,
+                            reason,
+                            detected_at,
+                        });
+                    }
+                    None => records.push(record),
+                }
+            }
+            coverage[index].add(skip);
+        }
+
+        info!("有效URL数量: {}", records.len());
+        // 只检查部分数据集的标签运行不替换，避免清掉其他数据集的问题
+        if tag.is_none() {
+            let center_names: Vec<String> = coverage.iter().map(|c| c.center_name.clone()).collect();
+            self.duckdb.replace_url_quality_issues(&center_names, &url_issues).await?;
+            if !url_issues.is_empty() {
+                info!("{} 个数据集的URL无法检查（格式错误、不支持的协议、内网地址或占位内容），已记录到 url_quality_issues", url_issues.len());
+            }
+        }
+        if !completed.is_empty() {
+            records.retain(|r| !completed.contains(&r.id));
+            info!("续跑运行 {}，跳过已完成的 {} 条，剩余 {} 条", run_id, completed.len(), records.len());
+        }
+        let center_names: Vec<String> = centers.iter().map(|c| c.name.clone()).collect();
+        self.duckdb.start_run_state(&run_id, started_at, &center_names).await?;
+        for center in coverage.iter().filter(|c| c.with_url < c.datasets) {
+            info!("数据中心 {} 有 {}/{} 个数据集没有可检查的URL: {:?}",
+                  center.center_name, center.datasets - center.with_url, center.datasets, center.skipped);
+        }
+        // 写入本次记录前取上一次的状态，用于找出新失败的URL
+        let previous_status = self.duckdb.get_last_status_codes().await?;
+        let previous_hashes = if centers.iter().any(|c| self.config.fingerprint_enabled(&c.name)) {
+            self.duckdb.get_last_content_hashes().await?
+        } else {
+            HashMap::new()
+        };
+        let previous_hashes = &previous_hashes;
+        let learn_head = centers.iter().any(|c| c.check_method == CheckMethod::Auto);
+        let head_learning = if learn_head {
+            HeadLearning::new(&self.duckdb.get_method_cache().await?, self.config.monitor.head_retest_runs)
+        } else {
+            HeadLearning::new(&[], 0)
+        };
+        let head_learning = &head_learning;
+        let timeouts = HostTimeouts::new(&self.config.monitor, &self.duckdb.get_learned_slow_hosts().await?);
+        let timeouts = &timeouts;
+        for record in &mut records {
+            record.run_id = Some(run_id.clone());
+        }
+        let shared = &self.config.monitor.shared_urls;
+        if shared.attribution != SharedUrlAttribution::All || !shared.primary.is_empty() {
+            let urls: Vec<String> = records.iter().map(|r| r.url.clone()).collect::<HashSet<_>>().into_iter().collect();
+            let history = self.duckdb.get_url_centers(&urls).await?;
+            let center_order: Vec<String> = self.config.centers.iter().map(|c| c.name.clone()).collect();
+            shared_urls::attribute(&mut records, &history, &center_order, shared);
+        }
+        self.duckdb.insert_records(&records).await?;
+
+        let (records, followers, attributed) = if self.config.monitor.check_duplicates_once {
+            self.split_duplicates(mongo, records).await?
+        } else {
+            (records, Vec::new(), Vec::new())
+        };
+
+        // 重复组内其他成员沿用代表成员本次的结果，按代表成员分组，代表成员的结果写库时一并写入
+        let mut followers_by_primary: HashMap<(String, String), Vec<MonitorRecord>> = HashMap::new();
+        for (record, primary) in followers {
+            followers_by_primary.entry(primary).or_default().push(record);
+        }
+        let follower_count = attributed.len();
+        let writer = StatusWriter::new(&self.duckdb, &self.config.monitor.spill_dir, &run_id);
+        // 沿用近期结果的记录不需要检查，先写库
+        writer.write(&attributed).await?;
+        let mut results = strip_written(attributed);
+
+        let dispatch_order = self.config.monitor.dispatch_order;
+        let dispatch_seed = (dispatch_order == DispatchOrder::Shuffled)
+            .then(|| RandomState::new().hash_one(&run_id));
+        let records = match (dispatch_order, dispatch_seed) {
+            (DispatchOrder::Interleaved, _) => interleave_by_center(records),
+            (DispatchOrder::Shuffled, Some(seed)) => shuffle(records, seed),
+            _ => records,
+        };
+
+        // 并发监测URL，取消后跳过尚未开始的URL，进行中的请求照常完成。
+        // 每完成 result_batch_size 个写一次库，写库期间不再取新的结果，内存中等待写库的记录
+        // 最多 result_batch_size + max_concurrent 个
+        let total_records = records.len();
+        let batch_size = self.config.monitor.result_batch_size.max(1);
+        let breaker = CircuitBreaker::new(self.config.monitor.circuit_breaker_failures);
+        let breaker = &breaker;
+        let circuit_open = ErrorCategory::HostCircuitOpen.to_string();
+        let circuit_open = &circuit_open;
+        let tracker = ConcurrencyTracker::new(centers.iter().map(|c| c.name.as_str()));
+        let tracker = &tracker;
+        // 自适应并发时 buffer_unordered 按上限 max 取记录，实际并发数由 limiter 控制
+        let limiter = self.config.monitor.adaptive_concurrency.as_ref()
+            .map(|adaptive| AdaptiveLimiter::new(adaptive, self.config.monitor.max_concurrent));
+        let limiter = limiter.as_ref();
+        let initial_limit = limiter.map(AdaptiveLimiter::limit);
+        let max_concurrent = self.config.monitor.adaptive_concurrency.as_ref()
+            .map_or(self.config.monitor.max_concurrent, |adaptive| adaptive.max);
+        let progress = Arc::new(Progress::new(total_records));
+        let ticker = (self.config.monitor.progress_interval_secs > 0)
+            .then(|| progress::spawn(progress.clone(), Duration::from_secs(self.config.monitor.progress_interval_secs)));
+        let progress = &progress;
+        let mut batches = stream::iter(records)
+            .map(|record| async move {
+                let permit = match limiter {
+                    Some(limiter) => Some(limiter.acquire().await),
+                    None => None,
+                };
+                if self.cancel.is_cancelled() {
+                    return None;
+                }
+                let guard = tracker.start(&record.center_name);
+                let mut record = self.process_record(record, Some(breaker), head_learning, timeouts).await;
+                drop(guard);
+                if let Some(limiter) = limiter
+                    && record.error_category.as_deref() != Some(circuit_open.as_str())
+                {
+                    limiter.record(is_congestion(&record));
+                }
+                drop(permit);
+                progress.record(self.config.monitor.success_statuses.is_success(record.status_code));
+                if let (Some(hash), Some(previous)) = (&record.content_hash, previous_hashes.get(&record.id)) {
+                    record.content_changed = Some(hash != previous);
+                }
+                self.buffered.fetch_add(1, Ordering::Relaxed);
+                Some(record)
+            })
+            .buffer_unordered(max_concurrent)
+            .filter_map(std::future::ready)
+            .chunks(batch_size);
+        let mut peak_buffered = 0;
+        let mut attributed_count = follower_count;
+        while let Some(mut batch) = batches.next().await {
+            peak_buffered = peak_buffered.max(self.buffered.load(Ordering::Relaxed));
+            let mut attributed = Vec::new();
+            for primary in &batch {
+                let Some(raw_id) = &primary.raw_id else {
+                    continue;
+                };
+                if let Some(followers) = followers_by_primary.remove(&(primary.center_name.clone(), raw_id.clone())) {
+                    attributed.extend(followers.into_iter().map(|record| attribute_result(record, primary)));
+                }
+            }
+            attributed_count += attributed.len();
+            let checked = batch.len();
+            batch.extend(attributed);
+            // 未检查的记录没有状态码和错误类别，统计时会被忽略
+            writer.write(&batch).await?;
+            self.buffered.fetch_sub(checked, Ordering::Relaxed);
+            results.extend(strip_written(batch));
+        }
+        // 出错提前返回时 ticker 随 drop 停止
+        drop(ticker);
+        if attributed_count > 0 {
+            info!("{} 个重复的数据集沿用同组数据集的检查结果", attributed_count);
+        }
+        info!("检查结果写库前在内存中最多缓冲 {} 条", peak_buffered);
+        if self.config.monitor.circuit_breaker_reprobe {
+            self.reprobe_open_hosts(breaker, head_learning, timeouts, &writer, &mut results).await?;
+        }
+
+        if learn_head {
+            let outcome = head_learning.outcome();
+            if !outcome.rejected.is_empty() {
+                info!("以下主机拒绝 HEAD 而 GET 成功，之后的运行直接用 GET: {:?}", outcome.rejected);
+            }
+            self.duckdb.update_method_cache(&outcome.rejected, &outcome.accepted, &outcome.skipped).await?;
+        }
+        if let Err(e) = slow_hosts::learn(&self.duckdb, &self.config.monitor).await {
+            warn!("识别慢主机失败: {:#}", e);
+        }
+
+        // 续跑时中断前已完成的记录从库中读回，与本次的结果一起汇总；
+        // run_progress 此时也包括本次写库的记录，只取中断前完成的
+        let resumed_records = if completed.is_empty() {
+            0
+        } else {
+            let mut previous = self.duckdb.get_run_progress_records(&run_id, started_at).await?;
+            previous.retain(|record| completed.contains(&record.id));
+            let count = previous.len();
+            results.extend(strip_written(previous));
+            count
+        };
 
-        duckdb_.update_status(&results).await?;
-        let success_count = results.iter()
-            .filter(|r| r.status_code == Some(200))
-            .count();
-        let local_issue_count = results.iter()
-            .filter(|r| r.is_likely_local_issue)
-            .count();
-        let remote_issue_count = results.iter()
-            .filter(|r| r.error_category.is_some() && !r.is_likely_local_issue)
-            .count();
+        let mut summary = MonitorSummary::from_records(&run_id, started_at, &results, &previous_status, &self.config.monitor.success_statuses);
+        tracker.apply(&mut summary);
+        summary.coverage = coverage;
+        summary.dispatch_order = dispatch_order;
+        summary.dispatch_seed = dispatch_seed;
+        summary.resumed_records = resumed_records;
+        summary.environment = Some(environment);
+        summary.tag = tag.map(String::from);
+        // 新失败的URL注明之前最后一次检查成功的时间
+        let urls: Vec<String> = summary.new_failures.iter().map(|f| f.url.clone()).collect();
+        match self.duckdb.get_url_status(&urls).await {
+            Ok(statuses) => for failure in &mut summary.new_failures {
+                failure.last_success_at = statuses.get(&failure.url).and_then(|status| status.last_success_at);
+            },
+            Err(e) => warn!("读取新失败URL的最后成功时间失败: {}", e),
+        }
+        if writer.spilled() > 0 {
+            summary.spilled = writer.spilled();
+            summary.spill_file = Some(writer.path().display().to_string());
+            warn!("{} 条检查结果写库失败，已写入 {}，可用 data_monitor replay-spill 重新写入",
+                  summary.spilled, writer.path().display());
+        }
+        if let (Some(limiter), Some(initial)) = (limiter, initial_limit) {
+            summary.adaptive_concurrency = Some(AdaptiveConcurrencyStats {
+                initial,
+                final_limit: limiter.limit(),
+                adjustments: limiter.adjustments(),
+            });
+        }
+        for center in &summary.centers {
+            if let Some(c) = &center.concurrency {
+                info!(
+                    "数据中心 {} 并发: 峰值 {}, 检查 {} 个用时 {:.1}s, 平均排队 {:.0}ms, 吞吐 {:.2}/s",
+                    center.center_name, c.peak_concurrent, c.checks, c.wall_time_ms as f64 / 1000.0,
+                    c.avg_queue_wait_ms, c.throughput_per_sec
+                );
+            }
+        }
+        if self.cancel.is_cancelled() {
+            summary.cancelled = true;
+            warn!("监测任务已取消，完成 {}/{} 个URL", results.len(), total_records);
+        }
         info!(
             "监测完成: 成功 {}/{}, 本地网络问题 {}, 远程问题 {}",
-            success_count,
-            results.len(),
-            local_issue_count,
-            remote_issue_count
+            summary.success,
+            summary.total,
+            summary.local_issues,
+            summary.remote_issues
         );
         // 如果本地网络问题过多，发出警告
-        if local_issue_count > results.len() / 10 {
+        if summary.local_issues > summary.total / 10 {
             warn!("检测到大量本地网络问题 ({}/{}), 请检查网络连接",
-                  local_issue_count, results.len());
+                  summary.local_issues, summary.total);
+        }
+        if let Some(detection) = &self.config.monitor.response_time_regression
+            && summary.tag.is_none()
+            && !summary.cancelled
+        {
+            match self.duckdb.get_recent_runs(regression::lookback_runs(detection, self.config.centers.len())).await {
+                Ok(recent_runs) => summary.response_time_regressions = regression::detect(&summary, &recent_runs, detection),
+                Err(e) => warn!("读取之前的运行汇总失败，跳过响应时间变慢检测: {}", e),
+            }
+            for r in &summary.response_time_regressions {
+                warn!("数据中心 {} 响应时间变慢: 中位数 {}ms，基线 {}ms（{:.1} 倍）", r.center_name, r.median_ms, r.baseline_ms, r.ratio);
+            }
+        }
+        let (config_json, config_hash) = ConfigSnapshot::new(&self.config, centers).to_json()?;
+        summary.config_hash = Some(config_hash);
+        summary.storage = Some(self.finish_storage(storage_start).await);
+        self.duckdb.insert_run(&summary, &config_json).await?;
+        // 取消的运行保持未结束，下次运行时续跑
+        if !summary.cancelled {
+            self.duckdb.finish_run_state(&run_id, "finished").await?;
+        }
+        Ok(summary)
+    }
+    /// 按重复组拆分待检查的记录，返回 (需要检查的, 代表成员在本次运行中的, 已沿用代表成员近期结果的)
+    ///
+    /// 代表成员不在本次运行（属于其他数据中心）时，沿用它在一个检查周期内的最近结果，没有则照常检查。
+    async fn split_duplicates(&self, mongo: &MongoDB, records: Vec<MonitorRecord>)
+        -> Result<(Vec<MonitorRecord>, Vec<(MonitorRecord, (String, String))>, Vec<MonitorRecord>)> {
+        let primaries = duplicates::primary_members(&mongo.get_duplicate_groups().await?);
+        if primaries.is_empty() {
+            return Ok((records, Vec::new(), Vec::new()));
+        }
+        let in_run: HashSet<(String, String)> = records.iter()
+            .filter_map(|r| Some((r.center_name.clone(), r.raw_id.clone()?)))
+            .collect();
+        let since = Utc::now() - chrono::Duration::days(self.config.monitor.check_interval_days as i64);
+
+        let (mut to_check, mut followers, mut attributed) = (Vec::new(), Vec::new(), Vec::new());
+        for record in records {
+            let primary = record.raw_id.as_ref()
+                .and_then(|raw_id| primaries.get(&(record.center_name.clone(), raw_id.clone())));
+            match primary {
+                Some(primary) if in_run.contains(primary) => followers.push((record, primary.clone())),
+                Some((center_name, raw_id)) => match self.duckdb.get_latest_record(center_name, raw_id, since).await? {
+                    Some(latest) => attributed.push(attribute_result(record, &latest)),
+                    None => to_check.push(record),
+                },
+                None => to_check.push(record),
+            }
+        }
+        Ok((to_check, followers, attributed))
+    }
+
+    /// 运行结束前对每个熔断的主机用一个被跳过的URL重新探测，收到响应则关闭熔断并重新检查该主机其余被跳过的URL
+    async fn reprobe_open_hosts(&self, breaker: &CircuitBreaker, head_learning: &HeadLearning, timeouts: &HostTimeouts, writer: &StatusWriter<'_>, results: &mut [MonitorRecord]) -> Result<()> {
+        let circuit_open = ErrorCategory::HostCircuitOpen.to_string();
+        let mut skipped: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, record) in results.iter().enumerate() {
+            if record.error_category.as_deref() == Some(circuit_open.as_str())
+                && let Some(host) = host_of(&record.url)
+            {
+                skipped.entry(host).or_default().push(index);
+            }
+        }
+        for (host, indices) in skipped {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            let probe = self.process_record(results[indices[0]].clone(), None, head_learning, timeouts).await;
+            let mut rechecked = Vec::new();
+            if probe.status_code.is_some() {
+                breaker.close(&host);
+                info!("主机 {} 重新探测收到响应，关闭熔断，重新检查其余 {} 个URL", host, indices.len() - 1);
+                let records: Vec<MonitorRecord> = indices[1..].iter().map(|&i| results[i].clone()).collect();
+                rechecked = stream::iter(records)
+                    .map(|record| self.process_record(record, None, head_learning, timeouts))
+                    .buffer_unordered(self.config.monitor.max_concurrent)
+                    .collect()
+                    .await;
+            } else {
+                info!("主机 {} 重新探测仍然没有响应，保持熔断", host);
+            }
+            rechecked.push(probe);
+            writer.write(&rechecked).await?;
+            let positions: HashMap<String, usize> = indices.iter().map(|&i| (results[i].id.clone(), i)).collect();
+            for record in strip_written(rechecked) {
+                if let Some(&index) = positions.get(&record.id) {
+                    results[index] = record;
+                }
+            }
         }
         Ok(())
     }
-    async fn process_record(&self, mut record: MonitorRecord) -> MonitorRecord {
-        let start_time = std::time::Instant::now();
-        info!("开始检查URL: {}", &record.url);
-        let check_result = self.check_url(&self.client, &record.url).await;
 
-        record.response_time_ms = Some(start_time.elapsed().as_millis() as u64);
+    /// `breaker` 为 None 时不检查也不更新熔断状态（重新探测时使用）
+    async fn process_record(&self, mut record: MonitorRecord, breaker: Option<&CircuitBreaker>, head_learning: &HeadLearning, timeouts: &HostTimeouts) -> MonitorRecord {
+        // 重新探测时记录中还保留着上一次检查的尝试
+        record.attempts_detail.clear();
+        let host = breaker.and_then(|_| host_of(&record.url));
+        if let (Some(breaker), Some(host)) = (breaker, &host)
+            && breaker.is_open(host)
+        {
+            record.check_time = Utc::now();
+            self.handle_check_result(&mut record, Err(CheckError {
+                category: ErrorCategory::HostCircuitOpen,
+                message: format!("主机 {} 已熔断，未请求", host),
+                detail: format!("本次运行中主机 {} 连续 {} 次没有响应", host, self.config.monitor.circuit_breaker_failures),
+                status_code: None,
+                http_version: None,
+                failed_hop: None,
+            }));
+            return record;
+        }
+        let run_start = std::time::Instant::now();
+        trace!("开始检查URL: {}", &record.url);
+        let fingerprint_bytes = self.config.fingerprint_enabled(&record.center_name)
+            .then_some(self.config.monitor.fingerprint_max_kb * 1024);
+        let auth = self.credentials_for(&record);
+        record.auth_used = auth.is_some();
+        let center = self.config.centers.iter().find(|c| c.name == record.center_name);
+        let requested_url = match center {
+            Some(center) if !center.strip_query_params.is_empty() => strip_query_params(&record.url, &center.strip_query_params),
+            _ => record.url.clone(),
+        };
+        // 不支持的协议不请求，也不计入主机熔断
+        let checker = match self.checker_for(center, &requested_url) {
+            Ok(checker) => checker,
+            Err(e) => {
+                record.check_time = Utc::now();
+                record.requested_url = Some(requested_url);
+                self.handle_check_result(&mut record, Err(*e));
+                return record;
+            }
+        };
+        // 计算指纹需要响应体，始终用 GET
+        let check_method = center.map_or(CheckMethod::Get, |c| c.check_method);
+        let learn_host = (check_method == CheckMethod::Auto).then(|| host_of(&requested_url)).flatten();
+        let head_first = fingerprint_bytes.is_none() && match check_method {
+            CheckMethod::Get => false,
+            CheckMethod::Head => true,
+            CheckMethod::Auto => !learn_host.as_deref().is_some_and(|host| head_learning.use_get(host)),
+        };
+        let timeout = timeouts.timeout_for(&requested_url);
+        record.timeout_secs = Some(timeout.as_secs());
+        // 暂时性的失败按 check_retries 重试，response_time_ms 等只取最后一次尝试，
+        // 每次尝试的结果记录在 attempts_detail，total_time_ms 包含所有尝试和重试前的等待
+        let mut attempt = 1;
+        let (check_result, phases, elapsed) = loop {
+            let start_time = std::time::Instant::now();
+            let request = CheckRequest { url: &requested_url, auth, fingerprint_bytes, head_first, timeout };
+            let (CheckOutcome { result: check_result, head_accepted }, phases) = timing::measure(checker.check(request)).await;
+            if let (Some(host), Some(head_ok)) = (&learn_host, head_accepted) {
+                head_learning.record(host, head_ok);
+            }
+            let elapsed = start_time.elapsed();
+            record.attempts_detail.push(CheckAttempt {
+                attempt,
+                duration_ms: elapsed.as_millis() as u64,
+                status_code: match &check_result {
+                    Ok(info) => Some(info.status_code),
+                    Err(e) => e.status_code,
+                },
+                error_category: check_result.as_ref().err().map(|e| e.category.to_string()),
+            });
+            let retryable = check_result.as_ref().is_err_and(|e| is_retryable(&e.category));
+            if !retryable || attempt > self.config.monitor.check_retries || self.cancel.is_cancelled() {
+                break (check_result, phases, elapsed);
+            }
+            let delay = Duration::from_secs(1 << attempt.min(6));
+            info!("检查URL {} 失败（第 {} 次），{:?} 后重试", record.url, attempt, delay);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        };
+        record.requested_url = Some(requested_url);
+        record.total_time_ms = Some(run_start.elapsed().as_millis() as u64);
+        record.response_time_ms = Some(elapsed.as_millis() as u64);
+        record.dns_ms = phases.dns_ms();
+        record.connect_ms = phases.connect_ms();
+        // 不计算指纹时不读取响应体，收到响应头即结束；没有收到响应（连接失败、超时）时没有首字节时间
+        let (responded, body_read) = match &check_result {
+            Ok(info) => (true, info.body_read.unwrap_or_default()),
+            Err(e) => (e.status_code.is_some(), Duration::ZERO),
+        };
+        record.ttfb_ms = responded.then_some(elapsed.saturating_sub(body_read).as_millis() as u64);
+        // 连接器没有被调用说明请求复用了连接池中的连接
+        record.connection_reused = (responded && checker.reuses_connections()).then_some(phases.connect.is_none());
+        if let (Some(breaker), Some(host)) = (breaker, &host) {
+            breaker.record(host, responded);
+        }
         record.check_time = Utc::now();
 
         self.handle_check_result(&mut record, check_result);
-        info!("完成检查URL: {}, 状态码: {:?}", record.url, record.status_code);
+        trace!("完成检查URL: {}, 状态码: {:?}", record.url, record.status_code);
         record
     }
     fn handle_check_result(&self, record: &mut MonitorRecord, check_result: Result<ResponseInfo, CheckError>) {
@@ -98,17 +807,27 @@ impl DataMonitor {
                 record.status_code = Some(response_info.status_code);
                 record.status_text = Some(response_info.status_text);
                 record.headers = response_info.headers;
+                record.http_version = Some(response_info.http_version);
+                record.content_hash = response_info.content_hash;
                 record.error_category = None;
                 record.error_msg = None;
                 record.error_detail = None;
                 record.is_likely_local_issue = false;
+                record.failed_hop_index = None;
+                record.failed_hop_url = None;
+                record.redirect_location = response_info.redirect_location;
             }
             Err(e) => {
+                let limits = TextLimits::from_config(&self.config.monitor);
                 record.status_code = e.status_code;
+                record.http_version = e.http_version;
                 record.error_category = Some(e.category.to_string());
-                record.error_msg = Some(e.message);
-                record.error_detail = Some(e.detail);
+                record.error_msg = Some(limits.error_msg(e.message));
+                record.error_detail = Some(limits.error_detail(e.detail));
                 record.is_likely_local_issue = e.category.is_likely_local_issue();
+                record.failed_hop_index = e.failed_hop.as_ref().map(|hop| hop.index as u32);
+                record.redirect_location = e.failed_hop.as_ref().and_then(|hop| hop.location.clone());
+                record.failed_hop_url = e.failed_hop.map(|hop| hop.url);
             }
         }
     }
@@ -128,77 +847,1055 @@ impl DataMonitor {
             error_msg: None,
             error_detail: None,
             response_time_ms: None,
+            dns_ms: None,
+            connect_ms: None,
+            ttfb_ms: None,
+            http_version: None,
+            connection_reused: None,
+            content_hash: None,
+            content_changed: None,
+            auth_used: false,
+            requested_url: None,
+            attempts_detail: Vec::new(),
+            total_time_ms: None,
+            failed_hop_index: None,
+            failed_hop_url: None,
+            redirect_location: None,
+            timeout_secs: None,
+            tags: dataset.tags.clone(),
+            run_id: None,
+            attributed_center: None,
+            attribution_rule: None,
             is_likely_local_issue: false,
             headers: None,
             created_at: None,
             updated_at: None,
         })
     }
+}
 
-    async fn check_url(&self, client: &reqwest::Client, url: &str) -> Result<ResponseInfo, CheckError> {
-        match client.get(url).header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) \
-                              AppleWebKit/537.36 (KHTML, like Gecko) \
-                              Chrome/127.0.0.0 Safari/537.36")
-            .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
-            .header("Accept-Language", "en-US,en;q=0.5")
-            .header("Connection", "keep-alive")
-            .send().await {
-            Ok(response) => {
-                let status = response.status();
-                let status_code = status.as_u16();
-                let status_text = status.canonical_reason()
-                    .unwrap_or("Unknown")
-                    .to_string();
-                let headers = response.headers()
-                    .iter()
-                    .map(|(k, v)| format!("{}: {:?}", k, v))
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                if status.is_success() {
-                    Ok(ResponseInfo {
-                        status_code,
-                        status_text,
-                        headers: Some(headers),
-                    })
-                } else if status.is_server_error() {
-                    Err(CheckError {
-                        category: ErrorCategory::ServerError,
-                        message: format!("服务器错误: {}", status),
-                        detail: format!("状态码: {}, 原因: {}", status_code, status_text),
-                        status_code: Some(status_code),
-                    })
-                } else if status.is_client_error() {
-                    Err(CheckError {
-                        category: ErrorCategory::ClientError,
-                        message: format!("客户端错误: {}", status),
-                        detail: format!("状态码: {}, 原因: {}", status_code, status_text),
-                        status_code: Some(status_code),
-                    })
-                } else {
-                    Ok(ResponseInfo {
-                        status_code,
-                        status_text,
-                        headers: Some(headers),
-                    })
-                }
+/// 在数据中心之间轮流取记录，同一数据中心内保持原顺序
+fn interleave_by_center(records: Vec<MonitorRecord>) -> Vec<MonitorRecord> {
+    let total = records.len();
+    let mut queues: Vec<VecDeque<MonitorRecord>> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for record in records {
+        let i = *index.entry(record.center_name.clone()).or_insert_with(|| {
+            queues.push(VecDeque::new());
+            queues.len() - 1
+        });
+        queues[i].push_back(record);
+    }
+    let mut interleaved = Vec::with_capacity(total);
+    while interleaved.len() < total {
+        for queue in &mut queues {
+            if let Some(record) = queue.pop_front() {
+                interleaved.push(record);
             }
-            Err(e) => {
-                let category = ErrorCategory::from_request_error(&e);
-                let detail = format!(
-                    "错误详情: {}\n错误链: {:?}\n是否超时: {}\n是否连接错误: {}\n是否重定向错误: {}",
-                    e,
-                    e.source().map(|s| s.to_string()).unwrap_or_default(),
-                    e.is_timeout(),
-                    e.is_connect(),
-                    e.is_redirect()
-                );
-                Err(CheckError {
-                    category,
-                    message: e.to_string(),
-                    detail,
-                    status_code: e.status().map(|s| s.as_u16()),
+        }
+    }
+    interleaved
+}
+
+/// 用 `seed` 打乱记录顺序（Fisher-Yates，splitmix64），相同种子得到相同顺序
+fn shuffle(mut records: Vec<MonitorRecord>, mut seed: u64) -> Vec<MonitorRecord> {
+    let mut next = || {
+        seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    for i in (1..records.len()).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        records.swap(i, j);
+    }
+    records
+}
+
+/// 去掉查询字符串中名称在 `params` 里的参数（同名参数全部去掉），其余参数保持原顺序和原编码
+fn strip_query_params(url: &str, params: &[String]) -> String {
+    let (without_fragment, fragment) = match url.find('#') {
+        Some(index) => url.split_at(index),
+        None => (url, ""),
+    };
+    let Some((base, query)) = without_fragment.split_once('?') else {
+        return url.to_string();
+    };
+    let kept: Vec<&str> = query.split('&')
+        .filter(|pair| {
+            let key = pair.split('=').next().unwrap_or_default();
+            let key = urlencoding::decode(&key.replace('+', " ")).map(|k| k.into_owned()).unwrap_or_else(|_| key.to_string());
+            !pair.is_empty() && !params.contains(&key)
+        })
+        .collect();
+    if kept.is_empty() {
+        format!("{}{}", base, fragment)
+    } else {
+        format!("{}?{}{}", base, kept.join("&"), fragment)
+    }
+}
+
+/// 可能在短时间内恢复的失败，配置 check_retries 时重试
+fn is_retryable(category: &ErrorCategory) -> bool {
+    matches!(category, ErrorCategory::Timeout | ErrorCategory::NetworkConnection
+        | ErrorCategory::ConnectionRefused | ErrorCategory::ServerError)
+}
+
+/// 超时和连接错误说明本地链路或对方可能已过载，作为降低并发的信号
+fn is_congestion(record: &MonitorRecord) -> bool {
+    const CONGESTION: [ErrorCategory; 3] = [ErrorCategory::Timeout, ErrorCategory::NetworkConnection, ErrorCategory::ConnectionRefused];
+    record.error_category.as_deref()
+        .is_some_and(|category| CONGESTION.iter().any(|c| c.to_string() == category))
+}
+
+/// 已写库的记录只保留汇总需要的字段，释放响应头和错误详情
+fn strip_written(mut records: Vec<MonitorRecord>) -> Vec<MonitorRecord> {
+    for record in &mut records {
+        record.headers = None;
+        record.error_detail = None;
+    }
+    records
+}
+
+/// 把代表成员的检查结果记到重复组内的另一个成员上
+fn attribute_result(mut record: MonitorRecord, from: &MonitorRecord) -> MonitorRecord {
+    record.check_time = Utc::now();
+    record.status_code = from.status_code;
+    record.status_text = from.status_text.clone();
+    record.error_category = from.error_category.clone();
+    record.error_msg = from.error_msg.clone();
+    record.error_detail = from.error_detail.clone();
+    record.response_time_ms = from.response_time_ms;
+    record.dns_ms = from.dns_ms;
+    record.connect_ms = from.connect_ms;
+    record.ttfb_ms = from.ttfb_ms;
+    record.http_version = from.http_version.clone();
+    record.connection_reused = from.connection_reused;
+    record.content_hash = from.content_hash.clone();
+    record.content_changed = from.content_changed;
+    record.auth_used = from.auth_used;
+    record.requested_url = from.requested_url.clone();
+    record.attempts_detail = from.attempts_detail.clone();
+    record.total_time_ms = from.total_time_ms;
+    record.failed_hop_index = from.failed_hop_index;
+    record.failed_hop_url = from.failed_hop_url.clone();
+    record.redirect_location = from.redirect_location.clone();
+    record.timeout_secs = from.timeout_secs;
+    record.is_likely_local_issue = from.is_likely_local_issue;
+    record.headers = from.headers.clone();
+    record
+}
+
+/// 按 `@id` 或 casdc_id 查询数据集的检查结果；MongoDB 不可用时只返回 DuckDB 中的记录
+pub async fn lookup_dataset(config: &Config, duckdb: &DuckDB, id: &str, limit: usize) -> Result<DatasetChecks> {
+    let centers: Vec<(&str, String)> = config.centers.iter()
+        .map(|c| (c.name.as_str(), config.collection_name(&c.name)))
+        .collect();
+    let dataset = match MongoDB::new(&config.mongodb).await {
+        Ok(mongo) => mongo.find_dataset(&centers, id).await.unwrap_or_else(|e| {
+            warn!("在 MongoDB 中查找数据集 {} 失败: {}", id, e);
+            None
+        }),
+        Err(e) => {
+            warn!("连接 MongoDB 失败，不补充数据集信息: {}", e);
+            None
+        }
+    };
+    let raw_id = dataset.as_ref().map(|(_, d)| d.raw_id.clone()).unwrap_or_else(|| id.to_string());
+    let history = duckdb.get_records_by_raw_id(&raw_id, limit).await?;
+    let url_status = match history.first() {
+        Some(latest) => duckdb.get_url_status(std::slice::from_ref(&latest.url)).await?.remove(&latest.url),
+        None => None,
+    };
+    Ok(DatasetChecks {
+        first_seen_at: url_status.as_ref().map(|status| status.first_seen_at),
+        last_success_at: url_status.and_then(|status| status.last_success_at),
+        casdc_id: dataset.as_ref().and_then(|(_, d)| d.casdc_id.clone()),
+        name: dataset.as_ref().map(|(_, d)| d.extract_name())
+            .or_else(|| history.iter().find_map(|r| r.name.clone())),
+        center_name: dataset.as_ref().map(|(center, _)| center.clone())
+            .or_else(|| history.first().map(|r| r.center_name.clone())),
+        latest: history.first().cloned(),
+        history,
+        raw_id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::filter::QueryFilter;
+    use crate::models::{CoverageReport, TimeBasis};
+    use crate::test_support::{response, stub_server, Request, Stub};
+
+    fn strip(url: &str, params: &[&str]) -> String {
+        let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+        strip_query_params(url, &params)
+    }
+
+    fn center_record(id: usize, center: &str) -> MonitorRecord {
+        MonitorRecord { id: id.to_string(), center_name: center.to_string(), ..MonitorRecord::default() }
+    }
+
+    fn centers(records: &[MonitorRecord]) -> String {
+        records.iter().map(|r| r.center_name.as_str()).collect()
+    }
+
+    #[test]
+    fn interleaves_round_robin() {
+        let records = ["A", "A", "A", "B", "C", "C"].iter().enumerate().map(|(i, c)| center_record(i, c)).collect();
+        assert_eq!(centers(&interleave_by_center(records)), "ABCACA");
+        assert!(interleave_by_center(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn interleave_round_robin_property() {
+        for seed in 0..200u64 {
+            // 用种子生成不同长度、不同数据中心分布的输入
+            let len = (seed % 37) as usize;
+            let mut state = seed;
+            let records: Vec<_> = (0..len)
+                .map(|i| {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                    center_record(i, ["A", "B", "C", "D", "E"][(state >> 33) as usize % (1 + seed as usize % 5)])
                 })
+                .collect();
+            let mut order: Vec<&str> = Vec::new();
+            let mut expected: HashMap<&str, Vec<&str>> = HashMap::new();
+            for record in &records {
+                if !order.contains(&record.center_name.as_str()) {
+                    order.push(&record.center_name);
+                }
+                expected.entry(&record.center_name).or_default().push(&record.id);
+            }
+
+            let interleaved = interleave_by_center(records.clone());
+            assert_eq!(interleaved.len(), records.len(), "seed {}", seed);
+            // 每一轮按数据中心首次出现的顺序各取一条，取完的数据中心跳过
+            let mut rounds: HashMap<&str, usize> = HashMap::new();
+            let mut previous: Option<(usize, usize)> = None;
+            for record in &interleaved {
+                let round = rounds.entry(&record.center_name).or_default();
+                assert_eq!(expected[record.center_name.as_str()][*round], record.id, "seed {}: 同一数据中心内顺序改变", seed);
+                let position = (*round, order.iter().position(|c| *c == record.center_name).unwrap());
+                assert!(previous.is_none_or(|p| p < position), "seed {}: 不是轮流取记录", seed);
+                previous = Some(position);
+                *round += 1;
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn strips_tracking_params() {
+        let params = ["token", "utm_source", "Signature"];
+        assert_eq!(strip("https://a.org/d?id=1&utm_source=mail&token=abc", &params), "https://a.org/d?id=1");
+        // 同名参数全部去掉，名称区分大小写
+        assert_eq!(strip("https://a.org/d?token=1&id=1&token=2&signature=x", &params), "https://a.org/d?id=1&signature=x");
+        // 参数名按解码后比较
+        assert_eq!(strip("https://a.org/d?to%6Ben=1&id=1", &params), "https://a.org/d?id=1");
+        assert_eq!(strip("https://a.org/d?token&id=1", &params), "https://a.org/d?id=1");
+        // 全部去掉时不留下 ?
+        assert_eq!(strip("https://a.org/d?token=1&Signature=2", &params), "https://a.org/d");
+    }
+
+    #[test]
+    fn preserves_other_params() {
+        let params = ["token"];
+        // 保留的参数保持原顺序和原编码
+        assert_eq!(strip("https://a.org/d?b=%2F&token=1&a=x+y&c=", &params), "https://a.org/d?b=%2F&a=x+y&c=");
+        assert_eq!(strip("https://a.org/d?tokens=1&my_token=2", &params), "https://a.org/d?tokens=1&my_token=2");
+        // 空参数对被去掉
+        assert_eq!(strip("https://a.org/d?&id=1&&token=2&", &params), "https://a.org/d?id=1");
+    }
+
+    #[test]
+    fn keeps_fragment() {
+        let params = ["token"];
+        assert_eq!(strip("https://a.org/d?token=1#files", &params), "https://a.org/d#files");
+        assert_eq!(strip("https://a.org/d?id=1&token=1#files", &params), "https://a.org/d?id=1#files");
+        // 片段中的 ? 不是查询字符串
+        assert_eq!(strip("https://a.org/d?id=1#a?token=2", &params), "https://a.org/d?id=1#a?token=2");
+    }
+
+    #[test]
+    fn url_without_query_is_unchanged() {
+        let params = ["token"];
+        assert_eq!(strip("https://a.org/d", &params), "https://a.org/d");
+        assert_eq!(strip("https://a.org/d#token=1", &params), "https://a.org/d#token=1");
+        assert_eq!(strip("https://a.org/d#x?token=1", &params), "https://a.org/d#x?token=1");
+        assert_eq!(strip("https://a.org/d?", &params), "https://a.org/d");
+    }
+
+    /// 使用内存 DuckDB 的监测器，`monitor` 为追加到 monitor 下的配置，`centers` 为数据中心列表
+    async fn monitor(monitor: &str, centers: &str) -> DataMonitor {
+        with_config(config(monitor, centers)).await
+    }
+
+    async fn with_config(config: Config) -> DataMonitor {
+        DataMonitor::new(Arc::new(config), Arc::new(DuckDB::new(":memory:").await.unwrap()))
+    }
+
+    fn config(monitor: &str, centers: &str) -> Config {
+        serde_yaml::from_str(&format!(r#"
+centers: {centers}
+mongodb: {{ uri: "mongodb://127.0.0.1:9/?serverSelectionTimeoutMS=100&connectTimeoutMS=100", database: "db" }}
+duckdb: {{ path: ":memory:" }}
+monitor:
+  fetch_interval_days: 30
+  check_interval_days: 7
+  http_timeout_secs: 5
+  max_concurrent: 4
+  progress_interval_secs: 0
+  {monitor}
+"#, centers = centers, monitor = monitor)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn cancelled_runs_start_no_checks() {
+        let stub = stub_server(|_| response("200 OK", &[], "")).await;
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let monitor = monitor("", "[]").await.with_cancel_token(cancel);
+        let urls = vec![format!("{}/a", stub.base), format!("{}/b", stub.base)];
+        let (summary, results) = monitor.check_urls("A", urls, false).await.unwrap();
+        assert!(summary.cancelled);
+        assert!(results.is_empty());
+        assert!(stub.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn records_http_version_and_connection_reuse() {
+        let stub = stub_server(|request| match request.target.as_str() {
+            "/old" => "HTTP/1.0 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+            _ => "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n".to_string(),
+        }).await;
+        let monitor = monitor("", "[]").await;
+        // 依次检查，之后的请求复用第一次保持的连接
+        let mut records = Vec::new();
+        for path in ["/keep", "/keep", "/old"] {
+            let (_, results) = monitor.check_urls("A", vec![format!("{}{}", stub.base, path)], true).await.unwrap();
+            records.extend(results);
+        }
+        let versions: Vec<_> = records.iter().map(|r| (r.http_version.as_deref(), r.connection_reused)).collect();
+        assert_eq!(versions, [(Some("HTTP/1.1"), Some(false)), (Some("HTTP/1.1"), Some(true)), (Some("HTTP/1.0"), Some(true))]);
+
+        let counts = monitor.duckdb.get_http_version_counts(&QueryFilter { include_in_progress: true, ..QueryFilter::default() }).await.unwrap();
+        let counts: Vec<_> = counts.iter().map(|c| (c.center_name.as_str(), c.http_version.as_str(), c.checks, c.reused)).collect();
+        assert_eq!(counts, [("A", "HTTP/1.0", 1, 1), ("A", "HTTP/1.1", 2, 1)]);
+    }
+
+    #[tokio::test]
+    async fn stored_headers_are_truncated() {
+        let stub = stub_server(|_| response("200 OK", &[&format!("X-Padding: {}", "a".repeat(2000))], "")).await;
+        let monitor = monitor("max_header_bytes: 200", "[]").await;
+        let (_, results) = monitor.check_urls("A", vec![format!("{}/a", stub.base)], false).await.unwrap();
+        let headers = results[0].headers.as_deref().unwrap();
+        assert!(headers.len() <= 200, "{}", headers.len());
+        assert!(headers.contains("已截断"), "{}", headers);
+    }
+
+    /// 数据中心 `center` 的数据集，`index` 决定 MongoDB ID，同一 `index` 在多次运行中是同一个数据集
+    fn dataset(index: u32, center: &str, url: &str) -> Dataset {
+        let mut id = [0u8; 12];
+        id[8..].copy_from_slice(&index.to_be_bytes());
+        Dataset {
+            _id: Some(ObjectId::from_bytes(id)),
+            raw_id: index.to_string(),
+            casdc_id: None,
+            data_type: None,
+            url: Some(Bson::String(url.to_string())),
+            name: None,
+            date_published: None,
+            sync_date: None,
+            center_name: Some(center.to_string()),
+            identifier: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// 所有请求经代理发到桩服务，使公网URL不被当作内网地址跳过
+    async fn proxied_monitor(monitor: &str, stub: &Stub) -> DataMonitor {
+        let mut config = config(monitor, "[]");
+        config.http.proxy = Some(stub.base.clone());
+        with_config(config).await
+    }
+
+    /// 按完整运行的流程检查 `datasets`，所有数据集属于数据中心 A
+    async fn run_datasets(monitor: &DataMonitor, datasets: Vec<Dataset>) -> MonitorSummary {
+        resume_datasets(monitor, datasets, None).await
+    }
+
+    async fn resume_datasets(monitor: &DataMonitor, datasets: Vec<Dataset>, resume: Option<Resume>) -> MonitorSummary {
+        let mongo = MongoDB::new(&monitor.config.mongodb).await.unwrap();
+        monitor.check_datasets(&mongo, datasets, vec![&center_a()], resume, None).await.unwrap()
+    }
+
+    fn center_a() -> Center {
+        serde_yaml::from_str(r#"{ name: "A", secretKey: "", url: "", enabled: true }"#).unwrap()
+    }
+
+    #[tokio::test]
+    async fn buffered_records_stay_within_batch_bound() {
+        let stub = stub_server(|_| response("200 OK", &[], "")).await;
+        let monitor = proxied_monitor("result_batch_size: 10", &stub).await;
+        let datasets: Vec<Dataset> = (0..400)
+            .map(|i| dataset(i, "A", &format!("http://data.casdc.cn/{}", i)))
+            .collect();
+
+        let done = std::sync::atomic::AtomicBool::new(false);
+        let (summary, peak) = tokio::join!(
+            async {
+                let summary = run_datasets(&monitor, datasets).await;
+                done.store(true, Ordering::Relaxed);
+                summary
+            },
+            async {
+                let mut peak = 0;
+                while !done.load(Ordering::Relaxed) {
+                    peak = peak.max(monitor.buffered_records());
+                    tokio::task::yield_now().await;
+                }
+                peak
+            },
+        );
+        assert_eq!(summary.total, 400);
+        assert_eq!(summary.success, 400);
+        // 最多一批等待写库，加上并发中已完成的检查
+        assert!(peak > 0 && peak <= 10 + 4, "{}", peak);
+        assert_eq!(monitor.buffered_records(), 0);
+        assert_eq!(stub.requests.lock().unwrap().len(), 400);
+    }
+
+    #[tokio::test]
+    async fn content_changes_between_runs_are_flagged() {
+        static SECOND_RUN: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+        let stub = stub_server(|request| {
+            let target = request.target.as_str();
+            let second = SECOND_RUN.load(Ordering::Relaxed);
+            match target {
+                // 只有空白变化
+                "http://data.casdc.cn/spaces" => response("200 OK", &["Content-Type: text/html"],
+                                                         if second { "<p>hello\n  world</p>" } else { "<p>hello world</p>" }),
+                "http://data.casdc.cn/replaced" => response("200 OK", &["Content-Type: text/plain"],
+                                                           if second { "维护中" } else { "data v1" }),
+                // 二进制内容按原始字节计算，空白变化也算变化
+                "http://data.casdc.cn/binary" => response("200 OK", &["Content-Type: application/octet-stream"],
+                                                         if second { "a  b" } else { "a b" }),
+                _ => response("200 OK", &[], "same"),
+            }
+        }).await;
+        let monitor = proxied_monitor("fingerprint: true", &stub).await;
+        let datasets = || ["spaces", "replaced", "binary", "same"].iter().enumerate()
+            .map(|(i, path)| dataset(i as u32, "A", &format!("http://data.casdc.cn/{}", path)))
+            .collect::<Vec<_>>();
+        let filter = QueryFilter { include_in_progress: true, ..QueryFilter::default() };
+
+        run_datasets(&monitor, datasets()).await;
+        assert!(monitor.duckdb.get_content_changes(&filter).await.unwrap().is_empty());
+        SECOND_RUN.store(true, Ordering::Relaxed);
+        run_datasets(&monitor, datasets()).await;
+
+        let mut changed: Vec<String> = monitor.duckdb.get_content_changes(&filter).await.unwrap()
+            .into_iter().map(|c| c.url).collect();
+        changed.sort();
+        assert_eq!(changed, ["http://data.casdc.cn/binary", "http://data.casdc.cn/replaced"]);
+    }
+
+    #[tokio::test]
+    async fn configured_credentials_are_sent_and_rejections_classified() {
+        let stub = stub_server(|request| {
+            let authorization = request.header("authorization").unwrap_or_default();
+            let accepted = match request.target.as_str() {
+                "/basic/data" => authorization == "Basic dTpw",
+                "/bearer/data" => authorization == "Bearer other",
+                _ => false,
+            };
+            response(if accepted { "200 OK" } else { "401 Unauthorized" }, &[], "")
+        }).await;
+        let password = std::env::temp_dir().join(format!("dataset-monitor-password-{}", std::process::id()));
+        std::fs::write(&password, "p\n").unwrap();
+        let monitor = monitor("", &format!(r#"
+  - name: "A"
+    secretKey: ""
+    url: ""
+    enabled: true
+    url_credentials:
+      - {{ pattern: "/basic/", username: "u", password: {{ file: "{}" }} }}
+      - {{ pattern: "/bearer/", token: "t" }}"#, password.display())).await;
+        let urls = ["/basic/data", "/bearer/data", "/open/data"].iter().map(|path| format!("{}{}", stub.base, path)).collect();
+        let (_, mut results) = monitor.check_urls("A", urls, false).await.unwrap();
+        results.sort_by(|a, b| a.url.cmp(&b.url));
+        let outcome: Vec<_> = results.iter().map(|r| (r.status_code, r.error_category.clone(), r.auth_used)).collect();
+        assert_eq!(outcome, [
+            (Some(200), None, true),
+            (Some(401), Some(ErrorCategory::AuthRejected.to_string()), true),
+            (Some(401), Some(ErrorCategory::ClientError.to_string()), false),
+        ]);
+        // 记录中不保存凭证
+        let stored = serde_json::to_string(&results).unwrap();
+        assert!(!stored.contains("dTpw") && !stored.contains("Bearer"), "{}", stored);
+        let _ = std::fs::remove_file(&password);
+    }
+
+    #[tokio::test]
+    async fn coverage_counts_skipped_datasets_by_reason() {
+        let stub = stub_server(|_| response("200 OK", &[], "")).await;
+        let monitor = proxied_monitor("", &stub).await;
+        let url = |i: u32| format!("http://data.casdc.cn/{}", i);
+        let first = vec![
+            dataset(0, "A", &url(0)),
+            dataset(1, "A", &url(1)),
+            Dataset { url: None, ..dataset(2, "A", "") },
+            Dataset { url: Some(Bson::Null), ..dataset(3, "A", "") },
+            Dataset { url: Some(Bson::Array(vec![Bson::String(url(4))])), ..dataset(4, "A", "") },
+            dataset(5, "A", "TODO"),
+            dataset(6, "A", "http://10.0.0.1/data"),
+            // 不在本次运行的数据中心中的数据集也单独统计
+            dataset(7, "B", &url(7)),
+        ];
+        let summary = run_datasets(&monitor, first).await;
+        assert_eq!(summary.total, 3);
+        let coverage: Vec<_> = summary.coverage.iter()
+            .map(|c| (c.center_name.as_str(), c.datasets, c.with_url, c.skipped.clone().into_iter().collect::<Vec<_>>(), c.coverage))
+            .collect();
+        assert_eq!(coverage, [
+            ("A", 7, 2, vec![(SkipReason::NoUrl, 2), (SkipReason::UnsupportedUrlType, 1), (SkipReason::UnusableUrl, 2)], 2.0 * 100.0 / 7.0),
+            ("B", 1, 1, vec![], 100.0),
+        ]);
+
+        run_datasets(&monitor, vec![dataset(0, "A", &url(0))]).await;
+        let report = CoverageReport::from_runs(&monitor.duckdb.get_recent_runs(10).await.unwrap());
+        let latest: Vec<_> = report.latest.iter().map(|p| (p.coverage.center_name.as_str(), p.coverage.coverage)).collect();
+        assert_eq!(latest, [("A", 100.0), ("B", 100.0)]);
+        let trend: Vec<_> = report.trend.iter().map(|p| (p.coverage.center_name.as_str(), p.coverage.datasets)).collect();
+        assert_eq!(trend, [("A", 1), ("A", 7), ("B", 1)]);
+    }
+
+    #[tokio::test]
+    async fn retries_record_each_attempt() {
+        static FLAKY_CALLS: AtomicUsize = AtomicUsize::new(0);
+        let stub = stub_server(|request| match request.target.as_str() {
+            "/flaky" if FLAKY_CALLS.fetch_add(1, Ordering::SeqCst) == 0 => response("503 Service Unavailable", &[], ""),
+            "/flaky" => response("200 OK", &[], ""),
+            "/down" => response("500 Internal Server Error", &[], ""),
+            _ => response("404 Not Found", &[], ""),
+        }).await;
+        let monitor = monitor("check_retries: 1", "[]").await;
+        let urls = ["/flaky", "/down", "/missing"].iter().map(|path| format!("{}{}", stub.base, path)).collect();
+        let (_, results) = monitor.check_urls("A", urls, true).await.unwrap();
+        let result = |path: &str| results.iter().find(|r| r.url.ends_with(path)).unwrap();
+        let attempts = |path: &str| result(path).attempts_detail.iter()
+            .map(|a| (a.attempt, a.status_code, a.error_category.clone()))
+            .collect::<Vec<_>>();
+        let server_error = Some(ErrorCategory::ServerError.to_string());
+
+        assert_eq!(attempts("/flaky"), [(1, Some(503), server_error.clone()), (2, Some(200), None)]);
+        assert_eq!(result("/flaky").status_code, Some(200));
+        assert_eq!(attempts("/down"), [(1, Some(500), server_error.clone()), (2, Some(500), server_error.clone())]);
+        assert_eq!(result("/down").error_category, server_error);
+        // 4xx 不重试
+        assert_eq!(attempts("/missing"), [(1, Some(404), Some(ErrorCategory::ClientError.to_string()))]);
+
+        // 响应时间是最后一次尝试的耗时，总耗时包括重试前等待的 2 秒
+        for path in ["/flaky", "/down"] {
+            let record = result(path);
+            assert_eq!(record.response_time_ms, Some(record.attempts_detail[1].duration_ms));
+            assert!(record.total_time_ms.unwrap() >= 2000, "{:?}", record.total_time_ms);
+        }
+        assert!(result("/missing").total_time_ms.unwrap() < 2000);
+
+        let filter = QueryFilter { include_in_progress: true, ..QueryFilter::default() };
+        let final_ms = monitor.duckdb.get_timing_breakdown(&filter, TimeBasis::Final).await.unwrap()[0].avg_total_ms.unwrap();
+        let total_ms = monitor.duckdb.get_timing_breakdown(&filter, TimeBasis::Total).await.unwrap()[0].avg_total_ms.unwrap();
+        assert!(final_ms < 1000.0 && total_ms >= 4000.0 / 3.0, "{} {}", final_ms, total_ms);
+
+        let stored = monitor.duckdb.get_latest_record("A", &format!("{}/flaky", stub.base), Utc::now() - chrono::Duration::hours(1))
+            .await.unwrap().unwrap();
+        assert_eq!(stored.attempts_detail.len(), 2);
+        assert_eq!(stored.total_time_ms, result("/flaky").total_time_ms);
+    }
+
+    /// 重定向测试用的桩服务：/loop/a 和 /loop/b 互相跳转，/two/1 经两次跳转到 /two/3
+    fn redirects(request: &Request) -> String {
+        match request.target.as_str() {
+            "/loop/a" => response("302 Found", &["Location: /loop/b"], ""),
+            "/loop/b" => response("302 Found", &["Location: /loop/a"], ""),
+            "/two/1" => response("301 Moved Permanently", &["Location: /two/2"], ""),
+            "/two/2" => response("302 Found", &["Location: /two/3"], ""),
+            "/two/3" => response("200 OK", &[], ""),
+            _ => response("404 Not Found", &[], ""),
+        }
+    }
+
+    #[tokio::test]
+    async fn redirect_loops_record_the_chain_and_limits_follow_center_overrides() {
+        let stub = stub_server(redirects).await;
+        let monitor = monitor("max_redirects: 3",
+                              r#"[{ name: B, secretKey: "", url: "", enabled: true, max_redirects: 1 }]"#).await;
+        let url = |path: &str| format!("{}{}", stub.base, path);
+
+        let (_, results) = monitor.check_urls("A", vec![url("/loop/a"), url("/two/1")], false).await.unwrap();
+        let looped = results.iter().find(|r| r.url.ends_with("/loop/a")).unwrap();
+        assert_eq!(looped.error_category, Some(ErrorCategory::TooManyRedirects.to_string()));
+        let chain = format!("重定向链:\n  1. {a}\n  2. {b}\n  3. {a}\n  4. {b} <- 失败\n  5. {a}", a = url("/loop/a"), b = url("/loop/b"));
+        assert!(looped.error_detail.as_deref().unwrap().ends_with(&chain), "{:?}", looped.error_detail);
+        assert_eq!(looped.failed_hop_index, Some(3));
+        let followed = results.iter().find(|r| r.url.ends_with("/two/1")).unwrap();
+        assert_eq!(followed.status_code, Some(200));
+
+        // B 最多跟随 1 次重定向
+        let (_, results) = monitor.check_urls("B", vec![url("/two/1")], false).await.unwrap();
+        assert_eq!(results[0].error_category, Some(ErrorCategory::TooManyRedirects.to_string()));
+        assert_eq!(results[0].failed_hop_url, Some(url("/two/2")));
+    }
+
+    #[tokio::test]
+    async fn failures_after_redirects_are_stored_against_the_failing_hop() {
+        // DOI 经解析服务跳转到出版商页面，第 2 跳（第三个URL）返回 503；另一个 DOI 的同样跳转成功
+        let stub = stub_server(|request| match request.target.as_str() {
+            "http://doi.org/10.1/x" => response("302 Found", &["Location: http://hdl.handle.net/x"], ""),
+            "http://hdl.handle.net/x" => response("301 Moved Permanently", &["Location: http://data.casdc.cn/landing/x"], ""),
+            "http://data.casdc.cn/landing/x" => response("503 Service Unavailable", &[], ""),
+            "http://doi.org/10.1/ok" => response("302 Found", &["Location: http://hdl.handle.net/ok"], ""),
+            "http://hdl.handle.net/ok" => response("301 Moved Permanently", &["Location: http://data.casdc.cn/landing/ok"], ""),
+            _ => response("200 OK", &[], ""),
+        }).await;
+        let monitor = proxied_monitor("max_redirects: 5", &stub).await;
+        let summary = run_datasets(&monitor, vec![
+            dataset(1, "A", "http://doi.org/10.1/x"),
+            dataset(2, "A", "http://doi.org/10.1/ok"),
+        ]).await;
+        assert_eq!((summary.total, summary.success), (2, 1));
+
+        // update_status 写入的最新一行
+        let since = Utc::now() - chrono::Duration::hours(1);
+        let failed = monitor.duckdb.get_latest_record("A", "1", since).await.unwrap().unwrap();
+        assert_eq!((failed.url.as_str(), failed.status_code), ("http://doi.org/10.1/x", Some(503)));
+        assert_eq!(failed.error_category, Some(ErrorCategory::ServerError.to_string()));
+        assert_eq!((failed.failed_hop_index, failed.failed_hop_url.as_deref()), (Some(2), Some("http://data.casdc.cn/landing/x")));
+        let chain = "重定向链:\n  1. http://doi.org/10.1/x\n  2. http://hdl.handle.net/x\n  3. http://data.casdc.cn/landing/x <- 失败";
+        assert!(failed.error_detail.as_deref().unwrap().ends_with(chain), "{:?}", failed.error_detail);
+        let succeeded = monitor.duckdb.get_latest_record("A", "2", since).await.unwrap().unwrap();
+        assert_eq!(succeeded.status_code, Some(200));
+        assert_eq!((succeeded.failed_hop_index, succeeded.failed_hop_url), (None, None));
+
+        // 问题URL的最近失败中同样带上失败的一跳
+        let problematic = monitor.duckdb.get_problematic_urls(&QueryFilter::default(), 50.0, 5).await.unwrap();
+        assert_eq!(problematic.len(), 1);
+        assert_eq!(problematic[0].url, "http://doi.org/10.1/x");
+        let recent = &problematic[0].recent_failures[0];
+        assert_eq!((recent.failed_hop_index, recent.failed_hop_url.as_deref()), (Some(2), Some("http://data.casdc.cn/landing/x")));
+        assert_eq!(stub.requests.lock().unwrap().len(), 6);
+    }
+
+    #[tokio::test]
+    async fn tagged_runs_check_tagged_datasets_and_scope_stats() {
+        let stub = stub_server(|request| match request.target.as_str() {
+            "http://data.casdc.cn/critical/broken" | "http://data.casdc.cn/other/broken" => response("503 Service Unavailable", &[], ""),
+            _ => response("200 OK", &[], ""),
+        }).await;
+        let monitor = proxied_monitor("", &stub).await;
+        let datasets = || {
+            let mut datasets = vec![
+                dataset(1, "A", "http://data.casdc.cn/critical/ok"),
+                dataset(2, "A", "http://data.casdc.cn/critical/broken"),
+                dataset(3, "A", "http://data.casdc.cn/other/broken"),
+            ];
+            datasets[0].tags = vec!["critical".to_string(), "core".to_string()];
+            datasets[1].tags = vec!["critical".to_string()];
+            datasets
+        };
+        let full = run_datasets(&monitor, datasets()).await;
+        assert_eq!((full.tag.as_deref(), full.total, full.success), (None, 3, 1));
+        let tags: Vec<_> = full.tags.iter().map(|t| (t.tag.as_str(), t.total, t.success)).collect();
+        assert_eq!(tags, [("core", 1, 1), ("critical", 2, 1)]);
+
+        // 标签运行只检查 get_tagged_datasets 返回的数据集
+        let mongo = MongoDB::new(&monitor.config.mongodb).await.unwrap();
+        let critical: Vec<Dataset> = datasets().into_iter().filter(|d| d.tags.iter().any(|t| t == "critical")).collect();
+        let tagged = monitor.check_datasets(&mongo, critical, vec![&center_a()], None, Some("critical")).await.unwrap();
+        assert_eq!((tagged.tag.as_deref(), tagged.total, tagged.success), (Some("critical"), 2, 1));
+        let recent = monitor.duckdb.get_recent_runs(10).await.unwrap();
+        let runs: Vec<_> = recent.iter().map(|r| (r.run_id.as_str(), r.tag.as_deref())).collect();
+        assert_eq!(runs, [(tagged.run_id.as_str(), Some("critical")), (full.run_id.as_str(), None)]);
+        // 标签运行不作为下一次完整运行的比较对象
+        assert_eq!(crate::alert::previous_summary(&recent, "next").unwrap().run_id, full.run_id);
+
+        let availability = |filter: QueryFilter| {
+            let duckdb = monitor.duckdb.clone();
+            async move {
+                duckdb.get_center_availability(&filter).await.unwrap().iter()
+                    .map(|c| (c.total_checks, c.success_checks))
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(availability(QueryFilter::default()).await, [(5, 2)]);
+        assert_eq!(availability(QueryFilter::default().tag("critical")).await, [(4, 2)]);
+        assert_eq!(availability(QueryFilter::default().tag("core")).await, [(2, 2)]);
+        assert!(availability(QueryFilter::default().tag("crit")).await.is_empty());
+        let problematic = monitor.duckdb.get_problematic_urls(&QueryFilter::default().tag("critical"), 50.0, 5).await.unwrap();
+        let urls: Vec<_> = problematic.iter().map(|p| (p.url.as_str(), p.total_checks, p.failed_checks)).collect();
+        assert_eq!(urls, [("http://data.casdc.cn/critical/broken", 2, 2)]);
+    }
+
+    #[tokio::test]
+    async fn tagged_runs_skip_disabled_centers() {
+        let stub = stub_server(|_| response("200 OK", &[], "")).await;
+        let Ok(uri) = std::env::var("DATASET_MONITOR_TEST_MONGODB_URI") else {
+            return;
+        };
+        let mut config = config("", r#"[{ name: "A", secretKey: "", url: "", enabled: true },
+            { name: "B", secretKey: "", url: "", enabled: false }]"#);
+        config.mongodb.uri = uri;
+        config.mongodb.database = format!("dataset_monitor_test_tagged_{}", std::process::id());
+        config.http.proxy = Some(stub.base.clone());
+        let database = mongodb::Client::with_uri_str(&config.mongodb.uri).await.unwrap().database(&config.mongodb.database);
+        database.drop().await.unwrap();
+        for (index, center) in [(1, "A"), (2, "B")] {
+            let mut dataset = dataset(index, center, &format!("http://data.casdc.cn/{}", center));
+            dataset.data_type = Some(Bson::String("Dataset".to_string()));
+            dataset.tags = vec!["critical".to_string()];
+            database.collection::<Dataset>(&config.collection_name(center)).insert_one(dataset).await.unwrap();
+        }
+
+        // 停用的数据中心 B 的数据集带同样的标签，但不在标签运行中检查
+        let summary = with_config(config).await.check_tagged("critical").await.unwrap();
+        assert_eq!((summary.total, summary.success), (1, 1));
+        let centers: Vec<_> = summary.centers.iter().map(|c| c.center_name.as_str()).collect();
+        assert_eq!(centers, ["A"]);
+        let requested = stub.targets();
+        assert_eq!(requested, ["http://data.casdc.cn/A"]);
+        database.drop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unfollowed_redirects_follow_the_terminal_redirect_policy() {
+        let stub = stub_server(|request| match request.target.as_str() {
+            "/cap/1" => response("302 Found", &["Location: /cap/2"], ""),
+            "/cap/2" => response("301 Moved Permanently", &["Location: /cap/3"], ""),
+            "/bare" => response("302 Found", &[], ""),
+            _ => response("200 OK", &[], ""),
+        }).await;
+        let url = |path: &str| format!("{}{}", stub.base, path);
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-terminal-redirect-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.yaml");
+        // (策略, 超过重定向上限的结果, 没有 Location 的结果)，结果为 (状态码, 错误类别, 记录的 Location)
+        let cases = [
+            ("success", (Some(301), None, Some("/cap/3")), (Some(302), None, None)),
+            ("failure", (None, Some(ErrorCategory::TooManyRedirects), Some("/cap/3")), (Some(302), Some(ErrorCategory::ServerError), None)),
+            ("redirected", (Some(301), Some(ErrorCategory::Redirected), Some("/cap/3")), (Some(302), Some(ErrorCategory::Redirected), None)),
+        ];
+        for (policy, capped, bare) in cases {
+            // 经 Config::load 加载，与实际运行一样按策略调整 success_statuses
+            std::fs::write(&path, format!(r#"
+centers: []
+mongodb: {{ uri: "mongodb://127.0.0.1:9", database: "db" }}
+duckdb: {{ path: ":memory:" }}
+monitor:
+  fetch_interval_days: 30
+  check_interval_days: 7
+  http_timeout_secs: 5
+  max_concurrent: 4
+  max_redirects: 1
+  terminal_redirect: {policy}
+"#)).unwrap();
+            let config = Config::load(path.to_str().unwrap()).unwrap();
+            let duckdb = DuckDB::new(":memory:").await.unwrap().with_success_statuses(&config.monitor.success_statuses);
+            let monitor = DataMonitor::new(Arc::new(config), Arc::new(duckdb));
+            let (summary, results) = monitor.check_urls("A", vec![url("/cap/1"), url("/bare"), url("/ok")], true).await.unwrap();
+
+            let outcome = |path: &str| {
+                let record = results.iter().find(|r| r.url == url(path)).unwrap();
+                (record.status_code, record.error_category.clone(), record.redirect_location.clone())
+            };
+            let expected = |(status, category, location): (Option<u16>, Option<ErrorCategory>, Option<&str>)| {
+                (status, category.map(|c| c.to_string()), location.map(url))
+            };
+            assert_eq!(outcome("/cap/1"), expected(capped), "{}", policy);
+            assert_eq!(outcome("/bare"), expected(bare), "{}", policy);
+            assert_eq!(outcome("/ok"), (Some(200), None, None), "{}", policy);
+
+            // 运行汇总、成功判断和统计查询对 3xx 的处理一致
+            let succeeded = if policy == "success" { 3 } else { 1 };
+            assert_eq!(summary.success, succeeded, "{}", policy);
+            assert_eq!(results.iter().filter(|r| monitor.config.monitor.success_statuses.is_success(r.status_code) && r.error_category.is_none()).count(), succeeded, "{}", policy);
+            let centers = monitor.duckdb.get_center_availability(&QueryFilter::default()).await.unwrap();
+            assert_eq!((centers[0].total_checks, centers[0].success_checks), (3, succeeded as i64), "{}", policy);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn runs_above_the_storage_soft_limit_prune_old_checks() {
+        let stub = stub_server(|_| response("200 OK", &[], "")).await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-soft-limit-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // (软限制 MB, 删除的记录数)，限制为 0 时任何大小都超过
+        for (round, (limit, pruned)) in [(1024, None), (0, Some(100))].into_iter().enumerate() {
+            let path = dir.join(format!("monitor-{}.db", round));
+            let duckdb = Arc::new(DuckDB::new(path.to_str().unwrap()).await.unwrap());
+            // 100 个数据集各有两条早于保留期的记录，清理时每个数据集保留最近一条
+            let old: Vec<MonitorRecord> = ["2020-01-01T00:00:00Z", "2021-01-01T00:00:00Z"].iter()
+                .flat_map(|time| (0..100).map(move |i| MonitorRecord {
+                    id: format!("old-{}", i),
+                    raw_id: Some(format!("old-{}", i)),
+                    url: format!("https://data.casdc.cn/old/{}", i),
+                    center_name: "A".to_string(),
+                    check_time: time.parse().unwrap(),
+                    status_code: Some(200),
+                    ..MonitorRecord::default()
+                }))
+                .collect();
+            duckdb.insert_records(&old).await.unwrap();
+            let mut config = config("", "[]");
+            config.duckdb.storage_soft_limit_mb = Some(limit);
+            let monitor = DataMonitor::new(Arc::new(config), duckdb.clone());
+            let urls = (0..50).map(|i| format!("{}/{}", stub.base, i)).collect();
+            let (summary, _) = monitor.check_urls("A", urls, true).await.unwrap();
+
+            let storage = summary.storage.unwrap();
+            assert!(storage.start.db_bytes.is_some() && storage.end.total_bytes() > 0, "{:?}", storage);
+            assert_eq!(storage.soft_limit_bytes, Some(limit * 1024 * 1024));
+            assert_eq!((storage.over_soft_limit(), storage.pruned_records), (pruned.is_some(), pruned), "limit {}", limit);
+            let remaining: i64 = duckdb.conn.lock().await
+                .query_row("SELECT COUNT(*) FROM dataset_monitor WHERE id LIKE 'old-%'", [], |row| row.get(0)).unwrap();
+            assert_eq!(remaining, 200 - pruned.unwrap_or(0) as i64, "limit {}", limit);
+            // 运行记录中的大小与汇总一致
+            let series = duckdb.get_storage_series(10).await.unwrap();
+            assert_eq!(series.len(), 1);
+            assert_eq!((series[0].run_id.as_str(), series[0].start, series[0].end), (summary.run_id.as_str(), storage.start, storage.end));
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn interrupted_runs_resume_with_the_remaining_datasets() {
+        let stub = stub_server(|_| response("200 OK", &[], "")).await;
+        let first = proxied_monitor("result_batch_size: 5", &stub).await;
+        let datasets = || (0..30).map(|i| dataset(i, "A", &format!("http://data.casdc.cn/{}", i))).collect::<Vec<_>>();
+        let requests = || stub.requests.lock().unwrap().len();
+
+        // 检查到一半时中断
+        let (interrupted, ()) = tokio::join!(run_datasets(&first, datasets()), async {
+            while requests() < 10 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            first.cancel.cancel();
+        });
+        assert!(interrupted.cancelled);
+        let checked = requests();
+        assert!(checked < 30, "{}", checked);
+        assert_eq!(first.duckdb.get_run_progress(&interrupted.run_id).await.unwrap().len(), checked);
+
+        let second = DataMonitor::new(first.config.clone(), first.duckdb.clone());
+        let resume = second.resumable_run(&[&center_a()]).await.unwrap().unwrap();
+        assert_eq!((resume.run_id.as_str(), resume.completed.len()), (interrupted.run_id.as_str(), checked));
+        let resumed = resume_datasets(&second, datasets(), Some(resume)).await;
+        // 只检查剩余的数据集，汇总包括中断前完成的
+        assert_eq!(requests(), 30);
+        assert_eq!((resumed.run_id.as_str(), resumed.total, resumed.resumed_records), (interrupted.run_id.as_str(), 30, checked));
+        assert!(second.duckdb.get_unfinished_runs().await.unwrap().is_empty());
+        assert!(second.resumable_run(&[&center_a()]).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn stale_unfinished_runs_are_aborted() {
+        let monitor = monitor("resume_max_age_hours: 12", "[]").await;
+        let names = ["A".to_string()];
+        monitor.duckdb.start_run_state("old", Utc::now() - chrono::Duration::hours(13), &names).await.unwrap();
+        monitor.duckdb.start_run_state("recent", Utc::now() - chrono::Duration::hours(11), &names).await.unwrap();
+        let resumable: Vec<String> = monitor.close_stale_runs().await.unwrap().into_iter().map(|r| r.run_id).collect();
+        assert_eq!(resumable, ["recent"]);
+        let unfinished: Vec<String> = monitor.duckdb.get_unfinished_runs().await.unwrap().into_iter().map(|r| r.run_id).collect();
+        assert_eq!(unfinished, ["recent"]);
+    }
+
+    #[tokio::test]
+    async fn runs_record_their_egress_ip() {
+        let stub = stub_server(|request| match request.target.as_str() {
+            "/ip" => response("200 OK", &[], "203.0.113.7"),
+            _ => response("200 OK", &[], ""),
+        }).await;
+        let monitor = proxied_monitor(&format!("egress_ip_url: {}/ip", stub.base), &stub).await;
+        let summary = run_datasets(&monitor, vec![dataset(1, "A", "http://data.casdc.cn/1")]).await;
+        let environment = summary.environment.as_ref().unwrap();
+        assert_eq!(environment.egress_ip.as_deref(), Some("203.0.113.7"));
+        assert_eq!(environment.version, crate::build_info::VERSION);
+
+        let stored = monitor.duckdb.get_run(&summary.run_id).await.unwrap().unwrap();
+        assert_eq!(stored.summary.environment.unwrap().egress_ip.as_deref(), Some("203.0.113.7"));
+
+        // 查询失败不影响运行
+        let monitor = proxied_monitor("egress_ip_url: http://127.0.0.1:9/ip", &stub).await;
+        let summary = run_datasets(&monitor, vec![dataset(1, "A", "http://data.casdc.cn/1")]).await;
+        assert_eq!(summary.environment.unwrap().egress_ip, None);
+        assert_eq!(summary.total, 1);
+    }
+
+    #[tokio::test]
+    async fn injected_clients_drive_checks() {
+        let stub = stub_server(|request| match request.target.as_str() {
+            "http://mock.invalid/ok" => response("200 OK", &[], "ok"),
+            "http://mock.invalid/moved" => response("301 Moved Permanently", &["Location: /ok"], ""),
+            _ => response("404 Not Found", &[], ""),
+        }).await;
+        // 接受连接但从不响应
+        let silent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let silent_base = format!("http://{}", silent.local_addr().unwrap());
+        // 注入的客户端把 slow.invalid 的请求发到不响应的地址，其他请求发到桩服务
+        let proxy = {
+            let stub = stub.base.clone();
+            reqwest::Proxy::custom(move |url| Some(if url.host_str() == Some("slow.invalid") { silent_base.clone() } else { stub.clone() }))
+        };
+        let client = reqwest::Client::builder().proxy(proxy).redirect(reqwest::redirect::Policy::none()).build().unwrap();
+        let mut config = config("", "[]");
+        config.monitor.http_timeout_secs = 1;
+        let monitor = DataMonitor::with_client(Arc::new(config), Arc::new(DuckDB::new(":memory:").await.unwrap()), client);
+
+        let urls = ["http://mock.invalid/ok", "http://mock.invalid/moved", "http://slow.invalid/data"].map(String::from).to_vec();
+        let (_, results) = monitor.check_urls("A", urls, false).await.unwrap();
+        let result = |url: &str| results.iter().find(|r| r.url == url).unwrap();
+        assert_eq!((result("http://mock.invalid/ok").status_code, result("http://mock.invalid/ok").error_category.clone()), (Some(200), None));
+        let moved = result("http://mock.invalid/moved");
+        assert_eq!((moved.status_code, moved.failed_hop_index), (Some(200), None));
+        let slow = result("http://slow.invalid/data");
+        assert_eq!((slow.status_code, slow.error_category.clone()), (None, Some(ErrorCategory::Timeout.to_string())));
+
+        let mut requested = stub.targets();
+        requested.sort();
+        assert_eq!(requested, ["http://mock.invalid/moved", "http://mock.invalid/ok", "http://mock.invalid/ok"]);
+        drop(silent);
+    }
+
+    #[tokio::test]
+    async fn consecutive_runs_share_one_duckdb_handle() {
+        let stub = stub_server(|request| match request.target.as_str() {
+            "http://data.casdc.cn/broken" => response("503 Service Unavailable", &[], ""),
+            _ => response("200 OK", &[], ""),
+        }).await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-shared-handle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("monitor.db");
+        let duckdb = Arc::new(DuckDB::new(path.to_str().unwrap()).await.unwrap());
+        let mut config = config("", "[]");
+        config.http.proxy = Some(stub.base.clone());
+        let monitor = DataMonitor::new(Arc::new(config), duckdb.clone());
+        let datasets = || vec![
+            dataset(1, "A", "http://data.casdc.cn/ok"),
+            dataset(2, "A", "http://data.casdc.cn/broken"),
+        ];
+
+        // 两次运行都写入同一个句柄而不重新打开文件，其他持有者（如 API）通过同一句柄读取结果
+        let first = run_datasets(&monitor, datasets()).await;
+        assert_eq!(duckdb.get_recent_runs(10).await.unwrap().len(), 1);
+        let second = run_datasets(&monitor, datasets()).await;
+        assert!(Arc::ptr_eq(&monitor.duckdb, &duckdb));
+        assert_ne!(first.run_id, second.run_id);
+        assert_eq!((second.total, second.success), (2, 1));
+
+        let runs: Vec<String> = duckdb.get_recent_runs(10).await.unwrap().into_iter().map(|r| r.run_id).collect();
+        assert_eq!(runs, [second.run_id.clone(), first.run_id.clone()]);
+        let centers = duckdb.get_center_availability(&QueryFilter::default()).await.unwrap();
+        assert_eq!((centers[0].total_checks, centers[0].success_checks), (4, 2));
+        drop(monitor);
+        drop(duckdb);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// 数据中心自己的 `@id` 是含斜杠、冒号、问号和井号的 IRI
+    const IRI: &str = "https://data.casdc.cn/dataset/ark:/88888/x7k2?version=2#main";
+
+    #[tokio::test]
+    async fn iri_dataset_ids_are_looked_up_after_checks() {
+        let stub = stub_server(|request| match request.target.as_str() {
+            "http://data.casdc.cn/files/other" => response("503 Service Unavailable", &[], ""),
+            _ => response("200 OK", &[], ""),
+        }).await;
+        let monitor = proxied_monitor("", &stub).await;
+        let datasets = || {
+            let mut checked = dataset(1, "A", "http://data.casdc.cn/files/x7k2");
+            checked.raw_id = IRI.to_string();
+            // 另一个数据集的 @id 以前者为前缀，不应一起查到
+            let mut other = dataset(2, "A", "http://data.casdc.cn/files/other");
+            other.raw_id = format!("{}/files", IRI);
+            vec![checked, other]
+        };
+        run_datasets(&monitor, datasets()).await;
+        run_datasets(&monitor, datasets()).await;
+
+        // MongoDB 不可用时只返回 DuckDB 中的记录
+        let checks = lookup_dataset(&monitor.config, &monitor.duckdb, IRI, 10).await.unwrap();
+        assert_eq!(checks.raw_id, IRI);
+        assert_eq!((checks.center_name.as_deref(), checks.casdc_id.as_deref()), (Some("A"), None));
+        assert_eq!(checks.history.len(), 2);
+        assert!(checks.history.iter().all(|r| r.raw_id.as_deref() == Some(IRI) && r.url == "http://data.casdc.cn/files/x7k2"));
+        assert!(checks.history[0].check_time >= checks.history[1].check_time);
+        let latest = checks.latest.as_ref().unwrap();
+        assert_eq!((latest.check_time, latest.status_code), (checks.history[0].check_time, Some(200)));
+        assert!(checks.first_seen_at.unwrap() <= checks.history[1].check_time);
+        assert_eq!(checks.last_success_at, Some(latest.check_time));
+
+        let limited = lookup_dataset(&monitor.config, &monitor.duckdb, IRI, 1).await.unwrap();
+        assert_eq!(limited.history.len(), 1);
+        let unknown = lookup_dataset(&monitor.config, &monitor.duckdb, "https://data.casdc.cn/dataset/ark:/88888", 10).await.unwrap();
+        assert!(unknown.history.is_empty() && unknown.latest.is_none());
+    }
+
+    #[tokio::test]
+    async fn fetched_iri_datasets_are_looked_up_end_to_end() {
+        let stub = stub_server(|request| match request.path() {
+            "http://center.invalid/auth" => response("200 OK", &[], r#"{"ticket":{"token":"t","expires":3600},"serviceList":[
+                {"name":"DATASET_LIST","version":"1","url":"http://center.invalid/list"},
+                {"name":"GET_DATASET_DETAILS","version":"1","url":"http://center.invalid/details"}]}"#),
+            "http://center.invalid/list" => response("200 OK", &[], r#"[{"id":"x7k2"}]"#),
+            "http://center.invalid/details" => response("200 OK", &[], &serde_json::json!({
+                "@id": IRI,
+                "@type": "Dataset",
+                "casdc_id": "CSTR:16666.11.x7k2",
+                "schema:url": "http://data.casdc.cn/files/x7k2",
+                "schema:name": "Ark dataset",
+            }).to_string()),
+            _ => response("200 OK", &[], ""),
+        }).await;
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-iri-lookup-{}", std::process::id()));
+        let mut config = config(&format!("persist_tokens: false\n  state_dir: \"{0}\"\n  raw_responses_dir: \"{0}/raw\"", dir.display()),
+            r#"[{ name: "A", secretKey: "k", url: "http://center.invalid/auth", enabled: true }]"#);
+        let Ok(uri) = std::env::var("DATASET_MONITOR_TEST_MONGODB_URI") else {
+            return;
+        };
+        config.mongodb.uri = uri;
+        config.mongodb.database = format!("dataset_monitor_test_iri_{}", std::process::id());
+        config.http.proxy = Some(stub.base.clone());
+        let database = mongodb::Client::with_uri_str(&config.mongodb.uri).await.unwrap().database(&config.mongodb.database);
+        database.drop().await.unwrap();
+        let config = Arc::new(config);
+        let center = config.centers[0].clone();
+
+        // 获取：详情写入 MongoDB
+        let client = reqwest::Client::builder().proxy(reqwest::Proxy::all(&stub.base).unwrap()).build().unwrap();
+        let fetcher = crate::fetcher::DataFetcher::with_client(config.clone(), client);
+        let mongo = MongoDB::new(&config.mongodb).await.unwrap();
+        let report = fetcher.fetch_center(&center, &mongo).await;
+        assert_eq!((report.discovered, report.processed), (1, 1), "{:?}", report.list_error);
+
+        // 检查：结果写入 DuckDB
+        let duckdb = Arc::new(DuckDB::new(":memory:").await.unwrap());
+        let summary = DataMonitor::new(config.clone(), duckdb.clone()).check_center(&center).await.unwrap();
+        assert_eq!((summary.total, summary.success), (1, 1));
+
+        // 查询：按 @id 和 casdc_id 得到同一个数据集
+        for id in [IRI, "CSTR:16666.11.x7k2"] {
+            let checks = lookup_dataset(&config, &duckdb, id, 10).await.unwrap();
+            assert_eq!(checks.raw_id, IRI, "{}", id);
+            assert_eq!(checks.casdc_id.as_deref(), Some("CSTR:16666.11.x7k2"));
+            assert_eq!((checks.name.as_deref(), checks.center_name.as_deref()), (Some("Ark dataset"), Some("A")));
+            assert_eq!(checks.history.len(), 1);
+            assert_eq!(checks.latest.as_ref().and_then(|r| r.status_code), Some(200));
+        }
+        database.drop().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,292 @@
+use crate::config::{AlertTarget, AlertTargetKind};
+use anyhow::{bail, Result};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::fmt::Write;
+
+/// 钉钉 markdown 消息正文上限（字节）
+const DINGTALK_MAX_BYTES: usize = 20_000;
+/// 企业微信 markdown 消息正文上限（字节）
+const WECHAT_MAX_BYTES: usize = 4_096;
+/// 为 “另有 N 条未列出” 预留的长度
+const TRUNCATION_NOTE_BYTES: usize = 64;
+
+/// 一条待发送的通知
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub title: String,
+    /// markdown 正文，不含列表条目
+    pub body: String,
+    /// 附带的列表条目（如失败的URL），超出平台长度限制时截断
+    pub items: Vec<String>,
+    /// 通用 webhook 原样发送的 JSON
+    pub payload: Value,
+}
+
+impl Notification {
+    /// 拼接 markdown 正文，总长度不超过 `max_bytes`，超出时截断列表条目
+    pub fn render_markdown(&self, max_bytes: usize) -> String {
+        let mut out = format!("### {}\n\n{}\n", self.title, self.body);
+        if out.len() > max_bytes {
+            truncate_to(&mut out, max_bytes);
+            return out;
+        }
+        if !self.items.is_empty() {
+            out.push('\n');
+        }
+        for (i, item) in self.items.iter().enumerate() {
+            let line = format!("- {}\n", item);
+            if out.len() + line.len() + TRUNCATION_NOTE_BYTES > max_bytes {
+                let _ = write!(out, "\n> 另有 {} 条未列出", self.items.len() - i);
+                break;
+            }
+            out.push_str(&line);
+        }
+        out
+    }
+}
+
+/// 按字符边界截断到不超过 `max_bytes`
+fn truncate_to(s: &mut String, max_bytes: usize) {
+    let mut end = max_bytes.min(s.len());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    s.truncate(end);
+}
+
+/// 告警通知渠道，新增渠道只需实现如何构造请求
+pub trait Notifier: Send + Sync {
+    /// 日志中显示的接收方
+    fn target(&self) -> &str;
+
+    fn request(&self, client: &reqwest::Client, notification: &Notification) -> reqwest::RequestBuilder;
+
+    /// 检查响应体，机器人接口在 HTTP 200 时仍可能通过 errcode 返回失败
+    fn check_response(&self, _body: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub fn build_notifier(target: &AlertTarget) -> Box<dyn Notifier> {
+    match target.kind {
+        AlertTargetKind::Webhook => Box::new(WebhookNotifier { url: target.url.clone() }),
+        AlertTargetKind::Dingtalk => Box::new(DingTalkNotifier {
+            url: target.url.clone(),
+            secret: target.secret.clone(),
+        }),
+        AlertTargetKind::Wechat => Box::new(WeChatNotifier { url: target.url.clone() }),
+    }
+}
+
+/// 通用 webhook：以 JSON POST 发送 payload
+pub struct WebhookNotifier {
+    url: String,
+}
+
+impl Notifier for WebhookNotifier {
+    fn target(&self) -> &str {
+        &self.url
+    }
+
+    fn request(&self, client: &reqwest::Client, notification: &Notification) -> reqwest::RequestBuilder {
+        client.post(&self.url).json(&notification.payload)
+    }
+}
+
+/// 钉钉自定义机器人，配置了 secret 时按加签方式在URL上附加 timestamp 和 sign
+pub struct DingTalkNotifier {
+    url: String,
+    secret: Option<String>,
+}
+
+impl Notifier for DingTalkNotifier {
+    fn target(&self) -> &str {
+        &self.url
+    }
+
+    fn request(&self, client: &reqwest::Client, notification: &Notification) -> reqwest::RequestBuilder {
+        let url = match &self.secret {
+            Some(secret) => dingtalk_signed_url(&self.url, secret, chrono::Utc::now().timestamp_millis()),
+            None => self.url.clone(),
+        };
+        client.post(url).json(&dingtalk_payload(notification))
+    }
+
+    fn check_response(&self, body: &str) -> Result<()> {
+        check_errcode(body)
+    }
+}
+
+/// 钉钉加签：HmacSHA256("{timestamp}\n{secret}")，Base64 后 URL 编码
+pub fn dingtalk_sign(secret: &str, timestamp_ms: i64) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}\n{}", timestamp_ms, secret).as_bytes());
+    let signature = base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes());
+    urlencoding::encode(&signature).into_owned()
+}
+
+pub fn dingtalk_signed_url(url: &str, secret: &str, timestamp_ms: i64) -> String {
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{}{}timestamp={}&sign={}", url, separator, timestamp_ms, dingtalk_sign(secret, timestamp_ms))
+}
+
+pub fn dingtalk_payload(notification: &Notification) -> Value {
+    json!({
+        "msgtype": "markdown",
+        "markdown": {
+            "title": notification.title,
+            "text": notification.render_markdown(DINGTALK_MAX_BYTES),
+        }
+    })
+}
+
+/// 企业微信群机器人
+pub struct WeChatNotifier {
+    url: String,
+}
+
+impl Notifier for WeChatNotifier {
+    fn target(&self) -> &str {
+        &self.url
+    }
+
+    fn request(&self, client: &reqwest::Client, notification: &Notification) -> reqwest::RequestBuilder {
+        client.post(&self.url).json(&wechat_payload(notification))
+    }
+
+    fn check_response(&self, body: &str) -> Result<()> {
+        check_errcode(body)
+    }
+}
+
+pub fn wechat_payload(notification: &Notification) -> Value {
+    json!({
+        "msgtype": "markdown",
+        "markdown": {
+            "content": notification.render_markdown(WECHAT_MAX_BYTES),
+        }
+    })
+}
+
+/// 钉钉与企业微信都以 `{"errcode": 0, "errmsg": "ok"}` 表示成功
+fn check_errcode(body: &str) -> Result<()> {
+    let value: Value = serde_json::from_str(body)?;
+    match value.get("errcode").and_then(Value::as_i64) {
+        Some(0) | None => Ok(()),
+        Some(code) => bail!("errcode {}: {}", code, value.get("errmsg").and_then(Value::as_str).unwrap_or("")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{response, stub_server, Requests};
+
+    /// 机器人桩服务，以 `body` 作为 JSON 响应体返回；返回服务地址和收到的请求
+    async fn robot_server(body: &'static str) -> (String, Requests) {
+        let stub = stub_server(move |_| response("200 OK", &["Content-Type: application/json"], body)).await;
+        (stub.base, stub.requests)
+    }
+
+    fn notification(items: usize) -> Notification {
+        Notification {
+            title: "数据中心 A 成功率告警".to_string(),
+            body: "成功率 **80.0%**，失败 20".to_string(),
+            items: (0..items).map(|i| format!("https://a.example.org/dataset/{}", i)).collect(),
+            payload: json!({ "type": "test" }),
+        }
+    }
+
+    async fn deliver(notifier: &dyn Notifier, notification: &Notification) -> Result<()> {
+        let response = notifier.request(&reqwest::Client::new(), notification).send().await?;
+        notifier.check_response(&response.text().await?)
+    }
+
+    #[tokio::test]
+    async fn dingtalk_requests_are_signed() {
+        let (base, requests) = robot_server(r#"{"errcode":0,"errmsg":"ok"}"#).await;
+        let notifier = build_notifier(&AlertTarget {
+            kind: AlertTargetKind::Dingtalk,
+            url: format!("{}/robot/send?access_token=abc", base),
+            secret: Some("SEC0123456789".to_string()),
+        });
+        let before = chrono::Utc::now().timestamp_millis();
+        deliver(notifier.as_ref(), &notification(2)).await.unwrap();
+        let after = chrono::Utc::now().timestamp_millis();
+
+        let request = requests.lock().unwrap().pop().unwrap();
+        let (target, body) = (request.target.as_str(), request.text());
+        let (path, query) = target.split_once('?').unwrap();
+        assert_eq!(path, "/robot/send");
+        let params: Vec<(&str, &str)> = query.split('&').map(|p| p.split_once('=').unwrap()).collect();
+        assert_eq!(params.iter().map(|(k, _)| *k).collect::<Vec<_>>(), ["access_token", "timestamp", "sign"]);
+        assert_eq!(params[0].1, "abc");
+        let timestamp: i64 = params[1].1.parse().unwrap();
+        assert!((before..=after).contains(&timestamp));
+
+        // 按钉钉文档独立计算签名：HmacSHA256("{timestamp}\n{secret}") 的 Base64
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"SEC0123456789").unwrap();
+        mac.update(format!("{}\nSEC0123456789", timestamp).as_bytes());
+        let expected = base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes());
+        assert_eq!(urlencoding::decode(params[2].1).unwrap(), expected);
+
+        let payload: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload, json!({
+            "msgtype": "markdown",
+            "markdown": {
+                "title": "数据中心 A 成功率告警",
+                "text": "### 数据中心 A 成功率告警\n\n成功率 **80.0%**，失败 20\n\n\
+                         - https://a.example.org/dataset/0\n- https://a.example.org/dataset/1\n",
+            },
+        }));
+    }
+
+    #[tokio::test]
+    async fn unsigned_dingtalk_requests_keep_the_url() {
+        let (base, requests) = robot_server(r#"{"errcode":310000,"errmsg":"keywords not in content"}"#).await;
+        let notifier = build_notifier(&AlertTarget {
+            kind: AlertTargetKind::Dingtalk,
+            url: format!("{}/robot/send?access_token=abc", base),
+            secret: None,
+        });
+        // HTTP 200 但 errcode 非 0 视为发送失败
+        let err = deliver(notifier.as_ref(), &notification(0)).await.unwrap_err();
+        assert_eq!(err.to_string(), "errcode 310000: keywords not in content");
+        assert_eq!(requests.lock().unwrap()[0].target, "/robot/send?access_token=abc");
+    }
+
+    #[tokio::test]
+    async fn wechat_payloads_respect_the_length_limit() {
+        let (base, requests) = robot_server(r#"{"errcode":0,"errmsg":"ok"}"#).await;
+        let notifier = build_notifier(&AlertTarget {
+            kind: AlertTargetKind::Wechat,
+            url: format!("{}/cgi-bin/webhook/send?key=k", base),
+            secret: None,
+        });
+        deliver(notifier.as_ref(), &notification(500)).await.unwrap();
+
+        let request = requests.lock().unwrap().pop().unwrap();
+        let (target, body) = (request.target.as_str(), request.text());
+        assert_eq!(target, "/cgi-bin/webhook/send?key=k");
+        let payload: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["msgtype"], "markdown");
+        let content = payload["markdown"]["content"].as_str().unwrap();
+        assert!(content.len() <= WECHAT_MAX_BYTES);
+        assert!(content.starts_with("### 数据中心 A 成功率告警\n\n"));
+        let listed = content.matches("\n- https://").count();
+        assert!(listed > 0 && listed < 500);
+        assert!(content.ends_with(&format!("> 另有 {} 条未列出", 500 - listed)));
+    }
+
+    #[test]
+    fn oversized_bodies_are_cut_at_char_boundaries() {
+        let mut notification = notification(3);
+        notification.body = "失败".repeat(100);
+        let text = notification.render_markdown(101);
+        assert!(text.len() <= 101);
+        assert!(!text.contains("https://"));
+    }
+}
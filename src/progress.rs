@@ -0,0 +1,117 @@
+//! 监测运行的进度：定时输出一行已检查数、成功率、吞吐和预计剩余时间，代替逐个URL的日志
+
+use crate::models::percentage;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// 计算吞吐的滑动窗口包含的输出次数
+const THROUGHPUT_WINDOW: usize = 10;
+
+/// 一次运行中已完成的检查数，检查任务完成时记录
+pub struct Progress {
+    total: usize,
+    checked: AtomicUsize,
+    succeeded: AtomicUsize,
+}
+
+impl Progress {
+    pub fn new(total: usize) -> Self {
+        Self { total, checked: AtomicUsize::new(0), succeeded: AtomicUsize::new(0) }
+    }
+
+    pub fn record(&self, success: bool) {
+        self.checked.fetch_add(1, Ordering::Relaxed);
+        if success {
+            self.succeeded.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// 后台输出进度的任务，drop 时停止
+pub struct ProgressTicker {
+    stop: CancellationToken,
+}
+
+impl Drop for ProgressTicker {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+/// 每隔 `interval` 输出一次进度；吞吐按最近 THROUGHPUT_WINDOW 次输出之间完成的检查数计算
+pub fn spawn(progress: Arc<Progress>, interval: Duration) -> ProgressTicker {
+    let stop = CancellationToken::new();
+    let cancelled = stop.clone();
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval_at(Instant::now() + interval, interval);
+        let mut samples = VecDeque::from([(Instant::now(), 0)]);
+        loop {
+            tokio::select! {
+                _ = cancelled.cancelled() => break,
+                _ = ticks.tick() => {}
+            }
+            let checked = progress.checked.load(Ordering::Relaxed);
+            let succeeded = progress.succeeded.load(Ordering::Relaxed);
+            let now = Instant::now();
+            samples.push_back((now, checked));
+            if samples.len() > THROUGHPUT_WINDOW + 1 {
+                samples.pop_front();
+            }
+            let (since, checked_since) = samples[0];
+            let elapsed = (now - since).as_secs_f64();
+            let throughput = if elapsed > 0.0 { (checked - checked_since) as f64 / elapsed } else { 0.0 };
+            let remaining = progress.total.saturating_sub(checked);
+            let eta = if throughput > 0.0 {
+                format_duration(Duration::from_secs_f64(remaining as f64 / throughput))
+            } else {
+                "未知".to_string()
+            };
+            info!("检查进度: {}/{} ({:.1}%)，成功率 {:.1}%，{:.1} 个/秒，预计剩余 {}",
+                  checked, progress.total, percentage(checked as i64, progress.total as i64), percentage(succeeded as i64, checked as i64), throughput, eta);
+        }
+    });
+    ProgressTicker { stop }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{}秒", s),
+        (0, m, s) => format!("{}分{}秒", m, s),
+        (h, m, _) => format!("{}小时{}分", h, m),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_use_the_two_largest_units() {
+        assert_eq!(format_duration(Duration::from_millis(999)), "0秒");
+        assert_eq!(format_duration(Duration::from_secs(59)), "59秒");
+        assert_eq!(format_duration(Duration::from_secs(60)), "1分0秒");
+        assert_eq!(format_duration(Duration::from_secs(3599)), "59分59秒");
+        assert_eq!(format_duration(Duration::from_secs(3600 * 26 + 61)), "26小时1分");
+    }
+
+    #[tokio::test]
+    async fn ticker_stops_when_dropped() {
+        let progress = Arc::new(Progress::new(3));
+        progress.record(true);
+        progress.record(false);
+        assert_eq!((progress.checked.load(Ordering::Relaxed), progress.succeeded.load(Ordering::Relaxed)), (2, 1));
+
+        let ticker = spawn(progress.clone(), Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(35)).await;
+        assert_eq!(Arc::strong_count(&progress), 2);
+        drop(ticker);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(Arc::strong_count(&progress), 1, "停止后释放进度");
+    }
+}
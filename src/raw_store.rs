@@ -0,0 +1,269 @@
+use crate::config::MonitorConfig;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+/// 解析失败时保存的原始响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawResponse {
+    pub center_name: String,
+    pub dataset_id: String,
+    pub saved_at: DateTime<Utc>,
+    /// 超过大小上限被截断，只保留前面部分供排查，`reparse` 时跳过
+    pub truncated: bool,
+    pub body: String,
+}
+
+/// 原始响应存储，每个响应一个文件：`{dir}/{数据中心}/{数据集ID}.json`
+pub struct RawStore {
+    dir: PathBuf,
+    max_bytes: usize,
+    retention: Duration,
+    total_bytes: u64,
+}
+
+impl RawStore {
+    pub fn from_config(config: &MonitorConfig) -> Self {
+        Self {
+            dir: PathBuf::from(&config.raw_responses_dir),
+            max_bytes: config.raw_response_max_kb * 1024,
+            retention: Duration::from_secs(config.raw_response_retention_days as u64 * 24 * 3600),
+            total_bytes: config.raw_response_total_mb * 1024 * 1024,
+        }
+    }
+
+    /// 保存原始响应，超过大小上限时在字符边界截断并标记 truncated，截断的响应无法重新解析
+    pub fn save(&self, center_name: &str, dataset_id: &str, body: &str) -> Result<PathBuf> {
+        let truncated = body.len() > self.max_bytes;
+        let mut end = body.len().min(self.max_bytes);
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        if truncated {
+            warn!("{} 数据集 {} 原始响应 {} 字节，超过上限只保存前 {} 字节，无法重新解析", center_name, dataset_id, body.len(), end);
+        }
+        let raw = RawResponse {
+            center_name: center_name.to_string(),
+            dataset_id: dataset_id.to_string(),
+            saved_at: Utc::now(),
+            truncated,
+            body: body[..end].to_string(),
+        };
+        let dir = self.dir.join(sanitize(center_name));
+        std::fs::create_dir_all(&dir).with_context(|| format!("创建原始响应目录失败: {}", dir.display()))?;
+        let path = dir.join(format!("{}.json", sanitize(dataset_id)));
+        std::fs::write(&path, serde_json::to_string(&raw)?)
+            .with_context(|| format!("写入原始响应失败: {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// 所有已保存的原始响应
+    pub fn entries(&self) -> Result<Vec<(PathBuf, RawResponse)>> {
+        let mut entries = Vec::new();
+        for path in self.files()? {
+            match std::fs::read_to_string(&path).map_err(anyhow::Error::from)
+                .and_then(|content| Ok(serde_json::from_str::<RawResponse>(&content)?))
+            {
+                Ok(raw) => entries.push((path, raw)),
+                Err(e) => warn!("读取原始响应 {} 失败: {}", path.display(), e),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// 删除超过保留天数的原始响应，剩余的总大小仍超过上限时从最旧的开始删除，返回删除的文件数
+    pub fn prune(&self) -> Result<usize> {
+        let mut removed = 0;
+        let now = SystemTime::now();
+        let mut kept = Vec::new();
+        for path in self.files()? {
+            let Ok(metadata) = std::fs::metadata(&path) else {
+                continue;
+            };
+            let modified = metadata.modified().unwrap_or(now);
+            if now.duration_since(modified).unwrap_or_default() > self.retention {
+                std::fs::remove_file(&path)?;
+                removed += 1;
+            } else {
+                kept.push((modified, metadata.len(), path));
+            }
+        }
+        if removed > 0 {
+            info!("删除 {} 个过期的原始响应", removed);
+        }
+
+        let mut total: u64 = kept.iter().map(|(_, len, _)| len).sum();
+        if total > self.total_bytes {
+            kept.sort();
+            let mut evicted = 0;
+            for (_, len, path) in &kept {
+                if total <= self.total_bytes {
+                    break;
+                }
+                std::fs::remove_file(path)?;
+                total -= len;
+                evicted += 1;
+            }
+            info!("原始响应超过总大小上限 {} 字节，删除 {} 个最旧的原始响应", self.total_bytes, evicted);
+            removed += evicted;
+        }
+        Ok(removed)
+    }
+
+    fn files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        if !self.dir.exists() {
+            return Ok(files);
+        }
+        for center_dir in std::fs::read_dir(&self.dir)? {
+            let center_dir = center_dir?.path();
+            if !center_dir.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(&center_dir)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == "json") {
+                    files.push(path);
+                }
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// 文件名中只保留字母数字（含中文）和 `-_.`，其他字符替换为下划线；
+/// 有字符被替换时追加原名称哈希的前 8 位，避免 `a/b` 和 `a_b` 写到同一个文件
+fn sanitize(s: &str) -> String {
+    use sha2::{Digest, Sha256};
+    let sanitized: String = s.chars()
+        .map(|c| if c.is_alphanumeric() || "-_.".contains(c) { c } else { '_' })
+        .collect();
+    if sanitized == s {
+        return sanitized;
+    }
+    let hash: String = Sha256::digest(s.as_bytes()).iter().take(4).map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}", sanitized, hash)
+}
+
+pub fn remove(path: &Path) -> Result<()> {
+    std::fs::remove_file(path).with_context(|| format!("删除原始响应失败: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn config(dir: &Path, extra: &str) -> Config {
+        serde_yaml::from_str(&format!(r#"
+centers: []
+mongodb: {{ uri: "mongodb://127.0.0.1:9/?serverSelectionTimeoutMS=100&connectTimeoutMS=100", database: "db" }}
+duckdb: {{ path: ":memory:" }}
+monitor:
+  fetch_interval_days: 30
+  check_interval_days: 7
+  http_timeout_secs: 5
+  max_concurrent: 10
+  raw_responses_dir: "{dir}"
+  {extra}
+"#, dir = dir.display(), extra = extra)).unwrap()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-raw-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// 把文件的修改时间设为 `days_ago` 天前
+    fn age(path: &Path, days_ago: u64) {
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(days_ago * 24 * 3600)).unwrap();
+    }
+
+    #[test]
+    fn oversized_bodies_are_truncated_at_a_char_boundary() {
+        let dir = temp_dir("save");
+        let store = RawStore::from_config(&config(&dir, "raw_response_max_kb: 1").monitor);
+
+        let path = store.save("数据中心", "ok", "{}").unwrap();
+        assert_eq!(path, dir.join("数据中心/ok.json"));
+        // 1024 字节落在第 342 个“数”字中间，截断到前一个字符边界
+        let body = "数".repeat(400);
+        store.save("数据中心", "big", &body).unwrap();
+
+        let mut entries = store.entries().unwrap();
+        entries.sort_by(|a, b| a.1.dataset_id.cmp(&b.1.dataset_id));
+        let saved: Vec<_> = entries.iter().map(|(_, raw)| (raw.dataset_id.as_str(), raw.truncated, raw.body.len())).collect();
+        assert_eq!(saved, [("big", true, 1023), ("ok", false, 2)]);
+        assert!(body.starts_with(&entries[0].1.body));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn names_that_differ_only_in_replaced_characters_do_not_collide() {
+        assert!(sanitize("CSTR:1.a-b_c").starts_with("CSTR_1.a-b_c-"));
+        assert_eq!(sanitize("数据集_1"), "数据集_1");
+        let names = ["a/b", "a_b", "a b", "a:b"].map(sanitize);
+        assert_eq!(names[1], "a_b");
+        assert!(names[0].starts_with("a_b-") && names[0].len() == "a_b-".len() + 8);
+        let mut unique = names.to_vec();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), names.len(), "{:?}", names);
+        assert_eq!(sanitize("a/b"), names[0]);
+    }
+
+    #[test]
+    fn prune_removes_expired_then_oldest_files_over_the_budget() {
+        let dir = temp_dir("prune");
+        let store = RawStore::from_config(&config(&dir, "raw_response_retention_days: 7\n  raw_response_total_mb: 1").monitor);
+        let body = "x".repeat(300 * 1024);
+        // 按修改时间从旧到新：expired 超过保留天数，其余共约 1.2MB，超过 1MB 的总上限
+        for (id, days_ago) in [("expired", 10), ("oldest", 5), ("older", 4), ("newer", 2), ("newest", 1)] {
+            age(&store.save("A", id, &body).unwrap(), days_ago);
+        }
+
+        assert_eq!(store.prune().unwrap(), 2);
+        let mut kept: Vec<String> = store.entries().unwrap().into_iter().map(|(_, raw)| raw.dataset_id).collect();
+        kept.sort();
+        assert_eq!(kept, ["newer", "newest", "older"]);
+        assert_eq!(store.prune().unwrap(), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn reparse_writes_fixed_datasets_and_keeps_the_rest() {
+        let Ok(uri) = std::env::var("DATASET_MONITOR_TEST_MONGODB_URI") else {
+            return;
+        };
+        let dir = temp_dir("reparse");
+        let mut config = config(&dir, "raw_response_max_kb: 1");
+        config.mongodb.uri = uri;
+        config.mongodb.database = format!("dataset_monitor_test_reparse_{}", std::process::id());
+        let mongo = crate::db::mongodb::MongoDB::new(&config.mongodb).await.unwrap();
+        let store = RawStore::from_config(&config.monitor);
+        store.save("A", "CSTR:1", r#"{"@id":"d1","@type":"Dataset","schema:url":"http://data.casdc.cn/d1"}"#).unwrap();
+        store.save("A", "CSTR:2", r#"{"@id":"d2"}"#).unwrap();
+        let truncated = format!(r#"{{"@id":"d3","@type":"Dataset","schema:url":"http://data.casdc.cn/d3","schema:name":"{}"}}"#, "x".repeat(2048));
+        store.save("A", "CSTR:3", &truncated).unwrap();
+
+        // data_fetch reparse：可解析的写库并删除原始响应，仍然解析失败和截断的保留
+        let fetcher = crate::fetcher::DataFetcher::new(std::sync::Arc::new(config.clone()));
+        assert_eq!(fetcher.reparse_raw_responses(&mongo).await.unwrap(), (1, 2));
+        let datasets = mongo.get_datasets(&config.collection_name("A")).await.unwrap();
+        let saved: Vec<_> = datasets.iter().map(|d| (d.raw_id.as_str(), d.casdc_id.as_deref())).collect();
+        assert_eq!(saved, [("d1", Some("CSTR:1"))]);
+        assert_eq!(mongo.get_processed_ids("A").await.unwrap(), ["CSTR:1"]);
+        let mut kept: Vec<String> = store.entries().unwrap().into_iter().map(|(_, raw)| raw.dataset_id).collect();
+        kept.sort();
+        assert_eq!(kept, ["CSTR:2", "CSTR:3"]);
+
+        mongodb::Client::with_uri_str(&config.mongodb.uri).await.unwrap()
+            .database(&config.mongodb.database).drop().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
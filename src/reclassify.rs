@@ -0,0 +1,216 @@
+//! 分类规则调整后，按当前规则重新计算历史记录的 error_category 和 is_likely_local_issue
+
+use crate::db::duckdb::DuckDB;
+use crate::db::filter::QueryFilter;
+use crate::models::{ErrorCategory, Reclassification, RequestErrorSignals, StoredClassification};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::info;
+
+/// 每批读取和更新的记录数，每批在一个事务中写回
+const RECLASSIFY_BATCH_SIZE: usize = 5000;
+/// dry-run 时输出的变化样例数
+const MAX_SAMPLES: usize = 50;
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReclassifyReport {
+    pub dry_run: bool,
+    /// 检查的记录数
+    pub scanned: usize,
+    /// 分类或本地问题标记变化的记录数，dry-run 时为将会变化的记录数
+    pub changed: usize,
+    /// 按变化前后的分类汇总，按行数倒序
+    pub transitions: Vec<CategoryTransition>,
+    /// dry-run 时的变化样例
+    pub samples: Vec<ReclassifiedRow>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CategoryTransition {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub local_issue_from: bool,
+    pub local_issue_to: bool,
+    pub rows: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReclassifiedRow {
+    pub id: String,
+    pub url: String,
+    pub check_time: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub local_issue_to: bool,
+}
+
+/// 重新分类 `filter` 范围内的记录，`dry_run` 时只统计不写回
+pub async fn reclassify(duckdb: &DuckDB, filter: &QueryFilter, dry_run: bool) -> Result<ReclassifyReport> {
+    let mut report = ReclassifyReport { dry_run, ..ReclassifyReport::default() };
+    let mut transitions: BTreeMap<(Option<String>, Option<String>, bool, bool), usize> = BTreeMap::new();
+    let mut after_row_id = -1;
+    loop {
+        let batch = duckdb.get_classification_batch(filter, after_row_id, RECLASSIFY_BATCH_SIZE).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        after_row_id = last.row_id;
+        report.scanned += batch.len();
+        let mut changes = Vec::new();
+        for row in &batch {
+            let (category, local_issue) = classify(row);
+            if category == row.error_category && local_issue == row.is_likely_local_issue {
+                continue;
+            }
+            *transitions.entry((row.error_category.clone(), category.clone(), row.is_likely_local_issue, local_issue)).or_default() += 1;
+            if dry_run && report.samples.len() < MAX_SAMPLES {
+                report.samples.push(ReclassifiedRow {
+                    id: row.id.clone(),
+                    url: row.url.clone(),
+                    check_time: row.check_time.clone(),
+                    from: row.error_category.clone(),
+                    to: category.clone(),
+                    local_issue_to: local_issue,
+                });
+            }
+            changes.push(Reclassification { row_id: row.row_id, error_category: category, is_likely_local_issue: local_issue });
+        }
+        report.changed += changes.len();
+        if !dry_run {
+            duckdb.update_classifications(&changes).await?;
+        }
+        info!("重新分类: 已检查 {} 条，{} 条变化", report.scanned, report.changed);
+    }
+    report.transitions = transitions.into_iter()
+        .map(|((from, to, local_issue_from, local_issue_to), rows)| CategoryTransition { from, to, local_issue_from, local_issue_to, rows })
+        .collect();
+    report.transitions.sort_by_key(|t| std::cmp::Reverse(t.rows));
+    Ok(report)
+}
+
+/// 按当前规则计算记录的分类和是否本地问题：有状态码时按状态码，
+/// 没有时从错误详情中解析请求错误的特征；无法重新判断的（熔断、不支持的协议、FTP 和文件检查）沿用原分类，
+/// 只按当前规则重新计算是否本地问题
+fn classify(row: &StoredClassification) -> (Option<String>, bool) {
+    let derived = match (row.status_code, row.error_detail.as_deref()) {
+        _ if matches!(row.http_version.as_deref(), Some("FTP" | "FILE")) => None,
+        // 没有跟随的 3xx 的分类取决于检查时的 terminal_redirect 和 follow_insecure_redirects，保留原分类
+        (Some(300..=399), _) => None,
+        (Some(status_code), _) => Some(ErrorCategory::from_status(status_code, row.auth_used)),
+        (None, Some(detail)) => RequestErrorSignals::parse_detail(detail).map(|(mut signals, request)| {
+            // 旧记录的详情中没有"是否请求错误"，沿用原来是否判为请求取消
+            if request.is_none() {
+                signals.request = row.error_category.as_deref() == Some(&ErrorCategory::RequestCanceled.to_string());
+            }
+            Some(ErrorCategory::from_signals(&signals))
+        }),
+        (None, None) => None,
+    };
+    let category = match derived {
+        Some(category) => category.map(|c| c.to_string()),
+        None => row.error_category.clone(),
+    };
+    // 其他工具的分类名称无法解析，保留原来的标记
+    let local_issue = match category.as_deref().map(str::parse::<ErrorCategory>) {
+        Some(Ok(category)) => category.is_likely_local_issue(),
+        Some(Err(_)) => row.is_likely_local_issue,
+        None => false,
+    };
+    (category, local_issue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::record;
+
+    fn stored(status_code: Option<u16>, error_category: Option<&str>, error_detail: Option<&str>) -> StoredClassification {
+        StoredClassification {
+            row_id: 0,
+            id: "1".to_string(),
+            url: "https://a.example.org/1".to_string(),
+            check_time: "2026-01-01 00:00:00".to_string(),
+            status_code,
+            error_category: error_category.map(str::to_string),
+            error_detail: error_detail.map(str::to_string),
+            auth_used: false,
+            http_version: Some("HTTP/1.1".to_string()),
+            is_likely_local_issue: false,
+        }
+    }
+
+    fn category(name: &str, local_issue: bool) -> (Option<String>, bool) {
+        (Some(name.to_string()), local_issue)
+    }
+
+    #[test]
+    fn status_codes_decide_the_category() {
+        assert_eq!(classify(&stored(Some(503), Some("CLIENT_ERROR"), None)), category("SERVER_ERROR", false));
+        assert_eq!(classify(&stored(Some(200), Some("TIMEOUT_ERROR"), None)), (None, false));
+        let auth = StoredClassification { auth_used: true, ..stored(Some(401), Some("CLIENT_ERROR"), None) };
+        assert_eq!(classify(&auth), category("AUTH_REJECTED", false));
+        assert_eq!(classify(&stored(Some(401), None, None)), category("CLIENT_ERROR", false));
+        // 3xx 保留原分类
+        assert_eq!(classify(&stored(Some(302), Some("REDIRECTED"), None)), category("REDIRECTED", false));
+        assert_eq!(classify(&stored(Some(302), None, None)), (None, false));
+    }
+
+    #[test]
+    fn request_errors_are_parsed_from_the_detail() {
+        let timeout = "是否超时: true\n是否连接错误: false\n是否请求错误: false";
+        assert_eq!(classify(&stored(None, Some("UNKNOWN_ERROR"), Some(timeout))), category("TIMEOUT_ERROR", true));
+        let refused = "是否超时: false\n是否连接错误: true\n错误链: \"tcp connect error: Connection refused\"";
+        assert_eq!(classify(&stored(None, Some("NETWORK_ERROR"), Some(refused))), category("CONNECTION_REFUSED_ERROR", false));
+        // 旧记录的详情没有是否请求错误时沿用原来的请求取消判断
+        let old = "是否超时: false\n是否连接错误: false";
+        assert_eq!(classify(&stored(None, Some("REQUEST_CANCELED_ERROR"), Some(old))), category("REQUEST_CANCELED_ERROR", true));
+        assert_eq!(classify(&stored(None, Some("SSL_ERROR"), Some(old))), category("UNKNOWN_ERROR", false));
+        let request = "是否超时: false\n是否连接错误: false\n是否请求错误: true";
+        assert_eq!(classify(&stored(None, Some("UNKNOWN_ERROR"), Some(request))), category("REQUEST_CANCELED_ERROR", true));
+    }
+
+    #[test]
+    fn unknown_checks_keep_their_category() {
+        // 熔断等没有请求错误详情的记录只重新计算本地问题标记
+        let circuit = StoredClassification { is_likely_local_issue: true, ..stored(None, Some("HOST_CIRCUIT_OPEN"), Some("主机熔断")) };
+        assert_eq!(classify(&circuit), category("HOST_CIRCUIT_OPEN", false));
+        let ftp = StoredClassification { http_version: Some("FTP".to_string()), ..stored(Some(550), Some("TIMEOUT_ERROR"), None) };
+        assert_eq!(classify(&ftp), category("TIMEOUT_ERROR", true));
+        // 其他工具的分类名称保留原来的标记
+        let imported = StoredClassification { is_likely_local_issue: true, ..stored(None, Some("OFFLINE"), None) };
+        assert_eq!(classify(&imported), category("OFFLINE", true));
+        assert_eq!(classify(&stored(None, None, None)), (None, false));
+    }
+
+    async fn category_of(db: &DuckDB, id: &str) -> Option<String> {
+        let url = format!("https://a.example.org/{}", id);
+        db.get_url_history(&url, 1).await.unwrap().remove(0).error_category
+    }
+
+    #[tokio::test]
+    async fn dry_run_reports_without_writing() {
+        let db = DuckDB::new(":memory:").await.unwrap();
+        db.insert_records(&[
+            record("1", "A", Some(503), Some("CLIENT_ERROR")),
+            record("2", "A", Some(404), Some("CLIENT_ERROR")),
+            record("3", "A", Some(500), Some("CLIENT_ERROR")),
+            record("4", "A", Some(200), Some("UNKNOWN_ERROR")),
+        ]).await.unwrap();
+        // 导入的记录不重新分类
+        db.import_records(&[record("5", "A", Some(503), Some("CLIENT_ERROR"))], "legacy", false).await.unwrap();
+
+        let report = reclassify(&db, &QueryFilter::default(), true).await.unwrap();
+        assert_eq!((report.scanned, report.changed, report.samples.len()), (4, 3, 3));
+        let transitions: Vec<_> = report.transitions.iter().map(|t| (t.from.as_deref(), t.to.as_deref(), t.rows)).collect();
+        assert_eq!(transitions, [(Some("CLIENT_ERROR"), Some("SERVER_ERROR"), 2), (Some("UNKNOWN_ERROR"), None, 1)]);
+        assert_eq!(category_of(&db, "1").await.as_deref(), Some("CLIENT_ERROR"));
+
+        let report = reclassify(&db, &QueryFilter::default(), false).await.unwrap();
+        assert_eq!((report.changed, report.samples.len()), (3, 0));
+        assert_eq!(category_of(&db, "1").await.as_deref(), Some("SERVER_ERROR"));
+        assert_eq!(category_of(&db, "4").await, None);
+        assert_eq!(category_of(&db, "5").await.as_deref(), Some("CLIENT_ERROR"));
+        assert_eq!(reclassify(&db, &QueryFilter::default(), false).await.unwrap().changed, 0);
+    }
+}
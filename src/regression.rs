@@ -0,0 +1,137 @@
+//! 数据中心响应时间变慢的检测：本次运行的响应时间中位数与之前运行的基线比较
+
+use crate::config::RegressionDetection;
+use crate::models::{CenterSummary, MonitorSummary, RecordedRegression, ResponseTimeRegression};
+
+/// 读取多少次最近的运行来计算基线：按数据中心调度时每次运行通常只包含一个数据中心
+pub fn lookback_runs(config: &RegressionDetection, center_count: usize) -> usize {
+    (config.baseline_runs * center_count.max(1) * 2).max(50)
+}
+
+/// 比较 `current` 中各数据中心的响应时间中位数与 `recent_runs`（按开始时间倒序）得出的基线，返回变慢的数据中心
+pub fn detect(current: &MonitorSummary, recent_runs: &[MonitorSummary], config: &RegressionDetection) -> Vec<ResponseTimeRegression> {
+    current.centers.iter()
+        .filter_map(|center| {
+            let median_ms = center.median_response_ms?;
+            let mut medians: Vec<u64> = recent_runs.iter()
+                .filter(|run| run.run_id != current.run_id && baseline_run(run))
+                .filter_map(|run| run.center(&center.center_name))
+                .filter(|previous| !local_dominated(previous))
+                .filter_map(|previous| previous.median_response_ms)
+                .take(config.baseline_runs)
+                .collect();
+            if medians.is_empty() || medians.len() < config.min_baseline_runs {
+                return None;
+            }
+            medians.sort_unstable();
+            let baseline_ms = medians[(medians.len() - 1) / 2].max(1);
+            let ratio = median_ms as f64 / baseline_ms as f64;
+            (ratio > config.factor).then(|| ResponseTimeRegression {
+                center_name: center.center_name.clone(),
+                median_ms,
+                baseline_ms,
+                ratio,
+                baseline_runs: medians.len(),
+            })
+        })
+        .collect()
+}
+
+/// 取消的运行只有部分结果，标签运行只检查部分数据集，本地网络问题占一半以上的运行响应时间不代表数据中心
+fn baseline_run(run: &MonitorSummary) -> bool {
+    !run.cancelled && run.tag.is_none() && run.local_issues * 2 <= run.total
+}
+
+fn local_dominated(center: &CenterSummary) -> bool {
+    center.local_issues * 2 > center.total
+}
+
+/// 各次运行中记录的响应时间变慢，顺序与 `runs` 相同
+pub fn recorded(runs: &[MonitorSummary]) -> Vec<RecordedRegression> {
+    runs.iter()
+        .flat_map(|run| run.response_time_regressions.iter().map(|regression| RecordedRegression {
+            run_id: run.run_id.clone(),
+            finished_at: run.finished_at,
+            regression: regression.clone(),
+        }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// 一次运行，`centers` 为 (数据中心, 响应时间中位数, 检查数, 本地问题数)
+    fn run(run_id: &str, centers: &[(&str, Option<u64>, u64, u64)]) -> MonitorSummary {
+        let total: u64 = centers.iter().map(|c| c.2).sum();
+        let local: u64 = centers.iter().map(|c| c.3).sum();
+        let centers: Vec<_> = centers.iter()
+            .map(|&(name, median, total, local)| json!({
+                "center_name": name, "total": total, "success": total - local, "success_rate": 0.0,
+                "local_issues": local, "remote_issues": 0, "error_categories": [], "sample_failures": [],
+                "median_response_ms": median,
+            }))
+            .collect();
+        serde_json::from_value(json!({
+            "run_id": run_id, "started_at": "2026-01-01T00:00:00Z", "finished_at": "2026-01-01T01:00:00Z",
+            "total": total, "success": total - local, "local_issues": local, "remote_issues": 0, "centers": centers,
+        })).unwrap()
+    }
+
+    fn config() -> RegressionDetection {
+        RegressionDetection { factor: 2.0, baseline_runs: 3, min_baseline_runs: 3 }
+    }
+
+    #[test]
+    fn lookback_covers_runs_of_every_center() {
+        assert_eq!(lookback_runs(&config(), 20), 120);
+        assert_eq!(lookback_runs(&config(), 0), 50);
+    }
+
+    #[test]
+    fn regressions_compare_against_the_median_of_usable_runs() {
+        let current = run("now", &[("A", Some(300), 10, 0), ("B", Some(150), 10, 0), ("C", Some(900), 10, 0), ("D", None, 10, 0)]);
+        let mut cancelled = run("cancelled", &[("A", Some(10), 10, 0)]);
+        cancelled.cancelled = true;
+        let mut tagged = run("tagged", &[("A", Some(10), 10, 0)]);
+        tagged.tag = Some("critical".to_string());
+        let recent = vec![
+            run("now", &[("A", Some(10), 10, 0)]),
+            run("r1", &[("A", Some(100), 10, 0), ("B", Some(100), 10, 0), ("C", Some(100), 10, 0)]),
+            cancelled,
+            tagged,
+            // 整次运行或该数据中心本地网络问题占一半以上
+            run("local-run", &[("A", Some(10), 4, 4), ("C", Some(100), 2, 1)]),
+            run("local-center", &[("A", Some(10), 10, 6), ("B", Some(100), 30, 0)]),
+            run("r2", &[("A", Some(120), 10, 0), ("C", Some(100), 10, 0)]),
+            run("r3", &[("A", Some(90), 10, 0), ("B", Some(100), 10, 0)]),
+            // 超过 baseline_runs 的更早运行不计入
+            run("r4", &[("A", Some(1000), 10, 0)]),
+        ];
+
+        let regressions = detect(&current, &recent, &config());
+        assert_eq!(regressions.len(), 1, "{:?}", regressions);
+        let a = &regressions[0];
+        assert_eq!((a.center_name.as_str(), a.median_ms, a.baseline_ms, a.baseline_runs), ("A", 300, 100, 3));
+        assert_eq!(a.ratio, 3.0);
+
+        // 基线运行不足 min_baseline_runs 的 C 不判断，降低要求后判断
+        let lenient = RegressionDetection { min_baseline_runs: 2, ..config() };
+        let names: Vec<String> = detect(&current, &recent, &lenient).into_iter().map(|r| r.center_name).collect();
+        assert_eq!(names, ["A", "C"]);
+    }
+
+    #[test]
+    fn recorded_regressions_keep_run_order() {
+        let mut first = run("r1", &[]);
+        let regression = ResponseTimeRegression { center_name: "A".to_string(), median_ms: 300, baseline_ms: 100, ratio: 3.0, baseline_runs: 3 };
+        first.response_time_regressions = vec![regression.clone(), ResponseTimeRegression { center_name: "B".to_string(), ..regression.clone() }];
+        let mut second = run("r2", &[]);
+        second.response_time_regressions = vec![regression];
+        let recorded: Vec<(String, String)> = recorded(&[first, run("r3", &[]), second]).into_iter()
+            .map(|r| (r.run_id, r.regression.center_name))
+            .collect();
+        assert_eq!(recorded, [("r1".to_string(), "A".to_string()), ("r1".to_string(), "B".to_string()), ("r2".to_string(), "A".to_string())]);
+    }
+}
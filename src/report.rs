@@ -0,0 +1,651 @@
+use crate::db::duckdb::DuckDB;
+use crate::db::filter::QueryFilter;
+use crate::models::{percentage, BrokenLink, DailyAvailability, Dashboard, MonitorSummary, WeeklyReport};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc, Weekday};
+use minijinja::{context, Environment};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// 周报中列出的问题URL数
+pub const TOP_URLS: usize = 50;
+/// 概览页列出的问题URL数
+pub const DASHBOARD_URLS: usize = 500;
+/// 运行汇总中列出的新失败URL数
+pub const RUN_SUMMARY_NEW_FAILURES: usize = 20;
+
+const WEEKLY_TEMPLATE: &str = include_str!("../templates/weekly_report.html");
+const DASHBOARD_TEMPLATE: &str = include_str!("../templates/dashboard.html");
+
+/// ISO 周的起始日（周一），按 UTC 计算
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IsoWeek {
+    monday: NaiveDate,
+}
+
+impl IsoWeek {
+    pub fn containing(date: NaiveDate) -> Self {
+        let week = date.iso_week();
+        Self {
+            monday: NaiveDate::from_isoywd_opt(week.year(), week.week(), Weekday::Mon).expect("valid iso week"),
+        }
+    }
+
+    /// 上一个完整的 ISO 周
+    pub fn previous(now: DateTime<Utc>) -> Self {
+        Self::containing(now.date_naive() - Duration::days(7))
+    }
+
+    /// 解析 `2025-W07` 格式
+    pub fn parse(s: &str) -> Result<Self> {
+        let (year, week) = s.split_once("-W").with_context(|| format!("无效的ISO周: {}", s))?;
+        let year: i32 = year.parse().with_context(|| format!("无效的ISO周: {}", s))?;
+        let week: u32 = week.parse().with_context(|| format!("无效的ISO周: {}", s))?;
+        let monday = NaiveDate::from_isoywd_opt(year, week, Weekday::Mon)
+            .with_context(|| format!("无效的ISO周: {}", s))?;
+        Ok(Self { monday })
+    }
+
+    pub fn label(&self) -> String {
+        let week = self.monday.iso_week();
+        format!("{}-W{:02}", week.year(), week.week())
+    }
+
+    pub fn start(&self) -> DateTime<Utc> {
+        self.monday.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+    }
+
+    pub fn end(&self) -> DateTime<Utc> {
+        self.start() + Duration::days(7)
+    }
+}
+
+/// 从 DuckDB 汇总一周的监测数据
+pub async fn build_weekly_report(duckdb: &DuckDB, week: IsoWeek) -> Result<WeeklyReport> {
+    let (since, until) = (week.start(), week.end());
+    let filter = QueryFilter::range(since, until);
+    let centers = duckdb.get_center_availability(&filter).await?;
+    let total_checks = centers.iter().map(|c| c.total_checks).sum();
+    let success_checks = centers.iter().map(|c| c.success_checks).sum();
+    Ok(WeeklyReport {
+        week: week.label(),
+        period_start: since,
+        period_end: until,
+        generated_at: Utc::now(),
+        total_checks,
+        success_checks,
+        availability: percentage(success_checks, total_checks),
+        centers,
+        daily: duckdb.get_daily_availability(&filter).await?,
+        top_urls: duckdb.get_top_failing_urls(&filter, TOP_URLS).await?,
+        error_categories: duckdb.get_error_category_counts(&filter).await?,
+    })
+}
+
+/// 渲染为独立的 HTML 文件内容（内联 CSS 和 SVG 图表）
+pub fn render_weekly_html(report: &WeeklyReport) -> Result<String> {
+    let mut env = Environment::new();
+    env.add_template("weekly_report.html", WEEKLY_TEMPLATE)?;
+    let html = env.get_template("weekly_report.html")?.render(context! {
+        report => report,
+        period_start => report.period_start.format("%Y-%m-%d").to_string(),
+        period_end => (report.period_end - Duration::days(1)).format("%Y-%m-%d").to_string(),
+        generated_at => report.generated_at.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        chart => availability_chart_svg(&report.daily),
+    })?;
+    Ok(html)
+}
+
+/// 每日可用率柱状图
+pub fn availability_chart_svg(daily: &[DailyAvailability]) -> String {
+    const WIDTH: usize = 560;
+    const HEIGHT: usize = 200;
+    const BAR_AREA: usize = 160;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+        w = WIDTH,
+        h = HEIGHT
+    );
+    if daily.is_empty() {
+        svg.push_str("<text x=\"10\" y=\"20\" font-size=\"12\">无数据</text></svg>");
+        return svg;
+    }
+    let slot = WIDTH / daily.len();
+    for (i, day) in daily.iter().enumerate() {
+        let bar_height = (day.availability / 100.0 * BAR_AREA as f64).round() as usize;
+        let x = i * slot + slot / 4;
+        let color = if day.availability >= 95.0 {
+            "#4caf50"
+        } else if day.availability >= 80.0 {
+            "#ff9800"
+        } else {
+            "#f44336"
+        };
+        let _ = write!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\
+             <text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"middle\">{:.1}%</text>\
+             <text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"middle\">{}</text>",
+            x,
+            BAR_AREA + 10 - bar_height,
+            slot / 2,
+            bar_height,
+            color,
+            x + slot / 4,
+            BAR_AREA + 6 - bar_height,
+            day.availability,
+            x + slot / 4,
+            HEIGHT - 8,
+            day.date.get(5..).unwrap_or(&day.date)
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// 写入 `{dir}/weekly-report-{week}.html`
+pub fn write_weekly_report(report: &WeeklyReport, dir: &str) -> Result<PathBuf> {
+    let html = render_weekly_html(report)?;
+    std::fs::create_dir_all(dir).with_context(|| format!("创建周报目录失败: {}", dir))?;
+    let path = Path::new(dir).join(format!("weekly-report-{}.html", report.week));
+    std::fs::write(&path, html).with_context(|| format!("写入周报失败: {}", path.display()))?;
+    Ok(path)
+}
+
+/// 生成并写入指定 ISO 周的周报
+pub async fn generate_weekly_report(duckdb: &DuckDB, week: IsoWeek, dir: &str) -> Result<PathBuf> {
+    let report = build_weekly_report(duckdb, week).await?;
+    write_weekly_report(&report, dir)
+}
+
+/// 从 DuckDB 汇总最近 `days` 天的概览数据
+pub async fn build_dashboard(duckdb: &DuckDB, days: u32) -> Result<Dashboard> {
+    let until = Utc::now();
+    let filter = QueryFilter::range(until - Duration::days(days as i64), until);
+    let centers = duckdb.get_center_availability(&filter).await?;
+    let total_checks = centers.iter().map(|c| c.total_checks).sum();
+    let success_checks = centers.iter().map(|c| c.success_checks).sum();
+    Ok(Dashboard {
+        days,
+        generated_at: until,
+        total_checks,
+        success_checks,
+        availability: percentage(success_checks, total_checks),
+        centers,
+        daily: duckdb.get_daily_availability(&filter).await?,
+        problematic_urls: duckdb.get_top_failing_urls(&filter, DASHBOARD_URLS).await?,
+        last_run: duckdb.get_recent_runs(1).await?.into_iter().next(),
+    })
+}
+
+/// 渲染为独立的 HTML 文件（内联 CSS、SVG 图表和筛选问题URL的脚本），任何静态文件服务都可以直接提供
+pub fn render_dashboard_html(dashboard: &Dashboard) -> Result<String> {
+    let mut env = Environment::new();
+    env.add_template("dashboard.html", DASHBOARD_TEMPLATE)?;
+    let html = env.get_template("dashboard.html")?.render(context! {
+        dashboard => dashboard,
+        generated_at => dashboard.generated_at.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        last_run_at => dashboard.last_run.as_ref().map(|run| run.finished_at.format("%Y-%m-%d %H:%M UTC").to_string()),
+        chart => availability_chart_svg(&dashboard.daily),
+    })?;
+    Ok(html)
+}
+
+/// 生成概览页并写入 `path`，先写临时文件再重命名，避免静态文件服务读到写了一半的文件
+pub async fn generate_dashboard(duckdb: &DuckDB, days: u32, path: &str) -> Result<()> {
+    let html = render_dashboard_html(&build_dashboard(duckdb, days).await?)?;
+    if let Some(dir) = Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).with_context(|| format!("创建概览页目录失败: {}", dir.display()))?;
+    }
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, html).with_context(|| format!("写入概览页失败: {}", tmp))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("写入概览页失败: {}", path))?;
+    Ok(())
+}
+
+/// 单次监测运行的 Markdown 汇总
+pub fn render_run_markdown(summary: &MonitorSummary) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# 监测运行 {}", summary.run_id);
+    let _ = writeln!(out);
+    let _ = writeln!(out, "- 开始: {}", summary.started_at.format("%Y-%m-%d %H:%M:%S UTC"));
+    let _ = writeln!(out, "- 结束: {}", summary.finished_at.format("%Y-%m-%d %H:%M:%S UTC"));
+    let _ = writeln!(out, "- 耗时: {} 秒", (summary.finished_at - summary.started_at).num_seconds());
+    let _ = writeln!(out, "- 成功: {}/{} ({:.1}%)", summary.success, summary.total,
+                     percentage(summary.success as i64, summary.total as i64));
+    let _ = writeln!(out, "- 本地网络问题: {}，远程问题: {}", summary.local_issues, summary.remote_issues);
+    if let Some(times) = &summary.response_times {
+        let _ = writeln!(out, "- 响应时间: 平均 {:.0} ms，P50 {} ms，P95 {} ms，最大 {} ms",
+                         times.avg_ms, times.p50_ms, times.p95_ms, times.max_ms);
+    }
+
+    let _ = writeln!(out);
+    let _ = writeln!(out, "## 各数据中心");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "| 数据中心 | 总数 | 成功 | 成功率 | 本地问题 | 远程问题 |");
+    let _ = writeln!(out, "|---|---:|---:|---:|---:|---:|");
+    for center in &summary.centers {
+        let _ = writeln!(out, "| {} | {} | {} | {:.1}% | {} | {} |",
+                         escape_markdown_cell(&center.center_name), center.total, center.success,
+                         center.success_rate, center.local_issues, center.remote_issues);
+    }
+
+    if !summary.response_time_regressions.is_empty() {
+        let _ = writeln!(out);
+        let _ = writeln!(out, "## 响应时间变慢");
+        let _ = writeln!(out);
+        let _ = writeln!(out, "| 数据中心 | 中位数 | 基线 | 倍数 |");
+        let _ = writeln!(out, "|---|---:|---:|---:|");
+        for r in &summary.response_time_regressions {
+            let _ = writeln!(out, "| {} | {} ms | {} ms | {:.1} |",
+                             escape_markdown_cell(&r.center_name), r.median_ms, r.baseline_ms, r.ratio);
+        }
+    }
+
+    let mut categories: Vec<(&str, usize)> = Vec::new();
+    for category in summary.centers.iter().flat_map(|c| &c.error_categories) {
+        match categories.iter_mut().find(|(name, _)| *name == category.category) {
+            Some((_, count)) => *count += category.count,
+            None => categories.push((&category.category, category.count)),
+        }
+    }
+    categories.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    if !categories.is_empty() {
+        let _ = writeln!(out);
+        let _ = writeln!(out, "## 错误类别");
+        let _ = writeln!(out);
+        let _ = writeln!(out, "| 错误类别 | 数量 |");
+        let _ = writeln!(out, "|---|---:|");
+        for (category, count) in &categories {
+            let _ = writeln!(out, "| {} | {} |", escape_markdown_cell(category), count);
+        }
+    }
+
+    if summary.new_failure_count > 0 {
+        let _ = writeln!(out);
+        let _ = writeln!(out, "## 新失败的URL ({})", summary.new_failure_count);
+        let _ = writeln!(out);
+        for failure in summary.new_failures.iter().take(RUN_SUMMARY_NEW_FAILURES) {
+            let _ = writeln!(out, "- [{}] [{}](<{}>) {}",
+                             failure.center_name,
+                             failure.name.as_deref().unwrap_or(&failure.url),
+                             failure.url,
+                             failure.error_category.as_deref().unwrap_or("-"));
+        }
+        if summary.new_failure_count > RUN_SUMMARY_NEW_FAILURES {
+            let _ = writeln!(out, "- ... 另有 {} 条", summary.new_failure_count - RUN_SUMMARY_NEW_FAILURES);
+        }
+    }
+    out
+}
+
+fn escape_markdown_cell(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+/// 写入 `{dir}/run-<时间>-<run_id>.md`，并只保留最近 `keep` 个文件
+pub fn write_run_summary(summary: &MonitorSummary, dir: &str, keep: usize) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("创建运行汇总目录失败: {}", dir))?;
+    let path = Path::new(dir).join(format!(
+        "run-{}-{}.md",
+        summary.finished_at.format("%Y%m%dT%H%M%SZ"),
+        summary.run_id
+    ));
+    std::fs::write(&path, render_run_markdown(summary))
+        .with_context(|| format!("写入运行汇总失败: {}", path.display()))?;
+    prune_run_summaries(dir, keep)?;
+    Ok(path)
+}
+
+/// 链接报告的列，与 W3C link checker 导出的列对应
+const LINK_REPORT_HEADER: [&str; 6] = ["urlname", "parentname", "result", "code", "reason", "checktime"];
+
+/// 数据中心最近一次监测运行中检查失败的URL，`run` 为 None 时从最近的运行汇总中查找；
+/// 之后附上记录的无法检查的URL（格式错误、内网地址等），result 列为问题类型
+pub async fn build_link_report(duckdb: &DuckDB, center_name: &str, run: Option<&MonitorSummary>) -> Result<Vec<BrokenLink>> {
+    let recent_runs;
+    let run = match run {
+        Some(run) => run,
+        None => {
+            recent_runs = duckdb.get_recent_runs(50).await?;
+            recent_runs.iter()
+                .find(|r| r.center(center_name).is_some_and(|c| c.total > 0))
+                .with_context(|| format!("最近的运行中没有数据中心 {}", center_name))?
+        }
+    };
+    // 共用URL归属其他数据中心时，链接仍在本数据中心的目录中，同样列出
+    let filter = QueryFilter::range(run.started_at, run.finished_at).center(center_name).ignore_attribution();
+    let mut links = duckdb.get_broken_links(&filter).await?;
+    links.extend(duckdb.get_url_quality_issues(Some(center_name)).await?.into_iter().map(|issue| BrokenLink {
+        url: issue.url,
+        name: issue.name,
+        status_code: None,
+        status_text: None,
+        error_category: Some(issue.reason.as_str().to_string()),
+        error_msg: Some(issue.reason.description().to_string()),
+        check_time: issue.detected_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+    }));
+    Ok(links)
+}
+
+/// 渲染为 CSV（`delimiter` 为 `b','`）或 TSV（`b'\t'`），名称和错误信息中的制表符、换行替换为空格
+pub fn render_link_report(links: &[BrokenLink], delimiter: u8) -> Result<String> {
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(Vec::new());
+    writer.write_record(LINK_REPORT_HEADER)?;
+    for link in links {
+        let result = match (link.status_code, &link.error_category) {
+            (Some(code), _) => format!("{} {}", code, link.status_text.as_deref().unwrap_or_default()).trim_end().to_string(),
+            (None, Some(category)) => category.clone(),
+            (None, None) => "error".to_string(),
+        };
+        let reason = link.error_msg.as_deref().or(link.status_text.as_deref()).unwrap_or_default();
+        writer.write_record([
+            sanitize_field(&link.url),
+            sanitize_field(link.name.as_deref().unwrap_or_default()),
+            sanitize_field(&result),
+            link.status_code.map(|code| code.to_string()).unwrap_or_default(),
+            sanitize_field(reason),
+            link.check_time.clone(),
+        ])?;
+    }
+    let bytes = writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e.error()))?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn sanitize_field(s: &str) -> String {
+    s.split(|c: char| c.is_control()).filter(|part| !part.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+/// 按文件扩展名选择分隔符：.csv 为逗号，其他为制表符
+pub fn link_report_delimiter(path: &Path) -> u8 {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("csv") => b',',
+        _ => b'\t',
+    }
+}
+
+/// 生成并写入链接报告，返回报告中的URL数
+pub async fn generate_link_report(duckdb: &DuckDB, center_name: &str, run: Option<&MonitorSummary>, path: &Path) -> Result<usize> {
+    let links = build_link_report(duckdb, center_name, run).await?;
+    let content = render_link_report(&links, link_report_delimiter(path))?;
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).with_context(|| format!("创建链接报告目录失败: {}", dir.display()))?;
+    }
+    std::fs::write(path, content).with_context(|| format!("写入链接报告失败: {}", path.display()))?;
+    Ok(links.len())
+}
+
+/// 为本次运行的每个数据中心写入 `{dir}/broken-links-{数据中心}.tsv`，覆盖上一次的文件
+pub async fn write_link_reports(duckdb: &DuckDB, summary: &MonitorSummary, dir: &str) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for center in summary.centers.iter().filter(|c| c.total > 0) {
+        let file_name = center.center_name.replace(|c: char| !c.is_alphanumeric() && c != '-' && c != '_', "_");
+        let path = Path::new(dir).join(format!("broken-links-{}.tsv", file_name));
+        let count = generate_link_report(duckdb, &center.center_name, Some(summary), &path).await?;
+        info!("数据中心 {} 的链接报告已写入: {}（{} 条）", center.center_name, path.display(), count);
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// 删除较旧的运行汇总，文件名以时间开头，按名称排序即按时间排序
+fn prune_run_summaries(dir: &str, keep: usize) -> Result<()> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("run-") && name.ends_with(".md"))
+        })
+        .collect();
+    if files.len() <= keep {
+        return Ok(());
+    }
+    files.sort();
+    for path in &files[..files.len() - keep] {
+        std::fs::remove_file(path).with_context(|| format!("删除旧的运行汇总失败: {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MonitorRecord;
+
+    /// 一小时前的检查记录，名称带 HTML 标签用于检查转义
+    fn record(id: &str, center: &str, url: &str, status_code: u16) -> MonitorRecord {
+        MonitorRecord {
+            url: url.to_string(),
+            name: Some(format!("<b>{}</b>", id)),
+            check_time: Utc::now() - Duration::hours(1),
+            error_msg: (status_code >= 400).then(|| format!("HTTP {}", status_code)),
+            ..crate::test_support::record(id, center, Some(status_code), (status_code >= 400).then_some("HTTP_5XX"))
+        }
+    }
+
+    #[tokio::test]
+    async fn dashboard_lists_centers_and_problematic_urls() {
+        let db = DuckDB::new(":memory:").await.unwrap();
+        db.insert_records(&[
+            record("1", "A", "https://a.casdc.cn/1", 200),
+            record("2", "A", "https://a.casdc.cn/2", 200),
+            record("3", "B", "https://b.casdc.cn/3", 200),
+            record("4", "B", "https://b.casdc.cn/4", 503),
+        ]).await.unwrap();
+        let dashboard = build_dashboard(&db, 7).await.unwrap();
+        assert_eq!((dashboard.total_checks, dashboard.success_checks, dashboard.availability), (4, 3, 75.0));
+        let centers: Vec<_> = dashboard.centers.iter().map(|c| (c.center_name.as_str(), c.availability)).collect();
+        assert_eq!(centers, [("A", 100.0), ("B", 50.0)]);
+        assert_eq!(dashboard.daily.len(), 1);
+        let urls: Vec<_> = dashboard.problematic_urls.iter().map(|u| u.url.as_str()).collect();
+        assert_eq!(urls, ["https://b.casdc.cn/4"]);
+
+        let html = render_dashboard_html(&dashboard).unwrap();
+        assert!(html.contains(r#"<tr data-center="B" data-rate="100.0">"#), "{}", html);
+        assert!(html.contains("HTTP 503"));
+        // 数据集名称按 HTML 转义
+        assert!(html.contains("&lt;b&gt;4&lt;&#x2f;b&gt;") && !html.contains("<b>4</b>"), "{}", html);
+        assert!(html.contains("<svg") && html.contains("<rect"));
+    }
+
+    #[tokio::test]
+    async fn empty_dashboard_is_written_in_place() {
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let html = render_dashboard_html(&build_dashboard(&db, 7).await.unwrap()).unwrap();
+        assert!(html.contains("没有监测数据") && html.contains("无数据"));
+        assert!(!html.contains("problematic-urls"));
+
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-dashboard-{}", std::process::id()));
+        let path = dir.join("site/index.html");
+        generate_dashboard(&db, 7, path.to_str().unwrap()).await.unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().contains("没有监测数据"));
+        assert!(!dir.join("site/index.html.tmp").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// 2026-W02 周内固定的检查记录：A 全部成功，B 一个URL持续失败、一个URL偶尔超时；另有一条周外记录
+    fn weekly_records() -> Vec<MonitorRecord> {
+        let at = |day: u32, hour: u32| NaiveDate::from_ymd_opt(2026, 1, day).unwrap().and_hms_opt(hour, 0, 0).unwrap().and_utc();
+        let check = |id: &str, center: &str, status_code: u16, day: u32, hour: u32, response_ms: u64| MonitorRecord {
+            id: format!("{}-{}-{}", id, day, hour),
+            check_time: at(day, hour),
+            response_time_ms: Some(response_ms),
+            ..record(id, center, &format!("https://{}.casdc.cn/{}", center.to_lowercase(), id), status_code)
+        };
+        let mut records = Vec::new();
+        for day in [5, 6, 7] {
+            records.push(check("a1", "A", 200, day, 2, 120));
+            records.push(check("a2", "A", 200, day, 2, 80));
+            records.push(check("b1", "B", 503, day, 3, 1500));
+            records.push(check("b2", "B", if day == 6 { 504 } else { 200 }, day, 3, 300));
+        }
+        records.push(check("b1", "B", 200, 12, 0, 100));
+        records
+    }
+
+    #[tokio::test]
+    async fn weekly_report_matches_golden_file() {
+        let db = DuckDB::new(":memory:").await.unwrap();
+        db.insert_records(&weekly_records()).await.unwrap();
+        let mut report = build_weekly_report(&db, IsoWeek::parse("2026-W02").unwrap()).await.unwrap();
+        report.generated_at = NaiveDate::from_ymd_opt(2026, 1, 12).unwrap().and_hms_opt(8, 0, 0).unwrap().and_utc();
+        assert_eq!((report.total_checks, report.success_checks), (12, 8));
+        assert_golden("weekly-report-2026-W02.html", &render_weekly_html(&report).unwrap());
+    }
+
+    fn run_summary(run_id: &str, finished_at: &str) -> MonitorSummary {
+        let center = |name: &str, total: usize, success: usize, categories: serde_json::Value| serde_json::json!({
+            "center_name": name,
+            "total": total,
+            "success": success,
+            "success_rate": percentage(success as i64, total as i64),
+            "local_issues": 0,
+            "remote_issues": total - success,
+            "error_categories": categories,
+            "sample_failures": [],
+        });
+        let failure = |id: &str, name: Option<&str>| serde_json::json!({
+            "id": id,
+            "url": format!("https://b.casdc.cn/{}", id),
+            "name": name,
+            "center_name": "B|C",
+            "status_code": 404,
+            "error_category": "HTTP_4XX",
+            "error_msg": "HTTP 404",
+            "is_likely_local_issue": false,
+        });
+        serde_json::from_value(serde_json::json!({
+            "run_id": run_id,
+            "started_at": "2026-01-05T02:00:00Z",
+            "finished_at": finished_at,
+            "total": 30,
+            "success": 24,
+            "local_issues": 1,
+            "remote_issues": 5,
+            "centers": [
+                center("A", 10, 9, serde_json::json!([{ "category": "TIMEOUT", "count": 1 }])),
+                center("B|C", 20, 15, serde_json::json!([
+                    { "category": "HTTP_4XX", "count": 4 },
+                    { "category": "TIMEOUT", "count": 1 },
+                ])),
+            ],
+            "new_failure_count": 22,
+            "new_failures": [failure("x1", Some("数据集一")), failure("x2", None)],
+            "response_times": { "avg_ms": 412.6, "p50_ms": 300, "p95_ms": 1800, "max_ms": 5000 },
+            "response_time_regressions": [
+                { "center_name": "A", "median_ms": 900, "baseline_ms": 300, "ratio": 3.0, "baseline_runs": 5 },
+            ],
+        })).unwrap()
+    }
+
+    #[test]
+    fn run_markdown_snapshot() {
+        let expected = "\
+# 监测运行 run-1
+
+- 开始: 2026-01-05 02:00:00 UTC
+- 结束: 2026-01-05 02:10:30 UTC
+- 耗时: 630 秒
+- 成功: 24/30 (80.0%)
+- 本地网络问题: 1，远程问题: 5
+- 响应时间: 平均 413 ms，P50 300 ms，P95 1800 ms，最大 5000 ms
+
+## 各数据中心
+
+| 数据中心 | 总数 | 成功 | 成功率 | 本地问题 | 远程问题 |
+|---|---:|---:|---:|---:|---:|
+| A | 10 | 9 | 90.0% | 0 | 1 |
+| B\\|C | 20 | 15 | 75.0% | 0 | 5 |
+
+## 响应时间变慢
+
+| 数据中心 | 中位数 | 基线 | 倍数 |
+|---|---:|---:|---:|
+| A | 900 ms | 300 ms | 3.0 |
+
+## 错误类别
+
+| 错误类别 | 数量 |
+|---|---:|
+| HTTP_4XX | 4 |
+| TIMEOUT | 2 |
+
+## 新失败的URL (22)
+
+- [B|C] [数据集一](<https://b.casdc.cn/x1>) HTTP_4XX
+- [B|C] [https://b.casdc.cn/x2](<https://b.casdc.cn/x2>) HTTP_4XX
+- ... 另有 2 条
+";
+        assert_eq!(render_run_markdown(&run_summary("run-1", "2026-01-05T02:10:30Z")), expected);
+    }
+
+    #[test]
+    fn run_summaries_keep_the_most_recent_files() {
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-run-summaries-{}", std::process::id()));
+        let dir_str = dir.to_str().unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes.md"), "").unwrap();
+        for (run_id, finished_at) in [("r1", "2026-01-05T03:00:00Z"), ("r2", "2026-01-06T03:00:00Z"), ("r3", "2026-01-07T03:00:00Z")] {
+            write_run_summary(&run_summary(run_id, finished_at), dir_str, 2).unwrap();
+        }
+        let mut names: Vec<_> = std::fs::read_dir(&dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        // 其他文件不受影响
+        assert_eq!(names, ["notes.md", "run-20260106T030000Z-r2.md", "run-20260107T030000Z-r3.md"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// 与 testdata 中的文件比较，设置 UPDATE_GOLDEN=1 时重新生成
+    fn assert_golden(name: &str, actual: &str) {
+        let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata").join(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&golden, actual).unwrap();
+        }
+        assert_eq!(actual, std::fs::read_to_string(&golden).unwrap());
+    }
+
+    #[tokio::test]
+    async fn link_reports_match_golden_files() {
+        let at = |minute: u32| NaiveDate::from_ymd_opt(2026, 1, 5).unwrap().and_hms_opt(2, minute, 0).unwrap().and_utc();
+        let check = |id: &str, center: &str, url: &str, status_code: Option<u16>, minute: u32| MonitorRecord {
+            check_time: at(minute),
+            status_code,
+            status_text: status_code.map(|code| if code == 404 { "Not Found" } else { "OK" }.to_string()),
+            ..record(id, center, url, status_code.unwrap_or(0))
+        };
+        let mut timeout = check("t", "A", "https://a.casdc.cn/timeout", None, 3);
+        timeout.name = Some("名称\t带制表符\n和换行".to_string());
+        timeout.error_category = Some("TIMEOUT".to_string());
+        timeout.error_msg = Some("请求超时\r\n(30s)".to_string());
+        let db = DuckDB::new(":memory:").await.unwrap();
+        db.insert_records(&[
+            check("ok", "A", "https://a.casdc.cn/ok", Some(200), 1),
+            check("gone", "A", "https://a.casdc.cn/gone?x=1,2", Some(404), 2),
+            timeout,
+            // 其他数据中心和运行之外的检查不列出
+            check("b", "B", "https://b.casdc.cn/gone", Some(404), 4),
+            check("late", "A", "https://a.casdc.cn/late", Some(404), 30),
+        ]).await.unwrap();
+        let run: MonitorSummary = serde_json::from_value(serde_json::json!({
+            "run_id": "r1",
+            "started_at": "2026-01-05T02:00:00Z",
+            "finished_at": "2026-01-05T02:10:00Z",
+            "total": 4, "success": 1, "local_issues": 0, "remote_issues": 3,
+            "centers": [],
+        })).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-link-report-{}", std::process::id()));
+        let tsv = dir.join("reports/broken-links-A.tsv");
+        assert_eq!(generate_link_report(&db, "A", Some(&run), &tsv).await.unwrap(), 2);
+        assert_golden("broken-links-A.tsv", &std::fs::read_to_string(&tsv).unwrap());
+        let csv = dir.join("broken-links-A.csv");
+        generate_link_report(&db, "A", Some(&run), &csv).await.unwrap();
+        assert_golden("broken-links-A.csv", &std::fs::read_to_string(&csv).unwrap());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
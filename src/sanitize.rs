@@ -0,0 +1,272 @@
+//! 保存前限制错误信息和响应头的大小并隐藏响应头中的凭证，以及压缩旧记录中过长的文本
+
+use crate::config::MonitorConfig;
+use crate::db::duckdb::DuckDB;
+use crate::models::StoredText;
+use anyhow::Result;
+use regex::Regex;
+use serde::Serialize;
+use std::sync::LazyLock;
+use tracing::info;
+
+/// 每批读取和写回的记录数
+const COMPACT_BATCH_SIZE: usize = 5000;
+/// 为截断标记预留的字节数，截断后的总长度不超过上限，再次压缩时不会重复截断
+const MARKER_RESERVE: usize = 48;
+
+/// 值需要隐藏的响应头，与响应头保存格式 `name: "value"` 匹配；DuckDB 筛选候选记录时使用同一个表达式
+pub const SENSITIVE_HEADER_PATTERN: &str = r#"(?i)\b(authorization|proxy-authorization|cookie|set-cookie|x-api-key|x-auth-token): "(?:[^"\\]|\\.)*""#;
+
+static SENSITIVE_HEADER: LazyLock<Regex> = LazyLock::new(|| Regex::new(SENSITIVE_HEADER_PATTERN).expect("invalid header pattern"));
+
+/// 保存的错误信息、错误详情和响应头的字节数上限
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct TextLimits {
+    pub error_msg: usize,
+    pub error_detail: usize,
+    pub headers: usize,
+}
+
+impl TextLimits {
+    pub fn from_config(config: &MonitorConfig) -> Self {
+        Self {
+            error_msg: config.max_error_msg_bytes,
+            error_detail: config.max_error_detail_bytes,
+            headers: config.max_header_bytes,
+        }
+    }
+
+    pub fn error_msg(&self, msg: String) -> String {
+        truncate_middle(msg, self.error_msg)
+    }
+
+    pub fn error_detail(&self, detail: String) -> String {
+        truncate_middle(detail, self.error_detail)
+    }
+
+    pub fn headers(&self, headers: &str) -> String {
+        truncate_end(redact_headers(headers), self.headers)
+    }
+}
+
+/// 超过 `max_bytes` 时截断末尾，并注明原长度
+pub fn truncate_end(mut s: String, max_bytes: usize) -> String {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let len = s.len();
+    s.truncate(floor_char_boundary(&s, max_bytes.saturating_sub(MARKER_RESERVE)));
+    s.push_str(&format!("...(已截断，共 {} 字节)", len));
+    s
+}
+
+/// 超过 `max_bytes` 时保留开头和结尾各一半，省略中间部分并注明省略的字节数。
+/// 错误详情末尾是错误特征（是否超时等），重新分类时需要解析，因此不截断末尾
+pub fn truncate_middle(s: String, max_bytes: usize) -> String {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let keep = max_bytes.saturating_sub(MARKER_RESERVE);
+    let head = floor_char_boundary(&s, keep / 2);
+    let mut tail = s.len() - (keep - keep / 2);
+    while !s.is_char_boundary(tail) {
+        tail += 1;
+    }
+    format!("{}\n...(省略 {} 字节)...\n{}", &s[..head], tail - head, &s[tail..])
+}
+
+fn floor_char_boundary(s: &str, mut index: usize) -> usize {
+    while !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// 把凭证类响应头（Authorization、Set-Cookie 等）的值替换为 "[已隐藏]"
+pub fn redact_headers(headers: &str) -> String {
+    SENSITIVE_HEADER.replace_all(headers, r#"$1: "[已隐藏]""#).into_owned()
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CompactReport {
+    pub dry_run: bool,
+    pub limits: Option<TextLimits>,
+    /// 超出上限或含凭证的记录数
+    pub scanned: usize,
+    /// 截断或隐藏后有变化的记录数
+    pub changed: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub bytes_saved: u64,
+}
+
+/// 按 `limits` 截断已有记录中过长的错误信息和响应头，并隐藏响应头中的凭证；`dry_run` 时只统计不写回。
+/// 写回后执行 CHECKPOINT，数据库文件中释放的空间会被之后写入的数据复用
+pub async fn compact(duckdb: &DuckDB, limits: TextLimits, dry_run: bool) -> Result<CompactReport> {
+    let mut report = CompactReport { dry_run, limits: Some(limits), ..CompactReport::default() };
+    let mut after_row_id = -1;
+    loop {
+        let batch = duckdb.get_oversized_text_batch(&limits, after_row_id, COMPACT_BATCH_SIZE).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        after_row_id = last.row_id;
+        report.scanned += batch.len();
+        let mut changes = Vec::new();
+        for row in batch {
+            let compacted = StoredText {
+                row_id: row.row_id,
+                error_msg: row.error_msg.clone().map(|msg| limits.error_msg(msg)),
+                error_detail: row.error_detail.clone().map(|detail| limits.error_detail(detail)),
+                headers: row.headers.as_deref().map(|headers| limits.headers(headers)),
+            };
+            if compacted == row {
+                continue;
+            }
+            report.bytes_before += row.bytes() as u64;
+            report.bytes_after += compacted.bytes() as u64;
+            changes.push(compacted);
+        }
+        report.changed += changes.len();
+        if !dry_run {
+            duckdb.update_texts(&changes).await?;
+        }
+        info!("压缩检查记录: 已检查 {} 条，{} 条变化", report.scanned, report.changed);
+    }
+    report.bytes_saved = report.bytes_before.saturating_sub(report.bytes_after);
+    if !dry_run && report.changed > 0 {
+        duckdb.checkpoint().await?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MonitorRecord;
+
+    const LIMITS: TextLimits = TextLimits { error_msg: 100, error_detail: 200, headers: 120 };
+
+    #[test]
+    fn truncate_end_marks_original_length() {
+        assert_eq!(truncate_end("short".to_string(), 100), "short");
+        let exact = "a".repeat(100);
+        assert_eq!(truncate_end(exact.clone(), 100), exact);
+
+        let truncated = truncate_end("a".repeat(1000), 100);
+        assert_eq!(truncated, format!("{}...(已截断，共 1000 字节)", "a".repeat(100 - MARKER_RESERVE)));
+        assert!(truncated.len() <= 100);
+        // 截断后不超过上限，再次截断不变
+        assert_eq!(truncate_end(truncated.clone(), 100), truncated);
+    }
+
+    #[test]
+    fn truncate_end_respects_char_boundaries() {
+        // 每个汉字 3 字节
+        let text = "数据集".repeat(100);
+        for max_bytes in MARKER_RESERVE..MARKER_RESERVE + 8 {
+            let truncated = truncate_end(text.clone(), max_bytes);
+            assert!(truncated.len() <= max_bytes, "{}: {}", max_bytes, truncated);
+            assert!(truncated.ends_with("...(已截断，共 900 字节)"));
+            let kept = truncated.trim_end_matches("...(已截断，共 900 字节)");
+            assert!(text.starts_with(kept));
+            assert_eq!(kept.len() % 3, 0);
+        }
+        // 保留的 52 字节落在字符中间，退到 51 字节
+        let truncated = truncate_end(text.clone(), 100);
+        assert_eq!(truncated, format!("{}数据...(已截断，共 900 字节)", "数据集".repeat(5)));
+        // 最大的 u64 长度也放得进预留的字节数
+        assert!(format!("...(已截断，共 {} 字节)", u64::MAX).len() <= MARKER_RESERVE);
+    }
+
+    #[test]
+    fn truncate_middle_keeps_head_and_tail() {
+        assert_eq!(truncate_middle("short".to_string(), 100), "short");
+        let detail = format!("{}{}{}", "开头", "x".repeat(1000), "operation timed out");
+        let truncated = truncate_middle(detail.clone(), 100);
+        assert!(truncated.len() <= 100, "{}", truncated);
+        assert!(truncated.starts_with("开头"));
+        // 末尾的错误特征保留，重新分类时仍能识别
+        assert!(truncated.ends_with("operation timed out"));
+        let (head, rest) = truncated.split_once("\n...(省略 ").unwrap();
+        let (omitted, tail) = rest.split_once(" 字节)...\n").unwrap();
+        assert_eq!(head.len() + omitted.parse::<usize>().unwrap() + tail.len(), detail.len());
+        assert_eq!(truncate_middle(truncated.clone(), 100), truncated);
+
+        // 两端都是多字节字符时按字符边界截断
+        let text = "错".repeat(200);
+        for max_bytes in 60..70 {
+            let truncated = truncate_middle(text.clone(), max_bytes);
+            assert!(truncated.len() <= max_bytes);
+            let (head, rest) = truncated.split_once("\n...(省略 ").unwrap();
+            let (_, tail) = rest.split_once(" 字节)...\n").unwrap();
+            assert!(head.chars().all(|c| c == '错') && tail.chars().all(|c| c == '错'));
+        }
+    }
+
+    #[test]
+    fn redacts_each_sensitive_header() {
+        for name in ["authorization", "Proxy-Authorization", "cookie", "Set-Cookie", "X-API-Key", "x-auth-token"] {
+            let headers = format!(r#"content-type: "text/html", {}: "secret value", server: "nginx""#, name);
+            assert_eq!(
+                redact_headers(&headers),
+                format!(r#"content-type: "text/html", {}: "[已隐藏]", server: "nginx""#, name),
+            );
+        }
+        // 值中转义的引号不会提前结束匹配，同一响应头出现多次时都隐藏
+        assert_eq!(
+            redact_headers(r#"set-cookie: "a=\"b\"; Path=/", set-cookie: "c=d""#),
+            r#"set-cookie: "[已隐藏]", set-cookie: "[已隐藏]""#,
+        );
+        // 名称相近的响应头不隐藏
+        let harmless = r#"cookie-policy: "strict", x-api-key-id: "42", www-authenticate: "Basic""#;
+        assert_eq!(redact_headers(harmless), harmless);
+        assert_eq!(redact_headers(""), "");
+    }
+
+    #[test]
+    fn limits_redact_before_truncating() {
+        let headers = format!(r#"authorization: "Bearer {}", server: "nginx""#, "t".repeat(500));
+        let limited = LIMITS.headers(&headers);
+        assert_eq!(limited, r#"authorization: "[已隐藏]", server: "nginx""#);
+        let long = format!(r#"x-auth-token: "abc", x-padding: "{}""#, "p".repeat(500));
+        let limited = LIMITS.headers(&long);
+        assert!(limited.starts_with(r#"x-auth-token: "[已隐藏]", x-padding: "ppp"#));
+        assert!(limited.len() <= LIMITS.headers && !limited.contains("abc"));
+        assert!(LIMITS.error_msg("e".repeat(500)).len() <= LIMITS.error_msg);
+        assert!(LIMITS.error_detail("d".repeat(500)).len() <= LIMITS.error_detail);
+    }
+
+    #[tokio::test]
+    async fn compact_truncates_and_redacts_stored_records() {
+        let duckdb = DuckDB::new(":memory:").await.unwrap();
+        let record = |id: &str, error_msg: String, headers: &str| MonitorRecord {
+            id: id.to_string(),
+            raw_id: Some(id.to_string()),
+            url: format!("https://a.example.org/{}", id),
+            center_name: "A".to_string(),
+            status_code: Some(500),
+            error_msg: Some(error_msg),
+            headers: Some(headers.to_string()),
+            ..MonitorRecord::default()
+        };
+        duckdb.insert_records(&[
+            record("1", "e".repeat(500), r#"server: "nginx""#),
+            record("2", "ok".to_string(), r#"Set-Cookie: "session=abc""#),
+            record("3", "ok".to_string(), r#"server: "nginx""#),
+        ]).await.unwrap();
+
+        let report = compact(&duckdb, LIMITS, true).await.unwrap();
+        assert_eq!((report.scanned, report.changed), (2, 2));
+        assert!(report.bytes_saved > 0);
+        // dry run 不写回
+        assert_eq!(compact(&duckdb, LIMITS, true).await.unwrap().changed, 2);
+
+        compact(&duckdb, LIMITS, false).await.unwrap();
+        let remaining = duckdb.get_oversized_text_batch(&LIMITS, -1, 10).await.unwrap();
+        // 隐藏后的响应头仍与模式匹配，但不再有变化
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].headers.as_deref(), Some(r#"Set-Cookie: "[已隐藏]""#));
+        assert_eq!(compact(&duckdb, LIMITS, false).await.unwrap().changed, 0);
+    }
+}
@@ -0,0 +1,782 @@
+use crate::alert::Alerter;
+use crate::config::{Center, Config, MonitorConfig};
+use crate::db::mongodb::MongoDB;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use croner::Cron;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::hash::{BuildHasher, RandomState};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::{task_tracker::TaskTrackerToken, AbortOnDropHandle, TaskTracker};
+use tracing::{error, info, warn};
+
+/// 任务触发结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerOutcome {
+    /// 本次触发已执行
+    Ran,
+    /// 上一次运行尚未结束，本次触发已排队，将在其结束后执行
+    Queued,
+    /// 上一次运行尚未结束，本次触发被跳过
+    Skipped,
+}
+
+/// 任务运行守卫，防止同一任务的多次触发重叠执行
+///
+/// 定时触发与手动触发必须共用同一个守卫（`Arc<JobGuard>`），否则无法互斥。
+pub struct JobGuard {
+    name: String,
+    queue_one: bool,
+    running: AtomicBool,
+    queued: AtomicBool,
+    skipped: AtomicU64,
+}
+
+impl JobGuard {
+    /// `queue_one` 为 true 时，运行期间的一次触发会排队，而不是直接跳过
+    pub fn new(name: &str, queue_one: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            queue_one,
+            running: AtomicBool::new(false),
+            queued: AtomicBool::new(false),
+            skipped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
+
+    /// 因重叠而被跳过的触发次数
+    pub fn skipped(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    pub async fn run<F, Fut>(&self, mut job: F) -> TriggerOutcome
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        if self.running.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            if self.queue_one && !self.queued.swap(true, Ordering::AcqRel) {
+                info!("任务 {} 正在运行，本次触发已排队", self.name);
+                return TriggerOutcome::Queued;
+            }
+            let skipped = self.skipped.fetch_add(1, Ordering::Relaxed) + 1;
+            warn!("任务 {} 上一次运行尚未结束，跳过本次触发 (累计跳过 {} 次)", self.name, skipped);
+            return TriggerOutcome::Skipped;
+        }
+
+        // 运行结束前若有排队的触发，则紧接着再执行一次
+        let mut running = RunningFlag(&self.running);
+        loop {
+            job().await;
+            if self.queued.swap(false, Ordering::AcqRel) {
+                info!("任务 {} 执行排队的触发", self.name);
+                continue;
+            }
+            drop(running);
+            // 释放后再确认一次，避免在释放瞬间排队的触发丢失
+            if !self.queued.load(Ordering::Acquire)
+                || self.running.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_err()
+            {
+                break;
+            }
+            running = RunningFlag(&self.running);
+            self.queued.store(false, Ordering::Release);
+            info!("任务 {} 执行排队的触发", self.name);
+        }
+        TriggerOutcome::Ran
+    }
+}
+
+/// 持有期间任务处于运行中，释放时清除运行标记；任务 panic 或被取消时同样清除，
+/// 否则之后的触发都会被当作重叠而跳过
+struct RunningFlag<'a>(&'a AtomicBool);
+
+impl Drop for RunningFlag<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JobStateFile {
+    last_success: Option<DateTime<Utc>>,
+    /// 上一次成功运行开始前的随机延迟秒数，手动触发和补跑为 0
+    #[serde(default)]
+    last_jitter_secs: u64,
+}
+
+/// 任务运行状态，持久化在 `{state_dir}/{job}.json` 中
+///
+/// 每个任务独立一个文件，data_fetch 与 data_monitor 两个进程互不干扰。
+pub struct JobState {
+    name: String,
+    path: PathBuf,
+    /// 本次运行开始前的随机延迟，在记录成功时一并写入并清零
+    jitter_secs: AtomicU64,
+}
+
+impl JobState {
+    pub fn new(state_dir: &str, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            path: PathBuf::from(state_dir).join(format!("{}.json", name)),
+            jitter_secs: AtomicU64::new(0),
+        }
+    }
+
+    fn read(&self) -> Option<JobStateFile> {
+        let content = std::fs::read_to_string(&self.path).ok()?;
+        match serde_json::from_str::<JobStateFile>(&content) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                warn!("任务 {} 状态文件 {} 解析失败: {}", self.name, self.path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// 上一次成功运行的时间，状态文件不存在或损坏时返回 None
+    pub fn last_success(&self) -> Option<DateTime<Utc>> {
+        self.read()?.last_success
+    }
+
+    /// 上一次成功运行开始前的随机延迟秒数
+    pub fn last_jitter_secs(&self) -> Option<u64> {
+        self.read().map(|state| state.last_jitter_secs)
+    }
+
+    /// 记录定时触发后实际的随机延迟，下一次 `record_success` 时写入状态文件
+    pub fn set_jitter(&self, secs: u64) {
+        self.jitter_secs.store(secs, Ordering::Relaxed);
+    }
+
+    pub fn record_success(&self, at: DateTime<Utc>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let state = JobStateFile {
+            last_success: Some(at),
+            last_jitter_secs: self.jitter_secs.swap(0, Ordering::Relaxed),
+        };
+        // 先写临时文件再改名，避免进程中断留下半个文件
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&state)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// 启动时判断是否需要补跑错过的调度
+    pub fn is_overdue(&self, interval: Duration, grace: Duration) -> bool {
+        let last = self.last_success();
+        let overdue = is_overdue(last, Utc::now(), interval, grace);
+        match last {
+            Some(last) if overdue => info!("任务 {} 上次成功运行于 {}，已超期，立即补跑", self.name, last),
+            Some(last) => info!("任务 {} 上次成功运行于 {}，未超期，等待下一次调度", self.name, last),
+            None => info!("任务 {} 没有成功运行记录，立即执行", self.name),
+        }
+        overdue
+    }
+}
+
+/// 距上次成功运行超过 `interval + grace` 即视为超期；从未成功运行过也视为超期
+pub fn is_overdue(last_success: Option<DateTime<Utc>>, now: DateTime<Utc>, interval: Duration, grace: Duration) -> bool {
+    match last_success {
+        Some(last) => now - last > interval + grace,
+        None => true,
+    }
+}
+
+/// 打印任务下一次触发时间（配置时区与 UTC 各一份）
+pub fn log_next_fire(name: &str, next: Option<DateTime<Utc>>, tz: Tz) {
+    match next {
+        Some(next) => info!(
+            "任务 {} 下一次触发时间: {} ({}) / {} (UTC)",
+            name,
+            next.with_timezone(&tz).format("%Y-%m-%d %H:%M:%S %Z"),
+            tz.name(),
+            next.format("%Y-%m-%d %H:%M:%S")
+        ),
+        None => warn!("任务 {} 无法计算下一次触发时间", name),
+    }
+}
+
+/// 单个数据中心的调度配置
+#[derive(Debug, Clone)]
+pub struct CenterSchedule {
+    pub center: Center,
+    pub cron: String,
+    /// 调度间隔，用于启动时判断是否需要补跑
+    pub interval: Duration,
+}
+
+/// 每个启用的数据中心一个数据获取任务，未单独配置的使用全局间隔
+pub fn fetch_schedules(config: &Config) -> Vec<CenterSchedule> {
+    config.centers.iter()
+        .filter(|center| center.enabled)
+        .map(|center| {
+            let days = center.fetch_interval_days.unwrap_or(config.monitor.fetch_interval_days);
+            center_schedule(center, center.fetch_cron.as_deref(), format!("0 0 0 */{} * *", days), days)
+        })
+        .collect()
+}
+
+/// 每个启用的数据中心一个URL监测任务，未单独配置的使用全局间隔
+pub fn check_schedules(config: &Config) -> Vec<CenterSchedule> {
+    config.centers.iter()
+        .filter(|center| center.enabled)
+        .map(|center| {
+            let days = center.check_interval_days.unwrap_or(config.monitor.check_interval_days);
+            center_schedule(center, center.check_cron.as_deref(), format!("0 0 0 5/{} * *", days), days)
+        })
+        .collect()
+}
+
+/// 配置了 cron 时按 cron 调度，补跑判断用的间隔取 cron 接下来两次触发的间隔；否则每 `days` 天调度一次
+fn center_schedule(center: &Center, cron: Option<&str>, default_cron: String, days: u32) -> CenterSchedule {
+    let days = Duration::days(days as i64);
+    let (cron, interval) = match cron {
+        Some(cron) => (cron.to_string(), cron_interval(cron, Utc::now()).unwrap_or(days)),
+        None => (default_cron, days),
+    };
+    CenterSchedule { center: center.clone(), cron, interval }
+}
+
+/// cron 表达式（带秒）在 `after` 之后相邻两次触发的间隔，无法解析时返回 None
+pub fn cron_interval(cron: &str, after: DateTime<Utc>) -> Option<Duration> {
+    let cron = Cron::new(cron).with_seconds_required().with_dom_and_dow().parse().ok()?;
+    let mut fires = cron.iter_after(after);
+    let first = fires.next()?;
+    Some(fires.next()? - first)
+}
+
+/// 当前实例标识：主机名 + 进程号
+pub fn instance_id() -> String {
+    let host = hostname::get()
+        .map(|h| h.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "unknown".to_string());
+    format!("{}-{}", host, std::process::id())
+}
+
+/// 在 MongoDB 租约锁保护下运行任务，未获取到锁（其他实例正在运行）时返回 Ok(None)
+///
+/// 运行期间后台定期续期，持有者崩溃后租约过期即可被其他实例抢占。
+/// 续期发现锁已被抢占时停止任务并返回错误，避免两个实例同时运行
+pub async fn run_with_lease<Fut, T>(db: &Arc<MongoDB>, name: &str, ttl: std::time::Duration, job: Fut) -> Result<Option<T>>
+where
+    Fut: Future<Output = Result<T>>,
+{
+    let owner = instance_id();
+    if !db.try_acquire_lock(name, &owner, ttl).await? {
+        info!("任务 {} 的运行锁由其他实例持有，跳过本次运行", name);
+        return Ok(None);
+    }
+    info!("实例 {} 获取任务 {} 的运行锁", owner, name);
+
+    let lost = CancellationToken::new();
+    // 任务 panic 时续期任务随之结束，租约过期后其他实例可以接手
+    let renew_task = AbortOnDropHandle::new({
+        let db = db.clone();
+        let name = name.to_string();
+        let owner = owner.clone();
+        let lost = lost.clone();
+        tokio::spawn(async move {
+            // 配置校验保证 ttl >= 3 秒，这里仍兜底避免 interval(0) panic
+            let mut interval = tokio::time::interval((ttl / 3).max(std::time::Duration::from_secs(1)));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                match db.renew_lock(&name, &owner, ttl).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        error!("任务 {} 的运行锁已被其他实例抢占，停止本次运行", name);
+                        lost.cancel();
+                        break;
+                    }
+                    Err(e) => warn!("任务 {} 运行锁续期失败: {}", name, e),
+                }
+            }
+        })
+    });
+
+    let result = tokio::select! {
+        result = job => result,
+        _ = lost.cancelled() => Err(anyhow::anyhow!("任务 {} 的运行锁已被其他实例抢占", name)),
+    };
+    drop(renew_task);
+    if let Err(e) = db.release_lock(name, &owner).await {
+        warn!("释放任务 {} 的运行锁失败: {}", name, e);
+    }
+    result.map(Some)
+}
+
+/// 在 [0, max_secs] 内选取随机延迟，且不超过 `cap_secs`（距下一次触发的时间）
+pub fn pick_jitter_secs(max_secs: u64, cap_secs: Option<u64>, seed: u64) -> u64 {
+    let max_secs = cap_secs.map_or(max_secs, |cap| max_secs.min(cap));
+    if max_secs == 0 {
+        return 0;
+    }
+    // splitmix64
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    max_secs.checked_add(1).map_or(z, |n| z % n)
+}
+
+/// 定时触发后随机延迟启动，避免共用同一配置的多个实例同时请求各数据中心
+///
+/// 返回实际延迟的秒数。
+pub async fn sleep_jitter(name: &str, max_minutes: u32, next_fire: Option<DateTime<Utc>>) -> u64 {
+    if max_minutes == 0 {
+        return 0;
+    }
+    let cap_secs = next_fire.map(|next| (next - Utc::now()).num_seconds().max(0) as u64);
+    let seed = RandomState::new().hash_one(std::process::id());
+    let delay = pick_jitter_secs(max_minutes as u64 * 60, cap_secs, seed);
+    info!("任务 {} 随机延迟 {} 秒后开始运行", name, delay);
+    tokio::time::sleep(std::time::Duration::from_secs(delay)).await;
+    delay
+}
+
+#[derive(Debug, Default)]
+struct SupervisorState {
+    consecutive_failures: u32,
+    /// 退避结束前的触发都会被跳过
+    backoff_until: Option<DateTime<Utc>>,
+}
+
+/// 任务监督：统计连续失败次数，失败后指数退避（跳过中间的触发），连续失败达到阈值时告警
+pub struct Supervisor {
+    name: String,
+    base_backoff: Duration,
+    max_backoff: Duration,
+    alert_threshold: u32,
+    state: Mutex<SupervisorState>,
+}
+
+impl Supervisor {
+    pub fn new(name: &str, base_backoff: Duration, max_backoff: Duration, alert_threshold: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            base_backoff,
+            max_backoff,
+            alert_threshold,
+            state: Mutex::new(SupervisorState::default()),
+        }
+    }
+
+    pub fn from_config(name: &str, config: &MonitorConfig) -> Self {
+        Self::new(
+            name,
+            Duration::minutes(config.failure_backoff_minutes as i64),
+            Duration::hours(config.max_failure_backoff_hours as i64),
+            config.failure_alert_threshold,
+        )
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.state.lock().unwrap().consecutive_failures
+    }
+
+    /// 当前是否允许运行（不在退避期内）
+    pub fn should_run(&self, now: DateTime<Utc>) -> bool {
+        let state = self.state.lock().unwrap();
+        match state.backoff_until {
+            Some(until) if now < until => {
+                warn!("任务 {} 已连续失败 {} 次，退避至 {}，跳过本次触发",
+                      self.name, state.consecutive_failures, until);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// 记录一次运行结果，返回 true 表示连续失败次数达到告警阈值
+    pub fn record(&self, success: bool, now: DateTime<Utc>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if success {
+            if state.consecutive_failures > 0 {
+                info!("任务 {} 在连续失败 {} 次后恢复", self.name, state.consecutive_failures);
+            }
+            *state = SupervisorState::default();
+            return false;
+        }
+
+        state.consecutive_failures += 1;
+        let backoff = self.backoff(state.consecutive_failures);
+        state.backoff_until = Some(now + backoff);
+        warn!("任务 {} 连续失败 {} 次，{} 分钟内不再运行",
+              self.name, state.consecutive_failures, backoff.num_minutes());
+        state.consecutive_failures == self.alert_threshold
+    }
+
+    /// 第 n 次连续失败后的退避时长：base * 2^(n-1)，溢出时取上限，不超过上限
+    fn backoff(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        i32::try_from(factor).ok()
+            .and_then(|factor| self.base_backoff.checked_mul(factor))
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+    }
+
+    /// 在监督下运行任务；处于退避期时返回 None，连续失败达到阈值时通过 `alerter` 告警
+    pub async fn run<Fut>(&self, alerter: &Alerter, job: Fut) -> Option<Result<()>>
+    where
+        Fut: Future<Output = Result<()>>,
+    {
+        if !self.should_run(Utc::now()) {
+            return None;
+        }
+        let result = job.await;
+        if self.record(result.is_ok(), Utc::now())
+            && let Err(e) = &result
+        {
+            let failures = self.consecutive_failures();
+            error!("任务 {} 已连续失败 {} 次，请检查", self.name, failures);
+            alerter.on_job_failures(&self.name, failures, &format!("{:#}", e)).await;
+        }
+        Some(result)
+    }
+}
+
+/// 退出时取消进行中的任务：取消后不再发起新的请求，已完成的结果照常写库
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    cancel: CancellationToken,
+    tracker: TaskTracker,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 传给 DataFetcher / DataMonitor 的取消令牌
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// 任务运行期间持有，退出时等待所有持有者结束
+    pub fn running(&self) -> TaskTrackerToken {
+        self.tracker.token()
+    }
+
+    /// 取消所有任务，并最多等待 `grace` 让它们写完已完成的结果
+    pub async fn cancel_and_wait(&self, grace: std::time::Duration) {
+        self.cancel.cancel();
+        self.tracker.close();
+        if !self.tracker.is_empty() {
+            info!("等待 {} 个进行中的任务结束", self.tracker.len());
+        }
+        if tokio::time::timeout(grace, self.tracker.wait()).await.is_err() {
+            warn!("等待进行中的任务结束超时（{} 秒），直接退出", grace.as_secs());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Notify;
+    use tokio::task::JoinHandle;
+
+    /// 启动一次首轮阻塞的运行，等到其真正开始后返回；之后的轮次立即完成
+    async fn spawn_slow_run(guard: &Arc<JobGuard>) -> (JoinHandle<TriggerOutcome>, Arc<AtomicU64>, Arc<Notify>) {
+        let runs = Arc::new(AtomicU64::new(0));
+        let started = Arc::new(Notify::new());
+        let release = Arc::new(Notify::new());
+        let handle = {
+            let guard = guard.clone();
+            let (runs, started, release) = (runs.clone(), started.clone(), release.clone());
+            tokio::spawn(async move {
+                guard.run(|| {
+                    let (runs, started, release) = (runs.clone(), started.clone(), release.clone());
+                    async move {
+                        if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                            started.notify_one();
+                            release.notified().await;
+                        }
+                    }
+                }).await
+            })
+        };
+        started.notified().await;
+        (handle, runs, release)
+    }
+
+    #[tokio::test]
+    async fn overlapping_trigger_is_skipped() {
+        let guard = Arc::new(JobGuard::new("fetch", false));
+        let (first, runs, release) = spawn_slow_run(&guard).await;
+        assert!(guard.is_running());
+
+        assert_eq!(guard.run(|| async { panic!("重叠的触发不应执行") }).await, TriggerOutcome::Skipped);
+        assert_eq!(guard.run(|| async { panic!("重叠的触发不应执行") }).await, TriggerOutcome::Skipped);
+        assert_eq!(guard.skipped(), 2);
+
+        release.notify_one();
+        assert_eq!(first.await.unwrap(), TriggerOutcome::Ran);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(!guard.is_running());
+
+        // 上一次结束后的触发正常执行
+        assert_eq!(guard.run(|| async {}).await, TriggerOutcome::Ran);
+        assert_eq!(guard.skipped(), 2);
+    }
+
+    #[tokio::test]
+    async fn overlapping_trigger_is_queued_once() {
+        let guard = Arc::new(JobGuard::new("monitor", true));
+        let (first, runs, release) = spawn_slow_run(&guard).await;
+
+        assert_eq!(guard.run(|| async {}).await, TriggerOutcome::Queued);
+        // 只排队一次，之后的触发仍然跳过
+        assert_eq!(guard.run(|| async {}).await, TriggerOutcome::Skipped);
+        assert_eq!(guard.skipped(), 1);
+
+        release.notify_one();
+        assert_eq!(first.await.unwrap(), TriggerOutcome::Ran);
+        // 排队的触发在首轮结束后由同一次 run 执行
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        assert!(!guard.is_running());
+    }
+
+    #[tokio::test]
+    async fn panicking_run_does_not_block_later_triggers() {
+        let guard = Arc::new(JobGuard::new("monitor", true));
+        let panicked = {
+            let guard = guard.clone();
+            tokio::spawn(async move { guard.run(|| async { panic!("任务失败") }).await }).await
+        };
+        assert!(panicked.unwrap_err().is_panic());
+        assert!(!guard.is_running());
+
+        let runs = AtomicU64::new(0);
+        let outcome = guard.run(|| async { runs.fetch_add(1, Ordering::SeqCst); }).await;
+        assert_eq!(outcome, TriggerOutcome::Ran);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(guard.skipped(), 0);
+    }
+
+    /// 每个测试独立的状态目录，避免并行测试互相覆盖
+    fn temp_state_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-{}-{}", tag, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn overdue_after_interval_and_grace() {
+        let now = Utc::now();
+        let (interval, grace) = (Duration::days(7), Duration::hours(1));
+        assert!(!is_overdue(Some(now - Duration::days(7)), now, interval, grace));
+        assert!(!is_overdue(Some(now - interval - grace), now, interval, grace));
+        assert!(is_overdue(Some(now - interval - grace - Duration::seconds(1)), now, interval, grace));
+        assert!(is_overdue(None, now, interval, grace));
+    }
+
+    #[test]
+    fn stale_state_file_is_overdue() {
+        let dir = temp_state_dir("stale");
+        let state = JobState::new(dir.to_str().unwrap(), "fetch");
+        let (interval, grace) = (Duration::days(1), Duration::minutes(30));
+
+        let fresh = Utc::now() - Duration::hours(2);
+        state.record_success(fresh).unwrap();
+        assert_eq!(state.last_success(), Some(fresh));
+        assert!(!state.is_overdue(interval, grace));
+
+        state.record_success(Utc::now() - Duration::days(3)).unwrap();
+        assert!(state.is_overdue(interval, grace));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_state_file_is_overdue() {
+        let dir = temp_state_dir("missing");
+        let state = JobState::new(dir.to_str().unwrap(), "fetch");
+        assert_eq!(state.last_success(), None);
+        assert!(state.is_overdue(Duration::days(1), Duration::zero()));
+    }
+
+    #[test]
+    fn corrupt_state_file_is_overdue() {
+        let dir = temp_state_dir("corrupt");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("fetch.json"), "{\"last_success\": ").unwrap();
+        let state = JobState::new(dir.to_str().unwrap(), "fetch");
+        assert_eq!(state.last_success(), None);
+        assert!(state.is_overdue(Duration::days(1), Duration::zero()));
+
+        // 损坏的文件在下一次成功运行后被覆盖
+        let at = Utc::now();
+        state.record_success(at).unwrap();
+        assert_eq!(state.last_success(), Some(at));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let mut seen = [false; 11];
+        for seed in 0..1000 {
+            let delay = pick_jitter_secs(10, None, seed);
+            assert!(delay <= 10);
+            seen[delay as usize] = true;
+            // 同一种子结果固定
+            assert_eq!(pick_jitter_secs(10, None, seed), delay);
+            // 不超过距下一次触发的时间
+            assert!(pick_jitter_secs(600, Some(30), seed) <= 30);
+            assert!(pick_jitter_secs(20, Some(600), seed) <= 20);
+        }
+        assert!(seen.iter().all(|s| *s), "1000 个种子应覆盖 [0, 10] 的每个值");
+        assert_eq!(pick_jitter_secs(0, None, 42), 0);
+        assert_eq!(pick_jitter_secs(600, Some(0), 42), 0);
+        // 上限为 u64::MAX 时不溢出
+        assert_eq!(pick_jitter_secs(u64::MAX, None, 42), pick_jitter_secs(u64::MAX, None, 42));
+    }
+
+    #[test]
+    fn jitter_is_recorded_with_success() {
+        let dir = temp_state_dir("jitter");
+        let state = JobState::new(dir.to_str().unwrap(), "monitor");
+        assert_eq!(state.last_jitter_secs(), None);
+
+        state.set_jitter(42);
+        state.record_success(Utc::now()).unwrap();
+        assert_eq!(state.last_jitter_secs(), Some(42));
+
+        // 没有随机延迟的运行（手动触发、补跑）记为 0
+        state.record_success(Utc::now()).unwrap();
+        assert_eq!(state.last_jitter_secs(), Some(0));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn backoff_doubles_and_saturates() {
+        let supervisor = Supervisor::new("fetch", Duration::minutes(10), Duration::hours(24), 3);
+        assert_eq!(supervisor.backoff(1), Duration::minutes(10));
+        assert_eq!(supervisor.backoff(2), Duration::minutes(20));
+        assert_eq!(supervisor.backoff(4), Duration::minutes(80));
+        assert_eq!(supervisor.backoff(8), Duration::minutes(1280));
+        assert_eq!(supervisor.backoff(9), Duration::hours(24));
+        // 2^31 超出 i32、2^32 超出 u32 时都取上限，不会得到负数或零
+        for failures in [31, 32, 33, 64, u32::MAX] {
+            assert_eq!(supervisor.backoff(failures), Duration::hours(24), "failures = {}", failures);
+        }
+    }
+
+    #[test]
+    fn scripted_failures_back_off_and_alert_once() {
+        let supervisor = Supervisor::new("monitor", Duration::minutes(10), Duration::hours(1), 3);
+        let start = Utc::now();
+        let mut now = start;
+        let mut alerts = Vec::new();
+        for (i, success) in [false, false, false, false, true, false].into_iter().enumerate() {
+            assert!(supervisor.should_run(now), "第 {} 次运行应在退避结束后执行", i + 1);
+            if supervisor.record(success, now) {
+                alerts.push(i + 1);
+            }
+            if !success {
+                // 退避期内的触发被跳过
+                assert!(!supervisor.should_run(now + Duration::minutes(9)));
+            }
+            now += Duration::hours(1);
+        }
+        // 只在第 3 次连续失败时告警，恢复后重新计数
+        assert_eq!(alerts, [3]);
+        assert_eq!(supervisor.consecutive_failures(), 1);
+        let backoff_until = supervisor.state.lock().unwrap().backoff_until;
+        assert_eq!(backoff_until, Some(start + Duration::hours(5) + Duration::minutes(10)));
+    }
+
+    #[tokio::test]
+    async fn supervised_run_reports_failures() {
+        let alerter = Alerter::new(&crate::config::AlertConfig::default());
+        let supervisor = Supervisor::new("fetch", Duration::zero(), Duration::zero(), 2);
+        let script = [Err("超时"), Err("连接失败"), Err("连接失败"), Ok(())];
+        for (i, outcome) in script.into_iter().enumerate() {
+            let result = supervisor.run(&alerter, async { outcome.map_err(anyhow::Error::msg) }).await;
+            assert_eq!(result.map(|r| r.is_ok()), Some(outcome.is_ok()));
+            let expected = if outcome.is_ok() { 0 } else { i as u32 + 1 };
+            assert_eq!(supervisor.consecutive_failures(), expected);
+        }
+    }
+
+    fn schedule_config() -> Config {
+        serde_yaml::from_str(r#"
+centers:
+  - { name: "A", secretKey: "", url: "https://a.example.org", enabled: true, check_interval_days: 3 }
+  - { name: "B", secretKey: "", url: "https://b.example.org", enabled: false }
+  - { name: "C", secretKey: "", url: "https://c.example.org", enabled: true, check_cron: "0 0 6 * * *" }
+mongodb: { uri: "mongodb://localhost:27017", database: "db" }
+duckdb: { path: "./monitor.db" }
+monitor: { fetch_interval_days: 30, check_interval_days: 7, http_timeout_secs: 30, max_concurrent: 10 }
+"#).unwrap()
+    }
+
+    #[test]
+    fn schedules_skip_disabled_centers() {
+        let config = schedule_config();
+        for schedules in [fetch_schedules(&config), check_schedules(&config)] {
+            let names: Vec<_> = schedules.iter().map(|s| s.center.name.as_str()).collect();
+            assert_eq!(names, ["A", "C"]);
+        }
+
+        let checks = check_schedules(&config);
+        assert_eq!(checks[0].cron, "0 0 0 5/3 * *");
+        assert_eq!(checks[0].interval, Duration::days(3));
+        assert_eq!(checks[1].cron, "0 0 6 * * *");
+        assert_eq!(checks[1].interval, Duration::days(1));
+    }
+
+    #[test]
+    fn cron_interval_follows_fire_times() {
+        let after = "2026-03-10T12:00:00Z".parse().unwrap();
+        assert_eq!(cron_interval("0 0 6 * * *", after), Some(Duration::days(1)));
+        assert_eq!(cron_interval("0 */15 * * * *", after), Some(Duration::minutes(15)));
+        assert_eq!(cron_interval("0 0 3 * * MON", after), Some(Duration::weeks(1)));
+        assert_eq!(cron_interval("0 0 0 */10 * *", after), Some(Duration::days(10)));
+        assert_eq!(cron_interval("不是 cron", after), None);
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_running_tasks_within_grace() {
+        let shutdown = Shutdown::new();
+        let running = shutdown.running();
+        let cancel = shutdown.cancel_token();
+        let task = tokio::spawn(async move {
+            cancel.cancelled().await;
+            // 取消后写完已完成的结果再结束
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            drop(running);
+        });
+        let started = std::time::Instant::now();
+        shutdown.cancel_and_wait(std::time::Duration::from_secs(5)).await;
+        assert!(shutdown.is_cancelled());
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        task.await.unwrap();
+
+        // 任务不结束时最多等待 grace
+        let shutdown = Shutdown::new();
+        let _stuck = shutdown.running();
+        let started = std::time::Instant::now();
+        shutdown.cancel_and_wait(std::time::Duration::from_millis(50)).await;
+        assert!(started.elapsed() >= std::time::Duration::from_millis(50));
+    }
+}
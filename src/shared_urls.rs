@@ -0,0 +1,133 @@
+//! 多个数据中心登记的同一URL：每个数据中心照常检查和记录，按 monitor.shared_urls 决定统计时归到哪个数据中心
+
+use crate::config::{SharedUrlAttribution, SharedUrlConfig};
+use crate::models::MonitorRecord;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+/// 由指定的归属（monitor.shared_urls.primary）决定
+pub const RULE_MAPPED: &str = "mapped";
+/// 由最早检查的数据中心决定
+pub const RULE_FIRST_SEEN: &str = "first_seen";
+
+/// 登记了某个URL的数据中心及其最早检查时间，本次运行中首次出现的为 None
+type UrlCenters<'a> = Vec<(&'a str, Option<DateTime<Utc>>)>;
+
+/// 设置共用URL的记录的 attributed_center 和 attribution_rule。
+/// `history` 为各URL在每个数据中心最早的检查时间（见 `DuckDB::get_url_centers`），
+/// 本次运行中首次出现的数据中心排在之后，彼此按 `center_order`（配置顺序）排列
+pub fn attribute(records: &mut [MonitorRecord], history: &HashMap<String, Vec<(String, DateTime<Utc>)>>, center_order: &[String], config: &SharedUrlConfig) {
+    let order = |center: &str| center_order.iter().position(|c| c == center).unwrap_or(center_order.len());
+    let mut centers: HashMap<&str, UrlCenters> = HashMap::new();
+    for record in records.iter() {
+        let url_centers = centers.entry(&record.url).or_insert_with(|| {
+            history.get(&record.url)
+                .map(|seen| seen.iter().map(|(center, first)| (center.as_str(), Some(*first))).collect())
+                .unwrap_or_default()
+        });
+        if !url_centers.iter().any(|(center, _)| *center == record.center_name) {
+            url_centers.push((&record.center_name, None));
+        }
+    }
+    let mut attribution: HashMap<String, (String, &'static str)> = HashMap::new();
+    for (url, mut url_centers) in centers {
+        if url_centers.len() < 2 {
+            continue;
+        }
+        // 有检查记录的在前（按最早检查时间），本次首次出现的按配置顺序
+        url_centers.sort_by_key(|(center, first)| (first.is_none(), *first, order(center)));
+        let mapped = config.primary.get(url)
+            .filter(|primary| url_centers.iter().any(|(center, _)| center == primary));
+        let primary = match (mapped, config.attribution) {
+            (Some(primary), _) => (primary.clone(), RULE_MAPPED),
+            (None, SharedUrlAttribution::FirstSeen) => (url_centers[0].0.to_string(), RULE_FIRST_SEEN),
+            (None, SharedUrlAttribution::All) => continue,
+        };
+        attribution.insert(url.to_string(), primary);
+    }
+    for record in records.iter_mut() {
+        if let Some((center, rule)) = attribution.get(&record.url) {
+            record.attributed_center = Some(center.clone());
+            record.attribution_rule = Some(rule.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(center: &str, url: &str) -> MonitorRecord {
+        MonitorRecord { url: url.to_string(), ..crate::test_support::record(url, center, None, None) }
+    }
+
+    fn config(yaml: &str) -> SharedUrlConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn attributed(records: &[MonitorRecord]) -> Vec<(Option<&str>, Option<&str>)> {
+        records.iter().map(|r| (r.attributed_center.as_deref(), r.attribution_rule.as_deref())).collect()
+    }
+
+    type History = HashMap<String, Vec<(String, DateTime<Utc>)>>;
+
+    fn fixture() -> (Vec<MonitorRecord>, History, Vec<String>) {
+        let records = vec![
+            record("B", "https://shared.example.org/1"),
+            record("A", "https://shared.example.org/1"),
+            record("C", "https://new.example.org/2"),
+            record("B", "https://new.example.org/2"),
+            record("A", "https://only-a.example.org/3"),
+        ];
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        // B 最早检查了 /1，C 之前检查过 /3 但本次没有登记
+        let history = HashMap::from([
+            ("https://shared.example.org/1".to_string(), vec![
+                ("A".to_string(), at("2026-02-01T00:00:00Z")),
+                ("B".to_string(), at("2026-01-01T00:00:00Z")),
+            ]),
+            ("https://only-a.example.org/3".to_string(), vec![("C".to_string(), at("2026-01-01T00:00:00Z"))]),
+        ]);
+        (records, history, vec!["A".to_string(), "B".to_string(), "C".to_string()])
+    }
+
+    #[test]
+    fn all_leaves_records_unattributed() {
+        let (mut records, history, order) = fixture();
+        attribute(&mut records, &history, &order, &config("{}"));
+        assert!(attributed(&records).iter().all(|a| *a == (None, None)));
+    }
+
+    #[test]
+    fn first_seen_prefers_history_then_config_order() {
+        let (mut records, history, order) = fixture();
+        attribute(&mut records, &history, &order, &config("attribution: first_seen"));
+        assert_eq!(attributed(&records), [
+            (Some("B"), Some(RULE_FIRST_SEEN)),
+            (Some("B"), Some(RULE_FIRST_SEEN)),
+            (Some("B"), Some(RULE_FIRST_SEEN)),
+            (Some("B"), Some(RULE_FIRST_SEEN)),
+            // 历史中的其他数据中心也算共用
+            (Some("C"), Some(RULE_FIRST_SEEN)),
+        ]);
+    }
+
+    #[test]
+    fn mapped_primary_wins_when_the_center_lists_the_url() {
+        let (mut records, history, order) = fixture();
+        let config = config(r#"
+attribution: first_seen
+primary:
+  "https://shared.example.org/1": A
+  "https://new.example.org/2": D
+"#);
+        attribute(&mut records, &history, &order, &config);
+        assert_eq!(attributed(&records)[..4], [
+            (Some("A"), Some(RULE_MAPPED)),
+            (Some("A"), Some(RULE_MAPPED)),
+            // D 没有登记这个URL，不生效
+            (Some("B"), Some(RULE_FIRST_SEEN)),
+            (Some("B"), Some(RULE_FIRST_SEEN)),
+        ]);
+    }
+}
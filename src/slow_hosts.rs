@@ -0,0 +1,123 @@
+use crate::circuit_breaker::host_of;
+use crate::config::MonitorConfig;
+use crate::db::duckdb::DuckDB;
+use crate::models::LearnedSlowHost;
+use anyhow::Result;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::info;
+
+/// 单次监测运行内各主机的检查超时：配置的 slow_hosts 优先，其次是自动识别的慢主机，其他主机用 http_timeout_secs
+pub struct HostTimeouts {
+    default: Duration,
+    overrides: HashMap<String, Duration>,
+}
+
+impl HostTimeouts {
+    pub fn new(config: &MonitorConfig, learned: &[LearnedSlowHost]) -> Self {
+        let mut overrides: HashMap<String, Duration> = learned.iter()
+            .map(|host| (host.host.clone(), Duration::from_secs(host.timeout_secs)))
+            .collect();
+        for slow_host in &config.slow_hosts {
+            overrides.insert(slow_host.host.to_lowercase(), Duration::from_secs(slow_host.timeout_secs));
+        }
+        Self { default: Duration::from_secs(config.http_timeout_secs), overrides }
+    }
+
+    /// 检查 `url` 使用的超时
+    pub fn timeout_for(&self, url: &str) -> Duration {
+        host_of(url)
+            .and_then(|host| self.overrides.get(&host).copied())
+            .unwrap_or(self.default)
+    }
+}
+
+/// 配置了 slow_host_learning 时重新识别慢主机，返回识别的主机；未配置时返回 None
+pub async fn learn(duckdb: &DuckDB, config: &MonitorConfig) -> Result<Option<Vec<LearnedSlowHost>>> {
+    let Some(learning) = &config.slow_host_learning else {
+        return Ok(None);
+    };
+    let since = Utc::now() - chrono::Duration::days(learning.lookback_days as i64);
+    let threshold_ms = (config.http_timeout_secs as f64 * 1000.0 * learning.slow_ratio) as u64;
+    let learned = duckdb.learn_slow_hosts(since, threshold_ms, learning).await?;
+    if !learned.is_empty() {
+        let hosts: Vec<&str> = learned.iter().map(|host| host.host.as_str()).collect();
+        info!("以下主机的成功响应经常超过 {} ms，之后的运行使用 {} 秒超时: {:?}", threshold_ms, learning.timeout_secs, hosts);
+    }
+    Ok(Some(learned))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MonitorRecord;
+
+    fn config(extra: &str) -> MonitorConfig {
+        serde_yaml::from_str(&format!(
+            "{{ fetch_interval_days: 30, check_interval_days: 7, http_timeout_secs: 10, max_concurrent: 10, {} }}",
+            extra
+        )).unwrap()
+    }
+
+    fn learned(host: &str, timeout_secs: u64) -> LearnedSlowHost {
+        LearnedSlowHost { host: host.to_string(), timeout_secs, learned_at: Utc::now(), total_checks: 10, slow_checks: 10 }
+    }
+
+    #[test]
+    fn configured_hosts_override_learned_ones() {
+        let config = config("slow_hosts: [{ host: Data.Example.ORG, timeout_secs: 60 }]");
+        let timeouts = HostTimeouts::new(&config, &[learned("data.example.org", 30), learned("slow.example.org", 30)]);
+        assert_eq!(timeouts.timeout_for("https://DATA.example.org/a"), Duration::from_secs(60));
+        assert_eq!(timeouts.timeout_for("https://slow.example.org:8443/a"), Duration::from_secs(30));
+        assert_eq!(timeouts.timeout_for("https://other.example.org/a"), Duration::from_secs(10));
+        assert_eq!(timeouts.timeout_for("not a url"), Duration::from_secs(10));
+    }
+
+    /// `host` 上 `checks` 次检查，其中前 `slow` 次响应时间超过阈值
+    fn checks(host: &str, checks: usize, slow: usize, status_code: u16, days_ago: i64) -> Vec<MonitorRecord> {
+        (0..checks)
+            .map(|i| MonitorRecord {
+                id: format!("{}-{}", host, i),
+                raw_id: Some(i.to_string()),
+                url: format!("https://{}/{}", host, i),
+                center_name: "A".to_string(),
+                check_time: Utc::now() - chrono::Duration::days(days_ago),
+                status_code: Some(status_code),
+                response_time_ms: Some(if i < slow { 9000 } else { 100 }),
+                ..MonitorRecord::default()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn learning_picks_hosts_with_mostly_slow_successes() {
+        let db = DuckDB::new(":memory:").await.unwrap();
+        assert!(learn(&db, &config("")).await.unwrap().is_none());
+
+        let records = [
+            checks("slower.example.org", 3, 3, 200, 1),
+            checks("slow.example.org", 4, 3, 200, 1),
+            checks("half.example.org", 4, 2, 200, 1),
+            checks("fast.example.org", 5, 2, 200, 1),
+            // 检查次数不足、失败的检查、超出统计范围的检查不计入
+            checks("few.example.org", 2, 2, 200, 1),
+            checks("failing.example.org", 5, 5, 503, 1),
+            checks("old.example.org", 5, 5, 200, 30),
+        ].concat();
+        db.insert_records(&records).await.unwrap();
+
+        let learning = "slow_host_learning: { timeout_secs: 45, min_checks: 3, max_hosts: 4 }";
+        let hosts = learn(&db, &config(learning)).await.unwrap().unwrap();
+        let found: Vec<(&str, i64, i64)> = hosts.iter().map(|h| (h.host.as_str(), h.total_checks, h.slow_checks)).collect();
+        assert_eq!(found, [("slower.example.org", 3, 3), ("slow.example.org", 4, 3), ("half.example.org", 4, 2)]);
+        assert!(hosts.iter().all(|h| h.timeout_secs == 45));
+
+        // 重新识别时替换之前的结果，按慢响应比例取前 max_hosts 个
+        let learning = "slow_host_learning: { timeout_secs: 45, min_checks: 3, max_hosts: 2 }";
+        learn(&db, &config(learning)).await.unwrap();
+        let stored: Vec<String> = db.get_learned_slow_hosts().await.unwrap().into_iter().map(|h| h.host).collect();
+        assert_eq!(stored.len(), 2);
+        assert!(stored.contains(&"slower.example.org".to_string()) && stored.contains(&"slow.example.org".to_string()));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::db::duckdb::DuckDB;
+use crate::models::MonitorRecord;
+use anyhow::{Context, Result};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::{error, info, warn};
+
+/// 单次监测运行的检查结果写库，失败时重试一次，仍失败则追加到溢出文件 `{dir}/{run_id}.ndjson`（每行一条记录）
+/// 继续处理后续批次，不让一次写库失败丢掉整个运行的结果。
+/// 写入后把记录 id 记到运行进度中，运行中断后续跑时跳过这些记录
+pub struct StatusWriter<'a> {
+    duckdb: &'a DuckDB,
+    run_id: String,
+    path: PathBuf,
+    spilled: AtomicUsize,
+}
+
+impl<'a> StatusWriter<'a> {
+    pub fn new(duckdb: &'a DuckDB, dir: &str, run_id: &str) -> Self {
+        Self {
+            duckdb,
+            run_id: run_id.to_string(),
+            path: Path::new(dir).join(format!("{}.ndjson", run_id)),
+            spilled: AtomicUsize::new(0),
+        }
+    }
+
+    /// 写入一批检查结果，只有写溢出文件也失败时才返回错误
+    pub async fn write(&self, records: &[MonitorRecord]) -> Result<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        self.write_or_spill(records).await?;
+        // 进度只影响续跑，记录失败时续跑会重新检查这些记录
+        let ids: Vec<&str> = records.iter().map(|r| r.id.as_str()).collect();
+        if let Err(e) = self.duckdb.record_progress(&self.run_id, &ids).await {
+            warn!("记录运行 {} 的进度失败: {:#}", self.run_id, e);
+        }
+        Ok(())
+    }
+
+    async fn write_or_spill(&self, records: &[MonitorRecord]) -> Result<()> {
+        let Err(e) = self.duckdb.update_status(records).await else {
+            return Ok(());
+        };
+        warn!("写入 {} 条检查结果失败，重试一次: {:#}", records.len(), e);
+        let Err(e) = self.duckdb.update_status(records).await else {
+            return Ok(());
+        };
+        error!("重试写入 {} 条检查结果仍失败，写入溢出文件 {}: {:#}", records.len(), self.path.display(), e);
+        append(&self.path, records)?;
+        self.spilled.fetch_add(records.len(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// 写入溢出文件的记录数
+    pub fn spilled(&self) -> usize {
+        self.spilled.load(Ordering::Relaxed)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn append(path: &Path, records: &[MonitorRecord]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("创建溢出目录失败: {}", parent.display()))?;
+    }
+    let file = std::fs::OpenOptions::new().create(true).append(true).open(path)
+        .with_context(|| format!("打开溢出文件失败: {}", path.display()))?;
+    let mut writer = std::io::BufWriter::new(file);
+    for record in records {
+        serde_json::to_writer(&mut writer, record)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush().with_context(|| format!("写入溢出文件失败: {}", path.display()))?;
+    Ok(())
+}
+
+/// 读取溢出文件中的记录，无法解析的行（如写到一半的最后一行）跳过
+pub fn read(path: &Path) -> Result<Vec<MonitorRecord>> {
+    let file = std::fs::File::open(path).with_context(|| format!("打开溢出文件失败: {}", path.display()))?;
+    let mut records = Vec::new();
+    for (index, line) in std::io::BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(e) => warn!("溢出文件 {} 第 {} 行无法解析，已跳过: {}", path.display(), index + 1, e),
+        }
+    }
+    Ok(records)
+}
+
+/// 把溢出文件中的记录作为新行写入 dataset_monitor（source 为 spill），已有相同 URL 和检查时间的记录跳过，
+/// 可以重复执行。返回写入的条数
+pub async fn replay(duckdb: &DuckDB, path: &Path) -> Result<usize> {
+    let records = read(path)?;
+    let inserted = duckdb.import_records(&records, "spill", true).await?;
+    info!("溢出文件 {} 有 {} 条记录，写入 {} 条", path.display(), records.len(), inserted);
+    Ok(inserted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::record;
+
+    async fn execute(db: &DuckDB, sql: &str) {
+        db.conn.lock().await.execute_batch(sql).unwrap();
+    }
+
+    async fn progress(db: &DuckDB, run_id: &str) -> i64 {
+        db.conn.lock().await
+            .query_row("SELECT COUNT(*) FROM run_progress WHERE run_id = ?", [run_id], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn failed_writes_spill_and_replay_once() {
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-spill-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let db = DuckDB::new(":memory:").await.unwrap();
+        let writer = StatusWriter::new(&db, dir.to_str().unwrap(), "run-1");
+        assert_eq!(writer.path(), dir.join("run-1.ndjson"));
+
+        writer.write(&[]).await.unwrap();
+        assert!(!writer.path().exists());
+
+        // 占用写库用的临时表名，两次写库都失败，记录写入溢出文件，进度照常记录
+        execute(&db, "CREATE TEMPORARY TABLE temp_updates (id VARCHAR)").await;
+        writer.write(&[record("1", "A", Some(200), None), record("2", "A", Some(200), None)]).await.unwrap();
+        writer.write(&[record("3", "A", Some(200), None)]).await.unwrap();
+        assert_eq!(writer.spilled(), 3);
+        assert_eq!(progress(&db, "run-1").await, 3);
+
+        // 写到一半的最后一行跳过
+        let mut file = std::fs::OpenOptions::new().append(true).open(writer.path()).unwrap();
+        file.write_all(b"\n{\"id\": \"4\", \"url\"").unwrap();
+        let ids: Vec<String> = read(writer.path()).unwrap().into_iter().map(|r| r.id).collect();
+        assert_eq!(ids, ["1", "2", "3"]);
+
+        execute(&db, "DROP TABLE temp_updates").await;
+        assert_eq!(replay(&db, writer.path()).await.unwrap(), 3);
+        assert_eq!(replay(&db, writer.path()).await.unwrap(), 0, "重复回放不重复写入");
+        let history = db.get_url_history("https://a.example.org/2", 10).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].status_code, Some(200));
+
+        // 写库成功时不写溢出文件
+        let writer = StatusWriter::new(&db, dir.to_str().unwrap(), "run-2");
+        writer.write(&[record("5", "A", Some(200), None)]).await.unwrap();
+        assert_eq!(writer.spilled(), 0);
+        assert!(!writer.path().exists());
+        assert_eq!(progress(&db, "run-2").await, 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
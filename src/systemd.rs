@@ -0,0 +1,186 @@
+//! systemd `Type=notify` 集成：未设置 NOTIFY_SOCKET 时所有调用都是空操作
+
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// 向 systemd 发送状态消息，返回是否发送成功
+#[cfg(unix)]
+pub fn notify(state: &str) -> bool {
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return false;
+    };
+    let path = path.to_string_lossy().into_owned();
+    let result = UnixDatagram::unbound().and_then(|socket| {
+        // 以 @ 开头的是 Linux 抽象命名空间 socket
+        #[cfg(target_os = "linux")]
+        if let Some(name) = path.strip_prefix('@') {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            return socket.send_to_addr(state.as_bytes(), &addr);
+        }
+        socket.send_to(state.as_bytes(), &path)
+    });
+    match result {
+        Ok(_) => {
+            debug!("sd_notify: {}", state);
+            true
+        }
+        Err(e) => {
+            warn!("sd_notify 发送 {} 失败: {}", state, e);
+            false
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn notify(_state: &str) -> bool {
+    false
+}
+
+/// 配置已加载、数据库可用
+pub fn ready() {
+    if notify("READY=1") {
+        info!("已通知 systemd 服务就绪");
+    }
+}
+
+pub fn watchdog() {
+    notify("WATCHDOG=1");
+}
+
+pub fn stopping() {
+    notify("STOPPING=1");
+}
+
+/// systemd 要求的看门狗间隔（WATCHDOG_USEC 的一半），未启用看门狗时返回 None
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Some(pid) = std::env::var("WATCHDOG_PID").ok().and_then(|p| p.parse::<u32>().ok())
+        && pid != std::process::id()
+    {
+        return None;
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    if usec == 0 {
+        return None;
+    }
+    Some(Duration::from_micros(usec / 2))
+}
+
+/// 启动看门狗定时器，长时间运行的任务期间也持续喂狗
+pub fn spawn_watchdog() -> Option<tokio::task::JoinHandle<()>> {
+    let interval = watchdog_interval()?;
+    info!("启用 systemd 看门狗，间隔 {:?}", interval);
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            watchdog();
+        }
+    }))
+}
+
+/// 等待 Ctrl-C 或 SIGTERM
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            Err(e) => {
+                warn!("无法监听 SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixDatagram;
+
+    fn recv(socket: &UnixDatagram) -> String {
+        let mut buf = [0u8; 64];
+        let len = socket.recv(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..len]).into_owned()
+    }
+
+    /// 环境变量是进程级的，所有修改 NOTIFY_SOCKET / WATCHDOG_* 的检查放在同一个测试里
+    #[test]
+    fn notify_protocol() {
+        let dir = std::env::temp_dir().join(format!("dataset-monitor-notify-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notify.sock");
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        // SAFETY: 本模块只有这一个测试读写这些环境变量
+        unsafe {
+            std::env::remove_var("NOTIFY_SOCKET");
+        }
+        assert!(!notify("READY=1"));
+
+        unsafe {
+            std::env::set_var("NOTIFY_SOCKET", &path);
+        }
+        ready();
+        watchdog();
+        stopping();
+        assert_eq!(recv(&socket), "READY=1");
+        assert_eq!(recv(&socket), "WATCHDOG=1");
+        assert_eq!(recv(&socket), "STOPPING=1");
+
+        // 抽象命名空间 socket
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::linux::net::SocketAddrExt;
+            let name = format!("dataset-monitor-notify-{}", std::process::id());
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(&name).unwrap();
+            let abstract_socket = UnixDatagram::bind_addr(&addr).unwrap();
+            abstract_socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            unsafe {
+                std::env::set_var("NOTIFY_SOCKET", format!("@{}", name));
+            }
+            assert!(notify("WATCHDOG=1"));
+            assert_eq!(recv(&abstract_socket), "WATCHDOG=1");
+        }
+
+        // 接收端不存在时发送失败，不 panic
+        unsafe {
+            std::env::set_var("NOTIFY_SOCKET", dir.join("missing.sock"));
+        }
+        assert!(!notify("READY=1"));
+
+        unsafe {
+            std::env::remove_var("NOTIFY_SOCKET");
+            std::env::set_var("WATCHDOG_USEC", "30000000");
+            std::env::set_var("WATCHDOG_PID", std::process::id().to_string());
+        }
+        assert_eq!(watchdog_interval(), Some(Duration::from_secs(15)));
+        unsafe {
+            std::env::set_var("WATCHDOG_PID", (std::process::id() + 1).to_string());
+        }
+        assert_eq!(watchdog_interval(), None);
+        unsafe {
+            std::env::remove_var("WATCHDOG_PID");
+            std::env::set_var("WATCHDOG_USEC", "0");
+        }
+        assert_eq!(watchdog_interval(), None);
+        unsafe {
+            std::env::remove_var("WATCHDOG_USEC");
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,149 @@
+//! 测试共用的检查记录和本地 HTTP 桩服务
+
+use crate::models::MonitorRecord;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// 数据中心 `center` 在 2026-01-01T00:00:00Z 的一条检查记录，URL 为 `https://{数据中心小写}.example.org/{id}`；
+/// 其他字段用 `MonitorRecord { .., ..record(..) }` 覆盖
+pub(crate) fn record(id: &str, center: &str, status_code: Option<u16>, error_category: Option<&str>) -> MonitorRecord {
+    MonitorRecord {
+        id: id.to_string(),
+        raw_id: Some(id.to_string()),
+        url: format!("https://{}.example.org/{}", center.to_lowercase(), id),
+        center_name: center.to_string(),
+        check_time: "2026-01-01T00:00:00Z".parse().unwrap(),
+        status_code,
+        error_category: error_category.map(str::to_string),
+        ..MonitorRecord::default()
+    }
+}
+
+/// 桩服务收到的请求
+#[derive(Debug, Clone)]
+pub(crate) struct Request {
+    /// 连接序号，从 1 开始，同一连接上的请求相同
+    pub connection: usize,
+    pub method: String,
+    /// 请求行中的目标，经代理访问时为完整 URL
+    pub target: String,
+    /// 请求行和请求头
+    pub head: String,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// 请求头的值，名称不区分大小写
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.head.lines().skip(1)
+            .find_map(|line| line.split_once(':').filter(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.trim()))
+    }
+
+    /// 去掉查询参数的请求目标
+    pub fn path(&self) -> &str {
+        self.target.split_once('?').map_or(&self.target, |(path, _)| path)
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+pub(crate) type Requests = Arc<Mutex<Vec<Request>>>;
+
+/// 按请求返回完整的响应（见 [`response`]）
+pub(crate) type Respond = Arc<dyn Fn(&Request) -> String + Send + Sync>;
+
+pub(crate) struct Stub {
+    pub base: String,
+    pub requests: Requests,
+}
+
+impl Stub {
+    /// 按收到的顺序列出请求目标
+    pub fn targets(&self) -> Vec<String> {
+        self.requests.lock().unwrap().iter().map(|request| request.target.clone()).collect()
+    }
+}
+
+/// `status` 如 "200 OK"，`headers` 每行一个，自动加上 Content-Length；响应后关闭连接
+pub(crate) fn response(status: &str, headers: &[&str], body: &str) -> String {
+    format!("{}Connection: close\r\n\r\n{}", response_head(status, headers, body.len()), body)
+}
+
+/// 同 [`response`]，但保持连接
+pub(crate) fn keep_alive(status: &str, headers: &[&str], body: &str) -> String {
+    format!("{}\r\n{}", response_head(status, headers, body.len()), body)
+}
+
+fn response_head(status: &str, headers: &[&str], length: usize) -> String {
+    let mut head = format!("HTTP/1.1 {}\r\n", status);
+    for header in headers {
+        head.push_str(header);
+        head.push_str("\r\n");
+    }
+    head.push_str(&format!("Content-Length: {}\r\n", length));
+    head
+}
+
+/// 本地 HTTP 桩服务：记录收到的请求，用 `respond` 的结果应答
+pub(crate) async fn stub_server(respond: impl Fn(&Request) -> String + Send + Sync + 'static) -> Stub {
+    delayed_server(Duration::ZERO, respond).await
+}
+
+/// 同 [`stub_server`]，每个响应延迟 `delay` 后发送
+pub(crate) async fn delayed_server(delay: Duration, respond: impl Fn(&Request) -> String + Send + Sync + 'static) -> Stub {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Requests::default();
+    let log = requests.clone();
+    let respond: Respond = Arc::new(respond);
+    tokio::spawn(async move {
+        let mut connection = 0;
+        while let Ok((socket, _)) = listener.accept().await {
+            connection += 1;
+            tokio::spawn(serve(socket, connection, log.clone(), respond.clone(), delay));
+        }
+    });
+    Stub { base, requests }
+}
+
+/// 依次处理同一连接上的请求，读完 Content-Length 指定的请求体后应答；
+/// 响应带 `Connection: close` 或连接出错时结束。也用于 TLS 等包装过的连接
+pub(crate) async fn serve<S: AsyncRead + AsyncWrite + Unpin>(mut socket: S, connection: usize, log: Requests, respond: Respond, delay: Duration) {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let head_end = loop {
+            if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                break end;
+            }
+            match socket.read(&mut chunk).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            }
+        };
+        let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+        let mut request = Request { connection, method: String::new(), target: String::new(), head, body: Vec::new() };
+        let length: usize = request.header("content-length").map_or(0, |value| value.parse().unwrap());
+        while buf.len() < head_end + 4 + length {
+            match socket.read(&mut chunk).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+        request.body = buf[head_end + 4..head_end + 4 + length].to_vec();
+        buf.drain(..head_end + 4 + length);
+        let mut parts = request.head.split_whitespace();
+        request.method = parts.next().unwrap_or_default().to_string();
+        request.target = parts.next().unwrap_or_default().to_string();
+
+        let response = respond(&request);
+        log.lock().unwrap().push(request);
+        tokio::time::sleep(delay).await;
+        if socket.write_all(response.as_bytes()).await.is_err() || response.contains("Connection: close") {
+            return;
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! URL检查的分阶段耗时：DNS 解析、建立连接（TCP + TLS）、首字节
+//!
+//! reqwest 不提供单个请求的阶段耗时，这里通过自定义 DNS 解析器和连接层记录，
+//! 二者在请求所在的任务中执行，结果写入 [`measure`] 设置的任务局部变量。
+//! 复用连接池中的连接时不会解析和建连，对应阶段为 None。
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// 一次请求（含重定向的各跳）各阶段的累计耗时
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Phases {
+    pub dns: Option<Duration>,
+    /// 连接器总耗时，包含其中的 DNS 解析
+    pub connect: Option<Duration>,
+}
+
+impl Phases {
+    pub fn dns_ms(&self) -> Option<u64> {
+        self.dns.map(|d| d.as_millis() as u64)
+    }
+
+    /// 建立连接耗时（TCP + TLS 握手），不含 DNS 解析
+    pub fn connect_ms(&self) -> Option<u64> {
+        self.connect
+            .map(|connect| connect.saturating_sub(self.dns.unwrap_or_default()).as_millis() as u64)
+    }
+}
+
+tokio::task_local! {
+    static PHASES: RefCell<Phases>;
+}
+
+fn record(update: impl FnOnce(&mut Phases)) {
+    // 不在 measure 中（如连接池在后台建连）时忽略
+    let _ = PHASES.try_with(|phases| update(&mut phases.borrow_mut()));
+}
+
+fn add(total: &mut Option<Duration>, elapsed: Duration) {
+    *total = Some(total.unwrap_or_default() + elapsed);
+}
+
+/// 执行请求并返回其间记录到的阶段耗时
+pub async fn measure<F: Future>(future: F) -> (F::Output, Phases) {
+    PHASES.scope(RefCell::new(Phases::default()), async move {
+        let output = future.await;
+        (output, PHASES.with(|phases| *phases.borrow()))
+    }).await
+}
+
+/// 记录耗时的 DNS 解析器，解析方式与 reqwest 默认的 getaddrinfo 相同
+pub struct TimedResolver;
+
+impl Resolve for TimedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let started = Instant::now();
+            // 传入拥有所有权的 host，返回的地址迭代器才不借用 name
+            let result = tokio::net::lookup_host(format!("{}:0", name.as_str())).await;
+            record(|phases| add(&mut phases.dns, started.elapsed()));
+            let addrs: Addrs = Box::new(result?);
+            Ok(addrs)
+        })
+    }
+}
+
+/// 记录新建连接耗时的连接层
+#[derive(Clone, Copy, Default)]
+pub struct ConnectTimingLayer;
+
+impl<S> Layer<S> for ConnectTimingLayer {
+    type Service = ConnectTiming<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConnectTiming { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct ConnectTiming<S> {
+    inner: S,
+}
+
+impl<S, R> Service<R> for ConnectTiming<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let started = Instant::now();
+        let future = self.inner.call(request);
+        Box::pin(async move {
+            let result = future.await;
+            record(|phases| add(&mut phases.connect, started.elapsed()));
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::test_support::{keep_alive, stub_server};
+
+    #[test]
+    fn connect_excludes_dns() {
+        let phases = Phases { dns: Some(Duration::from_millis(30)), connect: Some(Duration::from_millis(100)) };
+        assert_eq!((phases.dns_ms(), phases.connect_ms()), (Some(30), Some(70)));
+        let reused = Phases::default();
+        assert_eq!((reused.dns_ms(), reused.connect_ms()), (None, None));
+        let no_dns = Phases { dns: None, connect: Some(Duration::from_millis(5)) };
+        assert_eq!(no_dns.connect_ms(), Some(5));
+    }
+
+    #[tokio::test]
+    async fn measure_collects_only_its_own_phases() {
+        // 不在 measure 中记录的耗时被忽略
+        record(|phases| add(&mut phases.dns, Duration::from_millis(1)));
+        let ((), phases) = measure(async {
+            record(|phases| add(&mut phases.dns, Duration::from_millis(2)));
+            record(|phases| add(&mut phases.dns, Duration::from_millis(3)));
+            record(|phases| add(&mut phases.connect, Duration::from_millis(10)));
+        }).await;
+        assert_eq!(phases.dns, Some(Duration::from_millis(5)));
+        assert_eq!(phases.connect_ms(), Some(5));
+        let ((), empty) = measure(async {}).await;
+        assert!(empty.dns.is_none() && empty.connect.is_none());
+    }
+
+    /// 保持连接的桩服务，每个请求返回 "ok"；返回端口
+    async fn keep_alive_server() -> u16 {
+        let stub = stub_server(|_| keep_alive("200 OK", &[], "ok")).await;
+        stub.base.rsplit_once(':').unwrap().1.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn new_connections_are_timed_and_reused_ones_are_not() {
+        let port = keep_alive_server().await;
+        let client = reqwest::Client::builder()
+            .no_proxy()
+            .dns_resolver(Arc::new(TimedResolver))
+            .connector_layer(ConnectTimingLayer)
+            .build()
+            .unwrap();
+        let url = format!("http://localhost:{}/", port);
+        let get = || async { client.get(&url).send().await.unwrap().text().await.unwrap() };
+
+        let (body, first) = measure(get()).await;
+        assert_eq!(body, "ok");
+        assert!(first.dns.is_some() && first.connect.is_some(), "{:?}", first);
+        assert!(first.connect.unwrap() >= first.dns.unwrap());
+
+        let (_, reused) = measure(get()).await;
+        assert!(reused.dns.is_none() && reused.connect.is_none(), "{:?}", reused);
+    }
+}
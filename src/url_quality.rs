@@ -0,0 +1,151 @@
+//! 数据集URL的质量检查：构造监测记录时找出无法检查的URL（格式错误、不支持的协议、内网地址、占位内容），
+//! 这些URL不再发起请求，记录下来供数据中心修正
+
+use crate::models::{UrlIssue, UrlQualityIssue, UrlQualitySummary};
+use reqwest::Url;
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// 整个值就是占位内容（忽略大小写和首尾空白）
+const PLACEHOLDER_VALUES: [&str; 14] = [
+    "", "todo", "tbd", "tba", "n/a", "na", "none", "null", "nil", "unknown", "-", "#", "placeholder", "url",
+];
+
+/// RFC 2606 保留用于示例的域名
+const PLACEHOLDER_DOMAINS: [&str; 4] = ["example.com", "example.org", "example.net", "example"];
+
+/// URL无法检查的原因，`supported` 判断协议是否有对应的检查器；可以检查时返回 None
+pub fn classify(url: &str, supported: impl Fn(&str) -> bool) -> Option<UrlIssue> {
+    let trimmed = url.trim();
+    if PLACEHOLDER_VALUES.iter().any(|value| trimmed.eq_ignore_ascii_case(value)) {
+        return Some(UrlIssue::Placeholder);
+    }
+    // 解析时会去掉首尾空白、编码路径中的空格，这里按原始值判断
+    if url.contains(char::is_whitespace) {
+        return Some(UrlIssue::Unparseable);
+    }
+    let Ok(parsed) = Url::parse(url) else {
+        return Some(UrlIssue::Unparseable);
+    };
+    if !supported(parsed.scheme()) {
+        return Some(UrlIssue::UnsupportedScheme);
+    }
+    let host = parsed.host_str()?.trim_end_matches('.').to_ascii_lowercase();
+    if is_private_host(&host) {
+        Some(UrlIssue::PrivateAddress)
+    } else if is_placeholder_domain(&host) {
+        Some(UrlIssue::Placeholder)
+    } else {
+        None
+    }
+}
+
+/// `host` 为 [`Url::host_str`] 的小写形式，IPv6 地址带方括号
+fn is_private_host(host: &str) -> bool {
+    if let Some(ip) = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+        return ip.parse::<Ipv6Addr>().is_ok_and(|ip| {
+            ip.is_loopback() || ip.is_unspecified() || ip.to_ipv4_mapped().is_some_and(|ip| is_private_ipv4(&ip))
+        });
+    }
+    match host.parse::<Ipv4Addr>() {
+        Ok(ip) => is_private_ipv4(&ip),
+        Err(_) => host == "localhost" || host.ends_with(".localhost"),
+    }
+}
+
+fn is_private_ipv4(ip: &Ipv4Addr) -> bool {
+    ip.is_private() || ip.is_loopback() || ip.is_unspecified()
+}
+
+fn is_placeholder_domain(domain: &str) -> bool {
+    PLACEHOLDER_DOMAINS.iter().any(|placeholder| domain == *placeholder || domain.ends_with(&format!(".{}", placeholder)))
+}
+
+/// 按数据中心统计问题数，按问题数倒序
+pub fn summarize(issues: &[UrlQualityIssue]) -> Vec<UrlQualitySummary> {
+    let mut by_center: BTreeMap<&str, BTreeMap<UrlIssue, usize>> = BTreeMap::new();
+    for issue in issues {
+        *by_center.entry(&issue.center_name).or_default().entry(issue.reason).or_default() += 1;
+    }
+    let mut summaries: Vec<UrlQualitySummary> = by_center.into_iter()
+        .map(|(center_name, reasons)| UrlQualitySummary {
+            center_name: center_name.to_string(),
+            total: reasons.values().sum(),
+            reasons,
+        })
+        .collect();
+    summaries.sort_by_key(|summary| std::cmp::Reverse(summary.total));
+    summaries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(url: &str) -> Option<UrlIssue> {
+        classify(url, |scheme| matches!(scheme, "http" | "https" | "ftp" | "file"))
+    }
+
+    fn assert_all(urls: &[&str], expected: Option<UrlIssue>) {
+        for url in urls {
+            assert_eq!(check(url), expected, "{:?}", url);
+        }
+    }
+
+    #[test]
+    fn placeholder_values() {
+        assert_all(&["", "   ", "TODO", " n/a ", "None", "-", "#", "URL"], Some(UrlIssue::Placeholder));
+    }
+
+    #[test]
+    fn whitespace_and_unparseable() {
+        assert_all(
+            &["https://a.org/my file.zip", " https://a.org/x", "https://a.org/x\n", "https://a.org/\tx"],
+            Some(UrlIssue::Unparseable),
+        );
+        assert_all(&["a.org/data.zip", "/data/x.csv", "http://", "https://[::1", "http://a b.org"], Some(UrlIssue::Unparseable));
+    }
+
+    #[test]
+    fn unsupported_scheme() {
+        assert_all(&["s3://bucket/key", "mailto:data@a.org", "javascript:void(0)", "gopher://a.org/"], Some(UrlIssue::UnsupportedScheme));
+    }
+
+    #[test]
+    fn private_addresses() {
+        assert_all(
+            &[
+                "http://10.0.0.5/x", "http://172.16.0.1/", "https://192.168.1.20:8080/data", "http://127.0.0.1/",
+                "http://0.0.0.0/", "ftp://10.1.1.1/pub",
+            ],
+            Some(UrlIssue::PrivateAddress),
+        );
+        assert_all(&["http://[::1]/", "http://[::]/", "http://[::ffff:10.1.2.3]/", "http://[::ffff:127.0.0.1]:8080/"], Some(UrlIssue::PrivateAddress));
+        assert_all(&["http://localhost/", "http://LOCALHOST./x", "http://api.localhost:3000/"], Some(UrlIssue::PrivateAddress));
+        // RFC1918 之外的地址可以检查
+        assert_all(&["http://172.32.0.1/", "http://8.8.8.8/", "http://[2001:db8::1]/", "http://[::ffff:8.8.8.8]/", "http://localhost.a.org/"], None);
+    }
+
+    #[test]
+    fn example_domains() {
+        assert_all(
+            &["https://example.com/x", "https://data.EXAMPLE.org/x", "http://example/", "https://example.net./x"],
+            Some(UrlIssue::Placeholder),
+        );
+        assert_all(&["https://myexample.com/x", "https://example.com.cn/x", "https://example.community/"], None);
+    }
+
+    #[test]
+    fn valid_urls() {
+        assert_all(
+            &[
+                "https://data.cncb.ac.cn/x?y=1#z", "HTTP://A.ORG/Data.zip", "ftp://ftp.ncbi.nlm.nih.gov/pub/x.gz",
+                "file:///data/x.csv", "https://a.org:8443/%20x",
+            ],
+            None,
+        );
+        // 协议判断交给调用方
+        assert_eq!(classify("s3://bucket/key", |scheme| scheme == "s3"), None);
+        assert_eq!(classify("https://a.org/", |_| false), Some(UrlIssue::UnsupportedScheme));
+    }
+}